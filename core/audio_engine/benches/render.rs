@@ -0,0 +1,140 @@
+//! Render path benchmarks, run with `cargo bench -p audio_engine`.
+//!
+//! Everything is built from synthetic sources so the numbers don't depend on assets on disk.
+
+use std::{hint::black_box, io::Cursor};
+
+use audio_engine::{
+    buffer::AudioBuffer,
+    offline,
+    scheduler::{Scheduler, command::SchedulerCommand},
+    track::{Track, gainpan::GainPanTrack, sinewave::SineWaveTrack, wav::WavTrack},
+};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rtrb::RingBuffer;
+use transport::{clock::TempoClock, resolution::TickResolution};
+
+/// Counts allocations on the audio path, see [`audio_engine::rt_audit`]
+#[cfg(feature = "rt-audit")]
+#[global_allocator]
+static ALLOCATOR: audio_engine::rt_audit::AuditAllocator = audio_engine::rt_audit::AuditAllocator;
+
+const SAMPLE_RATE: u32 = 44100;
+const BLOCK_FRAMES: usize = 512;
+
+fn create_scheduler() -> Scheduler {
+    let (_, consumer) = RingBuffer::new(8);
+    Scheduler::new(
+        consumer,
+        TempoClock::new(120.0, f64::from(SAMPLE_RATE), TickResolution::Sixteenth),
+    )
+}
+
+fn sine_track(index: usize) -> Box<dyn Track> {
+    let sine = SineWaveTrack::new(110.0 * (index + 1) as f32, SAMPLE_RATE as f32);
+    Box::new(GainPanTrack::new(
+        &format!("sine-{index}"),
+        Box::new(sine),
+        0.5,
+        0.0,
+    ))
+}
+
+/// A stereo 16-bit WAV file of `frames` frames, held in memory
+fn synthetic_wav(frames: usize) -> Vec<u8> {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut cursor, spec).unwrap();
+    for frame in 0..frames {
+        let sample = ((frame % 200) as i16 - 100) * 100;
+        writer.write_sample(sample).unwrap();
+        writer.write_sample(-sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    cursor.into_inner()
+}
+
+/// One device-sized block with `N` tracks playing
+fn bench_scheduler_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scheduler_block");
+    group.throughput(Throughput::Elements(BLOCK_FRAMES as u64));
+
+    for track_count in [1, 8, 32, 128] {
+        let mut scheduler = create_scheduler();
+        for index in 0..track_count {
+            scheduler.process_command(SchedulerCommand::ScheduleTrack {
+                track: sine_track(index),
+                start_frame: 0,
+            });
+        }
+        scheduler.process_command(SchedulerCommand::Play);
+        let mut output = AudioBuffer::stereo(BLOCK_FRAMES);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(track_count),
+            &track_count,
+            |b, _| b.iter(|| scheduler.render(black_box(&mut output))),
+        );
+    }
+    group.finish();
+}
+
+/// Offline render of a short arrangement of staggered WAV tracks
+fn bench_timeline_render(c: &mut Criterion) {
+    let wav = synthetic_wav(SAMPLE_RATE as usize);
+    let mut group = c.benchmark_group("timeline_render");
+    group.sample_size(20);
+
+    group.bench_function("8_tracks_5s", |b| {
+        b.iter_batched(
+            || {
+                let mut scheduler = create_scheduler();
+                for index in 0..8u64 {
+                    let track = WavTrack::from_stream(Cursor::new(wav.clone())).unwrap();
+                    scheduler.process_command(SchedulerCommand::ScheduleTrack {
+                        track: Box::new(track),
+                        start_frame: index * u64::from(SAMPLE_RATE) / 2,
+                    });
+                }
+                scheduler
+            },
+            |mut scheduler| {
+                let max_frames = 5 * u64::from(SAMPLE_RATE);
+                black_box(offline::render_until_idle(
+                    &mut scheduler,
+                    BLOCK_FRAMES,
+                    max_frames,
+                ))
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+fn bench_wav_decode(c: &mut Criterion) {
+    let frames = SAMPLE_RATE as usize * 10;
+    let wav = synthetic_wav(frames);
+    let mut group = c.benchmark_group("wav_decode");
+    group.throughput(Throughput::Elements(frames as u64));
+    group.sample_size(20);
+
+    group.bench_function("stereo_16bit_10s", |b| {
+        b.iter(|| WavTrack::from_stream(Cursor::new(black_box(wav.clone()))).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_scheduler_block,
+    bench_timeline_render,
+    bench_wav_decode
+);
+criterion_main!(benches);
@@ -0,0 +1,223 @@
+//! Finding overs: audio whose inter-sample peaks go above full scale and will clip once
+//! converted, e.g. on export.
+
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::JoinHandle,
+};
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::math::{db_to_gain, gain_to_db},
+    events::EventBus,
+    metering::true_peak::{TRUE_PEAK_LATENCY, TruePeakDetector},
+    scheduler::event::SchedulerEvent,
+    track::timeline::TimelineTrack,
+};
+
+/// Overs closer together than this many frames are reported as one
+pub const OVER_MERGE_FRAMES: usize = 1024;
+
+/// A stretch of audio with true peaks above the ceiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Over {
+    /// First frame over the ceiling, to within the true-peak filter's few frames
+    pub start: usize,
+    /// Frame after the last one over the ceiling
+    pub end: usize,
+    /// Highest true peak in the stretch, in dBTP
+    pub peak_db: f32,
+}
+
+/// Every stretch of `buffer` whose true peaks go above `ceiling_db` dBTP, in order.
+/// Stretches less than [`OVER_MERGE_FRAMES`] apart are merged.
+pub fn find_overs(buffer: &AudioBuffer, ceiling_db: f32) -> Vec<Over> {
+    let ceiling = db_to_gain(ceiling_db);
+    let mut detectors: Vec<_> = (0..buffer.channels())
+        .map(|_| TruePeakDetector::new())
+        .collect();
+    let mut overs: Vec<Over> = Vec::new();
+    // run on past the end so peaks still inside the filter are seen
+    for index in 0..buffer.frames() + TRUE_PEAK_LATENCY {
+        let peak = detectors
+            .iter_mut()
+            .enumerate()
+            .map(|(channel, detector)| {
+                let sample = buffer.channel(channel).get(index).copied();
+                detector.process(sample.unwrap_or_default())
+            })
+            .fold(0.0f32, f32::max);
+        if peak <= ceiling {
+            continue;
+        }
+        let frame = index
+            .saturating_sub(TRUE_PEAK_LATENCY)
+            .min(buffer.frames() - 1);
+        let peak_db = gain_to_db(peak);
+        match overs.last_mut() {
+            Some(over) if frame < over.end + OVER_MERGE_FRAMES => {
+                over.end = over.end.max(frame + 1);
+                over.peak_db = over.peak_db.max(peak_db);
+            }
+            _ => overs.push(Over {
+                start: frame,
+                end: frame + 1,
+                peak_db,
+            }),
+        }
+    }
+    overs
+}
+
+/// Audio waiting for [`ClippingAnalyzer`]
+struct Job {
+    source: String,
+    audio: Arc<AudioBuffer>,
+    /// Timeline frame of the audio's first frame
+    start: u64,
+}
+
+/// Looks for overs on a background thread and reports them on the [`EventBus`] as
+/// [`SchedulerEvent::ClippingFound`], so they're caught before export.
+///
+/// # Example
+/// ```no_run
+/// # fn run(bus: &mut audio_engine::events::EventBus, mix: audio_engine::buffer::AudioBuffer) {
+/// use std::sync::Arc;
+/// use audio_engine::analysis::clipping::ClippingAnalyzer;
+///
+/// let analyzer = ClippingAnalyzer::spawn(-1.0);
+/// analyzer.analyze("mixdown", Arc::new(mix), 0);
+/// // later, e.g. from a UI timer
+/// analyzer.publish(bus);
+/// # }
+/// ```
+pub struct ClippingAnalyzer {
+    /// `None` once dropped, which ends the thread
+    jobs: Option<Sender<Job>>,
+    found: Receiver<SchedulerEvent>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ClippingAnalyzer {
+    /// Starts the analysis thread, flagging true peaks above `ceiling_db` dBTP
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn spawn(ceiling_db: f32) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (report, found) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            for job in queue {
+                for over in find_overs(&job.audio, ceiling_db) {
+                    let event = SchedulerEvent::ClippingFound {
+                        source: job.source.clone(),
+                        start: job.start + over.start as u64,
+                        end: job.start + over.end as u64,
+                        peak_db: over.peak_db,
+                    };
+                    if report.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self {
+            jobs: Some(jobs),
+            found,
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues `audio` for analysis, e.g. a render of a region. Overs are reported under
+    /// `source`, in timeline frames counted from `start`, the frame `audio` begins at.
+    pub fn analyze(&self, source: &str, audio: Arc<AudioBuffer>, start: u64) {
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(Job {
+                source: source.to_owned(),
+                audio,
+                start,
+            });
+        }
+    }
+
+    /// Queues every clip of `track` as played, with gain, fades and envelope, reported
+    /// under the track's id. Copies the clips' audio, not for the audio thread.
+    pub fn analyze_clips(&self, track: &TimelineTrack) {
+        let source = crate::track::Track::id(track);
+        for clip in track.clips() {
+            self.analyze(&source, Arc::new(clip.processed()), clip.start as u64);
+        }
+    }
+
+    /// Publishes the overs found so far on `bus`, returning how many
+    pub fn publish(&self, bus: &mut EventBus) -> usize {
+        let mut count = 0;
+        while let Ok(event) = self.found.try_recv() {
+            bus.publish(&event);
+            count += 1;
+        }
+        count
+    }
+
+    /// Waits for everything queued to be analyzed, then publishes the overs found on
+    /// `bus`, returning how many. E.g. before an export.
+    pub fn finish(mut self, bus: &mut EventBus) -> usize {
+        self.join();
+        self.publish(bus)
+    }
+
+    /// Lets the thread finish the queued jobs and waits for it
+    fn join(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ClippingAnalyzer {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rtrb::RingBuffer;
+
+    use super::*;
+    use crate::{events::ClippingFound, track::timeline::Clip};
+
+    /// Full-scale square wave, which overshoots between samples, then something quiet
+    fn audio() -> AudioBuffer {
+        let mut buffer = AudioBuffer::stereo(6000);
+        for (index, sample) in buffer.channel_mut(0)[..2000].iter_mut().enumerate() {
+            *sample = if index / 3 % 2 == 0 { 1.0 } else { -1.0 };
+        }
+        buffer.channel_mut(1)[5000..5010].fill(0.25);
+        buffer
+    }
+
+    #[test]
+    fn test_clip_overs_are_reported_on_the_bus_at_their_position() {
+        let (_, events) = RingBuffer::new(1);
+        let mut bus = EventBus::new(events);
+        let found = bus.subscribe::<ClippingFound>();
+        let mut track = TimelineTrack::new("bass");
+        track.add_clip(Clip::new(10_000, Arc::new(audio())));
+
+        let analyzer = ClippingAnalyzer::spawn(0.0);
+        analyzer.analyze_clips(&track);
+        assert_eq!(analyzer.finish(&mut bus), 1);
+        let over = found.try_next().unwrap();
+        assert_eq!(over.source, "bass");
+        assert!((10_000..10_010).contains(&over.start));
+        assert!((11_990..12_010).contains(&over.end));
+        assert!(over.peak_db > 0.0);
+
+        assert!(find_overs(&audio(), 6.0).is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+
+use crate::{analysis::spectrum::Stft, buffer::AudioBuffer};
+
+const CHROMA_FRAME_SIZE: usize = 8192;
+const CHROMA_HOP_SIZE: usize = 4096;
+/// Spectrum range folded into the chroma vector, below it bins are too coarse to tell notes apart
+const CHROMA_MIN_HZ: f64 = 60.0;
+const CHROMA_MAX_HZ: f64 = 5000.0;
+const A4_HZ: f64 = 440.0;
+
+/// Krumhansl-Kessler key profiles, starting on the tonic
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+const PITCH_FRAME_SIZE: usize = 2048;
+/// YIN threshold on the normalised difference function, lower is stricter
+const YIN_THRESHOLD: f32 = 0.15;
+/// Pitch detection range
+const PITCH_MIN_HZ: f64 = 40.0;
+const PITCH_MAX_HZ: f64 = 2000.0;
+/// Frames quieter than this (RMS) are skipped by pitch detection
+const PITCH_SILENCE_RMS: f32 = 1e-3;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// Musical key of a clip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+    /// Pitch class of the tonic, 0 = C up to 11 = B
+    pub tonic: u8,
+    pub mode: Mode,
+    /// Correlation with the key profile, from 0 (no tonal centre) to 1
+    pub confidence: f32,
+}
+
+impl KeyEstimate {
+    /// `true` when both keys share the same notes (same key or relative major/minor)
+    #[must_use]
+    pub fn is_compatible_with(&self, other: &Self) -> bool {
+        self.relative_major() == other.relative_major()
+    }
+
+    /// Tonic of the major key with the same notes
+    const fn relative_major(self) -> u8 {
+        match self.mode {
+            Mode::Major => self.tonic,
+            Mode::Minor => (self.tonic + 3) % 12,
+        }
+    }
+}
+
+impl fmt::Display for KeyEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        write!(f, "{} {mode}", NOTE_NAMES[usize::from(self.tonic)])
+    }
+}
+
+/// Detected fundamental of a (mostly) monophonic clip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f64,
+    /// MIDI note number with cents, e.g. 69.0 for A4 at 440 Hz
+    pub midi_note: f64,
+}
+
+impl PitchEstimate {
+    fn new(frequency: f64) -> Self {
+        Self {
+            frequency,
+            midi_note: 12.0f64.mul_add((frequency / A4_HZ).log2(), 69.0),
+        }
+    }
+
+    /// Semitones to shift by to land on `midi_note`, the input for a pitch shift
+    #[must_use]
+    pub fn semitones_to(&self, midi_note: f64) -> f64 {
+        midi_note - self.midi_note
+    }
+}
+
+/// Estimates the key of `buffer` by matching its chroma profile against major and minor keys.
+///
+/// Returns `None` for silence or material without pitched content.
+#[must_use]
+pub fn estimate_key(buffer: &AudioBuffer, sample_rate: f64) -> Option<KeyEstimate> {
+    let stft = Stft::new(CHROMA_FRAME_SIZE, CHROMA_HOP_SIZE);
+    let bin_hz = sample_rate / CHROMA_FRAME_SIZE as f64;
+    let pitch_classes: Vec<Option<usize>> = (0..stft.bins())
+        .map(|bin| {
+            let hz = bin as f64 * bin_hz;
+            (CHROMA_MIN_HZ..=CHROMA_MAX_HZ).contains(&hz).then(|| {
+                let midi = 12.0f64.mul_add((hz / A4_HZ).log2(), 69.0).round() as i64;
+                midi.rem_euclid(12) as usize
+            })
+        })
+        .collect();
+
+    let mut chroma = [0.0f32; 12];
+    stft.magnitudes(buffer, |magnitudes| {
+        for (magnitude, pitch_class) in magnitudes.iter().zip(&pitch_classes) {
+            if let Some(pitch_class) = pitch_class {
+                chroma[*pitch_class] += magnitude * magnitude;
+            }
+        }
+    });
+    if chroma.iter().sum::<f32>() <= f32::EPSILON {
+        return None;
+    }
+
+    let mut best: Option<KeyEstimate> = None;
+    for tonic in 0..12u8 {
+        for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+            let rotated: [f32; 12] =
+                std::array::from_fn(|i| profile[(i + 12 - usize::from(tonic)) % 12]);
+            let score = pearson(&chroma, &rotated);
+            if best.is_none_or(|best| score > best.confidence) {
+                best = Some(KeyEstimate {
+                    tonic,
+                    mode,
+                    confidence: score,
+                });
+            }
+        }
+    }
+    best.map(|key| KeyEstimate {
+        confidence: key.confidence.max(0.0),
+        ..key
+    })
+}
+
+/// Estimates the fundamental of `buffer` as the median YIN pitch of its voiced frames.
+///
+/// Meant for single notes and monophonic lines; returns `None` when nothing pitched is found.
+pub fn estimate_pitch(buffer: &AudioBuffer, sample_rate: f64) -> Option<PitchEstimate> {
+    let mono: Vec<f32> = buffer
+        .iter_frames()
+        .map(|(left, right)| (left + right) * 0.5)
+        .collect();
+    let min_lag = (sample_rate / PITCH_MAX_HZ).floor().max(2.0) as usize;
+    let max_lag = ((sample_rate / PITCH_MIN_HZ).ceil() as usize).min(PITCH_FRAME_SIZE / 2);
+
+    let mut pitches: Vec<f64> = mono
+        .chunks_exact(PITCH_FRAME_SIZE)
+        .filter(|frame| {
+            let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+            rms > PITCH_SILENCE_RMS
+        })
+        .filter_map(|frame| yin(frame, min_lag, max_lag))
+        .map(|lag| sample_rate / lag)
+        .collect();
+    if pitches.is_empty() {
+        return None;
+    }
+
+    pitches.sort_by(f64::total_cmp);
+    Some(PitchEstimate::new(pitches[pitches.len() / 2]))
+}
+
+/// Period of `frame` in (fractional) samples, using the YIN cumulative mean normalised difference
+fn yin(frame: &[f32], min_lag: usize, max_lag: usize) -> Option<f64> {
+    let window = frame.len() - max_lag;
+    let difference = |lag: usize| -> f32 {
+        frame[..window]
+            .iter()
+            .zip(&frame[lag..lag + window])
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum()
+    };
+
+    let mut normalised = vec![1.0f32; max_lag + 1];
+    let mut running_sum = 0.0;
+    for (lag, value) in normalised.iter_mut().enumerate().skip(1) {
+        let d = difference(lag);
+        running_sum += d;
+        *value = if running_sum > 0.0 {
+            d * lag as f32 / running_sum
+        } else {
+            1.0
+        };
+    }
+
+    let mut lag = min_lag;
+    while lag < max_lag {
+        if normalised[lag] < YIN_THRESHOLD {
+            // walk down to the bottom of this dip
+            while lag + 1 < max_lag && normalised[lag + 1] < normalised[lag] {
+                lag += 1;
+            }
+            let (before, at, after) = (
+                f64::from(normalised[lag - 1]),
+                f64::from(normalised[lag]),
+                f64::from(normalised[lag + 1]),
+            );
+            let curvature = 2.0f64.mul_add(-at, before) + after;
+            let offset = if curvature > 0.0 {
+                (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            };
+            return Some(lag as f64 + offset);
+        }
+        lag += 1;
+    }
+    None
+}
+
+fn pearson(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a) * (x - mean_a);
+        variance_b += (y - mean_b) * (y - mean_b);
+    }
+    let denominator = (variance_a * variance_b).sqrt();
+    if denominator <= f32::EPSILON {
+        0.0
+    } else {
+        covariance / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    fn midi_hz(note: f64) -> f64 {
+        A4_HZ * ((note - 69.0) / 12.0).exp2()
+    }
+
+    /// Plays `notes` (MIDI numbers) one after another, half a second each, with a few harmonics
+    fn melody(notes: &[f64]) -> AudioBuffer {
+        let note_frames = SAMPLE_RATE as usize / 2;
+        let mut samples = Vec::with_capacity(notes.len() * note_frames);
+        for &note in notes {
+            let hz = midi_hz(note);
+            for i in 0..note_frames {
+                let t = i as f64 / SAMPLE_RATE;
+                let sample: f64 = (1..=3)
+                    .map(|harmonic| {
+                        (2.0 * PI * hz * f64::from(harmonic) * t).sin() / f64::from(harmonic)
+                    })
+                    .sum();
+                let sample = (sample * 0.2) as f32;
+                samples.push((sample, sample));
+            }
+        }
+        AudioBuffer::from_frames(&samples)
+    }
+
+    #[test]
+    fn test_detects_major_key() {
+        // C major scale and triad, weighted towards the tonic
+        let notes = [
+            60.0, 62.0, 64.0, 65.0, 67.0, 69.0, 71.0, 72.0, 60.0, 64.0, 67.0, 60.0,
+        ];
+        let key = estimate_key(&melody(&notes), SAMPLE_RATE).unwrap();
+
+        assert_eq!(key.to_string(), "C major");
+        assert!(key.confidence > 0.5);
+    }
+
+    #[test]
+    fn test_detects_minor_key() {
+        // A natural minor, leaning on A, C and E
+        let notes = [
+            57.0, 59.0, 60.0, 62.0, 64.0, 65.0, 67.0, 69.0, 57.0, 60.0, 64.0, 57.0,
+        ];
+        let key = estimate_key(&melody(&notes), SAMPLE_RATE).unwrap();
+
+        assert_eq!((key.tonic, key.mode), (9, Mode::Minor));
+    }
+
+    #[test]
+    fn test_relative_keys_are_compatible() {
+        let c_major = KeyEstimate {
+            tonic: 0,
+            mode: Mode::Major,
+            confidence: 1.0,
+        };
+        let a_minor = KeyEstimate {
+            tonic: 9,
+            mode: Mode::Minor,
+            ..c_major
+        };
+        let d_major = KeyEstimate {
+            tonic: 2,
+            ..c_major
+        };
+
+        assert!(c_major.is_compatible_with(&a_minor));
+        assert!(!c_major.is_compatible_with(&d_major));
+    }
+
+    #[test]
+    fn test_detects_fundamental_of_single_note() {
+        let pitch = estimate_pitch(&melody(&[57.0, 57.0]), SAMPLE_RATE).unwrap();
+
+        assert!((pitch.frequency - 220.0).abs() < 1.0, "{pitch:?}");
+        assert!((pitch.semitones_to(60.0) - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_silence_has_no_key_or_pitch() {
+        let silence = AudioBuffer::stereo(SAMPLE_RATE as usize);
+        assert_eq!(estimate_key(&silence, SAMPLE_RATE), None);
+        assert_eq!(estimate_pitch(&silence, SAMPLE_RATE), None);
+    }
+}
@@ -0,0 +1,35 @@
+//! Offline analysis of audio material, run on decoded buffers away from the audio thread.
+
+use crate::{
+    analysis::{
+        key::{KeyEstimate, PitchEstimate, estimate_key, estimate_pitch},
+        tempo::{TempoConfig, TempoEstimate, estimate_tempo},
+    },
+    buffer::AudioBuffer,
+};
+
+pub mod clipping;
+pub mod key;
+pub mod onset;
+pub mod peaks;
+pub mod slice;
+mod spectrum;
+pub mod tempo;
+
+/// What analysis found out about a clip, kept with it as metadata
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipAnalysis {
+    pub tempo: Option<TempoEstimate>,
+    pub key: Option<KeyEstimate>,
+    pub pitch: Option<PitchEstimate>,
+}
+
+/// Runs tempo, key and pitch detection over `buffer`
+#[must_use]
+pub fn analyze(buffer: &AudioBuffer, sample_rate: f64) -> ClipAnalysis {
+    ClipAnalysis {
+        tempo: estimate_tempo(buffer, sample_rate, &TempoConfig::default()),
+        key: estimate_key(buffer, sample_rate),
+        pitch: estimate_pitch(buffer, sample_rate),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{analysis::spectrum::Stft, buffer::AudioBuffer};
+
+/// Log compression applied to magnitudes before differencing, evens out loud and quiet attacks
+const COMPRESSION: f32 = 100.0;
+/// Frames either side a peak must dominate
+const PEAK_RADIUS: usize = 3;
+/// Frames before and after a peak its local average is taken over
+const AVERAGE_BEFORE: usize = 10;
+const AVERAGE_AFTER: usize = 1;
+/// Flux below this (before normalisation) counts as silence
+const SILENCE_FLUX: f32 = 1e-6;
+
+/// Tuning for [`detect_onsets`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnsetConfig {
+    /// Analysis window in frames, a power of two
+    pub frame_size: usize,
+    /// Frames between analysis windows, which is also the time resolution of the result
+    pub hop_size: usize,
+    /// How far above the local average (on a 0 to 1 scale) a peak must rise,
+    /// lower values find more, softer transients
+    pub threshold: f32,
+    /// Shortest distance between two onsets, in seconds
+    pub min_gap: f64,
+}
+
+impl Default for OnsetConfig {
+    fn default() -> Self {
+        Self {
+            frame_size: 1024,
+            hop_size: 256,
+            threshold: 0.1,
+            min_gap: 0.05,
+        }
+    }
+}
+
+/// A detected transient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onset {
+    /// Position in frames from the start of the buffer
+    pub frame: u64,
+    /// Relative strength, 1.0 for the strongest onset in the material
+    pub strength: f32,
+}
+
+/// Finds transients in `buffer` using the spectral flux of its mono sum.
+///
+/// Positions are accurate to about one hop and come back in order, ready to use as slice
+/// points or warp-marker suggestions.
+///
+/// # Example
+/// ```
+/// use audio_engine::{analysis::onset::{OnsetConfig, detect_onsets}, buffer::AudioBuffer};
+///
+/// let mut frames = vec![(0.0, 0.0); 44100];
+/// frames[22050..22150].fill((0.8, 0.8)); // a click half a second in
+/// let onsets = detect_onsets(&AudioBuffer::from_frames(&frames), 44100.0, &OnsetConfig::default());
+///
+/// assert_eq!(onsets.len(), 1);
+/// ```
+#[must_use]
+pub fn detect_onsets(buffer: &AudioBuffer, sample_rate: f64, config: &OnsetConfig) -> Vec<Onset> {
+    let flux = spectral_flux(buffer, config.frame_size, config.hop_size);
+    let hop_size = config.hop_size.max(1);
+    let min_gap = (config.min_gap * sample_rate / hop_size as f64).round() as usize;
+
+    let mut onsets: Vec<Onset> = Vec::new();
+    let mut last_peak: Option<usize> = None;
+    for index in 0..flux.len() {
+        let value = flux[index];
+        let neighbours =
+            &flux[index.saturating_sub(PEAK_RADIUS)..(index + PEAK_RADIUS + 1).min(flux.len())];
+        if neighbours.iter().any(|&other| other > value) {
+            continue;
+        }
+
+        let around = &flux
+            [index.saturating_sub(AVERAGE_BEFORE)..(index + AVERAGE_AFTER + 1).min(flux.len())];
+        let average = around.iter().sum::<f32>() / around.len() as f32;
+        if value < average + config.threshold {
+            continue;
+        }
+
+        if let Some(last) = last_peak
+            && index - last < min_gap.max(1)
+        {
+            continue;
+        }
+        last_peak = Some(index);
+        onsets.push(Onset {
+            frame: (index * hop_size) as u64,
+            strength: value,
+        });
+    }
+    onsets
+}
+
+/// Half-wave rectified spectral flux per hop, normalised so the strongest change is 1.0.
+/// All zeros for silence.
+pub(crate) fn spectral_flux(buffer: &AudioBuffer, frame_size: usize, hop_size: usize) -> Vec<f32> {
+    let stft = Stft::new(frame_size.max(2), hop_size);
+    let mut previous = vec![0.0; stft.bins()];
+    let mut flux = Vec::with_capacity(buffer.frames() / stft.hop_size() + 1);
+
+    stft.magnitudes(buffer, |magnitudes| {
+        let mut sum = 0.0;
+        for (previous, &magnitude) in previous.iter_mut().zip(magnitudes) {
+            let compressed = (COMPRESSION * magnitude).ln_1p();
+            sum += (compressed - *previous).max(0.0);
+            *previous = compressed;
+        }
+        flux.push(sum);
+    });
+
+    let max = flux.iter().copied().fold(0.0, f32::max);
+    if max < SILENCE_FLUX {
+        flux.fill(0.0);
+    } else {
+        for value in &mut flux {
+            *value /= max;
+        }
+    }
+    flux
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    /// Decaying noise bursts starting at each of `starts`
+    fn hits(starts: &[usize], frames: usize) -> AudioBuffer {
+        let mut samples = vec![(0.0, 0.0); frames];
+        let mut seed = 1u32;
+        for &start in starts {
+            for (offset, frame) in samples[start..].iter_mut().take(4000).enumerate() {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                let sample = noise * (-(offset as f32) / 800.0).exp();
+                *frame = (sample, sample);
+            }
+        }
+        AudioBuffer::from_frames(&samples)
+    }
+
+    #[test]
+    fn test_finds_each_hit() {
+        let starts = [4410, 15000, 30000, 36000];
+        let onsets = detect_onsets(&hits(&starts, 44100), SAMPLE_RATE, &OnsetConfig::default());
+
+        assert_eq!(onsets.len(), starts.len(), "{onsets:?}");
+        for (onset, &start) in onsets.iter().zip(&starts) {
+            assert!(
+                onset.frame.abs_diff(start as u64) <= 512,
+                "{onset:?} vs {start}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_silence_has_no_onsets() {
+        let onsets = detect_onsets(
+            &AudioBuffer::stereo(44100),
+            SAMPLE_RATE,
+            &OnsetConfig::default(),
+        );
+        assert!(onsets.is_empty());
+    }
+
+    #[test]
+    fn test_min_gap_merges_close_hits() {
+        let config = OnsetConfig {
+            min_gap: 0.2,
+            ..OnsetConfig::default()
+        };
+        let onsets = detect_onsets(&hits(&[4410, 6000, 30000], 44100), SAMPLE_RATE, &config);
+
+        assert_eq!(onsets.len(), 2, "{onsets:?}");
+    }
+}
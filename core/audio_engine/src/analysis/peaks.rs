@@ -0,0 +1,150 @@
+//! Waveform overviews, so long files can be drawn without decoding them again.
+
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::buffer::AudioBuffer;
+
+/// Frames each peak covers by default
+pub const DEFAULT_PEAK_BLOCK: usize = 256;
+/// Start of every peak file, followed by the format version
+const MAGIC: &[u8; 4] = b"FFPK";
+const VERSION: u32 = 1;
+
+/// The largest absolute sample of every block of frames, per channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peaks {
+    block: usize,
+    channels: Vec<Vec<f32>>,
+}
+
+impl Peaks {
+    /// Peaks of `buffer` over blocks of `block` frames, the last block may be shorter
+    #[must_use]
+    pub fn from_buffer(buffer: &AudioBuffer, block: usize) -> Self {
+        let block = block.max(1);
+        let channels = (0..buffer.channels())
+            .map(|channel| {
+                buffer
+                    .channel(channel)
+                    .chunks(block)
+                    .map(|chunk| chunk.iter().fold(0.0f32, |peak, s| peak.max(s.abs())))
+                    .collect()
+            })
+            .collect();
+        Self { block, channels }
+    }
+
+    /// Where the peak file of the audio file at `media` is kept: next to it, with
+    /// `.peaks` added to its name
+    #[must_use]
+    pub fn path_for(media: &Path) -> PathBuf {
+        let mut name = media.file_name().unwrap_or_default().to_owned();
+        name.push(".peaks");
+        media.with_file_name(name)
+    }
+
+    /// Frames each peak covers
+    #[must_use]
+    pub const fn block(&self) -> usize {
+        self.block
+    }
+
+    #[must_use]
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    #[must_use]
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        &self.channels[channel]
+    }
+
+    /// Writes the peaks in the peak file format: a header, then each channel's peaks as
+    /// little-endian `f32`s
+    ///
+    /// # Errors
+    /// Whatever `writer` fails with.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let count = self.channels.first().map_or(0, Vec::len);
+        writer.write_all(MAGIC)?;
+        for value in [
+            VERSION,
+            to_u32(self.block)?,
+            to_u32(self.channels.len())?,
+            to_u32(count)?,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for peak in self.channels.iter().flatten() {
+            writer.write_all(&peak.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads peaks written by [`Peaks::write_to`]
+    ///
+    /// # Errors
+    /// [`io::ErrorKind::InvalidData`] if it isn't a peak file of a known version, or
+    /// whatever `reader` fails with.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a peak file");
+        if &magic != MAGIC || read_u32()? != VERSION {
+            return Err(invalid());
+        }
+        let block = read_u32()? as usize;
+        let channels = read_u32()? as usize;
+        let count = read_u32()? as usize;
+        let len = channels
+            .checked_mul(count)
+            .and_then(|len| len.checked_mul(4))
+            .ok_or_else(invalid)?;
+        let mut bytes = vec![0; len];
+        reader.read_exact(&mut bytes)?;
+        let peaks: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        Ok(Self {
+            block,
+            channels: (0..channels)
+                .map(|channel| peaks[channel * count..(channel + 1) * count].to_vec())
+                .collect(),
+        })
+    }
+}
+
+fn to_u32(value: usize) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many peaks"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peaks_roundtrip_through_the_file_format() {
+        let buffer = AudioBuffer::from_frames(&[(0.5, -0.25), (-0.75, 0.0), (0.1, 0.2)]);
+        let peaks = Peaks::from_buffer(&buffer, 2);
+        assert_eq!(peaks.channel(0), &[0.75, 0.1]);
+        assert_eq!(peaks.channel(1), &[0.25, 0.2]);
+
+        let mut file = Vec::new();
+        peaks.write_to(&mut file).unwrap();
+        assert_eq!(Peaks::read_from(file.as_slice()).unwrap(), peaks);
+        assert!(Peaks::read_from(&file[1..]).is_err());
+        assert_eq!(
+            Peaks::path_for(Path::new("media/vox.wav")),
+            Path::new("media/vox.wav.peaks")
+        );
+    }
+}
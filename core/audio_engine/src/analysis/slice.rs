@@ -0,0 +1,111 @@
+use crate::{
+    analysis::onset::{OnsetConfig, detect_onsets},
+    track::wav::WavTrack,
+};
+
+/// Tuning for [`slice_at_transients`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SliceConfig {
+    pub onsets: OnsetConfig,
+    /// Grid step in frames (e.g. [`transport::clock::TempoClock::samples_per_tick`]) slice
+    /// points snap to, measured on the timeline. `None` cuts exactly at the transients.
+    pub grid: Option<f64>,
+}
+
+/// One piece of a sliced clip
+pub struct ClipSlice {
+    /// Timeline position the piece plays at to reproduce the original clip
+    pub start_frame: u64,
+    pub track: WavTrack,
+}
+
+/// Cuts a clip placed at `start_frame` into one clip per transient, for re-arranging
+/// breakbeats and similar material.
+///
+/// The first slice always starts at the clip start, so material before the first transient
+/// is kept. Scheduling every slice at its `start_frame` plays the original clip back.
+#[must_use]
+pub fn slice_at_transients(
+    clip: &WavTrack,
+    start_frame: u64,
+    sample_rate: f64,
+    config: &SliceConfig,
+) -> Vec<ClipSlice> {
+    let samples = clip.samples();
+    let mut points: Vec<usize> = detect_onsets(samples, sample_rate, &config.onsets)
+        .into_iter()
+        .map(|onset| onset.frame)
+        .map(|frame| match config.grid {
+            Some(step) if step > 0.0 => {
+                let timeline = (start_frame + frame) as f64;
+                let snapped = ((timeline / step).round() * step).round() as u64;
+                snapped.saturating_sub(start_frame)
+            }
+            _ => frame,
+        })
+        .filter_map(|frame| usize::try_from(frame).ok())
+        .filter(|&frame| frame > 0 && frame < samples.frames())
+        .collect();
+    // snapping can move neighbouring transients onto the same grid line
+    points.dedup();
+
+    let starts = std::iter::once(0).chain(points.iter().copied());
+    clip.split_at(&points)
+        .into_iter()
+        .zip(starts)
+        .map(|(track, offset)| ClipSlice {
+            start_frame: start_frame + offset as u64,
+            track,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::AudioBuffer;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    fn break_loop(starts: &[usize]) -> WavTrack {
+        let mut samples = vec![(0.0, 0.0); 44100];
+        let mut seed = 3u32;
+        for &start in starts {
+            for (offset, frame) in samples[start..].iter_mut().take(3000).enumerate() {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                let sample = noise * (-(offset as f32) / 600.0).exp();
+                *frame = (sample, sample);
+            }
+        }
+        WavTrack::from_buffer(AudioBuffer::from_frames(&samples))
+    }
+
+    #[test]
+    fn test_slices_cover_the_whole_clip() {
+        let clip = break_loop(&[2000, 11025, 22050, 33075]);
+        let slices = slice_at_transients(&clip, 1000, SAMPLE_RATE, &SliceConfig::default());
+
+        assert_eq!(slices.len(), 5);
+        assert_eq!(slices[0].start_frame, 1000);
+        let total: usize = slices.iter().map(|s| s.track.samples().frames()).sum();
+        assert_eq!(total, 44100);
+        for pair in slices.windows(2) {
+            let end = pair[0].start_frame + pair[0].track.samples().frames() as u64;
+            assert_eq!(end, pair[1].start_frame);
+        }
+    }
+
+    #[test]
+    fn test_slice_points_snap_to_grid() {
+        let clip = break_loop(&[11000, 22100, 33000]);
+        let config = SliceConfig {
+            grid: Some(11025.0),
+            ..SliceConfig::default()
+        };
+        let slices = slice_at_transients(&clip, 11025, SAMPLE_RATE, &config);
+
+        let starts: Vec<_> = slices.iter().map(|s| s.start_frame).collect();
+        assert_eq!(starts, vec![11025, 22050, 33075, 44100]);
+    }
+}
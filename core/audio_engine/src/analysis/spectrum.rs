@@ -0,0 +1,76 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::buffer::AudioBuffer;
+
+/// Short-time Fourier transform of the mono sum of a buffer, with a Hann window.
+/// Frame `i` is centred on sample `i * hop_size`, the signal is zero padded at both ends.
+pub struct Stft {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    hop_size: usize,
+}
+
+impl Stft {
+    pub fn new(frame_size: usize, hop_size: usize) -> Self {
+        let window = (0..frame_size)
+            .map(|i| 0.5f32.mul_add(-(2.0 * PI * i as f32 / frame_size as f32).cos(), 0.5))
+            .collect();
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(frame_size),
+            window,
+            hop_size: hop_size.max(1),
+        }
+    }
+
+    pub const fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// Number of magnitude bins handed out per frame (DC up to Nyquist)
+    pub const fn bins(&self) -> usize {
+        self.window.len() / 2 + 1
+    }
+
+    /// Calls `visit` with the magnitude spectrum of every frame, in order
+    pub fn magnitudes(&self, buffer: &AudioBuffer, mut visit: impl FnMut(&[f32])) {
+        let frame_size = self.window.len();
+        let mono = mono_sum(buffer);
+        let half = frame_size as isize / 2;
+        let frames = buffer.frames().div_ceil(self.hop_size);
+
+        let mut spectrum = vec![Complex::default(); frame_size];
+        let mut scratch = vec![Complex::default(); self.fft.get_inplace_scratch_len()];
+        let mut magnitudes = vec![0.0; self.bins()];
+
+        for frame in 0..frames {
+            let first = (frame * self.hop_size) as isize - half;
+            for (i, (bin, weight)) in spectrum.iter_mut().zip(&self.window).enumerate() {
+                let sample = usize::try_from(first + i as isize)
+                    .ok()
+                    .and_then(|index| mono.get(index))
+                    .copied()
+                    .unwrap_or(0.0);
+                *bin = Complex::new(sample * weight, 0.0);
+            }
+            self.fft.process_with_scratch(&mut spectrum, &mut scratch);
+            for (magnitude, bin) in magnitudes.iter_mut().zip(&spectrum) {
+                *magnitude = bin.norm();
+            }
+            visit(&magnitudes);
+        }
+    }
+}
+
+/// Average of all channels
+fn mono_sum(buffer: &AudioBuffer) -> Vec<f32> {
+    let mut mono = vec![0.0; buffer.frames()];
+    let scale = 1.0 / buffer.channels().max(1) as f32;
+    for channel in 0..buffer.channels() {
+        for (target, sample) in mono.iter_mut().zip(buffer.channel(channel)) {
+            *target += sample * scale;
+        }
+    }
+    mono
+}
@@ -0,0 +1,182 @@
+use crate::{analysis::onset::spectral_flux, buffer::AudioBuffer};
+
+const FRAME_SIZE: usize = 1024;
+const HOP_SIZE: usize = 256;
+/// Tempo the octave weighting is centred on, most loops sit within an octave of it
+const PREFERRED_BPM: f64 = 120.0;
+/// Width of the octave weighting, in octaves
+const OCTAVE_SPREAD: f64 = 1.0;
+/// How close (relative) a loop's length must be to a whole number of beats to snap to it
+const LOOP_SNAP_TOLERANCE: f64 = 0.03;
+
+/// Tempo range searched by [`estimate_tempo`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoConfig {
+    pub min_bpm: f64,
+    pub max_bpm: f64,
+    /// Treat the material as a loop: when its length is close to a whole number of beats
+    /// the tempo is adjusted so it is exactly that many beats long
+    pub is_loop: bool,
+}
+
+impl Default for TempoConfig {
+    fn default() -> Self {
+        Self {
+            min_bpm: 60.0,
+            max_bpm: 180.0,
+            is_loop: true,
+        }
+    }
+}
+
+/// Result of tempo analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoEstimate {
+    pub bpm: f64,
+    /// How periodic the material is, from 0 (no pulse) to 1 (perfectly regular)
+    pub confidence: f32,
+}
+
+impl TempoEstimate {
+    /// Playback speed that brings the material to `target_bpm`, e.g. 1.25 to play a
+    /// 96 BPM loop in a 120 BPM project. This is what a time-stretch should be set to.
+    #[must_use]
+    pub fn stretch_ratio(&self, target_bpm: f64) -> f64 {
+        target_bpm / self.bpm
+    }
+}
+
+/// Estimates the tempo of `buffer` from the periodicity of its onsets.
+///
+/// Returns `None` for material without a detectable pulse (silence, drones, too short).
+#[must_use]
+pub fn estimate_tempo(
+    buffer: &AudioBuffer,
+    sample_rate: f64,
+    config: &TempoConfig,
+) -> Option<TempoEstimate> {
+    let flux = spectral_flux(buffer, FRAME_SIZE, HOP_SIZE);
+    let hops_per_minute = 60.0 * sample_rate / HOP_SIZE as f64;
+    let min_lag = (hops_per_minute / config.max_bpm).floor().max(1.0) as usize;
+    let max_lag = (hops_per_minute / config.min_bpm).ceil() as usize;
+    if flux.len() < max_lag * 2 {
+        return None;
+    }
+
+    let mean = flux.iter().sum::<f32>() / flux.len() as f32;
+    let centred: Vec<f32> = flux.iter().map(|value| value - mean).collect();
+    let energy = autocorrelation(&centred, 0);
+    if energy <= f32::EPSILON {
+        return None;
+    }
+
+    let correlations: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| autocorrelation(&centred, lag) / energy)
+        .collect();
+
+    let weighted = |lag: usize| {
+        let bpm = hops_per_minute / lag as f64;
+        let octaves = (bpm / PREFERRED_BPM).log2() / OCTAVE_SPREAD;
+        f64::from(correlations[lag]) * (-0.5 * octaves * octaves).exp()
+    };
+    let best = (min_lag..=max_lag)
+        .filter(|&lag| correlations[lag] > 0.0)
+        .max_by(|&a, &b| weighted(a).total_cmp(&weighted(b)))?;
+
+    // parabolic interpolation between lags for sub-hop resolution
+    let (before, peak, after) = (
+        f64::from(correlations[best - 1]),
+        f64::from(correlations[best]),
+        f64::from(correlations[best + 1]),
+    );
+    let curvature = 2.0f64.mul_add(-peak, before) + after;
+    let offset = if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let mut bpm = hops_per_minute / (best as f64 + offset);
+
+    if config.is_loop {
+        let seconds = buffer.frames() as f64 / sample_rate;
+        let beats = (seconds * bpm / 60.0).round();
+        let snapped = beats * 60.0 / seconds;
+        if beats >= 1.0 && (snapped / bpm - 1.0).abs() <= LOOP_SNAP_TOLERANCE {
+            bpm = snapped;
+        }
+    }
+
+    Some(TempoEstimate {
+        bpm,
+        confidence: correlations[best].clamp(0.0, 1.0),
+    })
+}
+
+fn autocorrelation(signal: &[f32], lag: usize) -> f32 {
+    signal.iter().zip(&signal[lag..]).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    /// Short noise clicks on every beat, accented on the one
+    fn click_track(bpm: f64, beats: usize) -> AudioBuffer {
+        let beat_frames = 60.0 * SAMPLE_RATE / bpm;
+        let frames = (beat_frames * beats as f64).round() as usize;
+        let mut samples = vec![(0.0, 0.0); frames];
+        let mut seed = 7u32;
+        for beat in 0..beats {
+            let start = (beat as f64 * beat_frames).round() as usize;
+            let gain = if beat % 4 == 0 { 1.0 } else { 0.6 };
+            for (offset, frame) in samples[start..].iter_mut().take(1500).enumerate() {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                let sample = gain * noise * (-(offset as f32) / 300.0).exp();
+                *frame = (sample, sample);
+            }
+        }
+        AudioBuffer::from_frames(&samples)
+    }
+
+    #[test]
+    fn test_detects_common_loop_tempos() {
+        for bpm in [90.0, 120.0, 140.0] {
+            let estimate =
+                estimate_tempo(&click_track(bpm, 16), SAMPLE_RATE, &TempoConfig::default())
+                    .unwrap();
+            assert!((estimate.bpm - bpm).abs() < 0.01, "{bpm}: {estimate:?}");
+            assert!(estimate.confidence > 0.3);
+        }
+    }
+
+    #[test]
+    fn test_non_loop_estimate_is_close() {
+        let config = TempoConfig {
+            is_loop: false,
+            ..TempoConfig::default()
+        };
+        let estimate = estimate_tempo(&click_track(100.0, 16), SAMPLE_RATE, &config).unwrap();
+        assert!((estimate.bpm - 100.0).abs() < 1.5, "{estimate:?}");
+    }
+
+    #[test]
+    fn test_silence_has_no_tempo() {
+        let silence = AudioBuffer::stereo(SAMPLE_RATE as usize * 4);
+        assert_eq!(
+            estimate_tempo(&silence, SAMPLE_RATE, &TempoConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stretch_ratio_matches_project_tempo() {
+        let estimate = TempoEstimate {
+            bpm: 96.0,
+            confidence: 1.0,
+        };
+        assert!((estimate.stretch_ratio(120.0) - 1.25).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,221 @@
+//! Session archives: a project with all of its audio in one file, to move it between
+//! machines.
+//!
+//! An archive is a tar file holding `project.ffp`, every track's audio under `media/`
+//! and a [`Peaks`] file next to each, so the waveforms don't need computing again after
+//! an import. Tracks sharing a file share it in the archive too, files with the same name
+//! from different directories are renamed apart.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::peaks::{DEFAULT_PEAK_BLOCK, Peaks},
+    error::{ArchiveError, ProjectError},
+    project::Project,
+    track::wav::WavTrack,
+};
+
+/// Name of the project file inside an archive
+const PROJECT_FILE: &str = "project.ffp";
+/// Directory of the audio inside an archive
+const MEDIA_DIRECTORY: &str = "media";
+
+impl Project {
+    /// Writes the project and all of its audio to the archive at `path`
+    ///
+    /// # Errors
+    /// [`ArchiveError::Io`] if a file can't be read or written, [`ArchiveError::Media`] if
+    /// a track's audio can't be decoded for its peaks.
+    pub fn export_archive<P: AsRef<Path>>(&self, path: P) -> Result<(), ArchiveError> {
+        let path = path.as_ref();
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| ArchiveError::Io { path, source }
+        };
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let file = File::create(path).map_err(io_error(path))?;
+        let mut archive = tar::Builder::new(BufWriter::new(file));
+
+        let mut archived = self.clone();
+        // source file to its name in the archive
+        let mut media: HashMap<PathBuf, PathBuf> = HashMap::new();
+        for track in &mut archived.tracks {
+            let source = self.media_path(&track.file);
+            if let Some(name) = media.get(&source) {
+                track.file.clone_from(name);
+                continue;
+            }
+            let name = Path::new(MEDIA_DIRECTORY).join(unique_name(&source, &media));
+            archive
+                .append_path_with_name(&source, &name)
+                .map_err(io_error(&source))?;
+
+            let samples = WavTrack::from_file(&source)?.samples;
+            let mut peaks = Vec::new();
+            Peaks::from_buffer(&samples, DEFAULT_PEAK_BLOCK)
+                .write_to(&mut peaks)
+                .map_err(io_error(&source))?;
+            append(&mut archive, &Peaks::path_for(&name), &peaks).map_err(io_error(path))?;
+
+            track.file.clone_from(&name);
+            media.insert(source, name);
+        }
+
+        let project = toml::to_string_pretty(&archived).map_err(ProjectError::from)?;
+        append(&mut archive, Path::new(PROJECT_FILE), project.as_bytes())
+            .map_err(io_error(path))?;
+        archive
+            .into_inner()
+            .and_then(|mut writer| std::io::Write::flush(&mut writer))
+            .map_err(io_error(path))
+    }
+
+    /// Unpacks the archive at `path` into `directory` and loads its project, with track
+    /// files resolved against `directory`
+    ///
+    /// # Errors
+    /// [`ArchiveError::Io`] if the archive can't be read or unpacked,
+    /// [`ArchiveError::Project`] if it holds no valid project.
+    pub fn import_archive<P: AsRef<Path>, D: AsRef<Path>>(
+        path: P,
+        directory: D,
+    ) -> Result<Self, ArchiveError> {
+        let (path, directory) = (path.as_ref(), directory.as_ref());
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let file = File::open(path).map_err(|source| ArchiveError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        std::fs::create_dir_all(directory)
+            .and_then(|()| tar::Archive::new(file).unpack(directory))
+            .map_err(|source| ArchiveError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Ok(Self::load(directory.join(PROJECT_FILE))?)
+    }
+}
+
+/// `source`'s file name, with a number added if a file in `taken` already has it
+fn unique_name(source: &Path, taken: &HashMap<PathBuf, PathBuf>) -> PathBuf {
+    let is_taken = |name: &Path| {
+        taken
+            .values()
+            .any(|taken| taken.file_name() == Some(name.as_os_str()))
+    };
+    let name = PathBuf::from(source.file_name().unwrap_or_default());
+    if !is_taken(&name) {
+        return name;
+    }
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    // one of these is free, there are more of them than taken names
+    (2..=taken.len() + 1)
+        .map(|number| PathBuf::from(format!("{stem}-{number}{extension}")))
+        .find(|name| !is_taken(name))
+        .unwrap_or(name)
+}
+
+/// Adds a file holding `data` to the archive
+fn append<W: std::io::Write>(
+    archive: &mut tar::Builder<W>,
+    name: &Path,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metadata::Metadata, project::ProjectTrack};
+
+    fn write_wav(path: &Path, level: f32) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..1000 {
+            writer.write_sample(level).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_archive_roundtrip_moves_media_with_the_project() {
+        let directory = std::env::temp_dir().join("freqform-archive-test");
+        let _ = std::fs::remove_dir_all(&directory);
+        for sub in ["session/a", "session/b"] {
+            std::fs::create_dir_all(directory.join(sub)).unwrap();
+        }
+        write_wav(&directory.join("session/a/take.wav"), 0.5);
+        write_wav(&directory.join("session/b/take.wav"), 0.25);
+        std::fs::write(
+            directory.join("session/song.ffp"),
+            "[[track]]\nid = \"a\"\nfile = \"a/take.wav\"\n\
+             [[track]]\nid = \"b\"\nfile = \"b/take.wav\"\n\
+             [[track]]\nid = \"a again\"\nfile = \"a/take.wav\"\n",
+        )
+        .unwrap();
+
+        let project = Project::load(directory.join("session/song.ffp")).unwrap();
+        project.export_archive(directory.join("song.tar")).unwrap();
+        let imported =
+            Project::import_archive(directory.join("song.tar"), directory.join("moved")).unwrap();
+
+        let files: Vec<_> = imported.tracks.iter().map(|track| &track.file).collect();
+        assert_eq!(
+            files,
+            [
+                Path::new("media/take.wav"),
+                Path::new("media/take-2.wav"),
+                Path::new("media/take.wav")
+            ]
+        );
+        let tracks = imported.build_tracks(48000.0).unwrap();
+        assert_eq!(tracks.len(), 3);
+        let peaks = File::open(directory.join("moved/media/take-2.wav.peaks")).unwrap();
+        assert_eq!(Peaks::read_from(peaks).unwrap().channel(0)[0], 0.25);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_missing_media_fails_the_export() {
+        let mut project = Project::new(120.0, 48000);
+        project.tracks.push(ProjectTrack {
+            id: "ghost".into(),
+            file: "does/not/exist.wav".into(),
+            start: 0.0,
+            gain: 1.0,
+            pan: 0.0,
+            offset_ms: 0.0,
+            group: None,
+            clip_group: None,
+            follow_tempo: None,
+            metadata: Metadata::default(),
+        });
+        let path = std::env::temp_dir().join("freqform-archive-missing.tar");
+        assert!(matches!(
+            project.export_archive(&path),
+            Err(ArchiveError::Io { .. })
+        ));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,277 @@
+//! Decoded audio shared between the clips, zones and tracks playing it, within a memory
+//! budget. [`SharedAudioPool`] shares it between engines too.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{buffer::AudioBuffer, error::AudioPoolError, track::wav::WavTrack};
+
+/// How much decoded audio an [`AudioPool`] holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolUsage {
+    /// Decoded audio held, in bytes
+    pub bytes: usize,
+    /// Of `bytes`, what something outside the pool still plays
+    pub referenced_bytes: usize,
+    pub sources: usize,
+    /// `None` without a budget
+    pub budget: Option<usize>,
+}
+
+struct PoolEntry {
+    audio: Arc<AudioBuffer>,
+    bytes: usize,
+    /// Value of the pool's clock when the source was last loaded
+    used: u64,
+}
+
+impl PoolEntry {
+    /// Nothing outside the pool plays it, dropping it frees its memory
+    fn is_unreferenced(&self) -> bool {
+        Arc::strong_count(&self.audio) == 1
+    }
+}
+
+/// Decodes each file once and shares the audio.
+///
+/// With a budget, loading past it first purges the least recently used sources nothing plays
+/// any more, and fails if that isn't enough, instead of growing until the machine runs out
+/// of memory.
+///
+/// Not for the audio thread: loading decodes files.
+///
+/// # Example
+/// ```no_run
+/// use audio_engine::audio_pool::AudioPool;
+///
+/// let mut pool = AudioPool::with_budget(512 * 1024 * 1024);
+/// let piano = pool.load("assets/wav/piano.wav")?;
+/// assert!(pool.usage().bytes >= piano.frames() * 4);
+/// # Ok::<(), audio_engine::error::AudioPoolError>(())
+/// ```
+#[derive(Default)]
+pub struct AudioPool {
+    sources: HashMap<PathBuf, PoolEntry>,
+    budget: Option<usize>,
+    /// Counts loads, to tell which sources were used least recently
+    clock: u64,
+}
+
+impl AudioPool {
+    /// A pool without a budget
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pool holding at most `bytes` of decoded audio
+    #[must_use]
+    pub fn with_budget(bytes: usize) -> Self {
+        Self {
+            budget: Some(bytes),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub const fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Limits the decoded audio held to `budget` bytes, purging unreferenced sources to get
+    /// under it. Sources still played stay even if they're over it.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+        if let Some(budget) = budget {
+            self.purge_to(budget);
+        }
+    }
+
+    /// The decoded audio of the WAV file at `path`, decoded on first use.
+    ///
+    /// # Errors
+    /// [`AudioPoolError::Decode`] if the file can't be decoded,
+    /// [`AudioPoolError::OverBudget`] if it doesn't fit in the budget even after purging.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<Arc<AudioBuffer>, AudioPoolError> {
+        let path = path.as_ref();
+        self.clock += 1;
+        if let Some(entry) = self.sources.get_mut(path) {
+            entry.used = self.clock;
+            return Ok(Arc::clone(&entry.audio));
+        }
+        let audio = WavTrack::decode_file(path)?;
+        self.insert(path.to_path_buf(), audio)
+    }
+
+    /// Adds audio decoded elsewhere under `path`, replacing what was there, see
+    /// [`AudioPool::load`]
+    ///
+    /// # Errors
+    /// [`AudioPoolError::OverBudget`] if it doesn't fit in the budget even after purging.
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        audio: AudioBuffer,
+    ) -> Result<Arc<AudioBuffer>, AudioPoolError> {
+        let bytes = Self::size_of(&audio);
+        self.sources.remove(&path);
+        if let Some(budget) = self.budget {
+            // only purge if that makes enough room
+            let usage = self.usage();
+            if usage.referenced_bytes + bytes > budget {
+                return Err(AudioPoolError::OverBudget {
+                    path,
+                    requested: bytes,
+                    used: usage.bytes,
+                    budget,
+                });
+            }
+            self.purge_to(budget - bytes);
+        }
+        self.clock += 1;
+        let audio = Arc::new(audio);
+        self.sources.insert(
+            path,
+            PoolEntry {
+                audio: Arc::clone(&audio),
+                bytes,
+                used: self.clock,
+            },
+        );
+        Ok(audio)
+    }
+
+    /// Whether the file at `path` is decoded and held
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.sources.contains_key(path.as_ref())
+    }
+
+    #[must_use]
+    pub fn usage(&self) -> PoolUsage {
+        let (bytes, referenced_bytes) =
+            self.sources
+                .values()
+                .fold((0, 0), |(bytes, referenced), entry| {
+                    let held = if entry.is_unreferenced() {
+                        0
+                    } else {
+                        entry.bytes
+                    };
+                    (bytes + entry.bytes, referenced + held)
+                });
+        PoolUsage {
+            bytes,
+            referenced_bytes,
+            sources: self.sources.len(),
+            budget: self.budget,
+        }
+    }
+
+    /// Drops every source nothing plays any more, returning the bytes freed
+    pub fn purge(&mut self) -> usize {
+        self.purge_to(0)
+    }
+
+    /// Drops unreferenced sources, least recently used first, until at most `bytes` are held.
+    /// Returns the bytes freed.
+    fn purge_to(&mut self, bytes: usize) -> usize {
+        let mut held = self.usage().bytes;
+        let mut unreferenced: Vec<_> = self
+            .sources
+            .iter()
+            .filter(|(_, entry)| entry.is_unreferenced())
+            .map(|(path, entry)| (entry.used, path.clone()))
+            .collect();
+        unreferenced.sort_unstable();
+        let mut freed = 0;
+        for (_, path) in unreferenced {
+            if held <= bytes {
+                break;
+            }
+            if let Some(entry) = self.sources.remove(&path) {
+                held -= entry.bytes;
+                freed += entry.bytes;
+            }
+        }
+        freed
+    }
+
+    /// Memory taken by the samples of `audio`
+    fn size_of(audio: &AudioBuffer) -> usize {
+        audio.channels() * audio.capacity() * size_of::<f32>()
+    }
+}
+
+/// An [`AudioPool`] several engines load through, so a file open in two projects is decoded
+/// and held once, within one budget. Clones share the pool.
+#[derive(Clone, Default)]
+pub struct SharedAudioPool(Arc<Mutex<AudioPool>>);
+
+impl SharedAudioPool {
+    #[must_use]
+    pub fn new(pool: AudioPool) -> Self {
+        Self(Arc::new(Mutex::new(pool)))
+    }
+
+    /// The pool, locked until the guard is dropped. Loading decodes under the lock, so other
+    /// engines wait for it.
+    pub fn lock(&self) -> MutexGuard<'_, AudioPool> {
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::Lock);
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 stereo frames, 800 bytes
+    fn audio() -> AudioBuffer {
+        AudioBuffer::stereo(100)
+    }
+
+    #[test]
+    fn test_budget_purges_least_recently_used_unreferenced_sources() {
+        let mut pool = AudioPool::with_budget(2000);
+        let kick = pool.insert("kick.wav".into(), audio()).unwrap();
+        drop(pool.insert("snare.wav".into(), audio()).unwrap());
+        drop(pool.insert("hat.wav".into(), audio()).unwrap());
+        assert_eq!(
+            pool.usage(),
+            PoolUsage {
+                bytes: 1600,
+                referenced_bytes: 800,
+                sources: 2,
+                budget: Some(2000),
+            }
+        );
+        // snare, the oldest unreferenced source, made room for the hat
+        assert!(!pool.contains("snare.wav"));
+        assert!(pool.contains("kick.wav"));
+
+        assert!(matches!(
+            pool.insert("loop.wav".into(), AudioBuffer::stereo(200)),
+            Err(AudioPoolError::OverBudget {
+                requested: 1600,
+                ..
+            })
+        ));
+        assert_eq!(pool.purge(), 800);
+        drop(kick);
+        assert_eq!(pool.purge(), 800);
+        assert_eq!(pool.usage().bytes, 0);
+    }
+
+    #[test]
+    fn test_shared_pool_holds_a_source_once() {
+        let shared = SharedAudioPool::default();
+        let other = shared.clone();
+        let kick = shared.lock().insert("kick.wav".into(), audio()).unwrap();
+        assert!(Arc::ptr_eq(&other.lock().load("kick.wav").unwrap(), &kick));
+        assert_eq!(other.lock().usage().sources, 1);
+    }
+}
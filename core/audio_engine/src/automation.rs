@@ -0,0 +1,256 @@
+//! Parameter automation: breakpoint lanes a channel's gain and pan follow while the
+//! transport rolls, and the mode deciding whether a lane plays back or records.
+//!
+//! Lanes live on their [`Channel`](crate::mixer::Channel) and are set up with
+//! [`ChannelChange::SetAutomationMode`](crate::scheduler::command::ChannelChange::SetAutomationMode)
+//! and [`ChannelChange::SetAutomation`](crate::scheduler::command::ChannelChange::SetAutomation).
+//! Their state is published per track in [`TrackSnapshot::automation`], so control surfaces
+//! and UIs show the same modes.
+//!
+//! [`TrackSnapshot::automation`]: crate::snapshot::TrackSnapshot::automation
+
+use serde::{Deserialize, Serialize};
+
+/// Moves closer than this write no new point
+const WRITE_EPSILON: f32 = 1e-6;
+
+/// What a lane does with its parameter while the transport rolls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AutomationMode {
+    /// The lane is ignored, the parameter stays where it's set
+    #[default]
+    Off,
+    /// Plays the lane back, manual moves only last until the next block
+    Read,
+    /// Records the parameter over the lane
+    Write,
+    /// Plays the lane back until the parameter is moved, then records from there until the
+    /// transport stops
+    Latch,
+}
+
+/// A channel parameter a lane automates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutomatedParameter {
+    Gain,
+    Pan,
+}
+
+impl AutomatedParameter {
+    /// Every parameter, a channel has room for a lane each
+    pub const ALL: [Self; 2] = [Self::Gain, Self::Pan];
+}
+
+/// Automation of one parameter: its mode, breakpoints and the manual value overriding them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutomationLane {
+    parameter: AutomatedParameter,
+    mode: AutomationMode,
+    /// `(timeline frame, value)`, sorted, linear in between
+    points: Vec<(u64, f32)>,
+    /// Manual value recorded instead of playing back, until the transport stops
+    #[serde(skip)]
+    pending: Option<f32>,
+}
+
+impl AutomationLane {
+    /// An empty lane, off
+    #[must_use]
+    pub const fn new(parameter: AutomatedParameter) -> Self {
+        Self {
+            parameter,
+            mode: AutomationMode::Off,
+            points: Vec::new(),
+            pending: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn parameter(&self) -> AutomatedParameter {
+        self.parameter
+    }
+
+    #[must_use]
+    pub const fn mode(&self) -> AutomationMode {
+        self.mode
+    }
+
+    /// Switching modes drops a pending override
+    pub fn set_mode(&mut self, mode: AutomationMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.pending = None;
+        }
+    }
+
+    #[must_use]
+    pub fn points(&self) -> &[(u64, f32)] {
+        &self.points
+    }
+
+    /// Replaces the breakpoints, sorted by frame, returning the old ones so they can be
+    /// dropped off the audio thread. Points that come sorted aren't sorted again, so that
+    /// doesn't allocate.
+    pub fn set_points(&mut self, mut points: Vec<(u64, f32)>) -> Vec<(u64, f32)> {
+        if !points.is_sorted_by_key(|&(frame, _)| frame) {
+            points.sort_by_key(|&(frame, _)| frame);
+        }
+        std::mem::replace(&mut self.points, points)
+    }
+
+    /// Moves every breakpoint through a time insertion or removal. `shift` maps old timeline
+    /// frames to new ones and keeps them in order, so nothing is sorted or allocated.
+    pub fn shift_points(&mut self, shift: impl Fn(u64) -> u64) {
+        for (frame, _) in &mut self.points {
+            *frame = shift(*frame);
+        }
+    }
+
+    /// Moves the breakpoints into `room`, an empty `Vec` reserved off the audio thread, when
+    /// it has more capacity than they do, so recording has room to write. Returns what's
+    /// left over to be dropped off the audio thread: the old points or `room` itself.
+    pub fn reserve_points(&mut self, mut room: Vec<(u64, f32)>) -> Vec<(u64, f32)> {
+        if room.capacity() <= self.points.capacity() {
+            return room;
+        }
+        room.clear();
+        room.extend_from_slice(&self.points);
+        std::mem::replace(&mut self.points, room)
+    }
+
+    /// The manual value being recorded over the lane, `None` while it plays back
+    #[must_use]
+    pub const fn pending(&self) -> Option<f32> {
+        self.pending
+    }
+
+    /// Value of the lane at timeline `frame`, held before the first and after the last
+    /// point. `None` without points.
+    #[must_use]
+    pub fn value_at(&self, frame: u64) -> Option<f32> {
+        let next = self.points.partition_point(|&(point, _)| point <= frame);
+        match (
+            next.checked_sub(1).map(|i| self.points[i]),
+            self.points.get(next),
+        ) {
+            (Some((from, a)), Some(&(to, b))) => {
+                let t = (frame - from) as f32 / (to - from) as f32;
+                Some((b - a).mul_add(t, a))
+            }
+            (Some((_, value)), None) | (None, Some(&(_, value))) => Some(value),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the lane sets its parameter while rolling, rather than being off or recording
+    #[must_use]
+    pub const fn is_playing_back(&self) -> bool {
+        matches!(
+            (self.mode, self.pending),
+            (AutomationMode::Read, _) | (AutomationMode::Latch, None)
+        )
+    }
+
+    /// The parameter was moved to `value` by hand. Writing and latching lanes record it from
+    /// here on, the others ignore it.
+    pub fn touch(&mut self, value: f32) {
+        if matches!(self.mode, AutomationMode::Write | AutomationMode::Latch) {
+            self.pending = Some(value);
+        }
+    }
+
+    /// The transport stopped: latched lanes go back to playing back
+    pub fn stop(&mut self) {
+        self.pending = None;
+    }
+
+    /// Runs the lane over the block of `frames` at timeline `frame`, while rolling, with the
+    /// parameter at `current`. Returns the value to set the parameter to, if the lane plays
+    /// back.
+    pub fn automate(&mut self, frame: u64, frames: usize, current: f32) -> Option<f32> {
+        if self.is_playing_back() {
+            return self.value_at(frame);
+        }
+        if self.mode != AutomationMode::Off {
+            self.write(frame, frames, self.pending.unwrap_or(current));
+        }
+        None
+    }
+
+    /// Records `value` over the block of `frames` at `frame`. Points past the lane's
+    /// capacity aren't written, the audio thread doesn't grow it.
+    fn write(&mut self, frame: u64, frames: usize, value: f32) {
+        let start = self.points.partition_point(|&(point, _)| point < frame);
+        let end = self
+            .points
+            .partition_point(|&(point, _)| point < frame + frames as u64);
+        self.points.drain(start..end);
+
+        // a held value stretches its last point instead of adding one per block
+        let held = |point: Option<&(u64, f32)>| {
+            point.is_some_and(|&(_, held)| (held - value).abs() < WRITE_EPSILON)
+        };
+        if start >= 2 && held(self.points.get(start - 1)) && held(self.points.get(start - 2)) {
+            self.points[start - 1].0 = frame;
+        } else if self.points.len() < self.points.capacity() {
+            self.points.insert(start, (frame, value));
+        }
+    }
+}
+
+/// State of one lane as published in a snapshot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutomationStatus {
+    pub parameter: AutomatedParameter,
+    pub mode: AutomationMode,
+    /// Where the parameter is now
+    pub value: f32,
+    /// Manual value recorded over the lane, see [`AutomationLane::pending`]
+    pub pending: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_plays_back_and_latch_records_after_a_touch() {
+        let mut lane = AutomationLane::new(AutomatedParameter::Gain);
+        lane.set_points(vec![(100, 1.0), (0, 0.0)]);
+        lane.reserve_points(Vec::with_capacity(8));
+        assert_eq!(lane.automate(50, 10, 0.3), None);
+
+        lane.set_mode(AutomationMode::Read);
+        assert_eq!(lane.automate(50, 10, 0.3), Some(0.5));
+        assert_eq!(lane.value_at(200), Some(1.0));
+        lane.touch(0.8);
+        assert_eq!(lane.pending(), None);
+
+        lane.set_mode(AutomationMode::Latch);
+        assert_eq!(lane.automate(25, 10, 0.3), Some(0.25));
+        lane.touch(0.8);
+        for block in 0..4 {
+            assert_eq!(lane.automate(30 + block * 10, 10, 0.8), None);
+        }
+        assert_eq!(lane.pending(), Some(0.8));
+        // the held value is written as a flat stretch up to where recording stopped
+        assert_eq!(lane.points(), [(0, 0.0), (30, 0.8), (60, 0.8), (100, 1.0)]);
+
+        lane.stop();
+        assert_eq!(lane.automate(80, 10, 0.8), Some(0.9));
+    }
+
+    #[test]
+    fn test_writing_stays_within_the_reserved_room() {
+        let mut lane = AutomationLane::new(AutomatedParameter::Pan);
+        lane.set_mode(AutomationMode::Write);
+        lane.automate(0, 10, 0.1);
+        assert!(lane.points().is_empty());
+
+        assert!(lane.reserve_points(Vec::with_capacity(2)).is_empty());
+        for block in 0..4 {
+            lane.automate(block * 10, 10, block as f32);
+        }
+        assert_eq!(lane.points(), [(0, 0.0), (10, 1.0)]);
+    }
+}
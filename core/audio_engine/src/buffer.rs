@@ -0,0 +1,309 @@
+use dasp_sample::{FromSample, Sample as _};
+
+/// Planar (non-interleaved) audio: every channel is its own contiguous slice of samples.
+///
+/// A buffer is allocated once with a frame capacity and then resized within it through
+/// [`AudioBuffer::set_frames`], so it can be reused on the audio thread without allocating.
+///
+/// # Example
+/// ```
+/// use audio_engine::buffer::AudioBuffer;
+///
+/// let mut buffer = AudioBuffer::stereo(4);
+/// let (left, right) = buffer.stereo_mut();
+/// left.fill(0.5);
+/// right.fill(0.25);
+///
+/// assert_eq!(buffer.frame(3), (0.5, 0.25));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioBuffer {
+    /// One vector per channel, each `capacity` samples long
+    channels: Vec<Vec<f32>>,
+    /// Number of frames currently in use
+    frames: usize,
+}
+
+impl AudioBuffer {
+    /// A silent buffer of `frames` frames
+    #[must_use]
+    pub fn new(channels: usize, frames: usize) -> Self {
+        Self {
+            channels: vec![vec![0.0; frames]; channels],
+            frames,
+        }
+    }
+
+    #[must_use]
+    pub fn stereo(frames: usize) -> Self {
+        Self::new(2, frames)
+    }
+
+    #[must_use]
+    pub fn mono(frames: usize) -> Self {
+        Self::new(1, frames)
+    }
+
+    /// Builds a stereo buffer from `(L, R)` frames
+    #[must_use]
+    pub fn from_frames(frames: &[(f32, f32)]) -> Self {
+        let left = frames.iter().map(|&(l, _)| l).collect();
+        let right = frames.iter().map(|&(_, r)| r).collect();
+        Self {
+            channels: vec![left, right],
+            frames: frames.len(),
+        }
+    }
+
+    #[must_use]
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Largest frame count reachable through [`AudioBuffer::set_frames`]
+    pub fn capacity(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    /// Changes the number of frames in use without reallocating.
+    ///
+    /// # Panics
+    /// If `frames` exceeds the buffer's capacity.
+    pub fn set_frames(&mut self, frames: usize) {
+        assert!(
+            frames <= self.capacity(),
+            "{frames} frames requested from a buffer of capacity {}",
+            self.capacity()
+        );
+        self.frames = frames;
+    }
+
+    #[must_use]
+    pub fn channel(&self, channel: usize) -> &[f32] {
+        &self.channels[channel][..self.frames]
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [f32] {
+        &mut self.channels[channel][..self.frames]
+    }
+
+    /// Left and right channels, borrowed together.
+    ///
+    /// # Panics
+    /// If the buffer has fewer than two channels.
+    pub fn stereo_mut(&mut self) -> (&mut [f32], &mut [f32]) {
+        let frames = self.frames;
+        let channels = self.channels.len();
+        let [left, right, ..] = self.channels.as_mut_slice() else {
+            panic!("stereo access to a {channels}-channel buffer");
+        };
+        (&mut left[..frames], &mut right[..frames])
+    }
+
+    /// The `(L, R)` pair at `index`; mono buffers report the same sample on both sides
+    #[must_use]
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        assert!(index < self.frames, "frame {index} out of range");
+        let left = self.channels[0][index];
+        let right = self.channels.get(1).map_or(left, |right| right[index]);
+        (left, right)
+    }
+
+    pub fn iter_frames(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        (0..self.frames).map(|index| self.frame(index))
+    }
+
+    pub fn clear(&mut self) {
+        let frames = self.frames;
+        for channel in &mut self.channels {
+            channel[..frames].fill(0.0);
+        }
+    }
+
+    /// Sums `source` into this buffer starting at frame `offset`.
+    /// Channels missing on either side are skipped.
+    pub fn add_from(&mut self, source: &Self, offset: usize) {
+        let frames = source.frames.min(self.frames.saturating_sub(offset));
+        for (target, source) in self.channels.iter_mut().zip(&source.channels) {
+            for (out, sample) in target[offset..offset + frames]
+                .iter_mut()
+                .zip(&source[..frames])
+            {
+                *out += sample;
+            }
+        }
+    }
+
+    /// Like [`AudioBuffer::add_from`], with `source` scaled by `gain`
+    pub fn add_scaled_from(&mut self, source: &Self, offset: usize, gain: f32) {
+        let frames = source.frames.min(self.frames.saturating_sub(offset));
+        for (target, source) in self.channels.iter_mut().zip(&source.channels) {
+            for (out, sample) in target[offset..offset + frames]
+                .iter_mut()
+                .zip(&source[..frames])
+            {
+                *out = sample.mul_add(gain, *out);
+            }
+        }
+    }
+
+    /// Copies `len` frames of `source`, starting at `source_start`, to frame `start`
+    pub fn copy_from(&mut self, start: usize, source: &Self, source_start: usize, len: usize) {
+        for (target, source) in self.channels.iter_mut().zip(&source.channels) {
+            target[start..start + len].copy_from_slice(&source[source_start..source_start + len]);
+        }
+    }
+
+    /// Like [`AudioBuffer::copy_from`], repeating `source`'s last channel into the channels it
+    /// doesn't have, so mono audio lands on both sides of a stereo buffer
+    pub fn copy_upmixed_from(
+        &mut self,
+        start: usize,
+        source: &Self,
+        source_start: usize,
+        len: usize,
+    ) {
+        let Some(last) = source.channels.len().checked_sub(1) else {
+            return;
+        };
+        for (index, target) in self.channels.iter_mut().enumerate() {
+            let source = &source.channels[index.min(last)];
+            target[start..start + len].copy_from_slice(&source[source_start..source_start + len]);
+        }
+    }
+
+    pub fn apply_gain(&mut self, gain: f32) {
+        let frames = self.frames;
+        for channel in &mut self.channels {
+            for sample in &mut channel[..frames] {
+                *sample *= gain;
+            }
+        }
+    }
+
+    /// Highest absolute sample of `channel` within `start..start + len`
+    #[must_use]
+    pub fn peak(&self, channel: usize, start: usize, len: usize) -> f32 {
+        self.channels[channel][start..start + len]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+    }
+
+    /// Appends `other`'s frames, growing the buffer. Allocates, not for the audio thread.
+    pub fn extend_from(&mut self, other: &Self) {
+        if self.channels.is_empty() {
+            self.channels = vec![Vec::new(); other.channels()];
+        }
+        let frames = self.frames;
+        for (channel, source) in self.channels.iter_mut().zip(&other.channels) {
+            channel.truncate(frames);
+            channel.extend_from_slice(&source[..other.frames]);
+        }
+        self.frames += other.frames;
+    }
+
+    /// Writes the buffer into an interleaved device buffer, converting to `T`.
+    /// Stops at whichever of the two runs out of frames first.
+    pub fn write_interleaved<T>(&self, output: &mut [T])
+    where
+        T: FromSample<f32>,
+    {
+        let channels = self.channels();
+        if channels == 0 {
+            return;
+        }
+        for (index, frame) in output.chunks_mut(channels).take(self.frames).enumerate() {
+            for (sample, channel) in frame.iter_mut().zip(&self.channels) {
+                *sample = channel[index].to_sample::<T>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_frames_reuses_capacity() {
+        let mut buffer = AudioBuffer::stereo(8);
+        buffer.set_frames(3);
+
+        assert_eq!(buffer.frames(), 3);
+        assert_eq!(buffer.capacity(), 8);
+        assert_eq!(buffer.channel(0).len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity")]
+    fn test_set_frames_beyond_capacity_panics() {
+        AudioBuffer::stereo(4).set_frames(5);
+    }
+
+    #[test]
+    fn test_add_from_sums_at_offset() {
+        let mut mix = AudioBuffer::stereo(4);
+        let source = AudioBuffer::from_frames(&[(0.5, 0.25), (0.5, 0.25)]);
+
+        mix.add_from(&source, 1);
+        mix.add_from(&source, 3); // only one frame fits
+
+        let frames: Vec<_> = mix.iter_frames().collect();
+        assert_eq!(
+            frames,
+            vec![(0.0, 0.0), (0.5, 0.25), (0.5, 0.25), (0.5, 0.25)]
+        );
+    }
+
+    #[test]
+    fn test_add_scaled_from_applies_gain() {
+        let mut mix = AudioBuffer::from_frames(&[(0.1, 0.1); 2]);
+        let source = AudioBuffer::from_frames(&[(0.5, -0.5); 2]);
+
+        mix.add_scaled_from(&source, 0, 0.5);
+
+        assert_eq!(mix.frame(1), (0.35, -0.15));
+    }
+
+    #[test]
+    fn test_copy_from_moves_frame_range() {
+        let mut target = AudioBuffer::stereo(3);
+        let source = AudioBuffer::from_frames(&[(0.1, 0.2), (0.3, 0.4), (0.5, 0.6)]);
+
+        target.copy_from(1, &source, 1, 2);
+
+        let frames: Vec<_> = target.iter_frames().collect();
+        assert_eq!(frames, vec![(0.0, 0.0), (0.3, 0.4), (0.5, 0.6)]);
+    }
+
+    #[test]
+    fn test_write_interleaved_converts_samples() {
+        let buffer = AudioBuffer::from_frames(&[(1.0, -1.0), (0.0, 0.5)]);
+        let mut output = [0i16; 4];
+
+        buffer.write_interleaved(&mut output);
+
+        assert_eq!(output[0], i16::MAX);
+        assert_eq!(output[1], i16::MIN);
+        assert_eq!(output[2], 0);
+        assert!(output[3] > 0);
+    }
+
+    #[test]
+    fn test_mono_frame_is_duplicated() {
+        let mut buffer = AudioBuffer::new(1, 2);
+        buffer.channel_mut(0)[1] = 0.3;
+
+        assert_eq!(buffer.frame(1), (0.3, 0.3));
+    }
+}
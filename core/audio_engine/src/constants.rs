@@ -1 +1,25 @@
 pub const AUDIO_SAMPLE_EPSILON: f32 = 1e-6;
+
+/// Usual length of the fades [`TimelineTrack`](crate::track::timeline::TimelineTrack)
+/// puts on edited clip edges, see its `set_edit_fade`
+pub const EDIT_FADE_SECONDS: f64 = 0.01;
+
+/// Largest block the scheduler renders in one pass, longer callbacks are split
+pub const MAX_BLOCK_FRAMES: usize = 4096;
+
+/// Active tracks the scheduler reserves room for up front
+pub const MAX_ACTIVE_TRACKS: usize = 256;
+
+/// Aux sends a channel has room for, see [`Channel::set_send`](crate::mixer::Channel::set_send)
+pub const MAX_SENDS: usize = 16;
+
+/// Automation points a lane has room to record, reserved by
+/// [`EngineHandle::send`](crate::engine::EngineHandle::send)
+pub const AUTOMATION_RECORD_ROOM: usize = 8192;
+
+/// Record enable changes the mixer has room for, see
+/// [`Mixer::set_record_enable`](crate::mixer::Mixer::set_record_enable)
+pub const RECORD_ENABLE_ROOM: usize = 256;
+
+/// Scenes a [`SceneLauncher`](crate::scene::SceneLauncher) has room for
+pub const MAX_SCENES: usize = 128;
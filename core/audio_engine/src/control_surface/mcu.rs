@@ -0,0 +1,748 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    dsp::math::gain_to_db,
+    monitor::MonitorChange,
+    scheduler::command::{
+        ChannelChange, LoopOptions, ParameterChange, SchedulerCommand, ScrubChange,
+    },
+};
+
+/// Channel strips per MCU unit (the master fader is strip 8)
+pub const MCU_STRIPS: usize = 8;
+pub const MCU_MASTER_STRIP: u8 = 8;
+
+/// Characters per strip on each of the two scribble strip rows
+const SCRIBBLE_CHARS: usize = 7;
+const SCRIBBLE_ROW_CHARS: usize = SCRIBBLE_CHARS * MCU_STRIPS;
+const SYSEX_HEADER: [u8; 5] = [0xF0, 0x00, 0x00, 0x66, 0x14];
+const SYSEX_LCD: u8 = 0x12;
+const SYSEX_END: u8 = 0xF7;
+
+/// Fader positions are 14-bit pitch bend values
+const FADER_MAX: u16 = 0x3FFF;
+/// Pan change per V-Pot detent
+const VPOT_PAN_STEP: f32 = 0.02;
+/// Playback speed while Rewind or Fast Forward is held
+const SHUTTLE_SPEED: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McuButton {
+    RecArm(u8),
+    Solo(u8),
+    Mute(u8),
+    Select(u8),
+    BankLeft,
+    BankRight,
+    ChannelLeft,
+    ChannelRight,
+    Cycle,
+    Rewind,
+    FastForward,
+    Stop,
+    Play,
+    Record,
+}
+
+impl McuButton {
+    fn from_note(note: u8) -> Option<Self> {
+        match note {
+            0x00..=0x07 => Some(Self::RecArm(note)),
+            0x08..=0x0F => Some(Self::Solo(note - 0x08)),
+            0x10..=0x17 => Some(Self::Mute(note - 0x10)),
+            0x18..=0x1F => Some(Self::Select(note - 0x18)),
+            0x2E => Some(Self::BankLeft),
+            0x2F => Some(Self::BankRight),
+            0x30 => Some(Self::ChannelLeft),
+            0x31 => Some(Self::ChannelRight),
+            0x56 => Some(Self::Cycle),
+            0x5B => Some(Self::Rewind),
+            0x5C => Some(Self::FastForward),
+            0x5D => Some(Self::Stop),
+            0x5E => Some(Self::Play),
+            0x5F => Some(Self::Record),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn note(&self) -> u8 {
+        match self {
+            Self::RecArm(strip) => *strip,
+            Self::Solo(strip) => 0x08 + strip,
+            Self::Mute(strip) => 0x10 + strip,
+            Self::Select(strip) => 0x18 + strip,
+            Self::BankLeft => 0x2E,
+            Self::BankRight => 0x2F,
+            Self::ChannelLeft => 0x30,
+            Self::ChannelRight => 0x31,
+            Self::Cycle => 0x56,
+            Self::Rewind => 0x5B,
+            Self::FastForward => 0x5C,
+            Self::Stop => 0x5D,
+            Self::Play => 0x5E,
+            Self::Record => 0x5F,
+        }
+    }
+}
+
+/// A decoded message from an MCU surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McuMessage {
+    /// 14-bit fader position, strip 8 is the master fader
+    Fader {
+        strip: u8,
+        value: u16,
+    },
+    /// Relative V-Pot rotation in detents (negative = counter-clockwise)
+    VPot {
+        strip: u8,
+        delta: i8,
+    },
+    ButtonPressed(McuButton),
+    ButtonReleased(McuButton),
+}
+
+impl McuMessage {
+    /// Decodes a raw MIDI message, ignoring anything that isn't part of the MCU surface protocol
+    #[must_use]
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let [status, data1, data2] = *bytes else {
+            return None;
+        };
+
+        match status & 0xF0 {
+            0xE0 => Some(Self::Fader {
+                strip: status & 0x0F,
+                value: u16::from(data2 & 0x7F) << 7 | u16::from(data1 & 0x7F),
+            }),
+            0x90 | 0x80 => {
+                let button = McuButton::from_note(data1)?;
+                if status & 0xF0 == 0x90 && data2 > 0 {
+                    Some(Self::ButtonPressed(button))
+                } else {
+                    Some(Self::ButtonReleased(button))
+                }
+            }
+            0xB0 if (0x10..=0x17).contains(&data1) => {
+                let ticks = (data2 & 0x3F) as i8;
+                let delta = if data2 & 0x40 != 0 { -ticks } else { ticks };
+                Some(Self::VPot {
+                    strip: data1 - 0x10,
+                    delta,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Pitch bend message moving a motorized fader to `gain` (0.0 to 1.0)
+#[must_use]
+pub fn fader_message(strip: u8, gain: f32) -> [u8; 3] {
+    let value = (gain.clamp(0.0, 1.0) * f32::from(FADER_MAX)).round() as u16;
+    [
+        0xE0 | (strip & 0x0F),
+        (value & 0x7F) as u8,
+        ((value >> 7) & 0x7F) as u8,
+    ]
+}
+
+/// Button LED on/off
+#[must_use]
+pub fn led_message(button: McuButton, on: bool) -> [u8; 3] {
+    [0x90, button.note(), if on { 0x7F } else { 0x00 }]
+}
+
+/// `SysEx` writing `text` to one strip's slot on the given scribble strip row (0 = top, 1 = bottom).
+/// Text is truncated/padded to 7 characters, non-ASCII is replaced with `?`.
+#[must_use]
+pub fn scribble_strip_message(strip: u8, row: u8, text: &str) -> Vec<u8> {
+    let offset = usize::from(row.min(1)) * SCRIBBLE_ROW_CHARS
+        + usize::from(strip.min(MCU_STRIPS as u8 - 1)) * SCRIBBLE_CHARS;
+
+    let mut message = Vec::with_capacity(SYSEX_HEADER.len() + 3 + SCRIBBLE_CHARS);
+    message.extend_from_slice(&SYSEX_HEADER);
+    message.push(SYSEX_LCD);
+    message.push(offset as u8);
+
+    let chars = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() {
+                c as u8
+            } else {
+                b'?'
+            }
+        })
+        .chain(std::iter::repeat(b' '))
+        .take(SCRIBBLE_CHARS);
+    message.extend(chars);
+
+    message.push(SYSEX_END);
+    message
+}
+
+struct SurfaceTrack {
+    id: String,
+    gain: f32,
+    pan: f32,
+    muted: bool,
+    soloed: bool,
+    armed: bool,
+}
+
+/// Maps MCU strips onto a bank of tracks and turns surface input into scheduler commands.
+///
+/// Faders set track gain (0.0 to 1.0), V-Pots nudge pan, Mute/Solo/Rec toggle the strip's
+/// channel, and the transport buttons drive playback: Rewind and Fast Forward shuttle while
+/// held, Record switches recording at the playhead, Cycle the loop. The master fader sets
+/// the monitor level. Bank/Channel buttons shift which tracks the eight strips control.
+pub struct McuSurface {
+    tracks: Vec<SurfaceTrack>,
+    bank_offset: usize,
+    /// Master fader position, 0.0 to 1.0
+    master_gain: f32,
+    /// Playhead tick, kept current by the host, see [`McuSurface::playhead`]
+    playhead: Arc<AtomicU64>,
+    recording: bool,
+    /// Loop start and end the Cycle button switches
+    cycle: (LoopOptions, LoopOptions),
+    cycling: bool,
+}
+
+impl McuSurface {
+    #[must_use]
+    pub fn new(track_ids: &[&str]) -> Self {
+        Self {
+            tracks: track_ids
+                .iter()
+                .map(|id| SurfaceTrack {
+                    id: (*id).to_owned(),
+                    gain: 1.0,
+                    pan: 0.0,
+                    muted: false,
+                    soloed: false,
+                    armed: false,
+                })
+                .collect(),
+            bank_offset: 0,
+            master_gain: 1.0,
+            playhead: Arc::new(AtomicU64::new(0)),
+            recording: false,
+            cycle: (
+                LoopOptions {
+                    bar: 1,
+                    beat: 1,
+                    tick: 1,
+                },
+                LoopOptions {
+                    bar: 5,
+                    beat: 1,
+                    tick: 1,
+                },
+            ),
+            cycling: false,
+        }
+    }
+
+    /// Playhead tick Record switches recording at. The surface doesn't see the transport,
+    /// so the host stores the current tick here, e.g. after each snapshot.
+    #[must_use]
+    pub fn playhead(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.playhead)
+    }
+
+    /// The loop Cycle switches on and off, the first four bars until set
+    pub fn set_cycle_range(&mut self, start: LoopOptions, end: LoopOptions) {
+        self.cycle = (start, end);
+    }
+
+    #[must_use]
+    pub fn bank_offset(&self) -> usize {
+        self.bank_offset
+    }
+
+    /// Id of the track currently mapped to `strip`
+    #[must_use]
+    pub fn track_for_strip(&self, strip: u8) -> Option<&str> {
+        self.strip_track(strip).map(|track| track.id.as_str())
+    }
+
+    pub fn handle(&mut self, message: McuMessage) -> Option<SchedulerCommand> {
+        match message {
+            McuMessage::Fader { strip, value } => {
+                let gain = f32::from(value.min(FADER_MAX)) / f32::from(FADER_MAX);
+                if strip == MCU_MASTER_STRIP {
+                    self.master_gain = gain;
+                    return Some(SchedulerCommand::Monitor(MonitorChange::SetLevel(
+                        gain_to_db(gain),
+                    )));
+                }
+                let track = self.strip_track_mut(strip)?;
+                track.gain = gain;
+                Some(SchedulerCommand::ParamChange {
+                    target_id: track.id.clone(),
+                    change: ParameterChange::SetGain(track.gain),
+                })
+            }
+            McuMessage::VPot { strip, delta } => {
+                let track = self.strip_track_mut(strip)?;
+                track.pan = f32::from(delta)
+                    .mul_add(VPOT_PAN_STEP, track.pan)
+                    .clamp(-1.0, 1.0);
+                Some(SchedulerCommand::ParamChange {
+                    target_id: track.id.clone(),
+                    change: ParameterChange::SetPan(track.pan),
+                })
+            }
+            McuMessage::ButtonPressed(button) => self.handle_button(button),
+            // shuttling ends where it got to
+            McuMessage::ButtonReleased(McuButton::Rewind | McuButton::FastForward) => {
+                Some(SchedulerCommand::Scrub(ScrubChange::Stop))
+            }
+            McuMessage::ButtonReleased(_) => None,
+        }
+    }
+
+    /// What the surface should show after sending `message`: motorized faders snap back
+    /// unless their position is echoed, and buttons light up with the state they toggled
+    #[must_use]
+    pub fn echo(&self, message: McuMessage) -> Option<[u8; 3]> {
+        match message {
+            McuMessage::Fader { strip, .. } if strip == MCU_MASTER_STRIP => {
+                Some(fader_message(strip, self.master_gain))
+            }
+            McuMessage::Fader { strip, .. } => self
+                .strip_track(strip)
+                .map(|track| fader_message(strip, track.gain)),
+            McuMessage::ButtonPressed(
+                button @ (McuButton::Mute(strip)
+                | McuButton::Solo(strip)
+                | McuButton::RecArm(strip)),
+            ) => self
+                .strip_track(strip)
+                .map(|track| led_message(button, track.button_lit(button))),
+            McuMessage::ButtonPressed(McuButton::Record) => {
+                Some(led_message(McuButton::Record, self.recording))
+            }
+            McuMessage::ButtonPressed(McuButton::Cycle) => {
+                Some(led_message(McuButton::Cycle, self.cycling))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fader positions, button LEDs and scribble strip names for the current bank, to resync
+    /// the surface
+    #[must_use]
+    pub fn feedback_messages(&self) -> Vec<Vec<u8>> {
+        let mut messages = Vec::with_capacity(MCU_STRIPS * 5 + 3);
+        for strip in 0..MCU_STRIPS as u8 {
+            let track = self.strip_track(strip);
+            let (name, gain) = track.map_or(("", 0.0), |track| (track.id.as_str(), track.gain));
+            messages.push(scribble_strip_message(strip, 0, name));
+            messages.push(fader_message(strip, gain).to_vec());
+            for button in [
+                McuButton::Mute(strip),
+                McuButton::Solo(strip),
+                McuButton::RecArm(strip),
+            ] {
+                let lit = track.is_some_and(|track| track.button_lit(button));
+                messages.push(led_message(button, lit).to_vec());
+            }
+        }
+        messages.push(fader_message(MCU_MASTER_STRIP, self.master_gain).to_vec());
+        messages.push(led_message(McuButton::Record, self.recording).to_vec());
+        messages.push(led_message(McuButton::Cycle, self.cycling).to_vec());
+        messages
+    }
+
+    fn handle_button(&mut self, button: McuButton) -> Option<SchedulerCommand> {
+        match button {
+            McuButton::Play => Some(SchedulerCommand::Play),
+            McuButton::Stop => Some(SchedulerCommand::Stop),
+            McuButton::Rewind => Some(SchedulerCommand::Scrub(ScrubChange::Audition(Some(
+                -SHUTTLE_SPEED,
+            )))),
+            McuButton::FastForward => Some(SchedulerCommand::Scrub(ScrubChange::Audition(Some(
+                SHUTTLE_SPEED,
+            )))),
+            McuButton::Record => {
+                self.recording = !self.recording;
+                Some(SchedulerCommand::SetRecordEnable {
+                    tick: self.playhead.load(Ordering::Relaxed),
+                    enabled: self.recording,
+                })
+            }
+            McuButton::Cycle => {
+                self.cycling = !self.cycling;
+                let (start, end) = self.cycle;
+                Some(SchedulerCommand::SetLoop {
+                    enabled: self.cycling,
+                    start,
+                    end,
+                })
+            }
+            McuButton::BankLeft
+            | McuButton::BankRight
+            | McuButton::ChannelLeft
+            | McuButton::ChannelRight => {
+                self.shift_bank(button);
+                None
+            }
+            McuButton::Mute(strip) | McuButton::Solo(strip) | McuButton::RecArm(strip) => {
+                let track = self.strip_track_mut(strip)?;
+                let change = match button {
+                    McuButton::Mute(_) => {
+                        track.muted = !track.muted;
+                        ChannelChange::SetMute(track.muted)
+                    }
+                    McuButton::Solo(_) => {
+                        track.soloed = !track.soloed;
+                        ChannelChange::SetSolo(track.soloed)
+                    }
+                    _ => {
+                        track.armed = !track.armed;
+                        ChannelChange::SetArmed(track.armed)
+                    }
+                };
+                Some(SchedulerCommand::ChannelChange {
+                    target_id: track.id.clone(),
+                    change,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn shift_bank(&mut self, button: McuButton) {
+        let by = match button {
+            McuButton::BankLeft => -(MCU_STRIPS as isize),
+            McuButton::BankRight => MCU_STRIPS as isize,
+            McuButton::ChannelLeft => -1,
+            _ => 1,
+        };
+        let max_offset = self.tracks.len().saturating_sub(1);
+        self.bank_offset = self.bank_offset.saturating_add_signed(by).min(max_offset);
+    }
+
+    fn strip_track(&self, strip: u8) -> Option<&SurfaceTrack> {
+        if usize::from(strip) >= MCU_STRIPS {
+            return None;
+        }
+        self.tracks.get(self.bank_offset + usize::from(strip))
+    }
+
+    fn strip_track_mut(&mut self, strip: u8) -> Option<&mut SurfaceTrack> {
+        if usize::from(strip) >= MCU_STRIPS {
+            return None;
+        }
+        self.tracks.get_mut(self.bank_offset + usize::from(strip))
+    }
+}
+
+impl SurfaceTrack {
+    /// Whether the strip's Mute, Solo or Rec LED is on
+    fn button_lit(&self, button: McuButton) -> bool {
+        match button {
+            McuButton::Mute(_) => self.muted,
+            McuButton::Solo(_) => self.soloed,
+            McuButton::RecArm(_) => self.armed,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fader_pitch_bend() {
+        let message = McuMessage::parse(&[0xE2, 0x7F, 0x7F]);
+        assert_eq!(
+            message,
+            Some(McuMessage::Fader {
+                strip: 2,
+                value: 0x3FFF
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_vpot_direction() {
+        assert_eq!(
+            McuMessage::parse(&[0xB0, 0x11, 0x03]),
+            Some(McuMessage::VPot { strip: 1, delta: 3 })
+        );
+        assert_eq!(
+            McuMessage::parse(&[0xB0, 0x11, 0x42]),
+            Some(McuMessage::VPot {
+                strip: 1,
+                delta: -2
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_buttons() {
+        assert_eq!(
+            McuMessage::parse(&[0x90, 0x5E, 0x7F]),
+            Some(McuMessage::ButtonPressed(McuButton::Play))
+        );
+        assert_eq!(
+            McuMessage::parse(&[0x90, 0x12, 0x00]),
+            Some(McuMessage::ButtonReleased(McuButton::Mute(2)))
+        );
+        assert_eq!(McuMessage::parse(&[0x90, 0x7A, 0x7F]), None);
+    }
+
+    #[test]
+    fn test_scribble_strip_sysex_layout() {
+        let message = scribble_strip_message(1, 1, "Vocals Lead");
+        assert_eq!(&message[..6], &[0xF0, 0x00, 0x00, 0x66, 0x14, 0x12]);
+        assert_eq!(message[6], 56 + 7); // bottom row, second strip
+        assert_eq!(&message[7..14], b"Vocals ");
+        assert_eq!(message[14], 0xF7);
+    }
+
+    #[test]
+    fn test_fader_message_round_trips() {
+        let bytes = fader_message(3, 1.0);
+        assert_eq!(
+            McuMessage::parse(&bytes),
+            Some(McuMessage::Fader {
+                strip: 3,
+                value: 0x3FFF
+            })
+        );
+    }
+
+    #[test]
+    fn test_fader_moves_mapped_track_gain() {
+        let mut surface = McuSurface::new(&["kick", "snare"]);
+        let cmd = surface.handle(McuMessage::Fader {
+            strip: 1,
+            value: 0x3FFF,
+        });
+
+        assert!(matches!(
+            cmd,
+            Some(SchedulerCommand::ParamChange {
+                target_id,
+                change: ParameterChange::SetGain(gain),
+            }) if target_id == "snare" && gain == 1.0
+        ));
+    }
+
+    #[test]
+    fn test_unmapped_strip_is_ignored() {
+        let mut surface = McuSurface::new(&["kick"]);
+        let cmd = surface.handle(McuMessage::Fader {
+            strip: 5,
+            value: 100,
+        });
+        assert!(cmd.is_none());
+    }
+
+    #[test]
+    fn test_vpot_accumulates_pan() {
+        let mut surface = McuSurface::new(&["kick"]);
+        surface.handle(McuMessage::VPot {
+            strip: 0,
+            delta: 10,
+        });
+        let cmd = surface.handle(McuMessage::VPot { strip: 0, delta: 5 });
+
+        assert!(matches!(
+            cmd,
+            Some(SchedulerCommand::ParamChange {
+                change: ParameterChange::SetPan(pan),
+                ..
+            }) if (pan - 0.3).abs() < 1e-6
+        ));
+    }
+
+    #[test]
+    fn test_bank_buttons_shift_strip_mapping() {
+        let ids: Vec<String> = (0..10).map(|i| format!("track-{i}")).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let mut surface = McuSurface::new(&ids);
+
+        surface.handle(McuMessage::ButtonPressed(McuButton::BankRight));
+        assert_eq!(surface.track_for_strip(0), Some("track-8"));
+        assert_eq!(surface.track_for_strip(2), None);
+
+        surface.handle(McuMessage::ButtonPressed(McuButton::ChannelLeft));
+        assert_eq!(surface.track_for_strip(0), Some("track-7"));
+
+        surface.handle(McuMessage::ButtonPressed(McuButton::BankLeft));
+        assert_eq!(surface.bank_offset(), 0);
+    }
+
+    #[test]
+    fn test_master_fader_sets_the_monitor_level() {
+        let mut surface = McuSurface::new(&["kick"]);
+        let master = McuMessage::Fader {
+            strip: MCU_MASTER_STRIP,
+            value: 0x3FFF / 2,
+        };
+        let cmd = surface.handle(master);
+
+        assert!(matches!(
+            cmd,
+            Some(SchedulerCommand::Monitor(MonitorChange::SetLevel(level)))
+                if (level + 6.02).abs() < 0.01
+        ));
+        assert_eq!(surface.echo(master), Some([0xE8, 0x7F, 0x3F]));
+    }
+
+    #[test]
+    fn test_fader_moves_are_echoed() {
+        let mut surface = McuSurface::new(&["kick"]);
+        let fader = McuMessage::Fader {
+            strip: 0,
+            value: 0x2000,
+        };
+        surface.handle(fader);
+        assert_eq!(surface.echo(fader), Some([0xE0, 0x00, 0x40]));
+        assert_eq!(
+            surface.echo(McuMessage::Fader {
+                strip: 1,
+                value: 0x2000
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_strip_buttons_toggle_the_channel() {
+        let mut surface = McuSurface::new(&["kick", "snare"]);
+        let mute = McuMessage::ButtonPressed(McuButton::Mute(1));
+        assert!(matches!(
+            surface.handle(mute),
+            Some(SchedulerCommand::ChannelChange {
+                target_id,
+                change: ChannelChange::SetMute(true),
+            }) if target_id == "snare"
+        ));
+        assert_eq!(surface.echo(mute), Some([0x90, 0x11, 0x7F]));
+        assert!(matches!(
+            surface.handle(mute),
+            Some(SchedulerCommand::ChannelChange {
+                change: ChannelChange::SetMute(false),
+                ..
+            })
+        ));
+        assert_eq!(surface.echo(mute), Some([0x90, 0x11, 0x00]));
+
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::Solo(0))),
+            Some(SchedulerCommand::ChannelChange {
+                change: ChannelChange::SetSolo(true),
+                ..
+            })
+        ));
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::RecArm(0))),
+            Some(SchedulerCommand::ChannelChange {
+                change: ChannelChange::SetArmed(true),
+                ..
+            })
+        ));
+        let leds = surface.feedback_messages();
+        assert!(leds.contains(&vec![0x90, 0x08, 0x7F]));
+        assert!(leds.contains(&vec![0x90, 0x00, 0x7F]));
+        assert!(leds.contains(&vec![0x90, 0x10, 0x00]));
+    }
+
+    #[test]
+    fn test_record_switches_recording_at_the_playhead() {
+        let mut surface = McuSurface::new(&[]);
+        surface.playhead().store(960, Ordering::Relaxed);
+        let record = McuMessage::ButtonPressed(McuButton::Record);
+        assert!(matches!(
+            surface.handle(record),
+            Some(SchedulerCommand::SetRecordEnable {
+                tick: 960,
+                enabled: true
+            })
+        ));
+        assert_eq!(surface.echo(record), Some([0x90, 0x5F, 0x7F]));
+        assert!(matches!(
+            surface.handle(record),
+            Some(SchedulerCommand::SetRecordEnable { enabled: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_cycle_toggles_the_loop() {
+        let mut surface = McuSurface::new(&[]);
+        let (start, end) = (
+            LoopOptions {
+                bar: 2,
+                beat: 1,
+                tick: 1,
+            },
+            LoopOptions {
+                bar: 4,
+                beat: 1,
+                tick: 1,
+            },
+        );
+        surface.set_cycle_range(start, end);
+        let cycle = McuMessage::ButtonPressed(McuButton::Cycle);
+        assert!(matches!(
+            surface.handle(cycle),
+            Some(SchedulerCommand::SetLoop { enabled: true, start: s, end: e }) if s == start && e == end
+        ));
+        assert_eq!(surface.echo(cycle), Some([0x90, 0x56, 0x7F]));
+        assert!(matches!(
+            surface.handle(cycle),
+            Some(SchedulerCommand::SetLoop { enabled: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_rewind_shuttles_back_while_held() {
+        let mut surface = McuSurface::new(&[]);
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::Rewind)),
+            Some(SchedulerCommand::Scrub(ScrubChange::Audition(Some(speed)))) if speed == -SHUTTLE_SPEED
+        ));
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonReleased(McuButton::Rewind)),
+            Some(SchedulerCommand::Scrub(ScrubChange::Stop))
+        ));
+    }
+
+    #[test]
+    fn test_fast_forward_shuttles_on_while_held() {
+        let mut surface = McuSurface::new(&[]);
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::FastForward)),
+            Some(SchedulerCommand::Scrub(ScrubChange::Audition(Some(speed)))) if speed == SHUTTLE_SPEED
+        ));
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonReleased(McuButton::FastForward)),
+            Some(SchedulerCommand::Scrub(ScrubChange::Stop))
+        ));
+    }
+
+    #[test]
+    fn test_transport_buttons() {
+        let mut surface = McuSurface::new(&[]);
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::Play)),
+            Some(SchedulerCommand::Play)
+        ));
+        assert!(matches!(
+            surface.handle(McuMessage::ButtonPressed(McuButton::Stop)),
+            Some(SchedulerCommand::Stop)
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+use midir::{MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use rtrb::Producer;
+
+use crate::{
+    control_surface::mcu::{McuMessage, McuSurface},
+    error::DeviceError,
+    scheduler::command::SchedulerCommand,
+};
+
+const CLIENT_NAME: &str = "FreqForm";
+
+/// A live MCU surface connection. Incoming messages are translated on midir's
+/// input thread and pushed onto the scheduler command queue, fader moves and button
+/// states are echoed back to the surface.
+pub struct McuConnection {
+    _input: MidiInputConnection<()>,
+}
+
+impl McuConnection {
+    /// Connects to the first MIDI input/output port pair whose name contains `port_name`
+    pub fn connect(
+        port_name: &str,
+        mut surface: McuSurface,
+        mut commands: Producer<SchedulerCommand>,
+    ) -> Result<Self, DeviceError> {
+        let mut output = Self::connect_output(port_name)?;
+        let midi_in = MidiInput::new(CLIENT_NAME)
+            .map_err(|e| DeviceError::Midi(format!("Failed to open MIDI input: {e}")))?;
+
+        let in_port = midi_in
+            .ports()
+            .into_iter()
+            .find(|port| {
+                midi_in
+                    .port_name(port)
+                    .is_ok_and(|name| name.contains(port_name))
+            })
+            .ok_or_else(|| {
+                DeviceError::Midi(format!("No MIDI input port matching '{port_name}'"))
+            })?;
+
+        Self::send_feedback(&mut output, &surface);
+
+        let input = midi_in
+            .connect(
+                &in_port,
+                "freqform-mcu-in",
+                move |_, bytes, ()| {
+                    let Some(message) = McuMessage::parse(bytes) else {
+                        return;
+                    };
+
+                    let bank_before = surface.bank_offset();
+                    if let Some(cmd) = surface.handle(message) {
+                        // surface input is best effort, drop on a full queue
+                        let _ = commands.push(cmd);
+                    }
+
+                    if surface.bank_offset() != bank_before {
+                        Self::send_feedback(&mut output, &surface);
+                    } else if let Some(echo) = surface.echo(message)
+                        && let Err(e) = output.send(&echo)
+                    {
+                        tracing::warn!(target: "freqform::mcu", error = %e, "MCU feedback not sent");
+                    }
+                },
+                (),
+            )
+            .map_err(|e| DeviceError::Midi(format!("Failed to connect MIDI input: {e}")))?;
+
+        Ok(Self { _input: input })
+    }
+
+    fn connect_output(port_name: &str) -> Result<MidiOutputConnection, DeviceError> {
+        let midi_out = MidiOutput::new(CLIENT_NAME)
+            .map_err(|e| DeviceError::Midi(format!("Failed to open MIDI output: {e}")))?;
+
+        let out_port = midi_out
+            .ports()
+            .into_iter()
+            .find(|port| {
+                midi_out
+                    .port_name(port)
+                    .is_ok_and(|name| name.contains(port_name))
+            })
+            .ok_or_else(|| {
+                DeviceError::Midi(format!("No MIDI output port matching '{port_name}'"))
+            })?;
+
+        midi_out
+            .connect(&out_port, "freqform-mcu-out")
+            .map_err(|e| DeviceError::Midi(format!("Failed to connect MIDI output: {e}")))
+    }
+
+    fn send_feedback(output: &mut MidiOutputConnection, surface: &McuSurface) {
+        for message in surface.feedback_messages() {
+            if let Err(e) = output.send(&message) {
+                tracing::warn!(target: "freqform::mcu", error = %e, "MCU feedback not sent");
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod mcu;
+#[cfg(all(feature = "mcu", not(target_arch = "wasm32")))]
+pub mod midi_io;
@@ -0,0 +1,528 @@
+//! Importing edit decisions made elsewhere: marker and region lists as CSV, and simple
+//! CMX3600 EDLs from picture editors.
+//!
+//! A CSV cue list starts with a header naming its columns, in any order and case:
+//!
+//! | Column | Also called | |
+//! |---|---|---|
+//! | `start` | `position`, `time`, `in` | required |
+//! | `name` | `marker name`, `title`, `label` | |
+//! | `end` | `out` | makes the cue a region |
+//! | `length` | `duration` | makes the cue a region, if there's no `end` |
+//! | `file` | `source` | audio to place as a clip over the region |
+//! | `source in` | `file start`, `offset` | where in the file the clip starts |
+//!
+//! Other columns are ignored. Times are SMPTE timecode (`01:00:10:12`, counted on the
+//! import's [`TimecodeGrid`]), minutes and seconds (`1:02.5`, `0:01:02.5`) or seconds
+//! (`62.5`), the latter two from the start of the timeline.
+//!
+//! An EDL's events become regions spanning their record in and out points, named and
+//! sourced from the `* FROM CLIP NAME:` comment after them or else their reel, with the
+//! source in point taken as counted from the start of that file. Black (`BL`) events are
+//! gaps and skipped.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use transport::{
+    clock::TempoClock, display::Timecode, markers::MarkerList, timecode::TimecodeGrid,
+};
+
+use crate::{
+    buffer::AudioBuffer,
+    error::CueListError,
+    scheduler::command::MarkerChange,
+    track::{
+        timeline::{Clip, TimelineTrack},
+        wav::WavTrack,
+    },
+};
+
+/// One marker, region or clip placement, in samples
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+    pub name: String,
+    pub start: u64,
+    /// End of the region, `None` for a marker
+    pub end: Option<u64>,
+    /// Audio to place as a clip over the region
+    pub source: Option<CueSource>,
+}
+
+/// Where a cue's clip audio comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueSource {
+    /// Relative to the directory given to [`CueList::add_clips`]
+    pub file: PathBuf,
+    /// Sample of the file the clip starts at
+    pub offset: u64,
+}
+
+/// Cues read from a CSV or EDL file, see the [module docs](self) for the formats
+///
+/// # Example
+/// ```
+/// use audio_engine::cue_list::CueList;
+/// use transport::{display::FrameRate, timecode::TimecodeGrid};
+///
+/// let grid = TimecodeGrid::new(FrameRate::Fps25, 48000.0);
+/// let cues = CueList::from_csv("Name,Start,End\nIntro,0,12.5\nAd break,0:30\n", &grid).unwrap();
+/// assert_eq!(cues.cues[0].end, Some(600_000));
+/// assert_eq!(cues.cues[1].start, 1_440_000);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CueList {
+    pub cues: Vec<Cue>,
+}
+
+impl CueList {
+    /// Reads the cue list at `path`, as an EDL if its extension is `.edl` and as CSV
+    /// otherwise
+    ///
+    /// # Errors
+    /// [`CueListError::Io`] if the file can't be read, otherwise as for
+    /// [`CueList::from_csv`] and [`CueList::from_edl`].
+    pub fn load<P: AsRef<Path>>(path: P, grid: &TimecodeGrid) -> Result<Self, CueListError> {
+        let path = path.as_ref();
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let text = std::fs::read_to_string(path).map_err(|source| CueListError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let is_edl = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("edl"));
+        if is_edl {
+            Self::from_edl(&text, grid)
+        } else {
+            Self::from_csv(&text, grid)
+        }
+    }
+
+    /// Reads a CSV cue list, timecodes are counted on `grid`
+    ///
+    /// # Errors
+    /// [`CueListError::MissingColumn`] without a `start` column, [`CueListError::Field`]
+    /// for times that don't parse and regions that end before they start.
+    pub fn from_csv(text: &str, grid: &TimecodeGrid) -> Result<Self, CueListError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let Some((_, header)) = lines.next() else {
+            return Ok(Self::default());
+        };
+        let header = csv_fields(header);
+        let column = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| names.contains(&column.to_ascii_lowercase().as_str()))
+        };
+        let start = column(&["start", "position", "time", "in"])
+            .ok_or(CueListError::MissingColumn("start"))?;
+        let name = column(&["name", "marker name", "title", "label"]);
+        let end = column(&["end", "out"]);
+        let length = column(&["length", "duration"]);
+        let file = column(&["file", "source"]);
+        let source_in = column(&["source in", "file start", "offset"]);
+
+        let mut cues = Vec::new();
+        for (index, line) in lines {
+            let fields = csv_fields(line);
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| fields.get(column))
+                    .map(String::as_str)
+                    .filter(|value| !value.is_empty())
+            };
+            let time = |column, name| {
+                field(column)
+                    .map(|value| {
+                        parse_time(value, grid).ok_or_else(|| CueListError::Field {
+                            line: index + 1,
+                            field: name,
+                            value: value.to_owned(),
+                        })
+                    })
+                    .transpose()
+            };
+            let start_frame = time(Some(start), "start")?.ok_or_else(|| CueListError::Field {
+                line: index + 1,
+                field: "start",
+                value: String::new(),
+            })?;
+            let end_frame = match (time(end, "end")?, time(length, "length")?) {
+                (Some(end), _) => Some(end),
+                (None, Some(length)) => Some(start_frame + length),
+                (None, None) => None,
+            };
+            let cue = Cue {
+                name: field(name).map_or_else(|| format!("Cue {}", cues.len() + 1), str::to_owned),
+                start: start_frame,
+                end: end_frame,
+                source: field(file).map(|file| CueSource {
+                    file: PathBuf::from(file),
+                    offset: 0,
+                }),
+            };
+            cues.push(check(cue, index + 1, time(source_in, "source in")?)?);
+        }
+        Ok(Self { cues })
+    }
+
+    /// Reads a CMX3600 EDL, record timecodes are counted on `grid`
+    ///
+    /// # Errors
+    /// [`CueListError::Field`] for events whose timecodes don't parse or end before they
+    /// start.
+    pub fn from_edl(text: &str, grid: &TimecodeGrid) -> Result<Self, CueListError> {
+        let source_grid = TimecodeGrid::new(grid.rate(), grid.sample_rate());
+        let mut cues: Vec<Cue> = Vec::new();
+        // whether the last event was kept, so its comments apply to the last cue
+        let mut kept = false;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if let Some(clip) = line.strip_prefix("* FROM CLIP NAME:") {
+                if let Some(cue) = cues.last_mut().filter(|_| kept) {
+                    let clip = clip.trim();
+                    clip.clone_into(&mut cue.name);
+                    if let Some(source) = cue.source.as_mut() {
+                        source.file = PathBuf::from(clip);
+                    }
+                }
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            // event number, reel, track, transition [, duration], source in and out,
+            // record in and out
+            let is_event = words.len() >= 8 && words[0].bytes().all(|byte| byte.is_ascii_digit());
+            if !is_event {
+                continue;
+            }
+            let reel = words[1];
+            let timecodes = &words[words.len() - 4..];
+            let timecode = |text: &str, grid: &TimecodeGrid| {
+                parse_timecode(text, grid).ok_or_else(|| CueListError::Field {
+                    line: index + 1,
+                    field: "timecode",
+                    value: text.to_owned(),
+                })
+            };
+            let offset = timecode(timecodes[0], &source_grid)?;
+            let start = timecode(timecodes[2], grid)?;
+            let end = timecode(timecodes[3], grid)?;
+            kept = !reel.eq_ignore_ascii_case("BL");
+            if !kept {
+                continue;
+            }
+            // an `AX` reel is an auxiliary source, only the clip name comment says which
+            let file = if reel.eq_ignore_ascii_case("AX") {
+                PathBuf::new()
+            } else {
+                PathBuf::from(reel)
+            };
+            let cue = Cue {
+                name: reel.to_owned(),
+                start,
+                end: Some(end),
+                source: Some(CueSource { file, offset }),
+            };
+            cues.push(check(cue, index + 1, None)?);
+        }
+        for cue in &mut cues {
+            cue.source
+                .take_if(|source| source.file.as_os_str().is_empty());
+        }
+        Ok(Self { cues })
+    }
+
+    /// Commands adding the cues as markers and regions to a running scheduler, at the
+    /// tempo of `clock`
+    #[must_use]
+    pub fn marker_changes(&self, clock: &TempoClock) -> Vec<MarkerChange> {
+        self.cues
+            .iter()
+            .map(|cue| {
+                cue.end.map_or_else(
+                    || MarkerChange::AddMarker {
+                        name: cue.name.clone(),
+                        tick: tick_of(cue.start, clock),
+                    },
+                    |end| MarkerChange::AddRegion {
+                        name: cue.name.clone(),
+                        start: tick_of(cue.start, clock),
+                        end: tick_of(end, clock),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Adds the cues to `markers` as markers and regions, at the tempo of `clock`
+    pub fn add_to(&self, markers: &mut MarkerList, clock: &TempoClock) {
+        for cue in &self.cues {
+            match cue.end {
+                Some(end) => {
+                    markers.add_region(&cue.name, tick_of(cue.start, clock), tick_of(end, clock));
+                }
+                None => markers.add_marker(&cue.name, tick_of(cue.start, clock)),
+            }
+        }
+    }
+
+    /// Places a clip on `track` for every region with a source, loading the files
+    /// relative to `directory`. Returns how many clips were placed.
+    ///
+    /// # Errors
+    /// [`CueListError::Media`] if a file can't be loaded, clips placed before it stay.
+    pub fn add_clips(
+        &self,
+        track: &mut TimelineTrack,
+        directory: &Path,
+    ) -> Result<usize, CueListError> {
+        let mut sources: HashMap<&Path, Arc<AudioBuffer>> = HashMap::new();
+        let mut placed = 0;
+        for cue in &self.cues {
+            let (Some(source), Some(end)) = (&cue.source, cue.end) else {
+                continue;
+            };
+            let audio = if let Some(audio) = sources.get(source.file.as_path()) {
+                Arc::clone(audio)
+            } else {
+                let audio = Arc::new(WavTrack::from_file(directory.join(&source.file))?.samples);
+                sources.insert(&source.file, Arc::clone(&audio));
+                audio
+            };
+            track.add_clip(Clip::excerpt(
+                cue.start as usize,
+                audio,
+                source.offset as usize,
+                (end - cue.start) as usize,
+            ));
+            placed += 1;
+        }
+        Ok(placed)
+    }
+}
+
+/// `cue` with its source offset set, unless it's a region ending before it starts
+fn check(mut cue: Cue, line: usize, offset: Option<u64>) -> Result<Cue, CueListError> {
+    if let Some(end) = cue.end.filter(|&end| end <= cue.start) {
+        return Err(CueListError::Field {
+            line,
+            field: "end",
+            value: end.to_string(),
+        });
+    }
+    if let (Some(source), Some(offset)) = (cue.source.as_mut(), offset) {
+        source.offset = offset;
+    }
+    Ok(cue)
+}
+
+/// Tick nearest sample `frame` at the tempo of `clock`
+fn tick_of(frame: u64, clock: &TempoClock) -> u64 {
+    (frame as f64 / clock.samples_per_tick()).round() as u64
+}
+
+/// A line's comma separated fields, trimmed, with double quotes around fields that
+/// contain commas and `""` for a quote inside them
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_owned()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_owned());
+    fields
+}
+
+/// Sample at a timecode, minutes and seconds or seconds, see the [module docs](self)
+fn parse_time(text: &str, grid: &TimecodeGrid) -> Option<u64> {
+    if text.contains(';') || text.matches(':').count() == 3 {
+        return parse_timecode(text, grid);
+    }
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        seconds = seconds * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    (seconds.is_finite() && seconds >= 0.0).then(|| (seconds * grid.sample_rate()).round() as u64)
+}
+
+/// Sample at `hh:mm:ss:ff` or `hh:mm:ss;ff` on `grid`
+fn parse_timecode(text: &str, grid: &TimecodeGrid) -> Option<u64> {
+    let parts: Vec<u64> = text
+        .split([':', ';'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds, frames] = parts[..] else {
+        return None;
+    };
+    if minutes >= 60 || seconds >= 60 || frames >= grid.rate().nominal() {
+        return None;
+    }
+    grid.frame_of(Timecode {
+        hours,
+        minutes,
+        seconds,
+        frames,
+        rate: grid.rate(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use transport::{display::FrameRate, resolution::TickResolution};
+
+    use super::*;
+
+    fn grid() -> TimecodeGrid {
+        TimecodeGrid::new(FrameRate::Fps25, 48000.0)
+    }
+
+    #[test]
+    fn test_csv_markers_and_regions() {
+        let csv = "#,Name,Start,Length,Color\n\
+                   1,\"Intro, cold open\",00:00:01:00,2.5,red\n\
+                   \n\
+                   2,\"Guest says \"\"hi\"\"\",1:00,,blue\n";
+        let cues = CueList::from_csv(csv, &grid()).unwrap();
+
+        assert_eq!(
+            cues.cues,
+            [
+                Cue {
+                    name: "Intro, cold open".into(),
+                    start: 48000,
+                    end: Some(168_000),
+                    source: None,
+                },
+                Cue {
+                    name: "Guest says \"hi\"".into(),
+                    start: 2_880_000,
+                    end: None,
+                    source: None,
+                },
+            ]
+        );
+
+        let clock = TempoClock::new(120.0, 48000.0, TickResolution::Quarter);
+        let mut markers = MarkerList::default();
+        cues.add_to(&mut markers, &clock);
+        assert_eq!(markers.region("Intro, cold open").unwrap().start, 960);
+        assert_eq!(markers.markers().len(), 1);
+        assert!(matches!(
+            cues.marker_changes(&clock)[1],
+            MarkerChange::AddMarker { tick: 57600, .. }
+        ));
+    }
+
+    #[test]
+    fn test_csv_errors_name_the_line() {
+        assert!(matches!(
+            CueList::from_csv("Name,End\nA,1\n", &grid()),
+            Err(CueListError::MissingColumn("start"))
+        ));
+        assert!(matches!(
+            CueList::from_csv("Name,Start\nA,1\nB,soon\n", &grid()),
+            Err(CueListError::Field {
+                line: 3,
+                field: "start",
+                ..
+            })
+        ));
+        assert!(matches!(
+            CueList::from_csv("Start,End\n2,1\n", &grid()),
+            Err(CueListError::Field {
+                line: 2,
+                field: "end",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_edl_events_become_sourced_regions() {
+        let edl = "TITLE: EPISODE 1\n\
+                   FCM: NON-DROP FRAME\n\
+                   \n\
+                   001  AX       AA     C        00:00:10:00 00:00:12:00 01:00:00:00 01:00:02:00\n\
+                   * FROM CLIP NAME: interview.wav\n\
+                   002  BL       AA     C        00:00:00:00 00:00:01:00 01:00:02:00 01:00:03:00\n\
+                   003  MUSIC    AA     D    025 00:00:00:00 00:00:04:00 01:00:03:00 01:00:07:00\n";
+        let start = Timecode {
+            hours: 1,
+            minutes: 0,
+            seconds: 0,
+            frames: 0,
+            rate: FrameRate::Fps25,
+        };
+        let cues = CueList::from_edl(edl, &grid().with_start(start)).unwrap();
+
+        assert_eq!(
+            cues.cues,
+            [
+                Cue {
+                    name: "interview.wav".into(),
+                    start: 0,
+                    end: Some(96000),
+                    source: Some(CueSource {
+                        file: "interview.wav".into(),
+                        offset: 480_000,
+                    }),
+                },
+                Cue {
+                    name: "MUSIC".into(),
+                    start: 144_000,
+                    end: Some(336_000),
+                    source: Some(CueSource {
+                        file: "MUSIC".into(),
+                        offset: 0,
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sourced_cues_place_clips() {
+        let directory = std::env::temp_dir().join("freqform-cue-list-test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(directory.join("take.wav"), spec).unwrap();
+        for sample in 0..1000 {
+            writer.write_sample(sample as f32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let grid = TimecodeGrid::new(FrameRate::Fps25, 1000.0);
+        let csv = "Name,Start,End,File,Source In\nA,1,1.2,take.wav,0.5\nMarker,3\n";
+        let cues = CueList::from_csv(csv, &grid).unwrap();
+        let mut track = TimelineTrack::new("dialogue");
+        assert_eq!(cues.add_clips(&mut track, &directory).unwrap(), 1);
+
+        let clip = &track.clips()[0];
+        assert_eq!((clip.start, clip.end()), (1000, 1200));
+        assert_eq!(clip.audio().channel(0)[0], 500.0);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
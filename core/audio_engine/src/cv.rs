@@ -0,0 +1,229 @@
+//! Control voltage outputs: automation lanes and LFOs rendered as audio-rate signals on
+//! device channels of their own, so a DC-coupled interface can drive modular gear, see
+//! [`CvOutput`].
+//!
+//! The signals are rendered with the timeline and played next to the control room speakers,
+//! like the cue output, see [`Scheduler::set_cv_outputs`].
+//!
+//! [`Scheduler::set_cv_outputs`]: crate::scheduler::Scheduler::set_cv_outputs
+
+use std::f64::consts::TAU;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use serde::{Deserialize, Serialize};
+
+use crate::{automation::AutomatedParameter, constants::MAX_BLOCK_FRAMES, mixer::Mixer};
+
+/// Frames of CV held between rendering and the device callback, a few blocks
+const CV_RING_FRAMES: usize = MAX_BLOCK_FRAMES * 4;
+
+/// Waveform of an LFO, from -1 to +1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    /// Rising ramp
+    Saw,
+    Square,
+}
+
+impl LfoShape {
+    /// Value at `phase`, in cycles from 0 to 1
+    #[must_use]
+    pub fn value(self, phase: f64) -> f32 {
+        let value = match self {
+            Self::Sine => (phase * TAU).sin(),
+            Self::Triangle => 4.0f64.mul_add(-((phase + 0.25).fract() - 0.5).abs(), 1.0),
+            Self::Saw => 2.0f64.mul_add(phase, -1.0),
+            Self::Square if phase < 0.5 => 1.0,
+            Self::Square => -1.0,
+        };
+        value as f32
+    }
+}
+
+/// What a CV output plays
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CvSource {
+    /// A channel's parameter, its automation lane at audio rate while that plays back and
+    /// the parameter as it's set otherwise. Holds its last value once the track is gone.
+    Automation {
+        track: String,
+        parameter: AutomatedParameter,
+    },
+    /// A free-running LFO of `rate` Hz
+    Lfo { shape: LfoShape, rate: f32 },
+}
+
+/// One CV signal and the device channel it's played on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CvOutput {
+    pub source: CvSource,
+    /// Device channel, 0-based
+    pub channel: usize,
+    /// The signal is `value * scale + offset`, clamped to full scale. What full scale is in
+    /// volts depends on the interface.
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl CvOutput {
+    /// Plays `source` unscaled on device channel `channel`
+    #[must_use]
+    pub const fn new(source: CvSource, channel: usize) -> Self {
+        Self {
+            source,
+            channel,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    /// Output sample for a source `value`
+    #[must_use]
+    pub fn sample(&self, value: f32) -> f32 {
+        value.mul_add(self.scale, self.offset).clamp(-1.0, 1.0)
+    }
+}
+
+/// A CV output with its LFO phase and the ring between rendering and the device
+struct CvChannel {
+    output: CvOutput,
+    /// In cycles
+    phase: f64,
+    /// Last sample played, held if the ring runs dry so the voltage never jumps to 0
+    last: f32,
+    rendered: Producer<f32>,
+    played: Consumer<f32>,
+}
+
+/// Renders the CV outputs of a scheduler and plays them on their device channels
+pub(crate) struct CvOutputs {
+    channels: Vec<CvChannel>,
+    sample_rate: f64,
+}
+
+impl CvOutputs {
+    pub(crate) fn new(outputs: Vec<CvOutput>, sample_rate: f64) -> Self {
+        let channels = outputs
+            .into_iter()
+            .map(|output| {
+                let (rendered, played) = RingBuffer::new(CV_RING_FRAMES);
+                let last = output.sample(0.0);
+                CvChannel {
+                    output,
+                    phase: 0.0,
+                    last,
+                    rendered,
+                    played,
+                }
+            })
+            .collect();
+        Self {
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub(crate) fn outputs(&self) -> impl Iterator<Item = &CvOutput> {
+        self.channels.iter().map(|channel| &channel.output)
+    }
+
+    /// Renders a block of `frames` at timeline `frame`. Automation is only played back
+    /// while `rolling`.
+    pub(crate) fn render(&mut self, mixer: &Mixer, frame: u64, frames: usize, rolling: bool) {
+        for channel in &mut self.channels {
+            match &channel.output.source {
+                CvSource::Automation { track, parameter } => {
+                    let Some(source) = mixer.channel(track) else {
+                        for _ in 0..frames {
+                            let _ = channel.rendered.push(channel.last);
+                        }
+                        continue;
+                    };
+                    let lane = source
+                        .automation()
+                        .iter()
+                        .find(|lane| lane.parameter() == *parameter)
+                        .filter(|lane| rolling && lane.is_playing_back());
+                    let current = match parameter {
+                        AutomatedParameter::Gain => source.gain(),
+                        AutomatedParameter::Pan => source.pan(),
+                    };
+                    for i in 0..frames {
+                        let value = lane
+                            .and_then(|lane| lane.value_at(frame + i as u64))
+                            .unwrap_or(current);
+                        let _ = channel.rendered.push(channel.output.sample(value));
+                    }
+                }
+                &CvSource::Lfo { shape, rate } => {
+                    let step = f64::from(rate) / self.sample_rate;
+                    for _ in 0..frames {
+                        let value = shape.value(channel.phase);
+                        let _ = channel.rendered.push(channel.output.sample(value));
+                        channel.phase = (channel.phase + step).rem_euclid(1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the rendered CV into its channels of an interleaved device buffer of
+    /// `channels` channels. Outputs past the device's channels are skipped.
+    pub(crate) fn write_interleaved<T: dasp_sample::FromSample<f32>>(
+        &mut self,
+        data: &mut [T],
+        channels: usize,
+    ) {
+        for channel in &mut self.channels {
+            for frame in data.chunks_mut(channels) {
+                channel.last = channel.played.pop().unwrap_or(channel.last);
+                if let Some(sample) = frame.get_mut(channel.output.channel) {
+                    *sample = T::from_sample_(channel.last);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfo_shapes() {
+        let quarters = |shape: LfoShape| -> Vec<f32> {
+            (0..4)
+                .map(|i| (shape.value(f64::from(i) / 4.0) * 1e4).round() / 1e4)
+                .collect()
+        };
+        assert_eq!(quarters(LfoShape::Sine), [0.0, 1.0, 0.0, -1.0]);
+        assert_eq!(quarters(LfoShape::Triangle), [0.0, 1.0, 0.0, -1.0]);
+        assert_eq!(quarters(LfoShape::Saw), [-1.0, -0.5, 0.0, 0.5]);
+        assert_eq!(quarters(LfoShape::Square), [1.0, 1.0, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_lfo_is_scaled_and_written_to_its_channel() {
+        let mut output = CvOutput::new(
+            CvSource::Lfo {
+                shape: LfoShape::Square,
+                rate: 1.0,
+            },
+            2,
+        );
+        output.scale = 0.5;
+        output.offset = 0.25;
+        let mut outputs = CvOutputs::new(vec![output], 4.0);
+        outputs.render(&Mixer::new(), 0, 4, true);
+
+        let mut data = [0.0f32; 3 * 5];
+        outputs.write_interleaved(&mut data, 3);
+        let cv: Vec<f32> = data.chunks(3).map(|frame| frame[2]).collect();
+        // the last value is held once the rendered frames run out
+        assert_eq!(cv, [0.75, 0.75, -0.25, -0.25, -0.25]);
+        assert!(data.chunks(3).all(|frame| frame[..2] == [0.0, 0.0]));
+    }
+}
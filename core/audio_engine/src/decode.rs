@@ -0,0 +1,320 @@
+//! Decoding audio files held in memory.
+//!
+//! [`decode`] is a pure function of its input, without I/O, so it's what fuzzers call.
+//! Malformed headers, truncated data and absurd channel counts or sample rates come back as
+//! [`DecodeError`]s, it never panics, and it allocates no more than the input and the
+//! [`DecodeLimits`] allow. Only `core` and `alloc` are needed.
+
+use crate::{buffer::AudioBuffer, error::DecodeError};
+
+/// What a file may ask for before decoding refuses it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub max_channels: u16,
+    pub max_sample_rate: u32,
+    /// Longest audio decoded, in frames
+    pub max_frames: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_channels: 64,
+            max_sample_rate: 768_000,
+            // over 12 hours at 48 kHz
+            max_frames: 1 << 31,
+        }
+    }
+}
+
+/// Audio as decoded, every channel of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedAudio {
+    pub samples: AudioBuffer,
+    /// Sample rate the file was recorded at, in Hz
+    pub sample_rate: u32,
+}
+
+/// Decodes the file in `bytes`, telling the format from its first bytes.
+///
+/// # Errors
+/// [`DecodeError::UnknownFormat`] for formats that can't be decoded, otherwise see
+/// [`decode_wav`].
+pub fn decode(bytes: &[u8], limits: &DecodeLimits) -> Result<DecodedAudio, DecodeError> {
+    match bytes.get(..4) {
+        Some(b"RIFF") => decode_wav(bytes, limits),
+        _ => Err(DecodeError::UnknownFormat),
+    }
+}
+
+/// `fmt ` codes of the sample encodings
+const PCM: u16 = 1;
+const IEEE_FLOAT: u16 = 3;
+/// `WAVE_FORMAT_EXTENSIBLE`, the actual encoding is in the sub format
+const EXTENSIBLE: u16 = 0xFFFE;
+
+/// Decodes a WAV file: 8, 16, 24 or 32-bit integer or 32 or 64-bit float samples.
+///
+/// A data chunk claiming more than the file holds, as unfinished recordings do, is decoded
+/// up to the last whole frame. Non-finite float samples decode as silence.
+///
+/// # Errors
+/// [`DecodeError::Malformed`] for files that aren't WAVs or whose headers are cut short or
+/// inconsistent, [`DecodeError::UnsupportedFormat`], [`DecodeError::UnsupportedChannels`]
+/// and [`DecodeError::UnsupportedSampleRate`] for what can't be decoded or is out of
+/// `limits`, [`DecodeError::TooLong`] for more frames than `limits` allow.
+pub fn decode_wav(bytes: &[u8], limits: &DecodeLimits) -> Result<DecodedAudio, DecodeError> {
+    if bytes.get(..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err(DecodeError::Malformed("not a RIFF WAVE file"));
+    }
+    let (mut format, mut data) = (None, None);
+    let mut at = 12usize;
+    while let Some(id) = at.checked_add(4).and_then(|end| bytes.get(at..end)) {
+        let size = read_u32(bytes, at + 4)
+            .ok_or(DecodeError::Malformed("chunk header cut short"))? as usize;
+        let body = at + 8;
+        let end = body.saturating_add(size).min(bytes.len());
+        match id {
+            b"fmt " => format = Some(WavFormat::parse(&bytes[body..end])?),
+            b"data" => data = Some(&bytes[body..end]),
+            _ => {}
+        }
+        // chunks are padded to an even size
+        at = body.saturating_add(size).saturating_add(size & 1);
+    }
+    let format = format.ok_or(DecodeError::Malformed("no fmt chunk"))?;
+    let data = data.ok_or(DecodeError::Malformed("no data chunk"))?;
+    format.decode(data, limits)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Int,
+    Float,
+}
+
+/// The `fmt ` chunk of a WAV file
+struct WavFormat {
+    encoding: Encoding,
+    channels: u16,
+    sample_rate: u32,
+    /// Bytes per frame
+    block_align: u16,
+    bits: u16,
+}
+
+impl WavFormat {
+    fn parse(chunk: &[u8]) -> Result<Self, DecodeError> {
+        let cut_short = || DecodeError::Malformed("fmt chunk cut short");
+        let field = |at| read_u16(chunk, at).ok_or_else(cut_short);
+        let channels = field(2)?;
+        let sample_rate = read_u32(chunk, 4).ok_or_else(cut_short)?;
+        let block_align = field(12)?;
+        let bits = field(14)?;
+        let mut tag = field(0)?;
+        if tag == EXTENSIBLE {
+            // the sub format GUID starts with the encoding's code
+            tag = field(24)?;
+        }
+        let encoding = match (tag, bits) {
+            (PCM, 8 | 16 | 24 | 32) => Encoding::Int,
+            (IEEE_FLOAT, 32 | 64) => Encoding::Float,
+            _ => return Err(DecodeError::UnsupportedFormat { format: tag, bits }),
+        };
+        Ok(Self {
+            encoding,
+            channels,
+            sample_rate,
+            block_align,
+            bits,
+        })
+    }
+
+    /// Decodes the whole frames of the `data` chunk
+    fn decode(&self, data: &[u8], limits: &DecodeLimits) -> Result<DecodedAudio, DecodeError> {
+        if self.channels == 0 || self.channels > limits.max_channels {
+            return Err(DecodeError::UnsupportedChannels(self.channels));
+        }
+        if self.sample_rate == 0 || self.sample_rate > limits.max_sample_rate {
+            return Err(DecodeError::UnsupportedSampleRate(self.sample_rate));
+        }
+        let sample_bytes = usize::from(self.bits / 8);
+        let frame_bytes = sample_bytes * usize::from(self.channels);
+        if usize::from(self.block_align) != frame_bytes {
+            return Err(DecodeError::Malformed(
+                "block align doesn't match the sample format",
+            ));
+        }
+        let frames = data.len() / frame_bytes;
+        if frames > limits.max_frames {
+            return Err(DecodeError::TooLong {
+                frames,
+                limit: limits.max_frames,
+            });
+        }
+
+        let mut samples = AudioBuffer::new(usize::from(self.channels), frames);
+        for (index, frame) in data.chunks_exact(frame_bytes).enumerate() {
+            for (channel, sample) in frame.chunks_exact(sample_bytes).enumerate() {
+                samples.channel_mut(channel)[index] = decode_sample(self.encoding, sample);
+            }
+        }
+        Ok(DecodedAudio {
+            samples,
+            sample_rate: self.sample_rate,
+        })
+    }
+}
+
+/// One little-endian sample as `f32`, integers scaled so their largest value is 1.0
+fn decode_sample(encoding: Encoding, bytes: &[u8]) -> f32 {
+    let sample = match (encoding, bytes.len()) {
+        (Encoding::Int, 1) => (f32::from(u8::from_le_bytes(array(bytes))) - 128.0) / 127.0,
+        (Encoding::Int, 2) => f32::from(i16::from_le_bytes(array(bytes))) / f32::from(i16::MAX),
+        (Encoding::Int, 3) => {
+            let [low, middle, high] = array(bytes);
+            // shifted down from the top of an i32 to extend the sign
+            (i32::from_le_bytes([0, low, middle, high]) >> 8) as f32 / 8_388_607.0
+        }
+        (Encoding::Int, 4) => i32::from_le_bytes(array(bytes)) as f32 / i32::MAX as f32,
+        (Encoding::Float, 4) => f32::from_le_bytes(array(bytes)),
+        (Encoding::Float, 8) => f64::from_le_bytes(array(bytes)) as f32,
+        _ => 0.0,
+    };
+    if sample.is_finite() { sample } else { 0.0 }
+}
+
+/// `bytes` as an array, zeroes if it's not `N` long
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes.try_into().unwrap_or([0; N])
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    let bytes = bytes.get(at..at.checked_add(2)?)?;
+    Some(u16::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    let bytes = bytes.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SeededRng;
+
+    /// A WAV file with a `fmt ` chunk of `format`, `channels` and `bits`, and `data`
+    fn wav(format: u16, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+        let block_align = (u32::from(channels) * u32::from(bits) / 8) as u16;
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        bytes.extend(16u32.to_le_bytes());
+        bytes.extend(format.to_le_bytes());
+        bytes.extend(channels.to_le_bytes());
+        bytes.extend(48_000u32.to_le_bytes());
+        bytes.extend((48_000 * u32::from(block_align)).to_le_bytes());
+        bytes.extend(block_align.to_le_bytes());
+        bytes.extend(bits.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        let riff_size = (bytes.len() - 8) as u32;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_decodes_every_sample_format() {
+        let limits = DecodeLimits::default();
+        let decoded = |format, bits, data: &[u8]| {
+            decode(&wav(format, 1, bits, data), &limits)
+                .unwrap()
+                .samples
+                .channel(0)[0]
+        };
+        assert_eq!(decoded(PCM, 8, &[255]), 1.0);
+        assert_eq!(
+            decoded(PCM, 16, &i16::MIN.to_le_bytes()),
+            -32768.0 / 32767.0
+        );
+        assert_eq!(decoded(PCM, 24, &[0xFF, 0xFF, 0x7F]), 1.0);
+        assert_eq!(
+            decoded(PCM, 24, &[0x00, 0x00, 0xC0]),
+            -4_194_304.0 / 8_388_607.0
+        );
+        assert_eq!(decoded(IEEE_FLOAT, 32, &0.5f32.to_le_bytes()), 0.5);
+        assert_eq!(decoded(IEEE_FLOAT, 64, &(-0.25f64).to_le_bytes()), -0.25);
+        assert_eq!(decoded(IEEE_FLOAT, 32, &f32::NAN.to_le_bytes()), 0.0);
+
+        let decoded = decode(&wav(PCM, 2, 16, &[0; 4]), &limits).unwrap();
+        assert_eq!(decoded.sample_rate, 48_000);
+        assert_eq!(
+            (decoded.samples.channels(), decoded.samples.frames()),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn test_rejects_what_it_cannot_decode() {
+        let limits = DecodeLimits {
+            max_frames: 2,
+            ..DecodeLimits::default()
+        };
+        let decoded = |bytes: &[u8]| decode(bytes, &limits);
+        assert!(matches!(decoded(b"OggS"), Err(DecodeError::UnknownFormat)));
+        assert!(matches!(decoded(b"RIFF"), Err(DecodeError::Malformed(_))));
+        assert!(matches!(
+            decoded(&wav(PCM, 0, 16, &[])),
+            Err(DecodeError::UnsupportedChannels(0))
+        ));
+        assert!(matches!(
+            decoded(&wav(PCM, 65_535, 16, &[])),
+            Err(DecodeError::UnsupportedChannels(65_535))
+        ));
+        assert!(matches!(
+            decoded(&wav(PCM, 1, 12, &[])),
+            Err(DecodeError::UnsupportedFormat {
+                format: 1,
+                bits: 12
+            })
+        ));
+        assert!(matches!(
+            decoded(&wav(PCM, 1, 8, &[0; 3])),
+            Err(DecodeError::TooLong {
+                frames: 3,
+                limit: 2
+            })
+        ));
+
+        let mut zero_rate = wav(PCM, 1, 16, &[]);
+        zero_rate[24..28].fill(0);
+        assert!(matches!(
+            decoded(&zero_rate),
+            Err(DecodeError::UnsupportedSampleRate(0))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_data_keeps_the_whole_frames() {
+        let bytes = wav(PCM, 2, 16, &[0, 64, 0, 64, 0, 32, 0, 32]);
+        let decoded = decode(&bytes[..bytes.len() - 3], &DecodeLimits::default()).unwrap();
+        assert_eq!(decoded.samples.frames(), 1);
+    }
+
+    #[test]
+    fn test_mangled_files_never_panic() {
+        let original = wav(PCM, 2, 24, &[0x55; 60]);
+        for end in 0..original.len() {
+            let _ = decode(&original[..end], &DecodeLimits::default());
+        }
+        let mut rng = SeededRng::new(3749);
+        for _ in 0..2000 {
+            let mut bytes = original.clone();
+            for _ in 0..4 {
+                let index = rng.next_u64() as usize % bytes.len();
+                bytes[index] = rng.next_u64() as u8;
+            }
+            let _ = decode(&bytes, &DecodeLimits::default());
+        }
+    }
+}
@@ -1,17 +1,114 @@
 use super::AudioDeviceManager;
-use crate::device_manager::{AudioDeviceError, AudioSource, AudioSourceBufferKind};
+use crate::{
+    device_manager::{AudioSource, AudioSourceBufferKind},
+    error::DeviceError,
+    stems::StemReader,
+};
 use cpal::{
     OutputCallbackInfo,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
 
 pub struct CpalAudioDeviceManager {
+    /// Name of the device played on, `None` for the default output
+    output_device: Option<String>,
     stream: Option<cpal::Stream>,
+    /// Streams playing stems into other devices, see [`Self::start_stem_stream`]
+    stem_streams: Vec<cpal::Stream>,
 }
 
 impl CpalAudioDeviceManager {
     pub fn new() -> Self {
-        Self { stream: None }
+        Self {
+            output_device: None,
+            stream: None,
+            stem_streams: Vec::new(),
+        }
+    }
+
+    /// Plays on the output device called `device_name` instead of the default, e.g. to give
+    /// each of several engines its own device
+    #[must_use]
+    pub fn with_output_device(device_name: &str) -> Self {
+        Self {
+            output_device: Some(device_name.to_owned()),
+            ..Self::new()
+        }
+    }
+
+    /// Plays `stem` on the output device called `device_name`, e.g. a loopback device
+    /// another app records from, where there's no JACK. Mono devices get the stem summed.
+    pub fn start_stem_stream(
+        &mut self,
+        device_name: &str,
+        mut stem: StemReader,
+    ) -> Result<(), DeviceError> {
+        let device = output_device(Some(device_name))?;
+
+        let config = device
+            .default_output_config()
+            .map_err(|e| DeviceError::StreamBuildFailed(e.to_string()))?;
+        if config.sample_format() != cpal::SampleFormat::F32 {
+            return Err(DeviceError::StreamBuildFailed(format!(
+                "Unsupported sample format '{}' for a stem",
+                config.sample_format()
+            )));
+        }
+
+        let channels = usize::from(config.channels());
+        let stream = self.build_output_stream(&device, config, move |data: &mut [f32], _| {
+            for frame in data.chunks_mut(channels) {
+                let (left, right) = stem.next_frame();
+                match frame {
+                    [mono] => *mono = (left + right) * 0.5,
+                    [l, r, rest @ ..] => {
+                        (*l, *r) = (left, right);
+                        rest.fill(0.0);
+                    }
+                    [] => {}
+                }
+            }
+        })?;
+
+        stream
+            .play()
+            .map_err(|e| DeviceError::StreamStartFailed(e.to_string()))?;
+
+        self.stem_streams.push(stream);
+        Ok(())
+    }
+
+    /// Names of every output device on the default host
+    pub fn output_device_names() -> Result<Vec<String>, DeviceError> {
+        let host = cpal::default_host();
+        let devices = host
+            .output_devices()
+            .map_err(|e| DeviceError::QueryFailed(e.to_string()))?;
+
+        devices
+            .map(|device| {
+                device
+                    .name()
+                    .map_err(|e| DeviceError::QueryFailed(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Sample rate the default output device will run at
+    pub fn default_output_sample_rate() -> Result<u32, DeviceError> {
+        Self::output_sample_rate(None)
+    }
+
+    /// Sample rate the output device called `device_name` will run at, the default output
+    /// device's for `None`
+    pub fn output_sample_rate(device_name: Option<&str>) -> Result<u32, DeviceError> {
+        let device = output_device(device_name)?;
+
+        let config = device
+            .default_output_config()
+            .map_err(|e| DeviceError::QueryFailed(e.to_string()))?;
+
+        Ok(config.sample_rate().0)
     }
 
     fn build_output_stream<'a, T, C>(
@@ -19,13 +116,13 @@ impl CpalAudioDeviceManager {
         device: &cpal::Device,
         config: cpal::SupportedStreamConfig,
         mut cb: C,
-    ) -> Result<cpal::Stream, AudioDeviceError>
+    ) -> Result<cpal::Stream, DeviceError>
     where
         T: cpal::SizedSample,
         C: FnMut(&mut [T], usize) + Send + 'static,
     {
-        let error_cb = move |err| {
-            eprintln!("Stream error: {}", err);
+        let error_cb = move |err: cpal::StreamError| {
+            tracing::error!(target: "freqform::device", error = %err, "output stream error");
         };
 
         let channels = config.channels() as usize;
@@ -36,7 +133,7 @@ impl CpalAudioDeviceManager {
 
         let stream = device
             .build_output_stream(&config.into(), data_cb, error_cb, None)
-            .map_err(|e| AudioDeviceError::StreamBuildFailed(e.to_string()))?;
+            .map_err(|e| DeviceError::StreamBuildFailed(e.to_string()))?;
 
         Ok(stream)
     }
@@ -46,16 +143,12 @@ impl AudioDeviceManager for CpalAudioDeviceManager {
     fn start_output_stream(
         &mut self,
         mut audio_source: Box<dyn AudioSource>,
-    ) -> Result<(), AudioDeviceError> {
-        let host = cpal::default_host();
-
-        let device = host
-            .default_output_device()
-            .ok_or(AudioDeviceError::DeviceNotFound)?;
+    ) -> Result<(), DeviceError> {
+        let device = output_device(self.output_device.as_deref())?;
 
         let config = device
             .default_output_config()
-            .map_err(|e| AudioDeviceError::StreamBuildFailed(e.to_string()))?;
+            .map_err(|e| DeviceError::StreamBuildFailed(e.to_string()))?;
 
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => {
@@ -74,7 +167,7 @@ impl AudioDeviceManager for CpalAudioDeviceManager {
                 })?
             }
             format => {
-                return Err(AudioDeviceError::StreamBuildFailed(format!(
+                return Err(DeviceError::StreamBuildFailed(format!(
                     "Unsupported sample format '{format}'"
                 )));
             }
@@ -82,13 +175,25 @@ impl AudioDeviceManager for CpalAudioDeviceManager {
 
         stream
             .play()
-            .map_err(|e| AudioDeviceError::StreamStartFailed(e.to_string()))?;
+            .map_err(|e| DeviceError::StreamStartFailed(e.to_string()))?;
 
         self.stream = Some(stream);
         Ok(())
     }
 }
 
+/// The output device called `name`, the default one for `None`
+fn output_device(name: Option<&str>) -> Result<cpal::Device, DeviceError> {
+    let host = cpal::default_host();
+    let Some(name) = name else {
+        return host.default_output_device().ok_or(DeviceError::NotFound);
+    };
+    host.output_devices()
+        .map_err(|e| DeviceError::QueryFailed(e.to_string()))?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        .ok_or(DeviceError::NotFound)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,65 @@
+use jack::{AsyncClient, AudioOut, Client, ClientOptions, Control, Port, ProcessScope};
+
+use crate::{error::DeviceError, stems::StemReader};
+
+/// Stems published as JACK output ports, a `{id}_l`/`{id}_r` pair per track or bus, so
+/// other JACK clients can record or stream them. Ports stay up until this is dropped.
+pub struct JackStemPorts {
+    client: AsyncClient<(), StemProcess>,
+    port_names: Vec<String>,
+}
+
+impl JackStemPorts {
+    /// Opens a JACK client called `client_name` with ports for every stem in `readers`.
+    /// Doesn't start a JACK server if none is running.
+    pub fn start(client_name: &str, readers: Vec<StemReader>) -> Result<Self, DeviceError> {
+        let (client, _) = Client::new(client_name, ClientOptions::NO_START_SERVER)
+            .map_err(|e| DeviceError::Jack(format!("Failed to open client: {e}")))?;
+
+        let mut port_names = Vec::with_capacity(readers.len() * 2);
+        let mut stems = Vec::with_capacity(readers.len());
+        for reader in readers {
+            let mut register = |side| {
+                let name = format!("{}_{side}", reader.id());
+                let port = client
+                    .register_port(&name, AudioOut)
+                    .map_err(|e| DeviceError::Jack(format!("Failed to register '{name}': {e}")))?;
+                port_names.push(port.name().unwrap_or(name));
+                Ok::<_, DeviceError>(port)
+            };
+            let ports = (register("l")?, register("r")?);
+            stems.push((reader, ports));
+        }
+
+        let client = client
+            .activate_async((), StemProcess { stems })
+            .map_err(|e| DeviceError::Jack(format!("Failed to activate client: {e}")))?;
+        Ok(Self { client, port_names })
+    }
+
+    /// Full names (`client:port`) of the registered ports, left before right per stem
+    #[must_use]
+    pub fn port_names(&self) -> &[String] {
+        &self.port_names
+    }
+
+    /// Sample rate the JACK server runs at, the scheduler should render at the same
+    #[must_use]
+    pub fn sample_rate(&self) -> usize {
+        self.client.as_client().sample_rate()
+    }
+}
+
+/// Copies every stem to its ports on JACK's process thread
+struct StemProcess {
+    stems: Vec<(StemReader, (Port<AudioOut>, Port<AudioOut>))>,
+}
+
+impl jack::ProcessHandler for StemProcess {
+    fn process(&mut self, _: &Client, scope: &ProcessScope) -> Control {
+        for (reader, (left, right)) in &mut self.stems {
+            reader.read(left.as_mut_slice(scope), right.as_mut_slice(scope));
+        }
+        Control::Continue
+    }
+}
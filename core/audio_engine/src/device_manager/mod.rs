@@ -1,11 +1,12 @@
-pub mod cpal_dm;
+use crate::error::DeviceError;
 
-#[derive(Clone, Debug)]
-pub enum AudioDeviceError {
-    DeviceNotFound,
-    StreamBuildFailed(String),
-    StreamStartFailed(String),
-}
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cpal_dm;
+#[cfg(all(feature = "jack", not(target_arch = "wasm32")))]
+pub mod jack_dm;
+// built for host tests too, the worklet exports are plain functions
+#[cfg(any(target_arch = "wasm32", test))]
+pub mod web_dm;
 
 pub enum AudioSourceBufferKind<'a> {
     F32(&'a mut [f32]),
@@ -24,5 +25,5 @@ pub trait AudioDeviceManager {
     fn start_output_stream(
         &mut self,
         audio_source: Box<dyn AudioSource>,
-    ) -> Result<(), AudioDeviceError>;
+    ) -> Result<(), DeviceError>;
 }
@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+
+use super::AudioDeviceManager;
+use crate::{
+    device_manager::{AudioSource, AudioSourceBufferKind},
+    error::DeviceError,
+};
+
+/// Frames per `AudioWorkletProcessor::process` call
+pub const RENDER_QUANTUM_FRAMES: usize = 128;
+
+const OUTPUT_CHANNELS: usize = 2;
+
+struct WebStream {
+    audio_source: Box<dyn AudioSource>,
+    /// Interleaved stereo output, read by the worklet after each render
+    buffer: Vec<f32>,
+}
+
+thread_local! {
+    static STREAM: RefCell<Option<WebStream>> = const { RefCell::new(None) };
+}
+
+/// `AudioDeviceManager` backed by a Web Audio `AudioWorklet`.
+///
+/// The browser owns the audio thread, so nothing is spawned here: the engine is compiled
+/// to wasm and instantiated inside the worklet scope (see `web/freqform-worklet.js`), which
+/// pulls audio through the exported `freqform_*` functions once per render quantum.
+pub struct WebAudioDeviceManager;
+
+impl WebAudioDeviceManager {
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for WebAudioDeviceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioDeviceManager for WebAudioDeviceManager {
+    fn start_output_stream(
+        &mut self,
+        audio_source: Box<dyn AudioSource>,
+    ) -> Result<(), DeviceError> {
+        STREAM.with(|stream| {
+            let mut stream = stream.borrow_mut();
+            if stream.is_some() {
+                return Err(DeviceError::StreamStartFailed(
+                    "A web audio stream is already running".into(),
+                ));
+            }
+
+            *stream = Some(WebStream {
+                audio_source,
+                buffer: vec![0.0; RENDER_QUANTUM_FRAMES * OUTPUT_CHANNELS],
+            });
+            Ok(())
+        })
+    }
+}
+
+/// Returns the interleaved stereo output buffer, sized for `frames` frames.
+/// Null when no stream has been started.
+#[unsafe(no_mangle)]
+pub extern "C" fn freqform_output_buffer(frames: usize) -> *mut f32 {
+    STREAM.with(|stream| match stream.borrow_mut().as_mut() {
+        Some(stream) => {
+            stream.buffer.resize(frames * OUTPUT_CHANNELS, 0.0);
+            stream.buffer.as_mut_ptr()
+        }
+        None => std::ptr::null_mut(),
+    })
+}
+
+/// Renders the next `frames` frames into the output buffer.
+/// Returns `false` (and renders nothing) when no stream has been started.
+#[unsafe(no_mangle)]
+pub extern "C" fn freqform_render(frames: usize) -> bool {
+    STREAM.with(|stream| match stream.borrow_mut().as_mut() {
+        Some(stream) => {
+            // the buffer is only resized by `freqform_output_buffer`, keep the callback allocation free
+            let len = (frames * OUTPUT_CHANNELS).min(stream.buffer.len());
+            let data = &mut stream.buffer[..len];
+            stream
+                .audio_source
+                .fill_buffer(AudioSourceBufferKind::F32(data), len / OUTPUT_CHANNELS);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Drops the running stream, silencing the worklet.
+#[unsafe(no_mangle)]
+pub extern "C" fn freqform_stop() {
+    STREAM.with(|stream| stream.borrow_mut().take());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills each frame with how many renders it has seen
+    struct Renders(f32);
+
+    impl AudioSource for Renders {
+        fn fill_buffer(&mut self, buffer: AudioSourceBufferKind<'_>, _frame_size: usize) {
+            self.0 += 1.0;
+            if let AudioSourceBufferKind::F32(data) = buffer {
+                data.fill(self.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_renders_into_the_output_buffer() {
+        assert!(freqform_output_buffer(RENDER_QUANTUM_FRAMES).is_null());
+        assert!(!freqform_render(RENDER_QUANTUM_FRAMES));
+
+        let mut device = WebAudioDeviceManager::new();
+        device.start_output_stream(Box::new(Renders(0.0))).unwrap();
+        assert!(device.start_output_stream(Box::new(Renders(0.0))).is_err());
+
+        let buffer = freqform_output_buffer(4);
+        assert!(!buffer.is_null());
+        assert!(freqform_render(4));
+        assert!(freqform_render(4));
+        STREAM.with(|stream| {
+            let stream = stream.borrow();
+            let stream = stream.as_ref().unwrap();
+            assert_eq!(stream.buffer, [2.0; 4 * OUTPUT_CHANNELS]);
+            assert_eq!(stream.buffer.as_ptr(), buffer.cast_const());
+        });
+
+        // more frames than the buffer holds only renders what fits
+        assert!(freqform_render(8));
+        STREAM.with(|stream| {
+            assert_eq!(
+                stream.borrow().as_ref().unwrap().buffer.len(),
+                4 * OUTPUT_CHANNELS
+            );
+        });
+    }
+
+    #[test]
+    fn test_stop_drops_the_stream() {
+        let mut device = WebAudioDeviceManager::new();
+        device.start_output_stream(Box::new(Renders(0.0))).unwrap();
+        freqform_stop();
+
+        assert!(STREAM.with(|stream| stream.borrow().is_none()));
+        assert!(freqform_output_buffer(RENDER_QUANTUM_FRAMES).is_null());
+        assert!(!freqform_render(RENDER_QUANTUM_FRAMES));
+        // a new stream can start once stopped
+        device.start_output_stream(Box::new(Renders(0.0))).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! Engine diagnostics through [`tracing`].
+//!
+//! The audio thread can't call a subscriber, which may lock, allocate or write to disk, so it
+//! queues fixed-size [`Diagnostic`]s on a lock-free ring instead. [`DiagnosticsDrain`]
+//! forwards them to `tracing` from another thread, under the [`AUDIO_TARGET`] target.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use tracing::Level;
+
+/// `tracing` target of everything logged on the audio thread
+pub const AUDIO_TARGET: &str = "freqform::audio";
+
+/// Something the audio thread noticed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: &'static str,
+    /// Timeline frame of the block being rendered
+    pub frame: u64,
+}
+
+/// Creates the channel the scheduler logs through. `capacity` bounds how many diagnostics
+/// can wait to be forwarded, once full they're counted and dropped.
+#[must_use]
+pub fn diagnostics_channel(capacity: usize) -> (DiagnosticsLogger, DiagnosticsDrain) {
+    let (producer, consumer) = RingBuffer::new(capacity);
+    let dropped = Arc::new(AtomicU64::new(0));
+    (
+        DiagnosticsLogger {
+            producer,
+            dropped: Arc::clone(&dropped),
+            frame: 0,
+        },
+        DiagnosticsDrain { consumer, dropped },
+    )
+}
+
+/// Audio thread side of the diagnostics channel: never blocks or allocates
+pub struct DiagnosticsLogger {
+    producer: Producer<Diagnostic>,
+    dropped: Arc<AtomicU64>,
+    /// Stamped on what's logged, see [`DiagnosticsLogger::set_frame`]
+    frame: u64,
+}
+
+impl DiagnosticsLogger {
+    /// Timeline frame of the block being rendered, stamped on everything logged from now on
+    pub const fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    pub fn log(&mut self, level: Level, message: &'static str) {
+        let diagnostic = Diagnostic {
+            level,
+            message,
+            frame: self.frame,
+        };
+        if self.producer.push(diagnostic).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn warn(&mut self, message: &'static str) {
+        self.log(Level::WARN, message);
+    }
+}
+
+/// Forwards what the audio thread logged to `tracing`, away from the audio thread.
+/// Forwards what's left when dropped.
+pub struct DiagnosticsDrain {
+    consumer: Consumer<Diagnostic>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl DiagnosticsDrain {
+    /// Emits every queued diagnostic as a `tracing` event, returning how many
+    pub fn forward(&mut self) -> usize {
+        let mut forwarded = 0;
+        while let Ok(diagnostic) = self.consumer.pop() {
+            let Diagnostic {
+                level,
+                message,
+                frame,
+            } = diagnostic;
+            match level {
+                Level::ERROR => tracing::error!(target: AUDIO_TARGET, frame, "{message}"),
+                Level::WARN => tracing::warn!(target: AUDIO_TARGET, frame, "{message}"),
+                Level::INFO => tracing::info!(target: AUDIO_TARGET, frame, "{message}"),
+                Level::DEBUG => tracing::debug!(target: AUDIO_TARGET, frame, "{message}"),
+                Level::TRACE => tracing::trace!(target: AUDIO_TARGET, frame, "{message}"),
+            }
+            forwarded += 1;
+        }
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            tracing::warn!(target: AUDIO_TARGET, dropped, "diagnostics queue full, dropped some");
+        }
+        forwarded
+    }
+
+    /// `true` once the logger has been dropped
+    pub fn is_abandoned(&self) -> bool {
+        self.consumer.is_abandoned()
+    }
+
+    /// Forwards on a background thread every `interval` until the logger goes away
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(mut self, interval: Duration) -> std::thread::JoinHandle<()> {
+        std::thread::Builder::new()
+            .name("freqform-diagnostics".into())
+            .spawn(move || {
+                while !self.is_abandoned() {
+                    self.forward();
+                    std::thread::sleep(interval);
+                }
+            })
+            .expect("Failed to spawn diagnostics thread")
+    }
+}
+
+impl Drop for DiagnosticsDrain {
+    fn drop(&mut self) {
+        self.forward();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    use super::*;
+
+    /// Keeps the level and `frame` field of every event
+    #[derive(Default)]
+    struct Recorder(Arc<Mutex<Vec<(Level, u64)>>>);
+
+    struct FrameField(u64);
+
+    impl Visit for FrameField {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "frame" {
+                self.0 = value;
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == AUDIO_TARGET
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut frame = FrameField(0);
+            event.record(&mut frame);
+            self.0
+                .lock()
+                .unwrap()
+                .push((*event.metadata().level(), frame.0));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_forwards_diagnostics_and_counts_the_dropped() {
+        let (mut logger, mut drain) = diagnostics_channel(2);
+        logger.set_frame(512);
+        logger.warn("event queue full");
+        logger.set_frame(1024);
+        logger.log(Level::ERROR, "callback overran");
+        logger.warn("lost");
+
+        let recorder = Recorder::default();
+        let events = Arc::clone(&recorder.0);
+        let forwarded = tracing::subscriber::with_default(recorder, || drain.forward());
+
+        assert_eq!(forwarded, 2);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![(Level::WARN, 512), (Level::ERROR, 1024), (Level::WARN, 0)]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    buffer::AudioBuffer,
+    constants::MAX_BLOCK_FRAMES,
+    dsp::{EffectSettings, Processor, math::gain_to_db},
+};
+
+/// Time constant of the level measurements the match gain is taken from
+const MATCH_SECONDS: f64 = 1.5;
+/// Length of the crossfade when switching between processed and bypassed
+const SWITCH_SECONDS: f64 = 0.01;
+/// Largest correction in either direction, so silence in or out can't blow it up
+const MAX_MATCH_DB: f32 = 24.0;
+/// Mean square below which a signal counts as silent (-100 dBFS)
+const SILENCE: f64 = 1e-10;
+
+/// Wraps an insert so it can be bypassed for an A/B comparison that isn't fooled by loudness.
+///
+/// While loudness matching is on, the processed signal is scaled to the RMS level of the
+/// unprocessed one, so switching between the two compares only the change in sound: an
+/// effect that just makes things louder no longer seems better for it. The wrapped
+/// processor keeps running while bypassed so its state and the level measurements stay
+/// current, and the dry signal is delayed by its latency so the two stay aligned. Switching
+/// crossfades over a few milliseconds instead of clicking.
+///
+/// Bypass is toggled on channel inserts with
+/// [`ChannelChange::SetInsertBypass`](crate::scheduler::command::ChannelChange::SetInsertBypass).
+///
+/// # Example
+/// ```
+/// use audio_engine::{
+///     buffer::AudioBuffer,
+///     dsp::{Processor as _, bypass::Bypass, gain::Gain},
+/// };
+///
+/// // a 6 dB boost is matched back down to the input level
+/// let mut boost = Bypass::new(Box::new(Gain::new(6.0)), 48000.0);
+/// let mut signal = AudioBuffer::from_frames(&vec![(0.5, -0.5); 48000]);
+/// boost.process(&mut signal, 0, 48000);
+///
+/// assert!((signal.frame(47999).0 - 0.5).abs() < 0.01);
+/// ```
+pub struct Bypass {
+    inner: Box<dyn Processor>,
+    bypassed: bool,
+    matching: bool,
+    /// Unprocessed copy of the block being processed
+    dry: AudioBuffer,
+    /// Dry signal delay lines matching the inner latency, one per channel
+    delay: [Vec<f32>; 2],
+    delay_position: usize,
+    /// Smoothed mean squares of the dry and processed signals
+    dry_power: f64,
+    wet_power: f64,
+    power_coefficient: f64,
+    /// Share of the dry signal in the output, moving towards 1 while bypassed
+    mix: f32,
+    switch_step: f32,
+}
+
+impl Bypass {
+    /// Wraps `inner`, processing with loudness matching on
+    #[must_use]
+    pub fn new(inner: Box<dyn Processor>, sample_rate: f64) -> Self {
+        let latency = inner.latency();
+        let samples = (MATCH_SECONDS * sample_rate).max(1.0);
+        Self {
+            inner,
+            bypassed: false,
+            matching: true,
+            dry: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            delay: [vec![0.0; latency], vec![0.0; latency]],
+            delay_position: 0,
+            dry_power: 0.0,
+            wet_power: 0.0,
+            power_coefficient: 1.0 - (-1.0 / samples).exp(),
+            mix: 0.0,
+            switch_step: (1.0 / (SWITCH_SECONDS * sample_rate).max(1.0)) as f32,
+        }
+    }
+
+    #[must_use]
+    pub fn inner(&self) -> &dyn Processor {
+        self.inner.as_ref()
+    }
+
+    pub fn inner_mut(&mut self) -> &mut dyn Processor {
+        self.inner.as_mut()
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Box<dyn Processor> {
+        self.inner
+    }
+
+    /// `true` if the processed signal is matched to the level of the unprocessed one
+    #[must_use]
+    pub fn is_matching(&self) -> bool {
+        self.matching
+    }
+
+    pub fn set_matching(&mut self, matching: bool) {
+        self.matching = matching;
+    }
+
+    /// Gain in dB the processed signal currently gets to match the unprocessed level, 0 while
+    /// matching is off
+    #[must_use]
+    pub fn match_gain_db(&self) -> f32 {
+        gain_to_db(self.match_gain())
+    }
+
+    fn match_gain(&self) -> f32 {
+        if !self.matching || self.dry_power < SILENCE || self.wet_power < SILENCE {
+            return 1.0;
+        }
+        let limit = MAX_MATCH_DB / 20.0;
+        let gain_db = ((self.dry_power / self.wet_power).log10() * 0.5) as f32;
+        10f32.powf(gain_db.clamp(-limit, limit))
+    }
+
+    /// Processes frames in blocks that fit the dry copy
+    fn run(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        key: Option<&AudioBuffer>,
+        start: usize,
+        len: usize,
+    ) {
+        let mut done = 0;
+        while done < len {
+            let frames = (len - done).min(MAX_BLOCK_FRAMES);
+            self.run_block(buffer, key, start + done, frames);
+            done += frames;
+        }
+    }
+
+    fn run_block(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        key: Option<&AudioBuffer>,
+        start: usize,
+        len: usize,
+    ) {
+        self.dry.copy_from(0, buffer, start, len);
+        match key {
+            Some(key) => self.inner.process_keyed(buffer, key, start, len),
+            None => self.inner.process(buffer, start, len),
+        }
+
+        let target = if self.bypassed { 1.0 } else { 0.0 };
+        let (left, right) = buffer.stereo_mut();
+        for frame in 0..len {
+            let mut dry = [self.dry.channel(0)[frame], self.dry.channel(1)[frame]];
+            if !self.delay[0].is_empty() {
+                for (sample, delay) in dry.iter_mut().zip(&mut self.delay) {
+                    *sample = std::mem::replace(&mut delay[self.delay_position], *sample);
+                }
+                self.delay_position = (self.delay_position + 1) % self.delay[0].len();
+            }
+            let wet = [left[start + frame], right[start + frame]];
+
+            let power = |[l, r]: [f32; 2]| f64::from(l.mul_add(l, r * r)) * 0.5;
+            self.dry_power += (power(dry) - self.dry_power) * self.power_coefficient;
+            self.wet_power += (power(wet) - self.wet_power) * self.power_coefficient;
+            let gain = self.match_gain();
+
+            self.mix = if self.mix < target {
+                (self.mix + self.switch_step).min(target)
+            } else {
+                (self.mix - self.switch_step).max(target)
+            };
+            let wet_gain = gain * (1.0 - self.mix);
+            left[start + frame] = dry[0].mul_add(self.mix, wet[0] * wet_gain);
+            right[start + frame] = dry[1].mul_add(self.mix, wet[1] * wet_gain);
+        }
+    }
+}
+
+impl Processor for Bypass {
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+        self.run(buffer, None, start, len);
+    }
+
+    fn process_keyed(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        key: &AudioBuffer,
+        start: usize,
+        len: usize,
+    ) {
+        self.run(buffer, Some(key), start, len);
+    }
+
+    fn latency(&self) -> usize {
+        self.inner.latency()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.delay.iter_mut().for_each(|delay| delay.fill(0.0));
+        self.delay_position = 0;
+        self.dry_power = 0.0;
+        self.wet_power = 0.0;
+        self.mix = if self.bypassed { 1.0 } else { 0.0 };
+    }
+
+    /// The wrapped processor's settings, presets don't keep the bypass
+    fn settings(&self) -> Option<EffectSettings> {
+        self.inner.settings()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{gain::Gain, limiter::TruePeakLimiter};
+
+    #[test]
+    fn test_bypass_switches_to_the_aligned_dry_signal() {
+        let limiter = TruePeakLimiter::new(1000.0, 0.0);
+        let latency = limiter.latency();
+        let mut bypass = Bypass::new(Box::new(limiter), 1000.0);
+        bypass.set_bypassed(true);
+        bypass.reset();
+        let mut impulse = AudioBuffer::stereo(64);
+        impulse.channel_mut(0)[0] = 0.5;
+
+        bypass.process(&mut impulse, 0, 64);
+        assert_eq!(bypass.latency(), latency);
+        assert_eq!(impulse.frame(latency), (0.5, 0.0));
+        assert!((impulse.peak(0, 0, 64) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_matching_evens_out_the_level_difference() {
+        let mut quiet = Bypass::new(Box::new(Gain::new(-12.0)), 1000.0);
+        let mut signal = AudioBuffer::from_frames(&vec![(0.5, 0.5); 20000]);
+        quiet.process(&mut signal, 0, 20000);
+        assert!((signal.frame(19999).0 - 0.5).abs() < 1e-3);
+        assert!((quiet.match_gain_db() - 12.0).abs() < 0.01);
+
+        quiet.set_matching(false);
+        assert!(quiet.match_gain_db().abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{buffer::AudioBuffer, dsp::Processor, rng::SeededRng};
+
+/// TPDF dither for reducing audio to `bits`-bit integers, e.g. a 16-bit export.
+///
+/// The noise comes from a seeded generator that starts over on [`Processor::reset`], so
+/// renders with the same seed come out the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dither {
+    /// Size of one step of the target format, full scale being 1.0
+    step: f32,
+    rng: SeededRng,
+}
+
+impl Dither {
+    #[must_use]
+    pub fn new(bits: u32, seed: u64) -> Self {
+        Self {
+            step: 1.0 / (1u64 << (bits.clamp(2, 32) - 1)) as f32,
+            rng: SeededRng::new(seed),
+        }
+    }
+}
+
+impl Processor for Dither {
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+        for channel in 0..buffer.channels() {
+            for sample in &mut buffer.channel_mut(channel)[start..start + len] {
+                // the difference of two uniform numbers has a triangular distribution
+                let noise = self.rng.next_f32() - self.rng.next_f32();
+                *sample += noise * self.step;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.rng.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_stays_within_a_step_and_repeats_after_reset() {
+        let mut dither = Dither::new(16, 9);
+        let mut first = AudioBuffer::stereo(256);
+        dither.process(&mut first, 0, 256);
+        let step = 1.0 / 32768.0;
+        assert!(first.channel(0).iter().all(|sample| sample.abs() < step));
+        assert!(first.channel(1).iter().any(|sample| *sample != 0.0));
+
+        dither.reset();
+        let mut second = AudioBuffer::stereo(256);
+        dither.process(&mut second, 0, 256);
+        assert_eq!(first.channel(0), second.channel(0));
+    }
+}
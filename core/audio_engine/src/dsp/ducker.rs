@@ -0,0 +1,220 @@
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{
+        EffectSettings, Processor,
+        math::{db_to_gain, gain_to_db},
+    },
+};
+
+const DEFAULT_THRESHOLD_DB: f32 = -30.0;
+const DEFAULT_DEPTH_DB: f32 = 12.0;
+const DEFAULT_ATTACK_SECONDS: f64 = 0.005;
+const DEFAULT_RELEASE_SECONDS: f64 = 0.2;
+
+/// Turns a signal down while its sidechain key is above a threshold, e.g. a bass ducking
+/// under the kick for the pumping sound of sidechain compression.
+///
+/// The level is taken from the key routed with
+/// [`Mixer::route_sidechain`](crate::mixer::Mixer::route_sidechain); without one the signal
+/// passes through. Gain moves down by up to [`Ducker::depth_db`] over the attack and
+/// recovers over the release once the key falls below the threshold.
+///
+/// # Example
+/// ```
+/// use audio_engine::{
+///     buffer::AudioBuffer,
+///     dsp::{Processor as _, ducker::Ducker},
+/// };
+///
+/// let mut ducker = Ducker::new(48000.0);
+/// ducker.set_depth(6.0);
+/// let kick = AudioBuffer::from_frames(&[(1.0, 1.0); 4800]);
+/// let mut bass = AudioBuffer::from_frames(&[(0.5, 0.5); 4800]);
+/// ducker.process_keyed(&mut bass, &kick, 0, 4800);
+///
+/// assert!((bass.frame(4799).0 - 0.25).abs() < 0.01);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ducker {
+    /// Linear key level ducking starts above
+    threshold: f32,
+    threshold_db: f32,
+    /// Linear gain at full depth
+    floor: f32,
+    depth_db: f32,
+    attack_coefficient: f32,
+    attack_seconds: f64,
+    release_coefficient: f32,
+    release_seconds: f64,
+    sample_rate: f64,
+    /// Gain applied to the current frame
+    gain: f32,
+}
+
+impl Ducker {
+    #[must_use]
+    pub fn new(sample_rate: f64) -> Self {
+        let mut ducker = Self {
+            threshold: 1.0,
+            threshold_db: 0.0,
+            floor: 1.0,
+            depth_db: 0.0,
+            attack_coefficient: 1.0,
+            attack_seconds: 0.0,
+            release_coefficient: 1.0,
+            release_seconds: 0.0,
+            sample_rate,
+            gain: 1.0,
+        };
+        ducker.set_threshold(DEFAULT_THRESHOLD_DB);
+        ducker.set_depth(DEFAULT_DEPTH_DB);
+        ducker.set_attack(DEFAULT_ATTACK_SECONDS);
+        ducker.set_release(DEFAULT_RELEASE_SECONDS);
+        ducker
+    }
+
+    #[must_use]
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// Key peak level in dBFS above which the signal is ducked
+    pub fn set_threshold(&mut self, threshold_db: f32) {
+        self.threshold = db_to_gain(threshold_db);
+        self.threshold_db = threshold_db;
+    }
+
+    #[must_use]
+    pub fn depth_db(&self) -> f32 {
+        self.depth_db
+    }
+
+    /// How far the signal is turned down while ducked, in dB
+    pub fn set_depth(&mut self, depth_db: f32) {
+        let depth_db = depth_db.max(0.0);
+        self.floor = db_to_gain(-depth_db);
+        self.depth_db = depth_db;
+    }
+
+    #[must_use]
+    pub fn attack_seconds(&self) -> f64 {
+        self.attack_seconds
+    }
+
+    /// Time constant of the gain going down once the key crosses the threshold
+    pub fn set_attack(&mut self, seconds: f64) {
+        self.attack_seconds = seconds;
+        self.attack_coefficient = self.coefficient(seconds);
+    }
+
+    #[must_use]
+    pub fn release_seconds(&self) -> f64 {
+        self.release_seconds
+    }
+
+    /// Time constant of the gain recovering once the key falls below the threshold
+    pub fn set_release(&mut self, seconds: f64) {
+        self.release_seconds = seconds;
+        self.release_coefficient = self.coefficient(seconds);
+    }
+
+    /// Current gain reduction in dB, 0 when not ducking
+    #[must_use]
+    pub fn gain_reduction_db(&self) -> f32 {
+        -gain_to_db(self.gain)
+    }
+
+    fn coefficient(&self, seconds: f64) -> f32 {
+        let samples = (seconds * self.sample_rate).max(1.0);
+        (1.0 - (-1.0 / samples).exp()) as f32
+    }
+
+    /// Moves the gain one frame towards `target`
+    fn follow(&mut self, target: f32) {
+        let coefficient = if target < self.gain {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.gain = (target - self.gain).mul_add(coefficient, self.gain);
+    }
+}
+
+impl Processor for Ducker {
+    /// Without a key only recovers from earlier ducking
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+        let (left, right) = buffer.stereo_mut();
+        for (l, r) in left[start..start + len]
+            .iter_mut()
+            .zip(&mut right[start..start + len])
+        {
+            self.follow(1.0);
+            *l *= self.gain;
+            *r *= self.gain;
+        }
+    }
+
+    fn process_keyed(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        key: &AudioBuffer,
+        start: usize,
+        len: usize,
+    ) {
+        let right_key = key.channels().min(2) - 1;
+        let (left, right) = buffer.stereo_mut();
+        for (frame, (l, r)) in left[start..start + len]
+            .iter_mut()
+            .zip(&mut right[start..start + len])
+            .enumerate()
+        {
+            let frame = start + frame;
+            let level = key.channel(0)[frame]
+                .abs()
+                .max(key.channel(right_key)[frame].abs());
+            self.follow(if level > self.threshold {
+                self.floor
+            } else {
+                1.0
+            });
+            *l *= self.gain;
+            *r *= self.gain;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+
+    fn settings(&self) -> Option<EffectSettings> {
+        Some(EffectSettings::Ducker {
+            threshold_db: self.threshold_db,
+            depth_db: self.depth_db,
+            attack_seconds: self.attack_seconds,
+            release_seconds: self.release_seconds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ducks_while_the_key_plays_and_recovers() {
+        let mut ducker = Ducker::new(1000.0);
+        ducker.set_attack(0.001);
+        ducker.set_release(0.01);
+        let mut key = AudioBuffer::from_frames(&[(0.0, 0.5); 100]);
+        key.channel_mut(1)[50..].fill(0.0);
+        let mut signal = AudioBuffer::from_frames(&[(1.0, 1.0); 100]);
+
+        ducker.process_keyed(&mut signal, &key, 0, 100);
+        let floor = 10f32.powf(-12.0 / 20.0);
+        assert!(signal.frame(0).0 < 0.6);
+        assert!((signal.frame(49).1 - floor).abs() < 1e-3);
+        // back to unity within a few release time constants
+        assert!(signal.frame(99).0 > 0.99);
+        assert!(ducker.gain_reduction_db() < 0.1);
+    }
+}
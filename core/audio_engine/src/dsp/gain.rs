@@ -0,0 +1,39 @@
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{
+        EffectSettings, Processor,
+        math::{db_to_gain, gain_to_db},
+    },
+};
+
+/// Fixed gain in dB, the simplest insert
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gain {
+    /// Linear factor
+    gain: f32,
+}
+
+impl Gain {
+    #[must_use]
+    pub fn new(gain_db: f32) -> Self {
+        Self {
+            gain: db_to_gain(gain_db),
+        }
+    }
+}
+
+impl Processor for Gain {
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+        for channel in 0..2 {
+            for sample in &mut buffer.channel_mut(channel)[start..start + len] {
+                *sample *= self.gain;
+            }
+        }
+    }
+
+    fn settings(&self) -> Option<EffectSettings> {
+        Some(EffectSettings::Gain {
+            gain_db: gain_to_db(self.gain),
+        })
+    }
+}
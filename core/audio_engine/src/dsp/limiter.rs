@@ -0,0 +1,240 @@
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{
+        EffectSettings, Processor,
+        math::{db_to_gain, gain_to_db},
+    },
+    metering::true_peak::{TRUE_PEAK_LATENCY, TruePeakDetector},
+};
+
+/// How far ahead gain reduction starts ramping in before a peak
+const LOOKAHEAD_SECONDS: f64 = 0.0015;
+const DEFAULT_RELEASE_SECONDS: f64 = 0.05;
+
+/// Lookahead brickwall limiter working on true (inter-sample) peaks, for the master bus and
+/// exports that must stay under a ceiling such as -1 dBTP.
+///
+/// Gain reduction is ramped in over the lookahead so it is fully applied by the time a peak
+/// leaves the delay line, and released smoothly afterwards. The output is delayed by
+/// [`TruePeakLimiter::latency`] frames. All memory is allocated in `new`.
+///
+/// # Example
+/// ```
+/// use audio_engine::{
+///     buffer::AudioBuffer,
+///     dsp::{Processor as _, limiter::TruePeakLimiter},
+/// };
+///
+/// let mut limiter = TruePeakLimiter::new(48000.0, -1.0);
+/// let mut loud = AudioBuffer::from_frames(&[(1.5, -1.5); 1024]);
+/// limiter.process(&mut loud, 0, 1024);
+///
+/// assert!(loud.peak(0, 0, 1024) <= 10f32.powf(-1.0 / 20.0));
+/// ```
+pub struct TruePeakLimiter {
+    /// Linear ceiling
+    ceiling: f32,
+    ceiling_dbtp: f32,
+    release_coefficient: f32,
+    release_seconds: f64,
+    sample_rate: f64,
+    detectors: [TruePeakDetector; 2],
+    /// Signal delay lines, one per channel
+    delay: [Vec<f32>; 2],
+    delay_position: usize,
+    /// Gain each recent frame needs on its own, for the minimum hold
+    required: Vec<f32>,
+    required_position: usize,
+    /// Held minimum gains being averaged into a ramp, and their running sum
+    held: Vec<f32>,
+    held_position: usize,
+    held_sum: f64,
+    /// Gain applied to the current output frame
+    gain: f32,
+}
+
+impl TruePeakLimiter {
+    #[must_use]
+    pub fn new(sample_rate: f64, ceiling_dbtp: f32) -> Self {
+        let lookahead = ((sample_rate * LOOKAHEAD_SECONDS).round() as usize).max(1);
+        // the detector reports a peak up to TRUE_PEAK_LATENCY frames late, hold and delay
+        // long enough to still catch the frames before it
+        let hold = lookahead + TRUE_PEAK_LATENCY;
+        let delay = lookahead - 1 + TRUE_PEAK_LATENCY;
+
+        let mut limiter = Self {
+            ceiling: 1.0,
+            ceiling_dbtp: 0.0,
+            release_coefficient: 1.0,
+            release_seconds: 0.0,
+            sample_rate,
+            detectors: [TruePeakDetector::new(), TruePeakDetector::new()],
+            delay: [vec![0.0; delay], vec![0.0; delay]],
+            delay_position: 0,
+            required: vec![1.0; hold],
+            required_position: 0,
+            held: vec![1.0; lookahead],
+            held_position: 0,
+            held_sum: lookahead as f64,
+            gain: 1.0,
+        };
+        limiter.set_ceiling(ceiling_dbtp);
+        limiter.set_release(DEFAULT_RELEASE_SECONDS);
+        limiter
+    }
+
+    pub fn set_ceiling(&mut self, ceiling_dbtp: f32) {
+        self.ceiling = db_to_gain(ceiling_dbtp);
+        self.ceiling_dbtp = ceiling_dbtp;
+    }
+
+    /// Time constant of the gain recovering after a peak
+    pub fn set_release(&mut self, seconds: f64) {
+        self.release_seconds = seconds;
+        let samples = (seconds * self.sample_rate).max(1.0);
+        self.release_coefficient = (1.0 - (-1.0 / samples).exp()) as f32;
+    }
+
+    /// Current gain reduction in dB, 0 when not limiting
+    #[must_use]
+    pub fn gain_reduction_db(&self) -> f32 {
+        -gain_to_db(self.gain)
+    }
+}
+
+impl Processor for TruePeakLimiter {
+    /// Limits frames `start..start + len` of a stereo buffer in place
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+        let (left, right) = buffer.stereo_mut();
+        for (l, r) in left[start..start + len]
+            .iter_mut()
+            .zip(&mut right[start..start + len])
+        {
+            let peak = self.detectors[0]
+                .process(*l)
+                .max(self.detectors[1].process(*r));
+            let required = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+            self.required[self.required_position] = required;
+            self.required_position = (self.required_position + 1) % self.required.len();
+
+            let hold = self.required.iter().copied().fold(1.0, f32::min);
+            self.held_sum += f64::from(hold - self.held[self.held_position]);
+            self.held[self.held_position] = hold;
+            self.held_position = (self.held_position + 1) % self.held.len();
+
+            let target = (self.held_sum / self.held.len() as f64) as f32;
+            self.gain = if target < self.gain {
+                target
+            } else {
+                (target - self.gain).mul_add(self.release_coefficient, self.gain)
+            };
+
+            let position = self.delay_position;
+            let delayed = (self.delay[0][position], self.delay[1][position]);
+            self.delay[0][position] = *l;
+            self.delay[1][position] = *r;
+            self.delay_position = (position + 1) % self.delay[0].len();
+
+            *l = delayed.0 * self.gain;
+            *r = delayed.1 * self.gain;
+        }
+    }
+
+    /// Frames the output lags behind the input
+    fn latency(&self) -> usize {
+        self.delay[0].len()
+    }
+
+    /// Clears the delay line and gain state, e.g. after a transport jump
+    fn reset(&mut self) {
+        self.detectors = [TruePeakDetector::new(), TruePeakDetector::new()];
+        for line in &mut self.delay {
+            line.fill(0.0);
+        }
+        self.required.fill(1.0);
+        self.held.fill(1.0);
+        self.held_sum = self.held.len() as f64;
+        self.gain = 1.0;
+    }
+
+    fn settings(&self) -> Option<EffectSettings> {
+        Some(EffectSettings::Limiter {
+            ceiling_dbtp: self.ceiling_dbtp,
+            release_seconds: self.release_seconds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::metering::loudness::LoudnessMeter;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn sine(amplitude: f32, frames: usize) -> AudioBuffer {
+        let samples: Vec<_> = (0..frames)
+            .map(|i| {
+                // close to fs/4 with a phase offset, so peaks fall between samples
+                let phase = 2.0 * PI * 11_987.0 * i as f32 / SAMPLE_RATE as f32 + PI / 4.0;
+                let sample = amplitude * phase.sin();
+                (sample, sample)
+            })
+            .collect();
+        AudioBuffer::from_frames(&samples)
+    }
+
+    fn true_peak(buffer: &AudioBuffer) -> f32 {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        meter.process(buffer, 0, buffer.frames());
+        meter.true_peak()
+    }
+
+    #[test]
+    fn test_output_stays_under_ceiling() {
+        let mut limiter = TruePeakLimiter::new(SAMPLE_RATE, -1.0);
+        // quiet, then a burst 7 dB over full scale, then quiet again
+        let mut buffer = sine(0.1, 4800);
+        buffer.extend_from(&sine(2.0, 9600));
+        buffer.extend_from(&sine(0.1, 9600));
+
+        for start in (0..buffer.frames()).step_by(256) {
+            let len = 256.min(buffer.frames() - start);
+            limiter.process(&mut buffer, start, len);
+        }
+
+        assert!(true_peak(&buffer) <= -1.0 + 0.05, "{}", true_peak(&buffer));
+    }
+
+    #[test]
+    fn test_material_under_ceiling_is_only_delayed() {
+        let mut limiter = TruePeakLimiter::new(SAMPLE_RATE, -1.0);
+        let original = sine(0.5, 2048);
+        let mut buffer = original.clone();
+        limiter.process(&mut buffer, 0, 2048);
+
+        let latency = limiter.latency();
+        for frame in latency..buffer.frames() {
+            assert!((buffer.frame(frame).0 - original.frame(frame - latency).0).abs() < 1e-6);
+        }
+        assert_eq!(limiter.gain_reduction_db(), 0.0);
+    }
+
+    #[test]
+    fn test_gain_recovers_after_peak() {
+        let mut limiter = TruePeakLimiter::new(SAMPLE_RATE, -1.0);
+        let mut burst = sine(2.0, 480);
+        limiter.process(&mut burst, 0, 480);
+        assert!(limiter.gain_reduction_db() > 5.0);
+
+        let mut quiet = sine(0.1, 48000);
+        limiter.process(&mut quiet, 0, 48000);
+        assert!(limiter.gain_reduction_db() < 0.01);
+    }
+}
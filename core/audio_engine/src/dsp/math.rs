@@ -0,0 +1,125 @@
+//! The gain math the engine applies, as pure functions, so hosts drawing fades, faders and
+//! meters show exactly what's heard.
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use serde::{Deserialize, Serialize};
+
+use crate::track::timeline::FadeCurve;
+
+/// How a pan position splits a channel between left and right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PanLaw {
+    /// Unity in the middle, the side panned away from is turned down, see [`balance_pan`]
+    #[default]
+    Balance,
+    /// -3 dB in the middle, the power stays the same across the field
+    EqualPower,
+    /// -6 dB in the middle, the summed level stays the same across the field
+    Linear,
+}
+
+/// Linear gain of `db` decibels
+#[must_use]
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Decibels of the linear `gain`, negative infinity for silence
+#[must_use]
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+/// Gain `position` of the way through a fade in (0.0 to 1.0) shaped by `curve`. A fade out
+/// is the fade in read backwards.
+#[must_use]
+pub fn fade_gain(curve: FadeCurve, position: f32) -> f32 {
+    match curve {
+        FadeCurve::Linear => position,
+        FadeCurve::EqualPower => (position * FRAC_PI_2).sin(),
+    }
+}
+
+/// `(left, right)` gains of the mixer's balance pan, -1.0 fully left to 1.0 fully right.
+/// Unity in the middle, the side panned away from is turned down.
+#[must_use]
+pub fn balance_pan(pan: f32) -> (f32, f32) {
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+/// `(left, right)` gains of `pan`, -1.0 fully left to 1.0 fully right, under `law`
+#[must_use]
+pub fn pan_gains(law: PanLaw, pan: f32) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    match law {
+        PanLaw::Balance => balance_pan(pan),
+        PanLaw::EqualPower => {
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            (angle.cos(), angle.sin())
+        }
+        PanLaw::Linear => ((1.0 - pan) * 0.5, (1.0 + pan) * 0.5),
+    }
+}
+
+/// `(from, to)` gains `mix` of the way (0.0 to 1.0) through an equal power crossfade,
+/// exactly 1.0 and 0.0 at the ends
+#[must_use]
+pub fn equal_power(mix: f32) -> (f32, f32) {
+    match mix {
+        0.0 => (1.0, 0.0),
+        1.0 => (0.0, 1.0),
+        mix => ((mix * FRAC_PI_2).cos(), (mix * FRAC_PI_2).sin()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_db_roundtrips(db in -120.0f32..24.0) {
+            prop_assert!((gain_to_db(db_to_gain(db)) - db).abs() < 1e-3);
+        }
+
+        #[test]
+        fn test_fades_rise_from_silence_to_unity(
+            from in 0.0f32..=1.0,
+            to in 0.0f32..=1.0,
+            equal_power in any::<bool>(),
+        ) {
+            let curve = if equal_power { FadeCurve::EqualPower } else { FadeCurve::Linear };
+            let (from, to) = (from.min(to), from.max(to));
+            prop_assert!(fade_gain(curve, from) <= fade_gain(curve, to));
+            prop_assert!((0.0..=1.0).contains(&fade_gain(curve, from)));
+            prop_assert_eq!(fade_gain(curve, 0.0), 0.0);
+            prop_assert!((fade_gain(curve, 1.0) - 1.0).abs() < 1e-6);
+        }
+
+        #[test]
+        fn test_equal_power_keeps_the_power(mix in 0.0f32..=1.0) {
+            let (from, to) = equal_power(mix);
+            prop_assert!((from.mul_add(from, to * to) - 1.0).abs() < 1e-5);
+        }
+
+        #[test]
+        fn test_balance_pan_only_turns_down_one_side(pan in -1.0f32..=1.0) {
+            let (left, right) = balance_pan(pan);
+            prop_assert_eq!(left.max(right), 1.0);
+            prop_assert!((0.0..=1.0).contains(&left.min(right)));
+        }
+
+        #[test]
+        fn test_pan_laws_keep_power_or_level(pan in -1.0f32..=1.0) {
+            let (left, right) = pan_gains(PanLaw::EqualPower, pan);
+            prop_assert!((left.mul_add(left, right * right) - 1.0).abs() < 1e-5);
+            let (left, right) = pan_gains(PanLaw::Linear, pan);
+            prop_assert!((left + right - 1.0).abs() < 1e-6);
+            prop_assert_eq!(pan_gains(PanLaw::Balance, pan), balance_pan(pan));
+        }
+    }
+}
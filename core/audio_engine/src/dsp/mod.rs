@@ -0,0 +1,103 @@
+//! Signal processors for busses and the export path.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{ducker::Ducker, gain::Gain, limiter::TruePeakLimiter},
+};
+
+pub mod bypass;
+pub mod dither;
+pub mod ducker;
+pub mod gain;
+pub mod limiter;
+pub mod math;
+pub mod stretch;
+
+/// A processor's type and parameters, what presets store to recreate it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectSettings {
+    Gain {
+        gain_db: f32,
+    },
+    Limiter {
+        ceiling_dbtp: f32,
+        release_seconds: f64,
+    },
+    Ducker {
+        threshold_db: f32,
+        depth_db: f32,
+        attack_seconds: f64,
+        release_seconds: f64,
+    },
+}
+
+impl EffectSettings {
+    /// A processor with these settings, running at `sample_rate`
+    #[must_use]
+    pub fn build(&self, sample_rate: f64) -> Box<dyn Processor> {
+        match *self {
+            Self::Gain { gain_db } => Box::new(Gain::new(gain_db)),
+            Self::Limiter {
+                ceiling_dbtp,
+                release_seconds,
+            } => {
+                let mut limiter = TruePeakLimiter::new(sample_rate, ceiling_dbtp);
+                limiter.set_release(release_seconds);
+                Box::new(limiter)
+            }
+            Self::Ducker {
+                threshold_db,
+                depth_db,
+                attack_seconds,
+                release_seconds,
+            } => {
+                let mut ducker = Ducker::new(sample_rate);
+                ducker.set_threshold(threshold_db);
+                ducker.set_depth(depth_db);
+                ducker.set_attack(attack_seconds);
+                ducker.set_release(release_seconds);
+                Box::new(ducker)
+            }
+        }
+    }
+}
+
+/// An insert effect on a mixer channel, bus or the master output.
+///
+/// Runs on the audio thread: `process` must not allocate, lock or block.
+pub trait Processor: Send + Sync {
+    /// Processes frames `start..start + len` of a stereo buffer in place
+    fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize);
+    /// Like [`Processor::process`], with the same frames of a sidechain `key` signal, see
+    /// [`Mixer::route_sidechain`](crate::mixer::Mixer::route_sidechain). Processors without
+    /// a key input ignore it.
+    fn process_keyed(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        _key: &AudioBuffer,
+        start: usize,
+        len: usize,
+    ) {
+        self.process(buffer, start, len);
+    }
+    /// Frames the processor delays its output by
+    fn latency(&self) -> usize {
+        0
+    }
+    /// Clears internal state such as delay lines, e.g. when the transport stops
+    fn reset(&mut self) {}
+    /// Settings to recreate the processor from, `None` if presets can't store it
+    fn settings(&self) -> Option<EffectSettings> {
+        None
+    }
+    /// `true` while the processor passes its input through unprocessed
+    fn is_bypassed(&self) -> bool {
+        false
+    }
+    /// Switches bypass, processors that can't be bypassed ignore it; wrap them in a
+    /// [`Bypass`](bypass::Bypass) to make them bypassable
+    fn set_bypassed(&mut self, _bypassed: bool) {}
+}
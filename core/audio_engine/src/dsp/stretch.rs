@@ -0,0 +1,155 @@
+//! Offline time-stretching: audio made longer or shorter at the same pitch, for clips that
+//! follow the project tempo, see [`Clip::set_follow_tempo`].
+//!
+//! [`Clip::set_follow_tempo`]: crate::track::timeline::Clip::set_follow_tempo
+
+use std::f32::consts::TAU;
+
+use crate::buffer::AudioBuffer;
+
+/// Length of the overlapping windows, long enough to hold a pitch period of bass notes
+const WINDOW_SECONDS: f64 = 0.04;
+/// How far a window may move to line up with the one before it
+const SEEK_SECONDS: f64 = 0.01;
+/// Compare every few samples when seeking, the alignment doesn't need them all
+const SEEK_STRIDE: usize = 4;
+/// Output samples covered by less window than this are left as they are
+const WEIGHT_EPSILON: f32 = 1e-6;
+
+/// `audio` played `speed` times as fast at the same pitch, e.g. 1.25 to bring a 96 BPM loop
+/// to 120 BPM, see [`TempoEstimate::stretch_ratio`].
+///
+/// Overlapping windows of the input are taken at the new speed, each moved by up to 10 ms to
+/// where it best continues the one before, and added up (WSOLA). Transients and loops come
+/// out well, ratios far from 1 smear.
+///
+/// [`TempoEstimate::stretch_ratio`]: crate::analysis::tempo::TempoEstimate::stretch_ratio
+#[must_use]
+pub fn time_stretch(audio: &AudioBuffer, speed: f64, sample_rate: f64) -> AudioBuffer {
+    let frames = audio.frames();
+    let length = (frames as f64 / speed).round() as usize;
+    let mut output = AudioBuffer::new(audio.channels(), length);
+    let window = ((WINDOW_SECONDS * sample_rate / 2.0) as usize).max(1) * 2;
+    let hop = window / 2;
+    let seek = (SEEK_SECONDS * sample_rate) as isize;
+    let shape: Vec<f32> = (0..window)
+        .map(|i| 0.5f32.mul_add(-(TAU * i as f32 / window as f32).cos(), 0.5))
+        .collect();
+    // windows are lined up on the sum of the channels, so they stay in phase with each other
+    let mono: Vec<f32> = (0..frames)
+        .map(|frame| {
+            (0..audio.channels())
+                .map(|channel| audio.channel(channel)[frame])
+                .sum()
+        })
+        .collect();
+
+    let mut weights = vec![0.0f32; length];
+    let mut previous: Option<isize> = None;
+    // the first window starts half a window early, so the start isn't faded in
+    let mut at = -(hop as isize);
+    while at < length as isize {
+        let nominal = (at as f64 * speed).round() as isize;
+        let from = previous.map_or(nominal, |previous| {
+            best_offset(&mono, previous + hop as isize, nominal, seek, hop)
+        });
+        for (i, &gain) in shape.iter().enumerate() {
+            let (Ok(out), Ok(input)) = (
+                usize::try_from(at + i as isize),
+                usize::try_from(from + i as isize),
+            ) else {
+                continue;
+            };
+            if out >= length || input >= frames {
+                continue;
+            }
+            for channel in 0..audio.channels() {
+                output.channel_mut(channel)[out] += audio.channel(channel)[input] * gain;
+            }
+            weights[out] += gain;
+        }
+        previous = Some(from);
+        at += hop as isize;
+    }
+
+    for channel in 0..output.channels() {
+        for (sample, &weight) in output.channel_mut(channel).iter_mut().zip(&weights) {
+            if weight > WEIGHT_EPSILON {
+                *sample /= weight;
+            }
+        }
+    }
+    output
+}
+
+/// Start of the window within `seek` of `nominal` whose first `len` frames are most like
+/// those at `natural`, where the previous window's audio carries on
+fn best_offset(mono: &[f32], natural: isize, nominal: isize, seek: isize, len: usize) -> isize {
+    let sample = |index: isize| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| mono.get(index))
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let score = |start: isize| -> f32 {
+        (0..len as isize)
+            .step_by(SEEK_STRIDE)
+            .map(|i| sample(start + i) * sample(natural + i))
+            .sum()
+    };
+    let mut best = (nominal, score(nominal));
+    for candidate in (nominal - seek..=nominal + seek).filter(|&candidate| candidate != nominal) {
+        let candidate_score = score(candidate);
+        if candidate_score > best.1 {
+            best = (candidate, candidate_score);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 8000.0;
+
+    fn sine(frequency: f64, frames: usize) -> AudioBuffer {
+        let mut buffer = AudioBuffer::mono(frames);
+        for (i, sample) in buffer.channel_mut(0).iter_mut().enumerate() {
+            *sample = (std::f64::consts::TAU * frequency * i as f64 / SAMPLE_RATE).sin() as f32;
+        }
+        buffer
+    }
+
+    /// Zero crossings going up per second, the pitch of a sine
+    fn frequency(buffer: &AudioBuffer) -> f64 {
+        let rising = buffer
+            .channel(0)
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        rising as f64 * SAMPLE_RATE / buffer.frames() as f64
+    }
+
+    #[test]
+    fn test_stretch_keeps_the_pitch() {
+        let audio = sine(200.0, 8000);
+        for speed in [0.8, 1.25] {
+            let stretched = time_stretch(&audio, speed, SAMPLE_RATE);
+            assert_eq!(stretched.frames(), (8000.0 / speed).round() as usize);
+            assert!((frequency(&stretched) - 200.0).abs() < 4.0, "{speed}");
+            let peak = stretched.peak(0, 0, stretched.frames());
+            assert!((0.9..1.1).contains(&peak), "{speed}: {peak}");
+        }
+    }
+
+    #[test]
+    fn test_unit_speed_leaves_audio_as_it_is() {
+        let audio = sine(330.0, 2000);
+        let stretched = time_stretch(&audio, 1.0, SAMPLE_RATE);
+        for (a, b) in audio.channel(0).iter().zip(stretched.channel(0)) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+}
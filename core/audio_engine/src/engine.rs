@@ -0,0 +1,1076 @@
+//! A scheduler running on an output device, set up the way most hosts want it.
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use rtrb::{Producer, RingBuffer};
+use transport::{clock::TempoClock, resolution::TickResolution};
+
+use crate::{
+    audio_pool::{AudioPool, SharedAudioPool},
+    automation::AutomationMode,
+    constants::AUTOMATION_RECORD_ROOM,
+    device_manager::{AudioDeviceManager, cpal_dm::CpalAudioDeviceManager},
+    diagnostics::diagnostics_channel,
+    error::{EngineError, SchedulingError, SettingsError},
+    events::EventBus,
+    metering::MeterBallistics,
+    mixer::Channel,
+    preset_library::PresetLibrary,
+    record::{RecordSettings, TakeWriter},
+    resample::ResampleQuality,
+    scheduler::{
+        Scheduler,
+        command::{ChannelChange, SchedulerCommand},
+        event::SchedulerEvent,
+        garbage::garbage_channel,
+    },
+    settings::EngineSettings,
+    snapshot::{SnapshotReader, snapshot_channel},
+    watchdog::{Watchdog, WatchdogConfig, WatchedSource},
+};
+
+/// Everything [`EngineBuilder`] sets up, with defaults that suit interactive playback
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// Name of the output device, `None` plays on the default one. Ignored for a custom
+    /// device.
+    pub output_device: Option<String>,
+    /// `None` runs at the output device's rate
+    pub sample_rate: Option<u32>,
+    /// Rate the device actually runs at, converted to from `sample_rate` when they differ.
+    /// `None` asks the output device, or assumes `sample_rate` for a custom one.
+    pub device_sample_rate: Option<u32>,
+    /// How the project's audio is converted to the device's rate
+    pub resample_quality: ResampleQuality,
+    pub bpm: f64,
+    pub resolution: TickResolution,
+    /// Fixed internal processing block in frames, `None` follows the device's callbacks
+    pub block_size: Option<usize>,
+    /// Commands that can be queued between two audio callbacks
+    pub command_capacity: usize,
+    /// Scheduler events that can be queued between two [`EventBus::pump`] calls
+    pub event_capacity: usize,
+    /// Master meter readings, one per rendered block, that can be queued between two
+    /// [`EventBus::pump`] calls. They have a ring of their own so they can't crowd out the
+    /// other events.
+    pub meter_capacity: usize,
+    /// Scene launches and clip starts and stops that can be queued between two
+    /// [`EventBus::pump`] calls. A launch waits for the next bar rather than drop any, see
+    /// [`Scheduler::set_launch_producer`].
+    pub launch_capacity: usize,
+    /// Retired tracks and channels that can wait to be dropped off the audio thread
+    pub garbage_capacity: usize,
+    /// How often retired tracks and channels are dropped, and the audio thread's
+    /// diagnostics forwarded to `tracing`
+    pub garbage_interval: Duration,
+    /// Audio thread diagnostics that can wait to be forwarded, see [`crate::diagnostics`]
+    pub diagnostics_capacity: usize,
+    /// Publishes [`EngineSnapshot`](crate::snapshot::EngineSnapshot)s for meters and UIs
+    pub snapshots: bool,
+    /// How long [`Engine::shutdown`] waits for the audio and collector threads
+    pub shutdown_timeout: Duration,
+    /// Most decoded audio [`Engine::audio_pool`] holds, in bytes. `None` is unlimited.
+    /// A pool shared with [`EngineBuilder::shared_audio_pool`] keeps its own budget.
+    pub audio_budget: Option<usize>,
+    /// Watches for the device to stop calling back, `None` doesn't
+    pub watchdog: Option<WatchdogConfig>,
+    /// Where [`Engine::presets`] finds presets, new ones are saved to the first
+    pub preset_dirs: Vec<PathBuf>,
+    /// How the input and correlation meters move
+    pub meter_ballistics: MeterBallistics,
+    /// How often [`Engine::autosave_due`] asks for an autosave, `None` never does
+    pub autosave_interval: Option<Duration>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            output_device: None,
+            sample_rate: None,
+            device_sample_rate: None,
+            resample_quality: ResampleQuality::default(),
+            bpm: 120.0,
+            resolution: TickResolution::Sixteenth,
+            block_size: None,
+            command_capacity: 128,
+            event_capacity: 128,
+            meter_capacity: 64,
+            launch_capacity: 256,
+            garbage_capacity: 64,
+            garbage_interval: Duration::from_millis(100),
+            diagnostics_capacity: 256,
+            snapshots: false,
+            shutdown_timeout: Duration::from_secs(2),
+            audio_budget: None,
+            watchdog: None,
+            preset_dirs: Vec::new(),
+            meter_ballistics: MeterBallistics::default(),
+            autosave_interval: None,
+        }
+    }
+}
+
+/// Wires a [`Scheduler`] to an output device with its command, event, garbage and
+/// snapshot channels.
+///
+/// # Example
+/// ```no_run
+/// use audio_engine::{
+///     engine::EngineBuilder,
+///     scheduler::command::SchedulerCommand,
+///     track::sinewave::SineWaveTrack,
+/// };
+///
+/// let engine = EngineBuilder::new().tempo(96.0).build()?;
+/// let handle = engine.handle();
+/// handle.send(SchedulerCommand::ScheduleTrack {
+///     track: Box::new(SineWaveTrack::new(440.0, engine.sample_rate() as f32)),
+///     start_frame: 0,
+/// })?;
+/// handle.send(SchedulerCommand::Play)?;
+/// # Ok::<(), audio_engine::error::EngineError>(())
+/// ```
+pub struct EngineBuilder {
+    config: EngineConfig,
+    device: Option<Box<dyn AudioDeviceManager>>,
+    audio_pool: Option<SharedAudioPool>,
+}
+
+impl EngineBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    #[must_use]
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self {
+            config,
+            device: None,
+            audio_pool: None,
+        }
+    }
+
+    #[must_use]
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.config.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Converts to a device running at `rate` Hz, see [`EngineConfig::device_sample_rate`]
+    #[must_use]
+    pub fn device_sample_rate(mut self, rate: u32) -> Self {
+        self.config.device_sample_rate = Some(rate);
+        self
+    }
+
+    #[must_use]
+    pub fn resample_quality(mut self, quality: ResampleQuality) -> Self {
+        self.config.resample_quality = quality;
+        self
+    }
+
+    #[must_use]
+    pub fn tempo(mut self, bpm: f64) -> Self {
+        self.config.bpm = bpm;
+        self
+    }
+
+    #[must_use]
+    pub fn resolution(mut self, resolution: TickResolution) -> Self {
+        self.config.resolution = resolution;
+        self
+    }
+
+    #[must_use]
+    pub fn block_size(mut self, block_size: Option<usize>) -> Self {
+        self.config.block_size = block_size;
+        self
+    }
+
+    #[must_use]
+    pub fn snapshots(mut self, enabled: bool) -> Self {
+        self.config.snapshots = enabled;
+        self
+    }
+
+    /// Caps the decoded audio held by [`Engine::audio_pool`] at `bytes`
+    #[must_use]
+    pub fn audio_budget(mut self, bytes: usize) -> Self {
+        self.config.audio_budget = Some(bytes);
+        self
+    }
+
+    /// Watches the audio callback, see [`Engine::check_watchdog`]
+    #[must_use]
+    pub fn watchdog(mut self, config: WatchdogConfig) -> Self {
+        self.config.watchdog = Some(config);
+        self
+    }
+
+    /// Looks for presets in `dir` too, see [`Engine::presets`]
+    #[must_use]
+    pub fn preset_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.config.preset_dirs.push(dir.into());
+        self
+    }
+
+    /// Plays on the output device called `name` instead of the default one
+    #[must_use]
+    pub fn output_device(mut self, name: &str) -> Self {
+        self.config.output_device = Some(name.to_owned());
+        self
+    }
+
+    /// Starts with the device, rate, block size, meter ballistics and autosave interval of
+    /// `settings`. Settings left unset keep what the builder has.
+    #[must_use]
+    pub fn settings(mut self, settings: &EngineSettings) -> Self {
+        if let Some(name) = &settings.output_device {
+            self.config.output_device = Some(name.clone());
+        }
+        self.config.sample_rate = settings.sample_rate.or(self.config.sample_rate);
+        self.config.block_size = settings.block_size.or(self.config.block_size);
+        self.config.meter_ballistics = settings.metering.unwrap_or(self.config.meter_ballistics);
+        self.config.autosave_interval = settings
+            .autosave_interval()
+            .or(self.config.autosave_interval);
+        self
+    }
+
+    /// Applies the settings file at `path`, see [`EngineBuilder::settings`]. A missing file
+    /// leaves the builder as it is.
+    ///
+    /// # Errors
+    /// If the file exists but can't be read or parsed.
+    pub fn load_settings(self, path: &Path) -> Result<Self, SettingsError> {
+        Ok(self.settings(&EngineSettings::load_or_default(path)?))
+    }
+
+    /// Loads audio through `pool`, shared with other engines, instead of a pool of its own
+    #[must_use]
+    pub fn shared_audio_pool(mut self, pool: SharedAudioPool) -> Self {
+        self.audio_pool = Some(pool);
+        self
+    }
+
+    /// Plays through `device` instead of the default cpal output
+    #[must_use]
+    pub fn device(mut self, device: Box<dyn AudioDeviceManager>) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Starts the engine on its output device
+    ///
+    /// # Errors
+    /// If the device can't be opened, the block size is invalid or a preset directory can't be
+    /// read.
+    pub fn build(self) -> Result<Engine, EngineError> {
+        self.build_with(|_| Ok(()))
+    }
+
+    /// Like [`EngineBuilder::build`], running `setup` on the scheduler before the stream
+    /// starts, e.g. to schedule a project's tracks without going through the command queue
+    ///
+    /// # Errors
+    /// If the device can't be opened, the block size is invalid, a preset directory can't be
+    /// read or `setup` fails.
+    pub fn build_with(
+        self,
+        setup: impl FnOnce(&mut Scheduler) -> Result<(), EngineError>,
+    ) -> Result<Engine, EngineError> {
+        let config = self.config;
+        let output_device = config.output_device.as_deref();
+        let sample_rate = match config.sample_rate {
+            Some(sample_rate) => sample_rate,
+            None => CpalAudioDeviceManager::output_sample_rate(output_device)?,
+        };
+        let device_sample_rate = match config.device_sample_rate {
+            Some(rate) => rate,
+            None if self.device.is_none() && config.sample_rate.is_some() => {
+                CpalAudioDeviceManager::output_sample_rate(output_device)?
+            }
+            None => sample_rate,
+        };
+
+        let mut presets = PresetLibrary::new();
+        for dir in &config.preset_dirs {
+            presets.add_dir(dir.clone());
+        }
+        presets.scan()?;
+
+        let (commands, consumer) = RingBuffer::new(config.command_capacity);
+        let tempo_clock = TempoClock::new(config.bpm, f64::from(sample_rate), config.resolution);
+        let mut scheduler = Scheduler::new(consumer, tempo_clock);
+        scheduler.set_block_size(config.block_size)?;
+        scheduler.set_device_sample_rate(device_sample_rate, config.resample_quality);
+        scheduler.set_meter_ballistics(config.meter_ballistics);
+
+        let (event_producer, event_consumer) = RingBuffer::new(config.event_capacity);
+        scheduler.set_event_producer(event_producer);
+        let (meter_producer, meter_consumer) = RingBuffer::new(config.meter_capacity);
+        scheduler.set_meter_producer(meter_producer);
+        let (launch_producer, launch_consumer) = RingBuffer::new(config.launch_capacity);
+        scheduler.set_launch_producer(launch_producer);
+
+        let snapshots = config.snapshots.then(|| {
+            let (publisher, reader) = snapshot_channel();
+            scheduler.set_snapshot_publisher(publisher);
+            reader
+        });
+
+        let (logger, drain) = diagnostics_channel(config.diagnostics_capacity);
+        scheduler.set_diagnostics_logger(logger);
+
+        let (garbage, collector) = garbage_channel(config.garbage_capacity);
+        scheduler.set_garbage_producer(garbage);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        scheduler.set_shutdown_signal(Arc::clone(&shutdown));
+
+        setup(&mut scheduler)?;
+
+        let mut device = self.device.unwrap_or_else(|| {
+            Box::new(output_device.map_or_else(
+                CpalAudioDeviceManager::new,
+                CpalAudioDeviceManager::with_output_device,
+            ))
+        });
+        let mut events = EventBus::new(event_consumer);
+        events.attach_meters(meter_consumer);
+        events.attach_launches(launch_consumer);
+        let watchdog = if let Some(watchdog) = config.watchdog {
+            let source = WatchedSource::new(Box::new(scheduler));
+            device.start_output_stream(Box::new(source.clone()))?;
+            let (dog, stalls) = Watchdog::channel(&source, watchdog.timeout);
+            events.attach(stalls);
+            Some(EngineWatchdog {
+                dog,
+                source,
+                restart: watchdog.restart,
+                restarted: false,
+            })
+        } else {
+            device.start_output_stream(Box::new(scheduler))?;
+            None
+        };
+
+        Ok(Engine {
+            handle: EngineHandle {
+                commands: Arc::new(Mutex::new(commands)),
+            },
+            events,
+            watchdog,
+            snapshots,
+            sample_rate,
+            device_sample_rate,
+            audio_pool: self.audio_pool.unwrap_or_else(|| {
+                SharedAudioPool::new(
+                    config
+                        .audio_budget
+                        .map_or_else(AudioPool::new, AudioPool::with_budget),
+                )
+            }),
+            presets,
+            autosave: config
+                .autosave_interval
+                .map(|interval| (interval, Instant::now())),
+            collector: Some(collector.spawn(config.garbage_interval)),
+            diagnostics: Some(drain.spawn(config.garbage_interval)),
+            device: Some(device),
+            shutdown,
+            shutdown_timeout: config.shutdown_timeout,
+            takes: Vec::new(),
+            next_take: 0,
+        })
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends commands to a running [`Engine`] from any thread
+#[derive(Clone)]
+pub struct EngineHandle {
+    commands: Arc<Mutex<Producer<SchedulerCommand>>>,
+}
+
+impl EngineHandle {
+    /// Queues `command` for the next audio callback
+    ///
+    /// # Errors
+    /// [`SchedulingError::CommandQueueFull`] if the audio thread has fallen behind.
+    pub fn send(&self, command: SchedulerCommand) -> Result<(), SchedulingError> {
+        let mut commands = self.commands.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut push = |command| {
+            commands
+                .push(command)
+                .map_err(|_| SchedulingError::CommandQueueFull)
+        };
+        // channels and automation storage are set up here, the audio thread only moves them
+        // into place
+        let command = match command {
+            SchedulerCommand::ScheduleTrack { track, start_frame } => {
+                SchedulerCommand::ScheduleChannel {
+                    channel: Box::new(Channel::new(track)),
+                    start_frame,
+                }
+            }
+            SchedulerCommand::ChannelChange {
+                target_id,
+                change:
+                    ChannelChange::SetAutomation {
+                        parameter,
+                        mut points,
+                    },
+            } => {
+                points.sort_by_key(|&(frame, _)| frame);
+                points.reserve_exact(AUTOMATION_RECORD_ROOM);
+                SchedulerCommand::ChannelChange {
+                    target_id,
+                    change: ChannelChange::SetAutomation { parameter, points },
+                }
+            }
+            SchedulerCommand::ChannelChange {
+                target_id,
+                change:
+                    ChannelChange::SetAutomationMode {
+                        parameter,
+                        mode: mode @ (AutomationMode::Write | AutomationMode::Latch),
+                    },
+            } => {
+                push(SchedulerCommand::ChannelChange {
+                    target_id: target_id.clone(),
+                    change: ChannelChange::ReserveAutomation {
+                        parameter,
+                        points: Vec::with_capacity(AUTOMATION_RECORD_ROOM),
+                    },
+                })?;
+                SchedulerCommand::ChannelChange {
+                    target_id,
+                    change: ChannelChange::SetAutomationMode { parameter, mode },
+                }
+            }
+            command => command,
+        };
+        push(command)
+    }
+}
+
+/// The watchdog of an [`Engine`] and what it needs to restart the stream
+struct EngineWatchdog {
+    dog: Watchdog,
+    source: WatchedSource,
+    restart: bool,
+    /// The stream was restarted during the current stall
+    restarted: bool,
+}
+
+/// A scheduler playing on an output device, built by [`EngineBuilder`]
+pub struct Engine {
+    handle: EngineHandle,
+    events: EventBus,
+    /// `None` unless enabled on the builder
+    watchdog: Option<EngineWatchdog>,
+    snapshots: Option<SnapshotReader>,
+    sample_rate: u32,
+    device_sample_rate: u32,
+    audio_pool: SharedAudioPool,
+    presets: PresetLibrary,
+    /// Autosave interval and when the last one was due
+    autosave: Option<(Duration, Instant)>,
+    collector: Option<JoinHandle<()>>,
+    /// Forwards the audio thread's diagnostics until the scheduler is dropped
+    diagnostics: Option<JoinHandle<()>>,
+    /// `None` once shut down
+    device: Option<Box<dyn AudioDeviceManager>>,
+    /// Set by the scheduler once it has retired everything for a shutdown
+    shutdown: Arc<AtomicBool>,
+    shutdown_timeout: Duration,
+    /// Takes being recorded, finalized on shutdown if the host hasn't closed them
+    takes: Vec<(TakeId, TakeWriter)>,
+    next_take: u64,
+}
+
+/// A take opened with [`Engine::open_take`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TakeId(u64);
+
+impl Engine {
+    pub fn handle(&self) -> EngineHandle {
+        self.handle.clone()
+    }
+
+    /// Scheduler events, call [`EventBus::pump`] regularly to deliver them
+    pub fn events(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// `None` unless snapshots were enabled on the builder
+    pub fn snapshots(&mut self) -> Option<&mut SnapshotReader> {
+        self.snapshots.as_mut()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Decoded audio shared by the tracks built for this engine, within
+    /// [`EngineConfig::audio_budget`], locked until the guard is dropped
+    pub fn audio_pool(&self) -> MutexGuard<'_, AudioPool> {
+        self.audio_pool.lock()
+    }
+
+    /// The audio pool, to share with another engine, see
+    /// [`EngineBuilder::shared_audio_pool`]
+    pub fn shared_audio_pool(&self) -> SharedAudioPool {
+        self.audio_pool.clone()
+    }
+
+    /// Presets in [`EngineConfig::preset_dirs`], for a preset browser
+    pub fn presets(&mut self) -> &mut PresetLibrary {
+        &mut self.presets
+    }
+
+    /// Whether the project should be autosaved now, `true` once every
+    /// [`EngineConfig::autosave_interval`]. The engine doesn't know the project, so call this
+    /// from the host's main loop and save when it says so.
+    pub fn autosave_due(&mut self) -> bool {
+        let Some((interval, last)) = self.autosave.as_mut() else {
+            return false;
+        };
+        let due = last.elapsed() >= *interval;
+        if due {
+            *last = Instant::now();
+        }
+        due
+    }
+
+    /// Rate the output device runs at, differs from [`Engine::sample_rate`] while the SRC
+    /// stage converts
+    pub fn device_sample_rate(&self) -> u32 {
+        self.device_sample_rate
+    }
+
+    /// Whether the device has stopped calling back, `false` without a watchdog.
+    ///
+    /// With [`WatchdogConfig::restart`], a stall rebuilds the output stream on the current
+    /// thread, once per stall, and publishes [`SchedulerEvent::StreamRestarted`] on the event
+    /// bus. Streams can't move between threads, so call this regularly alongside
+    /// [`EventBus::pump`].
+    ///
+    /// # Errors
+    /// If the stream can't be rebuilt.
+    pub fn check_watchdog(&mut self) -> Result<bool, EngineError> {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return Ok(false);
+        };
+        if !watchdog.dog.is_stalled() {
+            watchdog.restarted = false;
+            return Ok(false);
+        }
+        if watchdog.restart
+            && !watchdog.restarted
+            && let Some(device) = self.device.as_mut()
+        {
+            watchdog.restarted = true;
+            device.start_output_stream(Box::new(watchdog.source.clone()))?;
+            self.events.publish(&SchedulerEvent::StreamRestarted);
+        }
+        Ok(true)
+    }
+
+    /// Shuts the engine down in order: playback stops and the audio thread hands its tracks
+    /// to the garbage collector, the stream is closed and open takes are finalized, then the
+    /// collector drops what's left and its thread is joined. Dropping the engine does the
+    /// same, ignoring errors.
+    ///
+    /// # Errors
+    /// [`EngineError::Export`] if a take couldn't be finalized,
+    /// [`SchedulingError::ShutdownTimedOut`] if the audio thread didn't respond or the
+    /// collector didn't finish within the configured timeout. The stream is closed either way.
+    pub fn shutdown(mut self) -> Result<(), EngineError> {
+        self.stop()
+    }
+
+    /// Opens a WAV file at `path` for a take at the engine's sample rate, see
+    /// [`RecordSettings::create_writer`]. Takes still open when the engine shuts down are
+    /// finalized then.
+    ///
+    /// # Errors
+    /// [`EngineError::Export`] if the file can't be created.
+    pub fn open_take(
+        &mut self,
+        settings: &RecordSettings,
+        path: &Path,
+    ) -> Result<TakeId, EngineError> {
+        let writer = settings.create_writer(path, self.sample_rate)?;
+        let id = TakeId(self.next_take);
+        self.next_take += 1;
+        self.takes.push((id, writer));
+        Ok(id)
+    }
+
+    /// The open take `id`, to write recorded blocks to
+    pub fn take_mut(&mut self, id: TakeId) -> Option<&mut TakeWriter> {
+        self.takes
+            .iter_mut()
+            .find(|(take, _)| *take == id)
+            .map(|(_, writer)| writer)
+    }
+
+    /// Finalizes the take `id`, `None` if it isn't open
+    pub fn close_take(&mut self, id: TakeId) -> Option<Result<(), EngineError>> {
+        let index = self.takes.iter().position(|(take, _)| *take == id)?;
+        Some(self.takes.remove(index).1.finalize())
+    }
+
+    fn stop(&mut self) -> Result<(), EngineError> {
+        let Some(device) = self.device.take() else {
+            return Ok(());
+        };
+        // a stopping stream isn't a stall
+        self.watchdog = None;
+        let deadline = Instant::now() + self.shutdown_timeout;
+
+        let released = self.handle.send(SchedulerCommand::Shutdown).is_ok()
+            && wait_until(deadline, || self.shutdown.load(Ordering::Acquire));
+        drop(device);
+
+        // nothing is recorded once the stream is closed
+        let finalized = self
+            .takes
+            .drain(..)
+            .map(|(_, writer)| writer.finalize())
+            .fold(Ok(()), Result::and);
+
+        let collected = self
+            .collector
+            .take()
+            .is_none_or(|collector| wait_until(deadline, || collector.is_finished()));
+        // the last diagnostics are worth waiting for, but not failing over
+        if let Some(diagnostics) = self.diagnostics.take() {
+            wait_until(deadline, || diagnostics.is_finished());
+        }
+        finalized?;
+        if released && collected {
+            Ok(())
+        } else {
+            Err(SchedulingError::ShutdownTimedOut(self.shutdown_timeout).into())
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Independent engines running side by side in one process, e.g. one per project tab, or a
+/// second project previewed while the first plays.
+///
+/// Each engine has its own scheduler, device and command and event channels; they share
+/// one [`SharedAudioPool`], so audio used by several projects is decoded once.
+pub struct EngineGroup {
+    audio_pool: SharedAudioPool,
+    engines: Vec<(String, Engine)>,
+}
+
+impl EngineGroup {
+    /// A group loading audio through `pool`
+    #[must_use]
+    pub fn new(pool: AudioPool) -> Self {
+        Self {
+            audio_pool: SharedAudioPool::new(pool),
+            engines: Vec::new(),
+        }
+    }
+
+    /// The pool every engine of the group loads through
+    #[must_use]
+    pub fn audio_pool(&self) -> &SharedAudioPool {
+        &self.audio_pool
+    }
+
+    /// Builds an engine named `name` from `builder`, sharing the group's audio pool. An
+    /// engine of the same name is shut down and replaced.
+    ///
+    /// # Errors
+    /// If the old engine didn't shut down cleanly, or the new one can't be built.
+    pub fn open(&mut self, name: &str, builder: EngineBuilder) -> Result<&mut Engine, EngineError> {
+        self.close(name)?;
+        let engine = builder.shared_audio_pool(self.audio_pool.clone()).build()?;
+        self.engines.push((name.to_owned(), engine));
+        Ok(&mut self.engines.last_mut().expect("just pushed").1)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Engine> {
+        self.engines
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, engine)| engine)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Engine> {
+        self.engines
+            .iter_mut()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, engine)| engine)
+    }
+
+    /// Names of the engines, in the order they were opened
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.engines.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Every engine with its name, e.g. to pump their events
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Engine)> {
+        self.engines
+            .iter_mut()
+            .map(|(name, engine)| (name.as_str(), engine))
+    }
+
+    /// Shuts the engine named `name` down, see [`Engine::shutdown`]. The others keep playing.
+    ///
+    /// # Errors
+    /// If it didn't shut down cleanly.
+    pub fn close(&mut self, name: &str) -> Result<(), EngineError> {
+        match self
+            .engines
+            .iter()
+            .position(|(existing, _)| existing == name)
+        {
+            Some(index) => self.engines.remove(index).1.shutdown(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for EngineGroup {
+    fn default() -> Self {
+        Self::new(AudioPool::new())
+    }
+}
+
+/// Polls `done` until it returns `true` or `deadline` passes
+fn wait_until(deadline: Instant, mut done: impl FnMut() -> bool) -> bool {
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buffer::AudioBuffer,
+        device_manager::{AudioSource, AudioSourceBufferKind},
+        error::DeviceError,
+        events::{CallbackStalled, StreamRestarted, TransportChanged},
+        track::{Track, constant::ConstantTrack},
+    };
+
+    type Stream = Arc<Mutex<Option<Box<dyn AudioSource>>>>;
+
+    /// Keeps the source so the test can play the audio thread's part
+    struct ManualDevice(Stream);
+
+    impl AudioDeviceManager for ManualDevice {
+        fn start_output_stream(
+            &mut self,
+            audio_source: Box<dyn AudioSource>,
+        ) -> Result<(), DeviceError> {
+            *self.0.lock().unwrap() = Some(audio_source);
+            Ok(())
+        }
+    }
+
+    impl Drop for ManualDevice {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().take();
+        }
+    }
+
+    /// Silent track that tells when it has been dropped
+    struct Probe(#[expect(dead_code, reason = "only held to count references")] Arc<()>);
+
+    impl Track for Probe {
+        fn id(&self) -> String {
+            "probe".to_owned()
+        }
+
+        fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+            buffer.clear();
+        }
+    }
+
+    fn engine(stream: &Stream) -> Engine {
+        EngineBuilder::new()
+            .sample_rate(48000)
+            .device(Box::new(ManualDevice(stream.clone())))
+            .build()
+            .unwrap()
+    }
+
+    /// Plays the audio thread: renders small blocks until the stream is closed
+    fn run_audio_thread(stream: Stream) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut output = [0.0f32; 128];
+            while let Some(source) = stream.lock().unwrap().as_mut() {
+                source.fill_buffer(AudioSourceBufferKind::F32(&mut output), 64);
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        })
+    }
+
+    #[test]
+    fn test_engine_plays_commands_sent_through_the_handle() {
+        let stream = Stream::default();
+        let mut engine = engine(&stream);
+        let transport = engine.events().subscribe::<TransportChanged>();
+
+        let handle = engine.handle();
+        handle
+            .send(SchedulerCommand::ScheduleTrack {
+                track: Box::new(ConstantTrack::new(0.5, 0.25)),
+                start_frame: 0,
+            })
+            .unwrap();
+        handle.send(SchedulerCommand::Play).unwrap();
+
+        let mut output = [0.0f32; 8];
+        if let Some(source) = stream.lock().unwrap().as_mut() {
+            source.fill_buffer(AudioSourceBufferKind::F32(&mut output), 4);
+        }
+        assert_eq!(&output[6..], &[0.5, 0.25]);
+
+        engine.events().pump();
+        assert_eq!(transport.drain().len(), 1);
+        assert_eq!(engine.sample_rate(), 48000);
+        assert_eq!(engine.device_sample_rate(), 48000);
+        assert_eq!(engine.audio_pool().usage().budget, None);
+
+        let audio = run_audio_thread(stream.clone());
+        engine.shutdown().unwrap();
+        audio.join().unwrap();
+        assert!(stream.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_engine_starts_with_the_settings() {
+        let stream = Stream::default();
+        let settings = EngineSettings {
+            sample_rate: Some(44100),
+            autosave_seconds: Some(0),
+            ..EngineSettings::default()
+        };
+        let mut engine = EngineBuilder::new()
+            .sample_rate(48000)
+            .settings(&settings)
+            .device(Box::new(ManualDevice(stream.clone())))
+            .build()
+            .unwrap();
+        assert_eq!(engine.sample_rate(), 44100);
+        assert!(engine.autosave_due());
+
+        let mut unsaved = self::engine(&Stream::default());
+        assert!(!unsaved.autosave_due());
+
+        // ballistics the settings don't mention stay as the builder has them
+        let mut builder = EngineBuilder::new();
+        builder.config.meter_ballistics.peak_release_db_per_second = 6.0;
+        let builder = builder.settings(&settings);
+        assert_eq!(
+            builder.config.meter_ballistics.peak_release_db_per_second,
+            6.0
+        );
+    }
+
+    #[test]
+    fn test_engines_in_a_group_play_independently_and_share_audio() {
+        let mut group = EngineGroup::default();
+        let streams = [Stream::default(), Stream::default()];
+        for (name, stream) in ["song", "preview"].into_iter().zip(&streams) {
+            let builder = EngineBuilder::new()
+                .sample_rate(48000)
+                .device(Box::new(ManualDevice(stream.clone())));
+            group.open(name, builder).unwrap();
+        }
+        let kick = group
+            .audio_pool()
+            .lock()
+            .insert("kick.wav".into(), AudioBuffer::stereo(16))
+            .unwrap();
+        let preview = group.get("preview").unwrap();
+        assert!(Arc::ptr_eq(
+            &preview.audio_pool().load("kick.wav").unwrap(),
+            &kick
+        ));
+
+        let handle = group.get("song").unwrap().handle();
+        handle
+            .send(SchedulerCommand::ScheduleTrack {
+                track: Box::new(ConstantTrack::new(0.5, 0.25)),
+                start_frame: 0,
+            })
+            .unwrap();
+        handle.send(SchedulerCommand::Play).unwrap();
+        let render = |stream: &Stream| {
+            let mut output = [0.0f32; 8];
+            if let Some(source) = stream.lock().unwrap().as_mut() {
+                source.fill_buffer(AudioSourceBufferKind::F32(&mut output), 4);
+            }
+            output
+        };
+        assert_eq!(&render(&streams[0])[6..], &[0.5, 0.25]);
+        assert_eq!(render(&streams[1]), [0.0; 8]);
+
+        let audio = run_audio_thread(streams[1].clone());
+        group.close("preview").unwrap();
+        audio.join().unwrap();
+        assert!(streams[1].lock().unwrap().is_none());
+        assert_eq!(group.names().collect::<Vec<_>>(), ["song"]);
+        assert_eq!(&render(&streams[0])[6..], &[0.5, 0.25]);
+
+        let audio = run_audio_thread(streams[0].clone());
+        group.close("song").unwrap();
+        audio.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_drops_every_track_off_the_audio_thread() {
+        let stream = Stream::default();
+        let engine = engine(&stream);
+        let queued = Arc::new(());
+        let preview = Arc::new(());
+        let handle = engine.handle();
+        handle
+            .send(SchedulerCommand::ScheduleTrack {
+                track: Box::new(Probe(queued.clone())),
+                start_frame: 1_000_000,
+            })
+            .unwrap();
+        handle
+            .send(SchedulerCommand::Preview(Box::new(Probe(preview.clone()))))
+            .unwrap();
+        handle.send(SchedulerCommand::Play).unwrap();
+
+        let audio = run_audio_thread(stream);
+        engine.shutdown().unwrap();
+        audio.join().unwrap();
+
+        assert_eq!(Arc::strong_count(&queued), 1);
+        assert_eq!(Arc::strong_count(&preview), 1);
+    }
+
+    #[test]
+    fn test_shutdown_finalizes_open_takes_with_a_full_event_ring() {
+        let stream = Stream::default();
+        let config = EngineConfig {
+            sample_rate: Some(48000),
+            event_capacity: 1,
+            ..EngineConfig::default()
+        };
+        let mut engine = EngineBuilder::with_config(config)
+            .device(Box::new(ManualDevice(stream.clone())))
+            .build()
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("freqform-takes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vox-take1.wav");
+        let take = engine.open_take(&RecordSettings::default(), &path).unwrap();
+        engine
+            .take_mut(take)
+            .unwrap()
+            .write(&AudioBuffer::from_frames(&[(0.5, 0.5); 16]))
+            .unwrap();
+        // fills the event ring nobody pumps, ShutdownReady can't get through
+        engine.handle().send(SchedulerCommand::Play).unwrap();
+
+        let audio = run_audio_thread(stream);
+        engine.shutdown().unwrap();
+        audio.join().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 16);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watchdog_restarts_a_stalled_stream() {
+        let stream = Stream::default();
+        let mut engine = EngineBuilder::new()
+            .sample_rate(48000)
+            .watchdog(WatchdogConfig {
+                timeout: Duration::from_millis(20),
+                restart: true,
+            })
+            .device(Box::new(ManualDevice(stream.clone())))
+            .build()
+            .unwrap();
+        let stalls = engine.events().subscribe::<CallbackStalled>();
+        let restarts = engine.events().subscribe::<StreamRestarted>();
+
+        // nothing plays the stream
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert!(wait_until(deadline, || engine.check_watchdog().unwrap()));
+        assert!(engine.check_watchdog().unwrap());
+        engine.events().pump();
+        assert_eq!(stalls.drain().len(), 1);
+        assert_eq!(restarts.drain().len(), 1);
+
+        // the restarted stream plays the same scheduler
+        engine
+            .handle()
+            .send(SchedulerCommand::ScheduleTrack {
+                track: Box::new(ConstantTrack::new(0.5, 0.25)),
+                start_frame: 0,
+            })
+            .unwrap();
+        engine.handle().send(SchedulerCommand::Play).unwrap();
+        let mut output = [0.0f32; 8];
+        if let Some(source) = stream.lock().unwrap().as_mut() {
+            source.fill_buffer(AudioSourceBufferKind::F32(&mut output), 4);
+        }
+        assert_eq!(&output[6..], &[0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_shutdown_times_out_without_an_audio_thread() {
+        let stream = Stream::default();
+        let config = EngineConfig {
+            sample_rate: Some(48000),
+            shutdown_timeout: Duration::from_millis(20),
+            ..EngineConfig::default()
+        };
+        let engine = EngineBuilder::with_config(config)
+            .device(Box::new(ManualDevice(stream.clone())))
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            engine.shutdown(),
+            Err(EngineError::Scheduling(SchedulingError::ShutdownTimedOut(
+                _
+            )))
+        ));
+        // the stream is closed anyway
+        assert!(stream.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Every failure the engine reports, grouped by the subsystem it came from
+/// so hosts can match on the kind of failure rather than parse messages.
+///
+/// # Example
+/// ```no_run
+/// use audio_engine::{error::{DecodeError, EngineError}, track::wav::WavTrack};
+///
+/// match WavTrack::from_file("missing.wav").map_err(EngineError::from) {
+///     Err(EngineError::Decode(DecodeError::File { path, .. })) => {
+///         eprintln!("could not load {}", path.display());
+///     }
+///     Err(other) => eprintln!("{other}"),
+///     Ok(_) => {}
+/// }
+/// ```
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error(transparent)]
+    Device(#[from] DeviceError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Scheduling(#[from] SchedulingError),
+    #[error(transparent)]
+    Routing(#[from] RoutingError),
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    Preset(#[from] PresetError),
+    #[error(transparent)]
+    Sfz(#[from] SfzError),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+    #[error(transparent)]
+    CueList(#[from] CueListError),
+    #[error(transparent)]
+    AudioPool(#[from] AudioPoolError),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+    #[error("Failed to write {}: {source}", path.display())]
+    Export {
+        path: PathBuf,
+        #[source]
+        source: hound::Error,
+    },
+    #[error("Script error: {0}")]
+    Script(String),
+}
+
+/// Audio and MIDI device failures
+#[derive(Debug, Clone, Error)]
+pub enum DeviceError {
+    #[error("No output device available")]
+    NotFound,
+    #[error("Failed to query device: {0}")]
+    QueryFailed(String),
+    #[error("Failed to build stream: {0}")]
+    StreamBuildFailed(String),
+    #[error("Failed to start stream: {0}")]
+    StreamStartFailed(String),
+    #[error("MIDI device error: {0}")]
+    Midi(String),
+    #[error("JACK error: {0}")]
+    Jack(String),
+    #[error("Network stream error: {0}")]
+    Network(String),
+}
+
+/// Failures while loading audio material
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("Failed to read {}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to read audio stream: {0}")]
+    Stream(#[source] std::io::Error),
+    #[error("Unknown audio file format")]
+    UnknownFormat,
+    #[error("Malformed WAV: {0}")]
+    Malformed(&'static str),
+    #[error("Unsupported sample format {format} at {bits} bits")]
+    UnsupportedFormat { format: u16, bits: u16 },
+    #[error("Unsupported channel count: {0}")]
+    UnsupportedChannels(u16),
+    #[error("Unsupported sample rate: {0} Hz")]
+    UnsupportedSampleRate(u32),
+    #[error("Audio is {frames} frames long, more than the {limit} allowed")]
+    TooLong { frames: usize, limit: usize },
+}
+
+/// Failures loading audio into an [`AudioPool`](crate::audio_pool::AudioPool)
+#[derive(Debug, Error)]
+pub enum AudioPoolError {
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(
+        "{} needs {requested} bytes, {used} of the audio pool's {budget} are in use",
+        path.display()
+    )]
+    OverBudget {
+        path: PathBuf,
+        requested: usize,
+        used: usize,
+        budget: usize,
+    },
+}
+
+/// Failures handing work to the scheduler
+#[derive(Debug, Clone, Error)]
+pub enum SchedulingError {
+    #[error("Scheduler command queue is full")]
+    CommandQueueFull,
+    #[error("Block size must be between 1 and {max} frames, got {0}", max = crate::constants::MAX_BLOCK_FRAMES)]
+    InvalidBlockSize(usize),
+    #[error("Nothing to bounce: {0} doesn't exist")]
+    UnknownRange(String),
+    #[error("Engine didn't shut down within {0:?}")]
+    ShutdownTimedOut(std::time::Duration),
+}
+
+/// Failures resolving where a command or signal should go
+#[derive(Debug, Clone, Error)]
+pub enum RoutingError {
+    #[error("No track with id '{0}'")]
+    UnknownTarget(String),
+    #[error("No {0} in the routing graph")]
+    UnknownNode(String),
+    #[error("Routing {from} to {to} would create a feedback loop")]
+    Cycle { from: String, to: String },
+    #[error("Can't route {from} to {to}")]
+    InvalidConnection { from: String, to: String },
+}
+
+/// Failures reading or writing project files
+#[derive(Debug, Error)]
+pub enum ProjectError {
+    #[error("Failed to access project {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to parse project: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to encode project: {0}")]
+    Encode(#[from] toml::ser::Error),
+}
+
+/// Failures exporting or importing session archives
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("Failed to archive {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+    #[error(transparent)]
+    Media(#[from] DecodeError),
+}
+
+/// Failures importing a cue list or EDL
+#[derive(Debug, Error)]
+pub enum CueListError {
+    #[error("Failed to read cue list {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("The cue list has no {0} column")]
+    MissingColumn(&'static str),
+    #[error("Line {line}: invalid {field} '{value}'")]
+    Field {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+    #[error(transparent)]
+    Media(#[from] DecodeError),
+}
+
+/// Failures reading or writing preset files
+#[derive(Debug, Error)]
+pub enum PresetError {
+    #[error("Failed to access preset {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to parse preset: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to encode preset: {0}")]
+    Encode(#[from] toml::ser::Error),
+    #[error("No preset directory to save to")]
+    NoLibraryDirectory,
+    #[error("Preset {} can't be tagged, its format has no room for tags", .0.display())]
+    Untaggable(PathBuf),
+}
+
+/// Failures reading or writing the engine settings file
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Failed to access settings {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to parse settings: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to encode settings: {0}")]
+    Encode(#[from] toml::ser::Error),
+}
+
+/// Failures loading an SFZ instrument
+#[derive(Debug, Error)]
+pub enum SfzError {
+    #[error("Failed to read instrument {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Line {line}: invalid value '{value}' for {opcode}")]
+    Opcode {
+        line: usize,
+        opcode: String,
+        value: String,
+    },
+    #[error(transparent)]
+    Sample(#[from] DecodeError),
+}
@@ -0,0 +1,593 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use rtrb::Consumer;
+use transport::{display::Timecode, transport::TransportState};
+
+use crate::{
+    mix_snapshot::MixSnapshot,
+    scheduler::event::{SchedulerEvent, SchedulerEventConsumer},
+};
+
+/// A typed view over [`SchedulerEvent`] that hosts can subscribe to on an [`EventBus`]
+pub trait EngineEvent: Send + Sized + 'static {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransportChanged {
+    pub state: TransportState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackScheduled {
+    pub target_id: String,
+    pub start_frame: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackRemoved {
+    pub target_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackFinished {
+    pub target_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarStarted {
+    pub bar: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviewFinished;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReady;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterFrame {
+    /// Timeline frame the peaks are heard at, see [`SchedulerEvent::Meter`]
+    pub frame: u64,
+    pub peak_left: f32,
+    pub peak_right: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoFrame {
+    /// Timeline frame the picture changes at, see [`SchedulerEvent::VideoFrame`]
+    pub frame: u64,
+    pub video_frame: u64,
+    pub timecode: Timecode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordEnabled {
+    /// Timeline frame recording switched at, see [`SchedulerEvent::RecordEnabled`]
+    pub frame: u64,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPassCompleted {
+    /// Passes around the loop so far, see [`SchedulerEvent::LoopPassCompleted`]
+    pub pass: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixCaptured {
+    /// The captured mix, see [`SchedulerEvent::MixCaptured`]
+    pub snapshot: MixSnapshot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneLaunched {
+    /// Position of the scene among the launcher's, see [`SchedulerEvent::SceneLaunched`]
+    pub scene: usize,
+    /// Timeline frame the scene's clips play from, see [`SchedulerEvent::SceneLaunched`]
+    pub frame: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipLaunched {
+    pub scene: usize,
+    /// Position of the clip's [`ClipLaunch`](crate::scene::ClipLaunch) in the scene
+    pub launch: usize,
+    /// Timeline frame the clip plays from, see [`SchedulerEvent::ClipLaunched`]
+    pub frame: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipStopped {
+    pub scene: usize,
+    /// Position of the clip's [`ClipLaunch`](crate::scene::ClipLaunch) in the scene
+    pub launch: usize,
+    /// Timeline frame the clip is silent from, see [`SchedulerEvent::ClipStopped`]
+    pub frame: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClippingFound {
+    /// Track or render the overs are in
+    pub source: String,
+    /// Timeline frames the overs span, see [`SchedulerEvent::ClippingFound`]
+    pub start: u64,
+    pub end: u64,
+    /// Highest true peak, in dBTP
+    pub peak_db: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallbackStalled {
+    /// Time since the last callback, see [`SchedulerEvent::CallbackStalled`]
+    pub silent_for: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallbackResumed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamRestarted;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorRaised {
+    pub message: String,
+}
+
+/// Subscribing to `SchedulerEvent` itself receives every event
+impl EngineEvent for SchedulerEvent {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        Some(event.clone())
+    }
+}
+
+impl EngineEvent for TransportChanged {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::TransportChanged { state } => Some(Self { state: *state }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for TrackScheduled {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::TrackScheduled {
+                target_id,
+                start_frame,
+            } => Some(Self {
+                target_id: target_id.to_string(),
+                start_frame: *start_frame,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for TrackRemoved {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::TrackRemoved { target_id } => Some(Self {
+                target_id: target_id.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for TrackFinished {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::TrackFinished { target_id } => Some(Self {
+                target_id: target_id.to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for BarStarted {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::BarStarted { bar } => Some(Self { bar: *bar }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for PreviewFinished {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        matches!(event, SchedulerEvent::PreviewFinished).then_some(Self)
+    }
+}
+
+impl EngineEvent for ShutdownReady {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        matches!(event, SchedulerEvent::ShutdownReady).then_some(Self)
+    }
+}
+
+impl EngineEvent for MeterFrame {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::Meter {
+                frame,
+                peak_left,
+                peak_right,
+            } => Some(Self {
+                frame: *frame,
+                peak_left: *peak_left,
+                peak_right: *peak_right,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for VideoFrame {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::VideoFrame {
+                frame,
+                video_frame,
+                timecode,
+            } => Some(Self {
+                frame: *frame,
+                video_frame: *video_frame,
+                timecode: *timecode,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for RecordEnabled {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::RecordEnabled { frame, enabled } => Some(Self {
+                frame: *frame,
+                enabled: *enabled,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for LoopPassCompleted {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::LoopPassCompleted { pass } => Some(Self { pass: *pass }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for MixCaptured {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::MixCaptured { capture } => Some(Self {
+                snapshot: capture.snapshot().clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for SceneLaunched {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::SceneLaunched { scene, frame } => Some(Self {
+                scene: *scene,
+                frame: *frame,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for ClipLaunched {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::ClipLaunched {
+                scene,
+                launch,
+                frame,
+            } => Some(Self {
+                scene: *scene,
+                launch: *launch,
+                frame: *frame,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for ClipStopped {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::ClipStopped {
+                scene,
+                launch,
+                frame,
+            } => Some(Self {
+                scene: *scene,
+                launch: *launch,
+                frame: *frame,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for ClippingFound {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::ClippingFound {
+                source,
+                start,
+                end,
+                peak_db,
+            } => Some(Self {
+                source: source.clone(),
+                start: *start,
+                end: *end,
+                peak_db: *peak_db,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for CallbackStalled {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::CallbackStalled { silent_for } => Some(Self {
+                silent_for: *silent_for,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl EngineEvent for CallbackResumed {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        matches!(event, SchedulerEvent::CallbackResumed).then_some(Self)
+    }
+}
+
+impl EngineEvent for StreamRestarted {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        matches!(event, SchedulerEvent::StreamRestarted).then_some(Self)
+    }
+}
+
+impl EngineEvent for ErrorRaised {
+    fn from_scheduler_event(event: &SchedulerEvent) -> Option<Self> {
+        match event {
+            SchedulerEvent::Error { message } => Some(Self {
+                message: message.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Receiving end of an [`EventBus`] subscription. Dropping it unsubscribes.
+pub struct Subscription<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Subscription<T> {
+    #[must_use]
+    pub fn try_next(&self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Blocks until an event arrives or `timeout` elapses
+    #[must_use]
+    pub fn next_timeout(&self, timeout: Duration) -> Option<T> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// Every event delivered since the last call
+    #[must_use]
+    pub fn drain(&self) -> Vec<T> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+trait Dispatch: Send {
+    /// Returns `false` once the subscriber has gone away
+    fn dispatch(&self, event: &SchedulerEvent) -> bool;
+}
+
+struct TypedDispatch<T> {
+    sender: Sender<T>,
+}
+
+impl<T: EngineEvent> Dispatch for TypedDispatch<T> {
+    fn dispatch(&self, event: &SchedulerEvent) -> bool {
+        T::from_scheduler_event(event).is_none_or(|event| self.sender.send(event).is_ok())
+    }
+}
+
+/// Fans scheduler events out to typed subscribers on the non-RT side.
+///
+/// The bus owns the consumer end of the scheduler's event ring; call [`EventBus::pump`]
+/// regularly (e.g. from a UI timer) to move events from the audio thread to subscribers.
+///
+/// # Example
+/// ```no_run
+/// # fn run(events: audio_engine::scheduler::event::SchedulerEventConsumer) {
+/// use audio_engine::events::{EventBus, TransportChanged};
+///
+/// let mut bus = EventBus::new(events);
+/// let transport = bus.subscribe::<TransportChanged>();
+///
+/// bus.pump();
+/// for change in transport.drain() {
+///     println!("transport is now {:?}", change.state);
+/// }
+/// # }
+/// ```
+pub struct EventBus {
+    source: SchedulerEventConsumer,
+    /// The scheduler's meter ring, see [`EventBus::attach_meters`]
+    meters: Option<SchedulerEventConsumer>,
+    /// The scheduler's launch ring, see [`EventBus::attach_launches`]
+    launches: Option<SchedulerEventConsumer>,
+    /// Events raised on other threads, see [`EventBus::attach`]
+    attached: Vec<Receiver<SchedulerEvent>>,
+    subscribers: Vec<Box<dyn Dispatch>>,
+}
+
+impl EventBus {
+    pub fn new(source: SchedulerEventConsumer) -> Self {
+        Self {
+            source,
+            meters: None,
+            launches: None,
+            attached: Vec::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Also delivers the events sent to `source` on every [`EventBus::pump`], e.g. a
+    /// [`Watchdog`](crate::watchdog::Watchdog)'s
+    pub fn attach(&mut self, source: Receiver<SchedulerEvent>) {
+        self.attached.push(source);
+    }
+
+    /// Also delivers the meters sent to `meters`, see
+    /// [`Scheduler::set_meter_producer`](crate::scheduler::Scheduler::set_meter_producer)
+    pub fn attach_meters(&mut self, meters: SchedulerEventConsumer) {
+        self.meters = Some(meters);
+    }
+
+    /// Also delivers the scene launches and clip starts and stops sent to `launches`, see
+    /// [`Scheduler::set_launch_producer`](crate::scheduler::Scheduler::set_launch_producer)
+    pub fn attach_launches(&mut self, launches: SchedulerEventConsumer) {
+        self.launches = Some(launches);
+    }
+
+    pub fn subscribe<T: EngineEvent>(&mut self) -> Subscription<T> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Box::new(TypedDispatch { sender }));
+        Subscription { receiver }
+    }
+
+    /// Delivers an event raised off the audio thread (loader failures, device errors, ...)
+    pub fn publish(&mut self, event: &SchedulerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.dispatch(event));
+    }
+
+    /// Moves all pending scheduler and attached events to subscribers, returning how many
+    /// were delivered
+    pub fn pump(&mut self) -> usize {
+        let mut count = 0;
+        while let Ok(event) = self.source.pop() {
+            self.publish(&event);
+            count += 1;
+        }
+        while let Some(Ok(event)) = self.meters.as_mut().map(Consumer::pop) {
+            self.publish(&event);
+            count += 1;
+        }
+        while let Some(Ok(event)) = self.launches.as_mut().map(Consumer::pop) {
+            self.publish(&event);
+            count += 1;
+        }
+        let attached = std::mem::take(&mut self.attached);
+        for source in &attached {
+            for event in source.try_iter() {
+                self.publish(&event);
+                count += 1;
+            }
+        }
+        self.attached = attached;
+        count
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rtrb::RingBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_subscribers_only_receive_their_type() {
+        let (mut prod, cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(cons);
+        let transport = bus.subscribe::<TransportChanged>();
+        let bars = bus.subscribe::<BarStarted>();
+
+        prod.push(SchedulerEvent::TransportChanged {
+            state: TransportState::Playing,
+        })
+        .unwrap();
+        prod.push(SchedulerEvent::BarStarted { bar: 2 }).unwrap();
+        prod.push(SchedulerEvent::BarStarted { bar: 3 }).unwrap();
+
+        assert_eq!(bus.pump(), 3);
+        assert_eq!(
+            transport.drain(),
+            vec![TransportChanged {
+                state: TransportState::Playing
+            }]
+        );
+        assert_eq!(
+            bars.drain(),
+            vec![BarStarted { bar: 2 }, BarStarted { bar: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_dropped_subscription_is_pruned() {
+        let (mut prod, cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(cons);
+        let kept = bus.subscribe::<TrackFinished>();
+        drop(bus.subscribe::<TrackFinished>());
+
+        prod.push(SchedulerEvent::TrackFinished {
+            target_id: "a".into(),
+        })
+        .unwrap();
+        bus.pump();
+
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(kept.try_next().map(|e| e.target_id), Some("a".to_owned()));
+    }
+
+    #[test]
+    fn test_publish_reaches_error_subscribers() {
+        let (_, cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(cons);
+        let errors = bus.subscribe::<ErrorRaised>();
+        let everything = bus.subscribe::<SchedulerEvent>();
+
+        bus.publish(&SchedulerEvent::Error {
+            message: "device lost".into(),
+        });
+
+        assert_eq!(
+            errors.try_next(),
+            Some(ErrorRaised {
+                message: "device lost".into()
+            })
+        );
+        assert!(matches!(
+            everything.try_next(),
+            Some(SchedulerEvent::Error { .. })
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+//! Golden-file regression tests: small scenarios rendered offline and compared with WAVs
+//! rendered before, so refactors of the render path (block rendering, SIMD) can't change
+//! the output unnoticed.
+//!
+//! The goldens live in `golden/`. After an intended change to the output, render them again
+//! with `FREQFORM_BLESS_GOLDEN=1 cargo test -p audio_engine golden` and review the new files.
+
+use std::{path::PathBuf, sync::Arc};
+
+use rtrb::RingBuffer;
+use transport::{clock::TempoClock, resolution::TickResolution};
+
+use crate::{
+    buffer::AudioBuffer,
+    offline::write_wav,
+    scheduler::{
+        Scheduler,
+        command::{ChannelChange, LoopOptions, ParameterChange, SchedulerCommand},
+    },
+    track::{
+        Track,
+        sinewave::SineWaveTrack,
+        timeline::{Clip, FadeCurve, TimelineTrack},
+        wav::WavTrack,
+    },
+};
+
+/// Low, so the goldens stay small
+const SAMPLE_RATE: u32 = 8000;
+/// 2000 frames per beat at [`SAMPLE_RATE`]
+const TEMPO: f64 = 240.0;
+/// Largest difference allowed from a golden, about -80 dBFS, room for reordered float math
+const TOLERANCE: f32 = 1e-4;
+/// Set to render the goldens again instead of comparing with them
+const BLESS_VAR: &str = "FREQFORM_BLESS_GOLDEN";
+/// Every scenario is rendered in both, and has to match the same golden
+const BLOCK_SIZES: [&[usize]; 2] = [&[64], &[1, 333, 17, 128, 1000]];
+
+/// A scheduler set up for a scenario, and the commands to send at given frames
+struct Scenario {
+    scheduler: Scheduler,
+    frames: u64,
+    automation: Vec<(u64, SchedulerCommand)>,
+}
+
+impl Scenario {
+    fn new(frames: u64) -> Self {
+        let (_, consumer) = RingBuffer::new(8);
+        let clock = TempoClock::new(TEMPO, f64::from(SAMPLE_RATE), TickResolution::Sixteenth);
+        Self {
+            scheduler: Scheduler::new(consumer, clock),
+            frames,
+            automation: Vec::new(),
+        }
+    }
+
+    fn with_track(mut self, track: impl Track + 'static, start_frame: u64) -> Self {
+        self.scheduler
+            .process_command(SchedulerCommand::ScheduleTrack {
+                track: Box::new(track),
+                start_frame,
+            });
+        self
+    }
+
+    fn at(mut self, frame: u64, command: SchedulerCommand) -> Self {
+        self.automation.push((frame, command));
+        self
+    }
+
+    /// Plays the scenario in blocks sized by cycling through `sizes`, split where
+    /// automation lands so it's applied on its frame
+    fn render(mut self, sizes: &[usize]) -> AudioBuffer {
+        self.automation.sort_by_key(|&(frame, _)| frame);
+        let mut automation = self.automation.into_iter().peekable();
+        let mut output = AudioBuffer::stereo(0);
+        let mut block = AudioBuffer::stereo(sizes.iter().copied().max().unwrap_or(1));
+        self.scheduler.process_command(SchedulerCommand::Play);
+
+        let mut frame = 0;
+        for &size in sizes.iter().cycle() {
+            while let Some((_, command)) = automation.next_if(|&(at, _)| at <= frame) {
+                self.scheduler.process_command(command);
+            }
+            if frame == self.frames {
+                break;
+            }
+            let next = automation.peek().map_or(self.frames, |&(at, _)| at);
+            let len = (size as u64).min(next - frame).min(self.frames - frame);
+            block.set_frames(len as usize);
+            self.scheduler.render(&mut block);
+            output.extend_from(&block);
+            frame += len;
+        }
+        output
+    }
+}
+
+/// `frames` of a decaying two-partial tone, something fades and gain changes show up on
+fn tone(frames: usize, freq: f32) -> Arc<AudioBuffer> {
+    let step = std::f32::consts::TAU * freq / SAMPLE_RATE as f32;
+    let samples: Vec<_> = (0..frames)
+        .map(|index| {
+            let phase = index as f32 * step;
+            let decay = (-(index as f32) / frames as f32).exp();
+            let sample = 0.6f32.mul_add(phase.sin(), 0.2 * (phase * 3.0).sin()) * decay;
+            (sample, -sample)
+        })
+        .collect();
+    Arc::new(AudioBuffer::from_frames(&samples))
+}
+
+/// Renders `scenario` at every [`BLOCK_SIZES`] and compares each with the golden `name`
+fn check(name: &str, scenario: impl Fn() -> Scenario) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{name}.wav"));
+    if std::env::var_os(BLESS_VAR).is_some() {
+        write_wav(&path, &scenario().render(BLOCK_SIZES[0]), SAMPLE_RATE).unwrap();
+    }
+    let golden = WavTrack::decode_file(&path).unwrap_or_else(|error| {
+        panic!("no golden for '{name}' ({error}), render it with {BLESS_VAR}=1")
+    });
+
+    for sizes in BLOCK_SIZES {
+        let rendered = scenario().render(sizes);
+        assert_eq!(
+            (rendered.channels(), rendered.frames()),
+            (golden.channels(), golden.frames()),
+            "'{name}' rendered in blocks of {sizes:?} has a different shape than its golden"
+        );
+        let (frame, channel, difference) = (0..rendered.frames())
+            .flat_map(|frame| (0..rendered.channels()).map(move |channel| (frame, channel)))
+            .map(|(frame, channel)| {
+                let difference =
+                    (rendered.channel(channel)[frame] - golden.channel(channel)[frame]).abs();
+                (frame, channel, difference)
+            })
+            .fold((0, 0, 0.0f32), |worst, candidate| {
+                if candidate.2 > worst.2 {
+                    candidate
+                } else {
+                    worst
+                }
+            });
+        assert!(
+            difference <= TOLERANCE,
+            "'{name}' rendered in blocks of {sizes:?} is {difference} off its golden at frame \
+             {frame} of channel {channel}"
+        );
+    }
+}
+
+#[test]
+fn test_clips_with_gain_fades_and_envelope() {
+    check("clips", || {
+        let mut track = TimelineTrack::new("clips");
+        let mut first = Clip::new(100, tone(2000, 220.0));
+        first.set_fades(300, 500);
+        first.set_gain(0.8);
+        track.add_clip(first);
+        // overlaps the first one's fade out with an equal power fade in
+        let mut second = Clip::new(1600, tone(1500, 330.0));
+        second.set_fades(500, 200);
+        second.set_fade_curves(FadeCurve::EqualPower, FadeCurve::Linear);
+        second.set_envelope(vec![(0, 1.0), (700, 0.3), (1200, 1.2)]);
+        track.add_clip(second);
+        Scenario::new(3500).with_track(track, 0)
+    });
+}
+
+#[test]
+fn test_loop_wraps_back_to_its_start() {
+    check("loop", || {
+        let mut track = TimelineTrack::new("loop");
+        let mut clip = Clip::new(500, tone(3000, 440.0));
+        clip.set_fades(50, 50);
+        track.add_clip(clip);
+        // beat 2 to beat 3, frames 2000 to 4000
+        let loop_point = |beat| LoopOptions {
+            bar: 1,
+            beat,
+            tick: 1,
+        };
+        Scenario::new(7000).with_track(track, 0).at(
+            0,
+            SchedulerCommand::SetLoop {
+                enabled: true,
+                start: loop_point(2),
+                end: loop_point(3),
+            },
+        )
+    });
+}
+
+#[test]
+fn test_parameter_and_channel_automation() {
+    let id = || SineWaveTrack::new(0.0, 1.0).id();
+    check("automation", || {
+        Scenario::new(4000)
+            .with_track(SineWaveTrack::new(220.0, SAMPLE_RATE as f32), 0)
+            .at(
+                1000,
+                SchedulerCommand::ParamChange {
+                    target_id: id(),
+                    change: ParameterChange::SetFrequency(550.0),
+                },
+            )
+            .at(
+                1500,
+                SchedulerCommand::ChannelChange {
+                    target_id: id(),
+                    change: ChannelChange::SetGain(0.25),
+                },
+            )
+            .at(
+                2500,
+                SchedulerCommand::ChannelChange {
+                    target_id: id(),
+                    change: ChannelChange::SetPan(-0.75),
+                },
+            )
+            .at(
+                3000,
+                SchedulerCommand::ChannelChange {
+                    target_id: id(),
+                    change: ChannelChange::SetMute(true),
+                },
+            )
+            .at(
+                3500,
+                SchedulerCommand::ChannelChange {
+                    target_id: id(),
+                    change: ChannelChange::SetMute(false),
+                },
+            )
+    });
+}
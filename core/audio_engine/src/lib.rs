@@ -1,5 +1,52 @@
+//! Implementation detail of the `freqform` crate, which re-exports the supported parts of
+//! this one. Its modules may be reorganised between releases.
+
+pub mod analysis;
+pub mod archive;
+pub mod audio_pool;
+pub mod automation;
+pub mod buffer;
 pub mod constants;
+pub mod control_surface;
+pub mod cue_list;
+pub mod cv;
+pub mod decode;
 pub mod device_manager;
+pub mod diagnostics;
+pub mod dsp;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod engine;
+pub mod error;
+pub mod events;
+#[cfg(test)]
+mod golden;
+pub mod metadata;
+pub mod metering;
+pub mod midi;
+pub mod mix_snapshot;
 pub mod mixer;
+pub mod monitor;
+pub mod offline;
+pub mod performance;
+pub mod preset;
+pub mod preset_library;
+pub mod project;
+pub mod punch;
+pub mod record;
+pub mod resample;
+pub mod rng;
+pub mod routing;
+#[cfg(feature = "rt-audit")]
+pub mod rt_audit;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rtp;
+pub mod scene;
 pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod settings;
+pub mod snapshot;
+pub mod stems;
 pub mod track;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watchdog;
@@ -1,63 +1,219 @@
-use audio_engine::{
-    device_manager::{AudioDeviceManager, cpal_dm::CpalAudioDeviceManager},
-    scheduler::{
-        Scheduler,
-        command::{ParameterChange, SchedulerCommand},
-    },
-    track::{gainpan::GainPanTrack, wav::WavTrack},
-};
-use transport::{clock::TempoClock, resolution::TickResolution};
-
-fn main() {
-    let (mut prod, cons) = rtrb::RingBuffer::<SchedulerCommand>::new(128);
-    let tempo_clock = TempoClock::new(120.0, 44100.0, TickResolution::Sixteenth);
-    let audio_source = Box::new(Scheduler::new(cons, tempo_clock));
-    // make this into a factory
-    let mut manager = CpalAudioDeviceManager::new();
-
-    manager
-        .start_output_stream(audio_source)
-        .expect("Failed to start audio stream");
-
-    println!("Stream started");
-
-    let piano = {
-        let wav = WavTrack::from_file("./assets/wav/piano.wav").expect("Failed to load WAV");
-        GainPanTrack::new("x-track", Box::new(wav), 0.1, 1.0)
+use std::process::ExitCode;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> ExitCode {
+    cli::run()
+}
+
+/// On the web the AudioWorklet drives the engine, see `device_manager::web_dm`
+#[cfg(target_arch = "wasm32")]
+fn main() -> ExitCode {
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cli {
+    use std::{
+        error::Error,
+        path::PathBuf,
+        process::ExitCode,
+        time::{Duration, Instant},
     };
 
-    let time_to_frame = |time_in_sec: f64| {
-        let sample_rate = 44100.0;
-        (time_in_sec * sample_rate) as u64
+    use audio_engine::{
+        device_manager::cpal_dm::CpalAudioDeviceManager,
+        engine::EngineBuilder,
+        offline,
+        project::Project,
+        scheduler::{Scheduler, command::SchedulerCommand},
     };
+    use clap::{Parser, Subcommand};
+    use rtrb::RingBuffer;
+    use transport::{clock::TempoClock, resolution::TickResolution};
+
+    /// Longest offline render or playback, guards against projects with never-ending tracks
+    const MAX_RENDER_SECONDS: u64 = 60 * 60;
+    const COMMAND_QUEUE_SIZE: usize = 128;
+
+    type CliResult<T> = Result<T, Box<dyn Error>>;
+
+    #[derive(Parser)]
+    #[command(name = "freqform", about = "Headless FreqForm player and renderer")]
+    struct Cli {
+        /// List the available output devices
+        #[arg(long)]
+        list_devices: bool,
+
+        #[command(subcommand)]
+        command: Option<Command>,
+    }
+
+    #[derive(Subcommand)]
+    enum Command {
+        /// Play a project through the default output device until every track has finished
+        Play {
+            project: PathBuf,
+            /// Fixed internal processing block in frames, independent of the device buffer
+            #[arg(long)]
+            block_size: Option<usize>,
+        },
+        /// Render a project offline to a 32-bit float WAV file
+        Render {
+            project: PathBuf,
+            #[arg(long, short)]
+            out: PathBuf,
+            /// Frames rendered per scheduler call
+            #[arg(long, default_value_t = 512)]
+            block_size: usize,
+            /// Limit the render to this true-peak ceiling in dBTP, e.g. -1
+            #[arg(long, allow_hyphen_values = true)]
+            ceiling: Option<f32>,
+        },
+    }
+
+    #[expect(clippy::print_stderr, reason = "the CLI reports errors on the console")]
+    pub fn run() -> ExitCode {
+        let cli = Cli::parse();
+
+        let result = if cli.list_devices {
+            list_devices()
+        } else {
+            match cli.command {
+                Some(Command::Play {
+                    project,
+                    block_size,
+                }) => play(&project, block_size),
+                Some(Command::Render {
+                    project,
+                    out,
+                    block_size,
+                    ceiling,
+                }) => render(&project, &out, block_size, ceiling),
+                None => Err("Nothing to do, see `freqform --help`".into()),
+            }
+        };
+
+        match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("freqform: {e}");
+                ExitCode::FAILURE
+            }
+        }
+    }
+
+    #[expect(clippy::print_stdout, reason = "the CLI lists devices on the console")]
+    fn list_devices() -> CliResult<()> {
+        let names = CpalAudioDeviceManager::output_device_names()
+            .map_err(|e| format!("Failed to list devices: {e}"))?;
+
+        for name in names {
+            println!("{name}");
+        }
+        Ok(())
+    }
+
+    /// A scheduler for rendering `project` offline, every track scheduled
+    fn create_scheduler(project: &Project, sample_rate: f64) -> CliResult<Scheduler> {
+        let (_, cons) = RingBuffer::<SchedulerCommand>::new(COMMAND_QUEUE_SIZE);
+
+        let tempo_clock = TempoClock::new(project.bpm, sample_rate, TickResolution::Sixteenth);
+        let mut scheduler = Scheduler::new(cons, tempo_clock);
+
+        for (channel, start_frame) in project.build_channels(sample_rate)? {
+            scheduler.process_command(SchedulerCommand::ScheduleChannel {
+                channel: Box::new(channel),
+                start_frame,
+            });
+        }
+
+        Ok(scheduler)
+    }
+
+    /// Plays `project` at its own sample rate, converted to the device's if they differ
+    #[expect(
+        clippy::print_stdout,
+        reason = "the CLI reports progress on the console"
+    )]
+    fn play(path: &PathBuf, block_size: Option<usize>) -> CliResult<()> {
+        let project = Project::load(path)?;
 
-    prod.push(SchedulerCommand::Play).unwrap();
+        let mut track_count = 0;
+        let mut engine = EngineBuilder::new()
+            .sample_rate(project.sample_rate)
+            .tempo(project.bpm)
+            .block_size(block_size)
+            .snapshots(true)
+            .build_with(|scheduler| {
+                let channels = project.build_channels(scheduler.sample_rate())?;
+                track_count = channels.len();
+                for (channel, start_frame) in channels {
+                    scheduler.process_command(SchedulerCommand::ScheduleChannel {
+                        channel: Box::new(channel),
+                        start_frame,
+                    });
+                }
+                scheduler.process_command(SchedulerCommand::Play);
+                Ok(())
+            })
+            .map_err(|e| format!("Failed to start audio engine: {e}"))?;
 
-    prod.push(SchedulerCommand::ScheduleTrack {
-        track: Box::new(piano),
-        start_frame: time_to_frame(1.0),
-    })
-    .unwrap();
+        println!(
+            "Playing {} ({track_count} tracks) at {} Hz on a {} Hz device",
+            path.display(),
+            engine.sample_rate(),
+            engine.device_sample_rate()
+        );
 
-    std::thread::sleep(std::time::Duration::from_secs(2));
+        // the snapshot can't be missed like an event dropped from a full ring
+        let deadline = Instant::now() + Duration::from_secs(MAX_RENDER_SECONDS);
+        while Instant::now() < deadline {
+            engine.events().pump();
+            let idle = engine
+                .snapshots()
+                .and_then(|snapshots| snapshots.latest())
+                .is_some_and(|snapshot| snapshot.idle);
+            if idle {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
 
-    println!("Lowering gain to 0.3");
+        engine.shutdown()?;
+        Ok(())
+    }
 
-    prod.push(SchedulerCommand::ParamChange {
-        target_id: "x-track".into(),
-        change: ParameterChange::SetGain(1.0),
-    })
-    .unwrap();
+    #[expect(
+        clippy::print_stdout,
+        reason = "the CLI reports results on the console"
+    )]
+    fn render(
+        path: &PathBuf,
+        out: &PathBuf,
+        block_size: usize,
+        ceiling: Option<f32>,
+    ) -> CliResult<()> {
+        let project = Project::load(path)?;
+        let mut scheduler = create_scheduler(&project, f64::from(project.sample_rate))?;
 
-    // Change pan to -1.0 (left) after another 2s
-    std::thread::sleep(std::time::Duration::from_secs(2));
+        let max_frames = MAX_RENDER_SECONDS * u64::from(project.sample_rate);
+        let mut mix = offline::render_until_idle(&mut scheduler, block_size, max_frames);
+        if let Some(ceiling) = ceiling {
+            offline::limit(&mut mix, f64::from(project.sample_rate), ceiling);
+        }
+        offline::write_wav(out, &mix, project.sample_rate)?;
 
-    println!("Panning fully left");
-    prod.push(SchedulerCommand::ParamChange {
-        target_id: "x-track".into(),
-        change: ParameterChange::SetPan(-1.0),
-    })
-    .unwrap();
+        println!(
+            "Rendered {:.2}s to {}",
+            mix.frames() as f64 / f64::from(project.sample_rate),
+            out.display()
+        );
 
-    std::thread::park();
+        let loudness = offline::measure_loudness(&mix, f64::from(project.sample_rate));
+        println!(
+            "Integrated loudness {:.1} LUFS, max short-term {:.1} LUFS, true peak {:.1} dBTP",
+            loudness.integrated, loudness.max_short_term, loudness.true_peak
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,94 @@
+//! User metadata on tracks and clips: a color, notes and tags.
+//!
+//! The engine doesn't act on it, it only keeps it with the model, stores it in the project
+//! file and publishes it in [`EngineSnapshot`](crate::snapshot::EngineSnapshot)s so every
+//! front-end shows the same colors and labels.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+/// An sRGB color, written `#rrggbb` in project files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    #[must_use]
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Parses `#rrggbb` (the `#` is optional), `None` if it isn't a color
+    #[must_use]
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).ok();
+        Some(Self::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Self::from_hex(&hex)
+            .ok_or_else(|| D::Error::custom(format!("invalid color '{hex}', expected #rrggbb")))
+    }
+}
+
+/// What a user noted about a track or clip
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Metadata {
+    /// `true` if nothing has been set
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.notes.is_empty() && self.tags.is_empty()
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|existing| existing == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_hex_roundtrip() {
+        let color = Color::from_hex("#1E90ff").unwrap();
+        assert_eq!(color, Color::new(0x1e, 0x90, 0xff));
+        assert_eq!(color.to_string(), "#1e90ff");
+        assert_eq!(Color::from_hex("1e90ff"), Some(color));
+        assert_eq!(Color::from_hex("#1e90f"), None);
+        assert_eq!(Color::from_hex("#1e90fg"), None);
+    }
+}
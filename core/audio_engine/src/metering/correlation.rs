@@ -0,0 +1,204 @@
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::buffer::AudioBuffer;
+
+/// Integration time of the correlation meter, the usual ballistics for phase meters
+pub(crate) const CORRELATION_WINDOW_SECONDS: f64 = 0.3;
+/// Below this signal power the channels are treated as silent and the meter rests at zero
+const SILENCE_POWER: f64 = 1e-10;
+
+/// Stereo phase correlation of the master bus.
+///
+/// Reads +1 for mono material, 0 for unrelated channels and -1 when one channel is the
+/// inverse of the other, the case that cancels out when the mix is summed to mono.
+pub struct CorrelationMeter {
+    /// Per-sample weight of the exponential average
+    coefficient: f64,
+    left_power: f64,
+    right_power: f64,
+    cross: f64,
+}
+
+impl CorrelationMeter {
+    #[must_use]
+    pub fn new(sample_rate: f64) -> Self {
+        Self::with_window(sample_rate, CORRELATION_WINDOW_SECONDS)
+    }
+
+    /// A meter integrating over `window_seconds` instead of the usual 300 ms
+    #[must_use]
+    pub fn with_window(sample_rate: f64, window_seconds: f64) -> Self {
+        Self {
+            coefficient: 1.0 - (-1.0 / (window_seconds * sample_rate)).exp(),
+            left_power: 0.0,
+            right_power: 0.0,
+            cross: 0.0,
+        }
+    }
+
+    /// Measures frames `start..start + len` of a stereo buffer
+    pub fn process(&mut self, buffer: &AudioBuffer, start: usize, len: usize) {
+        let left = &buffer.channel(0)[start..start + len];
+        let right = &buffer.channel(1)[start..start + len];
+
+        for (&l, &r) in left.iter().zip(right) {
+            let (l, r) = (f64::from(l), f64::from(r));
+            self.left_power += self.coefficient * l.mul_add(l, -self.left_power);
+            self.right_power += self.coefficient * r.mul_add(r, -self.right_power);
+            self.cross += self.coefficient * l.mul_add(r, -self.cross);
+        }
+    }
+
+    /// Correlation between -1 and +1, 0 while the bus is silent
+    #[must_use]
+    pub fn correlation(&self) -> f32 {
+        let power = (self.left_power * self.right_power).sqrt();
+        if power < SILENCE_POWER {
+            return 0.0;
+        }
+        (self.cross / power).clamp(-1.0, 1.0) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.left_power = 0.0;
+        self.right_power = 0.0;
+        self.cross = 0.0;
+    }
+}
+
+/// One goniometer sample, rotated so mono material draws a vertical line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoniometerPoint {
+    /// (L + R) / 2, the vertical axis
+    pub mid: f32,
+    /// (L - R) / 2, the horizontal axis
+    pub side: f32,
+}
+
+/// Creates the channel master bus samples are tapped through for a goniometer (Lissajous) display.
+///
+/// Every `decimation`th frame is sent, and points are dropped while the ring holding
+/// `capacity` of them is full, so a stalled UI never blocks the audio thread.
+#[must_use]
+pub fn goniometer_channel(capacity: usize, decimation: usize) -> (GoniometerTap, GoniometerReader) {
+    let (producer, consumer) = RingBuffer::new(capacity);
+    (
+        GoniometerTap {
+            producer,
+            decimation: decimation.max(1),
+            countdown: 0,
+        },
+        GoniometerReader { consumer },
+    )
+}
+
+/// Audio thread end of the goniometer channel
+pub struct GoniometerTap {
+    producer: Producer<GoniometerPoint>,
+    decimation: usize,
+    /// Frames left until the next one is sent
+    countdown: usize,
+}
+
+impl GoniometerTap {
+    /// Sends frames `start..start + len` of a stereo buffer
+    pub fn process(&mut self, buffer: &AudioBuffer, start: usize, len: usize) {
+        let left = &buffer.channel(0)[start..start + len];
+        let right = &buffer.channel(1)[start..start + len];
+
+        for (&l, &r) in left.iter().zip(right) {
+            if self.countdown == 0 {
+                let _ = self.producer.push(GoniometerPoint {
+                    mid: (l + r) * 0.5,
+                    side: (l - r) * 0.5,
+                });
+                self.countdown = self.decimation;
+            }
+            self.countdown -= 1;
+        }
+    }
+}
+
+/// UI end of the goniometer channel
+pub struct GoniometerReader {
+    consumer: Consumer<GoniometerPoint>,
+}
+
+impl GoniometerReader {
+    /// Hands every point received since the last call to `draw`, oldest first
+    pub fn drain(&mut self, mut draw: impl FnMut(GoniometerPoint)) -> usize {
+        let mut count = 0;
+        while let Ok(point) = self.consumer.pop() {
+            draw(point);
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn measure(left_phase: f32, right_phase: f32, right_gain: f32) -> f32 {
+        let samples: Vec<_> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = 2.0 * PI * 440.0 * i as f32 / SAMPLE_RATE as f32;
+                ((t + left_phase).sin(), right_gain * (t + right_phase).sin())
+            })
+            .collect();
+        let buffer = AudioBuffer::from_frames(&samples);
+        let mut meter = CorrelationMeter::new(SAMPLE_RATE);
+        meter.process(&buffer, 0, buffer.frames());
+        meter.correlation()
+    }
+
+    #[test]
+    fn test_mono_material_is_fully_correlated() {
+        assert!((measure(0.0, 0.0, 0.5) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_inverted_channel_is_anti_correlated() {
+        assert!((measure(0.0, 0.0, -1.0) + 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_quadrature_channels_are_uncorrelated() {
+        assert!(measure(0.0, PI / 2.0, 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_silence_reads_zero() {
+        let buffer = AudioBuffer::stereo(1024);
+        let mut meter = CorrelationMeter::new(SAMPLE_RATE);
+        meter.process(&buffer, 0, buffer.frames());
+        assert_eq!(meter.correlation(), 0.0);
+    }
+
+    #[test]
+    fn test_goniometer_tap_decimates_and_rotates() {
+        let (mut tap, mut reader) = goniometer_channel(16, 4);
+        let buffer = AudioBuffer::from_frames(&[(0.5, 0.1); 10]);
+        tap.process(&buffer, 0, 6);
+        tap.process(&buffer, 6, 4);
+
+        let mut points = Vec::new();
+        assert_eq!(reader.drain(|point| points.push(point)), 3);
+        assert!((points[0].mid - 0.3).abs() < 1e-6);
+        assert!((points[0].side - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_goniometer_tap_drops_points_when_full() {
+        let (mut tap, mut reader) = goniometer_channel(4, 1);
+        let buffer = AudioBuffer::stereo(64);
+        tap.process(&buffer, 0, 64);
+
+        assert_eq!(reader.drain(|_| {}), 4);
+    }
+}
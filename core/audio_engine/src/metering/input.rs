@@ -0,0 +1,104 @@
+use crate::buffer::AudioBuffer;
+
+/// How fast the peak reading falls once the signal drops, unless set otherwise
+pub(crate) const RELEASE_DB_PER_SECOND: f32 = 20.0;
+/// Samples at or above full scale count as clipped
+const CLIP_LEVEL: f32 = 1.0;
+
+/// Input level of an armed track, as published in snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct InputLevel {
+    /// Linear sample peaks, falling back at the release of the
+    /// [`MeterBallistics`](super::MeterBallistics)
+    pub peak_left: f32,
+    pub peak_right: f32,
+    /// Set by the first overload and held until cleared, so short overs aren't missed
+    pub clipped: bool,
+}
+
+/// Pre-record input meter of a track: peak with release and a latching clip indicator
+#[derive(Debug, Clone, Default)]
+pub struct InputMeter {
+    level: InputLevel,
+}
+
+impl InputMeter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Measures frames `start..start + len` of a stereo input buffer, peaks falling back at
+    /// `release_db_per_second`
+    pub fn process(
+        &mut self,
+        input: &AudioBuffer,
+        start: usize,
+        len: usize,
+        sample_rate: f64,
+        release_db_per_second: f32,
+    ) {
+        let release = 10f32.powf(-release_db_per_second * (len as f64 / sample_rate) as f32 / 20.0);
+        let left = input.peak(0, start, len);
+        let right = input.peak(1, start, len);
+        self.level.peak_left = (self.level.peak_left * release).max(left);
+        self.level.peak_right = (self.level.peak_right * release).max(right);
+        self.level.clipped |= left >= CLIP_LEVEL || right >= CLIP_LEVEL;
+    }
+
+    #[must_use]
+    pub fn level(&self) -> InputLevel {
+        self.level
+    }
+
+    /// Turns the clip indicator off again
+    pub fn clear_clip(&mut self) {
+        self.level.clipped = false;
+    }
+
+    pub fn reset(&mut self) {
+        self.level = InputLevel::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_falls_back_after_signal_stops() {
+        let mut meter = InputMeter::new();
+        meter.process(
+            &AudioBuffer::from_frames(&[(0.5, -0.25)]),
+            0,
+            1,
+            48000.0,
+            20.0,
+        );
+        assert_eq!(meter.level().peak_left, 0.5);
+        assert_eq!(meter.level().peak_right, 0.25);
+
+        // one second of silence: 20 dB down
+        meter.process(&AudioBuffer::stereo(48000), 0, 48000, 48000.0, 20.0);
+        assert!((meter.level().peak_left - 0.05).abs() < 1e-4);
+        assert!(!meter.level().clipped);
+    }
+
+    #[test]
+    fn test_clip_holds_until_cleared() {
+        let mut meter = InputMeter::new();
+        meter.process(
+            &AudioBuffer::from_frames(&[(0.1, 1.2)]),
+            0,
+            1,
+            48000.0,
+            20.0,
+        );
+        meter.process(&AudioBuffer::stereo(4800), 0, 4800, 48000.0, 20.0);
+        assert!(meter.level().clipped);
+
+        meter.clear_clip();
+        assert!(!meter.level().clipped);
+        assert!(meter.level().peak_right > 0.0);
+    }
+}
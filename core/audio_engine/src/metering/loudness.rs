@@ -0,0 +1,383 @@
+use std::f64::consts::PI;
+
+use crate::{buffer::AudioBuffer, metering::true_peak::TruePeakDetector};
+
+/// Loudness is measured in 100 ms steps, momentary over 4 of them, short-term over 30
+const STEPS_PER_SECOND: f64 = 10.0;
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+
+/// Gating blocks quieter than this never count towards integrated loudness (BS.1770 absolute gate)
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks more than this far below the ungated level are ignored (BS.1770 relative gate)
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Gating block histogram, 0.1 LU bins from the absolute gate up to +10 LUFS
+const HISTOGRAM_BINS_PER_LU: f64 = 10.0;
+const HISTOGRAM_BINS: usize = 800;
+
+/// Momentary, short-term and integrated loudness in LUFS plus true peak in dBTP.
+/// Silence (or not enough audio yet) reads as negative infinity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessReading {
+    pub momentary: f32,
+    pub short_term: f32,
+    pub integrated: f32,
+    pub true_peak: f32,
+}
+
+/// What a finished render measured, for delivery specs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessSummary {
+    pub integrated: f32,
+    pub max_momentary: f32,
+    pub max_short_term: f32,
+    pub true_peak: f32,
+}
+
+/// ITU-R BS.1770 loudness meter for stereo material.
+///
+/// Memory is allocated up front, so [`LoudnessMeter::process`] can run on the audio thread.
+///
+/// # Example
+/// ```
+/// use audio_engine::{buffer::AudioBuffer, metering::loudness::LoudnessMeter};
+///
+/// let mut meter = LoudnessMeter::new(48000.0);
+/// let silence = AudioBuffer::stereo(48000);
+/// meter.process(&silence, 0, silence.frames());
+///
+/// assert_eq!(meter.integrated(), f32::NEG_INFINITY);
+/// ```
+pub struct LoudnessMeter {
+    filters: [KWeighting; 2],
+    true_peak: [TruePeakDetector; 2],
+    /// Highest true peak so far, linear
+    peak: f32,
+    /// Frames per 100 ms step
+    step_frames: usize,
+    step_position: usize,
+    step_energy: f64,
+    /// Mean square of the most recent steps, oldest first once full
+    steps: [f64; SHORT_TERM_STEPS],
+    steps_written: usize,
+    /// Energy sum and block count per 0.1 LU of gating block loudness
+    histogram: Vec<(f64, u64)>,
+    max_momentary: f64,
+    max_short_term: f64,
+}
+
+impl LoudnessMeter {
+    #[must_use]
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            filters: [KWeighting::new(sample_rate), KWeighting::new(sample_rate)],
+            true_peak: [TruePeakDetector::new(), TruePeakDetector::new()],
+            peak: 0.0,
+            step_frames: ((sample_rate / STEPS_PER_SECOND).round() as usize).max(1),
+            step_position: 0,
+            step_energy: 0.0,
+            steps: [0.0; SHORT_TERM_STEPS],
+            steps_written: 0,
+            histogram: vec![(0.0, 0); HISTOGRAM_BINS],
+            max_momentary: f64::NEG_INFINITY,
+            max_short_term: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Measures frames `start..start + len` of a stereo buffer
+    pub fn process(&mut self, buffer: &AudioBuffer, start: usize, len: usize) {
+        let left = &buffer.channel(0)[start..start + len];
+        let right = &buffer.channel(1)[start..start + len];
+
+        for (&l, &r) in left.iter().zip(right) {
+            let weighted_l = self.filters[0].process(f64::from(l));
+            let weighted_r = self.filters[1].process(f64::from(r));
+            let peak = self.true_peak[0]
+                .process(l)
+                .max(self.true_peak[1].process(r));
+            self.peak = self.peak.max(peak);
+
+            self.step_energy += weighted_l.mul_add(weighted_l, weighted_r * weighted_r);
+            self.step_position += 1;
+            if self.step_position == self.step_frames {
+                self.finish_step();
+            }
+        }
+    }
+
+    fn finish_step(&mut self) {
+        let mean_square = self.step_energy / self.step_frames as f64;
+        self.steps[self.steps_written % SHORT_TERM_STEPS] = mean_square;
+        self.steps_written += 1;
+        self.step_energy = 0.0;
+        self.step_position = 0;
+
+        if let Some(energy) = self.window_energy(MOMENTARY_STEPS) {
+            // every momentary window is also a gating block (400 ms, 75% overlap)
+            let loudness = energy_to_lufs(energy);
+            self.max_momentary = self.max_momentary.max(loudness);
+            if loudness > ABSOLUTE_GATE_LUFS {
+                let bin = ((loudness - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU) as usize;
+                let (sum, count) = &mut self.histogram[bin.min(HISTOGRAM_BINS - 1)];
+                *sum += energy;
+                *count += 1;
+            }
+        }
+        if let Some(energy) = self.window_energy(SHORT_TERM_STEPS) {
+            self.max_short_term = self.max_short_term.max(energy_to_lufs(energy));
+        }
+    }
+
+    /// Mean energy of the last `steps` steps, `None` until that many have been measured
+    fn window_energy(&self, steps: usize) -> Option<f64> {
+        if self.steps_written < steps {
+            return None;
+        }
+        let sum: f64 = (self.steps_written - steps..self.steps_written)
+            .map(|step| self.steps[step % SHORT_TERM_STEPS])
+            .sum();
+        Some(sum / steps as f64)
+    }
+
+    #[must_use]
+    pub fn momentary(&self) -> f32 {
+        self.window_energy(MOMENTARY_STEPS)
+            .map_or(f32::NEG_INFINITY, |energy| energy_to_lufs(energy) as f32)
+    }
+
+    #[must_use]
+    pub fn short_term(&self) -> f32 {
+        self.window_energy(SHORT_TERM_STEPS)
+            .map_or(f32::NEG_INFINITY, |energy| energy_to_lufs(energy) as f32)
+    }
+
+    /// Gated loudness of everything measured since creation or the last reset
+    #[must_use]
+    pub fn integrated(&self) -> f32 {
+        let (energy, count) = self
+            .histogram
+            .iter()
+            .fold((0.0, 0), |(energy, count), &(e, c)| (energy + e, count + c));
+        if count == 0 {
+            return f32::NEG_INFINITY;
+        }
+
+        let relative_gate = energy_to_lufs(energy / count as f64) - RELATIVE_GATE_LU;
+        let first_bin = ((relative_gate - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU).max(0.0);
+        let (energy, count) = self.histogram[(first_bin as usize).min(HISTOGRAM_BINS - 1)..]
+            .iter()
+            .fold((0.0, 0), |(energy, count), &(e, c)| (energy + e, count + c));
+
+        if count == 0 {
+            f32::NEG_INFINITY
+        } else {
+            energy_to_lufs(energy / count as f64) as f32
+        }
+    }
+
+    /// Highest inter-sample peak of either channel, in dBTP
+    #[must_use]
+    pub fn true_peak(&self) -> f32 {
+        20.0 * self.peak.log10()
+    }
+
+    #[must_use]
+    pub fn reading(&self) -> LoudnessReading {
+        LoudnessReading {
+            momentary: self.momentary(),
+            short_term: self.short_term(),
+            integrated: self.integrated(),
+            true_peak: self.true_peak(),
+        }
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> LoudnessSummary {
+        LoudnessSummary {
+            integrated: self.integrated(),
+            max_momentary: self.max_momentary as f32,
+            max_short_term: self.max_short_term as f32,
+            true_peak: self.true_peak(),
+        }
+    }
+
+    /// Starts a new measurement, keeping the allocated storage
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.true_peak = [TruePeakDetector::new(), TruePeakDetector::new()];
+        self.peak = 0.0;
+        self.step_position = 0;
+        self.step_energy = 0.0;
+        self.steps_written = 0;
+        self.histogram.fill((0.0, 0));
+        self.max_momentary = f64::NEG_INFINITY;
+        self.max_short_term = f64::NEG_INFINITY;
+    }
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    if energy <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    10.0f64.mul_add(energy.log10(), -0.691)
+}
+
+/// Transposed direct form II biquad
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0].mul_add(input, self.state[0]);
+        self.state[0] = self.b[1].mul_add(input, -self.a[0] * output) + self.state[1];
+        self.state[1] = self.b[2].mul_add(input, -self.a[1] * output);
+        output
+    }
+}
+
+/// The BS.1770 K-weighting curve: a high shelf for head effects followed by a high-pass.
+/// Coefficients are derived for any sample rate rather than using the 48 kHz tables.
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: f64) -> Self {
+        let f0 = 1_681.974_450_955_533;
+        let gain_db = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10.0f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            state: [0.0; 2],
+        };
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(input))
+    }
+
+    fn reset(&mut self) {
+        self.shelf.state = [0.0; 2];
+        self.high_pass.state = [0.0; 2];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn sine(frequency: f32, amplitude: f32, phase: f32, seconds: f64) -> AudioBuffer {
+        let frames = (SAMPLE_RATE * seconds) as usize;
+        let samples: Vec<_> = (0..frames)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sample = amplitude * (2.0 * PI * frequency).mul_add(t, phase).sin();
+                (sample, sample)
+            })
+            .collect();
+        AudioBuffer::from_frames(&samples)
+    }
+
+    fn measure(buffer: &AudioBuffer) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        // feed it in device-sized pieces like the scheduler does
+        let mut start = 0;
+        while start < buffer.frames() {
+            let len = 512.min(buffer.frames() - start);
+            meter.process(buffer, start, len);
+            start += len;
+        }
+        meter
+    }
+
+    #[test]
+    fn test_stereo_sine_at_minus_20_dbfs_reads_minus_20_lufs() {
+        let meter = measure(&sine(1000.0, 0.1, 0.0, 5.0));
+
+        assert!(
+            (meter.momentary() + 20.0).abs() < 0.1,
+            "{}",
+            meter.momentary()
+        );
+        assert!((meter.short_term() + 20.0).abs() < 0.1);
+        assert!((meter.integrated() + 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_silence_is_gated_out() {
+        let meter = measure(&AudioBuffer::stereo(SAMPLE_RATE as usize * 2));
+
+        assert_eq!(meter.integrated(), f32::NEG_INFINITY);
+        assert_eq!(meter.momentary(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_quiet_passage_is_relatively_gated() {
+        let mut program = sine(1000.0, 0.1, 0.0, 10.0);
+        program.extend_from(&sine(1000.0, 0.01, 0.0, 10.0)); // 20 LU quieter
+
+        let meter = measure(&program);
+        assert!(
+            (meter.integrated() + 20.0).abs() < 0.2,
+            "{}",
+            meter.integrated()
+        );
+    }
+
+    #[test]
+    fn test_true_peak_finds_inter_sample_peaks() {
+        // a quarter sample rate sine shifted 45 degrees never hits its crest on a sample
+        let buffer = sine(SAMPLE_RATE as f32 / 4.0, 0.5, PI / 4.0, 1.0);
+        let sample_peak = buffer.peak(0, 0, buffer.frames());
+        let meter = measure(&buffer);
+
+        let expected = 20.0 * 0.5f32.log10();
+        assert!(sample_peak < 0.36);
+        assert!(
+            (meter.true_peak() - expected).abs() < 0.5,
+            "{}",
+            meter.true_peak()
+        );
+    }
+
+    #[test]
+    fn test_reset_starts_a_new_measurement() {
+        let mut meter = measure(&sine(1000.0, 0.1, 0.0, 1.0));
+        meter.reset();
+
+        assert_eq!(meter.integrated(), f32::NEG_INFINITY);
+        assert_eq!(meter.summary().max_momentary, f32::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+pub mod correlation;
+pub mod input;
+pub mod loudness;
+pub(crate) mod true_peak;
+
+/// How the input and correlation meters move, set with
+/// [`Scheduler::set_meter_ballistics`](crate::scheduler::Scheduler::set_meter_ballistics)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeterBallistics {
+    /// How fast input peaks fall once the signal drops
+    pub peak_release_db_per_second: f32,
+    /// Integration time of the correlation meter
+    pub correlation_window_seconds: f64,
+}
+
+impl Default for MeterBallistics {
+    fn default() -> Self {
+        Self {
+            peak_release_db_per_second: input::RELEASE_DB_PER_SECOND,
+            correlation_window_seconds: correlation::CORRELATION_WINDOW_SECONDS,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::f64::consts::PI;
+
+/// Oversampling factor and taps per polyphase branch
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// Samples a peak can reach [`TruePeakDetector::process`] after the sample that caused it,
+/// the delay of the interpolation filter rounded up
+pub const TRUE_PEAK_LATENCY: usize = TAPS_PER_PHASE;
+
+/// 4x oversampling peak detector (BS.1770 Annex 2) using a Hann-windowed sinc interpolator
+pub struct TruePeakDetector {
+    /// Polyphase branches of the interpolation filter
+    phases: [[f32; TAPS_PER_PHASE]; OVERSAMPLING],
+    /// Last `TAPS_PER_PHASE` input samples, newest at `position`
+    history: [f32; TAPS_PER_PHASE],
+    position: usize,
+}
+
+impl TruePeakDetector {
+    pub fn new() -> Self {
+        let taps = TAPS_PER_PHASE * OVERSAMPLING;
+        let center = (taps - 1) as f64 / 2.0;
+        let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+
+        for (phase, branch) in phases.iter_mut().enumerate() {
+            let mut sum = 0.0;
+            let mut coefficients = [0.0f64; TAPS_PER_PHASE];
+            for (tap, coefficient) in coefficients.iter_mut().enumerate() {
+                let n = (tap * OVERSAMPLING + phase) as f64;
+                let x = (n - center) / OVERSAMPLING as f64;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let window = 0.5f64.mul_add(-(2.0 * PI * (n + 0.5) / taps as f64).cos(), 0.5);
+                *coefficient = sinc * window;
+                sum += *coefficient;
+            }
+            // unity gain per branch so DC and low frequencies read exactly
+            for (target, coefficient) in branch.iter_mut().zip(coefficients) {
+                *target = (coefficient / sum) as f32;
+            }
+        }
+
+        Self {
+            phases,
+            history: [0.0; TAPS_PER_PHASE],
+            position: 0,
+        }
+    }
+
+    /// Feeds one sample, returning the highest absolute value of it and the
+    /// interpolated points produced along with it
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.position = (self.position + 1) % TAPS_PER_PHASE;
+        self.history[self.position] = sample;
+
+        let mut peak = sample.abs();
+        for branch in &self.phases {
+            let mut value = 0.0;
+            for (tap, coefficient) in branch.iter().enumerate() {
+                let index = (self.position + TAPS_PER_PHASE - tap) % TAPS_PER_PHASE;
+                value += coefficient * self.history[index];
+            }
+            peak = peak.max(value.abs());
+        }
+        peak
+    }
+}
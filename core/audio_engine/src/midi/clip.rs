@@ -0,0 +1,348 @@
+use crate::midi::{EventKind, SequenceEvent};
+
+/// Edits [`MidiClip::undo`] can step back through
+pub const MAX_UNDO_STEPS: usize = 100;
+
+/// A note on a [`MidiClip`], positions in ticks from the clip's start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    pub start: u64,
+    pub length: u64,
+    pub key: u8,
+    pub velocity: u8,
+    pub channel: u8,
+    /// Percent chance the note plays each time it comes round
+    pub chance: u8,
+}
+
+/// Notes with the edits a piano roll makes on them, each of which can be undone.
+///
+/// Notes are kept sorted by start, then key; edits that move notes return their new index.
+/// Edits on several notes take a selection of indices, see [`MidiClip::all`].
+///
+/// # Example
+/// ```
+/// use audio_engine::midi::clip::{MidiClip, Note};
+///
+/// let mut clip = MidiClip::new();
+/// let note = Note { start: 130, length: 100, key: 60, velocity: 90, channel: 0, chance: 100 };
+/// clip.add_note(note);
+/// clip.quantize(&clip.all(), 120, 1.0, 0.0);
+/// assert_eq!(clip.notes()[0].start, 120);
+///
+/// clip.undo();
+/// assert_eq!(clip.notes()[0].start, 130);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MidiClip {
+    notes: Vec<Note>,
+    undo: Vec<Vec<Note>>,
+    redo: Vec<Vec<Note>>,
+}
+
+impl MidiClip {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All notes, sorted by start, then key
+    #[must_use]
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// A selection of every note
+    #[must_use]
+    pub fn all(&self) -> Vec<usize> {
+        (0..self.notes.len()).collect()
+    }
+
+    /// Adds a note, returning its index
+    pub fn add_note(&mut self, note: Note) -> usize {
+        self.checkpoint();
+        self.notes.push(note);
+        self.sort();
+        self.index_of(&note)
+    }
+
+    pub fn remove_note(&mut self, index: usize) -> Option<Note> {
+        if index >= self.notes.len() {
+            return None;
+        }
+        self.checkpoint();
+        Some(self.notes.remove(index))
+    }
+
+    /// Moves a note to tick `start` and key `key`, returning its new index
+    pub fn move_note(&mut self, index: usize, start: u64, key: u8) -> Option<usize> {
+        let mut note = *self.notes.get(index)?;
+        self.checkpoint();
+        note.start = start;
+        note.key = key.min(127);
+        self.notes[index] = note;
+        self.sort();
+        Some(self.index_of(&note))
+    }
+
+    /// Sets a note's length in ticks, at least one tick. `false` if there's no note at `index`.
+    pub fn resize_note(&mut self, index: usize, length: u64) -> bool {
+        if index >= self.notes.len() {
+            return false;
+        }
+        self.checkpoint();
+        self.notes[index].length = length.max(1);
+        true
+    }
+
+    /// Pulls the selected notes' starts towards the nearest line of a `grid` ticks wide grid.
+    ///
+    /// `strength` from 0 (no change) to 1 (onto the line) sets how far, `swing` from 0
+    /// (straight) to 1 delays every other grid line by up to half a grid unit.
+    pub fn quantize(&mut self, selection: &[usize], grid: u64, strength: f32, swing: f32) {
+        if grid == 0 {
+            return;
+        }
+        let strength = f64::from(strength.clamp(0.0, 1.0));
+        let swing = f64::from(swing.clamp(0.0, 1.0));
+        let line = |n: u64| {
+            let delay = if n % 2 == 1 {
+                swing * grid as f64 / 2.0
+            } else {
+                0.0
+            };
+            (n * grid) as f64 + delay
+        };
+        self.edit(selection, |note| {
+            let start = note.start as f64;
+            let nearest = note.start / grid;
+            let target = [nearest.saturating_sub(1), nearest, nearest + 1]
+                .into_iter()
+                .map(line)
+                .min_by(|a, b| (a - start).abs().total_cmp(&(b - start).abs()))
+                .unwrap_or(start);
+            note.start = (target - start).mul_add(strength, start).round() as u64;
+        });
+    }
+
+    /// Moves the selected notes by `semitones`, staying within keys 0 to 127
+    pub fn transpose(&mut self, selection: &[usize], semitones: i8) {
+        self.edit(selection, |note| {
+            note.key = note.key.saturating_add_signed(semitones).min(127);
+        });
+    }
+
+    /// Sets the selected notes' velocities on a line from `from` at the earliest to `to` at
+    /// the latest, by start
+    pub fn velocity_ramp(&mut self, selection: &[usize], from: u8, to: u8) {
+        let starts = selection.iter().filter_map(|&index| self.notes.get(index));
+        let (Some(first), Some(last)) = (
+            starts.clone().map(|note| note.start).min(),
+            starts.map(|note| note.start).max(),
+        ) else {
+            return;
+        };
+        let span = (last - first).max(1) as f32;
+        self.edit(selection, |note| {
+            let position = (note.start - first) as f32 / span;
+            let velocity = (f32::from(to) - f32::from(from)).mul_add(position, f32::from(from));
+            note.velocity = velocity.round().clamp(1.0, 127.0) as u8;
+        });
+    }
+
+    /// Stretches or shortens each selected note to end where the next later selected note
+    /// starts, so the line plays without gaps or overlaps. The last notes keep their length.
+    pub fn legato(&mut self, selection: &[usize]) {
+        let mut starts: Vec<u64> = selection
+            .iter()
+            .filter_map(|&index| self.notes.get(index))
+            .map(|note| note.start)
+            .collect();
+        starts.sort_unstable();
+        starts.dedup();
+        self.edit(selection, |note| {
+            let next = starts.partition_point(|&start| start <= note.start);
+            if let Some(&next) = starts.get(next) {
+                note.length = next - note.start;
+            }
+        });
+    }
+
+    /// Steps back one edit, `false` if there's nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let Some(notes) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(&mut self.notes, notes));
+        true
+    }
+
+    /// Redoes the last undone edit, `false` if there's nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let Some(notes) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(std::mem::replace(&mut self.notes, notes));
+        true
+    }
+
+    /// The notes as events for
+    /// [`MidiTrack::set_sequence`](crate::track::midi::MidiTrack::set_sequence), with note
+    /// offs ahead of note ons on the same frame
+    #[must_use]
+    pub fn to_sequence(&self, samples_per_tick: f64) -> Vec<SequenceEvent> {
+        let frame = |tick: u64| (tick as f64 * samples_per_tick).round() as usize;
+        let mut sequence: Vec<_> = (0u32..)
+            .zip(&self.notes)
+            .flat_map(|(index, note)| {
+                let event = |frame, kind| SequenceEvent {
+                    frame,
+                    kind,
+                    note: Some(index),
+                    chance: note.chance,
+                };
+                [
+                    event(
+                        frame(note.start),
+                        EventKind::NoteOn {
+                            channel: note.channel,
+                            key: note.key,
+                            velocity: note.velocity,
+                        },
+                    ),
+                    event(
+                        frame(note.start + note.length),
+                        EventKind::NoteOff {
+                            channel: note.channel,
+                            key: note.key,
+                        },
+                    ),
+                ]
+            })
+            .collect();
+        sequence.sort_by_key(|event| (event.frame, matches!(event.kind, EventKind::NoteOn { .. })));
+        sequence
+    }
+
+    /// Applies `change` to every selected note as one undoable edit
+    fn edit(&mut self, selection: &[usize], mut change: impl FnMut(&mut Note)) {
+        let valid = || selection.iter().filter(|&&index| index < self.notes.len());
+        if valid().next().is_none() {
+            return;
+        }
+        let mut selected: Vec<usize> = valid().copied().collect();
+        selected.sort_unstable();
+        selected.dedup();
+        self.checkpoint();
+        for index in selected {
+            change(&mut self.notes[index]);
+        }
+        self.sort();
+    }
+
+    /// Remembers the notes before an edit
+    fn checkpoint(&mut self) {
+        if self.undo.len() == MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.undo.push(self.notes.clone());
+        self.redo.clear();
+    }
+
+    fn sort(&mut self) {
+        self.notes.sort_by_key(|note| (note.start, note.key));
+    }
+
+    fn index_of(&self, note: &Note) -> usize {
+        self.notes
+            .iter()
+            .position(|other| other == note)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(start: u64, key: u8) -> Note {
+        Note {
+            start,
+            length: 60,
+            key,
+            velocity: 100,
+            channel: 0,
+            chance: 100,
+        }
+    }
+
+    fn clip(starts: &[u64]) -> MidiClip {
+        let mut clip = MidiClip::new();
+        for &start in starts {
+            clip.add_note(note(start, 60));
+        }
+        clip
+    }
+
+    fn starts(clip: &MidiClip) -> Vec<u64> {
+        clip.notes().iter().map(|note| note.start).collect()
+    }
+
+    #[test]
+    fn test_quantize_with_strength_and_swing() {
+        let mut half = clip(&[10, 250]);
+        half.quantize(&half.all(), 120, 0.5, 0.0);
+        assert_eq!(starts(&half), vec![5, 245]);
+
+        // the off-beat line at 120 moves to 150
+        let mut swung = clip(&[0, 140, 235]);
+        swung.quantize(&swung.all(), 120, 1.0, 0.5);
+        assert_eq!(starts(&swung), vec![0, 150, 240]);
+    }
+
+    #[test]
+    fn test_edits_can_be_undone_and_redone() {
+        let mut clip = clip(&[0, 120, 240]);
+        let index = clip.move_note(0, 300, 64).unwrap();
+        assert_eq!(index, 2);
+        clip.legato(&[0, 1]);
+        clip.velocity_ramp(&clip.all(), 40, 120);
+        clip.transpose(&[2], 12);
+
+        assert_eq!(clip.notes()[0].length, 120);
+        let velocities: Vec<_> = clip.notes().iter().map(|n| n.velocity).collect();
+        assert_eq!(velocities, vec![40, 93, 120]);
+        assert_eq!(clip.notes()[2].key, 76);
+
+        for _ in 0..4 {
+            assert!(clip.undo());
+        }
+        assert_eq!(starts(&clip), vec![0, 120, 240]);
+        assert!(clip.redo());
+        assert_eq!(starts(&clip), vec![120, 240, 300]);
+        clip.remove_note(0);
+        assert!(!clip.redo());
+    }
+
+    #[test]
+    fn test_sequence_puts_note_offs_first() {
+        let mut clip = clip(&[0, 60]);
+        clip.transpose(&[1], 2);
+        let sequence = clip.to_sequence(10.0);
+
+        assert_eq!(sequence.len(), 4);
+        assert_eq!(sequence[1].frame, 600);
+        assert_eq!(
+            sequence[1].kind,
+            EventKind::NoteOff {
+                channel: 0,
+                key: 60
+            }
+        );
+        assert_eq!(sequence[1].note, Some(0));
+        assert!(matches!(
+            sequence[2].kind,
+            EventKind::NoteOn { key: 62, .. }
+        ));
+    }
+}
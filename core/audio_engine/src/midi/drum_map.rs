@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+/// General MIDI percussion, keys 35 to 81
+const GENERAL_MIDI: [&str; 47] = [
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+];
+const GENERAL_MIDI_FIRST_KEY: u8 = 35;
+
+/// Names of the pads of a drum instrument, by key
+///
+/// # Example
+/// ```
+/// use audio_engine::midi::drum_map::DrumMap;
+///
+/// let mut pads = DrumMap::new("pads");
+/// pads.set_name(36, "Acoustic Snare");
+///
+/// // a General MIDI snare pattern played on the pads
+/// let remap = DrumMap::general_midi().remap_to(&pads);
+/// assert_eq!(remap.apply(38), 36);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrumMap {
+    pub name: String,
+    names: BTreeMap<u8, String>,
+}
+
+impl DrumMap {
+    /// A map without any pads named
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// The General MIDI percussion layout
+    #[must_use]
+    pub fn general_midi() -> Self {
+        let mut map = Self::new("General MIDI");
+        for (key, name) in (GENERAL_MIDI_FIRST_KEY..).zip(GENERAL_MIDI) {
+            map.set_name(key, name);
+        }
+        map
+    }
+
+    /// Names the pad on `key`, replacing its old name
+    pub fn set_name(&mut self, key: u8, name: &str) {
+        self.names.insert(key.min(127), name.to_owned());
+    }
+
+    pub fn remove_name(&mut self, key: u8) {
+        self.names.remove(&key);
+    }
+
+    pub fn name(&self, key: u8) -> Option<&str> {
+        self.names.get(&key).map(String::as_str)
+    }
+
+    /// The lowest key of the pad named `name`, ignoring case
+    #[must_use]
+    pub fn key(&self, name: &str) -> Option<u8> {
+        self.names
+            .iter()
+            .find(|(_, pad)| pad.eq_ignore_ascii_case(name))
+            .map(|(&key, _)| key)
+    }
+
+    /// Named pads, lowest key first
+    pub fn pads(&self) -> impl Iterator<Item = (u8, &str)> {
+        self.names.iter().map(|(&key, name)| (key, name.as_str()))
+    }
+
+    /// Moves notes written for this layout to the keys of the pads with the same names in
+    /// `target`. Pads `target` doesn't have stay on their keys.
+    #[must_use]
+    pub fn remap_to(&self, target: &Self) -> KeyRemap {
+        let mut remap = KeyRemap::identity();
+        for (key, name) in self.pads() {
+            if let Some(to) = target.key(name) {
+                remap.set(key, to);
+            }
+        }
+        remap
+    }
+}
+
+/// A key for every MIDI key, applied to notes at playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRemap([u8; 128]);
+
+impl Default for KeyRemap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl KeyRemap {
+    /// Every key stays where it is
+    #[must_use]
+    pub fn identity() -> Self {
+        Self(std::array::from_fn(|key| key as u8))
+    }
+
+    /// Plays notes on `from` on `to` instead
+    pub fn set(&mut self, from: u8, to: u8) {
+        self.0[usize::from(from.min(127))] = to.min(127);
+    }
+
+    #[must_use]
+    pub fn apply(&self, key: u8) -> u8 {
+        self.0[usize::from(key.min(127))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remap_matches_pads_by_name() {
+        let gm = DrumMap::general_midi();
+        assert_eq!(gm.name(42), Some("Closed Hi-Hat"));
+        assert_eq!(gm.key("open hi-hat"), Some(46));
+
+        let mut pads = DrumMap::new("pads");
+        pads.set_name(36, "Bass Drum 1");
+        pads.set_name(37, "Closed Hi-Hat");
+        let remap = gm.remap_to(&pads);
+
+        assert_eq!(remap.apply(36), 36);
+        assert_eq!(remap.apply(42), 37);
+        // no crash pad on the target, the note stays put
+        assert_eq!(remap.apply(49), 49);
+    }
+}
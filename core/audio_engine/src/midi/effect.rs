@@ -0,0 +1,367 @@
+use transport::{clock::TempoClock, resolution::QuantizeResolution};
+
+use crate::midi::{EventKind, EventList};
+
+/// Transforms events on their way to an instrument, e.g. in a
+/// [`MidiTrack`](crate::track::midi::MidiTrack)'s effect chain
+pub trait MidiEffect
+where
+    Self: Sync + Send,
+{
+    /// Writes the events of a block of `frames` frames starting at frame `position` of the
+    /// track, transformed, to `output`
+    fn process(
+        &mut self,
+        input: &EventList,
+        output: &mut EventList,
+        position: usize,
+        frames: usize,
+    );
+    /// Forgets held notes and any other state, e.g. after a seek
+    fn reset(&mut self) {}
+}
+
+/// The keys of a scale, as semitones above its root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    /// Bit `n` set when the scale has the key `n` semitones above the root
+    mask: u16,
+}
+
+impl Scale {
+    pub const CHROMATIC: Self = Self::from_steps(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    pub const MAJOR: Self = Self::from_steps(&[0, 2, 4, 5, 7, 9, 11]);
+    pub const NATURAL_MINOR: Self = Self::from_steps(&[0, 2, 3, 5, 7, 8, 10]);
+    pub const HARMONIC_MINOR: Self = Self::from_steps(&[0, 2, 3, 5, 7, 8, 11]);
+    pub const DORIAN: Self = Self::from_steps(&[0, 2, 3, 5, 7, 9, 10]);
+    pub const MIXOLYDIAN: Self = Self::from_steps(&[0, 2, 4, 5, 7, 9, 10]);
+    pub const MAJOR_PENTATONIC: Self = Self::from_steps(&[0, 2, 4, 7, 9]);
+    pub const MINOR_PENTATONIC: Self = Self::from_steps(&[0, 3, 5, 7, 10]);
+
+    /// A scale of the given semitones above the root, taken modulo 12
+    #[must_use]
+    pub const fn from_steps(steps: &[u8]) -> Self {
+        let mut mask = 0;
+        let mut index = 0;
+        while index < steps.len() {
+            mask |= 1 << (steps[index] % 12);
+            index += 1;
+        }
+        Self { mask }
+    }
+
+    /// `true` if the key `semitones` above the root is in the scale
+    #[must_use]
+    pub const fn contains(&self, semitones: u8) -> bool {
+        self.mask & (1 << (semitones % 12)) != 0
+    }
+}
+
+/// Moves notes onto the nearest key of a scale, the lower one when two are as near
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScaleQuantize {
+    /// Pitch class of the root, 0 for C to 11 for B
+    root: u8,
+    scale: Scale,
+}
+
+impl ScaleQuantize {
+    #[must_use]
+    pub const fn new(root: u8, scale: Scale) -> Self {
+        Self {
+            root: root % 12,
+            scale,
+        }
+    }
+
+    /// The scale key nearest `key`
+    #[must_use]
+    pub fn quantize(&self, key: u8) -> u8 {
+        if self.scale.mask == 0 {
+            return key;
+        }
+        let in_scale = |key: u8| self.scale.contains(key + 12 - self.root);
+        (0..12u8)
+            .flat_map(|distance| [key.checked_sub(distance), key.checked_add(distance)])
+            .flatten()
+            .find(|&candidate| candidate <= 127 && in_scale(candidate))
+            .unwrap_or(key)
+    }
+}
+
+impl MidiEffect for ScaleQuantize {
+    fn process(&mut self, input: &EventList, output: &mut EventList, _: usize, _: usize) {
+        for event in input.iter() {
+            let kind = event
+                .kind
+                .key()
+                .map_or(event.kind, |key| event.kind.with_key(self.quantize(key)));
+            output.push(event.offset, kind);
+        }
+    }
+}
+
+/// Plays a chord for every note: one note per interval, in semitones from the played key.
+/// Intervals that would leave the MIDI key range are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChordTrigger {
+    intervals: Vec<i8>,
+}
+
+impl ChordTrigger {
+    /// A chord of `intervals`, include 0 to keep the played note
+    #[must_use]
+    pub fn new(intervals: &[i8]) -> Self {
+        let mut intervals = intervals.to_vec();
+        intervals.sort_unstable();
+        intervals.dedup();
+        Self { intervals }
+    }
+
+    #[must_use]
+    pub fn major() -> Self {
+        Self::new(&[0, 4, 7])
+    }
+
+    #[must_use]
+    pub fn minor() -> Self {
+        Self::new(&[0, 3, 7])
+    }
+
+    #[must_use]
+    pub fn intervals(&self) -> &[i8] {
+        &self.intervals
+    }
+}
+
+impl MidiEffect for ChordTrigger {
+    fn process(&mut self, input: &EventList, output: &mut EventList, _: usize, _: usize) {
+        for event in input.iter() {
+            let Some(key) = event.kind.key() else {
+                output.push(event.offset, event.kind);
+                continue;
+            };
+            for &interval in &self.intervals {
+                if let Some(key) = key.checked_add_signed(interval).filter(|&key| key <= 127) {
+                    output.push(event.offset, event.kind.with_key(key));
+                }
+            }
+        }
+    }
+}
+
+/// Retriggers held notes on every line of a tempo-synced grid, for finger drumming: hold a
+/// pad and it repeats at the chosen division until released.
+///
+/// The grid starts at the track's first frame. Live notes routed to the track with
+/// [`SchedulerCommand::Midi`](crate::scheduler::command::SchedulerCommand::Midi) repeat like
+/// sequenced ones, and [`NoteRepeat::ENABLE`] and [`NoteRepeat::INTERVAL`] parameter events
+/// switch it and change the division while playing.
+#[derive(Debug, Clone)]
+pub struct NoteRepeat {
+    /// Frames between repeats
+    interval: f64,
+    enabled: bool,
+    /// `(channel, velocity, frame pressed)` of every held key
+    held: [Option<(u8, u8, usize)>; 128],
+}
+
+impl NoteRepeat {
+    /// Parameter turning repeats on (above 0.5) or off
+    pub const ENABLE: u32 = 0x4E52_0001;
+    /// Parameter setting the frames between repeats
+    pub const INTERVAL: u32 = 0x4E52_0002;
+
+    /// Repeats every `interval` frames
+    #[must_use]
+    pub fn new(interval: f64) -> Self {
+        Self {
+            interval: interval.max(1.0),
+            enabled: true,
+            held: [None; 128],
+        }
+    }
+
+    /// Repeats on every `division` line at the clock's tempo
+    #[must_use]
+    pub fn synced(division: QuantizeResolution, clock: &TempoClock) -> Self {
+        Self::new(Self::interval_of(division, clock))
+    }
+
+    /// Frames between `division` lines at the clock's tempo
+    #[must_use]
+    pub fn interval_of(division: QuantizeResolution, clock: &TempoClock) -> f64 {
+        division.ticks_per_grid_unit(clock.ticks_per_beat) as f64 * clock.samples_per_tick()
+    }
+
+    #[must_use]
+    pub const fn interval(&self) -> f64 {
+        self.interval
+    }
+
+    pub const fn set_interval(&mut self, interval: f64) {
+        self.interval = interval.max(1.0);
+    }
+
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Switching off lets held notes ring without repeating
+    pub const fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Retriggers the keys held since before `line`
+    fn repeat(&self, output: &mut EventList, line: usize, position: usize) {
+        for (key, held) in (0u8..).zip(&self.held) {
+            if let Some((channel, velocity, pressed)) = *held
+                && pressed < line
+            {
+                output.push(line - position, EventKind::NoteOff { channel, key });
+                output.push(
+                    line - position,
+                    EventKind::NoteOn {
+                        channel,
+                        key,
+                        velocity,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl MidiEffect for NoteRepeat {
+    fn process(
+        &mut self,
+        input: &EventList,
+        output: &mut EventList,
+        position: usize,
+        frames: usize,
+    ) {
+        let end = position + frames;
+        let mut index = (position as f64 / self.interval).ceil();
+        let mut line = (index * self.interval).round() as usize;
+        let mut repeat_until = |this: &Self, until: usize, output: &mut EventList| {
+            while this.enabled && line < until {
+                this.repeat(output, line, position);
+                index += 1.0;
+                line = (index * this.interval).round() as usize;
+            }
+        };
+
+        for event in input.iter() {
+            let at = position + event.offset;
+            repeat_until(self, at.min(end), output);
+            match event.kind {
+                EventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                } => self.held[usize::from(key.min(127))] = Some((channel, velocity, at)),
+                EventKind::NoteOff { key, .. } => self.held[usize::from(key.min(127))] = None,
+                EventKind::Parameter {
+                    id: Self::ENABLE,
+                    value,
+                } => {
+                    self.enabled = value > 0.5;
+                    continue;
+                }
+                EventKind::Parameter {
+                    id: Self::INTERVAL,
+                    value,
+                } => {
+                    self.set_interval(f64::from(value));
+                    continue;
+                }
+                _ => {}
+            }
+            output.push(event.offset, event.kind);
+        }
+        repeat_until(self, end, output);
+    }
+
+    fn reset(&mut self) {
+        self.held = [None; 128];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::EventKind;
+
+    fn keys(effect: &mut dyn MidiEffect, keys: &[u8]) -> Vec<u8> {
+        let mut input = EventList::new();
+        for &key in keys {
+            input.push(
+                0,
+                EventKind::NoteOn {
+                    channel: 0,
+                    key,
+                    velocity: 100,
+                },
+            );
+        }
+        let mut output = EventList::new();
+        effect.process(&input, &mut output, 0, 64);
+        output.iter().filter_map(|event| event.kind.key()).collect()
+    }
+
+    #[test]
+    fn test_scale_quantize_snaps_to_nearest_key() {
+        // D major: C# and F# are in, C and F aren't
+        let mut d_major = ScaleQuantize::new(2, Scale::MAJOR);
+        assert_eq!(
+            keys(&mut d_major, &[60, 61, 65, 66, 0]),
+            vec![59, 61, 64, 66, 1]
+        );
+    }
+
+    #[test]
+    fn test_chord_trigger_expands_notes() {
+        let mut chord = ChordTrigger::minor();
+        assert_eq!(keys(&mut chord, &[60]), vec![60, 63, 67]);
+        // the fifth above 125 is out of range
+        assert_eq!(keys(&mut chord, &[125]), vec![125]);
+    }
+
+    #[test]
+    fn test_note_repeat_retriggers_held_keys_on_the_grid() {
+        let mut repeat = NoteRepeat::new(10.0);
+        let on = EventKind::NoteOn {
+            channel: 9,
+            key: 36,
+            velocity: 90,
+        };
+        let mut input = EventList::new();
+        input.push(3, on);
+        let mut output = EventList::new();
+        repeat.process(&input, &mut output, 100, 16);
+
+        let ons = |output: &EventList| {
+            output
+                .iter()
+                .filter(|event| matches!(event.kind, EventKind::NoteOn { .. }))
+                .map(|event| event.offset)
+                .collect::<Vec<_>>()
+        };
+        // pressed at 103, repeats on line 110, then on 120 before the release at 128
+        assert_eq!(ons(&output), vec![3, 10]);
+
+        let mut input = EventList::new();
+        input.push(
+            12,
+            EventKind::NoteOff {
+                channel: 9,
+                key: 36,
+            },
+        );
+        output.clear();
+        repeat.process(&input, &mut output, 116, 20);
+        assert_eq!(ons(&output), vec![4]);
+        assert_eq!(output.len(), 3);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::rng::mix;
+
+/// Random variation applied to sequenced notes as they play.
+///
+/// Every roll is derived from the seed, the note and how often playback has jumped since
+/// the last reset, so a render from the start with the same seed always comes out the
+/// same, while loop passes still differ from each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Humanize {
+    /// Most frames a note moves either way
+    pub timing: usize,
+    /// Most a note's velocity changes either way
+    pub velocity: u8,
+    pub seed: u64,
+}
+
+/// Tells the rolls for a note apart
+const CHANCE: u64 = 0;
+const TIMING: u64 = 1;
+const VELOCITY: u64 = 2;
+
+impl Humanize {
+    /// Whether a note with a `chance` percent chance plays this pass
+    pub(crate) fn plays(&self, pass: u64, note: u32, chance: u8) -> bool {
+        chance >= 100 || self.roll(pass, note, CHANCE) * 100.0 < f64::from(chance)
+    }
+
+    /// Frames a note moves by this pass
+    pub(crate) fn offset(&self, pass: u64, note: u32) -> isize {
+        if self.timing == 0 {
+            return 0;
+        }
+        let spread = (self.timing * 2 + 1) as f64;
+        (self.roll(pass, note, TIMING) * spread) as isize - self.timing.cast_signed()
+    }
+
+    /// A note's velocity this pass, from 1 to 127
+    pub(crate) fn velocity(&self, pass: u64, note: u32, velocity: u8) -> u8 {
+        if self.velocity == 0 {
+            return velocity;
+        }
+        let spread = f64::from(u16::from(self.velocity) * 2 + 1);
+        let change = (self.roll(pass, note, VELOCITY) * spread) as i16 - i16::from(self.velocity);
+        (i16::from(velocity) + change).clamp(1, 127) as u8
+    }
+
+    /// A number in `0.0..1.0`, the same for the same arguments
+    fn roll(&self, pass: u64, note: u32, kind: u64) -> f64 {
+        let hash = mix(self.seed ^ mix(pass ^ mix((u64::from(note) << 2) | kind)));
+        (hash >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolls_stay_in_range_and_follow_chance() {
+        let humanize = Humanize {
+            timing: 10,
+            velocity: 20,
+            seed: 7,
+        };
+        let mut played = 0;
+        for note in 0..1000 {
+            assert!(humanize.offset(0, note).abs() <= 10);
+            assert!((80..=120).contains(&humanize.velocity(0, note, 100)));
+            if humanize.plays(0, note, 25) {
+                played += 1;
+            }
+        }
+        assert!((200..300).contains(&played));
+        assert_eq!(humanize.offset(3, 5), humanize.offset(3, 5));
+        assert!(!humanize.plays(0, 1, 0));
+    }
+}
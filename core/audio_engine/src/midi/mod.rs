@@ -0,0 +1,248 @@
+//! Note and controller events for instruments, timed to the frame within a block.
+
+use std::ops::Range;
+
+use crate::buffer::AudioBuffer;
+
+pub mod clip;
+pub mod drum_map;
+pub mod effect;
+pub mod humanize;
+pub mod sampler;
+pub mod sfz;
+
+/// Events an [`EventList`] holds before further ones are dropped
+pub const MAX_BLOCK_EVENTS: usize = 512;
+
+/// What happens at an [`Event`]. Channels count from 0 to 15, keys, velocities and
+/// controller values from 0 to 127 as in MIDI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    NoteOn {
+        channel: u8,
+        key: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        key: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// An instrument's own parameter, ids and ranges are up to the instrument
+    Parameter {
+        id: u32,
+        value: f32,
+    },
+}
+
+impl EventKind {
+    /// The key of a note on or off
+    #[must_use]
+    pub const fn key(&self) -> Option<u8> {
+        match *self {
+            Self::NoteOn { key, .. } | Self::NoteOff { key, .. } => Some(key),
+            Self::ControlChange { .. } | Self::Parameter { .. } => None,
+        }
+    }
+
+    /// The same note on another key, other events unchanged
+    #[must_use]
+    pub const fn with_key(self, key: u8) -> Self {
+        match self {
+            Self::NoteOn {
+                channel, velocity, ..
+            } => Self::NoteOn {
+                channel,
+                key,
+                velocity,
+            },
+            Self::NoteOff { channel, .. } => Self::NoteOff { channel, key },
+            other => other,
+        }
+    }
+}
+
+/// An event at a frame of the block being rendered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    /// Frame within the block, from 0
+    pub offset: usize,
+    pub kind: EventKind,
+}
+
+/// An event of a [`MidiTrack`](crate::track::midi::MidiTrack)'s sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceEvent {
+    /// Frame of the track's material the event plays at
+    pub frame: usize,
+    pub kind: EventKind,
+    /// The note the event belongs to: a note's on and off share it, so they are humanized
+    /// and left out together. Events without one play as they are.
+    pub note: Option<u32>,
+    /// Percent chance the note plays each time it comes round
+    pub chance: u8,
+}
+
+impl From<(usize, EventKind)> for SequenceEvent {
+    fn from((frame, kind): (usize, EventKind)) -> Self {
+        Self {
+            frame,
+            kind,
+            note: None,
+            chance: 100,
+        }
+    }
+}
+
+/// The events of one block, sorted by offset. Events at the same offset keep the order they
+/// were added in.
+///
+/// Preallocated to [`MAX_BLOCK_EVENTS`]: events past that are dropped instead of growing the
+/// list on the audio thread.
+#[derive(Debug, Clone)]
+pub struct EventList {
+    events: Vec<Event>,
+}
+
+impl Default for EventList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventList {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            events: Vec::with_capacity(MAX_BLOCK_EVENTS),
+        }
+    }
+
+    /// Adds an event at `offset`, `false` if the list is full and it was dropped
+    pub fn push(&mut self, offset: usize, kind: EventKind) -> bool {
+        if self.events.len() == self.events.capacity() {
+            return false;
+        }
+        let index = self.events.partition_point(|event| event.offset <= offset);
+        self.events.insert(index, Event { offset, kind });
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    /// Replaces every event's kind with `f` of it, keeping offsets
+    pub fn map_kinds(&mut self, mut f: impl FnMut(EventKind) -> EventKind) {
+        for event in &mut self.events {
+            event.kind = f(event.kind);
+        }
+    }
+
+    /// Splits a block of `frames` frames at the events: each item holds the events at a frame
+    /// and the frames to render before the next ones, so instruments can apply events and
+    /// render in turns. Events at or past `frames` come last, with an empty range.
+    pub fn segments(&self, frames: usize) -> impl Iterator<Item = (&[Event], Range<usize>)> {
+        let mut index = 0;
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            if index == self.events.len() && start >= frames {
+                return None;
+            }
+            let taken = if start >= frames {
+                self.events.len() - index
+            } else {
+                self.events[index..].partition_point(|event| event.offset <= start)
+            };
+            let events = &self.events[index..index + taken];
+            index += taken;
+            let end = self
+                .events
+                .get(index)
+                .map_or(frames, |event| event.offset.min(frames))
+                .max(start);
+            let range = start..end;
+            start = end;
+            Some((events, range))
+        })
+    }
+}
+
+/// Turns events into audio, e.g. a synth or sampler played by a
+/// [`MidiTrack`](crate::track::midi::MidiTrack)
+pub trait Instrument
+where
+    Self: Sync + Send,
+{
+    /// Renders all `buffer.frames()` frames of a (at least) stereo buffer, applying each of
+    /// `events` at its offset
+    fn process(&mut self, events: &EventList, buffer: &mut AudioBuffer);
+    /// Stereo outputs the instrument renders to, e.g. one per pad of a drum sampler.
+    /// The first is the main output.
+    fn outputs(&self) -> usize {
+        1
+    }
+    /// Renders a block to each of [`Instrument::outputs`] stereo buffers, all of the same
+    /// length. Single-output instruments play everything on the first.
+    fn process_outputs(&mut self, events: &EventList, outputs: &mut [AudioBuffer]) {
+        if let Some(main) = outputs.first_mut() {
+            self.process(events, main);
+        }
+    }
+    /// Silences every voice at once, e.g. after a seek
+    fn reset(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(key: u8) -> EventKind {
+        EventKind::NoteOn {
+            channel: 0,
+            key,
+            velocity: 100,
+        }
+    }
+
+    #[test]
+    fn test_segments_split_block_at_event_offsets() {
+        let mut events = EventList::new();
+        events.push(10, note(62));
+        events.push(0, note(60));
+        events.push(10, note(64));
+        events.push(40, note(65));
+
+        let segments: Vec<_> = events
+            .segments(32)
+            .map(|(events, range)| (events.iter().map(|e| e.kind).collect::<Vec<_>>(), range))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (vec![note(60)], 0..10),
+                (vec![note(62), note(64)], 10..32),
+                (vec![note(65)], 32..32),
+            ]
+        );
+        assert_eq!(EventList::new().segments(8).count(), 1);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    ops::{Range, RangeInclusive},
+    sync::Arc,
+};
+
+use crate::{
+    buffer::AudioBuffer,
+    midi::{EventKind, EventList, Instrument},
+    rng::SeededRng,
+};
+
+/// Voices a [`Sampler`] plays at once, the oldest is cut off for a new one past that
+pub const MAX_VOICES: usize = 64;
+
+/// Frames a released note fades out over by default, enough to avoid a click
+const DEFAULT_RELEASE: usize = 256;
+
+/// A sample played over a range of keys and velocities of a [`Sampler`]
+#[derive(Debug, Clone)]
+pub struct Zone {
+    /// Audio at the engine's sample rate, mono is played on both sides
+    pub sample: Arc<AudioBuffer>,
+    /// Key the sample plays at its recorded pitch, other keys resample it
+    pub root: u8,
+    pub keys: RangeInclusive<u8>,
+    pub velocities: RangeInclusive<u8>,
+    /// Linear gain, on top of the note's velocity
+    pub gain: f32,
+    /// Frames a note fades in over
+    pub attack: usize,
+    /// Frames a released note fades out over, `None` for the sampler's [`Sampler::release`]
+    pub release: Option<usize>,
+    /// Cutoff of a one-pole lowpass on the zone's voices, in cycles per frame (the frequency
+    /// over the sample rate)
+    pub cutoff: Option<f32>,
+    /// `(start, end)` frames looped while the note is held
+    pub loop_points: Option<(usize, usize)>,
+    /// Zones taking turns on the same notes: each key counts its notes, and the zone plays
+    /// those where the count modulo `round_robin_length` is `round_robin_position`
+    pub round_robin_length: u8,
+    pub round_robin_position: u8,
+    /// Zones picked at random: every note draws a number in `0.0..1.0` from the sampler's
+    /// seeded generator, and the zone plays when it falls in this range
+    pub random: Range<f32>,
+    /// Plays the whole sample whatever the note off, e.g. for drums
+    pub one_shot: bool,
+    /// Instrument output the zone plays on, see [`Instrument::outputs`]
+    pub output: usize,
+}
+
+impl Zone {
+    /// A zone playing `sample` on every key and velocity, at its recorded pitch on `root`
+    #[must_use]
+    pub fn new(sample: Arc<AudioBuffer>, root: u8) -> Self {
+        Self {
+            sample,
+            root: root.min(127),
+            keys: 0..=127,
+            velocities: 1..=127,
+            gain: 1.0,
+            attack: 0,
+            release: None,
+            cutoff: None,
+            loop_points: None,
+            round_robin_length: 1,
+            round_robin_position: 0,
+            random: 0.0..1.0,
+            one_shot: false,
+            output: 0,
+        }
+    }
+
+    fn plays(&self, key: u8, velocity: u8, count: u32, roll: f32) -> bool {
+        let length = u32::from(self.round_robin_length.max(1));
+        self.keys.contains(&key)
+            && self.velocities.contains(&velocity)
+            && count % length == u32::from(self.round_robin_position)
+            && self.random.contains(&roll)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    zone: usize,
+    channel: u8,
+    key: u8,
+    /// Frame of the sample, between frames while resampling
+    position: f64,
+    /// Sample frames per output frame
+    step: f64,
+    gain: f32,
+    /// Frames played, for the attack
+    age: usize,
+    held: bool,
+    /// Frames the release fade lasts
+    release: usize,
+    /// Frames left of the release fade, once released
+    fade: Option<usize>,
+    /// Lowpass state per side
+    filter: [f32; 2],
+}
+
+/// A multisampled instrument: every note plays the zones covering its key and velocity.
+///
+/// Velocity layers are zones over different velocity ranges, round robin alternates zones
+/// over the same notes to avoid the machine-gun effect of one sample repeating. Released
+/// notes fade out over their zone's release, [`Sampler::release`] frames unless it sets its
+/// own, and one-shot zones play on to the end of their sample.
+///
+/// Zones can play on separate outputs. The output count is read when the sampler is handed
+/// to a [`MidiTrack`](crate::track::midi::MidiTrack), so set zones up before that.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+///
+/// use audio_engine::{
+///     buffer::AudioBuffer,
+///     midi::sampler::{Sampler, Zone},
+/// };
+///
+/// let mut sampler = Sampler::new();
+/// let mut soft = Zone::new(Arc::new(AudioBuffer::stereo(4800)), 60);
+/// soft.velocities = 1..=63;
+/// let mut hard = soft.clone();
+/// hard.velocities = 64..=127;
+/// sampler.add_zone(soft);
+/// sampler.add_zone(hard);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    zones: Vec<Zone>,
+    /// Preallocated to [`MAX_VOICES`], oldest first
+    voices: Vec<Voice>,
+    /// Notes played per key, for round robin
+    counts: [u32; 128],
+    /// Draws the number random zones are picked by, see [`Zone::random`]
+    rng: SeededRng,
+    release: usize,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            zones: Vec::new(),
+            voices: Vec::with_capacity(MAX_VOICES),
+            counts: [0; 128],
+            rng: SeededRng::new(0),
+            release: DEFAULT_RELEASE,
+        }
+    }
+
+    /// Adds a zone, returning its index
+    pub fn add_zone(&mut self, zone: Zone) -> usize {
+        self.zones.push(zone);
+        self.zones.len() - 1
+    }
+
+    /// Removes a zone, cutting off its voices
+    pub fn remove_zone(&mut self, index: usize) -> Option<Zone> {
+        if index >= self.zones.len() {
+            return None;
+        }
+        self.voices.retain(|voice| voice.zone != index);
+        for voice in &mut self.voices {
+            if voice.zone > index {
+                voice.zone -= 1;
+            }
+        }
+        Some(self.zones.remove(index))
+    }
+
+    #[must_use]
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    pub fn zone_mut(&mut self, index: usize) -> Option<&mut Zone> {
+        self.zones.get_mut(index)
+    }
+
+    /// Frames a released note fades out over, for zones without a release of their own
+    #[must_use]
+    pub const fn release(&self) -> usize {
+        self.release
+    }
+
+    pub const fn set_release(&mut self, frames: usize) {
+        self.release = frames;
+    }
+
+    /// Seeds the random zone picks, so a render picks the same zones every time. See
+    /// [`Project::seed_for`](crate::project::Project::seed_for).
+    pub const fn set_seed(&mut self, seed: u64) {
+        self.rng = SeededRng::new(seed);
+    }
+
+    fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        let key = key.min(127);
+        let count = self.counts[usize::from(key)];
+        self.counts[usize::from(key)] = count.wrapping_add(1);
+        let roll = self.rng.next_f32();
+        for (index, zone) in self.zones.iter().enumerate() {
+            if !zone.plays(key, velocity, count, roll) {
+                continue;
+            }
+            if self.voices.len() == MAX_VOICES {
+                self.voices.remove(0);
+            }
+            self.voices.push(Voice {
+                zone: index,
+                channel,
+                key,
+                position: 0.0,
+                step: ((f64::from(key) - f64::from(zone.root)) / 12.0).exp2(),
+                gain: zone.gain * f32::from(velocity) / 127.0,
+                age: 0,
+                held: true,
+                release: zone.release.unwrap_or(self.release),
+                fade: None,
+                filter: [0.0; 2],
+            });
+        }
+    }
+
+    fn note_off(&mut self, channel: u8, key: u8) {
+        for voice in &mut self.voices {
+            if voice.held && voice.channel == channel && voice.key == key {
+                voice.held = false;
+                if !self.zones[voice.zone].one_shot {
+                    voice.fade = Some(voice.release);
+                }
+            }
+        }
+    }
+
+    /// Adds every voice's frames in `range` to its zone's output, dropping voices that end
+    fn render(&mut self, outputs: &mut [AudioBuffer], start: usize, end: usize) {
+        let zones = &self.zones;
+        self.voices.retain_mut(|voice| {
+            let zone = &zones[voice.zone];
+            let sample = &zone.sample;
+            let last = outputs.len() - 1;
+            let (left, right) = outputs[zone.output.min(last)].stereo_mut();
+            let right_channel = sample.channels().min(2) - 1;
+            let smoothing = zone
+                .cutoff
+                .map(|cutoff| 1.0 - (-std::f32::consts::TAU * cutoff.max(0.0)).exp());
+            for frame in start..end {
+                if let Some((loop_start, loop_end)) = zone.loop_points
+                    && voice.held
+                    && loop_end > loop_start
+                    && voice.position >= loop_end as f64
+                {
+                    voice.position -= (loop_end - loop_start) as f64;
+                }
+                let index = voice.position as usize;
+                if index >= sample.frames() || voice.fade == Some(0) {
+                    return false;
+                }
+                let mut gain = voice.gain;
+                if voice.age < zone.attack {
+                    gain *= voice.age as f32 / zone.attack as f32;
+                }
+                voice.age += 1;
+                if let Some(fade) = voice.fade.as_mut() {
+                    gain *= *fade as f32 / voice.release as f32;
+                    *fade -= 1;
+                }
+                let fraction = voice.position.fract() as f32;
+                let read = |channel: usize| {
+                    let samples = &sample.channel(channel)[..sample.frames()];
+                    let current = samples[index];
+                    let next = samples.get(index + 1).copied().unwrap_or(0.0);
+                    (next - current).mul_add(fraction, current)
+                };
+                let mut signal = [read(0), read(right_channel)];
+                if let Some(smoothing) = smoothing {
+                    for (state, sample) in voice.filter.iter_mut().zip(&mut signal) {
+                        *state = (*sample - *state).mul_add(smoothing, *state);
+                        *sample = *state;
+                    }
+                }
+                left[frame] = signal[0].mul_add(gain, left[frame]);
+                right[frame] = signal[1].mul_add(gain, right[frame]);
+                voice.position += voice.step;
+            }
+            true
+        });
+    }
+}
+
+impl Instrument for Sampler {
+    /// Plays every zone on `buffer`
+    fn process(&mut self, events: &EventList, buffer: &mut AudioBuffer) {
+        self.process_outputs(events, std::slice::from_mut(buffer));
+    }
+
+    fn outputs(&self) -> usize {
+        self.zones
+            .iter()
+            .map(|zone| zone.output + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Plays each zone on its output, zones past the last output play on the last
+    fn process_outputs(&mut self, events: &EventList, outputs: &mut [AudioBuffer]) {
+        let Some(frames) = outputs.first().map(AudioBuffer::frames) else {
+            return;
+        };
+        for (events, range) in events.segments(frames) {
+            for event in events {
+                match event.kind {
+                    EventKind::NoteOn {
+                        channel,
+                        key,
+                        velocity: 0,
+                    }
+                    | EventKind::NoteOff { channel, key } => self.note_off(channel, key),
+                    EventKind::NoteOn {
+                        channel,
+                        key,
+                        velocity,
+                    } => self.note_on(channel, key, velocity),
+                    _ => {}
+                }
+            }
+            self.render(outputs, range.start, range.end);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.voices.clear();
+        self.counts = [0; 128];
+        self.rng.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(key: u8, velocity: u8) -> EventKind {
+        EventKind::NoteOn {
+            channel: 0,
+            key,
+            velocity,
+        }
+    }
+
+    /// First frame each note of `notes` plays, one note per block
+    fn first_frames(sampler: &mut Sampler, notes: &[EventKind]) -> Vec<f32> {
+        notes
+            .iter()
+            .map(|&note| {
+                sampler.voices.clear();
+                let mut events = EventList::new();
+                events.push(0, note);
+                let mut buffer = AudioBuffer::stereo(4);
+                sampler.process(&events, &mut buffer);
+                buffer.frame(0).0
+            })
+            .collect()
+    }
+
+    fn constant(level: f32) -> Arc<AudioBuffer> {
+        Arc::new(AudioBuffer::from_frames(&[(level, level); 8]))
+    }
+
+    #[test]
+    fn test_velocity_layers_and_round_robin() {
+        let mut sampler = Sampler::new();
+        let mut soft = Zone::new(constant(0.5), 60);
+        soft.keys = 60..=60;
+        soft.velocities = 1..=63;
+        sampler.add_zone(soft);
+        for (position, level) in [(0, 0.25), (1, 1.0)] {
+            let mut hard = Zone::new(constant(level), 60);
+            hard.keys = 60..=60;
+            hard.velocities = 64..=127;
+            hard.round_robin_length = 2;
+            hard.round_robin_position = position;
+            sampler.add_zone(hard);
+        }
+
+        let played = first_frames(
+            &mut sampler,
+            &[note_on(60, 127), note_on(60, 127), note_on(60, 127)],
+        );
+        assert_eq!(played, vec![0.25, 1.0, 0.25]);
+        // the soft layer is picked by velocity, whatever the round robin count
+        let soft = first_frames(&mut sampler, &[note_on(60, 127 / 2)]);
+        assert!((soft[0] - 0.5 * 63.0 / 127.0).abs() < 1e-6);
+        // outside the zone's keys
+        assert_eq!(first_frames(&mut sampler, &[note_on(61, 20)]), vec![0.0]);
+        assert_eq!(sampler.outputs(), 1);
+    }
+
+    #[test]
+    fn test_random_zones_repeat_with_the_same_seed() {
+        let play = |seed| {
+            let mut sampler = Sampler::new();
+            sampler.set_seed(seed);
+            for (level, random) in [(0.25, 0.0..0.5), (1.0, 0.5..1.0)] {
+                let mut zone = Zone::new(constant(level), 60);
+                zone.random = random;
+                sampler.add_zone(zone);
+            }
+            first_frames(&mut sampler, &[note_on(60, 127); 16])
+        };
+
+        let played = play(5);
+        // one of the two zones plays every note
+        assert!(played.iter().all(|level| [0.25, 1.0].contains(level)));
+        assert!(played.contains(&0.25) && played.contains(&1.0));
+        assert_eq!(play(5), played);
+        assert_ne!(play(6), played);
+    }
+
+    #[test]
+    fn test_held_notes_loop_and_keys_resample() {
+        let ramp: Vec<_> = (0..10u8).map(|n| (f32::from(n), 0.0)).collect();
+        let mut zone = Zone::new(Arc::new(AudioBuffer::from_frames(&ramp)), 60);
+        zone.loop_points = Some((4, 8));
+        let mut sampler = Sampler::new();
+        sampler.add_zone(zone);
+
+        let mut events = EventList::new();
+        events.push(0, note_on(60, 127));
+        let mut buffer = AudioBuffer::stereo(12);
+        sampler.process(&events, &mut buffer);
+        assert_eq!(
+            buffer.channel(0),
+            &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 4.0, 5.0, 6.0, 7.0]
+        );
+
+        sampler.reset();
+        let mut events = EventList::new();
+        events.push(0, note_on(72, 127));
+        events.push(
+            2,
+            EventKind::NoteOff {
+                channel: 0,
+                key: 72,
+            },
+        );
+        sampler.set_release(2);
+        let mut buffer = AudioBuffer::stereo(6);
+        sampler.process(&events, &mut buffer);
+        // an octave up every other frame, then a two frame fade from the release
+        assert_eq!(buffer.channel(0), &[0.0, 2.0, 4.0, 3.0, 0.0, 0.0]);
+    }
+}
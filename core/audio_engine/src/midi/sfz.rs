@@ -0,0 +1,328 @@
+//! Loads SFZ instruments into a [`Sampler`].
+//!
+//! Supported opcodes: `sample`, `default_path`, `key`, `lokey`, `hikey`, `pitch_keycenter`,
+//! `lovel`, `hivel`, `volume`, `amplitude`, `loop_mode`, `loop_start`, `loop_end`,
+//! `seq_length`, `seq_position`, `ampeg_attack`, `ampeg_release`, `cutoff` and `output`.
+//! Both loop modes loop only while the note is held. Other opcodes and `#define`/`#include`
+//! are ignored, so instruments using them still load but may sound different.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::math::db_to_gain,
+    error::SfzError,
+    midi::sampler::{Sampler, Zone},
+    track::wav::WavTrack,
+};
+
+/// An opcode's name and value, with its line for error messages
+type Opcode = (String, String, usize);
+
+/// Opcodes of the headers a region inherits from, outermost first
+#[derive(Debug, Default)]
+struct Scopes {
+    control: Vec<Opcode>,
+    global: Vec<Opcode>,
+    master: Vec<Opcode>,
+    group: Vec<Opcode>,
+    region: Option<Vec<Opcode>>,
+    /// Header opcodes currently go to
+    current: String,
+}
+
+impl Scopes {
+    fn open(&mut self, header: &str) {
+        match header {
+            "global" => {
+                self.global.clear();
+                self.master.clear();
+                self.group.clear();
+            }
+            "master" => {
+                self.master.clear();
+                self.group.clear();
+            }
+            "group" => self.group.clear(),
+            "region" => self.region = Some(Vec::new()),
+            _ => {}
+        }
+        header.clone_into(&mut self.current);
+    }
+
+    fn push(&mut self, opcode: Option<Opcode>) {
+        let Some(opcode) = opcode else {
+            return;
+        };
+        let scope = match self.current.as_str() {
+            "control" => &mut self.control,
+            "global" => &mut self.global,
+            "master" => &mut self.master,
+            "group" => &mut self.group,
+            "region" => match self.region.as_mut() {
+                Some(region) => region,
+                None => return,
+            },
+            _ => return,
+        };
+        scope.push(opcode);
+    }
+}
+
+struct Loader<'a> {
+    directory: &'a Path,
+    sample_rate: f64,
+    /// Samples already loaded, regions often share them
+    samples: HashMap<PathBuf, Arc<AudioBuffer>>,
+    sampler: Sampler,
+}
+
+impl Loader<'_> {
+    /// Adds the open region as a zone, regions without a sample are left out
+    fn finish(&mut self, scopes: &mut Scopes) -> Result<(), SfzError> {
+        let Some(region) = scopes.region.take() else {
+            return Ok(());
+        };
+        let opcodes: Vec<&Opcode> = scopes
+            .control
+            .iter()
+            .chain(&scopes.global)
+            .chain(&scopes.master)
+            .chain(&scopes.group)
+            .chain(&region)
+            .collect();
+        let last = |name: &str| {
+            opcodes
+                .iter()
+                .rev()
+                .find(|(opcode, ..)| opcode == name)
+                .map(|(_, value, _)| value.replace('\\', "/"))
+        };
+        let Some(sample) = last("sample") else {
+            return Ok(());
+        };
+        let path = self
+            .directory
+            .join(last("default_path").unwrap_or_default())
+            .join(sample);
+        let mut zone = Zone::new(self.sample(path)?, 60);
+
+        let mut looping = false;
+        let mut loop_start = 0;
+        let mut loop_end = None;
+        let mut amplitude = 1.0;
+        for (name, value, line) in opcodes {
+            let invalid = || SfzError::Opcode {
+                line: *line,
+                opcode: name.clone(),
+                value: value.clone(),
+            };
+            let number = || value.parse::<f64>().map_err(|_| invalid());
+            let byte = || value.parse::<u8>().map_err(|_| invalid());
+            let key = || parse_key(value).ok_or_else(invalid);
+            let frames = |seconds: f64| (seconds.max(0.0) * self.sample_rate).round() as usize;
+            match name.as_str() {
+                "key" => {
+                    let key = key()?;
+                    zone.keys = key..=key;
+                    zone.root = key;
+                }
+                "lokey" => zone.keys = key()?..=*zone.keys.end(),
+                "hikey" => zone.keys = *zone.keys.start()..=key()?,
+                "pitch_keycenter" => zone.root = key()?,
+                "lovel" => zone.velocities = byte()?..=*zone.velocities.end(),
+                "hivel" => zone.velocities = *zone.velocities.start()..=byte()?,
+                "volume" => zone.gain = db_to_gain(number()? as f32),
+                "amplitude" => amplitude = number()? as f32 / 100.0,
+                "loop_mode" | "loopmode" => match value.as_str() {
+                    "no_loop" => looping = false,
+                    "one_shot" => zone.one_shot = true,
+                    "loop_continuous" | "loop_sustain" => looping = true,
+                    _ => return Err(invalid()),
+                },
+                "loop_start" | "loopstart" => loop_start = number()? as usize,
+                "loop_end" | "loopend" => loop_end = Some(number()? as usize),
+                "seq_length" => zone.round_robin_length = byte()?.max(1),
+                "seq_position" => {
+                    zone.round_robin_position = byte()?.checked_sub(1).ok_or_else(invalid)?;
+                }
+                "lorand" => zone.random.start = number()? as f32,
+                "hirand" => zone.random.end = number()? as f32,
+                "ampeg_attack" => zone.attack = frames(number()?),
+                "ampeg_release" => zone.release = Some(frames(number()?)),
+                "cutoff" => zone.cutoff = Some((number()? / self.sample_rate) as f32),
+                "output" => zone.output = usize::from(byte()?),
+                _ => {}
+            }
+        }
+        zone.gain *= amplitude;
+        if looping {
+            // SFZ loop ends are the last frame of the loop
+            let end = loop_end.map_or_else(|| zone.sample.frames(), |end| end + 1);
+            zone.loop_points = Some((loop_start, end));
+        }
+        self.sampler.add_zone(zone);
+        Ok(())
+    }
+
+    fn sample(&mut self, path: PathBuf) -> Result<Arc<AudioBuffer>, SfzError> {
+        if let Some(sample) = self.samples.get(&path) {
+            return Ok(Arc::clone(sample));
+        }
+        let sample = Arc::new(WavTrack::from_file(&path)?.samples);
+        self.samples.insert(path, Arc::clone(&sample));
+        Ok(sample)
+    }
+}
+
+/// A key as a MIDI number or a note name like `c4` (60), `f#3` or `eb-1`
+fn parse_key(value: &str) -> Option<u8> {
+    if let Ok(key) = value.parse::<u8>() {
+        return (key <= 127).then_some(key);
+    }
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+    let mut pitch = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let octave = if let Some(rest) = rest.strip_prefix('#') {
+        pitch += 1;
+        rest
+    } else if let Some(rest) = rest.strip_prefix('b').filter(|rest| !rest.is_empty()) {
+        pitch -= 1;
+        rest
+    } else {
+        rest
+    };
+    let key = (octave.parse::<i16>().ok()? + 1) * 12 + pitch;
+    u8::try_from(key).ok().filter(|&key| key <= 127)
+}
+
+impl Sampler {
+    /// Loads the SFZ instrument at `path` with its samples, converting times to frames at
+    /// `sample_rate`. Samples are expected to be at that rate already.
+    ///
+    /// # Errors
+    /// [`SfzError::Io`] if the file can't be read, [`SfzError::Opcode`] for values that
+    /// don't parse and [`SfzError::Sample`] for samples that can't be loaded.
+    pub fn from_sfz<P: AsRef<Path>>(path: P, sample_rate: f64) -> Result<Self, SfzError> {
+        let path = path.as_ref();
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let text = std::fs::read_to_string(path).map_err(|source| SfzError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::from_sfz_str(&text, directory, sample_rate)
+    }
+
+    /// Loads an SFZ instrument from its text, sample paths are relative to `directory`
+    ///
+    /// # Errors
+    /// As for [`Sampler::from_sfz`].
+    pub fn from_sfz_str(text: &str, directory: &Path, sample_rate: f64) -> Result<Self, SfzError> {
+        let mut loader = Loader {
+            directory,
+            sample_rate,
+            samples: HashMap::new(),
+            sampler: Self::new(),
+        };
+        let mut scopes = Scopes::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split("//").next().unwrap_or_default();
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let spaced = line.replace('<', " <").replace('>', "> ");
+            let mut opcode: Option<Opcode> = None;
+            for word in spaced.split_whitespace() {
+                if let Some(header) = word.strip_prefix('<').and_then(|w| w.strip_suffix('>')) {
+                    scopes.push(opcode.take());
+                    loader.finish(&mut scopes)?;
+                    scopes.open(header);
+                } else if let Some((name, value)) = word.split_once('=') {
+                    scopes.push(opcode.take());
+                    opcode = Some((name.to_owned(), value.to_owned(), index + 1));
+                } else if let Some((_, value, _)) = opcode.as_mut() {
+                    // sample paths may contain spaces
+                    value.push(' ');
+                    value.push_str(word);
+                }
+            }
+            scopes.push(opcode);
+        }
+        loader.finish(&mut scopes)?;
+        Ok(loader.sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_names() {
+        assert_eq!(parse_key("c4"), Some(60));
+        assert_eq!(parse_key("F#3"), Some(54));
+        assert_eq!(parse_key("eb-1"), Some(3));
+        assert_eq!(parse_key("b"), None);
+        assert_eq!(parse_key("128"), None);
+    }
+
+    #[test]
+    fn test_regions_inherit_group_opcodes() {
+        let directory = std::env::temp_dir().join("freqform-sfz-test");
+        std::fs::create_dir_all(directory.join("samples")).unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer =
+            hound::WavWriter::create(directory.join("samples/hit 1.wav"), spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(0.5f32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let text = "
+            // two velocity layers sharing a sample
+            <control> default_path=samples\\
+            <group> lokey=c3 hikey=c4 pitch_keycenter=60 ampeg_release=0.001
+            <region> sample=hit 1.wav hivel=63 volume=-6.0206
+            <region> sample=hit 1.wav lovel=64 loop_mode=loop_continuous loop_start=10 loop_end=19
+            <region> lokey=100 // no sample, left out
+        ";
+        let sampler = Sampler::from_sfz_str(text, &directory, 48000.0).unwrap();
+        let zones = sampler.zones();
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].keys, 48..=60);
+        assert_eq!(zones[0].velocities, 1..=63);
+        assert!((zones[0].gain - 0.5).abs() < 1e-4);
+        assert_eq!(zones[1].loop_points, Some((10, 20)));
+        assert_eq!(zones[1].release, Some(48));
+        assert!(Arc::ptr_eq(&zones[0].sample, &zones[1].sample));
+
+        let error = Sampler::from_sfz_str(
+            "<region> sample=samples/hit 1.wav lokey=h2",
+            &directory,
+            48000.0,
+        );
+        assert!(matches!(error, Err(SfzError::Opcode { line: 1, .. })));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,611 @@
+//! A/B mix states, for comparing mix revisions live, see [`MixSnapshots`].
+//!
+//! Every channel's and bus's fader, pan, mute, solo, sends and insert bypasses are captured
+//! under a name, then recalled at once or morphed to while playing. Snapshots only hold
+//! mixer settings, not the tracks or their audio, so they still apply after a track is
+//! frozen or replaced under the same id.
+//!
+//! The snapshots themselves stay on the control thread. Captures and morphs are built there
+//! with room for everything they'll hold, the scheduler only fills them in and plays them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::{MAX_ACTIVE_TRACKS, MAX_SENDS},
+    mixer::{AuxSend, Mixer},
+    scheduler::garbage::Garbage,
+};
+
+/// Bytes reserved for each id and bus name filled in on the audio thread, longer ones grow
+/// the buffer
+const NAME_CAPACITY: usize = 32;
+/// Insert bypasses a capture holds per channel, later inserts aren't captured
+const CAPTURED_INSERTS: usize = 32;
+
+/// One channel's settings in a [`MixSnapshot`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelMix {
+    /// Id of the channel's track
+    pub id: String,
+    pub gain: f32,
+    #[serde(default)]
+    pub pan: f32,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub solo: bool,
+    #[serde(default)]
+    pub sends: Vec<AuxSend>,
+    /// Bypass state of each insert, in chain order
+    #[serde(default)]
+    pub bypassed: Vec<bool>,
+}
+
+impl ChannelMix {
+    fn send(&self, bus: &str) -> Option<&AuxSend> {
+        self.sends.iter().find(|send| send.bus == bus)
+    }
+}
+
+/// One bus's settings in a [`MixSnapshot`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusMix {
+    pub id: String,
+    pub gain: f32,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub solo: bool,
+}
+
+/// The mixer's settings at one point, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct MixSnapshot {
+    pub name: String,
+    #[serde(default, rename = "channel")]
+    pub channels: Vec<ChannelMix>,
+    #[serde(default, rename = "bus")]
+    pub busses: Vec<BusMix>,
+}
+
+impl MixSnapshot {
+    /// Stores the settings of every channel and bus in `mixer`
+    #[must_use]
+    pub fn capture(name: &str, mixer: &Mixer) -> Self {
+        Self {
+            name: name.to_owned(),
+            channels: mixer
+                .channels()
+                .map(|channel| ChannelMix {
+                    id: channel.id().to_owned(),
+                    gain: channel.gain(),
+                    pan: channel.pan(),
+                    mute: channel.is_muted(),
+                    solo: channel.is_soloed(),
+                    sends: channel.sends().to_vec(),
+                    bypassed: channel
+                        .inserts()
+                        .iter()
+                        .map(|insert| insert.is_bypassed())
+                        .collect(),
+                })
+                .collect(),
+            busses: mixer
+                .busses()
+                .map(|bus| BusMix {
+                    id: bus.id().to_owned(),
+                    gain: bus.gain(),
+                    mute: bus.is_muted(),
+                    solo: bus.is_soloed(),
+                })
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn channel(&self, id: &str) -> Option<&ChannelMix> {
+        self.channels.iter().find(|channel| channel.id == id)
+    }
+
+    #[must_use]
+    pub fn bus(&self, id: &str) -> Option<&BusMix> {
+        self.busses.iter().find(|bus| bus.id == id)
+    }
+
+    /// Sets `mixer` up like the snapshot. Channels and busses it doesn't know are left as
+    /// they are.
+    pub fn apply(&self, mixer: &mut Mixer) {
+        Self::blend(self, self, 1.0, mixer);
+    }
+
+    /// Sets `mixer` `mix` of the way (0 to 1) from `from` to `to`: gains, pans and send
+    /// levels are interpolated, mutes, solos and bypasses switch halfway. Only channels and
+    /// busses `to` knows are touched; sends `to` doesn't have fade out and are removed at 1.
+    ///
+    /// Allocates the sends `to` adds and frees the ones it removes, the scheduler plays a
+    /// [`MixMorph`] instead.
+    pub fn blend(from: &Self, to: &Self, mix: f32, mixer: &mut Mixer) {
+        let mut names = to
+            .channels
+            .iter()
+            .flat_map(|channel| &channel.sends)
+            .map(|send| send.bus.clone())
+            .collect();
+        Self::blend_with(from, to, mix, mixer, &mut names, drop);
+    }
+
+    /// [`MixSnapshot::blend`] taking the names of added sends from `names` and handing
+    /// removed sends to `retire`. Sends there's no name left for aren't added.
+    fn blend_with(
+        from: &Self,
+        to: &Self,
+        mix: f32,
+        mixer: &mut Mixer,
+        names: &mut Vec<String>,
+        mut retire: impl FnMut(Garbage),
+    ) {
+        let mix = mix.clamp(0.0, 1.0);
+        let lerp = |a: f32, b: f32| (b - a).mul_add(mix, a);
+
+        for target in &to.channels {
+            let Some(channel) = mixer.channel_mut(&target.id) else {
+                continue;
+            };
+            let source = from.channel(&target.id).unwrap_or(target);
+            let switched = if mix < 0.5 { source } else { target };
+
+            channel.set_gain(lerp(source.gain, target.gain));
+            channel.set_pan(lerp(source.pan, target.pan));
+            channel.set_mute(switched.mute);
+            channel.set_solo(switched.solo);
+            for (index, &bypassed) in switched.bypassed.iter().enumerate() {
+                channel.set_insert_bypassed(index, bypassed);
+            }
+
+            for send in &target.sends {
+                let start = source.send(&send.bus).map_or(0.0, |send| send.level);
+                let pre_fader = switched.send(&send.bus).unwrap_or(send).pre_fader;
+                let level = lerp(start, send.level);
+                if let Some(existing) = channel.send_mut(&send.bus) {
+                    existing.level = level;
+                    existing.pre_fader = pre_fader;
+                } else if let Some(mut name) = names.pop() {
+                    copy_name(&mut name, &send.bus);
+                    // a channel without room hands the name back
+                    if let Some(name) = channel.set_send(name, level, pre_fader) {
+                        names.push(name);
+                    }
+                }
+            }
+            if mix >= 1.0 {
+                channel.retain_sends(
+                    |send| target.send(&send.bus).is_some(),
+                    |send| retire(send.into()),
+                );
+            } else {
+                for send in &source.sends {
+                    if target.send(&send.bus).is_none()
+                        && let Some(existing) = channel.send_mut(&send.bus)
+                    {
+                        existing.level = lerp(send.level, 0.0);
+                    }
+                }
+            }
+        }
+
+        for target in &to.busses {
+            let Some(bus) = mixer.bus_mut(&target.id) else {
+                continue;
+            };
+            let source = from.bus(&target.id).unwrap_or(target);
+            let switched = if mix < 0.5 { source } else { target };
+            bus.set_gain(lerp(source.gain, target.gain));
+            bus.set_mute(switched.mute);
+            bus.set_solo(switched.solo);
+        }
+    }
+}
+
+/// Overwrites `into` with `name`, without allocating while it fits
+fn copy_name(into: &mut String, name: &str) {
+    into.clear();
+    into.push_str(name);
+}
+
+/// A capture of the mix, built off the audio thread with spare entries for every channel,
+/// bus and send the scheduler fills in, see [`MixSnapshotChange::capture`]
+#[derive(Debug, Clone)]
+pub struct MixCapture {
+    snapshot: MixSnapshot,
+    channels: Vec<ChannelMix>,
+    busses: Vec<BusMix>,
+    names: Vec<String>,
+}
+
+impl MixCapture {
+    fn new(name: &str) -> Self {
+        Self {
+            snapshot: MixSnapshot {
+                name: name.to_owned(),
+                channels: Vec::with_capacity(MAX_ACTIVE_TRACKS),
+                busses: Vec::with_capacity(MAX_ACTIVE_TRACKS),
+            },
+            channels: (0..MAX_ACTIVE_TRACKS)
+                .map(|_| ChannelMix {
+                    id: String::with_capacity(NAME_CAPACITY),
+                    gain: 1.0,
+                    pan: 0.0,
+                    mute: false,
+                    solo: false,
+                    sends: Vec::with_capacity(MAX_SENDS),
+                    bypassed: Vec::with_capacity(CAPTURED_INSERTS),
+                })
+                .collect(),
+            busses: (0..MAX_ACTIVE_TRACKS)
+                .map(|_| BusMix {
+                    id: String::with_capacity(NAME_CAPACITY),
+                    gain: 1.0,
+                    mute: false,
+                    solo: false,
+                })
+                .collect(),
+            names: (0..MAX_ACTIVE_TRACKS * MAX_SENDS)
+                .map(|_| String::with_capacity(NAME_CAPACITY))
+                .collect(),
+        }
+    }
+
+    /// The captured mix, empty until the scheduler filled it in
+    #[must_use]
+    pub const fn snapshot(&self) -> &MixSnapshot {
+        &self.snapshot
+    }
+
+    #[must_use]
+    pub fn into_snapshot(self) -> MixSnapshot {
+        self.snapshot
+    }
+
+    /// Stores the settings of every channel and bus in `mixer` out of the spare entries.
+    /// Channels and busses past [`MAX_ACTIVE_TRACKS`] aren't captured.
+    pub(crate) fn fill(&mut self, mixer: &Mixer) {
+        for channel in mixer.channels() {
+            let Some(mut entry) = self.channels.pop() else {
+                break;
+            };
+            copy_name(&mut entry.id, channel.id());
+            entry.gain = channel.gain();
+            entry.pan = channel.pan();
+            entry.mute = channel.is_muted();
+            entry.solo = channel.is_soloed();
+            entry.bypassed.extend(
+                channel
+                    .inserts()
+                    .iter()
+                    .take(CAPTURED_INSERTS)
+                    .map(|insert| insert.is_bypassed()),
+            );
+            for send in channel.sends().iter().take(MAX_SENDS) {
+                let Some(mut bus) = self.names.pop() else {
+                    break;
+                };
+                copy_name(&mut bus, &send.bus);
+                entry.sends.push(AuxSend {
+                    bus,
+                    level: send.level,
+                    pre_fader: send.pre_fader,
+                });
+            }
+            self.snapshot.channels.push(entry);
+        }
+
+        for bus in mixer.busses() {
+            let Some(mut entry) = self.busses.pop() else {
+                break;
+            };
+            copy_name(&mut entry.id, bus.id());
+            entry.gain = bus.gain();
+            entry.mute = bus.is_muted();
+            entry.solo = bus.is_soloed();
+            self.snapshot.busses.push(entry);
+        }
+    }
+}
+
+/// A recall or blend, built off the audio thread by [`MixSnapshots`].
+///
+/// A morph sets the mixer every block until it's done, so channel changes made meanwhile
+/// are overridden.
+#[derive(Debug, Clone)]
+pub struct MixMorph {
+    from: MixSnapshot,
+    to: MixSnapshot,
+    /// Spare bus names, for the sends `to` adds and the ones a recall finds on the mixer
+    names: Vec<String>,
+    /// `from` is read off the mixer when the morph starts, for recalls
+    from_mixer: bool,
+    /// Where between `from` and `to` the morph ends
+    mix: f32,
+    elapsed: usize,
+    frames: usize,
+}
+
+impl MixMorph {
+    /// A morph from `from`, or the mix at the time it starts, to `mix` of the way to `to`
+    fn new(from: Option<&MixSnapshot>, to: &MixSnapshot, mix: f32, frames: usize) -> Self {
+        let from_mixer = from.is_none();
+        let mut from = from.unwrap_or(to).clone();
+        let mut names = to.channels.iter().map(|channel| channel.sends.len()).sum();
+        if from_mixer {
+            for channel in &mut from.channels {
+                channel
+                    .sends
+                    .reserve_exact(MAX_SENDS.saturating_sub(channel.sends.len()));
+            }
+            names += from.channels.len() * MAX_SENDS;
+        }
+        Self {
+            from,
+            to: to.clone(),
+            names: (0..names)
+                .map(|_| String::with_capacity(NAME_CAPACITY))
+                .collect(),
+            from_mixer,
+            mix: mix.clamp(0.0, 1.0),
+            elapsed: 0,
+            frames,
+        }
+    }
+
+    /// Reads where a recall starts from off `mixer`, for every channel and bus it goes to
+    pub(crate) fn start(&mut self, mixer: &Mixer) {
+        if !self.from_mixer {
+            return;
+        }
+        for from in &mut self.from.channels {
+            let Some(channel) = mixer.channel(&from.id) else {
+                continue;
+            };
+            from.gain = channel.gain();
+            from.pan = channel.pan();
+            from.mute = channel.is_muted();
+            from.solo = channel.is_soloed();
+            for (bypassed, insert) in from.bypassed.iter_mut().zip(channel.inserts()) {
+                *bypassed = insert.is_bypassed();
+            }
+            for send in &mut from.sends {
+                let current = channel
+                    .sends()
+                    .iter()
+                    .find(|current| current.bus == send.bus);
+                send.level = current.map_or(0.0, |current| current.level);
+                if let Some(current) = current {
+                    send.pre_fader = current.pre_fader;
+                }
+            }
+            // sends the recall fades out
+            for send in channel.sends() {
+                if from.send(&send.bus).is_some() || from.sends.len() == from.sends.capacity() {
+                    continue;
+                }
+                let Some(mut bus) = self.names.pop() else {
+                    break;
+                };
+                copy_name(&mut bus, &send.bus);
+                from.sends.push(AuxSend {
+                    bus,
+                    level: send.level,
+                    pre_fader: send.pre_fader,
+                });
+            }
+        }
+        for from in &mut self.from.busses {
+            if let Some(bus) = mixer.busses().find(|bus| bus.id() == from.id) {
+                from.gain = bus.gain();
+                from.mute = bus.is_muted();
+                from.solo = bus.is_soloed();
+            }
+        }
+    }
+
+    /// Moves the morph on by a block of `frames`, the mixer is set to where the morph is at
+    /// the block's end. Sends it removes go to `retire`. Returns `true` once it's done.
+    pub(crate) fn advance(
+        &mut self,
+        mixer: &mut Mixer,
+        frames: usize,
+        retire: impl FnMut(Garbage),
+    ) -> bool {
+        self.elapsed = (self.elapsed + frames).min(self.frames);
+        let progress = if self.frames == 0 {
+            1.0
+        } else {
+            self.elapsed as f32 / self.frames as f32
+        };
+        MixSnapshot::blend_with(
+            &self.from,
+            &self.to,
+            progress * self.mix,
+            mixer,
+            &mut self.names,
+            retire,
+        );
+        self.elapsed == self.frames
+    }
+}
+
+/// Changes to the scheduler's mix, built with [`MixSnapshotChange::capture`] and
+/// [`MixSnapshots`]
+#[derive(Debug, Clone)]
+pub enum MixSnapshotChange {
+    /// Fills the capture in with the current mix. Answered with
+    /// [`SchedulerEvent::MixCaptured`] so the host can store it.
+    ///
+    /// [`SchedulerEvent::MixCaptured`]: crate::scheduler::event::SchedulerEvent::MixCaptured
+    Capture(Box<MixCapture>),
+    /// Starts a recall or blend, replacing a running one
+    Morph(Box<MixMorph>),
+}
+
+impl MixSnapshotChange {
+    /// Captures the current mix as `name`
+    #[must_use]
+    pub fn capture(name: &str) -> Self {
+        Self::Capture(Box::new(MixCapture::new(name)))
+    }
+}
+
+/// The named mix states of a host, kept on the control thread. Recalls and blends are
+/// built from here and sent to the scheduler as [`MixSnapshotChange::Morph`].
+#[derive(Debug, Clone, Default)]
+pub struct MixSnapshots {
+    snapshots: Vec<MixSnapshot>,
+}
+
+impl MixSnapshots {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&MixSnapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.name == name)
+    }
+
+    /// Stored snapshots, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &MixSnapshot> {
+        self.snapshots.iter()
+    }
+
+    /// Stores a snapshot, e.g. a capture or one saved with the project, replacing one of
+    /// the same name
+    pub fn store(&mut self, snapshot: MixSnapshot) {
+        if let Some(existing) = self
+            .snapshots
+            .iter_mut()
+            .find(|existing| existing.name == snapshot.name)
+        {
+            *existing = snapshot;
+        } else {
+            self.snapshots.push(snapshot);
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<MixSnapshot> {
+        let index = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.name == name)?;
+        Some(self.snapshots.remove(index))
+    }
+
+    /// Switches to snapshot `name`, morphing from the current mix over `frames`, 0 switches
+    /// on the next block. `None` for names that aren't stored.
+    #[must_use]
+    pub fn recall(&self, name: &str, frames: usize) -> Option<MixSnapshotChange> {
+        let to = self.get(name)?;
+        Some(MixSnapshotChange::Morph(Box::new(MixMorph::new(
+            None, to, 1.0, frames,
+        ))))
+    }
+
+    /// Sets the mix `mix` of the way from snapshot `a` to `b`, e.g. from an A/B crossfader.
+    /// Stops a running morph.
+    #[must_use]
+    pub fn blend(&self, a: &str, b: &str, mix: f32) -> Option<MixSnapshotChange> {
+        let (from, to) = (self.get(a)?, self.get(b)?);
+        Some(MixSnapshotChange::Morph(Box::new(MixMorph::new(
+            Some(from),
+            to,
+            mix,
+            0,
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mixer::Bus, track::constant::ConstantTrack};
+
+    /// A `"constant-track"` channel and a reverb bus
+    fn mixer() -> Mixer {
+        let mut mixer = Mixer::new();
+        mixer.add_bus(Bus::new("reverb"));
+        mixer.add_track(Box::new(ConstantTrack::new(0.5, 0.5)));
+        mixer
+    }
+
+    #[test]
+    fn test_morphs_levels_and_switches_mutes_halfway() {
+        let mut mixer = mixer();
+        let a = MixSnapshot::capture("a", &mixer);
+
+        let track = mixer.channel_mut("constant-track").unwrap();
+        track.set_gain(0.5);
+        track.set_pan(1.0);
+        track.set_mute(true);
+        track.set_send("reverb".into(), 0.4, false);
+        mixer.bus_mut("reverb").unwrap().set_gain(0.0);
+        let b = MixSnapshot::capture("b", &mixer);
+        assert_eq!(b.channel("constant-track").unwrap().sends.len(), 1);
+
+        MixSnapshot::blend(&a, &b, 0.25, &mut mixer);
+        let track = mixer.channel("constant-track").unwrap();
+        assert!((track.gain() - 0.875).abs() < 1e-6 && (track.pan() - 0.25).abs() < 1e-6);
+        assert!(!track.is_muted());
+        assert!((track.sends()[0].level - 0.1).abs() < 1e-6);
+        assert!((mixer.busses().next().unwrap().gain() - 0.75).abs() < 1e-6);
+
+        MixSnapshot::blend(&a, &b, 0.5, &mut mixer);
+        assert!(mixer.channel("constant-track").unwrap().is_muted());
+
+        // the send `a` doesn't have is gone once it's fully recalled
+        a.apply(&mut mixer);
+        let track = mixer.channel("constant-track").unwrap();
+        assert_eq!(
+            (track.gain(), track.pan(), track.is_muted()),
+            (1.0, 0.0, false)
+        );
+        assert!(track.sends().is_empty());
+    }
+
+    #[test]
+    fn test_recall_morphs_over_blocks() {
+        let mut mixer = mixer();
+        let mut snapshots = MixSnapshots::new();
+        let MixSnapshotChange::Capture(mut capture) = MixSnapshotChange::capture("a") else {
+            unreachable!("capture builds a capture");
+        };
+        capture.fill(&mixer);
+        assert_eq!(*capture.snapshot(), MixSnapshot::capture("a", &mixer));
+        snapshots.store(capture.into_snapshot());
+
+        let track = mixer.channel_mut("constant-track").unwrap();
+        track.set_gain(0.0);
+        track.set_send("reverb".into(), 0.5, false);
+        let Some(MixSnapshotChange::Morph(mut morph)) = snapshots.recall("a", 100) else {
+            panic!("`a` is stored");
+        };
+        morph.start(&mixer);
+        assert!(!morph.advance(&mut mixer, 25, drop));
+        let track = mixer.channel("constant-track").unwrap();
+        assert!((track.gain() - 0.25).abs() < 1e-6);
+        assert!((track.sends()[0].level - 0.375).abs() < 1e-6);
+
+        // the send `a` doesn't have is retired rather than dropped
+        let mut retired = 0;
+        assert!(morph.advance(&mut mixer, 100, |_| retired += 1));
+        let track = mixer.channel("constant-track").unwrap();
+        assert_eq!(track.gain(), 1.0);
+        assert!(track.sends().is_empty());
+        assert_eq!(retired, 1);
+
+        // unknown names aren't recalled
+        assert!(snapshots.recall("b", 0).is_none());
+        assert!(snapshots.blend("a", "b", 0.5).is_none());
+        assert!(snapshots.remove("a").is_some());
+        assert_eq!(snapshots.iter().count(), 0);
+    }
+}
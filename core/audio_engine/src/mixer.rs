@@ -1,45 +1,1839 @@
-use crate::track::Track;
+use std::{sync::Arc, time::Duration};
 
-/// Holds multiple tracks for mixing.
-/// Produces interleaved stereo output ([f32])
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    automation::{AutomatedParameter, AutomationLane, AutomationStatus},
+    buffer::AudioBuffer,
+    constants::{MAX_ACTIVE_TRACKS, MAX_BLOCK_FRAMES, MAX_SENDS, RECORD_ENABLE_ROOM},
+    dsp::{
+        Processor,
+        math::{PanLaw, db_to_gain, gain_to_db, pan_gains},
+    },
+    error::RoutingError,
+    metadata::Metadata,
+    metering::input::{InputLevel, InputMeter},
+    punch::{MonitorMode, PunchSwitch},
+    record::RecordAutomation,
+    routing::{Connection, Node, RoutingGraph},
+    scene::LaunchFade,
+    scheduler::{
+        command::ChannelChange,
+        cpu::{self, CpuLoad},
+        garbage::Garbage,
+    },
+    stems::StemTap,
+    track::{self, Track},
+};
+
+/// Automatic gain compensation on the master sum, so projects with many tracks don't clip
+/// the master straight away, see [`Mixer::set_headroom`]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Headroom {
+    #[default]
+    Off,
+    /// Turns the sum down `db` for every doubling of the channels playing, 3 dB keeps the
+    /// level of uncorrelated tracks, 6 dB that of identical ones
+    PerDoubling { db: f32 },
+    /// Turns the sum down a fixed `db`, however many channels play
+    Fixed { db: f32 },
+}
+
+impl Headroom {
+    /// Linear gain on the sum of `channels` channels
+    #[must_use]
+    pub fn gain(self, channels: usize) -> f32 {
+        match self {
+            Self::Off => 1.0,
+            Self::PerDoubling { db } => db_to_gain(-db * (channels.max(1) as f32).log2()),
+            Self::Fixed { db } => db_to_gain(-db),
+        }
+    }
+}
+
+/// Gain compensation on the master sum as published in a snapshot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadroomStatus {
+    pub headroom: Headroom,
+    /// Channels summed in the last block, those playing, audible and not muted
+    pub channels: usize,
+    /// Gain on the sum in decibels, 0 or below
+    pub gain_db: f32,
+}
+
+/// A channel's contribution to an aux bus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuxSend {
+    pub bus: String,
+    /// Linear send level
+    pub level: f32,
+    /// Tap the signal before gain, pan and mute instead of after
+    #[serde(default)]
+    pub pre_fader: bool,
+}
+
+/// Inserts, fader and routing of one of a track's aux outputs, so e.g. each pad of a drum
+/// sampler can be processed on its own. Follows the channel's solo but has its own mute.
+pub struct OutputStrip {
+    gain: f32,
+    pan: f32,
+    mute: bool,
+    inserts: Vec<Box<dyn Processor>>,
+    /// Bus the output goes to, `None` for the master
+    output: Option<String>,
+}
+
+impl OutputStrip {
+    fn new() -> Self {
+        Self {
+            gain: 1.0,
+            pan: 0.0,
+            mute: false,
+            inserts: Vec::new(),
+            output: None,
+        }
+    }
+
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    #[must_use]
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    #[must_use]
+    pub fn is_muted(&self) -> bool {
+        self.mute
+    }
+
+    pub fn set_mute(&mut self, mute: bool) {
+        self.mute = mute;
+    }
+
+    /// Appends an insert after the existing ones
+    pub fn add_insert(&mut self, processor: Box<dyn Processor>) {
+        self.inserts.push(processor);
+    }
+
+    pub fn remove_insert(&mut self, index: usize) -> Option<Box<dyn Processor>> {
+        (index < self.inserts.len()).then(|| self.inserts.remove(index))
+    }
+
+    #[must_use]
+    pub fn inserts(&self) -> &[Box<dyn Processor>] {
+        &self.inserts
+    }
+
+    /// Bus the output goes to, `None` for the master, see [`Mixer::route_aux_output`]
+    #[must_use]
+    pub fn output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+}
+
+bitflags! {
+    /// Switches on a [`Channel`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct ChannelFlags: u8 {
+        const MUTE = 1;
+        const SOLO = 1 << 1;
+        /// Stays audible while other channels are soloed
+        const SOLO_SAFE = 1 << 2;
+        /// Armed for recording
+        const ARMED = 1 << 3;
+        /// The source is no longer played, only the inserts' tails are heard
+        const RELEASED = 1 << 4;
+        /// A clip waiting to be launched, see [`Channel::set_parked`]
+        const PARKED = 1 << 5;
+    }
+}
+
+/// One mixer strip: a track followed by inserts, a fader and sends.
+///
+/// Signal flow: source → inserts → pre-fader sends → gain, pan, mute → post-fader sends → mix.
+/// Each of the track's aux outputs gets an [`OutputStrip`] of its own.
+pub struct Channel {
+    /// Cached so lookups and snapshots don't call `Track::id` on every block, shared with
+    /// the events naming the channel
+    id: Arc<str>,
+    source: Box<dyn Track>,
+    gain: f32,
+    /// -1.0 = left, 0.0 = centre, 1.0 = right
+    pan: f32,
+    /// How `pan` splits the channel, also for its aux outputs
+    pan_law: PanLaw,
+    flags: ChannelFlags,
+    inserts: Vec<Box<dyn Processor>>,
+    sends: Vec<AuxSend>,
+    /// Bus the channel's output goes to, `None` for the master
+    output: Option<String>,
+    /// One per aux output of the source
+    aux: Vec<OutputStrip>,
+    /// Channel whose pre-fader signal keys the inserts, see [`Mixer::route_sidechain`]
+    sidechain: Option<String>,
+    /// Position in the arrangement, unordered channels go after all ordered ones
+    order: Option<usize>,
+    /// Folder group the track belongs to
+    group: Option<String>,
+    /// Shared with the snapshots it's published in
+    metadata: Arc<Metadata>,
+    /// Timeline frame the track started playing at
+    start_frame: u64,
+    /// Frames the track is moved by at render time, negative plays it earlier
+    offset: i64,
+    /// Hands the track between launched clips and the arrangement
+    launch_fade: LaunchFade,
+    /// At most one lane per parameter
+    automation: Vec<AutomationLane>,
+    input: InputMeter,
+    /// When the input is heard while armed
+    monitor_mode: MonitorMode,
+    /// Last block of input seen while armed, heard as `monitor_mode` says
+    monitor_input: AudioBuffer,
+    /// Scratch for mono sources, up-mixed into the stereo strip
+    mono: AudioBuffer,
+    punch: PunchSwitch,
+    /// Input recorded in the last block while armed, see [`Channel::take`]
+    take: AudioBuffer,
+    /// Timeline frame `take` starts at, `None` if nothing was recorded
+    take_frame: Option<u64>,
+    load: CpuLoad,
+}
+
+impl Channel {
+    /// A unity-gain channel named after `source`'s id
+    #[must_use]
+    pub fn new(source: Box<dyn Track>) -> Self {
+        Self {
+            id: source.id().into(),
+            aux: (0..source.aux_outputs())
+                .map(|_| OutputStrip::new())
+                .collect(),
+            source,
+            gain: 1.0,
+            pan: 0.0,
+            pan_law: PanLaw::default(),
+            flags: ChannelFlags::empty(),
+            inserts: Vec::new(),
+            sends: Vec::with_capacity(MAX_SENDS),
+            output: None,
+            sidechain: None,
+            order: None,
+            group: None,
+            metadata: Arc::default(),
+            start_frame: 0,
+            offset: 0,
+            launch_fade: LaunchFade::new(),
+            automation: Vec::with_capacity(AutomatedParameter::ALL.len()),
+            input: InputMeter::new(),
+            monitor_mode: MonitorMode::default(),
+            monitor_input: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            mono: AudioBuffer::mono(MAX_BLOCK_FRAMES),
+            punch: PunchSwitch::new(),
+            take: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            take_frame: None,
+            load: CpuLoad::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The id as shared with events, cloning it doesn't allocate
+    pub(crate) const fn shared_id(&self) -> &Arc<str> {
+        &self.id
+    }
+
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    #[must_use]
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    #[must_use]
+    pub const fn pan_law(&self) -> PanLaw {
+        self.pan_law
+    }
+
+    /// How the pan splits the channel and its aux outputs between left and right
+    pub const fn set_pan_law(&mut self, law: PanLaw) {
+        self.pan_law = law;
+    }
+
+    #[must_use]
+    pub fn is_muted(&self) -> bool {
+        self.flags.contains(ChannelFlags::MUTE)
+    }
+
+    pub fn set_mute(&mut self, mute: bool) {
+        self.flags.set(ChannelFlags::MUTE, mute);
+    }
+
+    #[must_use]
+    pub fn is_soloed(&self) -> bool {
+        self.flags.contains(ChannelFlags::SOLO)
+    }
+
+    /// Solos the channel alongside any others, see [`Mixer::set_solo`] for exclusive solo
+    pub fn set_solo(&mut self, solo: bool) {
+        self.flags.set(ChannelFlags::SOLO, solo);
+    }
+
+    #[must_use]
+    pub fn is_solo_safe(&self) -> bool {
+        self.flags.contains(ChannelFlags::SOLO_SAFE)
+    }
+
+    pub fn set_solo_safe(&mut self, solo_safe: bool) {
+        self.flags.set(ChannelFlags::SOLO_SAFE, solo_safe);
+    }
+
+    /// Appends an insert after the existing ones
+    pub fn add_insert(&mut self, processor: Box<dyn Processor>) {
+        self.inserts.push(processor);
+    }
+
+    pub fn remove_insert(&mut self, index: usize) -> Option<Box<dyn Processor>> {
+        (index < self.inserts.len()).then(|| self.inserts.remove(index))
+    }
+
+    #[must_use]
+    pub fn inserts(&self) -> &[Box<dyn Processor>] {
+        &self.inserts
+    }
+
+    /// Bypasses the insert at `index`, `false` if there is none or it can't be bypassed
+    pub fn set_insert_bypassed(&mut self, index: usize, bypassed: bool) -> bool {
+        let Some(insert) = self.inserts.get_mut(index) else {
+            return false;
+        };
+        insert.set_bypassed(bypassed);
+        insert.is_bypassed() == bypassed
+    }
+
+    /// Replaces every insert, returning the old ones so they can be dropped off the audio thread
+    pub fn replace_inserts(&mut self, inserts: Vec<Box<dyn Processor>>) -> Vec<Box<dyn Processor>> {
+        std::mem::replace(&mut self.inserts, inserts)
+    }
+
+    /// Adds a send to `bus`, or updates the existing one. Room for [`MAX_SENDS`] sends is
+    /// kept so adding one doesn't allocate, past that no more are added.
+    ///
+    /// Returns `bus` when it isn't kept, so it can be dropped off the audio thread.
+    pub fn set_send(&mut self, bus: String, level: f32, pre_fader: bool) -> Option<String> {
+        if let Some(send) = self.sends.iter_mut().find(|send| send.bus == bus) {
+            send.level = level;
+            send.pre_fader = pre_fader;
+            return Some(bus);
+        }
+        if self.sends.len() == self.sends.capacity() {
+            return Some(bus);
+        }
+        self.sends.push(AuxSend {
+            bus,
+            level,
+            pre_fader,
+        });
+        None
+    }
+
+    /// Keeps only the sends `keep` returns `true` for, the others are handed to `removed`
+    pub fn retain_sends(
+        &mut self,
+        mut keep: impl FnMut(&AuxSend) -> bool,
+        removed: impl FnMut(AuxSend),
+    ) {
+        self.sends
+            .extract_if(.., |send| !keep(send))
+            .for_each(removed);
+    }
+
+    pub fn send_mut(&mut self, bus: &str) -> Option<&mut AuxSend> {
+        self.sends.iter_mut().find(|send| send.bus == bus)
+    }
+
+    pub fn remove_send(&mut self, bus: &str) -> Option<AuxSend> {
+        let index = self.sends.iter().position(|send| send.bus == bus)?;
+        Some(self.sends.remove(index))
+    }
+
+    #[must_use]
+    pub fn sends(&self) -> &[AuxSend] {
+        &self.sends
+    }
+
+    /// Replaces every send, returning the old ones
+    pub fn replace_sends(&mut self, mut sends: Vec<AuxSend>) -> Vec<AuxSend> {
+        sends.reserve(MAX_SENDS.saturating_sub(sends.len()));
+        std::mem::replace(&mut self.sends, sends)
+    }
+
+    /// Bus the channel's output goes to, `None` for the master
+    #[must_use]
+    pub fn output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+
+    /// Channel keying the inserts, see [`Mixer::route_sidechain`]
+    #[must_use]
+    pub fn sidechain(&self) -> Option<&str> {
+        self.sidechain.as_deref()
+    }
+
+    /// Strips of the track's aux outputs, in order
+    #[must_use]
+    pub fn aux_outputs(&self) -> &[OutputStrip] {
+        &self.aux
+    }
+
+    pub fn aux_output_mut(&mut self, index: usize) -> Option<&mut OutputStrip> {
+        self.aux.get_mut(index)
+    }
+
+    #[must_use]
+    pub fn order(&self) -> Option<usize> {
+        self.order
+    }
+
+    /// Places the channel in the arrangement, applied when it's added to a [`Mixer`]
+    pub fn set_order(&mut self, order: usize) {
+        self.order = Some(order);
+    }
+
+    #[must_use]
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group;
+    }
+
+    /// The track's color, notes and tags
+    #[must_use]
+    pub fn metadata(&self) -> &Arc<Metadata> {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Arc<Metadata>) {
+        self.metadata = metadata;
+    }
+
+    /// Timeline frame the track started playing at
+    #[must_use]
+    pub fn start_frame(&self) -> u64 {
+        self.start_frame
+    }
+
+    pub(crate) fn set_start_frame(&mut self, start_frame: u64) {
+        self.start_frame = start_frame;
+    }
+
+    /// Moves the channel's start and automation through a time insertion or removal, see
+    /// [`AutomationLane::shift_points`]
+    pub(crate) fn ripple(&mut self, shift: impl Fn(u64) -> u64) {
+        self.start_frame = shift(self.start_frame);
+        for lane in &mut self.automation {
+            lane.shift_points(&shift);
+        }
+    }
+
+    #[must_use]
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Nudges the whole track `offset` frames later, or earlier when negative, e.g. to line
+    /// up material recorded elsewhere. Material moved before the timeline's start is cut.
+    pub fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+
+    /// Timeline frame the track's first frame plays at, after the offset
+    #[must_use]
+    pub fn play_frame(&self) -> i64 {
+        self.start_frame as i64 + self.offset
+    }
+
+    /// Frame `frame` of the track after the fader, read out of order for scrubbing.
+    /// Inserts are skipped; muted channels and tracks that can only stream are silent.
+    #[must_use]
+    pub fn frame_at(&self, frame: usize) -> (f32, f32) {
+        if self
+            .flags
+            .intersects(ChannelFlags::MUTE | ChannelFlags::PARKED)
+        {
+            return (0.0, 0.0);
+        }
+        let (left, right) = self.source.frame_at(frame).unwrap_or_default();
+        let (left_pan, right_pan) = pan_gains(self.pan_law, self.pan);
+        let gain = self.gain * self.launch_fade.gain();
+        (left * gain * left_pan, right * gain * right_pan)
+    }
+
+    /// Frames of delay added by the inserts
+    #[must_use]
+    pub fn latency(&self) -> usize {
+        self.inserts.iter().map(|insert| insert.latency()).sum()
+    }
+
+    #[must_use]
+    pub fn is_armed(&self) -> bool {
+        self.flags.contains(ChannelFlags::ARMED)
+    }
+
+    /// Arming starts input metering, disarming resets the meter
+    pub fn set_armed(&mut self, armed: bool) {
+        self.flags.set(ChannelFlags::ARMED, armed);
+        if !armed {
+            self.input.reset();
+        }
+    }
+
+    /// Input level while armed, `None` otherwise
+    #[must_use]
+    pub fn input_level(&self) -> Option<InputLevel> {
+        self.flags
+            .contains(ChannelFlags::ARMED)
+            .then(|| self.input.level())
+    }
+
+    /// Meters frames `start..start + len` of the track's input and keeps them for punch
+    /// monitoring, ignored unless armed. Peaks fall back at `release_db_per_second`.
+    pub fn meter_input(
+        &mut self,
+        input: &AudioBuffer,
+        start: usize,
+        len: usize,
+        sample_rate: f64,
+        release_db_per_second: f32,
+    ) {
+        if self.flags.contains(ChannelFlags::ARMED) {
+            self.input
+                .process(input, start, len, sample_rate, release_db_per_second);
+            let len = len.min(MAX_BLOCK_FRAMES);
+            self.monitor_input.set_frames(len);
+            self.monitor_input.copy_from(0, input, start, len);
+        }
+    }
+
+    /// Switches what's heard between playback and input at the punch points
+    #[must_use]
+    pub fn punch(&self) -> &PunchSwitch {
+        &self.punch
+    }
+
+    /// Input recorded in the last block while armed and record enabled, see
+    /// [`Mixer::set_record_automation`]: the timeline frame it starts at and its frames.
+    /// `None` if nothing was recorded.
+    #[must_use]
+    pub fn take(&self) -> Option<(u64, &AudioBuffer)> {
+        self.take_frame.map(|frame| (frame, &self.take))
+    }
+
+    /// Captures what `automation` records of the input in a block at timeline `frame`
+    fn capture(&mut self, automation: &RecordAutomation, frame: u64, frames: usize) {
+        self.take_frame = if self.flags.contains(ChannelFlags::ARMED) {
+            automation.capture(&self.monitor_input, frame, frames, &mut self.take)
+        } else {
+            None
+        };
+    }
+
+    #[must_use]
+    pub fn monitor_mode(&self) -> MonitorMode {
+        self.monitor_mode
+    }
+
+    /// Chooses when the input is heard instead of playback while the track is armed
+    pub fn set_monitor_mode(&mut self, mode: MonitorMode) {
+        self.monitor_mode = mode;
+    }
+
+    /// Armed and hearing its input with the transport `rolling` or not, outside the punch
+    /// range
+    #[must_use]
+    pub fn hears_input(&self, rolling: bool) -> bool {
+        self.flags.contains(ChannelFlags::ARMED) && self.monitor_mode.hears_input(rolling, false)
+    }
+
+    /// Smoothed share of the buffer deadline this channel took, in percent
+    #[must_use]
+    pub fn cpu_load(&self) -> f32 {
+        self.load.percent()
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.source.is_finished()
+    }
+
+    /// Gives the track back
+    #[must_use]
+    pub fn into_source(self) -> Box<dyn Track> {
+        self.source
+    }
+
+    /// Applies a change addressed at this channel; moves and exclusive solo are handled by
+    /// [`Mixer::apply`]. What the change replaces or leaves over is handed to `retire`, so
+    /// the audio thread doesn't free it.
+    pub fn apply(&mut self, change: ChannelChange, mut retire: impl FnMut(Garbage)) {
+        match change {
+            ChannelChange::SetGain(gain) => {
+                self.set_gain(gain);
+                self.touch(AutomatedParameter::Gain, gain);
+            }
+            ChannelChange::SetPan(pan) => {
+                self.set_pan(pan);
+                self.touch(AutomatedParameter::Pan, self.pan);
+            }
+            ChannelChange::SetMute(mute) => self.set_mute(mute),
+            ChannelChange::SetSolo(solo) => self.set_solo(solo),
+            ChannelChange::SetSoloSafe(solo_safe) => self.set_solo_safe(solo_safe),
+            ChannelChange::SetSend {
+                bus,
+                level,
+                pre_fader,
+            } => {
+                if let Some(bus) = self.set_send(bus, level, pre_fader) {
+                    retire(bus.into());
+                }
+            }
+            ChannelChange::RemoveSend { bus } => {
+                if let Some(send) = self.remove_send(&bus) {
+                    retire(send.into());
+                }
+                retire(bus.into());
+            }
+            ChannelChange::SetGroup(group) => {
+                if let Some(group) = std::mem::replace(&mut self.group, group) {
+                    retire(group.into());
+                }
+            }
+            ChannelChange::SetMetadata(metadata) => {
+                retire(std::mem::replace(&mut self.metadata, metadata).into());
+            }
+            ChannelChange::SetArmed(armed) => self.set_armed(armed),
+            ChannelChange::SetMonitorMode(mode) => self.set_monitor_mode(mode),
+            ChannelChange::SetOffset(offset) => self.set_offset(offset),
+            ChannelChange::ClearClip => self.input.clear_clip(),
+            ChannelChange::SetInsertBypass { index, bypassed } => {
+                self.set_insert_bypassed(index, bypassed);
+            }
+            ChannelChange::SetAutomationMode { parameter, mode } => {
+                self.automation_mut(parameter).set_mode(mode);
+            }
+            ChannelChange::SetAutomation { parameter, points } => {
+                retire(self.automation_mut(parameter).set_points(points).into());
+            }
+            ChannelChange::ReserveAutomation { parameter, points } => {
+                retire(self.automation_mut(parameter).reserve_points(points).into());
+            }
+            ChannelChange::MoveTo(_) => {}
+        }
+    }
+
+    /// The channel's track, or one it wraps at `path` (see [`track::find_mut`])
+    pub fn track_mut(&mut self, path: &str) -> Option<&mut dyn Track> {
+        track::find_mut(self.source.as_mut(), path)
+    }
+
+    /// Stops playing the source while the inserts keep running on silence, so reverb and
+    /// delay tails ring out
+    pub fn release(&mut self) {
+        self.flags.insert(ChannelFlags::RELEASED);
+    }
+
+    #[must_use]
+    pub fn is_parked(&self) -> bool {
+        self.flags.contains(ChannelFlags::PARKED)
+    }
+
+    /// Parks the channel as a clip scenes launch, see [`crate::scene`]: it joins the mixer
+    /// as soon as it's scheduled and stays silent until launched. Inserts keep running, so
+    /// tails ring out when it's parked again.
+    pub fn set_parked(&mut self, parked: bool) {
+        self.flags.set(ChannelFlags::PARKED, parked);
+    }
+
+    /// Fade applied as launched clips take the track over or give it back, see
+    /// [`crate::scene`]
+    #[must_use]
+    pub fn launch_fade(&self) -> &LaunchFade {
+        &self.launch_fade
+    }
+
+    pub fn launch_fade_mut(&mut self) -> &mut LaunchFade {
+        &mut self.launch_fade
+    }
+
+    /// Automation lanes of the channel's parameters, see [`crate::automation`]
+    #[must_use]
+    pub fn automation(&self) -> &[AutomationLane] {
+        &self.automation
+    }
+
+    /// The lane automating `parameter`, added (off) if there's none yet. Room for a lane per
+    /// parameter is kept, so adding one doesn't allocate.
+    pub fn automation_mut(&mut self, parameter: AutomatedParameter) -> &mut AutomationLane {
+        let index = self
+            .automation
+            .iter()
+            .position(|lane| lane.parameter() == parameter)
+            .unwrap_or_else(|| {
+                self.automation.push(AutomationLane::new(parameter));
+                self.automation.len() - 1
+            });
+        &mut self.automation[index]
+    }
+
+    /// State of every lane, with where its parameter is now
+    pub fn automation_status(&self) -> impl Iterator<Item = AutomationStatus> + '_ {
+        self.automation.iter().map(|lane| AutomationStatus {
+            parameter: lane.parameter(),
+            mode: lane.mode(),
+            value: self.parameter(lane.parameter()),
+            pending: lane.pending(),
+        })
+    }
+
+    fn parameter(&self, parameter: AutomatedParameter) -> f32 {
+        match parameter {
+            AutomatedParameter::Gain => self.gain,
+            AutomatedParameter::Pan => self.pan,
+        }
+    }
+
+    /// A manual move of `parameter` to `value`, recorded by writing lanes
+    fn touch(&mut self, parameter: AutomatedParameter, value: f32) {
+        if let Some(lane) = self
+            .automation
+            .iter_mut()
+            .find(|lane| lane.parameter() == parameter)
+        {
+            lane.touch(value);
+        }
+    }
+
+    /// Runs the automation over a rolling block at timeline `frame`
+    fn automate(&mut self, frame: u64, frames: usize) {
+        for index in 0..self.automation.len() {
+            let parameter = self.automation[index].parameter();
+            let current = self.parameter(parameter);
+            let Some(value) = self.automation[index].automate(frame, frames, current) else {
+                continue;
+            };
+            match parameter {
+                AutomatedParameter::Gain => self.set_gain(value),
+                AutomatedParameter::Pan => self.set_pan(value),
+            }
+        }
+    }
+
+    /// Restarts the track and clears insert state
+    pub fn reset(&mut self) {
+        self.source.reset();
+        for insert in &mut self.inserts {
+            insert.reset();
+        }
+        for insert in self.aux.iter_mut().flat_map(|strip| &mut strip.inserts) {
+            insert.reset();
+        }
+    }
+
+    /// Renders the source through the inserts, pre-fader. With a timeline `frame` the source
+    /// plays from its matching position, otherwise it streams on. Unless the transport is
+    /// `rolling` only the monitored input is heard. Inserts get the sidechain `key`, if any.
+    fn render(
+        &mut self,
+        buffer: &mut AudioBuffer,
+        frame: Option<u64>,
+        rolling: bool,
+        key: Option<&AudioBuffer>,
+    ) {
+        if !self
+            .flags
+            .intersects(ChannelFlags::RELEASED | ChannelFlags::PARKED)
+            && rolling
+        {
+            if self.source.channels() == 1 {
+                let frames = buffer.frames();
+                let mut mono = std::mem::take(&mut self.mono);
+                mono.set_frames(frames);
+                self.render_source(&mut mono, frame);
+                buffer.copy_upmixed_from(0, &mono, 0, frames);
+                self.mono = mono;
+            } else {
+                self.render_source(buffer, frame);
+            }
+        }
+        if let Some(frame) = frame {
+            let mode = self
+                .flags
+                .contains(ChannelFlags::ARMED)
+                .then_some(self.monitor_mode);
+            self.punch
+                .process(buffer, &self.monitor_input, frame, mode, rolling);
+        }
+        let frames = buffer.frames();
+        for insert in &mut self.inserts {
+            match key {
+                Some(key) => insert.process_keyed(buffer, key, 0, frames),
+                None => insert.process(buffer, 0, frames),
+            }
+        }
+    }
+
+    /// Renders the source from timeline `frame`, or streams it on without one
+    fn render_source(&mut self, buffer: &mut AudioBuffer, frame: Option<u64>) {
+        match frame {
+            Some(frame) => self.render_from(buffer, frame),
+            None => self.source.fill_next_samples(buffer),
+        }
+    }
+
+    /// Renders the source for a block starting at timeline `frame`, silent before the
+    /// channel's start, e.g. after seeking back past it
+    fn render_from(&mut self, buffer: &mut AudioBuffer, frame: u64) {
+        let frames = buffer.frames();
+        match self.play_frame() - frame as i64 {
+            ..=0 => self
+                .source
+                .fill_from((frame as i64 - self.play_frame()) as usize, buffer),
+            lead if (lead as usize) < frames => {
+                // the channel starts inside this block: render its first frames, then move
+                // them into place. Only as many as fit, so generators don't run ahead.
+                let lead = lead as usize;
+                buffer.set_frames(frames - lead);
+                self.source.fill_from(0, buffer);
+                buffer.set_frames(frames);
+                for channel in 0..buffer.channels() {
+                    let samples = buffer.channel_mut(channel);
+                    samples.copy_within(..frames - lead, lead);
+                    samples[..lead].fill(0.0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Copies aux output `index` of the block just rendered into `buffer`, through the
+    /// strip's inserts, pre-fader
+    fn render_aux(&mut self, index: usize, buffer: &mut AudioBuffer) {
+        let frames = buffer.frames();
+        if let Some(signal) = self.source.aux_output(index).filter(|_| {
+            !self
+                .flags
+                .intersects(ChannelFlags::RELEASED | ChannelFlags::PARKED)
+        }) {
+            buffer.copy_from(0, signal, 0, frames.min(signal.frames()));
+        }
+        for insert in &mut self.aux[index].inserts {
+            insert.process(buffer, 0, frames);
+        }
+    }
+
+    /// Applies gain, pan and mute
+    fn apply_fader(&self, buffer: &mut AudioBuffer) {
+        fader(
+            buffer,
+            self.gain,
+            pan_gains(self.pan_law, self.pan),
+            self.flags.contains(ChannelFlags::MUTE),
+        );
+    }
+}
+
+/// Applies `gain`, the `(left, right)` pan gains and `mute` to a stereo buffer
+fn fader(buffer: &mut AudioBuffer, gain: f32, (left_pan, right_pan): (f32, f32), mute: bool) {
+    if mute {
+        buffer.clear();
+        return;
+    }
+    let (left_gain, right_gain) = (gain * left_pan, gain * right_pan);
+    let (left, right) = buffer.stereo_mut();
+    for sample in left {
+        *sample *= left_gain;
+    }
+    for sample in right {
+        *sample *= right_gain;
+    }
+}
+
+bitflags! {
+    /// Switches on a [`Bus`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct BusFlags: u8 {
+        const MUTE = 1;
+        const SOLO = 1 << 1;
+        /// Stays audible while other channels or busses are soloed, e.g. an effect return
+        const SOLO_SAFE = 1 << 2;
+        /// Set while rendering when a soloed source feeds this bus
+        const SOLO_PATH = 1 << 3;
+        /// A cue mix, kept off the master, see [`Bus::set_cue`]
+        const CUE = 1 << 4;
+    }
+}
+
+/// A bus fed by channel outputs, sends and other busses, with its own inserts and level
+pub struct Bus {
+    id: String,
+    gain: f32,
+    flags: BusFlags,
+    /// Bus this one feeds, `None` for the master
+    output: Option<String>,
+    inserts: Vec<Box<dyn Processor>>,
+    /// Sum of the sends for the current block, preallocated to `MAX_BLOCK_FRAMES`
+    buffer: AudioBuffer,
+}
+
+impl Bus {
+    #[must_use]
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            gain: 1.0,
+            flags: BusFlags::empty(),
+            output: None,
+            inserts: Vec::new(),
+            buffer: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Makes this a cue (headphone) bus: the performers' own mix, fed by sends at their own
+    /// levels. It's never summed into the master or another bus, and control room solos
+    /// don't change it. It's heard through the stem tap, see
+    /// [`Scheduler::set_cue_output`](crate::scheduler::Scheduler::set_cue_output).
+    pub fn set_cue(&mut self, cue: bool) {
+        self.flags.set(BusFlags::CUE, cue);
+    }
+
+    #[must_use]
+    pub fn is_cue(&self) -> bool {
+        self.flags.contains(BusFlags::CUE)
+    }
+
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    #[must_use]
+    pub fn is_muted(&self) -> bool {
+        self.flags.contains(BusFlags::MUTE)
+    }
+
+    pub fn set_mute(&mut self, mute: bool) {
+        self.flags.set(BusFlags::MUTE, mute);
+    }
+
+    #[must_use]
+    pub fn is_soloed(&self) -> bool {
+        self.flags.contains(BusFlags::SOLO)
+    }
+
+    pub fn set_solo(&mut self, solo: bool) {
+        self.flags.set(BusFlags::SOLO, solo);
+    }
+
+    pub fn set_solo_safe(&mut self, solo_safe: bool) {
+        self.flags.set(BusFlags::SOLO_SAFE, solo_safe);
+    }
+
+    pub fn add_insert(&mut self, processor: Box<dyn Processor>) {
+        self.inserts.push(processor);
+    }
+
+    /// Bus this one feeds, `None` for the master
+    #[must_use]
+    pub fn output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+}
+
+/// Sums channels, and the busses they are routed to, into a stereo output.
+///
+/// Channels render in order, which is also the order they are listed in; busses render in
+/// routing order, after everything feeding them.
+///
+/// Solo works in place: while anything is soloed, only soloed channels and busses, the
+/// busses carrying them to the master, the sources feeding a soloed bus and solo-safe
+/// channels and busses stay audible.
 pub struct Mixer {
-    tracks: Vec<Box<dyn Track>>,
+    #[expect(
+        clippy::vec_box,
+        reason = "removed channels are handed to the garbage collector without moving them"
+    )]
+    channels: Vec<Box<Channel>>,
+    busses: Vec<Bus>,
+    /// Indices into `busses` in processing order, see [`Mixer::routing`]
+    bus_order: Vec<usize>,
+    /// Soloing a channel or bus unsolos all others
+    exclusive_solo: bool,
+    /// Per-channel scratch buffer, preallocated to `MAX_BLOCK_FRAMES`
+    scratch: AudioBuffer,
+    /// Pre-fader signal of every channel keying another, by channel id, for the block
+    keys: Vec<(String, AudioBuffer)>,
+    /// Punch range every channel, also the ones added later, switches to its input in
+    punch: PunchSwitch,
+    /// Where armed channels record
+    record: RecordAutomation,
+    /// The transport is moving, otherwise channels only play their monitored input
+    rolling: bool,
+    /// Post-fader signals of channels and busses streamed to other apps
+    stems: Option<StemTap>,
+    /// Gain of the talkback input in the cue busses, `None` while not talking
+    talkback: Option<f32>,
+    /// Last block of the talkback input, preallocated to `MAX_BLOCK_FRAMES`
+    talkback_input: AudioBuffer,
+    /// Gain compensation on the master sum
+    headroom: Headroom,
+    /// Channels summed in the last block and the compensation gain reached at its end
+    headroom_gain: (usize, f32),
 }
 
-impl Mixer {
-    pub fn new() -> Self {
-        Self { tracks: Vec::new() }
+impl Mixer {
+    pub fn new() -> Self {
+        Self {
+            channels: Vec::with_capacity(MAX_ACTIVE_TRACKS),
+            busses: Vec::new(),
+            bus_order: Vec::new(),
+            exclusive_solo: false,
+            scratch: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            keys: Vec::new(),
+            punch: PunchSwitch::new(),
+            record: RecordAutomation::with_capacity(RECORD_ENABLE_ROOM),
+            rolling: true,
+            stems: None,
+            talkback: None,
+            talkback_input: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            headroom: Headroom::Off,
+            headroom_gain: (0, 1.0),
+        }
+    }
+
+    /// Adds `track` on a new unity-gain channel at the end
+    pub fn add_track(&mut self, track: Box<dyn Track>) {
+        self.add_channel(Channel::new(track));
+    }
+
+    /// Inserts `channel` at its arrangement position, see [`Channel::set_order`].
+    /// Only allocates once more than [`MAX_ACTIVE_TRACKS`] channels exist, or to box a
+    /// channel that isn't boxed yet.
+    pub fn add_channel(&mut self, channel: impl Into<Box<Channel>>) {
+        let mut channel = channel.into();
+        channel.punch = self.punch.clone();
+        let index = channel.order.map_or(self.channels.len(), |order| {
+            self.channels
+                .partition_point(|other| other.order.is_some_and(|other| other <= order))
+        });
+        self.channels.insert(index, channel);
+    }
+
+    /// Streams the post-fader signal of the channels and busses `tap` was created for, see
+    /// [`stem_channel`](crate::stems::stem_channel). `None` stops streaming.
+    pub fn set_stem_tap(&mut self, tap: Option<StemTap>) {
+        self.stems = tap;
+    }
+
+    /// Mixes the talkback input into every cue bus at `gain`, `None` stops talking
+    pub const fn set_talkback(&mut self, gain: Option<f32>) {
+        self.talkback = gain;
+    }
+
+    /// Keeps frames `start..start + len` of the talkback mic, mono or stereo, for the next
+    /// block
+    pub fn talkback_input(&mut self, input: &AudioBuffer, start: usize, len: usize) {
+        let len = len.min(MAX_BLOCK_FRAMES);
+        self.talkback_input.set_frames(len);
+        self.talkback_input.copy_upmixed_from(0, input, start, len);
+    }
+
+    /// Streams only the talkback to the cue busses, for blocks where nothing is mixed,
+    /// e.g. while the transport is stopped
+    pub(crate) fn render_talkback(&mut self, frames: usize) {
+        let Some(stems) = self.stems.as_mut() else {
+            return;
+        };
+        for bus in self.busses.iter_mut().filter(|bus| bus.is_cue()) {
+            bus.buffer.set_frames(frames);
+            bus.buffer.clear();
+            if let Some(gain) = self.talkback {
+                bus.buffer.add_scaled_from(&self.talkback_input, 0, gain);
+            }
+            let gain = if bus.is_muted() { 0.0 } else { bus.gain };
+            stems.send(&bus.id, &bus.buffer, 0, frames, gain);
+        }
+    }
+
+    pub(crate) const fn stem_tap_mut(&mut self) -> Option<&mut StemTap> {
+        self.stems.as_mut()
+    }
+
+    /// Armed channels hear their input instead of their playback in timeline frames
+    /// `start..end`, crossfading over `ramp` frames at each end. `None` plays back only.
+    pub fn set_punch(&mut self, range: Option<(u64, u64)>, ramp: usize) {
+        self.punch.set_range(range, ramp);
+        for channel in &mut self.channels {
+            channel.punch.set_range(range, ramp);
+        }
+    }
+
+    /// Where armed channels record, see [`Channel::take`]
+    #[must_use]
+    pub fn record_automation(&self) -> &RecordAutomation {
+        &self.record
     }
 
-    pub fn add_track(&mut self, track: Box<dyn Track>) {
-        self.tracks.push(track);
+    /// Armed channels record the timeline frames `automation` enables, to the frame.
+    /// Returns the previous automation, so it can be dropped off the audio thread.
+    pub fn set_record_automation(&mut self, mut automation: RecordAutomation) -> RecordAutomation {
+        automation.reserve(RECORD_ENABLE_ROOM);
+        std::mem::replace(&mut self.record, automation)
+    }
+
+    /// Switches recording on or off at timeline `frame`, see [`RecordAutomation::set`]. Room
+    /// for [`RECORD_ENABLE_ROOM`] changes is kept so this doesn't allocate, past that it
+    /// returns `false` and the change is dropped.
+    pub fn set_record_enable(&mut self, frame: u64, enabled: bool) -> bool {
+        self.record.set_within_capacity(frame, enabled)
+    }
+
+    /// Removes every record enable change, keeping their room
+    pub fn clear_record_enable(&mut self) {
+        self.record.clear();
+    }
+
+    /// With the transport standing still, channels play only their monitored input, see
+    /// [`MonitorMode`]
+    pub(crate) fn set_rolling(&mut self, rolling: bool) {
+        self.rolling = rolling;
+    }
+
+    /// The transport stopped: latched automation goes back to playing back
+    pub(crate) fn stop_automation(&mut self) {
+        for channel in &mut self.channels {
+            channel.automation.iter_mut().for_each(AutomationLane::stop);
+        }
+    }
+
+    /// Removes the first channel with `id`
+    pub fn remove_channel(&mut self, id: &str) -> Option<Box<Channel>> {
+        let index = self.position(id)?;
+        Some(self.channels.remove(index))
+    }
+
+    /// Moves the channel with `id` to `index` (clamped to the end), `false` if there's no such channel
+    pub fn move_channel(&mut self, id: &str, index: usize) -> bool {
+        let Some(from) = self.position(id) else {
+            return false;
+        };
+        let to = index.min(self.channels.len() - 1);
+        if from < to {
+            self.channels[from..=to].rotate_left(1);
+        } else {
+            self.channels[to..=from].rotate_right(1);
+        }
+        // the new positions become the arrangement order for channels added later
+        for (order, channel) in self.channels.iter_mut().enumerate() {
+            channel.order = Some(order);
+        }
+        true
+    }
+
+    #[must_use]
+    pub fn channel(&self, id: &str) -> Option<&Channel> {
+        self.channels().find(|channel| *channel.id == *id)
+    }
+
+    pub fn channel_mut(&mut self, id: &str) -> Option<&mut Channel> {
+        self.channels_mut().find(|channel| *channel.id == *id)
+    }
+
+    /// The track at `path`: a channel's id, optionally followed by the ids of tracks it wraps,
+    /// e.g. `"drums/kick"`
+    pub fn track_mut(&mut self, path: &str) -> Option<&mut dyn Track> {
+        let (channel, rest) = path.split_once(track::PATH_SEPARATOR).unwrap_or((path, ""));
+        self.channel_mut(channel)?.track_mut(rest)
+    }
+
+    #[must_use]
+    pub const fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &Channel> {
+        self.channels.iter().map(Box::as_ref)
+    }
+
+    /// Channels in the folder `group`, in order
+    pub fn group<'a>(&'a self, group: &'a str) -> impl Iterator<Item = &'a Channel> {
+        self.channels()
+            .filter(move |channel| channel.group.as_deref() == Some(group))
+    }
+
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = &mut Channel> {
+        self.channels.iter_mut().map(Box::as_mut)
+    }
+
+    /// Removes every channel, in order
+    pub fn drain(&mut self) -> impl Iterator<Item = Box<Channel>> + '_ {
+        self.channels.drain(..)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Applies a change to the channel `id`, `false` if there's no such channel. What it
+    /// replaces is handed to `retire`, see [`Channel::apply`].
+    pub fn apply(&mut self, id: &str, change: ChannelChange, retire: impl FnMut(Garbage)) -> bool {
+        match change {
+            ChannelChange::MoveTo(index) => self.move_channel(id, index),
+            ChannelChange::SetSolo(solo) => self.set_solo(id, solo),
+            change => self
+                .channel_mut(id)
+                .map(|channel| channel.apply(change, retire))
+                .is_some(),
+        }
+    }
+
+    #[must_use]
+    pub const fn headroom(&self) -> Headroom {
+        self.headroom
+    }
+
+    /// Turns the master sum down as more channels play, see [`Headroom`]. The gain follows
+    /// the number of channels over a block, so tracks starting and ending don't click.
+    pub const fn set_headroom(&mut self, headroom: Headroom) {
+        self.headroom = headroom;
+    }
+
+    /// The gain compensation on the last block, `None` while it's off
+    #[must_use]
+    pub fn headroom_status(&self) -> Option<HeadroomStatus> {
+        let (channels, gain) = self.headroom_gain;
+        (self.headroom != Headroom::Off).then(|| HeadroomStatus {
+            headroom: self.headroom,
+            channels,
+            gain_db: gain_to_db(gain),
+        })
+    }
+
+    /// In exclusive mode soloing a channel or bus releases every other solo
+    pub fn set_exclusive_solo(&mut self, exclusive: bool) {
+        self.exclusive_solo = exclusive;
+    }
+
+    /// Solos or unsolos the channel `id`, honouring exclusive solo.
+    /// Returns `false` if there's no such channel.
+    pub fn set_solo(&mut self, id: &str, solo: bool) -> bool {
+        self.position(id).is_some_and(|index| {
+            if solo && self.exclusive_solo {
+                self.clear_solo();
+            }
+            self.channels[index].set_solo(solo);
+            true
+        })
+    }
+
+    /// Solos or unsolos bus `id`, honouring exclusive solo.
+    /// Returns `false` if there's no such bus.
+    pub fn set_bus_solo(&mut self, id: &str, solo: bool) -> bool {
+        Self::bus_index(&self.busses, Some(id)).is_some_and(|index| {
+            if solo && self.exclusive_solo {
+                self.clear_solo();
+            }
+            self.busses[index].set_solo(solo);
+            true
+        })
+    }
+
+    fn clear_solo(&mut self) {
+        for channel in &mut self.channels {
+            channel.set_solo(false);
+        }
+        for bus in &mut self.busses {
+            bus.set_solo(false);
+        }
+    }
+
+    /// Adds a bus feeding the master. Allocates, set busses up before playback or off the
+    /// audio thread.
+    pub fn add_bus(&mut self, bus: Bus) {
+        self.busses.push(bus);
+        self.update_bus_order();
+    }
+
+    pub fn busses(&self) -> impl Iterator<Item = &Bus> {
+        self.busses.iter()
+    }
+
+    pub fn bus_mut(&mut self, id: &str) -> Option<&mut Bus> {
+        self.busses.iter_mut().find(|bus| bus.id == id)
+    }
+
+    /// Removes a bus, whatever was routed to it goes to the master instead
+    pub fn remove_bus(&mut self, id: &str) -> Option<Bus> {
+        let index = self.busses.iter().position(|bus| bus.id == id)?;
+        let bus = self.busses.remove(index);
+        for channel in &mut self.channels {
+            if channel.output.as_deref() == Some(id) {
+                channel.output = None;
+            }
+            for strip in &mut channel.aux {
+                if strip.output.as_deref() == Some(id) {
+                    strip.output = None;
+                }
+            }
+        }
+        for other in &mut self.busses {
+            if other.output.as_deref() == Some(id) {
+                other.output = None;
+            }
+        }
+        self.update_bus_order();
+        Some(bus)
+    }
+
+    /// Sends the channel `id`'s output to `bus`, `None` for the master
+    ///
+    /// # Errors
+    /// [`RoutingError::UnknownTarget`] if there's no such channel,
+    /// [`RoutingError::UnknownNode`] if there's no such bus.
+    pub fn route_channel(&mut self, id: &str, bus: Option<&str>) -> Result<(), RoutingError> {
+        if let Some(bus) = bus {
+            self.routing().connect(
+                &Node::Track(id.to_owned()),
+                &Node::Bus(bus.to_owned()),
+                Connection::Output,
+            )?;
+        }
+        let channel = self
+            .channel_mut(id)
+            .ok_or_else(|| RoutingError::UnknownTarget(id.to_owned()))?;
+        channel.output = bus.map(str::to_owned);
+        Ok(())
+    }
+
+    /// Sends aux output `index` of the channel `id` to `bus`, `None` for the master
+    ///
+    /// # Errors
+    /// [`RoutingError::UnknownTarget`] if there's no such channel,
+    /// [`RoutingError::UnknownNode`] if there's no such bus or aux output.
+    pub fn route_aux_output(
+        &mut self,
+        id: &str,
+        index: usize,
+        bus: Option<&str>,
+    ) -> Result<(), RoutingError> {
+        let node = Node::TrackOutput(id.to_owned(), index);
+        if let Some(bus) = bus {
+            self.routing()
+                .connect(&node, &Node::Bus(bus.to_owned()), Connection::Output)?;
+        }
+        let strip = self
+            .channel_mut(id)
+            .ok_or_else(|| RoutingError::UnknownTarget(id.to_owned()))?
+            .aux
+            .get_mut(index)
+            .ok_or_else(|| RoutingError::UnknownNode(node.to_string()))?;
+        strip.output = bus.map(str::to_owned);
+        Ok(())
+    }
+
+    /// Keys the inserts of the channel `id` with the pre-fader signal of the channel `source`,
+    /// e.g. for a [`Ducker`](crate::dsp::ducker::Ducker). `None` removes the key.
+    ///
+    /// Key sources render ahead of the other channels. A source keyed itself by a channel
+    /// later in the order hears that channel's previous block. Allocates, set sidechains up
+    /// before playback or off the audio thread.
+    ///
+    /// # Errors
+    /// [`RoutingError::UnknownTarget`] if there's no channel `id`, [`RoutingError::UnknownNode`]
+    /// if there's no channel `source`, [`RoutingError::Cycle`] if `id` already keys `source`.
+    pub fn route_sidechain(&mut self, id: &str, source: Option<&str>) -> Result<(), RoutingError> {
+        if let Some(source) = source {
+            self.routing().connect(
+                &Node::Track(source.to_owned()),
+                &Node::Track(id.to_owned()),
+                Connection::Sidechain,
+            )?;
+        }
+        let channel = self
+            .channel_mut(id)
+            .ok_or_else(|| RoutingError::UnknownTarget(id.to_owned()))?;
+        channel.sidechain = source.map(str::to_owned);
+
+        let sources: Vec<String> = self
+            .channels
+            .iter()
+            .filter_map(|channel| channel.sidechain.clone())
+            .collect();
+        self.keys.retain(|(id, _)| sources.contains(id));
+        for source in sources {
+            if !self.keys.iter().any(|(id, _)| *id == source) {
+                self.keys
+                    .push((source, AudioBuffer::stereo(self.scratch.capacity())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds bus `id` into `target`, `None` for the master
+    ///
+    /// # Errors
+    /// [`RoutingError::UnknownNode`] if either bus doesn't exist, [`RoutingError::Cycle`] if
+    /// `target` already feeds `id`.
+    pub fn route_bus(&mut self, id: &str, target: Option<&str>) -> Result<(), RoutingError> {
+        let to = target.map_or(Node::Master, |target| Node::Bus(target.to_owned()));
+        self.routing()
+            .connect(&Node::Bus(id.to_owned()), &to, Connection::Output)?;
+        if let Some(bus) = self.busses.iter_mut().find(|bus| bus.id == id) {
+            bus.output = target.map(str::to_owned);
+        }
+        self.update_bus_order();
+        Ok(())
+    }
+
+    /// The current channel and bus routing as a graph
+    #[must_use]
+    pub fn routing(&self) -> RoutingGraph {
+        let mut graph = RoutingGraph::new();
+        for bus in &self.busses {
+            graph.add_node(Node::Bus(bus.id.clone()));
+        }
+        for channel in &self.channels {
+            graph.add_node(Node::Track(channel.id.to_string()));
+            for index in 0..channel.aux.len() {
+                graph.add_node(Node::TrackOutput(channel.id.to_string(), index));
+            }
+        }
+
+        let output = |target: Option<&String>| {
+            target
+                .map(|bus| Node::Bus(bus.clone()))
+                .filter(|bus| graph.contains(bus))
+                .unwrap_or(Node::Master)
+        };
+        let mut connections = Vec::new();
+        for bus in &self.busses {
+            connections.push((
+                Node::Bus(bus.id.clone()),
+                output(bus.output.as_ref()),
+                Connection::Output,
+            ));
+        }
+        for channel in &self.channels {
+            let from = Node::Track(channel.id.to_string());
+            connections.push((
+                from.clone(),
+                output(channel.output.as_ref()),
+                Connection::Output,
+            ));
+            for send in &channel.sends {
+                connections.push((from.clone(), Node::Bus(send.bus.clone()), Connection::Send));
+            }
+            if let Some(source) = &channel.sidechain {
+                connections.push((
+                    Node::Track(source.clone()),
+                    from.clone(),
+                    Connection::Sidechain,
+                ));
+            }
+            for (index, strip) in channel.aux.iter().enumerate() {
+                connections.push((
+                    Node::TrackOutput(channel.id.to_string(), index),
+                    output(strip.output.as_ref()),
+                    Connection::Output,
+                ));
+            }
+        }
+        for (from, to, kind) in connections {
+            // routes are validated when they're made, sends to missing busses are skipped
+            let _ = graph.connect(&from, &to, kind);
+        }
+        graph
+    }
+
+    fn update_bus_order(&mut self) {
+        let routing = self.routing();
+        self.bus_order = routing
+            .order()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Bus(id) => self.busses.iter().position(|bus| bus.id == *id),
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.channels.iter().position(|channel| *channel.id == *id)
+    }
+
+    /// Sums every channel into `output`, overwriting its previous contents. Outputs longer
+    /// than [`MAX_BLOCK_FRAMES`] are mixed in blocks of that size, so nothing is allocated.
+    pub fn mix(&mut self, output: &mut AudioBuffer) {
+        let frames = output.frames();
+        output.clear();
+
+        for start in (0..frames).step_by(MAX_BLOCK_FRAMES) {
+            let len = (frames - start).min(MAX_BLOCK_FRAMES);
+            self.mix_block(output, start, len, None, None, |_| {});
+        }
+    }
+
+    /// Adds frames `start..start + frames` of the mix to `output`.
+    ///
+    /// `frame` is the timeline position of the block, tracks render from there. Without it
+    /// tracks stream on from wherever they are.
+    ///
+    /// With a `deadline` every channel's CPU load is measured against it. `finished` is
+    /// called for each channel whose track ran out of material during this block.
+    pub(crate) fn mix_block(
+        &mut self,
+        output: &mut AudioBuffer,
+        start: usize,
+        frames: usize,
+        frame: Option<u64>,
+        deadline: Option<Duration>,
+        mut finished: impl FnMut(&Channel),
+    ) {
+        for bus in &mut self.busses {
+            bus.buffer.set_frames(frames);
+            bus.buffer.clear();
+            bus.flags.remove(BusFlags::SOLO_PATH);
+        }
+        self.scratch.set_frames(frames);
+        for (_, key) in &mut self.keys {
+            key.set_frames(frames);
+            key.clear();
+        }
+        for channel in &mut self.channels {
+            match frame.filter(|_| self.rolling) {
+                Some(frame) => {
+                    channel.capture(&self.record, frame, frames);
+                    channel.automate(frame, frames);
+                }
+                None => channel.take_frame = None,
+            }
+        }
+        // key sources render first, so the channels they key hear this block
+        for channel in &mut self.channels {
+            let Some(index) = self.keys.iter().position(|(id, _)| **id == *channel.id) else {
+                continue;
+            };
+            let was_finished = channel.is_finished();
+            let started = deadline.and_then(|_| cpu::now());
+            // taken out so the source's own key can be read meanwhile
+            let mut key = std::mem::take(&mut self.keys[index].1);
+            channel.render(
+                &mut key,
+                frame,
+                self.rolling,
+                Self::key(&self.keys, channel.sidechain.as_deref()),
+            );
+            self.keys[index].1 = key;
+            if let (Some(started), Some(deadline)) = (started, deadline) {
+                channel.load.record(started.elapsed(), deadline);
+            }
+            if !was_finished && channel.is_finished() {
+                finished(channel);
+            }
+        }
+        let soloing = self.channels.iter().any(|channel| channel.is_soloed())
+            || self.busses.iter().any(Bus::is_soloed);
+        let mut summed = 0;
+
+        for channel in &mut self.channels {
+            let rendered = Self::key(&self.keys, Some(&channel.id));
+            let was_finished = channel.is_finished();
+            let started = deadline
+                .filter(|_| rendered.is_none())
+                .and_then(|_| cpu::now());
+
+            // always rendered, so tracks silenced by a solo keep their position
+            self.scratch.clear();
+            if let Some(rendered) = rendered {
+                self.scratch.copy_from(0, rendered, 0, frames);
+            } else {
+                let key = Self::key(&self.keys, channel.sidechain.as_deref());
+                channel.render(&mut self.scratch, frame, self.rolling, key);
+            }
+            if channel.launch_fade.process(&mut self.scratch) {
+                channel.set_parked(true);
+            }
+            let target = Self::bus_index(&self.busses, channel.output.as_deref());
+            let solo_audible = !soloing || channel.is_soloed() || channel.is_solo_safe();
+            let audible = solo_audible || Self::feeds_soloed_bus(&self.busses, target);
+            // cue sends go out whatever is soloed in the control room
+            Self::send(channel, &self.scratch, &mut self.busses, true, audible);
+            channel.apply_fader(&mut self.scratch);
+            Self::send(channel, &self.scratch, &mut self.busses, false, audible);
+            if audible {
+                if !was_finished && !channel.is_muted() && !channel.is_parked() {
+                    summed += 1;
+                }
+                if let Some(stems) = self.stems.as_mut() {
+                    stems.send(&channel.id, &self.scratch, 0, frames, 1.0);
+                }
+                Self::sum(
+                    &self.scratch,
+                    target,
+                    channel.is_soloed(),
+                    &mut self.busses,
+                    output,
+                    start,
+                );
+            } else if let Some(stems) = self.stems.as_mut() {
+                stems.silence(&channel.id, frames);
+            }
+
+            for index in 0..channel.aux.len() {
+                self.scratch.clear();
+                channel.render_aux(index, &mut self.scratch);
+                let strip = &channel.aux[index];
+                let target = Self::bus_index(&self.busses, strip.output.as_deref());
+                if solo_audible || Self::feeds_soloed_bus(&self.busses, target) {
+                    let pan = pan_gains(channel.pan_law, strip.pan);
+                    fader(&mut self.scratch, strip.gain, pan, strip.mute);
+                    Self::sum(
+                        &self.scratch,
+                        target,
+                        channel.is_soloed(),
+                        &mut self.busses,
+                        output,
+                        start,
+                    );
+                }
+            }
+
+            if let (Some(started), Some(deadline)) = (started, deadline) {
+                channel.load.record(started.elapsed(), deadline);
+            }
+            if !was_finished && channel.is_finished() {
+                finished(channel);
+            }
+        }
+
+        for &index in &self.bus_order {
+            let target = Self::bus_index(&self.busses, self.busses[index].output.as_deref());
+            let feeds_solo = Self::feeds_soloed_bus(&self.busses, target);
+            let bus = &mut self.busses[index];
+            for insert in &mut bus.inserts {
+                insert.process(&mut bus.buffer, 0, frames);
+            }
+            if bus.is_cue() {
+                // past the cue bus's inserts, so talkback stays dry
+                if let Some(gain) = self.talkback {
+                    bus.buffer.add_scaled_from(&self.talkback_input, 0, gain);
+                }
+                if let Some(stems) = self.stems.as_mut() {
+                    let gain = if bus.is_muted() { 0.0 } else { bus.gain };
+                    stems.send(&bus.id, &bus.buffer, 0, frames, gain);
+                }
+                continue;
+            }
+            let carries_solo = bus.flags.intersects(BusFlags::SOLO | BusFlags::SOLO_PATH);
+            let audible =
+                !soloing || carries_solo || bus.flags.contains(BusFlags::SOLO_SAFE) || feeds_solo;
+            if bus.is_muted() || !audible {
+                if let Some(stems) = self.stems.as_mut() {
+                    stems.silence(&bus.id, frames);
+                }
+                continue;
+            }
+            if let Some(stems) = self.stems.as_mut() {
+                stems.send(&bus.id, &bus.buffer, 0, frames, bus.gain);
+            }
+            // taken out so it can be summed into another bus, leaves an empty buffer behind
+            let buffer = std::mem::take(&mut bus.buffer);
+            let gain = bus.gain;
+            match target {
+                Some(target) => {
+                    self.busses[target].buffer.add_scaled_from(&buffer, 0, gain);
+                    if carries_solo {
+                        self.busses[target].flags.insert(BusFlags::SOLO_PATH);
+                    }
+                }
+                None => output.add_scaled_from(&buffer, start, gain),
+            }
+            self.busses[index].buffer = buffer;
+        }
+        self.compensate_headroom(output, start, frames, summed);
     }
 
-    pub fn mix(&mut self, frame_size: usize) -> Vec<f32> {
-        let mut mix_buffer = vec![(0.0f32, 0.0f32); frame_size];
+    /// Turns frames `start..start + frames` of the master sum down for `summed` channels,
+    /// ramping from the gain of the last block
+    fn compensate_headroom(
+        &mut self,
+        output: &mut AudioBuffer,
+        start: usize,
+        frames: usize,
+        summed: usize,
+    ) {
+        let from = self.headroom_gain.1;
+        let to = self.headroom.gain(summed);
+        self.headroom_gain = (summed, to);
+        if (from - 1.0).abs() < f32::EPSILON && (to - 1.0).abs() < f32::EPSILON {
+            return;
+        }
+        let step = (to - from) / frames.max(1) as f32;
+        for channel in 0..output.channels() {
+            for (i, sample) in output.channel_mut(channel)[start..start + frames]
+                .iter_mut()
+                .enumerate()
+            {
+                *sample *= step.mul_add((i + 1) as f32, from);
+            }
+        }
+    }
 
-        for track in self.tracks.iter_mut() {
-            let samples = track.next_samples(frame_size);
-            for (i, (l, r)) in samples.iter().enumerate() {
-                mix_buffer[i].0 += l;
-                mix_buffer[i].1 += r;
+    /// Adds a post-fader `signal` to bus `target`, or at frame `start` of `output` for the
+    /// master
+    fn sum(
+        signal: &AudioBuffer,
+        target: Option<usize>,
+        solo: bool,
+        busses: &mut [Bus],
+        output: &mut AudioBuffer,
+        start: usize,
+    ) {
+        match target {
+            Some(bus) => {
+                busses[bus].buffer.add_from(signal, 0);
+                if solo {
+                    busses[bus].flags.insert(BusFlags::SOLO_PATH);
+                }
             }
+            None => output.add_from(signal, start),
         }
+    }
 
-        let mut interleave_buffer = Vec::with_capacity(frame_size * 2);
-        for (l, r) in mix_buffer {
-            interleave_buffer.push(l);
-            interleave_buffer.push(r);
+    /// `true` if the chain of busses starting at `bus` contains a soloed one
+    fn feeds_soloed_bus(busses: &[Bus], mut bus: Option<usize>) -> bool {
+        while let Some(index) = bus {
+            if busses[index].is_soloed() {
+                return true;
+            }
+            bus = Self::bus_index(busses, busses[index].output.as_deref());
         }
+        false
+    }
+
+    /// This block's signal of the key source `id`
+    fn key<'a>(keys: &'a [(String, AudioBuffer)], id: Option<&str>) -> Option<&'a AudioBuffer> {
+        let id = id?;
+        keys.iter()
+            .find(|(source, _)| source == id)
+            .map(|(_, key)| key)
+    }
+
+    fn bus_index(busses: &[Bus], id: Option<&str>) -> Option<usize> {
+        let id = id?;
+        busses.iter().position(|bus| bus.id == id)
+    }
 
-        interleave_buffer
+    /// Adds `signal` to the busses `channel` sends to `pre_fader`, only to cue busses
+    /// unless it's `audible`
+    fn send(
+        channel: &Channel,
+        signal: &AudioBuffer,
+        busses: &mut [Bus],
+        pre_fader: bool,
+        audible: bool,
+    ) {
+        for send in channel
+            .sends
+            .iter()
+            .filter(|send| send.pre_fader == pre_fader)
+        {
+            if let Some(bus) = busses
+                .iter_mut()
+                .find(|bus| bus.id == send.bus && (audible || bus.is_cue()))
+            {
+                bus.buffer.add_scaled_from(signal, 0, send.level);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::track::{constant::ConstantTrack, gainpan::GainPanTrack};
+    use crate::{
+        dsp::ducker::Ducker,
+        midi::{EventList, Instrument},
+        stems::StemReader,
+        track::{constant::ConstantTrack, gainpan::GainPanTrack, midi::MidiTrack, wav::WavTrack},
+    };
+
+    /// Constant track with a custom id
+    fn constant(id: &str, left: f32, right: f32) -> Box<dyn Track> {
+        Box::new(GainPanTrack::new(
+            id,
+            Box::new(ConstantTrack::new(left, right)),
+            1.0,
+            0.0,
+        ))
+    }
+
+    fn mix_one_frame(mixer: &mut Mixer) -> (f32, f32) {
+        let mut output = AudioBuffer::stereo(1);
+        mixer.mix(&mut output);
+        output.frame(0)
+    }
+
+    /// Doubles the signal
+    struct Doubler;
+
+    impl Processor for Doubler {
+        fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+            for channel in 0..buffer.channels() {
+                for sample in &mut buffer.channel_mut(channel)[start..start + len] {
+                    *sample *= 2.0;
+                }
+            }
+        }
+    }
 
     #[test]
     fn test_gain_one_pan_center_should_preserve_sample() {
@@ -47,8 +1841,8 @@ mod tests {
         let mut wrapped = GainPanTrack::new("x-track", Box::new(track), 1.0, 0.0);
 
         let samples = wrapped.next_samples(1);
-        assert_eq!(samples[0].0, 0.5); // (1.0 * 1.0 * 0.5)
-        assert_eq!(samples[0].1, 0.5);
+        assert_eq!(samples.frame(0).0, 1.0);
+        assert_eq!(samples.frame(0).1, 1.0);
     }
 
     #[test]
@@ -57,8 +1851,8 @@ mod tests {
         let mut wrapped = GainPanTrack::new("x-track", Box::new(track), 0.5, 0.0);
 
         let samples = wrapped.next_samples(1);
-        assert_eq!(samples[0].0, 0.25); // (1.0 * 0.5 * 0.5)
-        assert_eq!(samples[0].1, 0.25);
+        assert_eq!(samples.frame(0).0, 0.5); // (1.0 * 0.5)
+        assert_eq!(samples.frame(0).1, 0.5);
     }
 
     #[test]
@@ -67,8 +1861,8 @@ mod tests {
         let mut wrapped = GainPanTrack::new("x-track", Box::new(track), 1.0, -1.0);
 
         let samples = wrapped.next_samples(1);
-        assert_eq!(samples[0].0, 1.0); // Left channel full
-        assert_eq!(samples[0].1, 0.0); // Right channel muted
+        assert_eq!(samples.frame(0).0, 1.0); // Left channel full
+        assert_eq!(samples.frame(0).1, 0.0); // Right channel muted
     }
 
     #[test]
@@ -77,8 +1871,8 @@ mod tests {
         let mut wrapped = GainPanTrack::new("x-track", Box::new(track), 1.0, 1.0);
 
         let samples = wrapped.next_samples(1);
-        assert_eq!(samples[0].0, 0.0); // Left muted
-        assert_eq!(samples[0].1, 1.0); // Right full
+        assert_eq!(samples.frame(0).0, 0.0); // Left muted
+        assert_eq!(samples.frame(0).1, 1.0); // Right full
     }
 
     #[test]
@@ -92,14 +1886,349 @@ mod tests {
         mixer.add_track(Box::new(t1));
         mixer.add_track(Box::new(t2));
 
-        let output = mixer.mix(1);
-        assert_eq!(output, vec![0.5, 1.0]); // L = 0.2 + 0.3, R = 0.4 + 0.6
+        let mut output = AudioBuffer::stereo(1);
+        mixer.mix(&mut output);
+        assert_eq!(output.frame(0), (0.5, 1.0)); // L = 0.2 + 0.3, R = 0.4 + 0.6
+    }
+
+    #[test]
+    fn test_mono_tracks_are_upmixed_into_the_strip() {
+        let mut mixer = Mixer::new();
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&[]));
+        assert_eq!(wav.channels(), 2);
+        let mut mono = AudioBuffer::mono(4);
+        mono.channel_mut(0).copy_from_slice(&[0.1, 0.2, 0.3, 0.4]);
+        let wav = WavTrack::from_buffer(mono);
+        assert_eq!(wav.channels(), 1);
+        mixer.add_track(Box::new(wav));
+        mixer.channel_mut("wav-track").unwrap().set_pan(1.0);
+
+        let mut output = AudioBuffer::stereo(4);
+        mixer.mix(&mut output);
+        assert_eq!(output.channel(0), [0.0; 4]);
+        assert_eq!(output.channel(1), [0.1, 0.2, 0.3, 0.4]);
     }
 
     #[test]
     fn test_mixer_with_no_tracks_should_output_silence() {
         let mut mixer = Mixer::new();
-        let output = mixer.mix(2);
-        assert_eq!(output, vec![0.0, 0.0, 0.0, 0.0]);
+        let mut output = AudioBuffer::stereo(2);
+        mixer.mix(&mut output);
+        assert!(output.iter_frames().all(|frame| frame == (0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_outputs_longer_than_a_block_are_mixed_in_blocks() {
+        let mut mixer = Mixer::new();
+        mixer.add_track(constant("a", 0.5, 0.25));
+        let mut output = AudioBuffer::stereo(MAX_BLOCK_FRAMES + 5);
+        mixer.mix(&mut output);
+        assert!(output.iter_frames().all(|frame| frame == (0.5, 0.25)));
+        assert_eq!(mixer.scratch.capacity(), MAX_BLOCK_FRAMES);
+    }
+
+    #[test]
+    fn test_channel_fader_gain_pan_and_mute() {
+        let mut mixer = Mixer::new();
+        mixer.add_track(constant("keys", 0.5, 0.5));
+
+        let channel = mixer.channel_mut("keys").unwrap();
+        channel.set_gain(0.5);
+        channel.set_pan(-0.5);
+        assert_eq!(mix_one_frame(&mut mixer), (0.25, 0.125));
+
+        mixer.channel_mut("keys").unwrap().set_mute(true);
+        assert_eq!(mix_one_frame(&mut mixer), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_remove_and_reorder_channels() {
+        let mut mixer = Mixer::new();
+        for id in ["a", "b", "c", "d"] {
+            mixer.add_track(constant(id, 0.1, 0.1));
+        }
+
+        assert!(mixer.move_channel("a", 2));
+        assert!(mixer.move_channel("d", 0));
+        assert!(!mixer.move_channel("missing", 0));
+        let removed = mixer.remove_channel("b").unwrap();
+        assert_eq!(removed.id(), "b");
+
+        let order: Vec<_> = mixer.channels().map(Channel::id).collect();
+        assert_eq!(order, vec!["d", "c", "a"]);
+    }
+
+    #[test]
+    fn test_channels_are_kept_in_arrangement_order() {
+        let mut mixer = Mixer::new();
+        for (id, order) in [("drums", 0), ("keys", 2), ("bass", 1)] {
+            let mut channel = Channel::new(constant(id, 0.1, 0.1));
+            channel.set_order(order);
+            channel.set_group((id != "keys").then(|| "rhythm".to_owned()));
+            mixer.add_channel(channel);
+        }
+        mixer.add_track(constant("fx", 0.1, 0.1));
+
+        let order: Vec<_> = mixer.channels().map(Channel::id).collect();
+        assert_eq!(order, vec!["drums", "bass", "keys", "fx"]);
+        let rhythm: Vec<_> = mixer.group("rhythm").map(Channel::id).collect();
+        assert_eq!(rhythm, vec!["drums", "bass"]);
+    }
+
+    #[test]
+    fn test_inserts_run_before_the_fader() {
+        let mut mixer = Mixer::new();
+        let mut channel = Channel::new(constant("vox", 0.25, 0.25));
+        channel.add_insert(Box::new(Doubler));
+        channel.set_mute(true);
+        channel.set_send("verb".into(), 1.0, true);
+        mixer.add_channel(channel);
+        mixer.add_bus(Bus::new("verb"));
+
+        // muted, but the pre-fader send still carries the processed signal
+        assert_eq!(mix_one_frame(&mut mixer), (0.5, 0.5));
+    }
+
+    #[test]
+    fn test_busses_render_after_their_sources() {
+        let mut mixer = Mixer::new();
+        // added before the bus feeding it, still processed after it
+        let mut group = Bus::new("group");
+        group.add_insert(Box::new(Doubler));
+        mixer.add_bus(group);
+        mixer.add_bus(Bus::new("drums"));
+        mixer.add_track(constant("kick", 0.1, 0.1));
+        mixer.route_channel("kick", Some("drums")).unwrap();
+        mixer.route_bus("drums", Some("group")).unwrap();
+
+        let (left, _) = mix_one_frame(&mut mixer);
+        assert!((left - 0.2).abs() < 1e-6);
+        assert!(matches!(
+            mixer.route_bus("group", Some("drums")),
+            Err(RoutingError::Cycle { .. })
+        ));
+        assert!(matches!(
+            mixer.route_channel("kick", Some("missing")),
+            Err(RoutingError::UnknownNode(_))
+        ));
+    }
+
+    #[test]
+    fn test_removing_a_bus_reroutes_to_master() {
+        let mut mixer = Mixer::new();
+        let mut bus = Bus::new("drums");
+        bus.set_mute(true);
+        mixer.add_bus(bus);
+        mixer.add_track(constant("kick", 0.1, 0.1));
+        mixer.route_channel("kick", Some("drums")).unwrap();
+        assert_eq!(mix_one_frame(&mut mixer), (0.0, 0.0));
+
+        mixer.remove_bus("drums");
+        assert_eq!(mixer.channel("kick").unwrap().output(), None);
+        assert_eq!(mix_one_frame(&mut mixer), (0.1, 0.1));
+    }
+
+    #[test]
+    fn test_stems_stream_post_fader_channels_and_busses() {
+        let mut mixer = Mixer::new();
+        let mut drums = Bus::new("drums");
+        drums.set_gain(0.5);
+        mixer.add_bus(drums);
+        mixer.add_track(constant("kick", 0.2, 0.2));
+        mixer.add_track(constant("bass", 0.4, 0.4));
+        mixer.route_channel("kick", Some("drums")).unwrap();
+        mixer.channel_mut("bass").unwrap().set_mute(true);
+        let (tap, mut readers) = crate::stems::stem_channel(&["kick", "drums", "bass"], 4);
+        mixer.set_stem_tap(Some(tap));
+
+        mix_one_frame(&mut mixer);
+        let frames: Vec<_> = readers.iter_mut().map(StemReader::next_frame).collect();
+        assert!((frames[0].0 - 0.2).abs() < 1e-6);
+        assert!((frames[1].0 - 0.1).abs() < 1e-6);
+        assert_eq!(frames[2], (0.0, 0.0));
+        assert!(readers.iter().all(|reader| reader.available() == 0));
+    }
+
+    #[test]
+    fn test_cue_bus_ignores_faders_and_solos_and_stays_off_the_master() {
+        let mut mixer = Mixer::new();
+        let mut cue = Bus::new("cue");
+        cue.set_cue(true);
+        mixer.add_bus(cue);
+        for (id, level) in [("kick", 0.1), ("bass", 0.4)] {
+            let mut channel = Channel::new(constant(id, level, level));
+            channel.set_send("cue".into(), if id == "kick" { 1.0 } else { 0.5 }, true);
+            mixer.add_channel(channel);
+        }
+        mixer.channel_mut("kick").unwrap().set_gain(0.0);
+        mixer.channel_mut("bass").unwrap().set_solo(true);
+        let (tap, mut readers) = crate::stems::stem_channel(&["cue"], 4);
+        mixer.set_stem_tap(Some(tap));
+
+        let (left, _) = mix_one_frame(&mut mixer);
+        assert!((left - 0.4).abs() < 1e-6);
+        let (cue_left, _) = readers[0].next_frame();
+        assert!((cue_left - 0.3).abs() < 1e-6);
+    }
+
+    /// Kick and snare into a drum bus, bass to the master, all sending to a reverb
+    fn solo_session() -> Mixer {
+        let mut mixer = Mixer::new();
+        mixer.add_bus(Bus::new("drums"));
+        mixer.add_bus(Bus::new("verb"));
+        for (id, level) in [("kick", 0.1), ("snare", 0.2), ("bass", 0.4)] {
+            let mut channel = Channel::new(constant(id, level, level));
+            channel.set_send("verb".into(), 1.0, false);
+            mixer.add_channel(channel);
+        }
+        mixer.route_channel("kick", Some("drums")).unwrap();
+        mixer.route_channel("snare", Some("drums")).unwrap();
+        mixer
+    }
+
+    #[test]
+    fn test_solo_in_place_keeps_the_path_to_master() {
+        let mut mixer = solo_session();
+        assert!((mix_one_frame(&mut mixer).0 - 1.4).abs() < 1e-6);
+
+        // kick through the drum bus, its reverb send is silenced with the return
+        mixer.set_solo("kick", true);
+        assert!((mix_one_frame(&mut mixer).0 - 0.1).abs() < 1e-6);
+
+        // a solo-safe return stays up and only carries the soloed kick
+        mixer.bus_mut("verb").unwrap().set_solo_safe(true);
+        assert!((mix_one_frame(&mut mixer).0 - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_soloing_a_bus_keeps_its_sources() {
+        let mut mixer = solo_session();
+        mixer.set_bus_solo("drums", true);
+        assert!((mix_one_frame(&mut mixer).0 - 0.3).abs() < 1e-6);
+
+        mixer.channel_mut("bass").unwrap().set_solo_safe(true);
+        assert!((mix_one_frame(&mut mixer).0 - 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_exclusive_solo_releases_other_solos() {
+        let mut mixer = solo_session();
+        mixer.set_solo("kick", true);
+        mixer.set_solo("bass", true);
+        assert!((mix_one_frame(&mut mixer).0 - 0.5).abs() < 1e-6);
+
+        mixer.set_exclusive_solo(true);
+        assert!(mixer.apply("snare", ChannelChange::SetSolo(true), drop));
+        assert!(!mixer.channel("kick").unwrap().is_soloed());
+        assert!((mix_one_frame(&mut mixer).0 - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_post_fader_send_follows_gain() {
+        let mut mixer = Mixer::new();
+        let mut channel = Channel::new(constant("gtr", 0.4, 0.4));
+        channel.set_gain(0.5);
+        channel.set_send("delay".into(), 0.5, false);
+        mixer.add_channel(channel);
+        let mut bus = Bus::new("delay");
+        bus.add_insert(Box::new(Doubler));
+        mixer.add_bus(bus);
+
+        // dry 0.2, plus the send 0.2 * 0.5 doubled on the bus
+        let (left, _) = mix_one_frame(&mut mixer);
+        assert!((left - 0.4).abs() < 1e-6);
+    }
+
+    /// Plays `n + 1` on output `n`
+    struct Outputs;
+
+    impl Instrument for Outputs {
+        fn process(&mut self, _: &EventList, _: &mut AudioBuffer) {}
+
+        fn outputs(&self) -> usize {
+            3
+        }
+
+        fn process_outputs(&mut self, _: &EventList, outputs: &mut [AudioBuffer]) {
+            for (level, output) in (1u8..).zip(outputs) {
+                output.channel_mut(0).fill(f32::from(level));
+            }
+        }
+    }
+
+    #[test]
+    fn test_aux_outputs_mix_through_their_own_strips() {
+        let mut mixer = Mixer::new();
+        mixer.add_track(Box::new(MidiTrack::new("drums", Box::new(Outputs))));
+        let mut bus = Bus::new("kick");
+        bus.set_gain(0.5);
+        mixer.add_bus(bus);
+        mixer.route_aux_output("drums", 0, Some("kick")).unwrap();
+        let channel = mixer.channel_mut("drums").unwrap();
+        assert_eq!(channel.aux_outputs().len(), 2);
+        channel.aux_output_mut(1).unwrap().set_mute(true);
+
+        // main 1, plus aux 0 at 2 halved by the bus, aux 1 muted
+        assert_eq!(mix_one_frame(&mut mixer).0, 2.0);
+        assert!(matches!(
+            mixer.route_aux_output("drums", 2, None),
+            Err(RoutingError::UnknownNode(_))
+        ));
+        let routing = mixer.routing();
+        assert_eq!(
+            routing.output_of(&Node::TrackOutput("drums".into(), 0)),
+            Some(&Node::Bus("kick".into()))
+        );
+    }
+
+    #[test]
+    fn test_sidechain_keys_inserts_with_the_source_signal() {
+        let mut mixer = Mixer::new();
+        let mut bass = Channel::new(constant("bass", 0.5, 0.5));
+        let mut ducker = Ducker::new(1000.0);
+        ducker.set_depth(6.0);
+        ducker.set_attack(0.001);
+        bass.add_insert(Box::new(ducker));
+        mixer.add_channel(bass);
+        let mut kick = Channel::new(constant("kick", 1.0, 1.0));
+        kick.set_mute(true);
+        mixer.add_channel(kick);
+
+        mixer.route_sidechain("bass", Some("kick")).unwrap();
+        assert!(matches!(
+            mixer.route_sidechain("kick", Some("bass")),
+            Err(RoutingError::Cycle { .. })
+        ));
+        // the kick renders first although it comes second, the key is pre-fader
+        let mut output = AudioBuffer::stereo(100);
+        mixer.mix(&mut output);
+        // 0.5 down 6 dB
+        assert!((output.frame(99).0 - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_headroom_turns_the_sum_down_as_channels_play() {
+        let mut mixer = Mixer::new();
+        for id in ["kick", "snare", "bass", "keys"] {
+            mixer.add_track(constant(id, 0.5, 0.5));
+        }
+        mixer.apply("keys", ChannelChange::SetMute(true), drop);
+        assert_eq!(mixer.headroom_status(), None);
+        assert!((mix_one_frame(&mut mixer).0 - 1.5).abs() < 1e-6);
+
+        // three channels play: 6 dB per doubling is 1/3
+        mixer.set_headroom(Headroom::PerDoubling { db: 6.0206 });
+        let mut output = AudioBuffer::stereo(4);
+        mixer.mix(&mut output);
+        // ramped from unity over the block
+        assert!(output.frame(0).0 > output.frame(3).0);
+        assert!((output.frame(3).0 - 0.5).abs() < 1e-3);
+        let status = mixer.headroom_status().unwrap();
+        assert_eq!(status.channels, 3);
+        assert!((status.gain_db + 9.54).abs() < 0.01);
+
+        mixer.set_headroom(Headroom::Fixed { db: 6.0206 });
+        mix_one_frame(&mut mixer);
+        assert!((mix_one_frame(&mut mixer).0 - 0.75).abs() < 1e-3);
     }
 }
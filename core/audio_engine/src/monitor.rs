@@ -0,0 +1,317 @@
+//! Control room monitoring: what the speakers hear, after the master bus.
+use bitflags::bitflags;
+use dasp_sample::{FromSample, Sample as _};
+
+use crate::{buffer::AudioBuffer, dsp::math::db_to_gain};
+
+const DEFAULT_DIM_DB: f32 = -20.0;
+
+/// A pair of speakers on the output device
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeakerSet {
+    pub name: String,
+    /// Device channels (0-based) fed by the left and right monitor signal
+    pub left: usize,
+    pub right: usize,
+    /// Level correction in dB, to match the loudness of different speakers
+    pub trim_db: f32,
+}
+
+impl SpeakerSet {
+    #[must_use]
+    pub fn new(name: &str, left: usize, right: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            left,
+            right,
+            trim_db: 0.0,
+        }
+    }
+}
+
+/// Changes to the monitor section, sent while the scheduler is running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MonitorChange {
+    /// Listening level in dB
+    SetLevel(f32),
+    SetDim(bool),
+    /// Folds the monitor signal to mono, to check mono compatibility
+    SetMono(bool),
+    /// Switches to the speaker set at this index, unknown indices are ignored
+    SelectSpeakers(usize),
+    /// Talks to the performers: the talkback input goes to the cue busses, see
+    /// [`Scheduler::talkback_input`](crate::scheduler::Scheduler::talkback_input)
+    SetTalkback(bool),
+    /// Level of the talkback input in the cue busses, in dB
+    SetTalkbackLevel(f32),
+    /// Dims the speakers while talking, so the talkback mic doesn't pick them up
+    SetDimOnTalk(bool),
+}
+
+bitflags! {
+    /// Switches on the [`MonitorController`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct MonitorFlags: u8 {
+        const DIM = 1;
+        /// Folds the monitor signal to mono
+        const MONO = 1 << 1;
+        const TALKBACK = 1 << 2;
+        /// Dims the speakers while talking
+        const DIM_ON_TALK = 1 << 3;
+    }
+}
+
+/// Monitor controller between the master bus and the output device.
+///
+/// Only affects what is heard: master metering, bounces and exports see the master bus
+/// before it. Speaker sets map the stereo monitor signal to pairs of device channels, the
+/// other channels of the device stay silent.
+///
+/// # Example
+/// ```
+/// use audio_engine::monitor::{MonitorController, SpeakerSet};
+///
+/// let mut monitor = MonitorController::new();
+/// let alt = monitor.add_speaker_set(SpeakerSet::new("Nearfields", 2, 3));
+/// monitor.select_speaker_set(alt);
+/// monitor.set_dim(true);
+///
+/// assert_eq!(monitor.speaker_set().name, "Nearfields");
+/// assert!((monitor.gain() - 0.1).abs() < 1e-6);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MonitorController {
+    level_db: f32,
+    flags: MonitorFlags,
+    dim_db: f32,
+    speaker_sets: Vec<SpeakerSet>,
+    active: usize,
+    talkback_level_db: f32,
+}
+
+impl MonitorController {
+    /// Unity level on the device's first two channels
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            level_db: 0.0,
+            flags: MonitorFlags::DIM_ON_TALK,
+            dim_db: DEFAULT_DIM_DB,
+            speaker_sets: vec![SpeakerSet::new("Main", 0, 1)],
+            active: 0,
+            talkback_level_db: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub fn level_db(&self) -> f32 {
+        self.level_db
+    }
+
+    pub fn set_level_db(&mut self, level_db: f32) {
+        self.level_db = level_db;
+    }
+
+    #[must_use]
+    pub fn is_dimmed(&self) -> bool {
+        self.flags.contains(MonitorFlags::DIM)
+    }
+
+    pub fn set_dim(&mut self, dim: bool) {
+        self.flags.set(MonitorFlags::DIM, dim);
+    }
+
+    /// How far dim drops the level, in dB (-20 by default)
+    pub fn set_dim_level_db(&mut self, dim_db: f32) {
+        self.dim_db = dim_db;
+    }
+
+    #[must_use]
+    pub fn is_mono(&self) -> bool {
+        self.flags.contains(MonitorFlags::MONO)
+    }
+
+    pub fn set_mono(&mut self, mono: bool) {
+        self.flags.set(MonitorFlags::MONO, mono);
+    }
+
+    #[must_use]
+    pub fn is_talking(&self) -> bool {
+        self.flags.contains(MonitorFlags::TALKBACK)
+    }
+
+    pub fn set_talkback(&mut self, talkback: bool) {
+        self.flags.set(MonitorFlags::TALKBACK, talkback);
+    }
+
+    /// Level of the talkback input in the cue busses, in dB
+    pub fn set_talkback_level_db(&mut self, level_db: f32) {
+        self.talkback_level_db = level_db;
+    }
+
+    /// Whether the speakers dim while talking (on by default)
+    pub fn set_dim_on_talk(&mut self, dim_on_talk: bool) {
+        self.flags.set(MonitorFlags::DIM_ON_TALK, dim_on_talk);
+    }
+
+    /// Linear gain of the talkback input in the cue busses, `None` while not talking
+    #[must_use]
+    pub fn talkback_gain(&self) -> Option<f32> {
+        self.flags
+            .contains(MonitorFlags::TALKBACK)
+            .then(|| db_to_gain(self.talkback_level_db))
+    }
+
+    /// Adds a speaker set and returns its index for [`MonitorController::select_speaker_set`]
+    pub fn add_speaker_set(&mut self, speakers: SpeakerSet) -> usize {
+        self.speaker_sets.push(speakers);
+        self.speaker_sets.len() - 1
+    }
+
+    /// Switches speakers, `false` if there's no set at `index`
+    pub fn select_speaker_set(&mut self, index: usize) -> bool {
+        let exists = index < self.speaker_sets.len();
+        if exists {
+            self.active = index;
+        }
+        exists
+    }
+
+    /// The speakers currently listened on
+    #[must_use]
+    pub fn speaker_set(&self) -> &SpeakerSet {
+        &self.speaker_sets[self.active]
+    }
+
+    #[must_use]
+    pub fn speaker_sets(&self) -> &[SpeakerSet] {
+        &self.speaker_sets
+    }
+
+    pub fn apply(&mut self, change: MonitorChange) {
+        match change {
+            MonitorChange::SetLevel(level_db) => self.set_level_db(level_db),
+            MonitorChange::SetDim(dim) => self.set_dim(dim),
+            MonitorChange::SetMono(mono) => self.set_mono(mono),
+            MonitorChange::SelectSpeakers(index) => {
+                self.select_speaker_set(index);
+            }
+            MonitorChange::SetTalkback(talkback) => self.set_talkback(talkback),
+            MonitorChange::SetTalkbackLevel(level_db) => self.set_talkback_level_db(level_db),
+            MonitorChange::SetDimOnTalk(dim_on_talk) => self.set_dim_on_talk(dim_on_talk),
+        }
+    }
+
+    /// Linear gain applied to the monitor signal: level, dim (also while talking) and
+    /// speaker trim together
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        let dimmed = self.flags.contains(MonitorFlags::DIM)
+            || self
+                .flags
+                .contains(MonitorFlags::TALKBACK | MonitorFlags::DIM_ON_TALK);
+        let dim = if dimmed { self.dim_db } else { 0.0 };
+        db_to_gain(self.level_db + dim + self.speaker_set().trim_db)
+    }
+
+    /// Writes a stereo master buffer into an interleaved device buffer of `channels`
+    /// channels, routed to the active speaker set
+    pub fn write_interleaved<T>(&self, master: &AudioBuffer, output: &mut [T], channels: usize)
+    where
+        T: FromSample<f32>,
+    {
+        if channels == 0 {
+            return;
+        }
+        let gain = self.gain();
+        let speakers = self.speaker_set();
+        for (index, frame) in output
+            .chunks_mut(channels)
+            .take(master.frames())
+            .enumerate()
+        {
+            let (mut left, mut right) = master.frame(index);
+            if self.flags.contains(MonitorFlags::MONO) {
+                let mid = (left + right) * 0.5;
+                (left, right) = (mid, mid);
+            }
+            for sample in frame.iter_mut() {
+                *sample = 0.0f32.to_sample::<T>();
+            }
+            if let Some(sample) = frame.get_mut(speakers.left) {
+                *sample = (left * gain).to_sample::<T>();
+            }
+            if let Some(sample) = frame.get_mut(speakers.right) {
+                *sample = (right * gain).to_sample::<T>();
+            }
+        }
+    }
+}
+
+impl Default for MonitorController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_monitor_passes_stereo_through() {
+        let master = AudioBuffer::from_frames(&[(0.5, -0.25), (0.1, 0.2)]);
+        let mut output = [0.0f32; 4];
+
+        MonitorController::new().write_interleaved(&master, &mut output, 2);
+
+        assert_eq!(output, [0.5, -0.25, 0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_level_dim_and_mono() {
+        let master = AudioBuffer::from_frames(&[(1.0, 0.0)]);
+        let mut monitor = MonitorController::new();
+        monitor.set_level_db(-6.0);
+        monitor.set_dim(true);
+        monitor.set_dim_level_db(-14.0);
+        monitor.set_mono(true);
+        let mut output = [0.0f32; 2];
+
+        monitor.write_interleaved(&master, &mut output, 2);
+
+        assert!((output[0] - 0.05).abs() < 1e-6);
+        assert_eq!(output[0], output[1]);
+    }
+
+    #[test]
+    fn test_talking_dims_the_speakers() {
+        let mut monitor = MonitorController::new();
+        assert_eq!(monitor.talkback_gain(), None);
+        monitor.apply(MonitorChange::SetTalkbackLevel(-6.0));
+        monitor.apply(MonitorChange::SetTalkback(true));
+        assert!((monitor.gain() - 0.1).abs() < 1e-6);
+        assert!((monitor.talkback_gain().unwrap() - 0.501).abs() < 1e-3);
+
+        monitor.apply(MonitorChange::SetDimOnTalk(false));
+        assert_eq!(monitor.gain(), 1.0);
+    }
+
+    #[test]
+    fn test_speaker_set_selects_device_channels() {
+        let master = AudioBuffer::from_frames(&[(0.5, 0.25)]);
+        let mut monitor = MonitorController::new();
+        let mut alt = SpeakerSet::new("Alt", 2, 3);
+        alt.trim_db = -6.020_6;
+        let alt = monitor.add_speaker_set(alt);
+        monitor.apply(MonitorChange::SelectSpeakers(alt));
+        monitor.apply(MonitorChange::SelectSpeakers(7));
+        let mut output = [1.0f32; 4];
+
+        monitor.write_interleaved(&master, &mut output, 4);
+
+        assert_eq!(&output[..2], &[0.0, 0.0]);
+        assert!((output[2] - 0.25).abs() < 1e-4);
+        assert!((output[3] - 0.125).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,567 @@
+use std::path::Path;
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{Processor, dither::Dither, limiter::TruePeakLimiter, math::db_to_gain},
+    error::{EngineError, SchedulingError},
+    metering::loudness::{LoudnessMeter, LoudnessSummary},
+    scheduler::{
+        Scheduler,
+        command::{LoopOptions, SchedulerCommand},
+    },
+    track::timeline::Clip,
+};
+
+/// The tail counts as decayed once it has stayed under the threshold this long
+const TAIL_HOLD_SECONDS: f64 = 0.5;
+
+/// A section of the arrangement to bounce
+pub enum BounceRange {
+    Frames {
+        start: u64,
+        end: u64,
+    },
+    /// 1-based bar, beat and tick positions, like the loop points
+    Bbt {
+        start: LoopOptions,
+        end: LoopOptions,
+    },
+    /// From one marker to another
+    Markers {
+        start: String,
+        end: String,
+    },
+    Region(String),
+    /// The loop brace, while looping is enabled
+    Loop,
+}
+
+impl BounceRange {
+    /// `(start, end)` frames of the range on `scheduler`'s timeline
+    ///
+    /// # Errors
+    /// [`SchedulingError::UnknownRange`] for markers or regions that don't exist, or
+    /// [`BounceRange::Loop`] while looping is off.
+    pub fn resolve(&self, scheduler: &Scheduler) -> Result<(u64, u64), SchedulingError> {
+        let markers = scheduler.markers();
+        let marker = |name: &str| {
+            markers
+                .marker(name)
+                .map(|marker| scheduler.tick_to_frame(marker.tick))
+                .ok_or_else(|| SchedulingError::UnknownRange(format!("marker '{name}'")))
+        };
+        let (start, end) = match self {
+            Self::Frames { start, end } => (*start, *end),
+            Self::Bbt { start, end } => (
+                scheduler.bbt_to_frame(start.bar, start.beat, start.tick),
+                scheduler.bbt_to_frame(end.bar, end.beat, end.tick),
+            ),
+            Self::Markers { start, end } => (marker(start)?, marker(end)?),
+            Self::Region(name) => {
+                let region = markers
+                    .region(name)
+                    .ok_or_else(|| SchedulingError::UnknownRange(format!("region '{name}'")))?;
+                (
+                    scheduler.tick_to_frame(region.start),
+                    scheduler.tick_to_frame(region.end),
+                )
+            }
+            Self::Loop => scheduler
+                .loop_frames()
+                .ok_or_else(|| SchedulingError::UnknownRange("the loop".to_owned()))?,
+        };
+        Ok((start.min(end), start.max(end)))
+    }
+}
+
+/// How long a bounce keeps rendering after its range, for reverb and delay tails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TailSettings {
+    /// The tail ends once the output stays below this level, in dBFS
+    pub threshold_db: f32,
+    /// Longest tail rendered, whatever the level
+    pub max_seconds: f64,
+}
+
+impl Default for TailSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -80.0,
+            max_seconds: 10.0,
+        }
+    }
+}
+
+/// Bounces `range` of a freshly set up scheduler, frame-accurately.
+///
+/// Rendering starts at the top of the timeline so every track is where it would be in
+/// playback, only the range is kept. Looping is turned off. With `tail`, sources stop at
+/// the end of the range and the inserts keep running until their output has decayed.
+///
+/// # Errors
+/// When `range` can't be resolved, see [`BounceRange::resolve`].
+pub fn render_range(
+    scheduler: &mut Scheduler,
+    range: &BounceRange,
+    block_size: usize,
+    tail: Option<TailSettings>,
+) -> Result<AudioBuffer, SchedulingError> {
+    let (start, end) = range.resolve(scheduler)?;
+    let block_size = block_size.max(1);
+    let mut output = AudioBuffer::stereo((end - start) as usize);
+    let mut block = AudioBuffer::stereo(block_size);
+
+    let top = || LoopOptions {
+        bar: 1,
+        beat: 1,
+        tick: 1,
+    };
+    scheduler.process_command(SchedulerCommand::SetLoop {
+        enabled: false,
+        start: top(),
+        end: top(),
+    });
+    scheduler.process_command(SchedulerCommand::Play);
+
+    let mut frame = 0;
+    while frame < end {
+        let len = block_size.min((end - frame) as usize);
+        block.set_frames(len);
+        scheduler.render(&mut block);
+        let skip = (start.saturating_sub(frame) as usize).min(len);
+        let at = frame.saturating_sub(start) as usize;
+        output.copy_from(at, &block, skip, len - skip);
+        frame += len as u64;
+    }
+
+    if let Some(tail) = tail {
+        let tail = render_tail(scheduler, &mut block, tail);
+        output.extend_from(&tail);
+    }
+    Ok(output)
+}
+
+/// Renders until the output has stayed under the tail threshold, trimmed after the last
+/// frame above it
+fn render_tail(
+    scheduler: &mut Scheduler,
+    block: &mut AudioBuffer,
+    tail: TailSettings,
+) -> AudioBuffer {
+    scheduler.release_sources();
+    let sample_rate = scheduler.sample_rate();
+    let threshold = db_to_gain(tail.threshold_db);
+    let max_frames = (tail.max_seconds * sample_rate).round() as usize;
+    let hold = (TAIL_HOLD_SECONDS * sample_rate).round() as usize;
+
+    let mut rendered = AudioBuffer::stereo(0);
+    let mut audible = 0;
+    while rendered.frames() < max_frames && rendered.frames() - audible < hold {
+        block.set_frames(block.capacity().min(max_frames - rendered.frames()));
+        scheduler.render(block);
+        if let Some(last) = (0..block.frames()).rev().find(|&index| {
+            let (left, right) = block.frame(index);
+            left.abs().max(right.abs()) >= threshold
+        }) {
+            audible = rendered.frames() + last + 1;
+        }
+        rendered.extend_from(block);
+    }
+    rendered.set_frames(audible);
+    rendered
+}
+
+/// Plays `scheduler` faster than realtime until it goes idle (nothing queued, every track finished)
+/// or `max_frames` have been rendered, returning the stereo mix.
+pub fn render_until_idle(
+    scheduler: &mut Scheduler,
+    block_size: usize,
+    max_frames: u64,
+) -> AudioBuffer {
+    let block_size = block_size.max(1);
+    let mut output = AudioBuffer::stereo(0);
+    let mut block = AudioBuffer::stereo(block_size);
+
+    scheduler.process_command(SchedulerCommand::Play);
+
+    while (output.frames() as u64) < max_frames {
+        let remaining = (max_frames - output.frames() as u64) as usize;
+        block.set_frames(block_size.min(remaining));
+        scheduler.render(&mut block);
+        output.extend_from(&block);
+
+        if scheduler.is_idle() {
+            break;
+        }
+    }
+
+    output
+}
+
+/// Runs a finished stereo render through a true-peak limiter so it stays under `ceiling_dbtp`.
+/// The limiter's latency is compensated, the result lines up with the input.
+pub fn limit(mix: &mut AudioBuffer, sample_rate: f64, ceiling_dbtp: f32) {
+    let mut limiter = TruePeakLimiter::new(sample_rate, ceiling_dbtp);
+    let latency = limiter.latency();
+    let mut padded = mix.clone();
+    padded.extend_from(&AudioBuffer::stereo(latency));
+
+    let frames = padded.frames();
+    limiter.process(&mut padded, 0, frames);
+    mix.copy_from(0, &padded, latency, mix.frames());
+}
+
+/// Renders a clip's audio through `chain` without realtime constraints and returns a clip
+/// playing the result, see [`Clip::with_render`] for undoing it.
+///
+/// The chain's latency is compensated and `tail` frames are kept after the end of the clip
+/// for effects that ring on. Processors are reset before and after use.
+pub fn process_clip(clip: &Clip, chain: &mut [Box<dyn Processor>], tail: usize) -> Clip {
+    let latency: usize = chain.iter().map(|processor| processor.latency()).sum();
+    let mut audio = clip.audio();
+    audio.extend_from(&AudioBuffer::stereo(latency + tail));
+
+    let frames = audio.frames();
+    for processor in chain.iter_mut() {
+        processor.reset();
+        processor.process(&mut audio, 0, frames);
+        processor.reset();
+    }
+
+    let mut render = AudioBuffer::stereo(frames - latency);
+    render.copy_from(0, &audio, latency, frames - latency);
+    clip.with_render(render)
+}
+
+/// Returns a clip playing `clip`'s audio scaled so its sample peak sits at `peak_db` dBFS.
+/// Silent clips are left as they are.
+#[must_use]
+pub fn normalize_clip(clip: &Clip, peak_db: f32) -> Clip {
+    let mut audio = clip.audio();
+    let frames = audio.frames();
+    let peak = audio.peak(0, 0, frames).max(audio.peak(1, 0, frames));
+    if peak > 0.0 {
+        audio.apply_gain(db_to_gain(peak_db) / peak);
+    }
+    clip.with_render(audio)
+}
+
+/// Measures the loudness of a finished stereo render, e.g. to check it against a delivery spec
+#[must_use]
+pub fn measure_loudness(mix: &AudioBuffer, sample_rate: f64) -> LoudnessSummary {
+    let mut meter = LoudnessMeter::new(sample_rate);
+    meter.process(mix, 0, mix.frames());
+    meter.summary()
+}
+
+/// Writes `samples` as a 32-bit float WAV file with one WAV channel per buffer channel
+pub fn write_wav<P: AsRef<Path>>(
+    path: P,
+    samples: &AudioBuffer,
+    sample_rate: u32,
+) -> Result<(), EngineError> {
+    let path = path.as_ref();
+    #[cfg(feature = "rt-audit")]
+    crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+    let export_error = |source| EngineError::Export {
+        path: path.to_path_buf(),
+        source,
+    };
+    let channels =
+        u16::try_from(samples.channels()).map_err(|_| export_error(hound::Error::Unsupported))?;
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    write_samples(path, spec, samples, |sample| sample).map_err(export_error)
+}
+
+/// Writes `samples` as a `bits`-bit integer WAV file, TPDF dithered with noise seeded by
+/// `seed`, so the same render always writes the same file
+pub fn write_wav_dithered<P: AsRef<Path>>(
+    path: P,
+    samples: &AudioBuffer,
+    sample_rate: u32,
+    bits: u16,
+    seed: u64,
+) -> Result<(), EngineError> {
+    let path = path.as_ref();
+    #[cfg(feature = "rt-audit")]
+    crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+    let export_error = |source| EngineError::Export {
+        path: path.to_path_buf(),
+        source,
+    };
+    let channels =
+        u16::try_from(samples.channels()).map_err(|_| export_error(hound::Error::Unsupported))?;
+    if !(8..=32).contains(&bits) {
+        return Err(export_error(hound::Error::Unsupported));
+    }
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: bits,
+        sample_format: SampleFormat::Int,
+    };
+
+    let mut dithered = samples.clone();
+    Dither::new(u32::from(bits), seed).process(&mut dithered, 0, samples.frames());
+    let full_scale = (1u64 << (bits - 1)) as f64;
+    write_samples(path, spec, &dithered, |sample| {
+        (f64::from(sample) * full_scale)
+            .round()
+            .clamp(-full_scale, full_scale - 1.0) as i32
+    })
+    .map_err(export_error)
+}
+
+/// Writes every frame of `samples`, interleaved and converted with `convert`
+fn write_samples<S: hound::Sample>(
+    path: &Path,
+    spec: WavSpec,
+    samples: &AudioBuffer,
+    convert: impl Fn(f32) -> S,
+) -> Result<(), hound::Error> {
+    let mut writer = WavWriter::create(path, spec)?;
+    for frame in 0..samples.frames() {
+        for channel in 0..samples.channels() {
+            writer.write_sample(convert(samples.channel(channel)[frame]))?;
+        }
+    }
+    writer.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use rtrb::RingBuffer;
+    use transport::{clock::TempoClock, resolution::TickResolution};
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        dsp::gain::Gain,
+        mixer::Channel,
+        scheduler::command::MarkerChange,
+        track::{constant::ConstantTrack, wav::WavTrack},
+    };
+
+    /// Holds the input's peak and lets it fall slowly, like a reverb tail
+    struct Decay(f32);
+
+    impl Processor for Decay {
+        fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+            let (left, right) = buffer.stereo_mut();
+            for (left, right) in left[start..start + len]
+                .iter_mut()
+                .zip(&mut right[start..start + len])
+            {
+                self.0 = (self.0 * 0.99).max(left.abs());
+                (*left, *right) = (self.0, self.0);
+            }
+        }
+    }
+
+    /// Delays the signal by one frame and reports it
+    struct OneFrameLate([f32; 2]);
+
+    impl Processor for OneFrameLate {
+        fn process(&mut self, buffer: &mut AudioBuffer, start: usize, len: usize) {
+            for (channel, previous) in self.0.iter_mut().enumerate() {
+                for sample in &mut buffer.channel_mut(channel)[start..start + len] {
+                    *previous = std::mem::replace(sample, *previous);
+                }
+            }
+        }
+
+        fn latency(&self) -> usize {
+            1
+        }
+
+        fn reset(&mut self) {
+            self.0 = [0.0; 2];
+        }
+    }
+
+    fn ramp(frames: usize) -> WavTrack {
+        let samples: Vec<_> = (0..frames).map(|i| (i as f32, i as f32)).collect();
+        WavTrack::from_buffer(AudioBuffer::from_frames(&samples))
+    }
+
+    fn create_scheduler() -> Scheduler {
+        let (_, consumer) = RingBuffer::new(8);
+        Scheduler::new(
+            consumer,
+            TempoClock::new(120.0, 44100.0, TickResolution::Sixteenth),
+        )
+    }
+
+    #[test]
+    fn test_render_stops_when_tracks_finish() {
+        let mut scheduler = create_scheduler();
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(WavTrack::from_buffer(AudioBuffer::from_frames(
+                &[(0.5, 0.5); 100],
+            ))),
+            start_frame: 0,
+        });
+
+        let output = render_until_idle(&mut scheduler, 32, 10_000);
+
+        // rendering stops at the end of the block the track finished in
+        assert_eq!(output.frames(), 128);
+        assert_eq!(output.frame(99), (0.5, 0.5));
+        assert_eq!(output.frame(100), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_render_is_capped_at_max_frames() {
+        let mut scheduler = create_scheduler();
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ConstantTrack::new(0.1, 0.1)),
+            start_frame: 0,
+        });
+
+        let output = render_until_idle(&mut scheduler, 64, 100);
+        assert_eq!(output.frames(), 100);
+    }
+
+    #[test]
+    fn test_render_range_is_frame_accurate() {
+        let mut scheduler = create_scheduler();
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ramp(1000)),
+            start_frame: 0,
+        });
+
+        let range = BounceRange::Frames {
+            start: 110,
+            end: 160,
+        };
+        let output = render_range(&mut scheduler, &range, 32, None).unwrap();
+
+        assert_eq!(output.frames(), 50);
+        assert_eq!(output.frame(0), (110.0, 110.0));
+        assert_eq!(output.frame(49), (159.0, 159.0));
+    }
+
+    #[test]
+    fn test_render_range_between_markers() {
+        let mut scheduler = create_scheduler();
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ramp(1000)),
+            start_frame: 0,
+        });
+        for (name, tick) in [("in", 1), ("out", 3)] {
+            scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddMarker {
+                name: name.into(),
+                tick,
+            }));
+        }
+
+        let range = BounceRange::Markers {
+            start: "in".into(),
+            end: "out".into(),
+        };
+        let output = render_range(&mut scheduler, &range, 64, None).unwrap();
+        // 183.75 frames per tick at 120 bpm and 44.1 kHz
+        assert_eq!(output.frames(), 367);
+        assert_eq!(output.frame(0), (184.0, 184.0));
+
+        let missing = BounceRange::Region("chorus".into());
+        assert!(matches!(
+            render_range(&mut scheduler, &missing, 64, None),
+            Err(SchedulingError::UnknownRange(_))
+        ));
+        assert!(BounceRange::Loop.resolve(&scheduler).is_err());
+    }
+
+    #[test]
+    fn test_tail_rings_out_after_the_range() {
+        let mut scheduler = create_scheduler();
+        let mut channel = Channel::new(Box::new(ConstantTrack::new(0.5, 0.5)));
+        channel.add_insert(Box::new(Decay(0.0)));
+        scheduler.process_command(SchedulerCommand::ScheduleChannel {
+            channel: Box::new(channel),
+            start_frame: 0,
+        });
+
+        let tail = TailSettings {
+            threshold_db: -40.0,
+            max_seconds: 1.0,
+        };
+        let range = BounceRange::Frames { start: 0, end: 100 };
+        let output = render_range(&mut scheduler, &range, 64, Some(tail)).unwrap();
+
+        // 0.5 falls by 1% a frame, reaching 0.01 after about 390 frames
+        assert!((480..500).contains(&output.frames()), "{}", output.frames());
+        assert!(output.frame(output.frames() - 1).0 >= 0.01);
+    }
+
+    #[test]
+    fn test_process_clip_compensates_latency_and_can_be_undone() {
+        let source = Arc::new(AudioBuffer::from_frames(&[(0.5, 0.25); 10]));
+        let clip = Clip::new(100, source);
+        let mut chain: Vec<Box<dyn Processor>> = vec![
+            Box::new(OneFrameLate([0.0; 2])),
+            Box::new(Gain::new(-6.0206)),
+        ];
+
+        let processed = process_clip(&clip, &mut chain, 2);
+
+        assert_eq!(processed.start, 100);
+        assert_eq!(processed.length(), 12);
+        assert!((processed.source().frame(0).0 - 0.25).abs() < 1e-4);
+        assert_eq!(processed.source().frame(11), (0.0, 0.0));
+        let original = processed.unprocessed().unwrap();
+        assert_eq!(original.source().frame(0), (0.5, 0.25));
+        assert!(original.unprocessed().is_none());
+    }
+
+    #[test]
+    fn test_normalize_clip() {
+        let source = Arc::new(AudioBuffer::from_frames(&[(0.25, -0.5), (0.1, 0.1)]));
+        let normalized = normalize_clip(&Clip::new(0, source), 0.0);
+        assert_eq!(normalized.source().frame(0), (0.5, -1.0));
+
+        let silence = Clip::new(0, Arc::new(AudioBuffer::stereo(4)));
+        assert_eq!(normalize_clip(&silence, 0.0).source().frame(0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_limit_keeps_render_aligned_and_under_ceiling() {
+        let mut mix = AudioBuffer::from_frames(&[(0.25, 0.25); 2000]);
+        mix.copy_from(
+            1000,
+            &AudioBuffer::from_frames(&[(2.0, 2.0); 1000]),
+            0,
+            1000,
+        );
+
+        limit(&mut mix, 44100.0, -1.0);
+
+        let summary = measure_loudness(&mix, 44100.0);
+        assert!(summary.true_peak <= -0.95, "{summary:?}");
+        // material well before the overload passes untouched, in place
+        assert_eq!(mix.frame(100), (0.25, 0.25));
+    }
+
+    #[test]
+    fn test_measure_loudness_of_render() {
+        let mut scheduler = create_scheduler();
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ConstantTrack::new(0.0, 0.0)),
+            start_frame: 0,
+        });
+
+        let output = render_until_idle(&mut scheduler, 512, 44100);
+        let summary = measure_loudness(&output, 44100.0);
+
+        assert_eq!(summary.integrated, f32::NEG_INFINITY);
+        assert_eq!(summary.true_peak, f32::NEG_INFINITY);
+    }
+}
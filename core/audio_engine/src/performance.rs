@@ -0,0 +1,323 @@
+//! Performance capture: clip launches played live, turned into arrangement clips, so a jam in
+//! the scene launcher becomes an editable timeline, see [`PerformanceCapture`].
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::AudioBuffer,
+    scene::{ClipLaunch, Scene},
+    scheduler::event::SchedulerEvent,
+    track::timeline::{Clip, FadeCurve, TimelineTrack},
+};
+
+/// One stretch of a clip playing in a captured performance, in timeline frames
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedClip {
+    pub lane: String,
+    pub clip: String,
+    pub start: u64,
+    /// First silent frame
+    pub end: u64,
+}
+
+/// Where a looping clip is faded when it's arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LoopFades {
+    /// In at its first start and out at its last end, the passes in between join seamlessly
+    #[default]
+    Edges,
+    /// In and out on every pass, for loops that don't join up without a click
+    PerPass,
+}
+
+/// The audio a launched clip plays, to arrange a captured performance with
+#[derive(Debug, Clone)]
+pub struct ClipAudio {
+    pub audio: Arc<AudioBuffer>,
+    /// Starts over at its end while launched, rather than playing once
+    pub looping: bool,
+    /// Where a looping clip is faded, ignored for clips playing once
+    pub loop_fades: LoopFades,
+}
+
+impl ClipAudio {
+    /// `audio` played once
+    #[must_use]
+    pub const fn once(audio: Arc<AudioBuffer>) -> Self {
+        Self {
+            audio,
+            looping: false,
+            loop_fades: LoopFades::Edges,
+        }
+    }
+
+    /// `audio` looped while launched, faded at the edges of the whole performance
+    #[must_use]
+    pub const fn looped(audio: Arc<AudioBuffer>) -> Self {
+        Self {
+            audio,
+            looping: true,
+            loop_fades: LoopFades::Edges,
+        }
+    }
+}
+
+/// Records which clips played when, from the scheduler's
+/// [`SchedulerEvent::ClipLaunched`] and [`SchedulerEvent::ClipStopped`] events, to the frame.
+///
+/// Runs off the audio thread: hand it every event while capturing, then
+/// [`finish`](Self::finish) and [`arrange`] each lane. The events come from a ring of their
+/// own that holds launches back rather than drop them, see
+/// [`Scheduler::set_launch_producer`](crate::scheduler::Scheduler::set_launch_producer).
+#[derive(Debug, Clone, Default)]
+pub struct PerformanceCapture {
+    /// Clips playing, as captured clips still open at the end
+    playing: Vec<CapturedClip>,
+    captured: Vec<CapturedClip>,
+    /// `(frame, scene)` of every scene launched, by its position in the launcher
+    scenes: Vec<(u64, usize)>,
+}
+
+impl PerformanceCapture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes in a scheduler event, anything but launches and stops is ignored. Clips are
+    /// looked up in `scenes`, the launcher's scenes in the order they were added.
+    pub fn record(&mut self, event: &SchedulerEvent, scenes: &[Scene]) {
+        let clip = |scene: usize, launch: usize| match scenes.get(scene)?.launches.get(launch)? {
+            ClipLaunch {
+                lane,
+                clip: Some(clip),
+            } => Some((lane, clip)),
+            ClipLaunch { clip: None, .. } => None,
+        };
+        match *event {
+            SchedulerEvent::ClipLaunched {
+                scene,
+                launch,
+                frame,
+            } => {
+                let Some((lane, clip)) = clip(scene, launch) else {
+                    return;
+                };
+                // a relaunch restarts the clip
+                self.close(lane, clip, frame);
+                self.playing.push(CapturedClip {
+                    lane: lane.clone(),
+                    clip: clip.clone(),
+                    start: frame,
+                    end: u64::MAX,
+                });
+            }
+            SchedulerEvent::ClipStopped {
+                scene,
+                launch,
+                frame,
+            } => {
+                if let Some((lane, clip)) = clip(scene, launch) {
+                    self.close(lane, clip, frame);
+                }
+            }
+            SchedulerEvent::SceneLaunched { scene, frame } => self.scenes.push((frame, scene)),
+            _ => {}
+        }
+    }
+
+    /// Scenes launched so far as `(frame, scene)`, e.g. to place markers, see
+    /// [`SchedulerEvent::SceneLaunched`]
+    #[must_use]
+    pub fn scenes(&self) -> &[(u64, usize)] {
+        &self.scenes
+    }
+
+    /// Ends the capture at timeline `frame`, clips still playing are cut there. Returns the
+    /// captured clips in the order they started.
+    #[must_use]
+    pub fn finish(mut self, frame: u64) -> Vec<CapturedClip> {
+        for mut open in self.playing.drain(..) {
+            open.end = frame.max(open.start);
+            self.captured.push(open);
+        }
+        self.captured.sort_by_key(|clip| clip.start);
+        self.captured
+    }
+
+    /// Closes the stretch `clip` on `lane` plays at `frame`
+    fn close(&mut self, lane: &str, clip: &str, frame: u64) {
+        let Some(index) = self
+            .playing
+            .iter()
+            .position(|open| open.lane == lane && open.clip == clip)
+        else {
+            return;
+        };
+        let mut closed = self.playing.swap_remove(index);
+        closed.end = frame.max(closed.start);
+        self.captured.push(closed);
+    }
+}
+
+/// A timeline track `id` playing what `captured` played on `lane`.
+///
+/// The audio of each clip is looked up by id in `audio`. Looping clips repeat for as long as
+/// they played, others play once. Clips are faded in and out over `crossfade` frames, the
+/// launcher's crossfade, so the bounce sounds like the performance: where they start and
+/// stop, and on every pass of loops with [`LoopFades::PerPass`].
+///
+/// Clips `audio` doesn't know are left out.
+pub fn arrange(
+    id: &str,
+    lane: &str,
+    captured: &[CapturedClip],
+    crossfade: usize,
+    audio: impl Fn(&str) -> Option<ClipAudio>,
+) -> TimelineTrack {
+    let mut track = TimelineTrack::new(id);
+    for played in captured.iter().filter(|played| played.lane == lane) {
+        let Some(ClipAudio {
+            audio,
+            looping,
+            loop_fades,
+        }) = audio(&played.clip)
+        else {
+            continue;
+        };
+        let length = audio.frames();
+        if length == 0 {
+            continue;
+        }
+        let start = played.start as usize;
+        let end = played.end as usize;
+        let mut position = start;
+        while position < end {
+            let mut clip = Clip::excerpt(position, Arc::clone(&audio), 0, end - position);
+            let per_pass = loop_fades == LoopFades::PerPass;
+            let fade_in = if per_pass || position == start {
+                crossfade
+            } else {
+                0
+            };
+            let fade_out = if per_pass || position + length >= end {
+                crossfade
+            } else {
+                0
+            };
+            clip.set_fades(fade_in, fade_out);
+            clip.set_fade_curves(FadeCurve::EqualPower, FadeCurve::EqualPower);
+            track.add_clip(clip);
+            if !looping {
+                break;
+            }
+            position += length;
+        }
+    }
+    track
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scene 0 launches the beat on the drums, scene 1 the fill
+    fn scenes() -> Vec<Scene> {
+        ["beat", "fill"]
+            .into_iter()
+            .map(|clip| Scene {
+                name: clip.to_owned(),
+                note: None,
+                launches: vec![ClipLaunch {
+                    lane: "drums".into(),
+                    clip: Some(clip.into()),
+                }],
+            })
+            .collect()
+    }
+
+    fn launched(scene: usize, frame: u64) -> SchedulerEvent {
+        SchedulerEvent::ClipLaunched {
+            scene,
+            launch: 0,
+            frame,
+        }
+    }
+
+    #[test]
+    fn test_captured_launches_become_arrangement_clips() {
+        let scenes = scenes();
+        let mut capture = PerformanceCapture::new();
+        capture.record(&launched(0, 100), &scenes);
+        capture.record(
+            &SchedulerEvent::SceneLaunched {
+                scene: 0,
+                frame: 100,
+            },
+            &scenes,
+        );
+        capture.record(
+            &SchedulerEvent::ClipStopped {
+                scene: 0,
+                launch: 0,
+                frame: 350,
+            },
+            &scenes,
+        );
+        capture.record(&launched(1, 350), &scenes);
+        capture.record(&SchedulerEvent::BarStarted { bar: 2 }, &scenes);
+        assert_eq!(capture.scenes(), [(100, 0)]);
+
+        let captured = capture.finish(400);
+        assert_eq!(
+            captured
+                .iter()
+                .map(|clip| (clip.clip.as_str(), clip.start, clip.end))
+                .collect::<Vec<_>>(),
+            [("beat", 100, 350), ("fill", 350, 400)]
+        );
+
+        let beat = Arc::new(AudioBuffer::from_frames(&[(1.0, 1.0); 100]));
+        let fill = Arc::new(AudioBuffer::from_frames(&[(0.5, 0.5); 100]));
+        let track = arrange("drums", "drums", &captured, 0, |clip| match clip {
+            "beat" => Some(ClipAudio::looped(Arc::clone(&beat))),
+            "fill" => Some(ClipAudio::once(Arc::clone(&fill))),
+            _ => None,
+        });
+        // the looping beat repeats until it's stopped, the fill plays once up to the end
+        assert_eq!(
+            track
+                .clips()
+                .iter()
+                .map(|clip| (clip.start, clip.start + clip.length()))
+                .collect::<Vec<_>>(),
+            [(100, 200), (200, 300), (300, 350), (350, 400)]
+        );
+    }
+
+    #[test]
+    fn test_loops_fade_at_their_edges_or_every_pass() {
+        let captured = [CapturedClip {
+            lane: "pad".into(),
+            clip: "swell".into(),
+            start: 0,
+            end: 250,
+        }];
+        let swell = Arc::new(AudioBuffer::from_frames(&[(1.0, 1.0); 100]));
+        let fades = |loop_fades| {
+            let audio = ClipAudio {
+                loop_fades,
+                ..ClipAudio::looped(Arc::clone(&swell))
+            };
+            arrange("pad", "pad", &captured, 10, |_| Some(audio.clone()))
+                .clips()
+                .iter()
+                .map(Clip::fades)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fades(LoopFades::Edges), [(10, 0), (0, 0), (0, 10)]);
+        assert_eq!(fades(LoopFades::PerPass), [(10, 10), (10, 10), (10, 10)]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Named, reusable channel strip, effect chain and instrument settings, stored as TOML.
+//! [`PresetLibrary`](crate::preset_library::PresetLibrary) finds and organizes them.
+//!
+//! ```toml
+//! name = "Lead vocal"
+//! gain = 0.8
+//!
+//! [[effect]]
+//! type = "gain"
+//! gain_db = -3.0
+//!
+//! [[effect]]
+//! type = "limiter"
+//! ceiling_dbtp = -1.0
+//! release_seconds = 0.05
+//!
+//! [[send]]
+//! bus = "reverb"
+//! level = 0.3
+//! ```
+use std::path::Path;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    dsp::{EffectSettings, Processor},
+    error::PresetError,
+    mixer::{AuxSend, Channel},
+    scheduler::command::{ParameterChange, SchedulerCommand},
+};
+
+/// What a preset sets up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PresetKind {
+    Channel,
+    EffectChain,
+    Instrument,
+    /// An SFZ instrument for the [`Sampler`](crate::midi::sampler::Sampler), in its own
+    /// format
+    Sfz,
+}
+
+/// The preset types stored as TOML
+pub trait Preset: Serialize + DeserializeOwned {
+    const KIND: PresetKind;
+
+    fn name(&self) -> &str;
+
+    fn tags(&self) -> &[String];
+}
+
+/// An insert chain, e.g. a vocal chain to put on any track, bus or clip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectChainPreset {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, rename = "effect")]
+    pub effects: Vec<EffectSettings>,
+}
+
+impl Preset for EffectChainPreset {
+    const KIND: PresetKind = PresetKind::EffectChain;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+impl EffectChainPreset {
+    /// Stores the settings of `chain`, processors that can't describe their settings are left
+    /// out
+    #[must_use]
+    pub fn capture(name: &str, chain: &[Box<dyn Processor>]) -> Self {
+        Self {
+            name: name.to_owned(),
+            tags: Vec::new(),
+            effects: chain
+                .iter()
+                .filter_map(|effect| effect.settings())
+                .collect(),
+        }
+    }
+
+    /// Fresh processors with the stored settings, in chain order
+    #[must_use]
+    pub fn build(&self, sample_rate: f64) -> Vec<Box<dyn Processor>> {
+        self.effects
+            .iter()
+            .map(|effect| effect.build(sample_rate))
+            .collect()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+        load(path.as_ref())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PresetError> {
+        save(self, path.as_ref())
+    }
+}
+
+/// A whole channel strip: fader, insert chain and sends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelPreset {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    #[serde(default)]
+    pub pan: f32,
+    #[serde(default, rename = "effect")]
+    pub effects: Vec<EffectSettings>,
+    #[serde(default, rename = "send")]
+    pub sends: Vec<AuxSend>,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+impl ChannelPreset {
+    /// Stores `channel`'s strip, inserts that can't describe their settings are left out
+    #[must_use]
+    pub fn capture(name: &str, channel: &Channel) -> Self {
+        Self {
+            name: name.to_owned(),
+            tags: Vec::new(),
+            gain: channel.gain(),
+            pan: channel.pan(),
+            effects: EffectChainPreset::capture(name, channel.inserts()).effects,
+            sends: channel.sends().to_vec(),
+        }
+    }
+
+    /// Sets `channel` up like the stored strip, replacing its inserts and sends. Returns the
+    /// old inserts so they can be dropped off the audio thread.
+    pub fn apply(&self, channel: &mut Channel, sample_rate: f64) -> Vec<Box<dyn Processor>> {
+        channel.set_gain(self.gain);
+        channel.set_pan(self.pan);
+        channel.replace_sends(self.sends.clone());
+        channel.replace_inserts(
+            self.effects
+                .iter()
+                .map(|effect| effect.build(sample_rate))
+                .collect(),
+        )
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+        load(path.as_ref())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PresetError> {
+        save(self, path.as_ref())
+    }
+}
+
+impl Preset for ChannelPreset {
+    const KIND: PresetKind = PresetKind::Channel;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+/// A value of an instrument parameter, see [`ParameterChange::Instrument`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentParameter {
+    pub id: u32,
+    pub value: f32,
+}
+
+/// Parameter values of an instrument played by a
+/// [`MidiTrack`](crate::track::midi::MidiTrack), e.g. a synth patch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentPreset {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Which instrument the values are for, as the host names it
+    pub instrument: String,
+    #[serde(default, rename = "parameter")]
+    pub parameters: Vec<InstrumentParameter>,
+}
+
+impl InstrumentPreset {
+    /// Commands setting the instrument of track `target_id` (a track path, see
+    /// [`SchedulerCommand::ParamChange`]) to the stored values
+    pub fn commands<'a>(
+        &'a self,
+        target_id: &'a str,
+    ) -> impl Iterator<Item = SchedulerCommand> + 'a {
+        self.parameters
+            .iter()
+            .map(
+                |&InstrumentParameter { id, value }| SchedulerCommand::ParamChange {
+                    target_id: target_id.to_owned(),
+                    change: ParameterChange::Instrument { id, value },
+                },
+            )
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+        load(path.as_ref())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PresetError> {
+        save(self, path.as_ref())
+    }
+}
+
+impl Preset for InstrumentPreset {
+    const KIND: PresetKind = PresetKind::Instrument;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+pub(crate) fn load<T: DeserializeOwned>(path: &Path) -> Result<T, PresetError> {
+    #[cfg(feature = "rt-audit")]
+    crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+    let source = std::fs::read_to_string(path).map_err(|source| PresetError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(toml::from_str(&source)?)
+}
+
+pub(crate) fn save<T: Serialize>(preset: &T, path: &Path) -> Result<(), PresetError> {
+    let source = toml::to_string_pretty(preset)?;
+    #[cfg(feature = "rt-audit")]
+    crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+    std::fs::write(path, source).map_err(|source| PresetError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dsp::{gain::Gain, limiter::TruePeakLimiter},
+        track::constant::ConstantTrack,
+    };
+
+    #[test]
+    fn test_channel_strip_roundtrips_through_a_file() {
+        let mut vocal = Channel::new(Box::new(ConstantTrack::new(0.0, 0.0)));
+        vocal.set_gain(0.8);
+        vocal.set_pan(-0.25);
+        vocal.add_insert(Box::new(Gain::new(-6.0)));
+        vocal.add_insert(Box::new(TruePeakLimiter::new(48000.0, -1.0)));
+        vocal.set_send("reverb".into(), 0.3, false);
+
+        let preset = ChannelPreset::capture("Lead vocal", &vocal);
+        let path = std::env::temp_dir().join("freqform-preset-test.toml");
+        preset.save(&path).unwrap();
+        let loaded = ChannelPreset::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, preset);
+
+        let mut other = Channel::new(Box::new(ConstantTrack::new(0.0, 0.0)));
+        other.set_send("delay".into(), 1.0, true);
+        other.add_insert(Box::new(Gain::new(3.0)));
+        let replaced = loaded.apply(&mut other, 48000.0);
+
+        assert_eq!(replaced.len(), 1);
+        assert_eq!(other.gain(), 0.8);
+        assert_eq!(other.sends(), vocal.sends());
+        assert_eq!(
+            EffectChainPreset::capture("", other.inserts()).effects,
+            preset.effects
+        );
+    }
+
+    #[test]
+    fn test_minimal_preset_uses_defaults() {
+        let preset: ChannelPreset = toml::from_str(
+            r#"
+            name = "Clean"
+
+            [[effect]]
+            type = "gain"
+            gain_db = -3.0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(preset.gain, 1.0);
+        assert!(preset.sends.is_empty());
+        assert_eq!(preset.effects, vec![EffectSettings::Gain { gain_db: -3.0 }]);
+    }
+}
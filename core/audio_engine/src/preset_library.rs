@@ -0,0 +1,443 @@
+//! The preset browser: the presets found in a set of directories, with their kind, folder
+//! and tags, see [`PresetLibrary`].
+//!
+//! Channel, effect chain and instrument presets are the TOML files of [`crate::preset`],
+//! tagged with their `tags` field. SFZ instruments are listed as they are and can't be
+//! tagged. Subfolders of a directory become categories:
+//!
+//! ```text
+//! presets/
+//!   Vocal chain.toml        no category
+//!   drums/
+//!     Kick bus.toml         category "drums"
+//!     Acoustic kit.sfz      category "drums", load with Sampler::from_sfz
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::PresetError,
+    preset::{self, Preset, PresetKind},
+};
+
+/// Characters kept when a preset name becomes a file name, the rest turn into `_`
+const FILE_NAME_SAFE: &[char] = &[' ', '-', '_', '.', '(', ')'];
+
+/// A preset found by [`PresetLibrary::scan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetEntry {
+    pub path: PathBuf,
+    /// The preset's own name, the file name for SFZ
+    pub name: String,
+    pub kind: PresetKind,
+    /// Folder below the library directory, `/`-separated, `None` at the top
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl PresetEntry {
+    /// Whether `text` is part of the name, category or a tag, ignoring case
+    #[must_use]
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.name.to_lowercase().contains(&text)
+            || self
+                .category
+                .as_ref()
+                .is_some_and(|category| category.to_lowercase().contains(&text))
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(&text))
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|existing| existing == tag)
+    }
+}
+
+/// Presets for instruments and effects, found in a set of directories, for hosts to offer
+/// a single browser over.
+///
+/// Saving goes to the first directory. Runs off the audio thread: presets are built or
+/// applied with the types in [`crate::preset`], or turned into commands.
+#[derive(Debug, Clone, Default)]
+pub struct PresetLibrary {
+    dirs: Vec<PathBuf>,
+    entries: Vec<PresetEntry>,
+}
+
+impl PresetLibrary {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks for presets in `dir` too, from the next [`scan`](Self::scan)
+    pub fn add_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        let dir = dir.into();
+        if !self.dirs.contains(&dir) {
+            self.dirs.push(dir);
+        }
+    }
+
+    #[must_use]
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Lists the presets in the directories and their subfolders again. Directories that
+    /// don't exist yet are skipped, files that aren't presets or don't parse are left out.
+    ///
+    /// # Errors
+    /// If a directory can't be read.
+    pub fn scan(&mut self) -> Result<(), PresetError> {
+        self.entries.clear();
+        for dir in &self.dirs {
+            if dir.is_dir() {
+                scan_dir(dir, dir, &mut self.entries)?;
+            }
+        }
+        self.entries
+            .sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+        Ok(())
+    }
+
+    /// Every preset found, by category and name
+    #[must_use]
+    pub fn entries(&self) -> &[PresetEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn get(&self, path: &Path) -> Option<&PresetEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// Presets of `kind` (any kind if `None`) matching `text` (see [`PresetEntry::matches`])
+    /// and carrying all of `tags`
+    pub fn search<'a>(
+        &'a self,
+        kind: Option<PresetKind>,
+        text: &'a str,
+        tags: &'a [&str],
+    ) -> impl Iterator<Item = &'a PresetEntry> {
+        self.entries.iter().filter(move |entry| {
+            kind.is_none_or(|kind| entry.kind == kind)
+                && (text.is_empty() || entry.matches(text))
+                && tags.iter().all(|tag| entry.has_tag(tag))
+        })
+    }
+
+    /// Every tag in use, sorted
+    #[must_use]
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    /// Every category in use, sorted
+    #[must_use]
+    pub fn categories(&self) -> Vec<&str> {
+        let mut categories: Vec<&str> = self
+            .entries
+            .iter()
+            .filter_map(|entry| entry.category.as_deref())
+            .collect();
+        categories.dedup();
+        categories
+    }
+
+    /// Reads the preset at `path`
+    ///
+    /// # Errors
+    /// If the file can't be read or isn't a preset of type `P`.
+    pub fn load<P: Preset>(&self, path: &Path) -> Result<P, PresetError> {
+        preset::load(path)
+    }
+
+    /// Saves `preset` to the first directory, in the `category` subfolder if any, as a file
+    /// named after it. A preset of the same name there is replaced. Returns its path.
+    ///
+    /// # Errors
+    /// [`PresetError::NoLibraryDirectory`] without directories, or if the file can't be
+    /// written.
+    pub fn save<P: Preset>(
+        &mut self,
+        preset: &P,
+        category: Option<&str>,
+    ) -> Result<PathBuf, PresetError> {
+        let dir = self.dirs.first().ok_or(PresetError::NoLibraryDirectory)?;
+        let mut folder = dir.clone();
+        folder.extend(category.iter().flat_map(|category| category.split('/')));
+        std::fs::create_dir_all(&folder).map_err(|source| PresetError::Io {
+            path: folder.clone(),
+            source,
+        })?;
+        let path = folder.join(format!("{}.toml", file_name(preset.name())));
+        preset::save(preset, &path)?;
+
+        let entry = PresetEntry {
+            path: path.clone(),
+            name: preset.name().to_owned(),
+            kind: P::KIND,
+            category: category
+                .filter(|category| !category.is_empty())
+                .map(str::to_owned),
+            tags: preset.tags().to_vec(),
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.path == path)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        Ok(path)
+    }
+
+    /// Replaces the tags of the preset at `path`, in its file
+    ///
+    /// # Errors
+    /// [`PresetError::Untaggable`] for SFZ instruments, or if the file can't be rewritten.
+    pub fn set_tags(&mut self, path: &Path, tags: Vec<String>) -> Result<(), PresetError> {
+        if kind_of_file(path) == Some(PresetKind::Sfz) {
+            return Err(PresetError::Untaggable(path.to_path_buf()));
+        }
+        let mut table: toml::Table = preset::load(path)?;
+        if tags.is_empty() {
+            table.remove("tags");
+        } else {
+            table.insert(
+                "tags".to_owned(),
+                toml::Value::Array(tags.iter().cloned().map(toml::Value::String).collect()),
+            );
+        }
+        preset::save(&table, path)?;
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.path == path) {
+            entry.tags = tags;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the presets below `dir` of the library directory `root` to `entries`
+fn scan_dir(root: &Path, dir: &Path, entries: &mut Vec<PresetEntry>) -> Result<(), PresetError> {
+    let io = |source| PresetError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    for item in std::fs::read_dir(dir).map_err(io)? {
+        let item = item.map_err(io)?;
+        let path = item.path();
+        if item.file_type().map_err(io)?.is_dir() {
+            scan_dir(root, &path, entries)?;
+            continue;
+        }
+        match read_entry(root, &path) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(target: "freqform::preset", %error, "Preset left out of the library");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The preset at `path`, `None` if the file isn't one
+fn read_entry(root: &Path, path: &Path) -> Result<Option<PresetEntry>, PresetError> {
+    let category = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map(|relative| {
+            relative
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        });
+    let stem = || {
+        path.file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    };
+
+    let entry = match kind_of_file(path) {
+        Some(PresetKind::Sfz) => PresetEntry {
+            path: path.to_path_buf(),
+            name: stem(),
+            kind: PresetKind::Sfz,
+            category,
+            tags: Vec::new(),
+        },
+        Some(_) => {
+            let table: toml::Table = preset::load(path)?;
+            let Some(name) = table.get("name").and_then(toml::Value::as_str) else {
+                return Ok(None);
+            };
+            let tags = table
+                .get("tags")
+                .and_then(toml::Value::as_array)
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(toml::Value::as_str)
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default();
+            PresetEntry {
+                path: path.to_path_buf(),
+                name: name.to_owned(),
+                kind: kind_of_table(&table),
+                category,
+                tags,
+            }
+        }
+        None => return Ok(None),
+    };
+    Ok(Some(entry))
+}
+
+/// Kind of preset a file holds going by its extension, any of the TOML ones for `.toml`
+fn kind_of_file(path: &Path) -> Option<PresetKind> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "sfz" => Some(PresetKind::Sfz),
+        "toml" => Some(PresetKind::EffectChain),
+        _ => None,
+    }
+}
+
+/// Kind of a TOML preset going by its fields
+fn kind_of_table(table: &toml::Table) -> PresetKind {
+    if table.contains_key("instrument") {
+        PresetKind::Instrument
+    } else if ["gain", "pan", "send"]
+        .iter()
+        .any(|key| table.contains_key(*key))
+    {
+        PresetKind::Channel
+    } else {
+        PresetKind::EffectChain
+    }
+}
+
+/// `name` with the characters file systems trip over replaced
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || FILE_NAME_SAFE.contains(&c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() || name.starts_with('.') {
+        format!("preset{name}")
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dsp::EffectSettings,
+        preset::{ChannelPreset, EffectChainPreset, InstrumentParameter, InstrumentPreset},
+    };
+
+    #[test]
+    fn test_library_scans_tags_and_saves_presets() {
+        let dir = std::env::temp_dir().join("freqform-preset-library-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("drums")).unwrap();
+        std::fs::write(dir.join("drums/Kit.sfz"), "<region> sample=kick.wav").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+        std::fs::write(dir.join("broken.toml"), "name = ").unwrap();
+
+        let mut library = PresetLibrary::new();
+        library.add_dir(&dir);
+        library.add_dir(dir.join("missing"));
+        let chain = EffectChainPreset {
+            name: "Vocal/chain".into(),
+            tags: vec!["vocal".into()],
+            effects: vec![EffectSettings::Gain { gain_db: -3.0 }],
+        };
+        let chain_path = library.save(&chain, None).unwrap();
+        assert_eq!(chain_path, dir.join("Vocal_chain.toml"));
+        let pad = InstrumentPreset {
+            name: "Warm pad".into(),
+            tags: Vec::new(),
+            instrument: "synth".into(),
+            parameters: vec![InstrumentParameter { id: 3, value: 0.5 }],
+        };
+        library.save(&pad, Some("keys")).unwrap();
+        let strip = ChannelPreset {
+            name: "Kick bus".into(),
+            tags: Vec::new(),
+            gain: 0.5,
+            pan: 0.0,
+            effects: Vec::new(),
+            sends: Vec::new(),
+        };
+        library.save(&strip, Some("drums")).unwrap();
+
+        library.scan().unwrap();
+        let found: Vec<_> = library
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.kind, entry.category.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("Vocal/chain", PresetKind::EffectChain, None),
+                ("Kick bus", PresetKind::Channel, Some("drums")),
+                ("Kit", PresetKind::Sfz, Some("drums")),
+                ("Warm pad", PresetKind::Instrument, Some("keys")),
+            ]
+        );
+        assert_eq!(library.categories(), ["drums", "keys"]);
+
+        let kick = dir.join("drums/Kick bus.toml");
+        library
+            .set_tags(&kick, vec!["drums".into(), "glue".into()])
+            .unwrap();
+        assert!(matches!(
+            library.set_tags(&dir.join("drums/Kit.sfz"), Vec::new()),
+            Err(PresetError::Untaggable(_))
+        ));
+        library.scan().unwrap();
+        assert_eq!(library.tags(), ["drums", "glue", "vocal"]);
+        let glued: Vec<_> = library
+            .search(Some(PresetKind::Channel), "KICK", &["glue"])
+            .map(|entry| entry.path.as_path())
+            .collect();
+        assert_eq!(glued, [kick.as_path()]);
+        assert_eq!(library.search(None, "drums", &[]).count(), 2);
+
+        let loaded: ChannelPreset = library.load(&kick).unwrap();
+        assert_eq!(loaded.tags, ["drums", "glue"]);
+        assert_eq!(loaded.gain, 0.5);
+        let loaded: InstrumentPreset = library.load(&dir.join("keys/Warm pad.toml")).unwrap();
+        assert!(matches!(
+            loaded.commands("keys").next(),
+            Some(crate::scheduler::command::SchedulerCommand::ParamChange { target_id, .. })
+                if target_id == "keys"
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{math::PanLaw, stretch::time_stretch},
+    error::{EngineError, ProjectError},
+    metadata::{Color, Metadata},
+    mixer::Channel,
+    record::RecordSettings,
+    rng::derive_seed,
+    track::{
+        Track,
+        builder::TrackBuilder,
+        timeline::{Clip, FadeCurve, TimelineTrack},
+        wav::WavTrack,
+    },
+};
+
+/// A project file (`.ffp`), stored as TOML.
+///
+/// # Example
+/// ```toml
+/// bpm = 120.0
+/// sample_rate = 44100
+/// seed = 1234
+///
+/// [[track]]
+/// id = "piano"
+/// file = "assets/wav/piano.wav"
+/// start = 1.0
+/// gain = 0.8
+/// pan = -0.2
+/// group = "keys"
+/// clip_group = "piano take 3"
+///
+/// [track.metadata]
+/// color = "#1e90ff"
+/// notes = "second take"
+/// tags = ["lead"]
+///
+/// [[group]]
+/// id = "keys"
+///
+/// [record]
+/// format = "int24"
+/// name_template = "{track}-take{take}"
+/// directory = "audio"
+///
+/// [defaults]
+/// fade_ms = 5.0
+/// fade_curve = "equal_power"
+/// crossfade_ms = 10.0
+/// pan_law = "equal_power"
+/// track_color = "#808080"
+/// ```
+///
+/// Tracks are kept in arrangement order, the order they are listed in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(default = "default_bpm")]
+    pub bpm: f64,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Seeds everything random (humanizing, random round robin, dither), so renders of the
+    /// project come out the same bit for bit, see [`Project::seed_for`]
+    #[serde(default)]
+    pub seed: u32,
+    #[serde(default, rename = "track")]
+    pub tracks: Vec<ProjectTrack>,
+    #[serde(default, rename = "group", skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<TrackGroup>,
+    /// Format, naming and location of recorded takes
+    #[serde(default, skip_serializing_if = "RecordSettings::is_default")]
+    pub record: RecordSettings,
+    /// What new clips, tracks and channels start from
+    #[serde(default, skip_serializing_if = "ProjectDefaults::is_default")]
+    pub defaults: ProjectDefaults,
+    /// Directory relative track files are resolved against
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTrack {
+    pub id: String,
+    /// WAV file, relative to the project file
+    pub file: PathBuf,
+    /// Start position in seconds
+    #[serde(default)]
+    pub start: f64,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    #[serde(default)]
+    pub pan: f32,
+    /// Nudges the track later in milliseconds, earlier when negative
+    #[serde(default)]
+    pub offset_ms: f64,
+    /// Folder group the track is in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Clip group the track's audio moves with, tracks sharing one keep their distance
+    /// through every arrangement edit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip_group: Option<String>,
+    /// BPM of the track's audio. Set, the audio is stretched to the project tempo and the
+    /// track moves with the grid when the tempo changes, see [`Project::set_tempo`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_tempo: Option<f64>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+/// A folder of tracks, folders can be nested
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackGroup {
+    pub id: String,
+    /// Enclosing folder, `None` at the top level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+/// A project's house style: the fades, pan law and color new clips, tracks and channels get.
+///
+/// Consulted by [`Project::new_clip`], [`Project::new_timeline_track`],
+/// [`Project::add_track`] and [`Project::build_channels`]. Out of the box they match the
+/// engine's own defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectDefaults {
+    /// Fade in and out of new clips, in milliseconds
+    pub fade_ms: f64,
+    pub fade_curve: FadeCurve,
+    /// Fades given to the clip edges edits create, so cuts don't click, in milliseconds.
+    /// 0 for none, see [`TimelineTrack::set_edit_fade`].
+    pub crossfade_ms: f64,
+    pub crossfade_curve: FadeCurve,
+    pub pan_law: PanLaw,
+    /// Color of new tracks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_color: Option<Color>,
+}
+
+impl Default for ProjectDefaults {
+    fn default() -> Self {
+        Self {
+            fade_ms: 0.0,
+            fade_curve: FadeCurve::Linear,
+            crossfade_ms: 0.0,
+            crossfade_curve: FadeCurve::EqualPower,
+            pan_law: PanLaw::Balance,
+            track_color: None,
+        }
+    }
+}
+
+impl ProjectDefaults {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn default_bpm() -> f64 {
+    120.0
+}
+
+fn default_sample_rate() -> u32 {
+    44100
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+impl Project {
+    #[must_use]
+    pub fn new(bpm: f64, sample_rate: u32) -> Self {
+        Self {
+            bpm,
+            sample_rate,
+            seed: 0,
+            tracks: Vec::new(),
+            groups: Vec::new(),
+            record: RecordSettings::default(),
+            defaults: ProjectDefaults::default(),
+            root: PathBuf::new(),
+        }
+    }
+
+    /// A clip of `source` at frame `start`, with the project's default fades
+    #[must_use]
+    pub fn new_clip(&self, start: usize, source: Arc<AudioBuffer>) -> Clip {
+        let mut clip = Clip::new(start, source);
+        let fade = self.ms_to_frames(self.defaults.fade_ms);
+        clip.set_fades(fade, fade);
+        let curve = self.defaults.fade_curve;
+        clip.set_fade_curves(curve, curve);
+        clip
+    }
+
+    /// An empty timeline track fading the edges edits create as the project's defaults say
+    #[must_use]
+    pub fn new_timeline_track(&self, id: &str) -> TimelineTrack {
+        let mut track = TimelineTrack::new(id);
+        track.set_edit_fade(self.ms_to_frames(self.defaults.crossfade_ms));
+        track.set_edit_fade_curve(self.defaults.crossfade_curve);
+        track
+    }
+
+    /// Appends a track playing `file` from the start, in the default track color, and
+    /// returns it for further changes
+    pub fn add_track(&mut self, id: &str, file: impl Into<PathBuf>) -> &mut ProjectTrack {
+        let track = ProjectTrack {
+            id: id.to_owned(),
+            file: file.into(),
+            start: 0.0,
+            gain: default_gain(),
+            pan: 0.0,
+            offset_ms: 0.0,
+            group: None,
+            clip_group: None,
+            follow_tempo: None,
+            metadata: Metadata {
+                color: self.defaults.track_color,
+                ..Metadata::default()
+            },
+        };
+        self.tracks.push(track);
+        self.tracks.last_mut().expect("a track was just added")
+    }
+
+    /// `ms` milliseconds in frames at the project's sample rate
+    fn ms_to_frames(&self, ms: f64) -> usize {
+        (ms.max(0.0) / 1000.0 * f64::from(self.sample_rate)).round() as usize
+    }
+
+    /// Seed for the random feature or track named `key`, e.g. a track id for its
+    /// [`Humanize`](crate::midi::humanize::Humanize) or sampler, or `"dither"`. Each key gets
+    /// its own stream, so adding one doesn't change what the others draw.
+    #[must_use]
+    pub fn seed_for(&self, key: &str) -> u64 {
+        derive_seed(u64::from(self.seed), key)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ProjectError> {
+        let path = path.as_ref();
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let source = std::fs::read_to_string(path).map_err(|source| ProjectError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut project = Self::from_toml(&source)?;
+        project.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(project)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, ProjectError> {
+        Ok(toml::from_str(source)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ProjectError> {
+        let path = path.as_ref();
+        let source = toml::to_string_pretty(self)?;
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        std::fs::write(path, source).map_err(|source| ProjectError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Where a track's `file` is, relative files are resolved against the project file's
+    /// directory
+    #[must_use]
+    pub fn media_path(&self, file: &Path) -> PathBuf {
+        self.root.join(file)
+    }
+
+    /// Where take `take` of `track` is recorded to, following the record settings
+    #[must_use]
+    pub fn take_path(&self, track: &str, take: u32) -> PathBuf {
+        self.root
+            .join(&self.record.directory)
+            .join(self.record.file_name(track, take))
+    }
+
+    /// Moves track `id` to `index` in the arrangement (clamped to the end),
+    /// `false` if there's no such track
+    pub fn move_track(&mut self, id: &str, index: usize) -> bool {
+        let Some(from) = self.tracks.iter().position(|track| track.id == id) else {
+            return false;
+        };
+        let track = self.tracks.remove(from);
+        self.tracks.insert(index.min(self.tracks.len()), track);
+        true
+    }
+
+    /// Moves track `id` to start at `start` seconds, and the other tracks in its clip group
+    /// by as much. `false` if there's no such track.
+    pub fn set_start(&mut self, id: &str, start: f64) -> bool {
+        let Some(track) = self.tracks.iter().find(|track| track.id == id) else {
+            return false;
+        };
+        let by = start - track.start;
+        let group = track.clip_group.clone();
+        for track in &mut self.tracks {
+            if track.id == id || group.is_some() && track.clip_group == group {
+                track.start += by;
+            }
+        }
+        true
+    }
+
+    /// Inserts `length` seconds at `at`, tracks starting from there on move later
+    pub fn insert_time(&mut self, at: f64, length: f64) {
+        self.move_starts(|start| if start >= at { start + length } else { start });
+    }
+
+    /// Cuts `start..end` seconds out of the arrangement, pulling later tracks earlier.
+    /// Tracks starting inside the range move to its start.
+    pub fn remove_time(&mut self, start: f64, end: f64) {
+        self.move_starts(|track_start| {
+            if track_start >= end {
+                track_start - (end - start)
+            } else if track_start > start {
+                start
+            } else {
+                track_start
+            }
+        });
+    }
+
+    /// Moves every track from its start to `to` of it. Tracks in a clip group all move
+    /// as far as the earliest of them, so they stay together.
+    fn move_starts(&mut self, to: impl Fn(f64) -> f64) {
+        let earliest = |group: &str| {
+            self.tracks
+                .iter()
+                .filter(|track| track.clip_group.as_deref() == Some(group))
+                .map(|track| track.start)
+                .fold(f64::INFINITY, f64::min)
+        };
+        let moves: Vec<f64> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                let start = track.clip_group.as_deref().map_or(track.start, earliest);
+                to(start) - start
+            })
+            .collect();
+        for (track, by) in self.tracks.iter_mut().zip(moves) {
+            track.start += by;
+        }
+    }
+
+    /// Changes the project tempo to `bpm`. Tracks following the tempo move with the grid, so
+    /// loops stay on the beat, and are stretched to it when built. The others keep their
+    /// start in seconds.
+    pub fn set_tempo(&mut self, bpm: f64) {
+        let scale = self.bpm / bpm;
+        for track in &mut self.tracks {
+            if track.follow_tempo.is_some() {
+                track.start *= scale;
+            }
+        }
+        self.bpm = bpm;
+    }
+
+    /// Inserts `bars` bars of 4/4 at the project tempo before bar `at_bar` (1-based)
+    pub fn insert_bars(&mut self, at_bar: u32, bars: u32) {
+        let bar_seconds = 4.0 * 60.0 / self.bpm;
+        self.insert_time(
+            f64::from(at_bar.saturating_sub(1)) * bar_seconds,
+            f64::from(bars) * bar_seconds,
+        );
+    }
+
+    /// Like [`Project::build_tracks`], with every track on a mixer channel that keeps its
+    /// arrangement position, folder group and metadata, panned by the default pan law
+    pub fn build_channels(&self, sample_rate: f64) -> Result<Vec<(Channel, u64)>, EngineError> {
+        let tracks = self.build_tracks(sample_rate)?;
+        Ok(tracks
+            .into_iter()
+            .zip(&self.tracks)
+            .enumerate()
+            .map(|(order, ((track, start_frame), project_track))| {
+                let mut channel = Channel::new(track);
+                channel.set_order(order);
+                channel.set_group(project_track.group.clone());
+                channel.set_metadata(Arc::new(project_track.metadata.clone()));
+                channel.set_pan_law(self.defaults.pan_law);
+                channel.set_offset((project_track.offset_ms / 1000.0 * sample_rate).round() as i64);
+                (channel, start_frame)
+            })
+            .collect())
+    }
+
+    /// Loads every track's audio, returning each track with its start frame at `sample_rate`.
+    /// Tracks following the tempo are stretched to the project's.
+    pub fn build_tracks(
+        &self,
+        sample_rate: f64,
+    ) -> Result<Vec<(Box<dyn Track>, u64)>, EngineError> {
+        self.tracks
+            .iter()
+            .map(|track| {
+                let mut wav = WavTrack::from_file(self.media_path(&track.file))?;
+                if let Some(bpm) = track
+                    .follow_tempo
+                    .filter(|bpm| (bpm - self.bpm).abs() > f64::EPSILON)
+                {
+                    wav = WavTrack::from_buffer(time_stretch(
+                        &wav.samples,
+                        self.bpm / bpm,
+                        sample_rate,
+                    ));
+                }
+                let built = TrackBuilder::new(Box::new(wav))
+                    .id(&track.id)
+                    .gain(track.gain)
+                    .pan(track.pan)
+                    .build();
+                let start_frame = (track.start.max(0.0) * sample_rate).round() as u64;
+                Ok((built, start_frame))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::RecordFormat;
+
+    #[test]
+    fn test_parse_applies_defaults() {
+        let project = Project::from_toml(
+            r#"
+            [[track]]
+            id = "drums"
+            file = "drum.wav"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(project.bpm, 120.0);
+        assert_eq!(project.sample_rate, 44100);
+        assert_eq!(project.tracks.len(), 1);
+        assert_eq!(project.tracks[0].gain, 1.0);
+        assert_eq!(project.tracks[0].start, 0.0);
+    }
+
+    #[test]
+    fn test_roundtrip_through_toml() {
+        let mut project = Project::new(90.0, 48000);
+        project.seed = 77;
+        project.tracks.push(ProjectTrack {
+            id: "bass".into(),
+            file: "bass.wav".into(),
+            start: 2.5,
+            gain: 0.7,
+            pan: -0.5,
+            offset_ms: -12.5,
+            group: Some("low end".into()),
+            clip_group: Some("bass take".into()),
+            follow_tempo: None,
+            metadata: Metadata {
+                color: Some(Color::new(0x1e, 0x90, 0xff)),
+                notes: "DI only".into(),
+                tags: vec!["low".into()],
+            },
+        });
+        project.groups.push(TrackGroup {
+            id: "low end".into(),
+            parent: None,
+        });
+
+        let encoded = toml::to_string(&project).unwrap();
+        let decoded = Project::from_toml(&encoded).unwrap();
+
+        assert_eq!(decoded.bpm, 90.0);
+        assert_eq!(decoded.sample_rate, 48000);
+        assert_eq!(decoded.seed_for("bass"), project.seed_for("bass"));
+        assert_ne!(
+            decoded.seed_for("bass"),
+            Project::new(90.0, 48000).seed_for("bass")
+        );
+        assert_eq!(decoded.tracks[0].id, "bass");
+        assert_eq!(decoded.tracks[0].start, 2.5);
+        assert_eq!(decoded.tracks[0].pan, -0.5);
+        assert_eq!(decoded.tracks[0].offset_ms, -12.5);
+        assert_eq!(decoded.tracks[0].group.as_deref(), Some("low end"));
+        assert_eq!(decoded.tracks[0].clip_group.as_deref(), Some("bass take"));
+        assert_eq!(decoded.tracks[0].metadata, project.tracks[0].metadata);
+        assert!(encoded.contains("color = \"#1e90ff\""));
+        assert_eq!(decoded.groups, project.groups);
+        assert_eq!(decoded.record, RecordSettings::default());
+    }
+
+    #[test]
+    fn test_record_settings_are_stored_in_project() {
+        let project = Project::from_toml(
+            r#"
+            [record]
+            format = "float32"
+            directory = "takes"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(project.record.format, RecordFormat::Float32);
+        assert_eq!(
+            project.take_path("vox", 2),
+            Path::new("takes").join("vox-take2.wav")
+        );
+        let encoded = toml::to_string(&project).unwrap();
+        assert!(encoded.contains("[record]"));
+    }
+
+    #[test]
+    fn test_defaults_shape_new_clips_and_tracks() {
+        let mut project = Project::from_toml(
+            r##"
+            sample_rate = 48000
+
+            [defaults]
+            fade_ms = 5.0
+            fade_curve = "equal_power"
+            crossfade_ms = 10.0
+            crossfade_curve = "linear"
+            pan_law = "linear"
+            track_color = "#808080"
+            "##,
+        )
+        .unwrap();
+
+        let clip = project.new_clip(100, Arc::new(AudioBuffer::stereo(48_000)));
+        assert_eq!(clip.fades(), (240, 240));
+        assert_eq!(
+            clip.fade_curves(),
+            (FadeCurve::EqualPower, FadeCurve::EqualPower)
+        );
+        let track = project.new_timeline_track("vox");
+        assert_eq!(
+            (track.edit_fade(), track.edit_fade_curve()),
+            (480, FadeCurve::Linear)
+        );
+        let color = project.add_track("vox", "vox.wav").metadata.color;
+        assert_eq!(color, Some(Color::new(0x80, 0x80, 0x80)));
+
+        let encoded = toml::to_string(&project).unwrap();
+        assert_eq!(
+            Project::from_toml(&encoded).unwrap().defaults,
+            project.defaults
+        );
+        assert!(
+            !toml::to_string(&Project::new(120.0, 44100))
+                .unwrap()
+                .contains("defaults")
+        );
+    }
+
+    #[test]
+    fn test_move_track_reorders_arrangement() {
+        let mut project = Project::new(120.0, 44100);
+        for id in ["a", "b", "c"] {
+            project.tracks.push(ProjectTrack {
+                id: id.into(),
+                file: format!("{id}.wav").into(),
+                start: 0.0,
+                gain: 1.0,
+                pan: 0.0,
+                offset_ms: 0.0,
+                group: None,
+                clip_group: None,
+                follow_tempo: None,
+                metadata: Metadata::default(),
+            });
+        }
+
+        assert!(project.move_track("c", 0));
+        assert!(!project.move_track("d", 0));
+        let order: Vec<_> = project
+            .tracks
+            .iter()
+            .map(|track| track.id.as_str())
+            .collect();
+        assert_eq!(order, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_insert_and_remove_time_ripple_tracks() {
+        let mut project = Project::new(120.0, 44100);
+        for (id, start) in [("a", 0.0), ("b", 2.0), ("c", 5.0)] {
+            project.tracks.push(ProjectTrack {
+                id: id.into(),
+                file: format!("{id}.wav").into(),
+                start,
+                gain: 1.0,
+                pan: 0.0,
+                offset_ms: 0.0,
+                group: None,
+                clip_group: None,
+                follow_tempo: None,
+                metadata: Metadata::default(),
+            });
+        }
+
+        project.insert_bars(2, 1); // bar 2 starts at 2s, a bar lasts 2s
+        let starts: Vec<_> = project.tracks.iter().map(|track| track.start).collect();
+        assert_eq!(starts, vec![0.0, 4.0, 7.0]);
+
+        project.remove_time(1.0, 6.0);
+        let starts: Vec<_> = project.tracks.iter().map(|track| track.start).collect();
+        assert_eq!(starts, vec![0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_clip_groups_keep_their_distance() {
+        let mut project = Project::new(120.0, 44100);
+        for (id, start, clip_group) in [
+            ("kick", 2.0, Some("drums")),
+            ("snare", 2.5, Some("drums")),
+            ("bass", 2.5, None),
+        ] {
+            project.tracks.push(ProjectTrack {
+                id: id.into(),
+                file: format!("{id}.wav").into(),
+                start,
+                gain: 1.0,
+                pan: 0.0,
+                offset_ms: 0.0,
+                group: None,
+                clip_group: clip_group.map(str::to_owned),
+                follow_tempo: None,
+                metadata: Metadata::default(),
+            });
+        }
+        let starts = |project: &Project| -> Vec<f64> {
+            project.tracks.iter().map(|track| track.start).collect()
+        };
+
+        project.remove_time(1.0, 2.25);
+        assert_eq!(starts(&project), vec![1.0, 1.5, 1.25]);
+        assert!(project.set_start("snare", 3.5));
+        assert_eq!(starts(&project), vec![3.0, 3.5, 1.25]);
+        assert!(project.set_start("bass", 0.0));
+        assert_eq!(starts(&project), vec![3.0, 3.5, 0.0]);
+    }
+
+    #[test]
+    fn test_missing_file_fails_to_build() {
+        let project = Project::from_toml(
+            r#"
+            [[track]]
+            id = "ghost"
+            file = "does/not/exist.wav"
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            project.build_tracks(44100.0),
+            Err(EngineError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_tempo_change_moves_tracks_following_it() {
+        let mut project = Project::new(120.0, 44100);
+        project.add_track("loop", "loop.wav").start = 2.0;
+        project.tracks[0].follow_tempo = Some(120.0);
+        project.add_track("vocal", "vocal.wav").start = 2.0;
+
+        project.set_tempo(100.0);
+        let starts: Vec<_> = project.tracks.iter().map(|track| track.start).collect();
+        assert_eq!(starts, [2.4, 2.0]);
+        assert_eq!(project.bpm, 100.0);
+    }
+}
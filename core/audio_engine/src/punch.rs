@@ -0,0 +1,186 @@
+//! Input monitoring: when an armed track's performer hears their input instead of the
+//! track's playback.
+
+use crate::{buffer::AudioBuffer, dsp::math::equal_power};
+
+/// Length of the crossfade between playback and input at a punch point
+pub const PUNCH_RAMP_SECONDS: f64 = 0.01;
+
+/// When an armed track is heard from its input instead of its playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MonitorMode {
+    /// The input while the transport stands still, to rehearse and set levels, and inside
+    /// the punch range while it rolls. Playback everywhere else.
+    #[default]
+    Auto,
+    /// Always the input, e.g. to play along with an amp simulator
+    Input,
+    /// Never the input, for performers monitoring elsewhere
+    Off,
+}
+
+impl MonitorMode {
+    /// Whether a track in this mode hears its input, with the transport `rolling` or not
+    /// and the playhead `in_punch` range or not
+    #[must_use]
+    pub const fn hears_input(self, rolling: bool, in_punch: bool) -> bool {
+        match self {
+            Self::Auto => !rolling || in_punch,
+            Self::Input => true,
+            Self::Off => false,
+        }
+    }
+}
+
+/// Switches an armed track between its playback and its input as its [`MonitorMode`] says,
+/// e.g. at the punch points, with a short equal-power crossfade instead of a click in the
+/// headphones.
+///
+/// # Example
+/// ```
+/// use audio_engine::{buffer::AudioBuffer, punch::{MonitorMode, PunchSwitch}};
+///
+/// let mut switch = PunchSwitch::new();
+/// // punch in at frame 100, out at 200, ramping over 10 frames
+/// switch.set_range(Some((100, 200)), 10);
+///
+/// let mut playback = AudioBuffer::from_frames(&[(1.0, 1.0); 150]);
+/// let input = AudioBuffer::from_frames(&[(0.0, 0.0); 150]);
+/// switch.process(&mut playback, &input, 0, Some(MonitorMode::Auto), true);
+/// assert_eq!(playback.frame(99), (1.0, 1.0));
+/// assert_eq!(playback.frame(120), (0.0, 0.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PunchSwitch {
+    /// Timeline frames `start..end` the input is heard in
+    range: Option<(u64, u64)>,
+    /// Change of `mix` per frame
+    step: f32,
+    /// Share of the input in what's heard, 0.0 playback only to 1.0 input only
+    mix: f32,
+}
+
+impl PunchSwitch {
+    /// A switch with no punch range, playing back only
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            range: None,
+            step: 1.0,
+            mix: 0.0,
+        }
+    }
+
+    /// Timeline frames `start..end` the input is heard in
+    #[must_use]
+    pub const fn range(&self) -> Option<(u64, u64)> {
+        self.range
+    }
+
+    /// Hears the input in timeline frames `start..end`, crossfading over `ramp` frames at
+    /// each end, or only playback with `None`
+    pub fn set_range(&mut self, range: Option<(u64, u64)>, ramp: usize) {
+        self.range = range;
+        self.step = 1.0 / ramp.max(1) as f32;
+    }
+
+    /// Share of the input in what's heard, see [`PunchSwitch::process`]
+    #[must_use]
+    pub const fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Mixes `input` into `playback` for a block starting at timeline `frame`, crossfading
+    /// between them wherever `mode` switches, with the transport `rolling` or not. A track
+    /// that isn't armed, with no `mode`, ramps back to its playback. Frames `input` doesn't
+    /// have are silent.
+    pub fn process(
+        &mut self,
+        playback: &mut AudioBuffer,
+        input: &AudioBuffer,
+        frame: u64,
+        mode: Option<MonitorMode>,
+        rolling: bool,
+    ) {
+        let inside = |at: u64| {
+            let in_punch = self
+                .range
+                .is_some_and(|(start, end)| (start..end).contains(&at));
+            mode.is_some_and(|mode| mode.hears_input(rolling, in_punch))
+        };
+        let frames = playback.frames() as u64;
+        if self.mix == 0.0 && !inside(frame) && !self.crosses_in(frame, frame + frames) {
+            return;
+        }
+        for index in 0..playback.frames() {
+            let target = if inside(frame + index as u64) {
+                1.0
+            } else {
+                0.0
+            };
+            self.mix = if self.mix < target {
+                (self.mix + self.step).min(target)
+            } else {
+                (self.mix - self.step).max(target)
+            };
+            // exact at the ends, so a settled switch passes one side through untouched
+            let (played, heard) = equal_power(self.mix);
+            for channel in 0..playback.channels().min(2) {
+                let from_input = input
+                    .channel(channel.min(input.channels().saturating_sub(1)))
+                    .get(index)
+                    .copied()
+                    .unwrap_or_default();
+                let sample = &mut playback.channel_mut(channel)[index];
+                *sample = sample.mul_add(played, from_input * heard);
+            }
+        }
+    }
+
+    /// Punch-in lands in `from..to`
+    fn crosses_in(&self, from: u64, to: u64) -> bool {
+        self.range
+            .is_some_and(|(start, _)| (from..to).contains(&start))
+    }
+}
+
+impl Default for PunchSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_punch_points_crossfade_without_jumps() {
+        let mut switch = PunchSwitch::new();
+        switch.set_range(Some((10, 30)), 8);
+        let mut playback = AudioBuffer::from_frames(&[(1.0, 1.0); 50]);
+        let input = AudioBuffer::from_frames(&[(-1.0, -1.0); 50]);
+        switch.process(&mut playback, &input, 0, Some(MonitorMode::Auto), true);
+
+        let left = playback.channel(0);
+        assert_eq!(left[9], 1.0);
+        assert_eq!(left[20], -1.0);
+        assert_eq!(left[49], 1.0);
+        // equal-power halfway through the ramp in, nothing steps by more than the ramp
+        assert!(left[13].abs() < 1e-6);
+        assert!(left.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 0.3));
+
+        switch.process(&mut playback, &input, 20, None, true);
+        assert_eq!(switch.mix(), 0.0);
+    }
+
+    #[test]
+    fn test_monitor_modes() {
+        let modes = [MonitorMode::Auto, MonitorMode::Input, MonitorMode::Off];
+        let hears = |rolling, in_punch| modes.map(|mode| mode.hears_input(rolling, in_punch));
+
+        assert_eq!(hears(false, false), [true, true, false]);
+        assert_eq!(hears(true, false), [false, true, false]);
+        assert_eq!(hears(true, true), [true, true, false]);
+    }
+}
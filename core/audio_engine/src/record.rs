@@ -0,0 +1,364 @@
+//! Where recorded takes go and how they are encoded.
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+
+use crate::{buffer::AudioBuffer, error::EngineError};
+
+/// Largest 24-bit sample value
+const INT24_MAX: f32 = 8_388_607.0;
+
+/// Sample format of recorded WAV files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordFormat {
+    /// 24-bit integer, the usual choice for tracking
+    #[default]
+    Int24,
+    /// 32-bit float, can't clip after the converter
+    Float32,
+}
+
+impl RecordFormat {
+    #[must_use]
+    pub const fn wav_spec(self, channels: u16, sample_rate: u32) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            Self::Int24 => (24, SampleFormat::Int),
+            Self::Float32 => (32, SampleFormat::Float),
+        };
+        WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+/// Recording settings of a project, the `[record]` table of the project file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordSettings {
+    pub format: RecordFormat,
+    /// File name of a take without the extension. `{track}` is replaced with the track id
+    /// and `{take}` with the take number.
+    pub name_template: String,
+    /// Where takes are written, relative paths are resolved against the project file
+    pub directory: PathBuf,
+}
+
+impl RecordSettings {
+    /// File name of take `take` on `track`, with the `.wav` extension
+    #[expect(
+        clippy::literal_string_with_formatting_args,
+        reason = "template tokens, not format arguments"
+    )]
+    #[must_use]
+    pub fn file_name(&self, track: &str, take: u32) -> String {
+        let name = self
+            .name_template
+            .replace("{track}", track)
+            .replace("{take}", &take.to_string());
+        format!("{name}.wav")
+    }
+
+    /// Opens a WAV file for a stereo take in the configured format.
+    ///
+    /// # Errors
+    /// [`EngineError::Export`] if the file can't be created.
+    pub fn create_writer(&self, path: &Path, sample_rate: u32) -> Result<TakeWriter, EngineError> {
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        let writer =
+            WavWriter::create(path, self.format.wav_spec(2, sample_rate)).map_err(|source| {
+                EngineError::Export {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            })?;
+        Ok(TakeWriter {
+            writer,
+            format: self.format,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self {
+            format: RecordFormat::default(),
+            name_template: "{track}-take{take}".to_owned(),
+            directory: PathBuf::from("audio"),
+        }
+    }
+}
+
+/// Record enable switched on and off at exact timeline frames, e.g. auto-punch between two
+/// markers. Recording is off before the first change.
+///
+/// # Example
+/// ```
+/// use audio_engine::record::RecordAutomation;
+///
+/// let automation = RecordAutomation::punch(1000, 2000);
+/// assert!(!automation.is_enabled_at(999));
+/// assert!(automation.is_enabled_at(1000));
+/// assert!(!automation.is_enabled_at(2000));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordAutomation {
+    /// Timeline frame and the record enable from there on, by frame
+    changes: Vec<(u64, bool)>,
+}
+
+impl RecordAutomation {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+        }
+    }
+
+    /// Room for `room` changes, so setting that many doesn't allocate
+    #[must_use]
+    pub fn with_capacity(room: usize) -> Self {
+        Self {
+            changes: Vec::with_capacity(room),
+        }
+    }
+
+    /// Records timeline frames `start..end` only
+    #[must_use]
+    pub fn punch(start: u64, end: u64) -> Self {
+        let mut automation = Self::new();
+        automation.set(start, true);
+        automation.set(end.max(start), false);
+        automation
+    }
+
+    /// Switches recording on or off at timeline `frame`, replacing a change already there
+    pub fn set(&mut self, frame: u64, enabled: bool) {
+        match self.changes.binary_search_by_key(&frame, |&(at, _)| at) {
+            Ok(index) => self.changes[index].1 = enabled,
+            Err(index) => self.changes.insert(index, (frame, enabled)),
+        }
+    }
+
+    /// Like [`RecordAutomation::set`], but `false` instead of allocating when a new change
+    /// doesn't fit, for the audio thread
+    pub fn set_within_capacity(&mut self, frame: u64, enabled: bool) -> bool {
+        match self.changes.binary_search_by_key(&frame, |&(at, _)| at) {
+            Ok(index) => self.changes[index].1 = enabled,
+            Err(_) if self.changes.len() == self.changes.capacity() => return false,
+            Err(index) => self.changes.insert(index, (frame, enabled)),
+        }
+        true
+    }
+
+    /// Makes room for `room` changes in all
+    pub fn reserve(&mut self, room: usize) {
+        self.changes
+            .reserve(room.saturating_sub(self.changes.len()));
+    }
+
+    /// Removes the change at `frame`, `false` if there's none
+    pub fn remove(&mut self, frame: u64) -> bool {
+        self.changes
+            .binary_search_by_key(&frame, |&(at, _)| at)
+            .map(|index| self.changes.remove(index))
+            .is_ok()
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+    }
+
+    /// Timeline frames and the record enable from there on, by frame
+    #[must_use]
+    pub fn changes(&self) -> &[(u64, bool)] {
+        &self.changes
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Whether timeline `frame` is recorded
+    #[must_use]
+    pub fn is_enabled_at(&self, frame: u64) -> bool {
+        let index = self.changes.partition_point(|&(at, _)| at <= frame);
+        index > 0 && self.changes[index - 1].1
+    }
+
+    /// First change in timeline frames `from..to`
+    #[must_use]
+    pub fn next_change(&self, from: u64, to: u64) -> Option<(u64, bool)> {
+        let index = self.changes.partition_point(|&(at, _)| at < from);
+        self.changes.get(index).copied().filter(|&(at, _)| at < to)
+    }
+
+    /// Copies the recorded frames among the first `frames` of `input`, a block starting at
+    /// timeline `frame`, into `take`, from its first frame on. Returns the timeline frame `take` starts at, or
+    /// `None` if nothing in the block is recorded. Frames between a punch-out and a punch-in
+    /// inside the block are silent, so `take` stays aligned with the timeline.
+    pub fn capture(
+        &self,
+        input: &AudioBuffer,
+        frame: u64,
+        frames: usize,
+        take: &mut AudioBuffer,
+    ) -> Option<u64> {
+        let frames = frames.min(input.frames()) as u64;
+        let first = (frame..frame + frames).find(|&at| self.is_enabled_at(at))?;
+        let last = (first..frame + frames)
+            .rev()
+            .find(|&at| self.is_enabled_at(at))
+            .unwrap_or(first);
+        let (start, len) = ((first - frame) as usize, (last - first + 1) as usize);
+        let len = len.min(take.capacity());
+        take.set_frames(len);
+        take.copy_from(0, input, start, len);
+        let mut at = first;
+        while let Some((change, enabled)) = self.next_change(at + 1, first + len as u64) {
+            if !enabled {
+                let end = self
+                    .next_change(change + 1, first + len as u64)
+                    .map_or(first + len as u64, |(next, _)| next);
+                for channel in 0..take.channels() {
+                    take.channel_mut(channel)[(change - first) as usize..(end - first) as usize]
+                        .fill(0.0);
+                }
+            }
+            at = change;
+        }
+        Some(first)
+    }
+}
+
+/// A take being written to disk, block by block. Not for the audio thread.
+///
+/// Dropping it finalizes the file too, so takes survive the host quitting mid-recording;
+/// call [`TakeWriter::finalize`] to find out whether that worked. Takes opened with
+/// [`Engine::open_take`](crate::engine::Engine::open_take) are finalized when the engine
+/// shuts down.
+pub struct TakeWriter {
+    writer: WavWriter<BufWriter<File>>,
+    format: RecordFormat,
+    path: PathBuf,
+}
+
+impl TakeWriter {
+    /// Appends the frames of a stereo block
+    ///
+    /// # Errors
+    /// [`EngineError::Export`] if writing fails.
+    pub fn write(&mut self, block: &AudioBuffer) -> Result<(), EngineError> {
+        for frame in block.iter_frames() {
+            for sample in <[f32; 2]>::from(frame) {
+                let written = match self.format {
+                    RecordFormat::Int24 => self
+                        .writer
+                        .write_sample((sample.clamp(-1.0, 1.0) * INT24_MAX).round() as i32),
+                    RecordFormat::Float32 => self.writer.write_sample(sample),
+                };
+                written.map_err(|source| self.error(source))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Completes the file's header
+    ///
+    /// # Errors
+    /// [`EngineError::Export`] if writing fails.
+    pub fn finalize(self) -> Result<(), EngineError> {
+        let path = self.path;
+        self.writer
+            .finalize()
+            .map_err(|source| EngineError::Export { path, source })
+    }
+
+    fn error(&self, source: hound::Error) -> EngineError {
+        EngineError::Export {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_from_template() {
+        let mut settings = RecordSettings::default();
+        assert_eq!(settings.file_name("vox", 3), "vox-take3.wav");
+
+        settings.name_template = "{take} {track} {track}".into();
+        assert_eq!(settings.file_name("gtr", 1), "1 gtr gtr.wav");
+    }
+
+    #[test]
+    fn test_record_enable_is_captured_to_the_frame() {
+        let mut automation = RecordAutomation::punch(4, 12);
+        automation.set(8, false);
+        automation.set(10, true);
+        assert_eq!(automation.next_change(5, 20), Some((8, false)));
+        assert_eq!(automation.next_change(13, 20), None);
+
+        let frames: Vec<(f32, f32)> = (0..8).map(|index| (index as f32, 0.0)).collect();
+        let input = AudioBuffer::from_frames(&frames);
+        let mut take = AudioBuffer::stereo(8);
+        // block at timeline frames 2..10: recorded 4..8, then again from 10
+        assert_eq!(automation.capture(&input, 2, 8, &mut take), Some(4));
+        assert_eq!(take.channel(0), [2.0, 3.0, 4.0, 5.0]);
+
+        // block at 6..14: 6 and 7, silent 8 and 9, 10 and 11
+        assert_eq!(automation.capture(&input, 6, 8, &mut take), Some(6));
+        assert_eq!(take.channel(0), [0.0, 1.0, 0.0, 0.0, 4.0, 5.0]);
+
+        assert_eq!(automation.capture(&input, 12, 8, &mut take), None);
+        assert!(automation.remove(10));
+        assert!(!automation.is_enabled_at(11));
+    }
+
+    #[test]
+    fn test_changes_past_the_room_are_dropped() {
+        let mut automation = RecordAutomation::with_capacity(2);
+        assert!(automation.set_within_capacity(4, true));
+        assert!(automation.set_within_capacity(12, false));
+        assert!(!automation.set_within_capacity(8, false));
+        // replacing a change needs no room
+        assert!(automation.set_within_capacity(12, true));
+        assert_eq!(automation.changes(), [(4, true), (12, true)]);
+    }
+
+    #[test]
+    fn test_takes_are_written_in_the_configured_format() {
+        let path = std::env::temp_dir().join("freqform-record-test.wav");
+        let settings = RecordSettings::default();
+        let mut writer = settings.create_writer(&path, 48000).unwrap();
+        writer
+            .write(&AudioBuffer::from_frames(&[(0.5, -2.0)]))
+            .unwrap();
+        writer.finalize().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec(), RecordFormat::Int24.wav_spec(2, 48000));
+        let samples: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![4_194_304, -8_388_607]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
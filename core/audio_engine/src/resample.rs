@@ -0,0 +1,265 @@
+//! Sample-rate conversion, so a project can play through a device running at another rate.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{buffer::AudioBuffer, constants::MAX_BLOCK_FRAMES};
+
+/// Fractional positions the sinc kernel is tabulated at
+const SINC_PHASES: usize = 256;
+/// Input frames either side of an output frame the sinc kernel reaches
+const SINC_HALF_TAPS: usize = 16;
+
+/// How carefully [`Resampler`] converts between rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleQuality {
+    /// Straight lines between frames: cheap, dulls the top end and lets some aliasing through
+    Linear,
+    /// Polyphase Blackman-windowed sinc, clean up to just below the lower of the two
+    /// Nyquist frequencies
+    #[default]
+    Sinc,
+}
+
+impl ResampleQuality {
+    /// Input frames either side of an output frame the interpolation reads
+    const fn half_taps(self) -> usize {
+        match self {
+            Self::Linear => 1,
+            Self::Sinc => SINC_HALF_TAPS,
+        }
+    }
+}
+
+/// Streams stereo audio from one sample rate to another, pulling input as it's needed.
+/// Allocation free once built.
+///
+/// # Example
+/// ```
+/// use audio_engine::{buffer::AudioBuffer, resample::{ResampleQuality, Resampler}};
+///
+/// let mut resampler = Resampler::new(48000, 44100, ResampleQuality::Sinc);
+/// let mut output = AudioBuffer::stereo(441);
+/// let mut pulled = 0;
+/// resampler.process(&mut output, |input| {
+///     pulled += input.frames();
+///     input.clear();
+/// });
+/// assert_eq!(pulled, 480);
+/// ```
+pub struct Resampler {
+    from: u64,
+    to: u64,
+    quality: ResampleQuality,
+    /// Kernel per fractional position, `2 * half_taps` coefficients each, sinc only
+    kernels: Vec<f32>,
+    /// Last `2 * half_taps` input frames per channel, oldest first
+    history: [Vec<f32>; 2],
+    /// Position of the next output frame past the middle of `history`, in `1 / to` frames
+    phase: u64,
+    /// Input pulled but not yet moved into `history`
+    input: AudioBuffer,
+    read: usize,
+}
+
+impl Resampler {
+    /// Converts from `from` Hz to `to` Hz. The sinc kernel's cutoff follows the lower rate,
+    /// so converting down doesn't alias.
+    #[must_use]
+    pub fn new(from: u32, to: u32, quality: ResampleQuality) -> Self {
+        let taps = 2 * quality.half_taps();
+        let kernels = match quality {
+            ResampleQuality::Linear => Vec::new(),
+            ResampleQuality::Sinc => Self::sinc_kernels(f64::from(to.min(from)) / f64::from(from)),
+        };
+        let input = AudioBuffer::stereo(MAX_BLOCK_FRAMES);
+        Self {
+            from: u64::from(from.max(1)),
+            to: u64::from(to.max(1)),
+            quality,
+            kernels,
+            history: [vec![0.0; taps], vec![0.0; taps]],
+            phase: 0,
+            read: input.frames(),
+            input,
+        }
+    }
+
+    #[must_use]
+    pub const fn quality(&self) -> ResampleQuality {
+        self.quality
+    }
+
+    /// Rate converted from and to, in Hz
+    #[must_use]
+    pub const fn rates(&self) -> (u64, u64) {
+        (self.from, self.to)
+    }
+
+    /// Input frames the output is late by, rounded
+    #[must_use]
+    pub const fn latency(&self) -> usize {
+        self.quality.half_taps()
+    }
+
+    /// Forgets the audio heard so far, e.g. after a seek
+    pub fn reset(&mut self) {
+        for history in &mut self.history {
+            history.fill(0.0);
+        }
+        self.phase = 0;
+        self.read = self.input.frames();
+    }
+
+    /// Fills `output` with converted audio, calling `render` to fill blocks of input at the
+    /// source rate, as many as needed and no more than [`MAX_BLOCK_FRAMES`] each. Blocks are
+    /// sized so exactly as much input is pulled as the output covers.
+    pub fn process(&mut self, output: &mut AudioBuffer, mut render: impl FnMut(&mut AudioBuffer)) {
+        let frames = output.frames() as u64;
+        let mut needed = (self.phase + frames * self.from) / self.to;
+        for index in 0..output.frames() {
+            self.phase += self.from;
+            while self.phase >= self.to {
+                self.phase -= self.to;
+                if self.read == self.input.frames() {
+                    let block = needed.min(self.input.capacity() as u64).max(1) as usize;
+                    self.input.set_frames(block);
+                    render(&mut self.input);
+                    self.read = 0;
+                }
+                needed = needed.saturating_sub(1);
+                for (channel, history) in self.history.iter_mut().enumerate() {
+                    history.copy_within(1.., 0);
+                    *history.last_mut().unwrap() = self.input.channel(channel)[self.read];
+                }
+                self.read += 1;
+            }
+            for channel in 0..output.channels().min(2) {
+                output.channel_mut(channel)[index] = self.interpolate(channel);
+            }
+        }
+    }
+
+    /// The current output frame of `channel`, between the two middle frames of its history
+    fn interpolate(&self, channel: usize) -> f32 {
+        let history = &self.history[channel];
+        let fraction = self.phase as f64 / self.to as f64;
+        match self.quality {
+            ResampleQuality::Linear => {
+                (history[1] - history[0]).mul_add(fraction as f32, history[0])
+            }
+            ResampleQuality::Sinc => {
+                let taps = history.len();
+                let phase = ((fraction * SINC_PHASES as f64) as usize).min(SINC_PHASES - 1);
+                let kernel = &self.kernels[phase * taps..(phase + 1) * taps];
+                history
+                    .iter()
+                    .zip(kernel)
+                    .map(|(sample, coefficient)| sample * coefficient)
+                    .sum()
+            }
+        }
+    }
+
+    /// Windowed sinc kernels for every fractional position, low-passed at `cutoff` times
+    /// the source Nyquist frequency and normalised to unity gain
+    fn sinc_kernels(cutoff: f64) -> Vec<f32> {
+        let taps = 2 * SINC_HALF_TAPS;
+        let mut kernels = Vec::with_capacity(SINC_PHASES * taps);
+        for phase in 0..SINC_PHASES {
+            let fraction = phase as f64 / SINC_PHASES as f64;
+            let coefficients: Vec<f64> = (0..taps)
+                .map(|tap| {
+                    // distance from the output frame, which sits `fraction` past the middle
+                    let x = tap as f64 - (SINC_HALF_TAPS - 1) as f64 - fraction;
+                    let sinc = if x == 0.0 {
+                        cutoff
+                    } else {
+                        (PI * cutoff * x).sin() / (PI * x)
+                    };
+                    let n = (x + SINC_HALF_TAPS as f64) / taps as f64;
+                    let window = 0.08f64.mul_add(
+                        (4.0 * PI * n).cos(),
+                        0.5f64.mul_add(-(2.0 * PI * n).cos(), 0.42),
+                    );
+                    sinc * window
+                })
+                .collect();
+            let sum: f64 = coefficients.iter().sum();
+            kernels.extend(coefficients.iter().map(|c| (c / sum) as f32));
+        }
+        kernels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a 1 kHz sine at `from` Hz to `to` Hz, returning the output after the
+    /// filter has settled and the expected sine at the output rate, `latency` frames late
+    fn convert(from: u32, to: u32, quality: ResampleQuality) -> (Vec<f32>, Vec<f32>) {
+        let mut resampler = Resampler::new(from, to, quality);
+        let mut rendered = 0u64;
+        let mut output = AudioBuffer::stereo(1024);
+        let mut converted = Vec::new();
+        for _ in 0..4 {
+            resampler.process(&mut output, |input| {
+                for index in 0..input.frames() {
+                    let t = (rendered + index as u64) as f64 / f64::from(from);
+                    let sample = (2.0 * PI * 1000.0 * t).sin() as f32;
+                    input.channel_mut(0)[index] = sample;
+                    input.channel_mut(1)[index] = -sample;
+                }
+                rendered += input.frames() as u64;
+            });
+            converted.extend_from_slice(output.channel(0));
+        }
+        // output frame `index` is heard `latency + 1` input frames late, and one output
+        // frame early
+        let delay = (resampler.latency() + 1) as f64 / f64::from(from);
+        let expected: Vec<f32> = (0..converted.len())
+            .map(|index| {
+                let t = (index + 1) as f64 / f64::from(to) - delay;
+                (2.0 * PI * 1000.0 * t).sin() as f32
+            })
+            .collect();
+        (converted[512..].to_vec(), expected[512..].to_vec())
+    }
+
+    fn worst_error(converted: &[f32], expected: &[f32]) -> f32 {
+        converted
+            .iter()
+            .zip(expected)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_sinc_converts_a_sine_between_rates() {
+        for (from, to) in [(48000, 44100), (44100, 48000)] {
+            let (converted, expected) = convert(from, to, ResampleQuality::Sinc);
+            assert!(worst_error(&converted, &expected) < 1e-3, "{from} -> {to}");
+        }
+    }
+
+    #[test]
+    fn test_linear_is_rougher_than_sinc() {
+        let (converted, expected) = convert(48000, 44100, ResampleQuality::Linear);
+        let linear = worst_error(&converted, &expected);
+        assert!((1e-3..0.05).contains(&linear));
+    }
+
+    #[test]
+    fn test_pulls_exactly_the_input_covered() {
+        let mut resampler = Resampler::new(44100, 48000, ResampleQuality::Linear);
+        let mut output = AudioBuffer::stereo(480);
+        let mut pulled = 0;
+        for _ in 0..100 {
+            resampler.process(&mut output, |input| pulled += input.frames());
+        }
+        assert_eq!(pulled, 44100);
+    }
+}
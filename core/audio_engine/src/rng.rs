@@ -0,0 +1,89 @@
+//! Seeded randomness for everything stochastic (humanizing, random round robin, dither), so
+//! renders with the same seed come out the same bit for bit.
+
+/// `SplitMix64`: small, fast and allocation free, so it can run on the audio thread.
+/// Not for anything security related.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeededRng {
+    seed: u64,
+    state: u64,
+}
+
+impl SeededRng {
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Seed the generator started from
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts over from the seed, e.g. before rendering again
+    pub const fn reset(&mut self) {
+        self.state = self.seed;
+    }
+
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        finalize(self.state)
+    }
+
+    /// A number in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `0.0..1.0`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+}
+
+/// Seed for the consumer named `key` (a track id, a feature) of a project seeded with
+/// `seed`, so consumers don't share a stream and adding one doesn't shift the others
+#[must_use]
+pub fn derive_seed(seed: u64, key: &str) -> u64 {
+    // FNV-1a over the key, then mixed with the seed
+    let hash = key.bytes().fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+    });
+    mix(seed ^ mix(hash))
+}
+
+/// Increment of the `SplitMix64` state
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// `SplitMix64`'s finalizer
+const fn finalize(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Hashes `value` to a well spread `u64`, the same for the same value
+pub(crate) const fn mix(value: u64) -> u64 {
+    finalize(value.wrapping_add(GOLDEN_GAMMA))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_stream() {
+        let mut first = SeededRng::new(42);
+        let mut second = SeededRng::new(42);
+        let drawn: Vec<_> = (0..8).map(|_| first.next_u64()).collect();
+        assert!(drawn.iter().all(|value| *value == second.next_u64()));
+        assert_ne!(SeededRng::new(43).next_u64(), drawn[0]);
+
+        first.reset();
+        assert_eq!(first.next_u64(), drawn[0]);
+        assert!((0.0..1.0).contains(&first.next_f32()));
+        assert_ne!(derive_seed(42, "drums"), derive_seed(42, "bass"));
+    }
+}
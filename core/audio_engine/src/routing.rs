@@ -0,0 +1,352 @@
+//! Signal routing between tracks, busses and the master output.
+use crate::error::RoutingError;
+
+/// A point in the signal flow
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Track(String),
+    /// Aux output `.1` of a track, see [`Track::aux_outputs`](crate::track::Track::aux_outputs)
+    TrackOutput(String, usize),
+    Bus(String),
+    Master,
+}
+
+/// How a signal reaches its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connection {
+    /// The node's main output, every node has at most one
+    Output,
+    /// An additional, level-controlled copy into a bus
+    Send,
+    /// Key input of a processor on the destination, not mixed into its signal
+    Sidechain,
+}
+
+/// Directed graph of the engine's audio routing.
+///
+/// Every connection is checked when it is made, so the graph never contains a cycle and
+/// [`RoutingGraph::order`] always exists: each node comes after everything feeding it.
+///
+/// # Example
+/// ```
+/// use audio_engine::routing::{Connection, Node, RoutingGraph};
+///
+/// let mut graph = RoutingGraph::new();
+/// let drums = Node::Bus("drums".into());
+/// let verb = Node::Bus("verb".into());
+/// graph.add_node(drums.clone());
+/// graph.add_node(verb.clone());
+/// graph.connect(&drums, &verb, Connection::Send).unwrap();
+///
+/// assert!(graph.connect(&verb, &drums, Connection::Output).is_err());
+/// assert_eq!(graph.order(), vec![&drums, &verb, &Node::Master]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RoutingGraph {
+    /// Master is always the first node
+    nodes: Vec<Node>,
+    /// `(from, to, kind)` as indices into `nodes`
+    edges: Vec<(usize, usize, Connection)>,
+}
+
+impl RoutingGraph {
+    /// A graph holding only the master output
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::Master],
+            edges: Vec::new(),
+        }
+    }
+
+    /// Adds an unconnected node, `false` if it is already in the graph
+    pub fn add_node(&mut self, node: Node) -> bool {
+        if self.index(&node).is_some() {
+            return false;
+        }
+        self.nodes.push(node);
+        true
+    }
+
+    /// Removes `node` and every connection to or from it. The master can't be removed.
+    pub fn remove_node(&mut self, node: &Node) -> bool {
+        let Some(index) = self.index(node).filter(|&index| index != 0) else {
+            return false;
+        };
+        self.nodes.remove(index);
+        self.edges
+            .retain(|&(from, to, _)| from != index && to != index);
+        for (from, to, _) in &mut self.edges {
+            if *from > index {
+                *from -= 1;
+            }
+            if *to > index {
+                *to -= 1;
+            }
+        }
+        true
+    }
+
+    #[must_use]
+    pub fn contains(&self, node: &Node) -> bool {
+        self.index(node).is_some()
+    }
+
+    /// Connects `from` to `to`, replacing `from`'s previous output for [`Connection::Output`].
+    ///
+    /// # Errors
+    /// [`RoutingError::UnknownNode`] if either node isn't in the graph,
+    /// [`RoutingError::InvalidConnection`] for connections out of the master or audio into a
+    /// track, and [`RoutingError::Cycle`] if `to` already feeds `from`.
+    pub fn connect(
+        &mut self,
+        from: &Node,
+        to: &Node,
+        kind: Connection,
+    ) -> Result<(), RoutingError> {
+        let source = self.require(from)?;
+        let target = self.require(to)?;
+        let audio_into_track =
+            matches!(to, Node::Track(_) | Node::TrackOutput(..)) && kind != Connection::Sidechain;
+        if *from == Node::Master || audio_into_track {
+            return Err(RoutingError::InvalidConnection {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        if source == target || self.reaches(target, source) {
+            return Err(RoutingError::Cycle {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+
+        if kind == Connection::Output {
+            self.edges
+                .retain(|&(edge_from, _, edge_kind)| edge_from != source || edge_kind != kind);
+        }
+        if !self.edges.contains(&(source, target, kind)) {
+            self.edges.push((source, target, kind));
+        }
+        Ok(())
+    }
+
+    /// Removes a connection, `false` if it didn't exist
+    pub fn disconnect(&mut self, from: &Node, to: &Node, kind: Connection) -> bool {
+        let (Some(source), Some(target)) = (self.index(from), self.index(to)) else {
+            return false;
+        };
+        let before = self.edges.len();
+        self.edges.retain(|&edge| edge != (source, target, kind));
+        self.edges.len() != before
+    }
+
+    /// Where `node`'s main output goes, `None` when it isn't connected
+    #[must_use]
+    pub fn output_of(&self, node: &Node) -> Option<&Node> {
+        let source = self.index(node)?;
+        self.edges
+            .iter()
+            .find(|&&(from, _, kind)| from == source && kind == Connection::Output)
+            .map(|&(_, to, _)| &self.nodes[to])
+    }
+
+    /// Every connection as `(from, to, kind)`
+    pub fn connections(&self) -> impl Iterator<Item = (&Node, &Node, Connection)> {
+        self.edges
+            .iter()
+            .map(|&(from, to, kind)| (&self.nodes[from], &self.nodes[to], kind))
+    }
+
+    /// All nodes in processing order: every node after all nodes feeding it.
+    /// Unrelated nodes keep the order they were added in, the master comes last.
+    #[must_use]
+    pub fn order(&self) -> Vec<&Node> {
+        let mut incoming = vec![0usize; self.nodes.len()];
+        for &(_, to, _) in &self.edges {
+            incoming[to] += 1;
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut done = vec![false; self.nodes.len()];
+        // repeatedly take the first ready node, keeps the result stable for equal graphs
+        while order.len() < self.nodes.len() {
+            let Some(next) = (1..self.nodes.len())
+                .chain([0])
+                .find(|&index| !done[index] && incoming[index] == 0)
+            else {
+                unreachable!("routing graph contains a cycle");
+            };
+            done[next] = true;
+            for &(from, to, _) in &self.edges {
+                if from == next {
+                    incoming[to] -= 1;
+                }
+            }
+            order.push(&self.nodes[next]);
+        }
+        order
+    }
+
+    fn index(&self, node: &Node) -> Option<usize> {
+        self.nodes.iter().position(|candidate| candidate == node)
+    }
+
+    fn require(&self, node: &Node) -> Result<usize, RoutingError> {
+        self.index(node)
+            .ok_or_else(|| RoutingError::UnknownNode(node.to_string()))
+    }
+
+    /// `true` if a path leads from `from` to `to`
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![false; self.nodes.len()];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if std::mem::replace(&mut seen[node], true) {
+                continue;
+            }
+            stack.extend(
+                self.edges
+                    .iter()
+                    .filter(|&&(edge_from, _, _)| edge_from == node)
+                    .map(|&(_, edge_to, _)| edge_to),
+            );
+        }
+        false
+    }
+}
+
+impl Default for RoutingGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Track(id) => write!(f, "track '{id}'"),
+            Self::TrackOutput(id, index) => write!(f, "aux output {index} of track '{id}'"),
+            Self::Bus(id) => write!(f, "bus '{id}'"),
+            Self::Master => f.write_str("master"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str) -> Node {
+        Node::Track(id.into())
+    }
+
+    fn bus(id: &str) -> Node {
+        Node::Bus(id.into())
+    }
+
+    fn graph(nodes: &[Node]) -> RoutingGraph {
+        let mut graph = RoutingGraph::new();
+        for node in nodes {
+            graph.add_node(node.clone());
+        }
+        graph
+    }
+
+    #[test]
+    fn test_order_puts_sources_before_destinations() {
+        let mut graph = graph(&[bus("master-fx"), bus("drums"), track("kick")]);
+        graph
+            .connect(&track("kick"), &bus("drums"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&bus("drums"), &bus("master-fx"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&bus("master-fx"), &Node::Master, Connection::Output)
+            .unwrap();
+
+        assert_eq!(
+            graph.order(),
+            vec![
+                &track("kick"),
+                &bus("drums"),
+                &bus("master-fx"),
+                &Node::Master
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycles_are_rejected() {
+        let mut graph = graph(&[bus("a"), bus("b"), bus("c")]);
+        graph
+            .connect(&bus("a"), &bus("b"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&bus("b"), &bus("c"), Connection::Send)
+            .unwrap();
+
+        let error = graph.connect(&bus("c"), &bus("a"), Connection::Sidechain);
+        assert!(matches!(error, Err(RoutingError::Cycle { .. })));
+        assert!(matches!(
+            graph.connect(&bus("a"), &bus("a"), Connection::Send),
+            Err(RoutingError::Cycle { .. })
+        ));
+        assert_eq!(graph.connections().count(), 2);
+    }
+
+    #[test]
+    fn test_output_replaces_previous_output() {
+        let mut graph = graph(&[track("vox"), bus("a"), bus("b")]);
+        graph
+            .connect(&track("vox"), &bus("a"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&track("vox"), &bus("b"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&track("vox"), &bus("a"), Connection::Send)
+            .unwrap();
+
+        assert_eq!(graph.output_of(&track("vox")), Some(&bus("b")));
+        assert_eq!(graph.connections().count(), 2);
+    }
+
+    #[test]
+    fn test_invalid_connections() {
+        let mut graph = graph(&[track("kick"), track("bass")]);
+
+        assert!(matches!(
+            graph.connect(&track("kick"), &track("bass"), Connection::Output),
+            Err(RoutingError::InvalidConnection { .. })
+        ));
+        assert!(matches!(
+            graph.connect(&Node::Master, &bus("x"), Connection::Output),
+            Err(RoutingError::UnknownNode(_))
+        ));
+        // ducking the bass from the kick is fine
+        graph
+            .connect(&track("kick"), &track("bass"), Connection::Sidechain)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_remove_node_drops_its_connections() {
+        let mut graph = graph(&[track("kick"), bus("drums")]);
+        graph
+            .connect(&track("kick"), &bus("drums"), Connection::Output)
+            .unwrap();
+        graph
+            .connect(&bus("drums"), &Node::Master, Connection::Output)
+            .unwrap();
+
+        assert!(graph.remove_node(&track("kick")));
+        assert!(!graph.remove_node(&Node::Master));
+        assert_eq!(graph.connections().count(), 1);
+        assert_eq!(graph.output_of(&bus("drums")), Some(&Node::Master));
+    }
+}
@@ -0,0 +1,400 @@
+//! Real-time safety audit mode (feature `rt-audit`).
+//!
+//! [`AuditAllocator`] counts allocations made while a [`RealtimeScope`] is open on the
+//! current thread. The scheduler opens one around every render call, so any allocation,
+//! deallocation, lock or file access on the audio path is reported when the callback
+//! returns. Work the allocator can't see calls [`report`] itself: file I/O, and the locks the
+//! engine takes, such as [`SharedAudioPool::lock`](crate::audio_pool::SharedAudioPool::lock).
+//!
+//! The library doesn't install the allocator, test and bench binaries do:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: audio_engine::rt_audit::AuditAllocator = audio_engine::rt_audit::AuditAllocator;
+//! ```
+//!
+//! Meant for debug builds and tests, the bookkeeping costs a thread-local lookup per allocation.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Something that must not happen on the audio thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    Allocation,
+    Deallocation,
+    Lock,
+    FileIo,
+}
+
+/// What closing a scope with violations does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditPolicy {
+    /// Print a summary to stderr
+    Log,
+    /// Panic, failing the test that triggered it
+    Panic,
+}
+
+/// Violations seen during one realtime scope
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ViolationCounts {
+    pub allocations: u32,
+    pub deallocations: u32,
+    pub locks: u32,
+    pub file_io: u32,
+}
+
+impl ViolationCounts {
+    #[must_use]
+    pub fn total(&self) -> u32 {
+        self.allocations + self.deallocations + self.locks + self.file_io
+    }
+}
+
+struct AuditState {
+    depth: Cell<u32>,
+    policy: Cell<AuditPolicy>,
+    counts: Cell<ViolationCounts>,
+    last: Cell<ViolationCounts>,
+}
+
+thread_local! {
+    // const initialised and without `Drop`, so touching it from the allocator never allocates
+    static STATE: AuditState = const {
+        AuditState {
+            depth: Cell::new(0),
+            policy: Cell::new(AuditPolicy::Log),
+            counts: Cell::new(ViolationCounts {
+                allocations: 0,
+                deallocations: 0,
+                locks: 0,
+                file_io: 0,
+            }),
+            last: Cell::new(ViolationCounts {
+                allocations: 0,
+                deallocations: 0,
+                locks: 0,
+                file_io: 0,
+            }),
+        }
+    };
+}
+
+/// Records `violation` if the current thread is inside a realtime scope
+pub fn report(violation: Violation) {
+    let _ = STATE.try_with(|state| {
+        if state.depth.get() == 0 {
+            return;
+        }
+        let mut counts = state.counts.get();
+        match violation {
+            Violation::Allocation => counts.allocations += 1,
+            Violation::Deallocation => counts.deallocations += 1,
+            Violation::Lock => counts.locks += 1,
+            Violation::FileIo => counts.file_io += 1,
+        }
+        state.counts.set(counts);
+    });
+}
+
+/// Sets how the current thread's scopes react to violations
+pub fn set_policy(policy: AuditPolicy) {
+    STATE.with(|state| state.policy.set(policy));
+}
+
+/// Violations seen by the most recently closed scope on the current thread
+#[must_use]
+pub fn last_violations() -> ViolationCounts {
+    STATE.with(|state| state.last.get())
+}
+
+/// Marks the current thread as running audio code until dropped. Scopes nest;
+/// violations are checked when the outermost one closes.
+pub struct RealtimeScope {
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl RealtimeScope {
+    #[must_use]
+    pub fn enter() -> Self {
+        STATE.with(|state| {
+            if state.depth.get() == 0 {
+                state.counts.set(ViolationCounts::default());
+            }
+            state.depth.set(state.depth.get() + 1);
+        });
+        Self {
+            _not_send: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for RealtimeScope {
+    fn drop(&mut self) {
+        let (counts, policy) = STATE.with(|state| {
+            let depth = state.depth.get() - 1;
+            state.depth.set(depth);
+            if depth > 0 {
+                return (ViolationCounts::default(), state.policy.get());
+            }
+            let counts = state.counts.get();
+            state.last.set(counts);
+            (counts, state.policy.get())
+        });
+
+        if counts.total() == 0 {
+            return;
+        }
+
+        match policy {
+            AuditPolicy::Panic if !std::thread::panicking() => {
+                panic!("rt-audit: non realtime-safe work in audio callback: {counts:?}");
+            }
+            AuditPolicy::Panic => {}
+            AuditPolicy::Log => {
+                #[expect(
+                    clippy::print_stderr,
+                    reason = "audit reports are meant for the console"
+                )]
+                {
+                    eprintln!("rt-audit: non realtime-safe work in audio callback: {counts:?}");
+                }
+            }
+        }
+    }
+}
+
+/// System allocator that reports every call made inside a [`RealtimeScope`], to be installed
+/// as the `#[global_allocator]` of a test or bench binary
+pub struct AuditAllocator;
+
+// SAFETY: every method forwards to `System` with the caller's arguments unchanged;
+// `report` only touches a const thread-local and never allocates.
+unsafe impl GlobalAlloc for AuditAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        report(Violation::Allocation);
+        // SAFETY: forwarded as-is, the caller upholds `GlobalAlloc::alloc`'s contract
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        report(Violation::Allocation);
+        // SAFETY: forwarded as-is, the caller upholds `GlobalAlloc::alloc_zeroed`'s contract
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        report(Violation::Deallocation);
+        // SAFETY: `ptr` was handed out by `System` through this allocator with `layout`
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        report(Violation::Allocation);
+        // SAFETY: `ptr` was handed out by `System` through this allocator with `layout`
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, sync::Arc};
+
+    use rtrb::RingBuffer;
+
+    use super::*;
+    use crate::{
+        audio_pool::SharedAudioPool,
+        automation::AutomationStatus,
+        device_manager::{AudioSource as _, AudioSourceBufferKind},
+        metadata::Metadata,
+        mix_snapshot::{MixSnapshotChange, MixSnapshots},
+        mixer::Bus,
+        scheduler::{
+            command::SchedulerCommand, event::SchedulerEvent, garbage::garbage_channel, test_util,
+        },
+        snapshot::snapshot_channel,
+        track::{gainpan::GainPanTrack, sinewave::SineWaveTrack, wav::WavTrack},
+    };
+
+    #[global_allocator]
+    static ALLOCATOR: AuditAllocator = AuditAllocator;
+
+    /// Restores the default policy, even when the test panics
+    struct PolicyGuard;
+
+    impl PolicyGuard {
+        fn set(policy: AuditPolicy) -> Self {
+            set_policy(policy);
+            Self
+        }
+    }
+
+    impl Drop for PolicyGuard {
+        fn drop(&mut self) {
+            set_policy(AuditPolicy::Log);
+        }
+    }
+
+    #[test]
+    fn test_allocations_are_only_counted_inside_scopes() {
+        black_box(Vec::<u8>::with_capacity(64));
+
+        let scope = RealtimeScope::enter();
+        black_box(Vec::<u8>::with_capacity(64));
+        drop(scope);
+
+        let counts = last_violations();
+        assert_eq!(counts.allocations, 1);
+        assert_eq!(counts.deallocations, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "rt-audit")]
+    fn test_panic_policy_fails_on_allocation() {
+        let _policy = PolicyGuard::set(AuditPolicy::Panic);
+        let _scope = RealtimeScope::enter();
+        black_box(Box::new(42));
+    }
+
+    #[test]
+    fn test_file_io_is_reported() {
+        let scope = RealtimeScope::enter();
+        let _ = WavTrack::from_file("does/not/exist.wav");
+        drop(scope);
+
+        assert!(last_violations().file_io > 0);
+    }
+
+    #[test]
+    fn test_locks_are_reported() {
+        let pool = SharedAudioPool::default();
+        let scope = RealtimeScope::enter();
+        drop(pool.lock());
+        drop(scope);
+
+        assert_eq!(last_violations().locks, 1);
+    }
+
+    #[test]
+    fn test_snapshot_track_list_changes_are_realtime_safe() {
+        let (mut publisher, mut reader) = snapshot_channel();
+        let metadata = Arc::new(Metadata::default());
+        let lists = [
+            vec![("drums", None), ("bass", Some("rhythm"))],
+            vec![("keys", Some("pads"))],
+            vec![],
+        ];
+
+        let _policy = PolicyGuard::set(AuditPolicy::Panic);
+        for list in lists.iter().cycle().take(9) {
+            let scope = RealtimeScope::enter();
+            publisher.publish(|snapshot| {
+                let tracks = list.iter().map(|&(id, group)| {
+                    (id, group, &metadata, 0.0, None, None::<AutomationStatus>)
+                });
+                snapshot.set_tracks(tracks, drop);
+            });
+            drop(scope);
+            assert_eq!(last_violations(), ViolationCounts::default());
+            reader.latest();
+        }
+    }
+
+    #[test]
+    fn test_scheduler_steady_state_is_realtime_safe() {
+        let (mut scheduler, mut commands) = test_util::create_scheduler_with_channel();
+        let (events, mut event_consumer) = RingBuffer::new(256);
+        let (garbage, _collector) = garbage_channel(8);
+        let (snapshots, mut reader) = snapshot_channel();
+        scheduler.set_event_producer(events);
+        scheduler.set_garbage_producer(garbage);
+        scheduler.set_snapshot_publisher(snapshots);
+
+        for id in ["lead", "pad"] {
+            let track =
+                GainPanTrack::new(id, Box::new(SineWaveTrack::new(220.0, 44100.0)), 0.5, 0.0);
+            commands
+                .push(SchedulerCommand::ScheduleTrack {
+                    track: Box::new(track),
+                    start_frame: 0,
+                })
+                .unwrap();
+        }
+        commands.push(SchedulerCommand::Play).unwrap();
+
+        let mut device_buffer = vec![0.0f32; 512 * 2];
+        // warm up: tracks get activated and every pooled snapshot sees the full track list
+        for _ in 0..4 {
+            scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut device_buffer), 512);
+            reader.latest();
+            while event_consumer.pop().is_ok() {}
+        }
+
+        let _policy = PolicyGuard::set(AuditPolicy::Panic);
+        for _ in 0..64 {
+            scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut device_buffer), 512);
+            assert_eq!(last_violations(), ViolationCounts::default());
+            reader.latest();
+            while event_consumer.pop().is_ok() {}
+        }
+    }
+
+    #[test]
+    fn test_mix_captures_and_morphs_are_realtime_safe() {
+        let (mut scheduler, mut commands) = test_util::create_scheduler_with_channel();
+        let (events, mut event_consumer) = RingBuffer::new(256);
+        let (garbage, mut collector) = garbage_channel(8);
+        scheduler.set_event_producer(events);
+        scheduler.set_garbage_producer(garbage);
+        scheduler.mixer_mut().add_bus(Bus::new("reverb"));
+        let track = GainPanTrack::new(
+            "lead",
+            Box::new(SineWaveTrack::new(220.0, 44100.0)),
+            0.5,
+            0.0,
+        );
+        commands
+            .push(SchedulerCommand::ScheduleTrack {
+                track: Box::new(track),
+                start_frame: 0,
+            })
+            .unwrap();
+        commands.push(SchedulerCommand::Play).unwrap();
+        let mut device_buffer = vec![0.0f32; 512 * 2];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut device_buffer), 512);
+
+        let _policy = PolicyGuard::set(AuditPolicy::Panic);
+        commands
+            .push(SchedulerCommand::MixSnapshots(MixSnapshotChange::capture(
+                "a",
+            )))
+            .unwrap();
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut device_buffer), 512);
+        let mut snapshots = MixSnapshots::new();
+        while let Ok(event) = event_consumer.pop() {
+            if let SchedulerEvent::MixCaptured { capture } = event {
+                snapshots.store(capture.into_snapshot());
+            }
+        }
+
+        // the recall adds back the send `a` doesn't have and removes it when done
+        let lead = scheduler.mixer_mut().channel_mut("lead").unwrap();
+        lead.set_send("reverb".into(), 0.5, false);
+        lead.set_gain(0.0);
+        commands
+            .push(SchedulerCommand::MixSnapshots(
+                snapshots.recall("a", 1024).unwrap(),
+            ))
+            .unwrap();
+        for _ in 0..4 {
+            scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut device_buffer), 512);
+            assert_eq!(last_violations(), ViolationCounts::default());
+            collector.collect();
+        }
+        assert!(!scheduler.is_morphing_mix());
+    }
+}
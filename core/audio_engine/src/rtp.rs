@@ -0,0 +1,361 @@
+//! Streaming a stem, usually the master output, over the network as RTP, so a second machine
+//! or a remote collaborator can monitor the session live, see [`RtpSender`].
+//!
+//! Packets are AES67-style: stereo 24-bit big-endian samples (L24), a millisecond each by
+//! default. Players that don't speak RTP natively (ffplay, VLC) open [`RtpConfig::sdp`].
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::DeviceError, rng, stems::StemReader};
+
+/// Bytes of the fixed RTP header, without CSRCs or extensions
+pub const RTP_HEADER_LEN: usize = 12;
+/// RTP version field
+const RTP_VERSION: u8 = 2;
+/// Bytes per L24 sample
+const L24_BYTES: usize = 3;
+/// Largest 24-bit sample value
+const L24_MAX: f32 = 8_388_607.0;
+
+/// Where and how a stem is streamed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpConfig {
+    /// Receiver address, unicast or multicast
+    pub destination: SocketAddr,
+    /// Sample rate of the stream, the scheduler's
+    pub sample_rate: u32,
+    /// Dynamic payload type, announced in the SDP
+    pub payload_type: u8,
+    /// Identifies the stream to receivers, random by default
+    pub ssrc: u32,
+    /// Frames per packet
+    pub packet_frames: usize,
+    /// Audio held back before sending starts, room for the audio thread's jitter. More
+    /// latency, fewer dropouts on the receiver.
+    pub latency: Duration,
+}
+
+impl RtpConfig {
+    /// Streams to `destination` at `sample_rate`, with 1 ms packets and 20 ms of latency
+    #[must_use]
+    pub fn new(destination: SocketAddr, sample_rate: u32) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            destination,
+            sample_rate,
+            payload_type: 96,
+            ssrc: rng::mix(now.as_nanos() as u64) as u32,
+            packet_frames: (sample_rate as usize / 1000).max(1),
+            latency: Duration::from_millis(20),
+        }
+    }
+
+    /// Session description players open the stream with
+    #[must_use]
+    pub fn sdp(&self) -> String {
+        let ip = self.destination.ip();
+        let family = if ip.is_ipv4() { "IP4" } else { "IP6" };
+        let ptime = self.packet_frames as f64 * 1000.0 / f64::from(self.sample_rate);
+        format!(
+            "v=0\r\n\
+             o=- {ssrc} 0 IN {family} {ip}\r\n\
+             s=FreqForm\r\n\
+             c=IN {family} {ip}\r\n\
+             t=0 0\r\n\
+             m=audio {port} RTP/AVP {pt}\r\n\
+             a=rtpmap:{pt} L24/{rate}/2\r\n\
+             a=ptime:{ptime}\r\n\
+             a=recvonly\r\n",
+            ssrc = self.ssrc,
+            port = self.destination.port(),
+            pt = self.payload_type,
+            rate = self.sample_rate,
+        )
+    }
+}
+
+/// The RTP header fields the sender fills in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub payload_type: u8,
+    /// Counts packets, wrapping, so receivers notice losses and reordering
+    pub sequence: u16,
+    /// Stream position of the first frame, in frames, wrapping
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+/// Appends a packet of stereo `frames` to `packet`
+pub fn encode_packet(header: RtpHeader, frames: &[(f32, f32)], packet: &mut Vec<u8>) {
+    packet.reserve(RTP_HEADER_LEN + frames.len() * 2 * L24_BYTES);
+    packet.extend_from_slice(&[RTP_VERSION << 6, header.payload_type & 0x7F]);
+    packet.extend_from_slice(&header.sequence.to_be_bytes());
+    packet.extend_from_slice(&header.timestamp.to_be_bytes());
+    packet.extend_from_slice(&header.ssrc.to_be_bytes());
+    for sample in frames.iter().flat_map(|&frame| <[f32; 2]>::from(frame)) {
+        let value = (sample.clamp(-1.0, 1.0) * L24_MAX).round() as i32;
+        packet.extend_from_slice(&value.to_be_bytes()[1..]);
+    }
+}
+
+/// Header and stereo frames of an L24 packet, `None` if it isn't one
+#[must_use]
+pub fn decode_packet(packet: &[u8]) -> Option<(RtpHeader, Vec<(f32, f32)>)> {
+    let header = packet.get(..RTP_HEADER_LEN)?;
+    if header[0] >> 6 != RTP_VERSION {
+        return None;
+    }
+    // CSRCs and an extension are skipped, padding is removed
+    let csrcs = usize::from(header[0] & 0x0F) * 4;
+    let mut payload = packet.get(RTP_HEADER_LEN + csrcs..)?;
+    if header[0] & 0x10 != 0 {
+        let length = usize::from(u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]));
+        payload = payload.get(4 + length * 4..)?;
+    }
+    if header[0] & 0x20 != 0 {
+        let padding = usize::from(*payload.last()?);
+        payload = payload.get(..payload.len().checked_sub(padding)?)?;
+    }
+
+    let sample = |bytes: &[u8]| {
+        // sign extended by the arithmetic shift
+        let value = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8;
+        value as f32 / L24_MAX
+    };
+    let frames = payload
+        .chunks_exact(2 * L24_BYTES)
+        .map(|frame| (sample(&frame[..L24_BYTES]), sample(&frame[L24_BYTES..])))
+        .collect();
+    let header = RtpHeader {
+        payload_type: header[1] & 0x7F,
+        sequence: u16::from_be_bytes([header[2], header[3]]),
+        timestamp: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+        ssrc: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+    };
+    Some((header, frames))
+}
+
+/// Sends a stem as RTP from its own thread, see the [module docs](self).
+///
+/// Sending starts once the configured latency of audio is buffered, then a packet goes out
+/// every packet time, keeping that much queued. Packets the audio thread falls behind on are
+/// sent as silence, so receivers keep their clock. Stops when dropped.
+pub struct RtpSender {
+    packets: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RtpSender {
+    /// Starts streaming `stem`, e.g. the scheduler's [`MASTER_STEM`](crate::stems::MASTER_STEM)
+    pub fn start(config: &RtpConfig, mut stem: StemReader) -> Result<Self, DeviceError> {
+        let config = config.clone();
+        let any: IpAddr = if config.destination.is_ipv4() {
+            Ipv4Addr::UNSPECIFIED.into()
+        } else {
+            Ipv6Addr::UNSPECIFIED.into()
+        };
+        let socket = UdpSocket::bind((any, 0))
+            .map_err(|e| DeviceError::Network(format!("Failed to open socket: {e}")))?;
+        socket.connect(config.destination).map_err(|e| {
+            DeviceError::Network(format!("Failed to reach {}: {e}", config.destination))
+        })?;
+
+        let packets = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (packets, stop) = (Arc::clone(&packets), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let packet_frames = config.packet_frames.max(1);
+                let packet_time = Duration::from_secs_f64(
+                    packet_frames as f64 / f64::from(config.sample_rate.max(1)),
+                );
+                let prebuffer =
+                    (config.latency.as_secs_f64() * f64::from(config.sample_rate)) as usize;
+                let (mut left, mut right) = (vec![0.0; packet_frames], vec![0.0; packet_frames]);
+                let mut frames = Vec::with_capacity(packet_frames);
+                let mut packet = Vec::new();
+                let mut header = RtpHeader {
+                    payload_type: config.payload_type,
+                    sequence: rng::mix(u64::from(config.ssrc)) as u16,
+                    timestamp: 0,
+                    ssrc: config.ssrc,
+                };
+                // the device clock drifting ahead shows as twice the latency queued
+                let backlog = 2 * prebuffer.max(packet_frames);
+                let mut started = false;
+                let mut next_send = Instant::now();
+                let mut failing = false;
+
+                while !stop.load(Ordering::Relaxed) {
+                    if !started {
+                        started = stem.available() >= prebuffer.max(packet_frames);
+                        next_send = Instant::now();
+                        if !started {
+                            std::thread::park_timeout(packet_time / 2);
+                            continue;
+                        }
+                    }
+                    loop {
+                        if Instant::now() >= next_send {
+                            next_send += packet_time;
+                        } else if stem.available() < backlog {
+                            break;
+                        }
+                        stem.read(&mut left, &mut right);
+                        frames.clear();
+                        frames.extend(left.iter().copied().zip(right.iter().copied()));
+                        packet.clear();
+                        encode_packet(header, &frames, &mut packet);
+                        packets.fetch_add(1, Ordering::Relaxed);
+                        match socket.send(&packet) {
+                            Ok(_) => failing = false,
+                            Err(error) if !failing => {
+                                failing = true;
+                                tracing::warn!(target: "freqform::rtp", %error, "RTP packet not sent");
+                            }
+                            Err(_) => {}
+                        }
+                        header.sequence = header.sequence.wrapping_add(1);
+                        header.timestamp = header.timestamp.wrapping_add(packet_frames as u32);
+                    }
+                    std::thread::park_timeout(next_send.saturating_duration_since(Instant::now()));
+                }
+            })
+        };
+        Ok(Self {
+            packets,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Packets sent so far
+    #[must_use]
+    pub fn packets_sent(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RtpSender {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buffer::AudioBuffer,
+        stems::{MASTER_STEM, stem_channel},
+    };
+
+    #[test]
+    fn test_packets_roundtrip() {
+        let header = RtpHeader {
+            payload_type: 96,
+            sequence: 0xFFFF,
+            timestamp: 48,
+            ssrc: 7,
+        };
+        let mut packet = Vec::new();
+        encode_packet(header, &[(0.5, -0.5), (2.0, -1.0)], &mut packet);
+        assert_eq!(packet.len(), RTP_HEADER_LEN + 12);
+
+        let (decoded, frames) = decode_packet(&packet).unwrap();
+        assert_eq!(decoded, header);
+        assert!((frames[0].0 - 0.5).abs() < 1e-6 && (frames[0].1 + 0.5).abs() < 1e-6);
+        assert_eq!(frames[1], (1.0, -1.0));
+        assert!(decode_packet(&packet[..4]).is_none());
+    }
+
+    #[test]
+    fn test_streams_a_stem_over_udp() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut config = RtpConfig::new(receiver.local_addr().unwrap(), 8000);
+        config.latency = Duration::from_millis(2);
+        assert!(config.sdp().contains("a=rtpmap:96 L24/8000/2"));
+
+        let (mut tap, mut stems) = stem_channel(&[MASTER_STEM], 64);
+        let sender = RtpSender::start(&config, stems.remove(0)).unwrap();
+        tap.send(
+            MASTER_STEM,
+            &AudioBuffer::from_frames(&[(0.25, -0.25); 24]),
+            0,
+            24,
+            1.0,
+        );
+
+        let mut buffer = [0; 1500];
+        let mut packets = Vec::new();
+        while packets.len() < 3 {
+            let len = receiver.recv(&mut buffer).unwrap();
+            packets.push(decode_packet(&buffer[..len]).unwrap());
+        }
+        assert!(sender.packets_sent() >= 3);
+        let (first, frames) = &packets[0];
+        assert_eq!(first.ssrc, config.ssrc);
+        assert_eq!(frames.len(), 8);
+        assert!((frames[0].0 - 0.25).abs() < 1e-6);
+        let (second, _) = &packets[1];
+        assert_eq!(second.sequence, first.sequence.wrapping_add(1));
+        assert_eq!(second.timestamp, first.timestamp + 8);
+    }
+
+    #[test]
+    fn test_paces_packets_and_keeps_time_through_silence() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut config = RtpConfig::new(receiver.local_addr().unwrap(), 8000);
+        config.latency = Duration::from_millis(2);
+
+        let (mut tap, mut stems) = stem_channel(&[MASTER_STEM], 64);
+        tap.send(
+            MASTER_STEM,
+            &AudioBuffer::from_frames(&[(0.25, -0.25); 16]),
+            0,
+            16,
+            1.0,
+        );
+        let started = Instant::now();
+        let _sender = RtpSender::start(&config, stems.remove(0)).unwrap();
+
+        let mut buffer = [0; 1500];
+        let mut packets = Vec::new();
+        while packets.len() < 6 {
+            let len = receiver.recv(&mut buffer).unwrap();
+            packets.push(decode_packet(&buffer[..len]).unwrap());
+        }
+        // a millisecond apart, not all at once
+        assert!(started.elapsed() >= Duration::from_millis(5));
+        for pair in packets.windows(2) {
+            assert_eq!(pair[1].0.timestamp, pair[0].0.timestamp.wrapping_add(8));
+        }
+        assert!((packets[1].1[7].0 - 0.25).abs() < 1e-6);
+        assert!(
+            packets[2..]
+                .iter()
+                .all(|(_, frames)| frames.iter().all(|&frame| frame == (0.0, 0.0)))
+        );
+    }
+}
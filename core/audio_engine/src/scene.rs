@@ -0,0 +1,545 @@
+//! Scenes: named sets of clip launches across tracks, launched together on the next bar from
+//! the host or a MIDI note, see [`SceneLauncher`].
+//!
+//! A clip is a mixer channel scheduled [parked](crate::mixer::Channel::set_parked). Launching
+//! it plays it from its start, and parks the clip that played on the same lane (the track
+//! the clip belongs to).
+//!
+//! # Arrangement and session
+//!
+//! A lane named after a mixer channel is that track. Once a clip launches on it, launched
+//! playback takes precedence: the track's arrangement channel is silenced, and stays so
+//! when the lane's clips stop, until [`SceneChange::ReturnToArrangement`] gives the track
+//! back to the timeline. The arrangement keeps its place meanwhile, so it comes back where
+//! the playhead is. Both switches crossfade over [`SceneChange::SetCrossfade`] frames.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buffer::AudioBuffer,
+    constants::MAX_SCENES,
+    dsp::math::fade_gain,
+    midi::EventKind,
+    mixer::Mixer,
+    scheduler::{event::SchedulerEvent, garbage::Garbage},
+    track::timeline::FadeCurve,
+};
+
+/// What a scene does on one lane
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipLaunch {
+    /// The track the clip plays on, one clip plays per lane
+    pub lane: String,
+    /// Id of the clip's channel, `None` stops the lane
+    pub clip: Option<String>,
+}
+
+/// A named set of clip launches, lanes it doesn't mention keep playing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    /// Key of the MIDI note that launches the scene, on any channel
+    #[serde(default)]
+    pub note: Option<u8>,
+    #[serde(default, rename = "launch")]
+    pub launches: Vec<ClipLaunch>,
+}
+
+/// Changes to the scheduler's scenes, see [`SceneLauncher`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneChange {
+    /// Adds a scene, or replaces the one with the same name. Scenes are reported by their
+    /// position in the order they were added, see [`SceneLauncher::iter`].
+    Add(Scene),
+    Remove(String),
+    /// Launches the scene with this name on the next bar, replacing a launch still waiting.
+    /// Answered with [`SchedulerEvent::ClipLaunched`] and [`SchedulerEvent::ClipStopped`]
+    /// for each clip, so hosts can capture the performance, see
+    /// [`PerformanceCapture`](crate::performance::PerformanceCapture).
+    Launch(String),
+    /// Launches the scene mapped to the key of a note on, anything else is ignored
+    Midi(EventKind),
+    /// Stops every lane on the next bar
+    StopAll,
+    /// Stops every lane on the next bar and hands the tracks back to their arrangement
+    ReturnToArrangement,
+    /// Frames launched and arrangement playback crossfade over when one takes over from the
+    /// other, 0 (the default) cuts
+    SetCrossfade(usize),
+}
+
+/// What waits for the next bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Scene(usize),
+    StopAll,
+    ReturnToArrangement,
+}
+
+/// Fades a channel in or out, on top of its fader, as launched clips take its track over
+/// or give it back. Equal power, so a clip and the arrangement crossfade evenly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaunchFade {
+    /// 0.0 silent to 1.0 heard
+    position: f32,
+    target: f32,
+    /// Change of `position` per frame
+    step: f32,
+    /// Parks the channel once the fade out is done
+    park: bool,
+}
+
+impl LaunchFade {
+    /// Heard, not fading
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            position: 1.0,
+            target: 1.0,
+            step: 1.0,
+            park: false,
+        }
+    }
+
+    /// Fading out to park the channel
+    #[must_use]
+    pub const fn is_parking(&self) -> bool {
+        self.park
+    }
+
+    /// Gain the fade applies right now
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        fade_gain(FadeCurve::EqualPower, self.position)
+    }
+
+    /// Fades in (`heard`) or out over `frames`, 0 jumps. A fade out that `park`s parks the
+    /// channel once it's silent.
+    pub fn fade(&mut self, heard: bool, frames: usize, park: bool) {
+        self.target = if heard { 1.0 } else { 0.0 };
+        self.step = 1.0 / frames.max(1) as f32;
+        self.park = park && !heard;
+        if frames == 0 {
+            self.position = self.target;
+        }
+    }
+
+    /// Applies the fade to `buffer`. Returns `true` once a fade out that parks is silent.
+    pub fn process(&mut self, buffer: &mut AudioBuffer) -> bool {
+        if self.position == self.target {
+            match self.position {
+                1.0 => return false,
+                0.0 => buffer.clear(),
+                _ => {}
+            }
+            return std::mem::take(&mut self.park);
+        }
+        for index in 0..buffer.frames() {
+            self.position = if self.position < self.target {
+                (self.position + self.step).min(self.target)
+            } else {
+                (self.position - self.step).max(self.target)
+            };
+            let gain = self.gain();
+            for channel in 0..buffer.channels() {
+                buffer.channel_mut(channel)[index] *= gain;
+            }
+        }
+        false
+    }
+}
+
+impl Default for LaunchFade {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The scenes of a scheduler and the launch waiting for the next bar.
+///
+/// Launches are quantized to the bar while the transport plays; while it doesn't they
+/// happen right away and are heard when it starts. Room for [`MAX_SCENES`] scenes is kept so
+/// adding one doesn't allocate.
+#[derive(Debug)]
+pub struct SceneLauncher {
+    scenes: Vec<Scene>,
+    pending: Option<Pending>,
+    /// Crossfade between launched and arrangement playback, in frames
+    crossfade: usize,
+}
+
+impl SceneLauncher {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            scenes: Vec::with_capacity(MAX_SCENES),
+            pending: None,
+            crossfade: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|scene| scene.name == name)
+    }
+
+    /// Scenes in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Scene> {
+        self.scenes.iter()
+    }
+
+    /// `true` while a launch waits for the next bar
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Applies `change`, handing replaced and removed scenes and names to `retire`. Returns
+    /// `false` when an added scene found no room, it's retired as well.
+    pub fn apply(&mut self, change: SceneChange, mut retire: impl FnMut(Garbage)) -> bool {
+        match change {
+            SceneChange::Add(scene) => {
+                if let Some(existing) = self.scenes.iter_mut().find(|s| s.name == scene.name) {
+                    retire(std::mem::replace(existing, scene).into());
+                } else if self.scenes.len() < MAX_SCENES {
+                    self.scenes.push(scene);
+                } else {
+                    retire(scene.into());
+                    return false;
+                }
+            }
+            SceneChange::Remove(name) => {
+                if let Some(index) = self.position(&name) {
+                    retire(self.scenes.remove(index).into());
+                    self.pending = match self.pending {
+                        Some(Pending::Scene(pending)) if pending == index => None,
+                        Some(Pending::Scene(pending)) if pending > index => {
+                            Some(Pending::Scene(pending - 1))
+                        }
+                        pending => pending,
+                    };
+                }
+                retire(name.into());
+            }
+            SceneChange::Launch(name) => {
+                if let Some(index) = self.position(&name) {
+                    self.pending = Some(Pending::Scene(index));
+                }
+                retire(name.into());
+            }
+            SceneChange::Midi(EventKind::NoteOn { key, velocity, .. }) if velocity > 0 => {
+                if let Some(index) = self.scenes.iter().position(|s| s.note == Some(key)) {
+                    self.pending = Some(Pending::Scene(index));
+                }
+            }
+            SceneChange::Midi(_) => {}
+            SceneChange::StopAll => self.pending = Some(Pending::StopAll),
+            SceneChange::ReturnToArrangement => {
+                self.pending = Some(Pending::ReturnToArrangement);
+            }
+            SceneChange::SetCrossfade(frames) => self.crossfade = frames,
+        }
+        true
+    }
+
+    /// Most events the waiting launch reports, see [`SceneLauncher::launch_pending`]
+    #[must_use]
+    pub fn pending_reports(&self) -> usize {
+        match self.pending {
+            None => 0,
+            Some(Pending::Scene(index)) => {
+                let launches = &self.scenes[index].launches;
+                let stops: usize = launches
+                    .iter()
+                    .map(|launch| self.lane_clips(&launch.lane).count())
+                    .sum();
+                stops + launches.len() + 1
+            }
+            Some(Pending::StopAll | Pending::ReturnToArrangement) => self.clips().count(),
+        }
+    }
+
+    /// Carries out the waiting launch on `mixer`, the clips start at timeline `frame`.
+    /// What happens is reported to `emit`: each clip starting and going silent (after the
+    /// crossfade), then the scene launched. Clips are reported by the scene and
+    /// [`ClipLaunch`] naming them, the first one for clips several scenes launch.
+    pub fn launch_pending(
+        &mut self,
+        mixer: &mut Mixer,
+        frame: u64,
+        mut emit: impl FnMut(SchedulerEvent),
+    ) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let crossfade = self.crossfade;
+        // fades a playing clip out and parks it
+        let stop = |(scene, launch, clip): (usize, usize, &str),
+                    mixer: &mut Mixer,
+                    emit: &mut dyn FnMut(_)| {
+            let Some(channel) = mixer.channel_mut(clip) else {
+                return;
+            };
+            if channel.is_parked() || channel.launch_fade().is_parking() {
+                return;
+            }
+            channel
+                .launch_fade_mut()
+                .fade(false, crossfade, crossfade > 0);
+            if crossfade == 0 {
+                channel.set_parked(true);
+            }
+            emit(SchedulerEvent::ClipStopped {
+                scene,
+                launch,
+                frame: frame + crossfade as u64,
+            });
+        };
+        match pending {
+            Pending::Scene(index) => {
+                let scene = &self.scenes[index];
+                for (position, launch) in scene.launches.iter().enumerate() {
+                    let clip = launch.clip.as_deref();
+                    for other in self
+                        .lane_clips(&launch.lane)
+                        .filter(|&(_, _, other)| Some(other) != clip)
+                    {
+                        stop(other, mixer, &mut emit);
+                    }
+                    if let Some(arrangement) = mixer.channel_mut(&launch.lane) {
+                        arrangement.launch_fade_mut().fade(false, crossfade, false);
+                    }
+                    let Some(channel) = clip.and_then(|clip| mixer.channel_mut(clip)) else {
+                        continue;
+                    };
+                    if channel.is_parked() {
+                        channel.launch_fade_mut().fade(false, 0, false);
+                    }
+                    channel.launch_fade_mut().fade(true, crossfade, false);
+                    channel.reset();
+                    channel.set_start_frame(frame);
+                    channel.set_parked(false);
+                    emit(SchedulerEvent::ClipLaunched {
+                        scene: index,
+                        launch: position,
+                        frame,
+                    });
+                }
+                emit(SchedulerEvent::SceneLaunched {
+                    scene: index,
+                    frame,
+                });
+            }
+            Pending::StopAll => {
+                for (scene, launch, _, clip) in self.clips() {
+                    stop((scene, launch, clip), mixer, &mut emit);
+                }
+            }
+            Pending::ReturnToArrangement => {
+                for (scene, launch, lane, clip) in self.clips() {
+                    stop((scene, launch, clip), mixer, &mut emit);
+                    if let Some(arrangement) = mixer.channel_mut(lane) {
+                        arrangement.launch_fade_mut().fade(true, crossfade, false);
+                    }
+                }
+            }
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().position(|scene| scene.name == name)
+    }
+
+    /// Every clip any scene launches, as `(scene, launch, lane, clip)`. Clips several
+    /// launches name come up for each, stopping them is only reported the first time.
+    fn clips(&self) -> impl Iterator<Item = (usize, usize, &str, &str)> {
+        self.scenes
+            .iter()
+            .enumerate()
+            .flat_map(|(scene, launches)| {
+                launches
+                    .launches
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(launch, clip)| {
+                        Some((scene, launch, clip.lane.as_str(), clip.clip.as_deref()?))
+                    })
+            })
+    }
+
+    /// Clips any scene launches on `lane`, as `(scene, launch, clip)`
+    fn lane_clips<'a>(&'a self, lane: &'a str) -> impl Iterator<Item = (usize, usize, &'a str)> {
+        self.clips()
+            .filter(move |&(_, _, other, _)| other == lane)
+            .map(|(scene, launch, _, clip)| (scene, launch, clip))
+    }
+}
+
+impl Default for SceneLauncher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mixer::Channel,
+        track::{constant::ConstantTrack, gainpan::GainPanTrack},
+    };
+
+    fn scene(name: &str, note: u8, clip: Option<&str>) -> Scene {
+        Scene {
+            name: name.to_owned(),
+            note: Some(note),
+            launches: vec![ClipLaunch {
+                lane: "drums".to_owned(),
+                clip: clip.map(str::to_owned),
+            }],
+        }
+    }
+
+    fn launch(launcher: &mut SceneLauncher, mixer: &mut Mixer, frame: u64) -> Vec<SchedulerEvent> {
+        let mut events = Vec::new();
+        launcher.launch_pending(mixer, frame, |event| events.push(event));
+        events
+    }
+
+    fn parked(mixer: &Mixer) -> Vec<bool> {
+        mixer.channels().map(Channel::is_parked).collect()
+    }
+
+    #[test]
+    fn test_launching_swaps_the_clip_on_each_lane() {
+        let mut mixer = Mixer::new();
+        for id in ["verse", "chorus"] {
+            let track = GainPanTrack::new(id, Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+            let mut channel = Channel::new(Box::new(track));
+            channel.set_parked(true);
+            mixer.add_channel(channel);
+        }
+        let mut launcher = SceneLauncher::new();
+        launcher.apply(SceneChange::Add(scene("a", 36, Some("verse"))), drop);
+        launcher.apply(SceneChange::Add(scene("b", 37, Some("chorus"))), drop);
+        launcher.apply(SceneChange::Add(scene("break", 38, None)), drop);
+
+        launcher.apply(
+            SceneChange::Midi(EventKind::NoteOn {
+                channel: 9,
+                key: 37,
+                velocity: 100,
+            }),
+            drop,
+        );
+        let events = launch(&mut launcher, &mut mixer, 100);
+        assert!(matches!(
+            &events[..],
+            [
+                SchedulerEvent::ClipLaunched {
+                    scene: 1,
+                    launch: 0,
+                    frame: 100
+                },
+                SchedulerEvent::SceneLaunched {
+                    scene: 1,
+                    frame: 100
+                },
+            ]
+        ));
+        assert_eq!(parked(&mixer), [true, false]);
+        assert_eq!(mixer.channel("chorus").unwrap().start_frame(), 100);
+
+        launcher.apply(SceneChange::Launch("a".into()), drop);
+        let events = launch(&mut launcher, &mut mixer, 200);
+        assert!(matches!(
+            &events[0],
+            SchedulerEvent::ClipStopped {
+                scene: 1,
+                launch: 0,
+                frame: 200
+            }
+        ));
+        assert_eq!(parked(&mixer), [false, true]);
+        launcher.apply(SceneChange::Launch("break".into()), drop);
+        launch(&mut launcher, &mut mixer, 300);
+        assert_eq!(parked(&mixer), [true, true]);
+
+        // note offs and removed scenes launch nothing
+        launcher.apply(
+            SceneChange::Midi(EventKind::NoteOff {
+                channel: 9,
+                key: 36,
+            }),
+            drop,
+        );
+        assert!(!launcher.is_pending());
+        launcher.apply(SceneChange::Launch("a".into()), drop);
+        launcher.apply(SceneChange::Remove("a".into()), drop);
+        assert!(!launcher.is_pending());
+        assert!(launch(&mut launcher, &mut mixer, 400).is_empty());
+    }
+
+    #[test]
+    fn test_scenes_past_the_capacity_are_retired() {
+        let mut launcher = SceneLauncher::new();
+        for index in 0..MAX_SCENES {
+            assert!(launcher.apply(SceneChange::Add(scene(&index.to_string(), 0, None)), drop));
+        }
+        let mut retired = 0;
+        assert!(!launcher.apply(SceneChange::Add(scene("extra", 0, None)), |_| retired += 1));
+        assert_eq!((launcher.iter().count(), retired), (MAX_SCENES, 1));
+
+        // replacing one still works, and hands the old one back
+        assert!(launcher.apply(SceneChange::Add(scene("0", 1, None)), |_| retired += 1));
+        assert_eq!(retired, 2);
+        assert_eq!(launcher.get("0").unwrap().note, Some(1));
+    }
+
+    #[test]
+    fn test_launched_clips_take_the_track_over_until_returned() {
+        let mut mixer = Mixer::new();
+        for (id, parked) in [("drums", false), ("loop", true)] {
+            let track = GainPanTrack::new(id, Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+            let mut channel = Channel::new(Box::new(track));
+            channel.set_parked(parked);
+            mixer.add_channel(channel);
+        }
+        let mut launcher = SceneLauncher::new();
+        launcher.apply(
+            SceneChange::Add(Scene {
+                name: "jam".into(),
+                note: None,
+                launches: vec![ClipLaunch {
+                    lane: "drums".into(),
+                    clip: Some("loop".into()),
+                }],
+            }),
+            drop,
+        );
+        launcher.apply(SceneChange::SetCrossfade(4), drop);
+        let mut output = AudioBuffer::stereo(4);
+
+        launcher.apply(SceneChange::Launch("jam".into()), drop);
+        launch(&mut launcher, &mut mixer, 0);
+        mixer.mix(&mut output);
+        // equal power halfway, the clip alone at the end
+        assert!((output.frame(1).0 - std::f32::consts::SQRT_2).abs() < 1e-5);
+        assert!((output.frame(3).0 - 1.0).abs() < 1e-6);
+        assert_eq!(mixer.channel("drums").unwrap().launch_fade().gain(), 0.0);
+
+        // stopping the lane's clips doesn't hand the track back, returning does
+        launcher.apply(SceneChange::ReturnToArrangement, drop);
+        let events = launch(&mut launcher, &mut mixer, 4);
+        // the clip is silent once it's faded out
+        assert!(matches!(
+            &events[..],
+            [SchedulerEvent::ClipStopped { frame: 8, .. }]
+        ));
+        mixer.mix(&mut output);
+        assert!((output.frame(3).0 - 1.0).abs() < 1e-6);
+        mixer.mix(&mut output);
+        assert_eq!(parked(&mixer), [false, true]);
+        assert_eq!(output.frame(0), (1.0, 1.0));
+    }
+}
@@ -1,48 +1,278 @@
+use std::sync::Arc;
+
 use rtrb::Consumer;
-use transport::resolution::TickResolution;
+use transport::{resolution::TickResolution, roll::RollLength};
 
-use crate::track::Track;
+use crate::{
+    automation::{AutomatedParameter, AutomationMode},
+    metadata::Metadata,
+    midi::EventKind,
+    mix_snapshot::MixSnapshotChange,
+    mixer::{Channel, Headroom},
+    monitor::MonitorChange,
+    punch::MonitorMode,
+    scene::SceneChange,
+    track::{Track, video::VideoTrack},
+};
 
 pub enum ParameterChange {
     SetGain(f32),
     SetPan(f32),
+    /// Oscillator frequency in Hz, glides to the new value instead of jumping
+    SetFrequency(f32),
+    /// A parameter of a [`MidiTrack`](crate::track::midi::MidiTrack)'s instrument, applied
+    /// at the start of the next block
+    Instrument {
+        id: u32,
+        value: f32,
+    },
+}
+
+/// Changes to a mixer channel, addressed by the id of the track it plays
+pub enum ChannelChange {
+    SetGain(f32),
+    SetPan(f32),
+    SetMute(bool),
+    /// Follows the mixer's exclusive solo setting
+    SetSolo(bool),
+    SetSoloSafe(bool),
+    /// Moves the channel to this position in the mixer's (and render) order
+    MoveTo(usize),
+    SetSend {
+        bus: String,
+        level: f32,
+        pre_fader: bool,
+    },
+    RemoveSend {
+        bus: String,
+    },
+    /// Moves the track into a folder group, `None` takes it out
+    SetGroup(Option<String>),
+    /// Replaces the track's color, notes and tags
+    SetMetadata(Arc<Metadata>),
+    /// Arms the track for recording, its input is metered while armed
+    SetArmed(bool),
+    /// Chooses when the armed track's input is heard instead of its playback
+    SetMonitorMode(MonitorMode),
+    /// Turns the track's input clip indicator off
+    ClearClip,
+    /// Moves the track this many frames later, earlier when negative
+    SetOffset(i64),
+    /// Bypasses the insert at `index`, see [`Bypass`](crate::dsp::bypass::Bypass)
+    SetInsertBypass {
+        index: usize,
+        bypassed: bool,
+    },
+    /// Chooses whether the automation of `parameter` plays back or records, see
+    /// [`crate::automation`]
+    SetAutomationMode {
+        parameter: AutomatedParameter,
+        mode: AutomationMode,
+    },
+    /// Replaces the automation of `parameter` with `(timeline frame, value)` breakpoints.
+    /// Recording only fills the room `points` has spare, which
+    /// [`EngineHandle::send`](crate::engine::EngineHandle::send) reserves.
+    SetAutomation {
+        parameter: AutomatedParameter,
+        points: Vec<(u64, f32)>,
+    },
+    /// Room for the automation of `parameter` to record into: `points` is empty, with the
+    /// capacity wanted. Sent by [`EngineHandle::send`](crate::engine::EngineHandle::send)
+    /// ahead of switching a lane to writing or latching, see
+    /// [`AutomationLane::reserve_points`](crate::automation::AutomationLane::reserve_points).
+    ReserveAutomation {
+        parameter: AutomatedParameter,
+        points: Vec<(u64, f32)>,
+    },
+}
+
+/// Edits to the timeline's markers and regions, positions in ticks
+pub enum MarkerChange {
+    /// Adds a marker, or moves the existing one with this name
+    AddMarker {
+        name: String,
+        tick: u64,
+    },
+    RemoveMarker {
+        name: String,
+    },
+    /// Adds a region, or replaces the existing one with this name
+    AddRegion {
+        name: String,
+        start: u64,
+        end: u64,
+    },
+    /// Moves a region to `start`, keeping its length
+    MoveRegion {
+        name: String,
+        start: u64,
+    },
+    RemoveRegion {
+        name: String,
+    },
 }
 
+/// Scrubbing, sent while the host drags the playhead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrubChange {
+    /// Starts scrubbing from the current playhead position, normal playback pauses
+    Start,
+    /// The playhead was dragged to this frame
+    MoveTo(u64),
+    /// Plays from the scrub position at a fixed speed (negative for backwards),
+    /// `None` goes back to following the playhead
+    Audition(Option<f32>),
+    /// Ends scrubbing, the playhead stays where the scrub head is
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LoopOptions {
     pub bar: u64,
     pub beat: u64,
     pub tick: u64,
 }
 
+/// How the loop behaves while recording, see [`SchedulerCommand::SetLoopRecord`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoopRecordOptions {
+    /// Pauses at the loop start after this many passes, if the last one was recorded, e.g.
+    /// to stop after four takes
+    pub stop_after_passes: Option<u32>,
+    /// Switches looping off when recording stops, so the next play runs past the loop end
+    pub disable_loop_on_record_stop: bool,
+}
+
 // @todo change this to automation events
 pub enum SchedulerCommand {
+    /// Puts `track` on a new unity-gain channel. Setting the channel up allocates, so
+    /// [`EngineHandle::send`](crate::engine::EngineHandle::send) turns this into
+    /// `ScheduleChannel` before it reaches the audio thread.
     ScheduleTrack {
         track: Box<dyn Track>,
         start_frame: u64,
     },
+    /// Like `ScheduleTrack`, for a channel already set up with inserts and sends
+    ScheduleChannel {
+        channel: Box<Channel>,
+        start_frame: u64,
+    },
+    /// A change to a track's own parameters. `target_id` is a track path: the id of the
+    /// channel's track, followed by the ids of tracks it wraps, e.g. `"drums/kick"`.
     ParamChange {
         target_id: String,
         change: ParameterChange,
     },
+    ChannelChange {
+        target_id: String,
+        change: ChannelChange,
+    },
+    /// A live event for a [`MidiTrack`](crate::track::midi::MidiTrack), addressed like
+    /// `ParamChange`
+    Midi {
+        target_id: String,
+        event: EventKind,
+    },
     StopTrack {
         target_id: String,
     },
     RestartTrack {
         target_id: String,
     },
-    /// Tempo change command
+    /// Tempo change command, `resolution: None` keeps the current tick resolution
     SetTempo {
         bpm: f64,
-        resolution: TickResolution,
+        resolution: Option<TickResolution>,
     },
     SetLoop {
         enabled: bool,
         start: LoopOptions,
         end: LoopOptions,
     },
+    /// Sets how the loop behaves while recording. Every pass is reported with
+    /// [`SchedulerEvent::LoopPassCompleted`].
+    ///
+    /// [`SchedulerEvent::LoopPassCompleted`]: crate::scheduler::event::SchedulerEvent::LoopPassCompleted
+    SetLoopRecord(LoopRecordOptions),
+    /// Gain compensation on the master sum, see [`Mixer::set_headroom`]
+    ///
+    /// [`Mixer::set_headroom`]: crate::mixer::Mixer::set_headroom
+    SetHeadroom(Headroom),
+    Monitor(MonitorChange),
+    /// Captures the mix or morphs between A/B mix states, see
+    /// [`MixSnapshots`](crate::mix_snapshot::MixSnapshots)
+    MixSnapshots(MixSnapshotChange),
+    /// Adds, removes or launches scenes, see [`SceneLauncher`](crate::scene::SceneLauncher).
+    /// Launches are answered with [`SchedulerEvent::SceneLaunched`] as they happen.
+    ///
+    /// [`SchedulerEvent::SceneLaunched`]: crate::scheduler::event::SchedulerEvent::SceneLaunched
+    Scenes(SceneChange),
+    Markers(MarkerChange),
+    /// Inserts `ticks` of silence at `at_tick`: tracks, playing or queued, automation, markers
+    /// and the loop after it move later. Use `TempoClock::ticks_per_bar` to insert whole bars.
+    InsertTime {
+        at_tick: u64,
+        ticks: u64,
+    },
+    /// Cuts `start_tick..end_tick` out of the timeline, pulling what follows earlier
+    RemoveTime {
+        start_tick: u64,
+        end_tick: u64,
+    },
+    /// Moves the playhead to a marker, or the start of a region, with this name
+    SeekToMarker {
+        name: String,
+    },
+    Scrub(ScrubChange),
+    /// Punches in at `start_tick` and out at `end_tick`: armed tracks are heard from their
+    /// input instead of their playback in between, crossfaded over
+    /// [`PUNCH_RAMP_SECONDS`](crate::punch::PUNCH_RAMP_SECONDS). `None` plays back only.
+    SetPunch(Option<(u64, u64)>),
+    /// Switches recording on armed tracks on or off at `tick`, to the frame. Answered with
+    /// [`SchedulerEvent::RecordEnabled`] as playback passes it.
+    ///
+    /// [`SchedulerEvent::RecordEnabled`]: crate::scheduler::event::SchedulerEvent::RecordEnabled
+    SetRecordEnable {
+        tick: u64,
+        enabled: bool,
+    },
+    /// Removes every scheduled record enable change, nothing is recorded
+    ClearRecordEnable,
+    /// Records armed tracks inside `start_tick..end_tick` only, and punches their monitoring
+    /// there too. [`MarkerList::punch_range`] finds the range of a region or a pair of markers.
+    ///
+    /// [`MarkerList::punch_range`]: transport::markers::MarkerList::punch_range
+    AutoPunch {
+        start_tick: u64,
+        end_tick: u64,
+    },
+    /// Sets the video the transport reports picture frames for, `None` removes it
+    SetVideoReference(Option<VideoTrack>),
+    /// Plays `track` right away on the preview voice, e.g. from a file browser, replacing
+    /// the previous preview. Not part of the arrangement and unaffected by the transport.
+    Preview(Box<dyn Track>),
+    StopPreview,
+    /// Context played around [`SchedulerCommand::PlayRange`]
+    SetRoll {
+        pre_roll: RollLength,
+        post_roll: RollLength,
+    },
+    /// Plays a selection or punch region, `start_tick..end_tick`, with pre- and post-roll.
+    /// Playback pauses once the post-roll has played and the playhead returns to `start_tick`.
+    PlayRange {
+        start_tick: u64,
+        end_tick: u64,
+    },
     Play,
     Pause,
     Stop,
+    /// Stops playback and hands every track, queued or playing, to the garbage collector
+    /// ahead of the engine going away. Answered with [`SchedulerEvent::ShutdownReady`], and
+    /// on the signal set with [`Scheduler::set_shutdown_signal`], which can't be dropped.
+    ///
+    /// [`SchedulerEvent::ShutdownReady`]: crate::scheduler::event::SchedulerEvent::ShutdownReady
+    /// [`Scheduler::set_shutdown_signal`]: crate::scheduler::Scheduler::set_shutdown_signal
+    Shutdown,
 }
 
 pub type SchedulerCommandConsumer = Consumer<SchedulerCommand>;
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+/// Weight of the newest measurement in the moving average
+const CPU_SMOOTHING: f32 = 0.1;
+
+/// Exponentially smoothed share of a buffer deadline spent rendering, in percent.
+/// 100% means rendering took as long as the audio it produced lasts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuLoad {
+    percent: f32,
+}
+
+impl CpuLoad {
+    pub fn record(&mut self, elapsed: Duration, deadline: Duration) {
+        if deadline.is_zero() {
+            return;
+        }
+        let sample = (elapsed.as_secs_f32() / deadline.as_secs_f32()) * 100.0;
+        self.percent += (sample - self.percent) * CPU_SMOOTHING;
+    }
+
+    #[must_use]
+    pub fn percent(&self) -> f32 {
+        self.percent
+    }
+}
+
+/// Current time, or `None` where the platform has no monotonic clock (wasm32)
+pub(crate) fn now() -> Option<Instant> {
+    (!cfg!(target_arch = "wasm32")).then(Instant::now)
+}
+
+/// Real time covered by `frames` frames at `sample_rate`
+pub(crate) fn deadline(frames: usize, sample_rate: f64) -> Duration {
+    Duration::from_secs_f64(frames as f64 / sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_converges_to_measured_share() {
+        let mut load = CpuLoad::default();
+        for _ in 0..200 {
+            load.record(Duration::from_millis(5), Duration::from_millis(10));
+        }
+        assert!((load.percent() - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_single_spike_is_smoothed() {
+        let mut load = CpuLoad::default();
+        load.record(Duration::from_millis(10), Duration::from_millis(10));
+        let expected = 100.0 * CPU_SMOOTHING;
+        assert!((load.percent() - expected).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_deadline_matches_block_length() {
+        assert_eq!(deadline(441, 44100.0), Duration::from_millis(10));
+    }
+}
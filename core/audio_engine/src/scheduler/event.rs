@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use rtrb::{Consumer, Producer};
+use transport::{display::Timecode, transport::TransportState};
+
+use crate::mix_snapshot::MixCapture;
+
+/// Notifications emitted by the scheduler from the audio thread
+#[derive(Debug, Clone)]
+pub enum SchedulerEvent {
+    /// Playback entered a new bar (1-based)
+    BarStarted { bar: u64 },
+    /// An active track ran out of material. The id is shared with the channel, so the audio
+    /// thread doesn't allocate a copy.
+    TrackFinished { target_id: Arc<str> },
+    /// Play, pause or stop took effect
+    TransportChanged { state: TransportState },
+    /// A track was queued for playback
+    TrackScheduled {
+        target_id: Arc<str>,
+        start_frame: u64,
+    },
+    /// A track was stopped and removed from playback
+    TrackRemoved { target_id: String },
+    /// The preview voice played to the end
+    PreviewFinished,
+    /// Every track was handed to the garbage collector after a shutdown command
+    ShutdownReady,
+    /// Peak levels of the last rendered block. `frame` is the timeline frame its first frame
+    /// plays at once the master limiter's delay is taken into account, so UIs can show the
+    /// peaks when the playhead gets there. Sent on the meter ring, see
+    /// [`Scheduler::set_meter_producer`](crate::scheduler::Scheduler::set_meter_producer).
+    Meter {
+        frame: u64,
+        peak_left: f32,
+        peak_right: f32,
+    },
+    /// The video reference's picture changes to `video_frame` at timeline frame `frame`,
+    /// see [`VideoTrack`](crate::track::video::VideoTrack). Emitted as the block is rendered,
+    /// [`Scheduler::output_latency`](crate::scheduler::Scheduler::output_latency) frames
+    /// before it's heard.
+    VideoFrame {
+        frame: u64,
+        video_frame: u64,
+        timecode: Timecode,
+    },
+    /// Recording on armed tracks switched on or off at timeline frame `frame`, see
+    /// [`Channel::take`](crate::mixer::Channel::take)
+    RecordEnabled { frame: u64, enabled: bool },
+    /// Playback wrapped from the loop end to its start for the `pass`th time (1-based),
+    /// counted since the loop was set or the transport stopped, so hosts can show take counts
+    LoopPassCompleted { pass: u32 },
+    /// The mix was captured, answering
+    /// [`MixSnapshotChange::Capture`](crate::mix_snapshot::MixSnapshotChange::Capture). The
+    /// host keeps it with [`MixSnapshots::store`](crate::mix_snapshot::MixSnapshots::store).
+    MixCaptured { capture: Box<MixCapture> },
+    /// The `scene`th scene of the [`SceneLauncher`](crate::scene::SceneLauncher) launched,
+    /// its clips play from timeline frame `frame`
+    SceneLaunched { scene: usize, frame: u64 },
+    /// The `scene`th scene launched the clip of its `launch`th
+    /// [`ClipLaunch`](crate::scene::ClipLaunch) from timeline frame `frame`
+    ClipLaunched {
+        scene: usize,
+        launch: usize,
+        frame: u64,
+    },
+    /// The clip of the `launch`th [`ClipLaunch`](crate::scene::ClipLaunch) of the `scene`th
+    /// scene stopped, silent from timeline frame `frame` once its crossfade is done
+    ClipStopped {
+        scene: usize,
+        launch: usize,
+        frame: u64,
+    },
+    /// Analysis found true peaks above the ceiling in `source`, from timeline frame `start`
+    /// to `end`, see [`crate::analysis::clipping::ClippingAnalyzer`]
+    ClippingFound {
+        source: String,
+        start: u64,
+        end: u64,
+        peak_db: f32,
+    },
+    /// The device hasn't called back for `silent_for`, see
+    /// [`Watchdog`](crate::watchdog::Watchdog). Raised off the audio thread.
+    CallbackStalled { silent_for: Duration },
+    /// The device calls back again after a stall
+    CallbackResumed,
+    /// The output stream was rebuilt after a stall
+    StreamRestarted,
+    /// Something went wrong, `message` is meant for logs/users
+    Error { message: String },
+}
+
+pub type SchedulerEventProducer = Producer<SchedulerEvent>;
+pub type SchedulerEventConsumer = Consumer<SchedulerEvent>;
@@ -0,0 +1,306 @@
+use std::sync::Arc;
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::{
+    metadata::Metadata,
+    mix_snapshot::{MixCapture, MixMorph},
+    mixer::{AuxSend, Channel},
+    scene::Scene,
+    track::Track,
+};
+
+/// Scheduler side of the garbage channel, what's removed is pushed here instead of dropped
+pub type GarbageProducer = Producer<Garbage>;
+
+/// Something removed on the audio thread, freed by the [`GarbageCollector`] instead
+pub enum Garbage {
+    Track(Box<dyn Track>),
+    /// A whole channel, with its id, inserts and sends
+    Channel(Box<Channel>),
+    Send(AuxSend),
+    Sends(Vec<AuxSend>),
+    /// A bus or group name left over from a command
+    Name(String),
+    Metadata(Arc<Metadata>),
+    /// Replaced automation breakpoints
+    Points(Vec<(u64, f32)>),
+    /// A replaced or removed scene, or one there was no room for
+    Scene(Scene),
+    /// A mix capture the event queue had no room for
+    MixCapture(Box<MixCapture>),
+    /// A finished or replaced mix snapshot morph
+    MixMorph(Box<MixMorph>),
+}
+
+impl From<Box<dyn Track>> for Garbage {
+    fn from(track: Box<dyn Track>) -> Self {
+        Self::Track(track)
+    }
+}
+
+impl From<Box<Channel>> for Garbage {
+    fn from(channel: Box<Channel>) -> Self {
+        Self::Channel(channel)
+    }
+}
+
+impl From<AuxSend> for Garbage {
+    fn from(send: AuxSend) -> Self {
+        Self::Send(send)
+    }
+}
+
+impl From<Vec<AuxSend>> for Garbage {
+    fn from(sends: Vec<AuxSend>) -> Self {
+        Self::Sends(sends)
+    }
+}
+
+impl From<String> for Garbage {
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<Arc<Metadata>> for Garbage {
+    fn from(metadata: Arc<Metadata>) -> Self {
+        Self::Metadata(metadata)
+    }
+}
+
+impl From<Vec<(u64, f32)>> for Garbage {
+    fn from(points: Vec<(u64, f32)>) -> Self {
+        Self::Points(points)
+    }
+}
+
+impl From<Scene> for Garbage {
+    fn from(scene: Scene) -> Self {
+        Self::Scene(scene)
+    }
+}
+
+impl From<Box<MixCapture>> for Garbage {
+    fn from(capture: Box<MixCapture>) -> Self {
+        Self::MixCapture(capture)
+    }
+}
+
+impl From<Box<MixMorph>> for Garbage {
+    fn from(morph: Box<MixMorph>) -> Self {
+        Self::MixMorph(morph)
+    }
+}
+
+/// Creates the channel the scheduler uses to hand removed tracks, channels and the like off
+/// the audio thread. `capacity` bounds how many can wait for collection, once full they're
+/// dropped in place.
+#[must_use]
+pub fn garbage_channel(capacity: usize) -> (GarbageProducer, GarbageCollector) {
+    let (producer, consumer) = RingBuffer::new(capacity);
+    (producer, GarbageCollector { consumer })
+}
+
+/// Drops what the scheduler retired, away from the audio thread
+pub struct GarbageCollector {
+    consumer: Consumer<Garbage>,
+}
+
+impl GarbageCollector {
+    /// Drops everything retired so far, returning how many items were freed
+    pub fn collect(&mut self) -> usize {
+        let mut freed = 0;
+        while let Ok(garbage) = self.consumer.pop() {
+            drop(garbage);
+            freed += 1;
+        }
+        freed
+    }
+
+    /// `true` once the scheduler holding the producer has been dropped
+    pub fn is_abandoned(&self) -> bool {
+        self.consumer.is_abandoned()
+    }
+
+    /// Collects on a background thread every `interval` until the scheduler goes away
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(mut self, interval: std::time::Duration) -> std::thread::JoinHandle<()> {
+        std::thread::Builder::new()
+            .name("freqform-gc".into())
+            .spawn(move || {
+                while !self.is_abandoned() {
+                    self.collect();
+                    std::thread::sleep(interval);
+                }
+                self.collect();
+            })
+            .expect("Failed to spawn garbage collector thread")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        buffer::AudioBuffer,
+        scheduler::{
+            command::{ChannelChange, SchedulerCommand},
+            test_util,
+        },
+    };
+
+    /// Counts how many times it has been dropped
+    struct DropProbe {
+        id: &'static str,
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl Track for DropProbe {
+        fn id(&self) -> String {
+            self.id.to_owned()
+        }
+
+        fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+            let (left, right) = buffer.stereo_mut();
+            left.fill(0.1);
+            right.fill(0.1);
+        }
+    }
+
+    impl Drop for DropProbe {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn probe(id: &'static str, drops: &Arc<AtomicUsize>) -> Box<dyn Track> {
+        Box::new(DropProbe {
+            id,
+            drops: Arc::clone(drops),
+        })
+    }
+
+    #[test]
+    fn test_stopped_track_is_dropped_by_collector() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        let (garbage, mut collector) = garbage_channel(4);
+        sched.set_garbage_producer(garbage);
+
+        sched.process_command(SchedulerCommand::ScheduleTrack {
+            track: probe("a", &drops),
+            start_frame: 0,
+        });
+        sched.process_command(SchedulerCommand::Play);
+        sched.next_samples(4);
+        sched.process_command(SchedulerCommand::StopTrack {
+            target_id: "a".into(),
+        });
+
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(collector.collect(), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_transport_stop_retires_every_active_track() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        let (garbage, mut collector) = garbage_channel(4);
+        sched.set_garbage_producer(garbage);
+
+        for id in ["a", "b", "c"] {
+            sched.process_command(SchedulerCommand::ScheduleTrack {
+                track: probe(id, &drops),
+                start_frame: 0,
+            });
+        }
+        sched.process_command(SchedulerCommand::Play);
+        sched.next_samples(4);
+        sched.process_command(SchedulerCommand::Stop);
+
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(collector.collect(), 3);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_full_ring_falls_back_to_dropping_in_place() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        let (garbage, mut collector) = garbage_channel(1);
+        sched.set_garbage_producer(garbage);
+
+        for id in ["a", "b"] {
+            sched.process_command(SchedulerCommand::ScheduleTrack {
+                track: probe(id, &drops),
+                start_frame: 0,
+            });
+        }
+        sched.process_command(SchedulerCommand::Play);
+        sched.next_samples(4);
+        sched.process_command(SchedulerCommand::Stop);
+
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(collector.collect(), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_channel_changes_retire_what_they_replace() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        let (garbage, mut collector) = garbage_channel(4);
+        sched.set_garbage_producer(garbage);
+        let mut channel = Channel::new(probe("a", &drops));
+        channel.set_send("verb".into(), 0.5, false);
+        sched.process_command(SchedulerCommand::ScheduleChannel {
+            channel: Box::new(channel),
+            start_frame: 0,
+        });
+        sched.process_command(SchedulerCommand::Play);
+        sched.next_samples(4);
+
+        // the removed send and the command's bus name
+        sched.process_command(SchedulerCommand::ChannelChange {
+            target_id: "a".into(),
+            change: ChannelChange::RemoveSend { bus: "verb".into() },
+        });
+        assert!(sched.mixer().channel("a").unwrap().sends().is_empty());
+        assert_eq!(collector.collect(), 2);
+
+        sched.process_command(SchedulerCommand::StopTrack {
+            target_id: "a".into(),
+        });
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(collector.collect(), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_collector_thread_exits_with_scheduler() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        let (garbage, collector) = garbage_channel(4);
+        sched.set_garbage_producer(garbage);
+        let handle = collector.spawn(std::time::Duration::from_millis(1));
+
+        sched.process_command(SchedulerCommand::ScheduleTrack {
+            track: probe("a", &drops),
+            start_frame: 0,
+        });
+        sched.process_command(SchedulerCommand::Play);
+        sched.next_samples(4);
+        sched.process_command(SchedulerCommand::Stop);
+        drop(sched);
+
+        handle.join().unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,18 +1,64 @@
-use std::collections::BinaryHeap;
+use std::{
+    collections::BinaryHeap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
 
-use cpal::Sample;
-use transport::{clock::TempoClock, timeline::TimelinePosition, transport::TransportState};
+use dasp_sample::{FromSample, Sample as _};
+use rtrb::PushError;
+use transport::{
+    clock::TempoClock,
+    markers::MarkerList,
+    resolution::TickResolution,
+    roll::RollLength,
+    timebase,
+    timeline::{TimelinePosition, shift_for_insert, shift_for_removal},
+    transport::TransportState,
+};
 
 use crate::{
+    buffer::AudioBuffer,
+    constants::{MAX_ACTIVE_TRACKS, MAX_BLOCK_FRAMES},
+    cv::{CvOutput, CvOutputs},
     device_manager::{AudioSource, AudioSourceBufferKind},
+    diagnostics::DiagnosticsLogger,
+    dsp::{Processor as _, limiter::TruePeakLimiter},
+    error::SchedulingError,
+    metering::{
+        MeterBallistics,
+        correlation::{CorrelationMeter, GoniometerTap},
+        loudness::LoudnessMeter,
+    },
+    mix_snapshot::{MixMorph, MixSnapshotChange},
+    mixer::{Channel, Mixer},
+    monitor::MonitorController,
+    punch::PUNCH_RAMP_SECONDS,
+    resample::{ResampleQuality, Resampler},
+    scene::SceneLauncher,
     scheduler::{
-        command::{SchedulerCommand, SchedulerCommandConsumer},
+        command::{
+            LoopRecordOptions, MarkerChange, SchedulerCommand, SchedulerCommandConsumer,
+            ScrubChange,
+        },
+        cpu::CpuLoad,
+        event::{SchedulerEvent, SchedulerEventProducer},
+        garbage::{Garbage, GarbageProducer},
+        scrub::Scrubber,
         track::ScheduledTrack,
     },
-    track::Track,
+    snapshot::{Playhead, SnapshotPublisher},
+    stems::{MASTER_STEM, StemReader, StemTap},
+    track::{Track, video::VideoTrack},
 };
 
 pub mod command;
+pub mod cpu;
+pub mod event;
+pub mod garbage;
+pub mod scrub;
 pub mod track;
 
 pub struct LoopPoints {
@@ -27,8 +73,8 @@ pub struct LoopPoints {
 pub struct Scheduler {
     /// a queue of future tracks
     scheduled: BinaryHeap<ScheduledTrack>,
-    /// currently playing tracks
-    active_tracks: Vec<Box<dyn Track>>,
+    /// currently playing tracks, one mixer channel each
+    mixer: Mixer,
     /// the current timeline position (starts at 0)
     current_frame: u64,
     automation_events: SchedulerCommandConsumer,
@@ -41,15 +87,97 @@ pub struct Scheduler {
     loop_points: Option<LoopPoints>,
     loop_start_frame: u64,
     loop_end_frame: u64,
+    /// How the loop behaves while recording
+    loop_record: LoopRecordOptions,
+    /// Passes around the loop so far, see [`SchedulerEvent::LoopPassCompleted`]
+    loop_passes: u32,
 
     transport_state: TransportState,
+    /// Named positions and sections of the arrangement
+    markers: MarkerList,
+    /// Scrub head while the host drags the playhead, replaces normal playback
+    scrub: Option<Scrubber>,
+    /// Track auditioned outside the arrangement, heard whatever the transport does
+    preview: Option<Box<dyn Track>>,
+    /// Preview render scratch, preallocated to `MAX_BLOCK_FRAMES`
+    preview_buffer: AudioBuffer,
+    pre_roll: RollLength,
+    post_roll: RollLength,
+    /// `(start, stop)` frames of a range being played: the transport pauses at `stop`
+    /// and returns to `start`
+    play_range: Option<(u64, u64)>,
+    /// Picture the transport reports frames for
+    video: Option<VideoTrack>,
+    /// Video frame last reported, so locates within a frame don't repeat it
+    video_frame: Option<u64>,
+
+    /// Optional sink for notifications (bar boundaries, finished tracks)
+    events: Option<SchedulerEventProducer>,
+    /// The event ring was full last time, so the overflow is only logged once
+    events_overflowing: bool,
+    /// Optional sink for [`SchedulerEvent::Meter`], kept apart so meters arriving every
+    /// block can't crowd out the other events
+    meters: Option<SchedulerEventProducer>,
+    /// Optional sink for scene launches and clip starts and stops, see
+    /// [`Scheduler::set_launch_producer`]
+    launches: Option<SchedulerEventProducer>,
+    /// Optional sink for diagnostics, forwarded to `tracing` off the audio thread
+    diagnostics: Option<DiagnosticsLogger>,
+    /// Optional sink removed tracks are handed to, so they're dropped off the audio thread
+    garbage: Option<GarbageProducer>,
+    /// Set once a shutdown command has retired everything, see [`Scheduler::set_shutdown_signal`]
+    shutdown: Option<Arc<AtomicBool>>,
+    /// Optional state snapshot sink, also turns on CPU metering
+    snapshots: Option<SnapshotPublisher>,
+    /// CPU load of whole render calls
+    callback_load: CpuLoad,
+    /// Timeline frame when the current render call started
+    callback_frame: u64,
+    /// Frames rendered but not yet heard when the current render call started, see
+    /// [`Scheduler::output_latency`]
+    callback_latency: usize,
+    /// Optional master bus limiter, runs before the master meters
+    limiter: Option<TruePeakLimiter>,
+    /// Master bus loudness meter, `None` while loudness metering is off
+    loudness: Option<LoudnessMeter>,
+    /// Master bus phase correlation meter, `None` while off
+    correlation: Option<CorrelationMeter>,
+    /// How the input and correlation meters move
+    meter_ballistics: MeterBallistics,
+    /// Optional sink for master bus samples drawn by a goniometer
+    goniometer: Option<GoniometerTap>,
+    /// Level, dim, mono and speaker selection between the master bus and the device
+    monitor: MonitorController,
+    /// Recall or blend of mix snapshots setting the mixer each block
+    mix_morph: Option<Box<MixMorph>>,
+    /// Scenes and the launch waiting for the next bar
+    scenes: SceneLauncher,
+    /// Cue bus stem and the device channels it's played on, see
+    /// [`Scheduler::set_cue_output`]
+    cue_output: Option<(StemReader, usize, usize)>,
+    /// Control voltages played on device channels of their own, see
+    /// [`Scheduler::set_cv_outputs`]
+    cv_outputs: Option<CvOutputs>,
+
+    /// Mix scratch buffer for device callbacks, preallocated to `MAX_BLOCK_FRAMES`
+    output_buffer: AudioBuffer,
+    /// Converts to the device's rate when it isn't the project's, see
+    /// [`Scheduler::set_device_sample_rate`]
+    resampler: Option<Resampler>,
+
+    /// Fixed processing block size, `None` processes whatever size the caller asks for
+    block_size: Option<usize>,
+    /// Last fixed-size block, handed out across callbacks
+    pending_block: AudioBuffer,
+    /// Frames of `pending_block` already handed out
+    pending_read: usize,
 }
 
 impl Scheduler {
     pub fn new(consumer: SchedulerCommandConsumer, tempo_clock: TempoClock) -> Self {
         Self {
-            scheduled: BinaryHeap::new(),
-            active_tracks: Vec::new(),
+            scheduled: BinaryHeap::with_capacity(MAX_ACTIVE_TRACKS),
+            mixer: Mixer::new(),
             current_frame: 0,
             automation_events: consumer,
             sample_rate: tempo_clock.sample_rate(),
@@ -58,33 +186,337 @@ impl Scheduler {
             loop_points: None,
             loop_start_frame: 0,
             loop_end_frame: 0,
+            loop_record: LoopRecordOptions::default(),
+            loop_passes: 0,
             transport_state: TransportState::Stopped,
+            markers: MarkerList::new(),
+            scrub: None,
+            preview: None,
+            preview_buffer: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            pre_roll: RollLength::Off,
+            post_roll: RollLength::Off,
+            play_range: None,
+            video: None,
+            video_frame: None,
+            events: None,
+            events_overflowing: false,
+            meters: None,
+            launches: None,
+            shutdown: None,
+            diagnostics: None,
+            garbage: None,
+            snapshots: None,
+            callback_load: CpuLoad::default(),
+            callback_frame: 0,
+            callback_latency: 0,
+            limiter: None,
+            loudness: None,
+            correlation: None,
+            meter_ballistics: MeterBallistics::default(),
+            goniometer: None,
+            monitor: MonitorController::new(),
+            mix_morph: None,
+            scenes: SceneLauncher::new(),
+            cue_output: None,
+            cv_outputs: None,
+            output_buffer: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            resampler: None,
+            block_size: None,
+            pending_block: AudioBuffer::stereo(MAX_BLOCK_FRAMES),
+            pending_read: MAX_BLOCK_FRAMES,
+        }
+    }
+
+    /// Processes audio in blocks of exactly `block_size` frames, whatever the device asks for.
+    ///
+    /// Commands, automation and events then always land on the same frame boundaries. Track
+    /// starts, loop ends, play range ends and scene launches still split a block, so they
+    /// happen on their exact frame either way. Frames left over from a block are handed out on
+    /// the next call, so the output runs up to one block behind the clock. `None` restores rendering at the caller's size.
+    pub fn set_block_size(&mut self, block_size: Option<usize>) -> Result<(), SchedulingError> {
+        if let Some(size) = block_size
+            && !(1..=MAX_BLOCK_FRAMES).contains(&size)
+        {
+            return Err(SchedulingError::InvalidBlockSize(size));
+        }
+
+        self.block_size = block_size;
+        // drop anything left over from the previous block size
+        self.pending_read = self.pending_block.frames();
+        Ok(())
+    }
+
+    /// Events are dropped when the consumer falls behind and the ring is full
+    pub fn set_event_producer(&mut self, producer: SchedulerEventProducer) {
+        self.events = Some(producer);
+    }
+
+    /// Sends a [`SchedulerEvent::Meter`] for every rendered block to `producer`. Meters are
+    /// dropped while it's full, the next ones supersede them anyway.
+    pub fn set_meter_producer(&mut self, producer: SchedulerEventProducer) {
+        self.meters = Some(producer);
+    }
+
+    /// Sends [`SchedulerEvent::SceneLaunched`], [`SchedulerEvent::ClipLaunched`] and
+    /// [`SchedulerEvent::ClipStopped`] to `producer` instead of the event ring. Nothing is
+    /// dropped: a launch waits for the next bar while `producer` has no room for everything
+    /// it reports, so a [`PerformanceCapture`](crate::performance::PerformanceCapture)
+    /// doesn't miss a clip.
+    pub fn set_launch_producer(&mut self, producer: SchedulerEventProducer) {
+        self.launches = Some(producer);
+    }
+
+    /// Sets `signal` once [`SchedulerCommand::Shutdown`] has handed every track to the garbage
+    /// collector. Unlike [`SchedulerEvent::ShutdownReady`] it can't be lost to a full event
+    /// ring.
+    pub fn set_shutdown_signal(&mut self, signal: Arc<AtomicBool>) {
+        self.shutdown = Some(signal);
+    }
+
+    /// Logs what goes wrong on the audio thread, see [`crate::diagnostics`]
+    pub fn set_diagnostics_logger(&mut self, logger: DiagnosticsLogger) {
+        self.diagnostics = Some(logger);
+    }
+
+    /// Without a garbage sink (or once it's full) removed tracks are dropped in place
+    pub fn set_garbage_producer(&mut self, producer: GarbageProducer) {
+        self.garbage = Some(producer);
+    }
+
+    /// A snapshot with CPU usage is published after every render call
+    pub fn set_snapshot_publisher(&mut self, publisher: SnapshotPublisher) {
+        self.snapshots = Some(publisher);
+    }
+
+    /// Puts a true-peak limiter on the master output, `None` removes it.
+    /// Output is delayed by [`TruePeakLimiter::latency`] frames while it is in place.
+    pub fn set_master_limiter(&mut self, limiter: Option<TruePeakLimiter>) {
+        self.limiter = limiter;
+    }
+
+    /// Plays through a device running at `rate` Hz, converting the project's audio with a
+    /// realtime SRC stage of `quality` when the rates differ. Not for the audio thread.
+    pub fn set_device_sample_rate(&mut self, rate: u32, quality: ResampleQuality) {
+        let project = self.sample_rate.round() as u32;
+        self.resampler = (rate != project).then(|| Resampler::new(project, rate, quality));
+    }
+
+    /// Rate of the device the scheduler plays through, in Hz
+    pub fn device_sample_rate(&self) -> u32 {
+        self.resampler.as_ref().map_or_else(
+            || self.sample_rate.round() as u32,
+            |resampler| resampler.rates().1 as u32,
+        )
+    }
+
+    /// Frames between a timeline frame being rendered and it being handed to the device: the
+    /// master limiter's lookahead, the SRC stage's filter, plus what's left of the last
+    /// fixed-size block.
+    /// The published playhead is held back by this much while playing, so cursors don't lead
+    /// the sound.
+    pub fn output_latency(&self) -> usize {
+        let buffered = if self.block_size.is_some() {
+            self.pending_block
+                .frames()
+                .saturating_sub(self.pending_read)
+        } else {
+            0
+        };
+        let resampler = self.resampler.as_ref().map_or(0, Resampler::latency);
+        self.limiter_latency() + resampler + buffered
+    }
+
+    fn limiter_latency(&self) -> usize {
+        self.limiter.as_ref().map_or(0, TruePeakLimiter::latency)
+    }
+
+    /// Measures BS.1770 loudness and true peak of the master output while playing.
+    /// The reading is published with every snapshot and restarts on Stop.
+    pub fn set_loudness_metering(&mut self, enabled: bool) {
+        self.loudness = enabled.then(|| LoudnessMeter::new(self.sample_rate));
+    }
+
+    /// Loudness measured since metering was enabled or the transport last stopped
+    pub fn loudness(&self) -> Option<&LoudnessMeter> {
+        self.loudness.as_ref()
+    }
+
+    /// Measures the phase correlation of the master output, published with every snapshot
+    pub fn set_correlation_metering(&mut self, enabled: bool) {
+        self.correlation = enabled.then(|| {
+            CorrelationMeter::with_window(
+                self.sample_rate,
+                self.meter_ballistics.correlation_window_seconds,
+            )
+        });
+    }
+
+    /// Sets how fast input peaks fall and how long the correlation meter integrates. A
+    /// running correlation reading starts over.
+    pub fn set_meter_ballistics(&mut self, ballistics: MeterBallistics) {
+        self.meter_ballistics = ballistics;
+        if self.correlation.is_some() {
+            self.set_correlation_metering(true);
+        }
+    }
+
+    pub const fn meter_ballistics(&self) -> MeterBallistics {
+        self.meter_ballistics
+    }
+
+    /// Master output samples are sent to `tap` while playing
+    pub fn set_goniometer_tap(&mut self, tap: GoniometerTap) {
+        self.goniometer = Some(tap);
+    }
+
+    /// Plays the stem of a cue bus (see [`Bus::set_cue`](crate::mixer::Bus::set_cue)) on the
+    /// device channels `left` and `right` (0-based), next to the control room speakers, at
+    /// the project's sample rate. For a second device use
+    /// [`CpalAudioDeviceManager::start_stem_stream`](crate::device_manager::cpal_dm::CpalAudioDeviceManager::start_stem_stream)
+    /// instead. `None` stops playing it.
+    pub fn set_cue_output(&mut self, cue: Option<StemReader>, left: usize, right: usize) {
+        self.cue_output = cue.map(|cue| (cue, left, right));
+    }
+
+    /// Plays `outputs` on their device channels, next to the control room speakers, for a
+    /// DC-coupled interface to drive external gear. Replaces the outputs played so far, an
+    /// empty list stops them.
+    pub fn set_cv_outputs(&mut self, outputs: Vec<CvOutput>) {
+        self.cv_outputs = (!outputs.is_empty()).then(|| CvOutputs::new(outputs, self.sample_rate));
+    }
+
+    /// The CV outputs played, see [`Scheduler::set_cv_outputs`]
+    pub fn cv_outputs(&self) -> impl Iterator<Item = &CvOutput> {
+        self.cv_outputs.iter().flat_map(CvOutputs::outputs)
+    }
+
+    /// Streams tracks and busses to other apps, see [`Mixer::set_stem_tap`]. The master
+    /// output is streamed as [`MASTER_STEM`].
+    pub fn set_stem_tap(&mut self, tap: StemTap) {
+        self.mixer.set_stem_tap(Some(tap));
+    }
+
+    /// Channels of the active tracks, in render order
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// For setting up channels and busses while the scheduler isn't running yet;
+    /// use [`SchedulerCommand::ChannelChange`] once it is
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    /// A mix snapshot recall is still morphing, see [`MixSnapshotChange::Morph`]
+    pub const fn is_morphing_mix(&self) -> bool {
+        self.mix_morph.is_some()
+    }
+
+    pub fn scenes(&self) -> &SceneLauncher {
+        &self.scenes
+    }
+
+    pub fn markers(&self) -> &MarkerList {
+        &self.markers
+    }
+
+    pub fn monitor(&self) -> &MonitorController {
+        &self.monitor
+    }
+
+    /// For setting up speaker sets; use [`SchedulerCommand::Monitor`] while running
+    pub fn monitor_mut(&mut self) -> &mut MonitorController {
+        &mut self.monitor
+    }
+
+    fn retire(
+        garbage: &mut Option<GarbageProducer>,
+        diagnostics: &mut Option<DiagnosticsLogger>,
+        retired: impl Into<Garbage>,
+    ) {
+        // a full ring hands it back inside the error, which is then dropped here
+        if let Some(garbage) = garbage.as_mut()
+            && garbage.push(retired.into()).is_err()
+            && let Some(diagnostics) = diagnostics.as_mut()
+        {
+            diagnostics.warn("garbage queue full, dropped on the audio thread");
+        }
+    }
+
+    fn emit(&mut self, event: SchedulerEvent) {
+        if let Some(events) = self.events.as_mut() {
+            let dropped = events.push(event).err();
+            let overflowing = dropped.is_some();
+            if overflowing
+                && !self.events_overflowing
+                && let Some(diagnostics) = self.diagnostics.as_mut()
+            {
+                diagnostics.warn("event queue full, events dropped until it's pumped");
+            }
+            self.events_overflowing = overflowing;
+            // a capture owns its snapshot, which isn't freed here
+            if let Some(PushError::Full(SchedulerEvent::MixCaptured { capture })) = dropped {
+                Self::retire(&mut self.garbage, &mut self.diagnostics, capture);
+            }
+        }
+    }
+
+    fn advance_mix_morph(&mut self, frames: usize) {
+        let Some(morph) = self.mix_morph.as_mut() else {
+            return;
+        };
+        let done = morph.advance(&mut self.mixer, frames, |retired| {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, retired);
+        });
+        if done && let Some(morph) = self.mix_morph.take() {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, morph);
         }
     }
 
     pub fn process_command(&mut self, cmd: SchedulerCommand) {
         match cmd {
             SchedulerCommand::ScheduleTrack { track, start_frame } => {
-                self.schedule(track, start_frame)
+                self.schedule(track, start_frame);
+            }
+            SchedulerCommand::ScheduleChannel {
+                channel,
+                start_frame,
+            } => {
+                let target_id = Arc::clone(channel.shared_id());
+                if self.schedule_channel(channel, start_frame) {
+                    self.emit(SchedulerEvent::TrackScheduled {
+                        target_id,
+                        start_frame,
+                    });
+                }
             }
             SchedulerCommand::ParamChange { target_id, change } => {
-                for track in self.active_tracks.iter_mut() {
-                    track.apply_param_change(&target_id, &change);
+                if let Some(track) = self.mixer.track_mut(&target_id) {
+                    track.apply_param_change(&change);
+                }
+            }
+            SchedulerCommand::ChannelChange { target_id, change } => {
+                self.mixer.apply(&target_id, change, |garbage| {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, garbage);
+                });
+            }
+            SchedulerCommand::Midi { target_id, event } => {
+                if let Some(track) = self.mixer.track_mut(&target_id) {
+                    track.queue_event(event);
                 }
             }
             SchedulerCommand::StopTrack { target_id } => {
                 self.stop_track(target_id);
             }
             SchedulerCommand::RestartTrack { target_id } => {
-                if let Some(track) = self
-                    .active_tracks
-                    .iter_mut()
-                    .find(|track| track.id() == target_id)
-                {
-                    track.reset();
+                if let Some(channel) = self.mixer.channel_mut(&target_id) {
+                    channel.reset();
+                    channel.set_start_frame(self.current_frame);
                 }
             }
             SchedulerCommand::SetTempo { bpm, resolution } => {
+                let resolution =
+                    resolution.unwrap_or(TickResolution::PPQN(self.tempo_clock.ticks_per_beat));
                 self.tempo_clock = TempoClock::new(bpm, self.sample_rate, resolution);
             }
             SchedulerCommand::SetLoop {
@@ -93,6 +525,7 @@ impl Scheduler {
                 end,
             } => {
                 self.looping_enabled = enabled;
+                self.loop_passes = 0;
 
                 if enabled {
                     let loop_points = LoopPoints {
@@ -107,10 +540,8 @@ impl Scheduler {
                     let start_ticks = self.bbt_to_tick_count(&loop_points, true);
                     let end_ticks = self.bbt_to_tick_count(&loop_points, false);
 
-                    let start_frame =
-                        (start_ticks as f64 * self.tempo_clock.samples_per_tick()).round() as u64;
-                    let end_frame =
-                        (end_ticks as f64 * self.tempo_clock.samples_per_tick()).round() as u64;
+                    let start_frame = self.tick_to_frame(start_ticks);
+                    let end_frame = self.tick_to_frame(end_ticks);
 
                     self.loop_points = Some(loop_points);
                     self.loop_start_frame = start_frame;
@@ -119,162 +550,925 @@ impl Scheduler {
                     self.loop_points = None;
                 }
             }
+            SchedulerCommand::SetLoopRecord(options) => self.loop_record = options,
+            SchedulerCommand::SetHeadroom(headroom) => self.mixer.set_headroom(headroom),
+            SchedulerCommand::Monitor(change) => {
+                self.monitor.apply(change);
+                self.mixer.set_talkback(self.monitor.talkback_gain());
+            }
+            SchedulerCommand::MixSnapshots(MixSnapshotChange::Capture(mut capture)) => {
+                capture.fill(&self.mixer);
+                self.emit(SchedulerEvent::MixCaptured { capture });
+            }
+            SchedulerCommand::MixSnapshots(MixSnapshotChange::Morph(mut morph)) => {
+                morph.start(&self.mixer);
+                if let Some(replaced) = self.mix_morph.replace(morph) {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, replaced);
+                }
+            }
+            SchedulerCommand::Scenes(change) => {
+                let (garbage, diagnostics) = (&mut self.garbage, &mut self.diagnostics);
+                let added = self.scenes.apply(change, |retired| {
+                    Self::retire(garbage, diagnostics, retired);
+                });
+                if !added && let Some(diagnostics) = self.diagnostics.as_mut() {
+                    diagnostics.warn("scene list full, scene dropped");
+                }
+                if self.transport_state != TransportState::Playing {
+                    self.launch_scene();
+                }
+            }
+            SchedulerCommand::Markers(change) => self.apply_marker_change(change),
+            SchedulerCommand::InsertTime { at_tick, ticks } => {
+                self.markers.insert_time(at_tick, ticks);
+                let at = self.tick_to_frame(at_tick);
+                let length = self.tick_to_frame(ticks);
+                self.ripple(|frame| shift_for_insert(frame, at, length));
+            }
+            SchedulerCommand::RemoveTime {
+                start_tick,
+                end_tick,
+            } => {
+                self.markers.remove_time(start_tick, end_tick);
+                let start = self.tick_to_frame(start_tick);
+                let end = self.tick_to_frame(end_tick);
+                self.ripple(|frame| shift_for_removal(frame, start, end));
+            }
+            SchedulerCommand::SeekToMarker { name } => {
+                let tick = self
+                    .markers
+                    .marker(&name)
+                    .map(|marker| marker.tick)
+                    .or_else(|| self.markers.region(&name).map(|region| region.start));
+                if let Some(tick) = tick {
+                    self.seek(self.tick_to_frame(tick));
+                }
+            }
+            SchedulerCommand::Scrub(change) => self.apply_scrub_change(change),
+            SchedulerCommand::SetPunch(range) => {
+                let range =
+                    range.map(|(start, end)| (self.tick_to_frame(start), self.tick_to_frame(end)));
+                let ramp = (PUNCH_RAMP_SECONDS * self.sample_rate).round() as usize;
+                self.mixer.set_punch(range, ramp);
+            }
+            SchedulerCommand::SetRecordEnable { tick, enabled } => {
+                let frame = self.tick_to_frame(tick);
+                if !self.mixer.set_record_enable(frame, enabled)
+                    && let Some(diagnostics) = self.diagnostics.as_mut()
+                {
+                    diagnostics.warn("record enable list full, change dropped");
+                }
+            }
+            SchedulerCommand::ClearRecordEnable => self.mixer.clear_record_enable(),
+            SchedulerCommand::AutoPunch {
+                start_tick,
+                end_tick,
+            } => self.auto_punch(start_tick, end_tick),
+            SchedulerCommand::SetVideoReference(video) => {
+                self.video = video;
+                self.video_frame = None;
+                self.locate_video();
+            }
+            SchedulerCommand::Preview(track) => {
+                if let Some(previous) = self.preview.replace(track) {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, previous);
+                }
+            }
+            SchedulerCommand::StopPreview => {
+                if let Some(previous) = self.preview.take() {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, previous);
+                }
+            }
+            SchedulerCommand::SetRoll {
+                pre_roll,
+                post_roll,
+            } => {
+                self.pre_roll = pre_roll;
+                self.post_roll = post_roll;
+            }
+            SchedulerCommand::PlayRange {
+                start_tick,
+                end_tick,
+            } => {
+                let start = self.tick_to_frame(start_tick.min(end_tick));
+                let end = self.tick_to_frame(start_tick.max(end_tick));
+                let stop = end + self.post_roll.frames(&self.tempo_clock);
+                self.play_range = Some((start, stop));
+                self.transport_state = TransportState::Playing;
+                // the clock only follows a seek while running
+                self.tempo_clock.start();
+                self.seek(start.saturating_sub(self.pre_roll.frames(&self.tempo_clock)));
+                self.emit_transport_state();
+            }
             SchedulerCommand::Play => {
                 self.transport_state = TransportState::Playing;
                 self.tempo_clock.start();
+                self.emit_transport_state();
             }
             SchedulerCommand::Pause => {
+                if self.is_recording() {
+                    self.recording_stopped();
+                }
                 self.transport_state = TransportState::Paused;
+                self.mixer.stop_automation();
+                self.emit_transport_state();
             }
             SchedulerCommand::Stop => {
+                if self.is_recording() {
+                    self.recording_stopped();
+                }
                 self.transport_state = TransportState::Stopped;
+                self.play_range = None;
+                self.loop_passes = 0;
                 self.current_frame = 0;
                 self.tempo_clock.reset();
-                self.active_tracks.clear(); // stop playback
+                self.locate_video();
+                // stop playback
+                for channel in self.mixer.drain() {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, channel);
+                }
+                if let Some(limiter) = self.limiter.as_mut() {
+                    limiter.reset();
+                }
+                if let Some(loudness) = self.loudness.as_mut() {
+                    loudness.reset();
+                }
+                if let Some(correlation) = self.correlation.as_mut() {
+                    correlation.reset();
+                }
+                self.emit_transport_state();
+            }
+            SchedulerCommand::Shutdown => {
+                self.process_command(SchedulerCommand::Stop);
+                for track in self.scheduled.drain() {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, track.channel);
+                }
+                if let Some(preview) = self.preview.take() {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, preview);
+                }
+                if let Some(shutdown) = self.shutdown.as_ref() {
+                    shutdown.store(true, Ordering::Release);
+                }
+                self.emit(SchedulerEvent::ShutdownReady);
             }
         }
     }
 
-    fn schedule(&mut self, track: Box<dyn Track>, start_frame: u64) {
-        self.scheduled.push(ScheduledTrack { track, start_frame });
+    fn apply_marker_change(&mut self, change: MarkerChange) {
+        match change {
+            MarkerChange::AddMarker { name, tick } => self.markers.add_marker(&name, tick),
+            MarkerChange::RemoveMarker { name } => {
+                self.markers.remove_marker(&name);
+            }
+            MarkerChange::AddRegion { name, start, end } => {
+                self.markers.add_region(&name, start, end);
+            }
+            MarkerChange::MoveRegion { name, start } => {
+                self.markers.move_region(&name, start);
+            }
+            MarkerChange::RemoveRegion { name } => {
+                self.markers.remove_region(&name);
+            }
+        }
     }
 
-    pub fn next_samples(&mut self, frame_size: usize) -> Vec<(f32, f32)> {
-        // @audit allocation here, needs review
-        let mut buffer = vec![(0.0f32, 0.0f32); frame_size];
+    fn apply_scrub_change(&mut self, change: ScrubChange) {
+        match change {
+            ScrubChange::Start => {
+                self.scrub = Some(Scrubber::new(self.current_frame, self.sample_rate));
+            }
+            ScrubChange::MoveTo(frame) => {
+                if let Some(scrub) = self.scrub.as_mut() {
+                    scrub.move_to(frame);
+                }
+            }
+            ScrubChange::Audition(speed) => self
+                .scrub
+                .get_or_insert_with(|| Scrubber::new(self.current_frame, self.sample_rate))
+                .set_speed(speed),
+            ScrubChange::Stop => {
+                if let Some(scrub) = self.scrub.take() {
+                    self.seek(scrub.position());
+                }
+            }
+        }
+    }
 
-        while let Ok(cmd) = self.automation_events.pop() {
-            self.process_command(cmd);
+    /// `true` while scrubbing replaces normal playback
+    pub fn is_scrubbing(&self) -> bool {
+        self.scrub.is_some()
+    }
+
+    /// Timeline frame of `tick` at the current tempo
+    pub fn tick_to_frame(&self, tick: u64) -> u64 {
+        timebase::tick_to_frame(tick, self.tempo_clock.samples_per_tick())
+    }
+
+    /// Moves tracks, their automation and the loop through a time insertion or removal.
+    /// Tracks already playing move with the timeline too, so a cut before the playhead pulls
+    /// them earlier.
+    fn ripple(&mut self, shift: impl Fn(u64) -> u64) {
+        // rebuilt in place, the heap's allocation is reused
+        let mut scheduled = std::mem::take(&mut self.scheduled).into_vec();
+        for track in &mut scheduled {
+            track.start_frame = shift(track.start_frame);
+            track.channel.ripple(&shift);
+        }
+        self.scheduled = BinaryHeap::from(scheduled);
+        for channel in self.mixer.channels_mut() {
+            channel.ripple(&shift);
         }
+        self.loop_start_frame = shift(self.loop_start_frame);
+        self.loop_end_frame = shift(self.loop_end_frame);
+    }
 
-        if self.transport_state != TransportState::Playing {
-            return buffer;
+    /// Moves the timeline to `frame` the way a loop wrap does, tracks follow from their
+    /// matching position
+    fn seek(&mut self, frame: u64) {
+        self.current_frame = frame;
+        self.tempo_clock.reset();
+        self.tempo_clock.advance_by(frame);
+        self.locate_video();
+    }
+
+    /// Reports the video frame at the playhead after it jumped
+    fn locate_video(&mut self) {
+        let Some(video) = self.video else {
+            return;
+        };
+        match video.frame_at(self.current_frame) {
+            Some(video_frame) => self.show_video_frame(&video, self.current_frame, video_frame),
+            None => self.video_frame = None,
         }
+    }
 
-        while let Some(top) = self.scheduled.peek() {
-            if top.start_frame <= self.current_frame {
-                let ScheduledTrack { track, .. } = self.scheduled.pop().unwrap();
-                // @audit possible allocation here
-                self.active_tracks.push(track);
-            } else {
-                break;
-            }
+    /// Reports the video frame boundaries in `from..to` as the playhead passes them
+    fn play_video(&mut self, from: u64, to: u64) {
+        let Some(video) = self.video else {
+            return;
+        };
+        for (frame, video_frame) in video.boundaries(from, to) {
+            self.show_video_frame(&video, frame, video_frame);
         }
+    }
 
-        // @audit allocation here, needs review
-        let mut tmp_buffer = vec![(0.0f32, 0.0f32); frame_size];
-        for track in self.active_tracks.iter_mut() {
-            track.fill_next_samples(&mut tmp_buffer[..]);
-            for (i, (l, r)) in tmp_buffer.iter().enumerate() {
-                buffer[i].0 += l;
-                buffer[i].1 += r;
-            }
+    fn show_video_frame(&mut self, video: &VideoTrack, frame: u64, video_frame: u64) {
+        if self.video_frame == Some(video_frame) {
+            return;
         }
+        self.video_frame = Some(video_frame);
+        self.emit(SchedulerEvent::VideoFrame {
+            frame,
+            video_frame,
+            timecode: video.timecode(video_frame),
+        });
+    }
 
-        // Advance the tempo clock by the number of samples processed
-        self.tempo_clock.advance_by(frame_size as u64);
-        self.current_frame += frame_size as u64;
+    fn emit_transport_state(&mut self) {
+        self.emit(SchedulerEvent::TransportChanged {
+            state: self.transport_state,
+        });
+    }
 
-        // Loop wrap logic
-        if self.looping_enabled && self.current_frame >= self.loop_end_frame {
-            self.current_frame = self.loop_start_frame;
-            self.tempo_clock.reset();
-            self.tempo_clock.advance_by(self.current_frame); // Sync tick position to loop start
+    fn schedule(&mut self, track: Box<dyn Track>, start_frame: u64) {
+        self.process_command(SchedulerCommand::ScheduleChannel {
+            channel: Box::new(Channel::new(track)),
+            start_frame,
+        });
+    }
+
+    /// Parked clips join the mixer right away, scenes launch them rather than the timeline.
+    /// Channels past [`MAX_ACTIVE_TRACKS`], counting the ones still waiting for their start,
+    /// are retired instead, returning `false`.
+    fn schedule_channel(&mut self, channel: Box<Channel>, start_frame: u64) -> bool {
+        if self.mixer.channel_count() + self.scheduled.len() >= MAX_ACTIVE_TRACKS {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, channel);
+            if let Some(diagnostics) = self.diagnostics.as_mut() {
+                diagnostics.warn("mixer full, scheduled track dropped");
+            }
+            return false;
+        }
+        if channel.is_parked() {
+            self.mixer.add_channel(channel);
+        } else {
+            self.scheduled.push(ScheduledTrack {
+                channel,
+                start_frame,
+            });
         }
+        true
+    }
 
+    /// Convenience wrapper around [`Scheduler::render`] that allocates its output.
+    /// Not for use on the audio thread.
+    pub fn next_samples(&mut self, frame_size: usize) -> AudioBuffer {
+        let mut buffer = AudioBuffer::stereo(frame_size);
+        self.render(&mut buffer);
         buffer
     }
 
-    fn stop_track(&mut self, target_id: String) {
-        self.active_tracks.retain(|track| track.id() != target_id);
+    /// Renders the next `output.frames()` frames into the stereo buffer `output`.
+    ///
+    /// Work is split into blocks of at most [`MAX_BLOCK_FRAMES`] (or the fixed size set with
+    /// [`Scheduler::set_block_size`]) so the preallocated scratch buffers are always large
+    /// enough, keeping the steady state allocation free.
+    pub fn render(&mut self, output: &mut AudioBuffer) {
+        #[cfg(feature = "rt-audit")]
+        let _audit = crate::rt_audit::RealtimeScope::enter();
+        let started = self.metering_start();
+        let frames = output.frames();
+        self.render_frames(output, frames);
+        self.finish_callback(started, frames);
     }
 
-    pub fn current_tick(&self) -> u64 {
-        self.tempo_clock.current_tick()
+    /// Fills the first `frames` frames of `output`, in variable or fixed-size blocks
+    fn render_frames(&mut self, output: &mut AudioBuffer, frames: usize) {
+        // scrubbing skips the fixed-size blocks, the extra block of latency would lag the drag
+        let Some(block_size) = self.block_size.filter(|_| self.scrub.is_none()) else {
+            let mut start = 0;
+            while start < frames {
+                // commands can move the boundaries, apply them before looking
+                self.process_commands();
+                let len = (frames - start)
+                    .min(MAX_BLOCK_FRAMES)
+                    .min(self.frames_to_boundary());
+                self.render_block(output, start, len);
+                start += len;
+            }
+            return;
+        };
+
+        // take the pending block out so `render_block` can borrow `self` mutably
+        let mut block = std::mem::take(&mut self.pending_block);
+        let mut written = 0;
+        while written < frames {
+            if self.pending_read >= block.frames() {
+                // the block is split at boundaries too, so events land on their exact frame
+                block.set_frames(block_size);
+                let mut filled = 0;
+                while filled < block_size {
+                    self.process_commands();
+                    let len = (block_size - filled).min(self.frames_to_boundary());
+                    self.render_block(&mut block, filled, len);
+                    filled += len;
+                }
+                self.pending_read = 0;
+            }
+            let len = (block.frames() - self.pending_read).min(frames - written);
+            output.copy_from(written, &block, self.pending_read, len);
+            self.pending_read += len;
+            written += len;
+        }
+        self.pending_block = block;
     }
 
-    pub fn tick_phase(&self) -> f64 {
-        self.tempo_clock.tick_phase()
+    /// Frames until the next track start, loop end, play range end or scene launch, where a
+    /// block has to end so the output doesn't depend on how the device sizes its callbacks
+    fn frames_to_boundary(&self) -> usize {
+        if self.transport_state != TransportState::Playing || self.scrub.is_some() {
+            return usize::MAX;
+        }
+        let loop_end = self.looping_enabled.then_some(self.loop_end_frame);
+        let range_end = self.play_range.map(|(_, stop)| stop);
+        let track_start = self.scheduled.peek().map(ScheduledTrack::play_frame);
+        let launch = self.scenes.is_pending().then(|| self.next_bar_frame());
+        [loop_end, range_end, track_start, launch]
+            .into_iter()
+            .flatten()
+            .filter(|&frame| frame > self.current_frame)
+            .map(|frame| usize::try_from(frame - self.current_frame).unwrap_or(usize::MAX))
+            .min()
+            .unwrap_or(usize::MAX)
     }
 
-    fn bbt_to_tick_count(&self, loop_points: &LoopPoints, start: bool) -> u64 {
-        let (bar, beat, tick) = if start {
-            (
-                loop_points.start_bar,
-                loop_points.start_beat,
-                loop_points.start_tick,
-            )
-        } else {
-            (
-                loop_points.end_bar,
-                loop_points.end_beat,
-                loop_points.end_tick,
-            )
+    /// Start time of a measurement, `None` while metering is off
+    fn metering_start(&mut self) -> Option<Instant> {
+        self.callback_frame = self.current_frame;
+        if let Some(diagnostics) = self.diagnostics.as_mut() {
+            diagnostics.set_frame(self.current_frame);
+        }
+        self.callback_latency = self.output_latency();
+        self.snapshots.as_ref().and_then(|_| cpu::now())
+    }
+
+    /// Records the callback's CPU load and publishes a snapshot
+    fn finish_callback(&mut self, started: Option<Instant>, frames: usize) {
+        if self.snapshots.is_none() {
+            return;
+        }
+        let idle = self.is_idle();
+        let Some(snapshots) = self.snapshots.as_mut() else {
+            return;
         };
 
-        let ticks_per_beat = self.tempo_clock.ticks_per_beat;
-        let beats_per_bar = self.tempo_clock.time_signature.beats_per_bar;
+        if let Some(started) = started {
+            let (elapsed, deadline) = (started.elapsed(), cpu::deadline(frames, self.sample_rate));
+            self.callback_load.record(elapsed, deadline);
+            if elapsed > deadline
+                && let Some(diagnostics) = self.diagnostics.as_mut()
+            {
+                diagnostics.warn("render call took longer than the audio it rendered");
+            }
+        }
+
+        snapshots.publish(|snapshot| {
+            snapshot.current_frame = self.current_frame;
+            let moving = self.transport_state == TransportState::Playing && self.scrub.is_none();
+            snapshot.playhead = Playhead {
+                frame: if moving {
+                    self.callback_frame
+                        .saturating_sub(self.callback_latency as u64)
+                } else {
+                    self.callback_frame
+                },
+                timestamp: started,
+                rate: if moving { self.sample_rate } else { 0.0 },
+            };
+            snapshot.transport_state = self.transport_state;
+            snapshot.idle = idle;
+            snapshot.cpu_load = self.callback_load.percent();
+            snapshot.loudness = self.loudness.as_ref().map(LoudnessMeter::reading);
+            snapshot.correlation = self.correlation.as_ref().map(CorrelationMeter::correlation);
+            snapshot.headroom = self.mixer.headroom_status();
+            snapshot.set_tracks(
+                self.mixer.channels().map(|channel| {
+                    (
+                        channel.id(),
+                        channel.group(),
+                        channel.metadata(),
+                        channel.cpu_load(),
+                        channel.input_level(),
+                        channel.automation_status(),
+                    )
+                }),
+                |metadata| {
+                    Self::retire(&mut self.garbage, &mut self.diagnostics, metadata);
+                },
+            );
+        });
+    }
+
+    /// Renders frames `start..start + frame_size` of `output`
+    fn render_block(&mut self, output: &mut AudioBuffer, start: usize, frame_size: usize) {
+        for channel in 0..output.channels() {
+            output.channel_mut(channel)[start..start + frame_size].fill(0.0);
+        }
 
-        let total_ticks = ((bar - 1) * beats_per_bar * ticks_per_beat)
-            + ((beat - 1) * ticks_per_beat)
-            + (tick - 1);
+        self.process_commands();
+        self.advance_mix_morph(frame_size);
 
-        total_ticks
+        let frame = self.current_frame;
+        self.render_timeline(output, start, frame_size);
+        if let Some(cv) = self.cv_outputs.as_mut() {
+            let rolling = self.transport_state == TransportState::Playing && self.scrub.is_none();
+            cv.render(&self.mixer, frame, frame_size, rolling);
+        }
+        self.render_preview(output, start, frame_size);
     }
 
-    fn fill_sample<T>(&self, data: &mut [T], samples: &[(f32, f32)])
-    where
-        T: cpal::FromSample<f32>,
-    {
-        for (i, sample) in data.iter_mut().enumerate() {
-            let channel = i % 2; // wrap 
-            let raw_sample = if channel == 0 {
-                samples[i / 2].0
-            } else {
-                samples[i / 2].1
-            };
-            *sample = raw_sample.to_sample::<T>();
+    /// Applies the commands sent since the last block
+    fn process_commands(&mut self) {
+        while let Ok(cmd) = self.automation_events.pop() {
+            self.process_command(cmd);
         }
     }
 
-    pub fn get_timeline_position(&self) -> TimelinePosition {
-        let (bar, beat, tick_within_beat) = self.tempo_clock.bar_beat_tick();
-        let tick = self.current_tick();
+    /// Plays the input of armed tracks monitoring it while paused, see
+    /// [`MonitorMode`](crate::punch::MonitorMode). The playhead and the tracks stay put.
+    fn render_monitoring(&mut self, output: &mut AudioBuffer, start: usize, frame_size: usize) {
+        if self.transport_state != TransportState::Paused
+            || !self
+                .mixer
+                .channels()
+                .any(|channel| channel.hears_input(false))
+        {
+            self.mixer.render_talkback(frame_size);
+            return;
+        }
+        self.mixer.set_rolling(false);
+        self.mixer.mix_block(
+            output,
+            start,
+            frame_size,
+            Some(self.current_frame),
+            None,
+            |_| {},
+        );
+        self.mixer.set_rolling(true);
+    }
 
-        TimelinePosition {
-            bar,
-            beat,
-            tick,
-            current_frame: self.current_frame,
-            tick_within_beat,
+    /// Mixes the preview voice into frames `start..start + frame_size`, after the master
+    /// bus processing so it never ends up in meters or bounces
+    fn render_preview(&mut self, output: &mut AudioBuffer, start: usize, frame_size: usize) {
+        let Some(preview) = self.preview.as_mut() else {
+            return;
+        };
+        self.preview_buffer.set_frames(frame_size);
+        preview.fill_next_samples(&mut self.preview_buffer);
+        output.add_from(&self.preview_buffer, start);
+
+        if preview.is_finished()
+            && let Some(finished) = self.preview.take()
+        {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, finished);
+            self.emit(SchedulerEvent::PreviewFinished);
         }
     }
-}
 
-impl AudioSource for Scheduler {
-    fn fill_buffer(&mut self, buffer: AudioSourceBufferKind<'_>, frame_size: usize) {
-        let stereo_samples = self.next_samples(frame_size);
+    /// Plays the arrangement, or the scrub head, into frames `start..start + frame_size`
+    fn render_timeline(&mut self, output: &mut AudioBuffer, start: usize, frame_size: usize) {
+        if let Some(scrub) = self.scrub.as_mut() {
+            let sources = self
+                .mixer
+                .channels()
+                .map(|channel| (channel, channel.play_frame()))
+                .chain(self.scheduled.iter().map(|track| {
+                    (
+                        track.channel.as_ref(),
+                        track.start_frame as i64 + track.channel.offset(),
+                    )
+                }));
+            scrub.render(sources, output, start, frame_size);
+            self.current_frame = scrub.position();
+            self.locate_video();
+            self.mixer.render_talkback(frame_size);
+            return;
+        }
 
-        match buffer {
-            AudioSourceBufferKind::F32(data) => {
-                self.fill_sample(data, &stereo_samples[..]);
-            }
-            AudioSourceBufferKind::I16(data) => {
-                self.fill_sample(data, &stereo_samples[..]);
-            }
-            AudioSourceBufferKind::U16(data) => {
-                self.fill_sample(data, &stereo_samples[..]);
+        if self.transport_state != TransportState::Playing {
+            self.render_monitoring(output, start, frame_size);
+            return;
+        }
+
+        while let Some(top) = self.scheduled.peek() {
+            if top.play_frame() <= self.current_frame {
+                let ScheduledTrack {
+                    mut channel,
+                    start_frame,
+                } = self.scheduled.pop().unwrap();
+                channel.set_start_frame(start_frame);
+                self.mixer.add_channel(channel);
+            } else {
+                break;
             }
         }
-    }
-}
 
-//@todo move this guys to somewhere else, anywhere.. just get them tf out this file
-#[cfg(test)]
-mod test_util {
-    use crate::scheduler::{Scheduler, command::SchedulerCommand};
-    use rtrb::{Producer, RingBuffer};
-    use transport::clock::TempoClock;
+        if self.scenes.is_pending() && self.next_bar_frame() == self.current_frame {
+            self.launch_scene();
+        }
+
+        let deadline = self
+            .snapshots
+            .as_ref()
+            .map(|_| cpu::deadline(frame_size, self.sample_rate));
+        let events = &mut self.events;
+        self.mixer.mix_block(
+            output,
+            start,
+            frame_size,
+            Some(self.current_frame),
+            deadline,
+            |channel| {
+                if let Some(events) = events.as_mut() {
+                    let _ = events.push(SchedulerEvent::TrackFinished {
+                        target_id: Arc::clone(channel.shared_id()),
+                    });
+                }
+            },
+        );
+
+        let block_end = self.current_frame + frame_size as u64;
+        let mut from = self.current_frame;
+        while let Some((frame, enabled)) =
+            self.mixer.record_automation().next_change(from, block_end)
+        {
+            self.emit(SchedulerEvent::RecordEnabled { frame, enabled });
+            if !enabled {
+                self.recording_stopped();
+            }
+            from = frame + 1;
+        }
+
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.process(output, start, frame_size);
+        }
+        if let Some(loudness) = self.loudness.as_mut() {
+            loudness.process(output, start, frame_size);
+        }
+        if let Some(correlation) = self.correlation.as_mut() {
+            correlation.process(output, start, frame_size);
+        }
+        if let Some(goniometer) = self.goniometer.as_mut() {
+            goniometer.process(output, start, frame_size);
+        }
+        if let Some(stems) = self.mixer.stem_tap_mut() {
+            stems.send(MASTER_STEM, output, start, frame_size, 1.0);
+        }
+
+        let (bar_before, _, _) = self.tempo_clock.bar_beat_tick();
+        let block_frame = self.current_frame;
+
+        // Advance the tempo clock by the number of samples processed
+        self.tempo_clock.advance_by(frame_size as u64);
+        self.current_frame += frame_size as u64;
+        self.play_video(block_frame, self.current_frame);
+
+        // Loop wrap logic
+        if self.looping_enabled && self.current_frame >= self.loop_end_frame {
+            let recorded = self
+                .mixer
+                .record_automation()
+                .is_enabled_at(self.loop_end_frame.saturating_sub(1));
+            self.seek(self.loop_start_frame); // Sync tick position to loop start
+            self.loop_passes = self.loop_passes.saturating_add(1);
+            self.emit(SchedulerEvent::LoopPassCompleted {
+                pass: self.loop_passes,
+            });
+            if recorded
+                && self
+                    .loop_record
+                    .stop_after_passes
+                    .is_some_and(|passes| self.loop_passes >= passes)
+            {
+                self.transport_state = TransportState::Paused;
+                self.emit_transport_state();
+                self.recording_stopped();
+            }
+        }
+
+        if let Some((range_start, stop)) = self.play_range
+            && self.current_frame >= stop
+        {
+            self.play_range = None;
+            self.transport_state = TransportState::Paused;
+            self.seek(range_start);
+            self.emit_transport_state();
+        }
+
+        let (bar, _, _) = self.tempo_clock.bar_beat_tick();
+        if bar != bar_before {
+            self.emit(SchedulerEvent::BarStarted { bar });
+        }
+
+        if self.meters.is_some() {
+            let peak_left = output.peak(0, start, frame_size);
+            let peak_right = output.peak(1, start, frame_size);
+            let frame = block_frame.saturating_sub(self.limiter_latency() as u64);
+            if let Some(meters) = self.meters.as_mut() {
+                let _ = meters.push(SchedulerEvent::Meter {
+                    frame,
+                    peak_left,
+                    peak_right,
+                });
+            }
+        }
+    }
+
+    /// First frame of the bar at or after the playhead
+    fn next_bar_frame(&self) -> u64 {
+        let ticks_per_bar = self.tempo_clock.ticks_per_bar().max(1);
+        let tick = self.tempo_clock.current_tick() + u64::from(self.tempo_clock.tick_phase() > 0.0);
+        let bar = tick.div_ceil(ticks_per_bar) * ticks_per_bar;
+        self.tick_to_frame(bar).max(self.current_frame)
+    }
+
+    /// Carries out the scene launch waiting, from the playhead. Held back while the launch
+    /// ring has no room for its events.
+    fn launch_scene(&mut self) {
+        if let Some(launches) = self.launches.as_ref()
+            && launches.slots() < self.scenes.pending_reports()
+        {
+            if let Some(diagnostics) = self.diagnostics.as_mut() {
+                diagnostics.warn("launch queue full, scene launch held back");
+            }
+            return;
+        }
+        let mut events = self.launches.as_mut().or(self.events.as_mut());
+        self.scenes
+            .launch_pending(&mut self.mixer, self.current_frame, |event| {
+                if let Some(events) = events.as_mut() {
+                    let _ = events.push(event);
+                }
+            });
+    }
+
+    /// Whether the transport is rolling over a recorded frame
+    fn is_recording(&self) -> bool {
+        self.transport_state == TransportState::Playing
+            && self
+                .mixer
+                .record_automation()
+                .is_enabled_at(self.current_frame)
+    }
+
+    /// Switches looping off after a take if [`LoopRecordOptions`] asks for it
+    const fn recording_stopped(&mut self) {
+        if self.loop_record.disable_loop_on_record_stop {
+            self.looping_enabled = false;
+        }
+    }
+
+    /// Records and punches armed tracks inside `start_tick..end_tick` only, replacing the
+    /// record enable changes in place
+    fn auto_punch(&mut self, start_tick: u64, end_tick: u64) {
+        let (start, end) = (self.tick_to_frame(start_tick), self.tick_to_frame(end_tick));
+        let ramp = (PUNCH_RAMP_SECONDS * self.sample_rate).round() as usize;
+        self.mixer.clear_record_enable();
+        let punched = self.mixer.set_record_enable(start, true)
+            && self.mixer.set_record_enable(end.max(start), false);
+        if !punched && let Some(diagnostics) = self.diagnostics.as_mut() {
+            diagnostics.warn("record enable list full, punch dropped");
+        }
+        self.mixer.set_punch(Some((start, end)), ramp);
+    }
+
+    /// Meters a block of device input on every armed track, for setting levels before a
+    /// take, and keeps it to be heard inside the punch range. Call it from the input
+    /// stream's callback with the stereo input block.
+    pub fn meter_input(&mut self, input: &AudioBuffer) {
+        let frames = input.frames();
+        for channel in self.mixer.channels_mut() {
+            channel.meter_input(
+                input,
+                0,
+                frames,
+                self.sample_rate,
+                self.meter_ballistics.peak_release_db_per_second,
+            );
+        }
+    }
+
+    /// Keeps a block of the talkback mic, mono or stereo, for the cue busses while talking,
+    /// see [`MonitorChange::SetTalkback`](crate::monitor::MonitorChange::SetTalkback). Call
+    /// it from the input stream's callback.
+    pub fn talkback_input(&mut self, input: &AudioBuffer) {
+        self.mixer.talkback_input(input, 0, input.frames());
+    }
+
+    /// `true` when nothing is queued and every active track has run out of material
+    pub fn is_idle(&self) -> bool {
+        self.scheduled.is_empty() && self.mixer.channels().all(Channel::is_finished)
+    }
+
+    fn stop_track(&mut self, target_id: String) {
+        let mut removed = false;
+        while let Some(channel) = self.mixer.remove_channel(&target_id) {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, channel);
+            removed = true;
+        }
+
+        if removed {
+            self.emit(SchedulerEvent::TrackRemoved { target_id });
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.tempo_clock.current_tick()
+    }
+
+    pub fn tick_phase(&self) -> f64 {
+        self.tempo_clock.tick_phase()
+    }
+
+    /// Timeline frame of a 1-based bar, beat and tick position, as used for loop points
+    pub fn bbt_to_frame(&self, bar: u64, beat: u64, tick: u64) -> u64 {
+        self.tick_to_frame(self.bbt_to_ticks(bar, beat, tick))
+    }
+
+    /// `(start, end)` frames of the loop while looping is enabled
+    pub fn loop_frames(&self) -> Option<(u64, u64)> {
+        (self.looping_enabled && self.loop_points.is_some())
+            .then_some((self.loop_start_frame, self.loop_end_frame))
+    }
+
+    /// Stops feeding new material: queued tracks are dropped and active tracks stop playing
+    /// while their inserts keep running, so effect tails ring out
+    pub fn release_sources(&mut self) {
+        for track in self.scheduled.drain() {
+            Self::retire(&mut self.garbage, &mut self.diagnostics, track.channel);
+        }
+        for channel in self.mixer.channels_mut() {
+            channel.release();
+        }
+    }
+
+    fn bbt_to_tick_count(&self, loop_points: &LoopPoints, start: bool) -> u64 {
+        let (bar, beat, tick) = if start {
+            (
+                loop_points.start_bar,
+                loop_points.start_beat,
+                loop_points.start_tick,
+            )
+        } else {
+            (
+                loop_points.end_bar,
+                loop_points.end_beat,
+                loop_points.end_tick,
+            )
+        };
+
+        self.bbt_to_ticks(bar, beat, tick)
+    }
+
+    fn bbt_to_ticks(&self, bar: u64, beat: u64, tick: u64) -> u64 {
+        timebase::bbt_to_ticks(
+            (bar, beat, tick),
+            self.tempo_clock.ticks_per_beat,
+            self.tempo_clock.time_signature.beats_per_bar,
+        )
+    }
+
+    /// Renders into an interleaved device buffer of `channels` channels, block by block,
+    /// through the SRC stage, if any, and the monitor section.
+    ///
+    /// Callbacks can be any size, including empty; a partial frame at the end is silenced.
+    fn render_interleaved<T>(&mut self, data: &mut [T], channels: usize)
+    where
+        T: FromSample<f32>,
+    {
+        #[cfg(feature = "rt-audit")]
+        let _audit = crate::rt_audit::RealtimeScope::enter();
+        let whole = data.len() / channels.max(1) * channels;
+        let (data, partial) = data.split_at_mut(whole);
+        partial.fill_with(|| 0.0f32.to_sample());
+        if channels == 0 {
+            return;
+        }
+        let started = self.metering_start();
+        // take the scratch buffer out so `render_block` can borrow `self` mutably
+        let mut stereo = std::mem::take(&mut self.output_buffer);
+        let mut resampler = self.resampler.take();
+        for chunk in data.chunks_mut(MAX_BLOCK_FRAMES * channels) {
+            let frames = chunk.len() / channels;
+            stereo.set_frames(frames);
+            match resampler.as_mut() {
+                Some(resampler) => resampler.process(&mut stereo, |input| {
+                    self.render_frames(input, input.frames());
+                }),
+                None => self.render_frames(&mut stereo, frames),
+            }
+            self.monitor.write_interleaved(&stereo, chunk, channels);
+            if let Some((cue, left, right)) = self.cue_output.as_mut() {
+                for frame in chunk.chunks_mut(channels) {
+                    let (cue_left, cue_right) = cue.next_frame();
+                    if let Some(sample) = frame.get_mut(*left) {
+                        *sample = cue_left.to_sample();
+                    }
+                    if let Some(sample) = frame.get_mut(*right) {
+                        *sample = cue_right.to_sample();
+                    }
+                }
+            }
+            if let Some(cv) = self.cv_outputs.as_mut() {
+                cv.write_interleaved(chunk, channels);
+            }
+        }
+        self.resampler = resampler;
+        self.output_buffer = stereo;
+        self.finish_callback(started, data.len() / channels);
+    }
+
+    pub fn get_timeline_position(&self) -> TimelinePosition {
+        let (bar, beat, tick_within_beat) = self.tempo_clock.bar_beat_tick();
+        let tick = self.current_tick();
+
+        TimelinePosition {
+            bar,
+            beat,
+            tick,
+            current_frame: self.current_frame,
+            tick_within_beat,
+        }
+    }
+}
+
+impl AudioSource for Scheduler {
+    fn fill_buffer(&mut self, buffer: AudioSourceBufferKind<'_>, frame_size: usize) {
+        let channels = |len: usize| len.checked_div(frame_size).unwrap_or(2);
+        match buffer {
+            AudioSourceBufferKind::F32(data) => self.render_interleaved(data, channels(data.len())),
+            AudioSourceBufferKind::I16(data) => self.render_interleaved(data, channels(data.len())),
+            AudioSourceBufferKind::U16(data) => self.render_interleaved(data, channels(data.len())),
+        }
+    }
+}
+
+//@todo move this guys to somewhere else, anywhere.. just get them tf out this file
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::scheduler::{
+        Scheduler,
+        command::SchedulerCommand,
+        event::{SchedulerEvent, SchedulerEventConsumer},
+    };
+    use rtrb::{Producer, RingBuffer};
+    use transport::clock::TempoClock;
+
+    use crate::device_manager::{AudioSource as _, AudioSourceBufferKind};
 
     pub fn create_scheduler_with_channel() -> (Scheduler, Producer<SchedulerCommand>) {
         let (producer, consumer) = RingBuffer::new(32);
@@ -283,226 +1477,1344 @@ mod test_util {
             44100.0,
             transport::resolution::TickResolution::Sixteenth,
         );
-        let scheduler = Scheduler::new(consumer, tempo_clock);
-        (scheduler, producer)
+        let scheduler = Scheduler::new(consumer, tempo_clock);
+        (scheduler, producer)
+    }
+
+    /// Drives `scheduler` the way a device does, through interleaved stereo callbacks sized
+    /// by cycling through `sizes`, until `frames` frames are rendered. Returns the output.
+    pub fn render_irregular(scheduler: &mut Scheduler, sizes: &[usize], frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames * 2];
+        let mut written = 0;
+        for &size in sizes.iter().cycle() {
+            if written == frames {
+                break;
+            }
+            let size = size.min(frames - written);
+            let data = &mut output[written * 2..(written + size) * 2];
+            scheduler.fill_buffer(AudioSourceBufferKind::F32(data), size);
+            written += size;
+        }
+        output
+    }
+
+    /// Pops every pending event
+    pub fn drain_events(events: &mut SchedulerEventConsumer) -> Vec<SchedulerEvent> {
+        std::iter::from_fn(|| events.pop().ok()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rtrb::RingBuffer;
+    use transport::{display::FrameRate, resolution::TickResolution, timecode::TimecodeGrid};
+
+    use super::*;
+    use crate::{
+        automation::{AutomatedParameter, AutomationMode},
+        constants::AUDIO_SAMPLE_EPSILON,
+        cv::CvSource,
+        midi::EventKind,
+        mix_snapshot::{MixSnapshotChange, MixSnapshots},
+        monitor::{MonitorChange, SpeakerSet},
+        punch::MonitorMode,
+        scene::{ClipLaunch, Scene, SceneChange},
+        scheduler::command::{ChannelChange, ParameterChange},
+        track::{
+            constant::ConstantTrack, gainpan::GainPanTrack, sinewave::SineWaveTrack, wav::WavTrack,
+        },
+    };
+
+    fn sum_energy(buffer: &AudioBuffer) -> f32 {
+        buffer.iter_frames().map(|(l, r)| l.abs() + r.abs()).sum()
+    }
+
+    #[test]
+    fn test_track_scheduled_at_zero_plays_immediately() {
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(ConstantTrack::new(0.1, 0.1)), 0);
+        sched.process_command(SchedulerCommand::Play);
+
+        let output = sched.next_samples(4);
+        assert_eq!(output.frames(), 4);
+        assert!(sum_energy(&output) > 0.0);
+    }
+
+    #[test]
+    fn test_track_scheduled_in_future_does_not_play_early() {
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(ConstantTrack::new(1.0, 1.0)), 100);
+        sched.process_command(SchedulerCommand::Play);
+
+        let output = sched.next_samples(10); // still before frame 100
+        assert_eq!(output.frames(), 10);
+        assert!(sum_energy(&output) == 0.0);
+
+        sched.next_samples(80);
+        sched.next_samples(10); // now at frame 100
+
+        let output = sched.next_samples(1);
+        assert!(sum_energy(&output) > 0.0);
+    }
+
+    #[test]
+    fn test_multiple_tracks_mixed_properly() {
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(ConstantTrack::new(0.3, 0.3)), 0);
+        sched.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        sched.process_command(SchedulerCommand::Play);
+
+        let output = sched.next_samples(1);
+        let (l, r) = output.frame(0);
+        assert!((l - 0.8).abs() < AUDIO_SAMPLE_EPSILON);
+        assert!((r - 0.8).abs() < AUDIO_SAMPLE_EPSILON);
+    }
+
+    #[test]
+    fn test_track_starts_midway_and_mixes_correctly() {
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.process_command(SchedulerCommand::Play);
+        sched.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 4); // start late
+
+        let out1 = sched.next_samples(4);
+        assert!(sum_energy(&out1) == 0.0); // silent
+
+        let out2 = sched.next_samples(2);
+        assert_eq!(out2.frames(), 2);
+        assert!((out2.frame(0).0 - 0.5).abs() < AUDIO_SAMPLE_EPSILON);
+    }
+
+    #[test]
+    fn test_gain_change_applies_during_playback() {
+        let gain_track =
+            GainPanTrack::new("x-track", Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+
+        scheduler.schedule(Box::new(gain_track), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(1); // activate
+
+        scheduler.process_command(SchedulerCommand::ParamChange {
+            target_id: "x-track".to_string(),
+            change: ParameterChange::SetGain(0.25),
+        });
+
+        let output = scheduler.next_samples(1);
+        assert!((output.frame(0).0 - 0.25).abs() < AUDIO_SAMPLE_EPSILON); // (1.0 * 0.25)
+        assert!((output.frame(0).1 - 0.25).abs() < AUDIO_SAMPLE_EPSILON);
+    }
+
+    #[test]
+    fn test_param_change_reaches_wrapped_track_by_path() {
+        let inner = GainPanTrack::new("inner", Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+        let outer = GainPanTrack::new("outer", Box::new(inner), 1.0, 0.0);
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(outer), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(1);
+
+        scheduler.process_command(SchedulerCommand::ParamChange {
+            target_id: "outer/inner".to_owned(),
+            change: ParameterChange::SetGain(0.5),
+        });
+        // not a channel, so nothing is addressed
+        scheduler.process_command(SchedulerCommand::ParamChange {
+            target_id: "inner".to_owned(),
+            change: ParameterChange::SetGain(0.0),
+        });
+
+        let output = scheduler.next_samples(1);
+        assert!((output.frame(0).0 - 0.5).abs() < AUDIO_SAMPLE_EPSILON); // 1.0 * 0.5 * 1.0
+    }
+
+    #[test]
+    fn test_channel_changes_apply_during_playback() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        let mut channel = Channel::new(Box::new(GainPanTrack::new(
+            "pad",
+            Box::new(ConstantTrack::new(1.0, 1.0)),
+            1.0,
+            0.0,
+        )));
+        channel.set_gain(0.5);
+        scheduler.process_command(SchedulerCommand::ScheduleChannel {
+            channel: Box::new(channel),
+            start_frame: 0,
+        });
+        scheduler.process_command(SchedulerCommand::Play);
+        let output = scheduler.next_samples(1);
+        assert!((output.frame(0).0 - 1.0).abs() < AUDIO_SAMPLE_EPSILON); // 0.5 + 1.0 * 0.5
+
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "pad".into(),
+            change: ChannelChange::SetPan(1.0),
+        });
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "pad".into(),
+            change: ChannelChange::MoveTo(0),
+        });
+        let output = scheduler.next_samples(1);
+        assert_eq!(output.frame(0), (0.5, 1.0));
+        assert_eq!(scheduler.mixer().channels().next().unwrap().id(), "pad");
+    }
+
+    #[test]
+    fn test_mix_snapshot_recall_morphs_during_playback() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(8);
+        scheduler.set_event_producer(event_prod);
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(1);
+        scheduler.process_command(SchedulerCommand::MixSnapshots(MixSnapshotChange::capture(
+            "a",
+        )));
+        let mut events = test_util::drain_events(&mut event_cons);
+        let Some(SchedulerEvent::MixCaptured { capture }) = events.pop() else {
+            panic!("the capture is answered");
+        };
+        assert_eq!(capture.snapshot().name, "a");
+        let mut snapshots = MixSnapshots::new();
+        snapshots.store(capture.into_snapshot());
+
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "constant-track".into(),
+            change: ChannelChange::SetGain(0.0),
+        });
+        scheduler.process_command(SchedulerCommand::MixSnapshots(
+            snapshots.recall("a", 64).unwrap(),
+        ));
+        let output = scheduler.next_samples(32);
+        // halfway back to unity gain
+        assert!((output.frame(0).0 - 0.25).abs() < AUDIO_SAMPLE_EPSILON);
+        let output = scheduler.next_samples(32);
+        assert!((output.frame(0).0 - 0.5).abs() < AUDIO_SAMPLE_EPSILON);
+        assert!(!scheduler.is_morphing_mix());
+    }
+
+    #[test]
+    fn test_channels_past_the_mixer_capacity_are_retired() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(MAX_ACTIVE_TRACKS + 1);
+        let (garbage, mut collector) = garbage::garbage_channel(4);
+        scheduler.set_event_producer(event_prod);
+        scheduler.set_garbage_producer(garbage);
+        let clip = |index: usize| {
+            let track = ConstantTrack::new(0.5, 0.5);
+            let mut channel = Channel::new(Box::new(GainPanTrack::new(
+                &format!("clip-{index}"),
+                Box::new(track),
+                1.0,
+                0.0,
+            )));
+            channel.set_parked(index.is_multiple_of(2));
+            Box::new(channel)
+        };
+        for index in 0..=MAX_ACTIVE_TRACKS {
+            scheduler.process_command(SchedulerCommand::ScheduleChannel {
+                channel: clip(index),
+                start_frame: 0,
+            });
+        }
+
+        // parked clips and tracks waiting for their start both count
+        assert_eq!(
+            scheduler.mixer().channel_count() + scheduler.scheduled.len(),
+            MAX_ACTIVE_TRACKS
+        );
+        assert_eq!(
+            test_util::drain_events(&mut event_cons).len(),
+            MAX_ACTIVE_TRACKS
+        );
+        assert_eq!(collector.collect(), 1);
+    }
+
+    #[test]
+    fn test_stop_track_removes_it_from_output() {
+        let gpt = GainPanTrack::new("test-id", Box::new(ConstantTrack::new(0.5, 0.5)), 1.0, 0.0);
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(gpt), 0);
+
+        sched.next_samples(1); // Activate
+
+        // Stop the track
+        sched.process_command(SchedulerCommand::StopTrack {
+            target_id: "test-id".into(),
+        });
+
+        let out = sched.next_samples(1);
+        assert_eq!(out.frame(0), (0.0, 0.0)); // No output = stopped
+    }
+
+    #[test]
+    fn test_restart_resets_playback_position() {
+        let samples = vec![(1.0, 1.0), (0.5, 0.5), (0.0, 0.0)];
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&samples));
+
+        let gain = GainPanTrack::new("track-id", Box::new(wav), 1.0, 0.0);
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(gain), 0);
+        sched.process_command(SchedulerCommand::Play);
+
+        let out1 = sched.next_samples(1); // (1.0, 1.0)
+        let out2 = sched.next_samples(1); // (0.5, 0.5)
+
+        sched.process_command(SchedulerCommand::RestartTrack {
+            target_id: "track-id".to_string(),
+        });
+
+        let out3 = sched.next_samples(1); // should reset to (1.0, 1.0)
+
+        assert_eq!(out1.frame(0), (1.0, 1.0));
+        assert_eq!(out2.frame(0), (0.5, 0.5));
+        assert_eq!(out3.frame(0), (1.0, 1.0)); // confirms retrigger
+    }
+
+    #[test]
+    fn test_schedule_command_adds_track_correctly() {
+        let (mut scheduler, mut producer) = test_util::create_scheduler_with_channel();
+        scheduler.process_command(SchedulerCommand::Play);
+
+        // Push command to ring
+        let success = producer.push(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ConstantTrack::new(0.4, 0.4)),
+            start_frame: 0,
+        });
+        assert!(success.is_ok(), "Should be able to enqueue command");
+
+        // Should now have one active track
+        let output = scheduler.next_samples(2);
+        assert_eq!(output.frames(), 2);
+        assert!((output.frame(0).0 - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_scheduled_track_via_command_plays_at_correct_time() {
+        let (mut scheduler, mut producer) = test_util::create_scheduler_with_channel();
+        scheduler.process_command(SchedulerCommand::Play);
+
+        // Send command for track to start at frame 3
+        producer
+            .push(SchedulerCommand::ScheduleTrack {
+                track: Box::new(ConstantTrack::new(0.2, 0.2)),
+                start_frame: 3,
+            })
+            .expect("Failed to enqueue");
+
+        // Advance scheduler past frame 3
+        let silent = scheduler.next_samples(3);
+        assert!(silent.iter_frames().all(|(l, r)| (l + r).abs() < 1e-6));
+
+        let active = scheduler.next_samples(1);
+        assert!((active.frame(0).0 - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_when_full() {
+        let (mut prod, mut cons) = RingBuffer::new(1);
+
+        // Fill it
+        prod.push(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ConstantTrack::new(0.0, 0.0)),
+            start_frame: 0,
+        })
+        .unwrap();
+
+        // Next push should fail
+        let result = prod.push(SchedulerCommand::ScheduleTrack {
+            track: Box::new(ConstantTrack::new(0.1, 0.1)),
+            start_frame: 0,
+        });
+
+        assert!(
+            result.is_err(),
+            "Second command should fail due to full ring buffer"
+        );
+
+        // Consume one and push again
+        let _ = cons.pop();
+        assert!(
+            prod.push(SchedulerCommand::ScheduleTrack {
+                track: Box::new(ConstantTrack::new(0.2, 0.2)),
+                start_frame: 0,
+            })
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_clock_advances_with_next_samples() {
+        let (mut scheduler, mut prod) = test_util::create_scheduler_with_channel();
+        prod.push(SchedulerCommand::Play).unwrap();
+
+        // 16th note = 5512.5 samples at 120 BPM
+        scheduler.next_samples(5513); // cross one 16th note
+        assert_eq!(scheduler.current_tick(), 30);
+    }
+
+    #[test]
+    fn test_scene_launches_on_the_next_bar() {
+        // a fixed block size must still launch on the bar, not on the next block start
+        for block_size in [None, Some(64)] {
+            let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+            scheduler.set_block_size(block_size).unwrap();
+            // room for a meter event per block
+            let (event_prod, mut event_cons) = RingBuffer::new(64);
+            scheduler.set_event_producer(event_prod);
+            let mut clip = Channel::new(Box::new(ConstantTrack::new(0.5, 0.5)));
+            clip.set_parked(true);
+            scheduler.schedule_channel(Box::new(clip), 0);
+            scheduler.process_command(SchedulerCommand::Scenes(SceneChange::Add(Scene {
+                name: "verse".into(),
+                note: Some(36),
+                launches: vec![ClipLaunch {
+                    lane: "drums".into(),
+                    clip: Some("constant-track".into()),
+                }],
+            })));
+            scheduler.process_command(SchedulerCommand::Play);
+            scheduler.next_samples(1000);
+            scheduler.process_command(SchedulerCommand::Scenes(SceneChange::Midi(
+                EventKind::NoteOn {
+                    channel: 0,
+                    key: 36,
+                    velocity: 127,
+                },
+            )));
+            test_util::drain_events(&mut event_cons);
+
+            // 120 BPM, 4/4: the next bar starts at frame 88200
+            let output = scheduler.next_samples(88200);
+            assert_eq!(output.frame(87199), (0.0, 0.0));
+            assert_eq!(output.frame(87200), (0.5, 0.5));
+            assert!(
+                test_util::drain_events(&mut event_cons)
+                    .iter()
+                    .any(|event| matches!(
+                        event,
+                        SchedulerEvent::SceneLaunched {
+                            scene: 0,
+                            frame: 88200
+                        }
+                    ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_launches_wait_for_room_in_their_ring() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(8);
+        let (launch_prod, mut launch_cons) = RingBuffer::new(3);
+        scheduler.set_event_producer(event_prod);
+        scheduler.set_launch_producer(launch_prod);
+        let mut clip = Channel::new(Box::new(ConstantTrack::new(0.5, 0.5)));
+        clip.set_parked(true);
+        scheduler.schedule_channel(Box::new(clip), 0);
+        scheduler.process_command(SchedulerCommand::Scenes(SceneChange::Add(Scene {
+            name: "verse".into(),
+            note: None,
+            launches: vec![ClipLaunch {
+                lane: "drums".into(),
+                clip: Some("constant-track".into()),
+            }],
+        })));
+
+        // stopped, so launches happen right away: the clip and the scene fill two slots
+        for _ in 0..2 {
+            scheduler.process_command(SchedulerCommand::Scenes(SceneChange::Launch(
+                "verse".into(),
+            )));
+        }
+        assert!(scheduler.scenes().is_pending());
+        assert_eq!(launch_cons.slots(), 2);
+        assert!(
+            !test_util::drain_events(&mut event_cons)
+                .iter()
+                .any(|event| matches!(
+                    event,
+                    SchedulerEvent::ClipLaunched { .. } | SchedulerEvent::SceneLaunched { .. }
+                ))
+        );
+
+        // once they're read the held back launch goes through
+        test_util::drain_events(&mut launch_cons);
+        scheduler.process_command(SchedulerCommand::Scenes(SceneChange::SetCrossfade(0)));
+        assert!(!scheduler.scenes().is_pending());
+        assert!(matches!(
+            &test_util::drain_events(&mut launch_cons)[..],
+            [
+                SchedulerEvent::ClipLaunched {
+                    scene: 0,
+                    launch: 0,
+                    frame: 0
+                },
+                SchedulerEvent::SceneLaunched { scene: 0, frame: 0 },
+            ]
+        ));
+    }
+    #[test]
+    fn test_bar_boundary_emits_bar_started() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(8);
+        scheduler.set_event_producer(event_prod);
+        scheduler.process_command(SchedulerCommand::Play);
+        test_util::drain_events(&mut event_cons);
+
+        // 120 BPM, 4/4: one bar = 2 seconds = 88200 samples
+        scheduler.next_samples(88199);
+        assert!(test_util::drain_events(&mut event_cons).is_empty());
+
+        scheduler.next_samples(2);
+        assert!(matches!(
+            test_util::drain_events(&mut event_cons)[..],
+            [SchedulerEvent::BarStarted { bar: 2 }]
+        ));
+    }
+
+    #[test]
+    fn test_finished_track_emits_event_once() {
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&[(0.5, 0.5); 2]));
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(8);
+        scheduler.set_event_producer(event_prod);
+        scheduler.schedule(
+            Box::new(GainPanTrack::new("one-shot", Box::new(wav), 1.0, 0.0)),
+            0,
+        );
+        scheduler.process_command(SchedulerCommand::Play);
+        test_util::drain_events(&mut event_cons);
+
+        scheduler.next_samples(1);
+        assert!(test_util::drain_events(&mut event_cons).is_empty());
+
+        scheduler.next_samples(4);
+        assert!(matches!(
+            &test_util::drain_events(&mut event_cons)[..],
+            [SchedulerEvent::TrackFinished { target_id }] if &**target_id == "one-shot"
+        ));
+
+        scheduler.next_samples(4);
+        assert!(test_util::drain_events(&mut event_cons).is_empty());
+    }
+
+    #[test]
+    fn test_transport_and_track_lifecycle_events() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(16);
+        scheduler.set_event_producer(event_prod);
+
+        scheduler.process_command(SchedulerCommand::ScheduleTrack {
+            track: Box::new(GainPanTrack::new(
+                "lead",
+                Box::new(ConstantTrack::new(0.1, 0.1)),
+                1.0,
+                0.0,
+            )),
+            start_frame: 0,
+        });
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(4);
+        scheduler.process_command(SchedulerCommand::StopTrack {
+            target_id: "lead".into(),
+        });
+        scheduler.process_command(SchedulerCommand::StopTrack {
+            target_id: "lead".into(),
+        });
+        scheduler.process_command(SchedulerCommand::Stop);
+
+        let events = test_util::drain_events(&mut event_cons);
+        assert!(matches!(
+            &events[..],
+            [
+                SchedulerEvent::TrackScheduled { target_id, start_frame: 0 },
+                SchedulerEvent::TransportChanged { state: TransportState::Playing },
+                SchedulerEvent::TrackRemoved { target_id: removed },
+                SchedulerEvent::TransportChanged { state: TransportState::Stopped },
+            ] if &**target_id == "lead" && removed == "lead"
+        ));
+    }
+
+    #[test]
+    fn test_meter_event_reports_block_peaks() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (meter_prod, mut meter_cons) = RingBuffer::new(8);
+        scheduler.set_meter_producer(meter_prod);
+        scheduler.schedule(Box::new(ConstantTrack::new(-0.75, 0.25)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+
+        scheduler.next_samples(8);
+
+        assert!(matches!(
+            meter_cons.pop(),
+            Ok(SchedulerEvent::Meter { frame: 0, peak_left, peak_right })
+                if peak_left == 0.75 && peak_right == 0.25
+        ));
+    }
+
+    #[test]
+    fn test_meters_dont_crowd_out_other_events() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(2);
+        let (meter_prod, _meter_cons) = RingBuffer::new(2);
+        scheduler.set_event_producer(event_prod);
+        scheduler.set_meter_producer(meter_prod);
+        scheduler.process_command(SchedulerCommand::Play);
+        for _ in 0..16 {
+            scheduler.next_samples(64);
+        }
+        scheduler.process_command(SchedulerCommand::Stop);
+
+        let events = test_util::drain_events(&mut event_cons);
+        assert!(matches!(
+            events.as_slice(),
+            [
+                SchedulerEvent::TransportChanged { .. },
+                SchedulerEvent::TransportChanged {
+                    state: TransportState::Stopped
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_render_splits_callbacks_longer_than_max_block() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.25, 0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+
+        let mut output = AudioBuffer::stereo(MAX_BLOCK_FRAMES * 2 + 3);
+        scheduler.render(&mut output);
+
+        assert!(output.iter_frames().all(|frame| frame == (0.25, 0.5)));
+        assert_eq!(scheduler.current_frame, output.frames() as u64);
+    }
+
+    #[test]
+    fn test_fill_buffer_writes_interleaved_device_samples() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.25, -0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+
+        let mut data = vec![0.0f32; (MAX_BLOCK_FRAMES + 10) * 2];
+        let frames = data.len() / 2;
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut data), frames);
+
+        assert!(data.chunks_exact(2).all(|frame| frame == [0.25, -0.5]));
+    }
+
+    #[test]
+    fn test_device_at_another_rate_plays_through_src() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.set_device_sample_rate(48000, ResampleQuality::Sinc);
+        assert_eq!(scheduler.device_sample_rate(), 48000);
+        scheduler.schedule(Box::new(ConstantTrack::new(0.25, -0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+
+        let mut data = vec![0.0f32; 4800 * 2];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut data), 4800);
+
+        // the timeline moves at the project's rate, a tenth of a second either way
+        assert_eq!(scheduler.current_frame, 4410);
+        assert!(
+            data[200..]
+                .chunks_exact(2)
+                .all(|frame| { (frame[0] - 0.25).abs() < 1e-3 && (frame[1] + 0.5).abs() < 1e-3 })
+        );
+    }
+
+    #[test]
+    fn test_monitor_routes_device_output_but_not_master() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.set_loudness_metering(true);
+        let alt = scheduler
+            .monitor_mut()
+            .add_speaker_set(SpeakerSet::new("Alt", 2, 3));
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.process_command(SchedulerCommand::Monitor(MonitorChange::SelectSpeakers(
+            alt,
+        )));
+        scheduler.process_command(SchedulerCommand::Monitor(MonitorChange::SetDim(true)));
+
+        let mut data = vec![0.0f32; 4800 * 4];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut data), 4800);
+
+        assert!(data.chunks_exact(4).all(|frame| {
+            frame[..2] == [0.0, 0.0] && (frame[2] - 0.05).abs() < 1e-6 && frame[2] == frame[3]
+        }));
+        // the meters still see the undimmed master bus at about -6 dBTP, not -26
+        let peak = scheduler.loudness().unwrap().true_peak();
+        assert!(peak > -7.0, "{peak}");
+    }
+
+    #[test]
+    fn test_seek_to_marker_moves_playhead() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddMarker {
+            name: "chorus".into(),
+            tick: 960, // bar 3, 4/4 at 120 ticks per beat
+        }));
+        scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddRegion {
+            name: "verse".into(),
+            start: 480,
+            end: 960,
+        }));
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+
+        scheduler.process_command(SchedulerCommand::SeekToMarker {
+            name: "chorus".into(),
+        });
+        assert_eq!(scheduler.get_timeline_position().bar, 3);
+        scheduler.process_command(SchedulerCommand::SeekToMarker {
+            name: "verse".into(),
+        });
+        assert_eq!(scheduler.get_timeline_position().bar, 2);
+        assert_eq!(scheduler.get_timeline_position().current_frame, 88200);
+
+        // unknown names leave the playhead alone
+        scheduler.process_command(SchedulerCommand::SeekToMarker {
+            name: "outro".into(),
+        });
+        assert_eq!(scheduler.get_timeline_position().bar, 2);
+        assert_eq!(scheduler.markers().markers().len(), 1);
+    }
+
+    #[test]
+    fn test_track_offset_moves_playback() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let samples: Vec<_> = (0..1000).map(|i| (i as f32, i as f32)).collect();
+        let mut channel = Channel::new(Box::new(WavTrack::from_buffer(AudioBuffer::from_frames(
+            &samples,
+        ))));
+        channel.set_offset(-32);
+        scheduler.schedule_channel(Box::new(channel), 64);
+        scheduler.process_command(SchedulerCommand::Play);
+
+        assert_eq!(scheduler.next_samples(32).frame(31), (0.0, 0.0));
+        // queued tracks come in early enough to play their offset
+        let output = scheduler.next_samples(32);
+        assert_eq!(output.frame(0), (0.0, 0.0));
+        assert_eq!(output.frame(5), (5.0, 5.0));
+
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "wav-track".to_owned(),
+            change: ChannelChange::SetOffset(0),
+        });
+        assert_eq!(scheduler.next_samples(32).frame(1), (1.0, 1.0));
+    }
+
+    #[test]
+    fn test_tracks_follow_the_playhead_after_seeking() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let samples: Vec<_> = (0..1000).map(|i| (i as f32, i as f32)).collect();
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&samples));
+        scheduler.schedule(Box::new(wav), 64);
+        scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddMarker {
+            name: "top".into(),
+            tick: 0,
+        }));
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+        let output = scheduler.next_samples(64);
+        assert_eq!(output.frame(63), (63.0, 63.0));
+
+        // back before the track's start: silent until the playhead reaches it again
+        scheduler.process_command(SchedulerCommand::SeekToMarker { name: "top".into() });
+        let output = scheduler.next_samples(128);
+        assert_eq!(output.frame(63), (0.0, 0.0));
+        assert_eq!(output.frame(65), (1.0, 1.0));
+        let output = scheduler.next_samples(1);
+        assert_eq!(output.frame(0), (64.0, 64.0));
+    }
+
+    #[test]
+    fn test_scrub_replaces_playback_and_moves_playhead() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let samples: Vec<_> = (0..1000).map(|i| (i as f32, i as f32)).collect();
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&samples));
+        scheduler.schedule(Box::new(wav), 100);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+
+        // not yet playing, but queued tracks are heard while scrubbing
+        scheduler.process_command(SchedulerCommand::Scrub(ScrubChange::Start));
+        scheduler.process_command(SchedulerCommand::Scrub(ScrubChange::Audition(Some(2.0))));
+        let output = scheduler.next_samples(64);
+        assert!(scheduler.is_scrubbing());
+        assert_eq!(output.frame(0), (0.0, 0.0));
+        assert_eq!(output.frame(63), (90.0, 90.0)); // frame 64 + 2 * 63 - 100
+        assert_eq!(scheduler.get_timeline_position().current_frame, 192);
+
+        scheduler.process_command(SchedulerCommand::Scrub(ScrubChange::Stop));
+        assert!(!scheduler.is_scrubbing());
+        scheduler.next_samples(64);
+        assert_eq!(scheduler.get_timeline_position().current_frame, 256);
+    }
+
+    #[test]
+    fn test_play_range_with_pre_and_post_roll() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.process_command(SchedulerCommand::SetRoll {
+            pre_roll: RollLength::Bars(1),
+            post_roll: RollLength::Seconds(0.5),
+        });
+        // bars 3 to 4, one 4/4 bar at 120 bpm is 88200 frames
+        scheduler.process_command(SchedulerCommand::PlayRange {
+            start_tick: 960,
+            end_tick: 1440,
+        });
+        assert_eq!(scheduler.get_timeline_position().bar, 2);
+        assert_eq!(scheduler.get_timeline_position().current_frame, 88200);
+
+        // through the pre-roll, the range and the post-roll
+        let frames = 88200 + 88200 + 22050;
+        for _ in 0..frames / 512 {
+            scheduler.next_samples(512);
+            assert_eq!(scheduler.transport_state, TransportState::Playing);
+        }
+        scheduler.next_samples(512);
+
+        assert_eq!(scheduler.transport_state, TransportState::Paused);
+        assert_eq!(scheduler.get_timeline_position().current_frame, 176_400);
+    }
+
+    #[test]
+    fn test_preview_plays_while_stopped_and_finishes() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(16);
+        scheduler.set_event_producer(event_prod);
+        let wav = WavTrack::from_buffer(AudioBuffer::from_frames(&[(0.25, 0.5); 100]));
+        scheduler.process_command(SchedulerCommand::Preview(Box::new(wav)));
+
+        let output = scheduler.next_samples(64);
+        assert_eq!(output.frame(0), (0.25, 0.5));
+        assert_eq!(scheduler.get_timeline_position().current_frame, 0);
+
+        let output = scheduler.next_samples(64);
+        assert_eq!(output.frame(35), (0.25, 0.5));
+        assert_eq!(output.frame(36), (0.0, 0.0));
+        assert!(matches!(
+            test_util::drain_events(&mut event_cons).as_slice(),
+            [SchedulerEvent::PreviewFinished]
+        ));
+
+        scheduler.process_command(SchedulerCommand::Preview(Box::new(ConstantTrack::new(
+            0.1, 0.1,
+        ))));
+        scheduler.process_command(SchedulerCommand::StopPreview);
+        assert_eq!(scheduler.next_samples(8).frame(0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_insert_and_remove_time_move_queued_tracks() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 18375); // tick 100
+        scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddMarker {
+            name: "drop".into(),
+            tick: 100,
+        }));
+
+        scheduler.process_command(SchedulerCommand::InsertTime {
+            at_tick: 50,
+            ticks: 100,
+        });
+        assert_eq!(scheduler.scheduled.peek().unwrap().start_frame, 36750);
+        assert_eq!(scheduler.markers().marker("drop").unwrap().tick, 200);
+
+        scheduler.process_command(SchedulerCommand::RemoveTime {
+            start_tick: 0,
+            end_tick: 150,
+        });
+        assert_eq!(scheduler.scheduled.peek().unwrap().start_frame, 9187);
+        assert_eq!(scheduler.markers().marker("drop").unwrap().tick, 50);
+    }
+
+    #[test]
+    fn test_insert_and_remove_time_move_playing_tracks_and_their_automation() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "constant-track".into(),
+            change: ChannelChange::SetAutomation {
+                parameter: AutomatedParameter::Gain,
+                points: vec![(18375, 1.0), (36750, 0.0)],
+            },
+        });
+
+        // 100 ticks before the playhead: the playing track now starts after it
+        scheduler.process_command(SchedulerCommand::InsertTime {
+            at_tick: 0,
+            ticks: 100,
+        });
+        let channel = scheduler.mixer.channels().next().unwrap();
+        assert_eq!(channel.start_frame(), 18375);
+        assert_eq!(
+            channel.automation()[0].points(),
+            [(36750, 1.0), (55125, 0.0)]
+        );
+        assert_eq!(scheduler.next_samples(64).frame(0), (0.0, 0.0));
+
+        scheduler.process_command(SchedulerCommand::RemoveTime {
+            start_tick: 0,
+            end_tick: 100,
+        });
+        let channel = scheduler.mixer.channels().next().unwrap();
+        assert_eq!(channel.start_frame(), 0);
+        assert_eq!(
+            channel.automation()[0].points(),
+            [(18375, 1.0), (36750, 0.0)]
+        );
+        assert_eq!(scheduler.next_samples(64).frame(0), (0.5, 0.5));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use rtrb::RingBuffer;
-    use transport::resolution::TickResolution;
+    #[test]
+    fn test_playhead_lags_by_the_output_latency() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        scheduler.set_snapshot_publisher(publisher);
+        let limiter = TruePeakLimiter::new(44100.0, -1.0);
+        let lookahead = limiter.latency() as u64;
+        scheduler.set_master_limiter(Some(limiter));
+        scheduler.set_block_size(Some(256)).unwrap();
+        scheduler.process_command(SchedulerCommand::Play);
 
-    use super::*;
-    use crate::{
-        constants::AUDIO_SAMPLE_EPSILON,
-        scheduler::command::ParameterChange,
-        track::{constant::ConstantTrack, gainpan::GainPanTrack, wav::WavTrack},
-    };
+        scheduler.next_samples(200);
+        scheduler.next_samples(200);
+        // the second call starts with the clock at 256 but only 200 frames handed out
+        let snapshot = reader.latest().unwrap();
+        assert_eq!(snapshot.current_frame, 512);
+        assert_eq!(snapshot.playhead.frame, 200 - lookahead);
+        assert_eq!(scheduler.output_latency(), 112 + lookahead as usize);
+    }
 
-    fn sum_energy(buffer: &[(f32, f32)]) -> f32 {
-        buffer.iter().map(|(l, r)| l.abs() + r.abs()).sum()
+    #[test]
+    fn test_video_reference_reports_frames_as_they_change() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(16);
+        scheduler.set_event_producer(event_prod);
+        let grid = TimecodeGrid::new(FrameRate::Fps25, 44100.0);
+        scheduler.process_command(SchedulerCommand::SetVideoReference(Some(VideoTrack::new(
+            grid,
+        ))));
+        scheduler.process_command(SchedulerCommand::Play);
+        // a frame every 1764 samples
+        scheduler.next_samples(4000);
+        scheduler.process_command(SchedulerCommand::Stop);
+
+        let frames: Vec<_> = test_util::drain_events(&mut event_cons)
+            .into_iter()
+            .filter_map(|event| match event {
+                SchedulerEvent::VideoFrame {
+                    frame, video_frame, ..
+                } => Some((frame, video_frame)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(frames, vec![(0, 0), (1764, 1), (3528, 2), (0, 0)]);
     }
 
     #[test]
-    fn test_track_scheduled_at_zero_plays_immediately() {
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.schedule(Box::new(ConstantTrack::new(0.1, 0.1)), 0);
-        sched.process_command(SchedulerCommand::Play);
+    fn test_snapshot_reports_tracks_and_cpu_load() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        scheduler.set_snapshot_publisher(publisher);
+        for id in ["drums", "bass"] {
+            let track =
+                GainPanTrack::new(id, Box::new(SineWaveTrack::new(440.0, 44100.0)), 1.0, 0.0);
+            scheduler.schedule(Box::new(track), 0);
+        }
+        scheduler.process_command(SchedulerCommand::Play);
 
-        let output = sched.next_samples(4);
-        assert_eq!(output.len(), 4);
-        assert!(sum_energy(&output) > 0.0);
+        scheduler.next_samples(512);
+        scheduler.process_command(SchedulerCommand::StopTrack {
+            target_id: "drums".into(),
+        });
+        scheduler.next_samples(512);
+
+        let snapshot = reader.latest().unwrap();
+        assert_eq!(snapshot.current_frame, 1024);
+        assert_eq!(snapshot.playhead.frame, 512);
+        assert_eq!(snapshot.playhead.rate, 44100.0);
+        assert_eq!(snapshot.transport_state, TransportState::Playing);
+        assert!(snapshot.cpu_load > 0.0);
+        let ids: Vec<_> = snapshot.tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["bass"]);
+        assert!(snapshot.tracks[0].cpu_load > 0.0);
+        assert!(!snapshot.idle);
+
+        scheduler.process_command(SchedulerCommand::StopTrack {
+            target_id: "bass".into(),
+        });
+        scheduler.next_samples(512);
+        assert!(reader.latest().unwrap().idle);
     }
 
     #[test]
-    fn test_track_scheduled_in_future_does_not_play_early() {
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.schedule(Box::new(ConstantTrack::new(1.0, 1.0)), 100);
-        sched.process_command(SchedulerCommand::Play);
+    fn test_snapshot_reports_input_of_armed_tracks() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        scheduler.set_snapshot_publisher(publisher);
+        for id in ["vox", "gtr"] {
+            let track = GainPanTrack::new(id, Box::new(ConstantTrack::new(0.0, 0.0)), 1.0, 0.0);
+            scheduler.schedule(Box::new(track), 0);
+        }
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "vox".into(),
+            change: ChannelChange::SetArmed(true),
+        });
 
-        let output = sched.next_samples(10); // still before frame 100
-        assert_eq!(output.len(), 10);
-        assert!(sum_energy(&output) == 0.0);
+        scheduler.meter_input(&AudioBuffer::from_frames(&[(0.5, 1.5); 64]));
+        scheduler.next_samples(64);
 
-        sched.next_samples(80);
-        sched.next_samples(10); // now at frame 100
+        let snapshot = reader.latest().unwrap();
+        let vox = snapshot.tracks.iter().find(|t| t.id == "vox").unwrap();
+        let input = vox.input.unwrap();
+        assert_eq!(input.peak_left, 0.5);
+        assert!(input.clipped);
+        let gtr = snapshot.tracks.iter().find(|t| t.id == "gtr").unwrap();
+        assert!(gtr.input.is_none());
+    }
 
-        let output = sched.next_samples(1);
-        assert!(sum_energy(&output) > 0.0);
+    #[test]
+    fn test_automation_plays_back_and_is_reported_in_snapshots() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        scheduler.set_snapshot_publisher(publisher);
+        scheduler.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(1);
+        for change in [
+            ChannelChange::SetAutomation {
+                parameter: AutomatedParameter::Gain,
+                points: vec![(1, 1.0), (33, 0.0)],
+            },
+            ChannelChange::SetAutomationMode {
+                parameter: AutomatedParameter::Gain,
+                mode: AutomationMode::Latch,
+            },
+        ] {
+            scheduler.process_command(SchedulerCommand::ChannelChange {
+                target_id: "constant-track".into(),
+                change,
+            });
+        }
+        assert_eq!(scheduler.next_samples(32).frame(0).0, 0.5);
+        assert_eq!(scheduler.next_samples(16).frame(0).0, 0.0);
+        reader.latest();
+
+        // moving the fader latches it over the lane until the transport stops
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "constant-track".into(),
+            change: ChannelChange::SetGain(0.5),
+        });
+        assert_eq!(scheduler.next_samples(16).frame(0).0, 0.25);
+        let status = reader.latest().unwrap().tracks[0].automation[0];
+        assert_eq!(status.mode, AutomationMode::Latch);
+        assert_eq!((status.value, status.pending), (0.5, Some(0.5)));
+
+        scheduler.process_command(SchedulerCommand::Pause);
+        scheduler.next_samples(16);
+        let status = reader.latest().unwrap().tracks[0].automation[0];
+        assert_eq!(status.pending, None);
     }
 
     #[test]
-    fn test_multiple_tracks_mixed_properly() {
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.schedule(Box::new(ConstantTrack::new(0.3, 0.3)), 0);
-        sched.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
-        sched.process_command(SchedulerCommand::Play);
+    fn test_cue_bus_plays_on_its_own_channel_pair() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let mut cue = crate::mixer::Bus::new("cue");
+        cue.set_cue(true);
+        scheduler.mixer.add_bus(cue);
+        let track = GainPanTrack::new("vox", Box::new(ConstantTrack::new(0.5, 0.5)), 1.0, 0.0);
+        let mut channel = Channel::new(Box::new(track));
+        channel.set_send("cue".into(), 0.25, true);
+        scheduler.schedule_channel(Box::new(channel), 0);
+        let (tap, mut stems) = crate::stems::stem_channel(&["cue"], 1024);
+        scheduler.set_stem_tap(tap);
+        scheduler.set_cue_output(stems.pop(), 2, 3);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        let output = sched.next_samples(1);
-        let (l, r) = output[0];
-        assert!((l - 0.8).abs() < AUDIO_SAMPLE_EPSILON);
-        assert!((r - 0.8).abs() < AUDIO_SAMPLE_EPSILON);
+        let mut output = [0.0f32; 4 * 64];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut output), 64);
+        // the control room hears the fader, the cue the pre-fader send
+        let last = &output[4 * 63..];
+        assert_eq!(last, [0.5, 0.5, 0.125, 0.125]);
     }
 
     #[test]
-    fn test_track_starts_midway_and_mixes_correctly() {
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.process_command(SchedulerCommand::Play);
-        sched.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 4); // start late
-
-        let out1 = sched.next_samples(4);
-        assert!(sum_energy(&out1) == 0.0); // silent
+    fn test_cv_output_plays_automation_on_its_own_channel() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let mut channel = Channel::new(Box::new(ConstantTrack::new(0.5, 0.5)));
+        channel.apply(
+            ChannelChange::SetAutomation {
+                parameter: AutomatedParameter::Gain,
+                points: vec![(0, 0.0), (64, 1.0)],
+            },
+            drop,
+        );
+        channel.apply(
+            ChannelChange::SetAutomationMode {
+                parameter: AutomatedParameter::Gain,
+                mode: AutomationMode::Read,
+            },
+            drop,
+        );
+        scheduler.schedule_channel(Box::new(channel), 0);
+        scheduler.set_cv_outputs(vec![CvOutput::new(
+            CvSource::Automation {
+                track: "constant-track".into(),
+                parameter: AutomatedParameter::Gain,
+            },
+            2,
+        )]);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        let out2 = sched.next_samples(2);
-        assert_eq!(out2.len(), 2);
-        assert!((out2[0].0 - 0.5).abs() < AUDIO_SAMPLE_EPSILON);
+        let mut output = [0.0f32; 3 * 64];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut output), 64);
+        // the lane ramps at audio rate on its channel, the block-rate gain starts at 0
+        let cv: Vec<f32> = output.chunks(3).map(|frame| frame[2]).collect();
+        assert_eq!(cv[0], 0.0);
+        assert_eq!(cv[16], 0.25);
+        assert_eq!(cv[63], 63.0 / 64.0);
+        assert_eq!(&output[..2], [0.0, 0.0]);
     }
 
     #[test]
-    fn test_gain_change_applies_during_playback() {
-        let gain_track =
-            GainPanTrack::new("x-track", Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+    fn test_talkback_reaches_the_cue_while_stopped() {
         let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let mut cue = crate::mixer::Bus::new("cue");
+        cue.set_cue(true);
+        scheduler.mixer.add_bus(cue);
+        let (tap, mut stems) = crate::stems::stem_channel(&["cue"], 1024);
+        scheduler.set_stem_tap(tap);
+        scheduler.set_cue_output(stems.pop(), 2, 3);
+        let mut mic = AudioBuffer::mono(64);
+        mic.channel_mut(0).fill(0.5);
+        scheduler.talkback_input(&mic);
+
+        let mut output = [0.0f32; 4 * 64];
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut output), 64);
+        assert!(output.iter().all(|&sample| sample == 0.0));
+
+        scheduler.process_command(SchedulerCommand::Monitor(MonitorChange::SetTalkback(true)));
+        scheduler.fill_buffer(AudioSourceBufferKind::F32(&mut output), 64);
+        assert_eq!(&output[4 * 63..], [0.0, 0.0, 0.5, 0.5]);
+        assert!((scheduler.monitor().gain() - 0.1).abs() < 1e-6);
+    }
 
-        scheduler.schedule(Box::new(gain_track), 0);
+    #[test]
+    fn test_monitor_modes_choose_input_or_playback() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let track = GainPanTrack::new("vox", Box::new(ConstantTrack::new(0.25, 0.25)), 1.0, 0.0);
+        scheduler.schedule(Box::new(track), 0);
         scheduler.process_command(SchedulerCommand::Play);
-        scheduler.next_samples(1); // activate
-
-        scheduler.process_command(SchedulerCommand::ParamChange {
-            target_id: "x-track".to_string(),
-            change: ParameterChange::SetGain(0.25),
+        scheduler.next_samples(64);
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "vox".into(),
+            change: ChannelChange::SetArmed(true),
         });
-
-        let output = scheduler.next_samples(1);
-        assert!((output[0].0 - 0.125).abs() < AUDIO_SAMPLE_EPSILON); // (1.0 * 0.25 * 0.5 pan_l)
-        assert!((output[0].1 - 0.125).abs() < AUDIO_SAMPLE_EPSILON);
+        scheduler.meter_input(&AudioBuffer::from_frames(&[(0.5, 0.5); 64]));
+
+        // auto mode plays back while rolling outside a punch range
+        assert_eq!(scheduler.next_samples(64).frame(63), (0.25, 0.25));
+        // and hears the input, only, once paused
+        scheduler.process_command(SchedulerCommand::Pause);
+        let frame = scheduler.current_frame;
+        assert_eq!(scheduler.next_samples(64).frame(63), (0.5, 0.5));
+        assert_eq!(scheduler.current_frame, frame);
+
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "vox".into(),
+            change: ChannelChange::SetMonitorMode(MonitorMode::Off),
+        });
+        assert_eq!(scheduler.next_samples(64).frame(63), (0.0, 0.0));
     }
 
     #[test]
-    fn test_stop_track_removes_it_from_output() {
-        let gpt = GainPanTrack::new("test-id", Box::new(ConstantTrack::new(0.5, 0.5)), 1.0, 0.0);
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.schedule(Box::new(gpt), 0);
-
-        sched.next_samples(1); // Activate
-
-        // Stop the track
-        sched.process_command(SchedulerCommand::StopTrack {
-            target_id: "test-id".into(),
+    fn test_auto_punch_records_between_markers_to_the_frame() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(64);
+        scheduler.set_event_producer(event_prod);
+        scheduler.schedule(Box::new(ConstantTrack::new(0.0, 0.0)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
+        scheduler.next_samples(64);
+        scheduler.process_command(SchedulerCommand::ChannelChange {
+            target_id: "constant-track".into(),
+            change: ChannelChange::SetArmed(true),
         });
+        for (name, tick) in [("take in", 1), ("take out", 2)] {
+            scheduler.process_command(SchedulerCommand::Markers(MarkerChange::AddMarker {
+                name: name.into(),
+                tick,
+            }));
+        }
+        let (start_tick, end_tick) = scheduler.markers().punch_range("take").unwrap();
+        scheduler.process_command(SchedulerCommand::AutoPunch {
+            start_tick,
+            end_tick,
+        });
+        test_util::drain_events(&mut event_cons);
+
+        let (start, end) = (scheduler.tick_to_frame(1), scheduler.tick_to_frame(2));
+        let mut recorded = Vec::new();
+        while scheduler.current_frame < end + 64 {
+            scheduler.meter_input(&AudioBuffer::from_frames(&[(0.5, 0.5); 64]));
+            scheduler.next_samples(64);
+            let channel = scheduler.mixer.channels().next().unwrap();
+            if let Some((frame, take)) = channel.take() {
+                recorded.push((frame, take.frames()));
+            }
+        }
 
-        let out = sched.next_samples(1);
-        assert_eq!(out[0], (0.0, 0.0)); // No output = stopped
+        assert_eq!(recorded.first().map(|&(frame, _)| frame), Some(start));
+        let frames: usize = recorded.iter().map(|&(_, frames)| frames).sum();
+        assert_eq!(frames as u64, end - start);
+        let changes: Vec<_> = test_util::drain_events(&mut event_cons)
+            .into_iter()
+            .filter_map(|event| match event {
+                SchedulerEvent::RecordEnabled { frame, enabled } => Some((frame, enabled)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(changes, [(start, true), (end, false)]);
     }
 
     #[test]
-    fn test_restart_resets_playback_position() {
-        let samples = vec![(1.0, 1.0), (0.5, 0.5), (0.0, 0.0)];
-        let wav = WavTrack {
-            samples: samples.clone(),
-            position: 0,
-        };
-
-        let gain = GainPanTrack::new("track-id", Box::new(wav), 1.0, 0.0);
-        let (mut sched, _) = test_util::create_scheduler_with_channel();
-        sched.schedule(Box::new(gain), 0);
-        sched.process_command(SchedulerCommand::Play);
-
-        let out1 = sched.next_samples(1); // (1.0, 1.0)
-        let out2 = sched.next_samples(1); // (0.5, 0.5)
+    fn test_snapshot_reports_master_loudness() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        scheduler.set_snapshot_publisher(publisher);
+        scheduler.set_loudness_metering(true);
+        scheduler.schedule(Box::new(SineWaveTrack::new(1000.0, 44100.0)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        sched.process_command(SchedulerCommand::RestartTrack {
-            target_id: "track-id".to_string(),
-        });
+        for _ in 0..50 {
+            scheduler.next_samples(512);
+            reader.latest();
+        }
+        let loudness = reader.latest().unwrap().loudness.unwrap();
+        assert!(loudness.momentary.is_finite());
+        assert!(loudness.integrated.is_finite());
+        assert!(loudness.true_peak <= 0.1);
+
+        scheduler.process_command(SchedulerCommand::Stop);
+        scheduler.next_samples(512);
+        let loudness = reader.latest().unwrap().loudness.unwrap();
+        assert_eq!(loudness.integrated, f32::NEG_INFINITY);
+    }
 
-        let out3 = sched.next_samples(1); // should reset to (1.0, 1.0)
+    #[test]
+    fn test_master_limiter_holds_ceiling() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.set_master_limiter(Some(TruePeakLimiter::new(44100.0, -6.0)));
+        scheduler.schedule(Box::new(ConstantTrack::new(1.0, -1.0)), 0);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        assert_eq!(out1[0], (0.5, 0.5)); // 1.0 * 1.0 * 0.5
-        assert_eq!(out2[0], (0.25, 0.25));
-        assert_eq!(out3[0], (0.5, 0.5)); // confirms retrigger
+        let output = scheduler.next_samples(22050);
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        assert!(output.peak(0, 0, 22050) <= ceiling + 1e-4);
+        // settles at the ceiling once the release from the initial overshoot is over
+        assert!((output.frame(22049).1 + ceiling).abs() < 1e-3);
     }
 
     #[test]
-    fn test_schedule_command_adds_track_correctly() {
-        let (mut scheduler, mut producer) = test_util::create_scheduler_with_channel();
+    fn test_snapshot_reports_master_correlation() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (publisher, mut reader) = crate::snapshot::snapshot_channel();
+        let (tap, mut points) = crate::metering::correlation::goniometer_channel(1024, 8);
+        scheduler.set_snapshot_publisher(publisher);
+        scheduler.set_correlation_metering(true);
+        scheduler.set_goniometer_tap(tap);
+        scheduler.schedule(Box::new(SineWaveTrack::new(440.0, 44100.0)), 0);
         scheduler.process_command(SchedulerCommand::Play);
 
-        // Push command to ring
-        let success = producer.push(SchedulerCommand::ScheduleTrack {
-            track: Box::new(ConstantTrack::new(0.4, 0.4)),
-            start_frame: 0,
-        });
-        assert!(success.is_ok(), "Should be able to enqueue command");
+        scheduler.next_samples(512);
+        let correlation = reader.latest().unwrap().correlation.unwrap();
+        assert!((correlation - 1.0).abs() < 0.01);
 
-        // Should now have one active track
-        let output = scheduler.next_samples(2);
-        assert_eq!(output.len(), 2);
-        assert!((output[0].0 - 0.4).abs() < 1e-6);
+        let mut sides = Vec::new();
+        assert_eq!(points.drain(|point| sides.push(point.side)), 64);
+        assert!(sides.iter().all(|side| *side == 0.0));
     }
 
     #[test]
-    fn test_scheduled_track_via_command_plays_at_correct_time() {
-        let (mut scheduler, mut producer) = test_util::create_scheduler_with_channel();
+    fn test_fixed_block_size_accumulates_across_callbacks() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.set_block_size(Some(64)).unwrap();
+        scheduler.schedule(Box::new(ConstantTrack::new(0.25, 0.5)), 0);
         scheduler.process_command(SchedulerCommand::Play);
 
-        // Send command for track to start at frame 3
-        producer
-            .push(SchedulerCommand::ScheduleTrack {
-                track: Box::new(ConstantTrack::new(0.2, 0.2)),
-                start_frame: 3,
-            })
-            .expect("Failed to enqueue");
+        let output = scheduler.next_samples(100);
+        assert!(output.iter_frames().all(|frame| frame == (0.25, 0.5)));
+        assert_eq!(scheduler.current_frame, 128); // two whole blocks
 
-        // Advance scheduler past frame 3
-        let silent = scheduler.next_samples(3);
-        assert!(silent.iter().all(|&(l, r)| (l + r).abs() < 1e-6));
+        scheduler.next_samples(28); // served from the second block
+        assert_eq!(scheduler.current_frame, 128);
 
-        let active = scheduler.next_samples(1);
-        assert!((active[0].0 - 0.2).abs() < 1e-6);
+        scheduler.next_samples(1);
+        assert_eq!(scheduler.current_frame, 192);
     }
 
     #[test]
-    fn test_ring_buffer_drops_when_full() {
-        let (mut prod, mut cons) = RingBuffer::new(1);
+    fn test_fixed_block_size_applies_commands_on_block_boundaries() {
+        let (mut scheduler, mut commands) = test_util::create_scheduler_with_channel();
+        scheduler.set_block_size(Some(64)).unwrap();
+        let track = GainPanTrack::new("lead", Box::new(ConstantTrack::new(1.0, 1.0)), 1.0, 0.0);
+        scheduler.schedule(Box::new(track), 0);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        // Fill it
-        prod.push(SchedulerCommand::ScheduleTrack {
-            track: Box::new(ConstantTrack::new(0.0, 0.0)),
-            start_frame: 0,
-        })
-        .unwrap();
+        scheduler.next_samples(10);
+        commands
+            .push(SchedulerCommand::ParamChange {
+                target_id: "lead".into(),
+                change: ParameterChange::SetGain(0.5),
+            })
+            .unwrap();
+        let output = scheduler.next_samples(60); // frames 10..70
 
-        // Next push should fail
-        let result = prod.push(SchedulerCommand::ScheduleTrack {
-            track: Box::new(ConstantTrack::new(0.1, 0.1)),
-            start_frame: 0,
-        });
+        assert_eq!(output.frame(53), (1.0, 1.0)); // frame 63, old gain
+        assert_eq!(output.frame(54), (0.5, 0.5)); // frame 64, next block
+    }
 
-        assert!(
-            result.is_err(),
-            "Second command should fail due to full ring buffer"
-        );
+    #[test]
+    fn test_invalid_block_size_is_rejected() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
 
-        // Consume one and push again
-        let _ = cons.pop();
+        assert!(matches!(
+            scheduler.set_block_size(Some(0)),
+            Err(SchedulingError::InvalidBlockSize(0))
+        ));
         assert!(
-            prod.push(SchedulerCommand::ScheduleTrack {
-                track: Box::new(ConstantTrack::new(0.2, 0.2)),
-                start_frame: 0,
-            })
-            .is_ok()
+            scheduler
+                .set_block_size(Some(MAX_BLOCK_FRAMES + 1))
+                .is_err()
         );
+        assert!(scheduler.set_block_size(Some(128)).is_ok());
     }
 
     #[test]
-    fn test_clock_advances_with_next_samples() {
-        let (mut scheduler, mut prod) = test_util::create_scheduler_with_channel();
-        prod.push(SchedulerCommand::Play).unwrap();
+    fn test_finished_track_does_not_leak_into_next_track() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        scheduler.schedule(
+            Box::new(WavTrack::from_buffer(AudioBuffer::from_frames(
+                &[(1.0, 1.0); 2],
+            ))),
+            0,
+        );
+        scheduler.schedule(Box::new(WavTrack::from_buffer(AudioBuffer::stereo(0))), 0);
+        scheduler.process_command(SchedulerCommand::Play);
 
-        // 16th note = 5512.5 samples at 120 BPM
-        scheduler.next_samples(5513); // cross one 16th note
-        assert_eq!(scheduler.current_tick(), 30);
+        let output = scheduler.next_samples(4);
+        assert_eq!(
+            output.iter_frames().collect::<Vec<_>>(),
+            vec![(1.0, 1.0), (1.0, 1.0), (0.0, 0.0), (0.0, 0.0)]
+        );
     }
 
     // #[test]
@@ -526,7 +2838,7 @@ mod tests {
         producer
             .push(SchedulerCommand::SetTempo {
                 bpm: 60.0,
-                resolution: TickResolution::Quarter,
+                resolution: Some(TickResolution::Quarter),
             })
             .unwrap();
 
@@ -536,12 +2848,36 @@ mod tests {
         // At 60 BPM, quarter note = 44100 samples
         scheduler.next_samples(44100);
         assert_eq!(scheduler.current_tick(), 481);
+
+        // a tempo without a resolution keeps quarter-note ticks
+        scheduler.process_command(SchedulerCommand::SetTempo {
+            bpm: 120.0,
+            resolution: None,
+        });
+        assert_eq!(scheduler.tempo_clock.ticks_per_beat, 480);
+    }
+
+    #[test]
+    fn test_partial_frames_are_silenced() {
+        let (mut sched, _) = test_util::create_scheduler_with_channel();
+        sched.schedule(Box::new(ConstantTrack::new(0.5, 0.5)), 0);
+        sched.process_command(SchedulerCommand::Play);
+
+        let mut data = [1.0f32; 7];
+        sched.fill_buffer(AudioSourceBufferKind::F32(&mut data), 3);
+        assert_eq!(data, [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(sched.current_frame, 3);
     }
 }
 
 #[cfg(test)]
 mod scheduler_loop_tests {
-    use crate::scheduler::command::LoopOptions;
+    use rtrb::RingBuffer;
+
+    use crate::{
+        scheduler::command::LoopOptions,
+        track::{constant::ConstantTrack, sinewave::SineWaveTrack},
+    };
 
     use super::*;
 
@@ -641,6 +2977,86 @@ mod scheduler_loop_tests {
         assert_eq!(scheduler.current_tick(), expected_tick);
     }
 
+    #[test]
+    fn test_output_does_not_depend_on_callback_sizes() {
+        let render = |sizes: &[usize]| {
+            let (mut scheduler, mut prod) = test_util::create_scheduler_with_channel();
+            let (events, mut consumer) = RingBuffer::new(4096);
+            scheduler.set_event_producer(events);
+            scheduler.schedule(Box::new(SineWaveTrack::new(440.0, 44100.0)), 1000);
+            scheduler.schedule(Box::new(ConstantTrack::new(0.25, -0.25)), 30_001);
+            prod.push(SchedulerCommand::SetLoop {
+                enabled: true,
+                start: LoopOptions {
+                    bar: 1,
+                    beat: 1,
+                    tick: 1,
+                },
+                end: LoopOptions {
+                    bar: 2,
+                    beat: 1,
+                    tick: 1,
+                },
+            })
+            .unwrap();
+            prod.push(SchedulerCommand::Play).unwrap();
+
+            let output = test_util::render_irregular(&mut scheduler, sizes, 100_000);
+            let events: Vec<_> = test_util::drain_events(&mut consumer)
+                .iter()
+                .map(|event| format!("{event:?}"))
+                .collect();
+            (output, events)
+        };
+
+        let (steady, steady_events) = render(&[512]);
+        let (irregular, irregular_events) = render(&[0, 1, 7, 480, MAX_BLOCK_FRAMES + 3, 13, 1023]);
+        assert!(steady.iter().any(|sample| *sample != 0.0));
+        assert_eq!(steady, irregular);
+        assert_eq!(steady_events, irregular_events);
+    }
+
+    #[test]
+    fn test_fixed_blocks_split_at_loop_ends_and_track_starts() {
+        let render = |block_size: Option<usize>| {
+            let (mut scheduler, mut prod) = test_util::create_scheduler_with_channel();
+            scheduler.set_block_size(block_size).unwrap();
+            let (events, mut consumer) = RingBuffer::new(4096);
+            scheduler.set_event_producer(events);
+            // neither start nor the loop end (88200) falls on a 64 frame block boundary
+            scheduler.schedule(Box::new(SineWaveTrack::new(440.0, 44100.0)), 1000);
+            scheduler.schedule(Box::new(ConstantTrack::new(0.25, -0.25)), 30_001);
+            prod.push(SchedulerCommand::SetLoop {
+                enabled: true,
+                start: LoopOptions {
+                    bar: 1,
+                    beat: 1,
+                    tick: 1,
+                },
+                end: LoopOptions {
+                    bar: 2,
+                    beat: 1,
+                    tick: 1,
+                },
+            })
+            .unwrap();
+            prod.push(SchedulerCommand::Play).unwrap();
+
+            let output = test_util::render_irregular(&mut scheduler, &[512], 100_000);
+            let events: Vec<_> = test_util::drain_events(&mut consumer)
+                .iter()
+                .map(|event| format!("{event:?}"))
+                .collect();
+            (output, events)
+        };
+
+        let (variable, variable_events) = render(None);
+        let (fixed, fixed_events) = render(Some(64));
+        assert!(variable.iter().any(|sample| *sample != 0.0));
+        assert_eq!(variable, fixed);
+        assert_eq!(variable_events, fixed_events);
+    }
+
     #[test]
     fn test_looping_disabled_no_wrap() {
         let (mut scheduler, mut prod) = test_util::create_scheduler_with_channel();
@@ -691,6 +3107,78 @@ mod scheduler_loop_tests {
         // Should not wrap
         assert!(scheduler.current_frame > scheduler.loop_end_frame);
     }
+
+    #[test]
+    fn test_loop_record_counts_passes_and_stops_after_the_last() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let (event_prod, mut event_cons) = RingBuffer::new(64);
+        scheduler.set_event_producer(event_prod);
+        let point = |beat| LoopOptions {
+            bar: 1,
+            beat,
+            tick: 1,
+        };
+        scheduler.process_command(SchedulerCommand::SetLoop {
+            enabled: true,
+            start: point(1),
+            end: point(2),
+        });
+        scheduler.process_command(SchedulerCommand::SetLoopRecord(LoopRecordOptions {
+            stop_after_passes: Some(2),
+            disable_loop_on_record_stop: true,
+        }));
+        scheduler.process_command(SchedulerCommand::SetRecordEnable {
+            tick: 0,
+            enabled: true,
+        });
+        scheduler.process_command(SchedulerCommand::Play);
+
+        let loop_end = scheduler.loop_end_frame as usize;
+        for _ in 0..3 * loop_end / 1000 {
+            scheduler.next_samples(1000);
+        }
+
+        let passes: Vec<_> = test_util::drain_events(&mut event_cons)
+            .into_iter()
+            .filter_map(|event| match event {
+                SchedulerEvent::LoopPassCompleted { pass } => Some(pass),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(passes, vec![1, 2]);
+        assert_eq!(scheduler.transport_state, TransportState::Paused);
+        assert_eq!(scheduler.current_frame, scheduler.loop_start_frame);
+        assert!(!scheduler.looping_enabled);
+    }
+
+    #[test]
+    fn test_loop_keeps_going_without_recording() {
+        let (mut scheduler, _) = test_util::create_scheduler_with_channel();
+        let point = |beat| LoopOptions {
+            bar: 1,
+            beat,
+            tick: 1,
+        };
+        scheduler.process_command(SchedulerCommand::SetLoop {
+            enabled: true,
+            start: point(1),
+            end: point(2),
+        });
+        scheduler.process_command(SchedulerCommand::SetLoopRecord(LoopRecordOptions {
+            stop_after_passes: Some(1),
+            disable_loop_on_record_stop: true,
+        }));
+        scheduler.process_command(SchedulerCommand::Play);
+
+        let loop_end = scheduler.loop_end_frame as usize;
+        for _ in 0..3 * loop_end / 1000 {
+            scheduler.next_samples(1000);
+        }
+
+        assert_eq!(scheduler.transport_state, TransportState::Playing);
+        assert_eq!(scheduler.loop_passes, 2);
+        assert!(scheduler.looping_enabled);
+    }
 }
 
 #[cfg(test)]
@@ -706,7 +3194,7 @@ mod scheduler_transport_tests {
         let output = scheduler.next_samples(512);
 
         // Should be silence
-        assert!(output.iter().all(|(l, r)| *l == 0.0 && *r == 0.0));
+        assert!(output.iter_frames().all(|(l, r)| l == 0.0 && r == 0.0));
         assert_eq!(scheduler.current_frame, 0);
         assert_eq!(scheduler.current_tick(), 0);
     }
@@ -726,7 +3214,7 @@ mod scheduler_transport_tests {
 
         let output = scheduler.next_samples(512);
 
-        assert!(output.iter().any(|(l, r)| *l != 0.0 || *r != 0.0));
+        assert!(output.iter_frames().any(|(l, r)| l != 0.0 || r != 0.0));
         assert!(scheduler.current_frame > 0);
 
         assert!(scheduler.current_tick() > 0);
@@ -748,7 +3236,7 @@ mod scheduler_transport_tests {
 
         let output = scheduler.next_samples(512);
 
-        assert!(output.iter().all(|(l, r)| *l == 0.0 && *r == 0.0));
+        assert!(output.iter_frames().all(|(l, r)| l == 0.0 && r == 0.0));
         assert_eq!(scheduler.current_frame, frame_after_play);
         assert_eq!(scheduler.current_tick(), tick_after_play);
     }
@@ -767,7 +3255,7 @@ mod scheduler_transport_tests {
         assert_eq!(scheduler.current_frame, 0);
         assert_eq!(scheduler.current_tick(), 0);
         let output = scheduler.next_samples(512);
-        assert!(output.iter().all(|(l, r)| *l == 0.0 && *r == 0.0));
+        assert!(output.iter_frames().all(|(l, r)| l == 0.0 && r == 0.0));
     }
 
     #[test]
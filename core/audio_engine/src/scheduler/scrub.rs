@@ -0,0 +1,173 @@
+use crate::{buffer::AudioBuffer, mixer::Channel};
+
+/// How quickly the scrub head catches up with the dragged playhead, in seconds
+const FOLLOW_TIME: f64 = 0.03;
+/// Fastest the scrub head moves while following a drag, as a multiple of normal speed
+const MAX_SPEED: f64 = 4.0;
+
+/// Scrub playback: a head chasing the playhead the host drags, or moving at a fixed speed.
+///
+/// Audio is read straight from the tracks at the head's position, so it speeds up, slows
+/// down and changes pitch with the drag, and fades out when the playhead stops moving.
+/// Only tracks that support [`Track::frame_at`](crate::track::Track::frame_at) are heard.
+#[derive(Debug, Clone, Copy)]
+pub struct Scrubber {
+    /// Where the host's playhead is, in frames
+    target: f64,
+    /// Where audio is being read from, in frames
+    head: f64,
+    /// Fixed playback speed for auditioning, `None` while following the playhead
+    speed: Option<f64>,
+    /// Share of the distance to the target covered per frame
+    follow: f64,
+}
+
+impl Scrubber {
+    /// A scrub head resting at `frame`
+    #[must_use]
+    pub fn new(frame: u64, sample_rate: f64) -> Self {
+        Self {
+            target: frame as f64,
+            head: frame as f64,
+            speed: None,
+            follow: 1.0 / (FOLLOW_TIME * sample_rate),
+        }
+    }
+
+    /// Drags the playhead to `frame`, the head follows over the next few milliseconds
+    pub fn move_to(&mut self, frame: u64) {
+        self.target = frame as f64;
+    }
+
+    /// Plays at a fixed `speed` (1.0 is normal, negative plays backwards) instead of
+    /// following the playhead, `None` goes back to following it
+    pub fn set_speed(&mut self, speed: Option<f32>) {
+        self.speed = speed.map(f64::from);
+        if self.speed.is_none() {
+            self.target = self.head;
+        }
+    }
+
+    /// Frame the scrub head is at
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.head.max(0.0).round() as u64
+    }
+
+    /// Mixes `sources` (channels with the timeline frame they start at) around the scrub head
+    /// into frames `start..start + frames` of `output`, then advances the head
+    pub fn render<'a>(
+        &mut self,
+        sources: impl Iterator<Item = (&'a Channel, i64)>,
+        output: &mut AudioBuffer,
+        start: usize,
+        frames: usize,
+    ) {
+        for (channel, start_frame) in sources {
+            // every source replays the same head movement from the block's start
+            let mut head = *self;
+            let (left, right) = output.stereo_mut();
+            for (left, right) in left[start..start + frames]
+                .iter_mut()
+                .zip(&mut right[start..start + frames])
+            {
+                let (position, level) = head.step();
+                let (l, r) = Self::read(channel, position - start_frame as f64);
+                *left += l * level;
+                *right += r * level;
+            }
+        }
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Moves the head by one frame, returns where it was and how loud to play there
+    fn step(&mut self) -> (f64, f32) {
+        let position = self.head;
+        let (velocity, level) = if let Some(speed) = self.speed {
+            (speed, 1.0)
+        } else {
+            let velocity = ((self.target - self.head) * self.follow).clamp(-MAX_SPEED, MAX_SPEED);
+            // a resting head would hold a single sample, fade it out instead
+            (velocity, velocity.abs().min(1.0) as f32)
+        };
+        self.head = (self.head + velocity).max(0.0);
+        (position, level)
+    }
+
+    /// The channel's audio at a fractional frame, linearly interpolated
+    fn read(channel: &Channel, frame: f64) -> (f32, f32) {
+        if frame < 0.0 {
+            return (0.0, 0.0);
+        }
+        let index = frame.floor() as usize;
+        let fraction = (frame - frame.floor()) as f32;
+        let (l0, r0) = channel.frame_at(index);
+        let (l1, r1) = channel.frame_at(index + 1);
+        (
+            (l1 - l0).mul_add(fraction, l0),
+            (r1 - r0).mul_add(fraction, r0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track::wav::WavTrack;
+
+    fn ramp(frames: usize) -> Channel {
+        let samples: Vec<_> = (0..frames).map(|i| (i as f32, i as f32)).collect();
+        Channel::new(Box::new(WavTrack::from_buffer(AudioBuffer::from_frames(
+            &samples,
+        ))))
+    }
+
+    #[test]
+    fn test_audition_plays_at_fixed_speed() {
+        let channel = ramp(100);
+        let mut scrub = Scrubber::new(10, 1000.0);
+        scrub.set_speed(Some(2.0));
+        let mut output = AudioBuffer::stereo(4);
+
+        scrub.render(std::iter::once((&channel, 0)), &mut output, 0, 4);
+
+        let left: Vec<_> = output.iter_frames().map(|(left, _)| left).collect();
+        assert_eq!(left, vec![10.0, 12.0, 14.0, 16.0]);
+        assert_eq!(scrub.position(), 18);
+    }
+
+    #[test]
+    fn test_head_follows_playhead_and_goes_quiet_at_rest() {
+        let channel = ramp(10_000);
+        let mut scrub = Scrubber::new(0, 1000.0);
+        scrub.move_to(1000);
+        let mut output = AudioBuffer::stereo(64);
+
+        scrub.render(std::iter::once((&channel, 0)), &mut output, 0, 64);
+        // capped at four times normal speed while far behind
+        assert_eq!(scrub.position(), 256);
+        assert!(output.frame(63).0 > 200.0);
+
+        for _ in 0..50 {
+            output.clear();
+            scrub.render(std::iter::once((&channel, 0)), &mut output, 0, 64);
+        }
+        assert_eq!(scrub.position(), 1000);
+        assert!(output.frame(63).0.abs() < 1.0);
+    }
+
+    #[test]
+    fn test_sources_play_from_their_start_frame() {
+        let channel = ramp(100);
+        let mut scrub = Scrubber::new(5, 1000.0);
+        scrub.set_speed(Some(1.0));
+        let mut output = AudioBuffer::stereo(3);
+
+        scrub.render(std::iter::once((&channel, 6)), &mut output, 0, 3);
+
+        assert_eq!(output.frame(0), (0.0, 0.0));
+        assert_eq!(output.frame(2), (1.0, 1.0));
+    }
+}
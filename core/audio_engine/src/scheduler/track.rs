@@ -1,15 +1,24 @@
-use crate::track::Track;
+use crate::mixer::Channel;
 
 pub struct ScheduledTrack {
-    /// Track to be scheduled
-    pub track: Box<dyn Track>,
+    /// Channel to add to the mixer, with the track to be scheduled
+    pub channel: Box<Channel>,
     /// the frame to start playing track
     pub start_frame: u64,
 }
 
+impl ScheduledTrack {
+    /// Frame the channel has to be playing from, its start moved by the channel's offset
+    #[must_use]
+    pub fn play_frame(&self) -> u64 {
+        self.start_frame
+            .saturating_add_signed(self.channel.offset())
+    }
+}
+
 impl PartialEq for ScheduledTrack {
     fn eq(&self, other: &Self) -> bool {
-        self.start_frame == other.start_frame
+        self.play_frame() == other.play_frame()
     }
 }
 
@@ -17,12 +26,12 @@ impl Eq for ScheduledTrack {}
 
 impl PartialOrd for ScheduledTrack {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(other.start_frame.cmp(&self.start_frame))
+        Some(other.play_frame().cmp(&self.play_frame()))
     }
 }
 
 impl Ord for ScheduledTrack {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.start_frame.cmp(&self.start_frame)
+        other.play_frame().cmp(&self.play_frame())
     }
 }
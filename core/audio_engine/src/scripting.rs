@@ -0,0 +1,288 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FuncArgs, Scope};
+use rtrb::Producer;
+use transport::resolution::TickResolution;
+
+use crate::{
+    error::{EngineError, SchedulingError},
+    events::Subscription,
+    scheduler::{
+        command::{ParameterChange, SchedulerCommand},
+        event::SchedulerEvent,
+    },
+    track::{builder::TrackBuilder, wav::WavTrack},
+};
+
+type CommandSink = Rc<RefCell<Producer<SchedulerCommand>>>;
+
+/// Runs a rhai script against scheduler events, on the control thread.
+///
+/// The script's top level runs once on load, then these optional hooks are called from
+/// [`ScriptHost::poll`]:
+/// - `on_bar(bar)`: playback entered a new bar
+/// - `on_track_end(id)`: a track ran out of material
+///
+/// Scripts drive the engine with `play()`, `pause()`, `stop()`, `set_tempo(bpm)` (keeping the
+/// tick resolution), `set_tempo(bpm, ticks_per_beat)`, `set_gain(id, gain)`,
+/// `set_pan(id, pan)`, `set_frequency(id, hz)`, `stop_track(id)`, `restart_track(id)` and
+/// `schedule_wav(id, path, start_frame)`.
+///
+/// # Example
+/// ```no_run
+/// # fn run(
+/// #     commands: rtrb::Producer<audio_engine::scheduler::command::SchedulerCommand>,
+/// #     bus: &mut audio_engine::events::EventBus,
+/// # ) {
+/// use audio_engine::scripting::ScriptHost;
+///
+/// let script = r#"
+///     fn on_bar(bar) {
+///         if bar % 4 == 0 { restart_track("drums"); }
+///     }
+/// "#;
+/// let mut host = ScriptHost::new(script, commands, bus.subscribe()).unwrap();
+/// bus.pump();
+/// host.poll().unwrap();
+/// # }
+/// ```
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    events: Subscription<SchedulerEvent>,
+}
+
+impl ScriptHost {
+    pub fn new(
+        script: &str,
+        commands: Producer<SchedulerCommand>,
+        events: Subscription<SchedulerEvent>,
+    ) -> Result<Self, EngineError> {
+        let mut engine = Engine::new();
+        Self::register_commands(&mut engine, Rc::new(RefCell::new(commands)));
+
+        let ast = engine
+            .compile(script)
+            .map_err(|e| EngineError::Script(format!("Failed to compile script: {e}")))?;
+
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| EngineError::Script(format!("Failed to run script: {e}")))?;
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            events,
+        })
+    }
+
+    /// Dispatches every pending scheduler event to the script's hooks
+    pub fn poll(&mut self) -> Result<(), EngineError> {
+        while let Some(event) = self.events.try_next() {
+            match event {
+                SchedulerEvent::BarStarted { bar } => self.call_hook("on_bar", (bar as i64,))?,
+                SchedulerEvent::TrackFinished { target_id } => {
+                    self.call_hook("on_track_end", (target_id.to_string(),))?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn call_hook(&mut self, name: &str, args: impl FuncArgs) -> Result<(), EngineError> {
+        let defined = self.ast.iter_functions().any(|f| f.name == name);
+        if !defined {
+            return Ok(());
+        }
+
+        self.engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, name, args)
+            .map(|_| ())
+            .map_err(|e| EngineError::Script(format!("Script hook '{name}' failed: {e}")))
+    }
+
+    fn register_commands(engine: &mut Engine, commands: CommandSink) {
+        let send = move |cmd: SchedulerCommand| -> Result<(), Box<EvalAltResult>> {
+            commands
+                .borrow_mut()
+                .push(cmd)
+                .map_err(|_| SchedulingError::CommandQueueFull.to_string().into())
+        };
+
+        let sink = send.clone();
+        engine.register_fn("play", move || sink(SchedulerCommand::Play));
+        let sink = send.clone();
+        engine.register_fn("pause", move || sink(SchedulerCommand::Pause));
+        let sink = send.clone();
+        engine.register_fn("stop", move || sink(SchedulerCommand::Stop));
+
+        let sink = send.clone();
+        engine.register_fn("set_tempo", move |bpm: f64| {
+            sink(SchedulerCommand::SetTempo {
+                bpm,
+                resolution: None,
+            })
+        });
+        let sink = send.clone();
+        engine.register_fn("set_tempo", move |bpm: f64, ticks_per_beat: i64| {
+            sink(SchedulerCommand::SetTempo {
+                bpm,
+                resolution: Some(TickResolution::PPQN(ticks_per_beat.max(1) as u64)),
+            })
+        });
+
+        let sink = send.clone();
+        engine.register_fn("set_gain", move |id: &str, gain: f64| {
+            sink(SchedulerCommand::ParamChange {
+                target_id: id.to_owned(),
+                change: ParameterChange::SetGain(gain as f32),
+            })
+        });
+        let sink = send.clone();
+        engine.register_fn("set_pan", move |id: &str, pan: f64| {
+            sink(SchedulerCommand::ParamChange {
+                target_id: id.to_owned(),
+                change: ParameterChange::SetPan(pan as f32),
+            })
+        });
+
+        let sink = send.clone();
+        engine.register_fn("set_frequency", move |id: &str, hz: f64| {
+            sink(SchedulerCommand::ParamChange {
+                target_id: id.to_owned(),
+                change: ParameterChange::SetFrequency(hz as f32),
+            })
+        });
+
+        let sink = send.clone();
+        engine.register_fn("stop_track", move |id: &str| {
+            sink(SchedulerCommand::StopTrack {
+                target_id: id.to_owned(),
+            })
+        });
+        let sink = send.clone();
+        engine.register_fn("restart_track", move |id: &str| {
+            sink(SchedulerCommand::RestartTrack {
+                target_id: id.to_owned(),
+            })
+        });
+
+        engine.register_fn(
+            "schedule_wav",
+            move |id: &str, path: &str, start_frame: i64| -> Result<(), Box<EvalAltResult>> {
+                let wav = WavTrack::from_file(path).map_err(|e| e.to_string())?;
+                send(SchedulerCommand::ScheduleTrack {
+                    track: TrackBuilder::new(Box::new(wav)).id(id).build(),
+                    start_frame: start_frame.max(0) as u64,
+                })
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rtrb::RingBuffer;
+
+    use super::*;
+    use crate::events::EventBus;
+
+    #[test]
+    fn test_on_bar_hook_issues_commands() {
+        let (cmd_prod, mut cmd_cons) = RingBuffer::new(8);
+        let (mut event_prod, event_cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(event_cons);
+
+        let script = r#"
+            fn on_bar(bar) {
+                if bar == 2 { set_gain("lead", 0.5); }
+            }
+        "#;
+        let mut host = ScriptHost::new(script, cmd_prod, bus.subscribe()).unwrap();
+
+        event_prod
+            .push(SchedulerEvent::BarStarted { bar: 1 })
+            .unwrap();
+        bus.pump();
+        host.poll().unwrap();
+        assert!(cmd_cons.pop().is_err());
+
+        event_prod
+            .push(SchedulerEvent::BarStarted { bar: 2 })
+            .unwrap();
+        bus.pump();
+        host.poll().unwrap();
+        assert!(matches!(
+            cmd_cons.pop(),
+            Ok(SchedulerCommand::ParamChange {
+                target_id,
+                change: ParameterChange::SetGain(gain),
+            }) if target_id == "lead" && gain == 0.5
+        ));
+    }
+
+    #[test]
+    fn test_top_level_runs_once_on_load() {
+        let (cmd_prod, mut cmd_cons) = RingBuffer::new(8);
+        let (_, event_cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(event_cons);
+
+        ScriptHost::new("play();", cmd_prod, bus.subscribe()).unwrap();
+
+        assert!(matches!(cmd_cons.pop(), Ok(SchedulerCommand::Play)));
+        assert!(cmd_cons.pop().is_err());
+    }
+
+    #[test]
+    fn test_on_track_end_receives_track_id() {
+        let (cmd_prod, mut cmd_cons) = RingBuffer::new(8);
+        let (mut event_prod, event_cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(event_cons);
+
+        let script = "fn on_track_end(id) { restart_track(id); }";
+        let mut host = ScriptHost::new(script, cmd_prod, bus.subscribe()).unwrap();
+
+        event_prod
+            .push(SchedulerEvent::TrackFinished {
+                target_id: "loop".into(),
+            })
+            .unwrap();
+        bus.pump();
+        host.poll().unwrap();
+
+        assert!(matches!(
+            cmd_cons.pop(),
+            Ok(SchedulerCommand::RestartTrack { target_id }) if target_id == "loop"
+        ));
+    }
+
+    #[test]
+    fn test_missing_hooks_are_ignored() {
+        let (cmd_prod, _) = RingBuffer::new(8);
+        let (mut event_prod, event_cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(event_cons);
+        let mut host = ScriptHost::new("let x = 1;", cmd_prod, bus.subscribe()).unwrap();
+
+        event_prod
+            .push(SchedulerEvent::BarStarted { bar: 3 })
+            .unwrap();
+        bus.pump();
+        assert!(host.poll().is_ok());
+    }
+
+    #[test]
+    fn test_compile_error_is_reported() {
+        let (cmd_prod, _) = RingBuffer::new(8);
+        let (_, event_cons) = RingBuffer::new(8);
+        let mut bus = EventBus::new(event_cons);
+        assert!(matches!(
+            ScriptHost::new("fn (", cmd_prod, bus.subscribe()),
+            Err(EngineError::Script(_))
+        ));
+    }
+}
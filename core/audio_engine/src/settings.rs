@@ -0,0 +1,156 @@
+//! Engine settings kept per machine rather than per project, stored as TOML.
+//!
+//! They hold the device, rate and block size to start with, meter ballistics and how often
+//! to autosave, and are applied with [`EngineBuilder::load_settings`](crate::engine::EngineBuilder::load_settings).
+//!
+//! ```toml
+//! output_device = "Studio Interface"
+//! sample_rate = 48000
+//! block_size = 256
+//! autosave_seconds = 300
+//!
+//! [metering]
+//! peak_release_db_per_second = 12.0
+//! correlation_window_seconds = 0.3
+//! ```
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::SettingsError, metering::MeterBallistics};
+
+/// File name of the settings in [`EngineSettings::default_path`]'s directory
+const SETTINGS_FILE: &str = "engine.toml";
+
+/// Settings the engine starts with, whatever project is open. Missing entries keep the
+/// engine's defaults.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineSettings {
+    /// Name of the output device, `None` plays on the system default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_device: Option<String>,
+    /// `None` runs at the output device's rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// Fixed processing block in frames, `None` follows the device's callbacks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<usize>,
+    /// `None` keeps the engine's meter ballistics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metering: Option<MeterBallistics>,
+    /// Seconds between autosaves, `None` doesn't autosave
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autosave_seconds: Option<u64>,
+}
+
+impl EngineSettings {
+    /// Reads the settings at `path`
+    ///
+    /// # Errors
+    /// If the file can't be read or isn't valid settings TOML.
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let source = std::fs::read_to_string(path).map_err(|source| SettingsError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(toml::from_str(&source)?)
+    }
+
+    /// Like [`EngineSettings::load`], with the defaults if there's no file at `path` yet, as
+    /// on the first start
+    ///
+    /// # Errors
+    /// If the file exists but can't be read or isn't valid settings TOML.
+    pub fn load_or_default(path: &Path) -> Result<Self, SettingsError> {
+        match Self::load(path) {
+            Err(SettingsError::Io { source, .. })
+                if source.kind() == std::io::ErrorKind::NotFound =>
+            {
+                Ok(Self::default())
+            }
+            result => result,
+        }
+    }
+
+    /// Writes the settings to `path`, creating its directory
+    ///
+    /// # Errors
+    /// If the file or its directory can't be written.
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        let source = toml::to_string_pretty(self)?;
+        let io = |source| SettingsError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        std::fs::write(path, source).map_err(io)
+    }
+
+    /// Where the settings live for the current user: `freqform/engine.toml` in the platform's
+    /// configuration directory. `None` if the environment doesn't say where that is.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = || std::env::var_os("HOME").map(PathBuf::from);
+        let dir = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| home().map(|home| home.join(".config")))
+        }?;
+        Some(dir.join("freqform").join(SETTINGS_FILE))
+    }
+
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        self.autosave_seconds.map(Duration::from_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_roundtrip_and_default_when_missing() {
+        let dir = std::env::temp_dir().join(format!("freqform-settings-{}", std::process::id()));
+        let path = dir.join("nested").join(SETTINGS_FILE);
+        assert_eq!(
+            EngineSettings::load_or_default(&path).unwrap(),
+            EngineSettings::default()
+        );
+
+        let settings = EngineSettings {
+            output_device: Some("Studio Interface".into()),
+            block_size: Some(256),
+            metering: Some(MeterBallistics {
+                peak_release_db_per_second: 12.0,
+                ..MeterBallistics::default()
+            }),
+            autosave_seconds: Some(300),
+            ..EngineSettings::default()
+        };
+        settings.save(&path).unwrap();
+        let loaded = EngineSettings::load_or_default(&path).unwrap();
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.autosave_interval(), Some(Duration::from_mins(5)));
+
+        // entries left out keep their defaults
+        std::fs::write(&path, "[metering]\ncorrelation_window_seconds = 1.0\n").unwrap();
+        let partial = EngineSettings::load(&path).unwrap();
+        assert_eq!(
+            partial
+                .metering
+                .map(|metering| metering.peak_release_db_per_second),
+            Some(20.0)
+        );
+        assert_eq!(partial.sample_rate, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+use transport::transport::TransportState;
+
+use crate::{
+    automation::{AutomatedParameter, AutomationStatus},
+    constants::MAX_ACTIVE_TRACKS,
+    metadata::Metadata,
+    metering::{input::InputLevel, loudness::LoudnessReading},
+    mixer::HeadroomStatus,
+};
+
+/// Snapshots in circulation: one held by the reader, one in flight, one being written
+const SNAPSHOT_POOL_SIZE: usize = 3;
+/// Bytes reserved up front for each track id and group name, longer ones grow the buffer
+const NAME_CAPACITY: usize = 64;
+/// Automation statuses a track can have, one per [`AutomatedParameter`]
+const AUTOMATED_PARAMETERS: usize = AutomatedParameter::ALL.len();
+/// How far past its timestamp a [`Playhead`] is extrapolated, so a stalled engine doesn't
+/// run the cursor away
+const MAX_EXTRAPOLATION: Duration = Duration::from_millis(100);
+
+/// Engine state as of the last audio callback, for meters and status displays
+#[derive(Debug, Clone)]
+pub struct EngineSnapshot {
+    pub current_frame: u64,
+    /// Playhead estimate for drawing a cursor between snapshots
+    pub playhead: Playhead,
+    pub transport_state: TransportState,
+    /// Nothing is queued and every track has run out of material, see
+    /// [`crate::scheduler::Scheduler::is_idle`]
+    pub idle: bool,
+    /// Smoothed share of the buffer deadline the whole callback took, in percent
+    pub cpu_load: f32,
+    /// Active tracks, in arrangement order
+    pub tracks: Vec<TrackSnapshot>,
+    /// Master bus loudness, when enabled with [`crate::scheduler::Scheduler::set_loudness_metering`]
+    pub loudness: Option<LoudnessReading>,
+    /// Master bus phase correlation from -1 to +1, when enabled with
+    /// [`crate::scheduler::Scheduler::set_correlation_metering`]
+    pub correlation: Option<f32>,
+    /// Gain compensation on the master sum, when enabled with
+    /// [`crate::mixer::Mixer::set_headroom`]
+    pub headroom: Option<HeadroomStatus>,
+    /// Preallocated track entries, taken as the track list grows and returned as it shrinks
+    spare: Vec<TrackSnapshot>,
+    /// Preallocated group names, for entries going in and out of a group
+    spare_names: Vec<String>,
+    /// Metadata of the spare entries, kept here so it's never freed when they're reused
+    no_metadata: Arc<Metadata>,
+}
+
+/// Where the playhead was at a known instant, so a UI can move its cursor smoothly between
+/// snapshots instead of stepping once per callback
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Playhead {
+    /// Timeline frame at `timestamp`
+    pub frame: u64,
+    /// When the audio callback that started at `frame` ran, `None` without a monotonic clock
+    pub timestamp: Option<Instant>,
+    /// Frames the playhead moves per second, 0 while it stands still
+    pub rate: f64,
+}
+
+impl Playhead {
+    /// Estimated timeline frame at `now`. Loops and transport changes after the snapshot
+    /// aren't known yet, the next snapshot corrects for them.
+    #[must_use]
+    pub fn frame_at(&self, now: Instant) -> f64 {
+        let elapsed = self.timestamp.map_or(Duration::ZERO, |timestamp| {
+            now.saturating_duration_since(timestamp)
+                .min(MAX_EXTRAPOLATION)
+        });
+        elapsed.as_secs_f64().mul_add(self.rate, self.frame as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackSnapshot {
+    pub id: String,
+    /// Position among the active tracks
+    pub index: usize,
+    /// Folder group the track belongs to
+    pub group: Option<String>,
+    /// Color, notes and tags, shared with the channel so publishing doesn't copy them
+    pub metadata: Arc<Metadata>,
+    /// Smoothed share of the buffer deadline this track took, in percent
+    pub cpu_load: f32,
+    /// Input peaks and clip indicator, `None` unless the track is armed
+    pub input: Option<InputLevel>,
+    /// Mode, value and pending override of each automated parameter
+    pub automation: Vec<AutomationStatus>,
+}
+
+impl EngineSnapshot {
+    fn new() -> Self {
+        let no_metadata = Arc::<Metadata>::default();
+        Self {
+            current_frame: 0,
+            playhead: Playhead::default(),
+            transport_state: TransportState::Stopped,
+            idle: true,
+            cpu_load: 0.0,
+            tracks: Vec::with_capacity(MAX_ACTIVE_TRACKS),
+            loudness: None,
+            correlation: None,
+            headroom: None,
+            spare: (0..MAX_ACTIVE_TRACKS)
+                .map(|index| TrackSnapshot {
+                    id: String::with_capacity(NAME_CAPACITY),
+                    index,
+                    group: None,
+                    metadata: Arc::clone(&no_metadata),
+                    cpu_load: 0.0,
+                    input: None,
+                    automation: Vec::with_capacity(AUTOMATED_PARAMETERS),
+                })
+                .collect(),
+            spare_names: (0..MAX_ACTIVE_TRACKS)
+                .map(|_| String::with_capacity(NAME_CAPACITY))
+                .collect(),
+            no_metadata,
+        }
+    }
+
+    /// Overwrites the track list with preallocated entries and string buffers, so it doesn't
+    /// allocate for up to [`MAX_ACTIVE_TRACKS`] tracks with names under [`NAME_CAPACITY`]
+    /// bytes. Metadata an entry no longer shows is handed to `retire`, it may be the last
+    /// reference left.
+    pub(crate) fn set_tracks<'a>(
+        &mut self,
+        tracks: impl Iterator<
+            Item = (
+                &'a str,
+                Option<&'a str>,
+                &'a Arc<Metadata>,
+                f32,
+                Option<InputLevel>,
+                impl IntoIterator<Item = AutomationStatus>,
+            ),
+        >,
+        mut retire: impl FnMut(Arc<Metadata>),
+    ) {
+        let mut count = 0;
+        for (id, group, metadata, cpu_load, input, automation) in tracks {
+            if count == self.tracks.len() {
+                let mut track = self.spare.pop().unwrap_or_else(|| TrackSnapshot {
+                    id: String::new(),
+                    index: count,
+                    group: None,
+                    metadata: Arc::clone(metadata),
+                    cpu_load,
+                    input,
+                    automation: Vec::new(),
+                });
+                track.index = count;
+                self.tracks.push(track);
+            }
+            let track = &mut self.tracks[count];
+            track.id.clear();
+            track.id.push_str(id);
+            match (track.group.as_mut(), group) {
+                (Some(existing), Some(group)) => {
+                    existing.clear();
+                    existing.push_str(group);
+                }
+                (None, Some(group)) => {
+                    let mut name = self.spare_names.pop().unwrap_or_default();
+                    name.push_str(group);
+                    track.group = Some(name);
+                }
+                (Some(_), None) => {
+                    if let Some(mut name) = track.group.take() {
+                        name.clear();
+                        self.spare_names.push(name);
+                    }
+                }
+                (None, None) => {}
+            }
+            if !Arc::ptr_eq(&track.metadata, metadata) {
+                let previous = std::mem::replace(&mut track.metadata, Arc::clone(metadata));
+                if !Arc::ptr_eq(&previous, &self.no_metadata) {
+                    retire(previous);
+                }
+            }
+            track.cpu_load = cpu_load;
+            track.input = input;
+            track.automation.clear();
+            track.automation.extend(automation);
+            count += 1;
+        }
+        for mut track in self.tracks.drain(count..) {
+            retire(std::mem::replace(
+                &mut track.metadata,
+                Arc::clone(&self.no_metadata),
+            ));
+            self.spare.push(track);
+        }
+    }
+}
+
+/// Creates the channel the scheduler publishes [`EngineSnapshot`]s through.
+///
+/// Snapshots are preallocated and recycled between the two ends, so publishing
+/// from the audio thread doesn't allocate once the track list has reached its size.
+#[must_use]
+pub fn snapshot_channel() -> (SnapshotPublisher, SnapshotReader) {
+    let (published, incoming) = RingBuffer::new(SNAPSHOT_POOL_SIZE);
+    let (mut recycle, free) = RingBuffer::new(SNAPSHOT_POOL_SIZE);
+    for _ in 0..SNAPSHOT_POOL_SIZE {
+        let _ = recycle.push(Box::new(EngineSnapshot::new()));
+    }
+
+    (
+        SnapshotPublisher { free, published },
+        SnapshotReader {
+            incoming,
+            recycle,
+            current: None,
+        },
+    )
+}
+
+/// Audio thread end of the snapshot channel
+pub struct SnapshotPublisher {
+    free: Consumer<Box<EngineSnapshot>>,
+    published: Producer<Box<EngineSnapshot>>,
+}
+
+impl SnapshotPublisher {
+    /// Fills a recycled snapshot and publishes it.
+    /// Skipped when the reader hasn't returned any snapshots yet.
+    pub fn publish(&mut self, fill: impl FnOnce(&mut EngineSnapshot)) {
+        if let Ok(mut snapshot) = self.free.pop() {
+            fill(&mut snapshot);
+            let _ = self.published.push(snapshot);
+        }
+    }
+}
+
+/// UI end of the snapshot channel
+pub struct SnapshotReader {
+    incoming: Consumer<Box<EngineSnapshot>>,
+    recycle: Producer<Box<EngineSnapshot>>,
+    current: Option<Box<EngineSnapshot>>,
+}
+
+impl SnapshotReader {
+    /// The most recent snapshot, `None` until the scheduler has published one
+    pub fn latest(&mut self) -> Option<&EngineSnapshot> {
+        while let Ok(snapshot) = self.incoming.pop() {
+            if let Some(previous) = self.current.replace(snapshot) {
+                let _ = self.recycle.push(previous);
+            }
+        }
+        self.current.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        automation::{AutomatedParameter, AutomationMode},
+        metadata::Color,
+    };
+
+    #[test]
+    fn test_reader_sees_latest_snapshot() {
+        let (mut publisher, mut reader) = snapshot_channel();
+        assert!(reader.latest().is_none());
+
+        publisher.publish(|s| s.current_frame = 64);
+        publisher.publish(|s| s.current_frame = 128);
+
+        assert_eq!(reader.latest().map(|s| s.current_frame), Some(128));
+    }
+
+    #[test]
+    fn test_snapshots_are_recycled() {
+        let (mut publisher, mut reader) = snapshot_channel();
+
+        for frame in 0..100 {
+            publisher.publish(|s| s.current_frame = frame);
+            assert_eq!(reader.latest().map(|s| s.current_frame), Some(frame));
+        }
+    }
+
+    #[test]
+    fn test_publish_is_skipped_while_reader_lags() {
+        let (mut publisher, mut reader) = snapshot_channel();
+
+        for frame in 0..10 {
+            publisher.publish(|s| s.current_frame = frame);
+        }
+
+        // only the pool's worth of snapshots made it through
+        assert_eq!(
+            reader.latest().map(|s| s.current_frame),
+            Some(SNAPSHOT_POOL_SIZE as u64 - 1)
+        );
+    }
+
+    #[test]
+    fn test_playhead_extrapolates_from_timestamp() {
+        let timestamp = Instant::now();
+        let playhead = Playhead {
+            frame: 1000,
+            timestamp: Some(timestamp),
+            rate: 48000.0,
+        };
+
+        let later = timestamp + Duration::from_millis(5);
+        assert!((playhead.frame_at(later) - 1240.0).abs() < 1e-6);
+        assert_eq!(
+            playhead.frame_at(timestamp + Duration::from_secs(1)),
+            5800.0
+        );
+
+        let stopped = Playhead {
+            rate: 0.0,
+            ..playhead
+        };
+        assert_eq!(stopped.frame_at(later), 1000.0);
+    }
+
+    #[test]
+    fn test_set_tracks_reuses_entries() {
+        let mut snapshot = EngineSnapshot::new();
+        let plain = Arc::default();
+        let red = Arc::new(Metadata {
+            color: Some(Color::new(255, 0, 0)),
+            ..Metadata::default()
+        });
+        let armed = InputLevel {
+            peak_left: 0.5,
+            peak_right: 0.5,
+            clipped: true,
+        };
+        let latched = AutomationStatus {
+            parameter: AutomatedParameter::Gain,
+            mode: AutomationMode::Latch,
+            value: 0.5,
+            pending: Some(0.5),
+        };
+        snapshot.set_tracks(
+            [
+                ("drums", None, &plain, 1.0, Some(armed), None),
+                ("bass", Some("rhythm"), &plain, 2.0, None, Some(latched)),
+            ]
+            .into_iter(),
+            drop,
+        );
+        snapshot.set_tracks(
+            [
+                ("keys", Some("pads"), &red, 3.0, None, Some(latched)),
+                ("bass", None, &plain, 2.0, Some(armed), None),
+            ]
+            .into_iter(),
+            drop,
+        );
+
+        assert_eq!(
+            snapshot.tracks,
+            vec![
+                TrackSnapshot {
+                    id: "keys".into(),
+                    index: 0,
+                    group: Some("pads".into()),
+                    metadata: Arc::clone(&red),
+                    cpu_load: 3.0,
+                    input: None,
+                    automation: vec![latched]
+                },
+                TrackSnapshot {
+                    id: "bass".into(),
+                    index: 1,
+                    group: None,
+                    metadata: plain,
+                    cpu_load: 2.0,
+                    input: Some(armed),
+                    automation: Vec::new()
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Live stems: the post-fader signal of tracks and busses sent out of the audio thread, so
+//! another app (a DAW, streaming software) can take them in as they play.
+//!
+//! The stems are published as JACK ports by `JackStemPorts` (`jack` feature), played into
+//! a virtual/loopback device by
+//! [`CpalAudioDeviceManager::start_stem_stream`](crate::device_manager::cpal_dm::CpalAudioDeviceManager::start_stem_stream),
+//! or sent over the network by [`RtpSender`](crate::rtp::RtpSender).
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::buffer::AudioBuffer;
+
+/// Stem id the scheduler streams its master output as, after the master limiter
+pub const MASTER_STEM: &str = "master";
+
+/// Creates the channels the tracks and busses `ids` are streamed through, each holding up to
+/// `capacity` frames.
+///
+/// A stem drops frames while its ring is full and reads silence while it's empty, so a
+/// stalled or drifting reader never blocks the audio thread.
+#[must_use]
+pub fn stem_channel(ids: &[&str], capacity: usize) -> (StemTap, Vec<StemReader>) {
+    let (stems, readers) = ids
+        .iter()
+        .map(|&id| {
+            let (producer, consumer) = RingBuffer::new(capacity);
+            let reader = StemReader {
+                id: id.to_owned(),
+                consumer,
+            };
+            ((id.to_owned(), producer), reader)
+        })
+        .unzip();
+    (StemTap { stems }, readers)
+}
+
+/// Audio thread end of the stem channels, see [`Mixer::set_stem_tap`]
+///
+/// [`Mixer::set_stem_tap`]: crate::mixer::Mixer::set_stem_tap
+pub struct StemTap {
+    stems: Vec<(String, Producer<(f32, f32)>)>,
+}
+
+impl StemTap {
+    /// Ids of the streamed tracks and busses
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.stems.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// Sends frames `start..start + frames` of stereo `signal` scaled by `gain` as stem
+    /// `id`, ignored if `id` isn't streamed
+    pub fn send(&mut self, id: &str, signal: &AudioBuffer, start: usize, frames: usize, gain: f32) {
+        let Some((_, producer)) = self.stems.iter_mut().find(|(stem, _)| stem == id) else {
+            return;
+        };
+        let left = &signal.channel(0)[start..start + frames];
+        let right = &signal.channel(1)[start..start + frames];
+        for (&l, &r) in left.iter().zip(right) {
+            let _ = producer.push((l * gain, r * gain));
+        }
+    }
+
+    /// Sends `frames` of silence as stem `id`, e.g. while it's muted, so it stays in time
+    pub fn silence(&mut self, id: &str, frames: usize) {
+        let Some((_, producer)) = self.stems.iter_mut().find(|(stem, _)| stem == id) else {
+            return;
+        };
+        for _ in 0..frames {
+            let _ = producer.push((0.0, 0.0));
+        }
+    }
+}
+
+/// Reading end of one stem, handed to the thread feeding the other app
+pub struct StemReader {
+    id: String,
+    consumer: Consumer<(f32, f32)>,
+}
+
+impl StemReader {
+    /// Id of the track or bus streamed
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Frames waiting to be read
+    pub fn available(&self) -> usize {
+        self.consumer.slots()
+    }
+
+    /// Next frame, silence if the audio thread hasn't sent one yet
+    pub fn next_frame(&mut self) -> (f32, f32) {
+        self.consumer.pop().unwrap_or((0.0, 0.0))
+    }
+
+    /// Fills `left` and `right` (the same length) with the next frames. Returns how many
+    /// were sent, the rest is silence.
+    pub fn read(&mut self, left: &mut [f32], right: &mut [f32]) -> usize {
+        let mut read = 0;
+        for (l, r) in left.iter_mut().zip(right) {
+            let frame = self.consumer.pop();
+            read += usize::from(frame.is_ok());
+            (*l, *r) = frame.unwrap_or((0.0, 0.0));
+        }
+        read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stems_stream_their_own_signal() {
+        let (mut tap, mut readers) = stem_channel(&["drums", "bass"], 8);
+        let signal = AudioBuffer::from_frames(&[(0.5, 0.25); 4]);
+        tap.send("drums", &signal, 1, 3, 2.0);
+        tap.silence("bass", 2);
+        tap.send("vox", &signal, 0, 4, 1.0);
+
+        let (mut left, mut right) = ([1.0; 4], [1.0; 4]);
+        assert_eq!(readers[0].id(), "drums");
+        assert_eq!(readers[0].read(&mut left, &mut right), 3);
+        assert_eq!((left, right), ([1.0, 1.0, 1.0, 0.0], [0.5, 0.5, 0.5, 0.0]));
+        assert_eq!(readers[1].available(), 2);
+        assert_eq!(readers[1].next_frame(), (0.0, 0.0));
+
+        // a full ring drops what doesn't fit
+        tap.send(
+            "drums",
+            &AudioBuffer::from_frames(&[(0.1, 0.1); 10]),
+            0,
+            10,
+            1.0,
+        );
+        assert_eq!(readers[0].available(), 8);
+    }
+}
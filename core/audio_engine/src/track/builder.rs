@@ -0,0 +1,117 @@
+use crate::{
+    dsp::Processor,
+    track::{Track, effect::EffectTrack, gainpan::GainPanTrack},
+};
+
+/// Id of the effect chain inside a built track, e.g. `"drums/effects"`
+pub const EFFECTS_ID: &str = "effects";
+
+/// Composes a track with effects, gain and pan into a single schedulable track.
+///
+/// The source plays through the effects, in the order they were added, then gain and pan.
+/// The built track is named by [`TrackBuilder::id`] and takes gain and pan changes
+/// addressed to that id.
+///
+/// # Example
+/// ```
+/// use audio_engine::{
+///     dsp::gain::Gain,
+///     track::{builder::TrackBuilder, sinewave::SineWaveTrack},
+/// };
+///
+/// let track = TrackBuilder::new(Box::new(SineWaveTrack::new(440.0, 44100.0)))
+///     .id("lead")
+///     .gain(0.8)
+///     .pan(-0.2)
+///     .effect(Box::new(Gain::new(-3.0)))
+///     .build();
+/// assert_eq!(track.id(), "lead");
+/// ```
+pub struct TrackBuilder {
+    id: String,
+    source: Box<dyn Track>,
+    gain: f32,
+    pan: f32,
+    effects: Vec<Box<dyn Processor>>,
+}
+
+impl TrackBuilder {
+    /// Starts from `source` at unity gain, panned center and named after `source`'s id
+    #[must_use]
+    pub fn new(source: Box<dyn Track>) -> Self {
+        Self {
+            id: source.id(),
+            source,
+            gain: 1.0,
+            pan: 0.0,
+            effects: Vec::new(),
+        }
+    }
+
+    /// Names the built track, and so the channel it plays on
+    #[must_use]
+    pub fn id(mut self, id: &str) -> Self {
+        id.clone_into(&mut self.id);
+        self
+    }
+
+    #[must_use]
+    pub const fn gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// -1.0 = Left, 0.0 = Center, 1.0 = Right
+    #[must_use]
+    pub const fn pan(mut self, pan: f32) -> Self {
+        self.pan = pan;
+        self
+    }
+
+    /// Adds an effect after the ones added before
+    #[must_use]
+    pub fn effect(mut self, effect: Box<dyn Processor>) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> Box<dyn Track> {
+        let mut track = self.source;
+        if !self.effects.is_empty() {
+            track = Box::new(EffectTrack::new(EFFECTS_ID, track, self.effects));
+        }
+        Box::new(GainPanTrack::new(&self.id, track, self.gain, self.pan))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dsp::gain::Gain,
+        scheduler::command::ParameterChange,
+        track::{self, constant::ConstantTrack},
+    };
+
+    #[test]
+    fn test_builds_one_track_named_by_the_builder() {
+        let mut track = TrackBuilder::new(Box::new(ConstantTrack::new(1.0, 1.0)))
+            .id("drums")
+            .gain(0.5)
+            .pan(1.0)
+            .effect(Box::new(Gain::new(-6.0)))
+            .build();
+        assert_eq!(track.id(), "drums");
+
+        let output = track.next_samples(1);
+        assert_eq!(output.frame(0).0, 0.0);
+        assert!((output.frame(0).1 - 0.2505).abs() < 1e-3); // -6 dB, then half gain
+
+        // the effect chain can be addressed inside it
+        assert!(track::find_mut(track.as_mut(), EFFECTS_ID).is_some());
+        track.apply_param_change(&ParameterChange::SetGain(1.0));
+        let output = track.next_samples(1);
+        assert!((output.frame(0).1 - 0.501).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,390 @@
+//! Clip groups: clips, usually on different tracks, edited as one, e.g. every mic of a
+//! multitracked drum take.
+//!
+//! Clips join a group with [`LinkedEdit::group_clips`] or
+//! [`TimelineTrack::set_clip_group`]. A [`LinkedEdit`] takes a clip and makes the same
+//! change to every clip in its group: moving them by the same distance, moving their
+//! starts or ends by the same distance, fading or removing them. Each track applies the
+//! change in its own [`EditMode`](super::timeline::EditMode). A clip outside any group is
+//! edited on its own.
+//!
+//! # Example
+//! ```
+//! use std::sync::Arc;
+//! use audio_engine::{
+//!     buffer::AudioBuffer,
+//!     track::{clip_group::LinkedEdit, timeline::{Clip, TimelineTrack}},
+//! };
+//!
+//! let take = Arc::new(AudioBuffer::stereo(100));
+//! let mut tracks = [TimelineTrack::new("kick"), TimelineTrack::new("snare")];
+//! for track in &mut tracks {
+//!     track.add_clip(Clip::new(0, Arc::clone(&take)));
+//! }
+//! let mut edit = LinkedEdit::new(&mut tracks).phase_locked(true);
+//! edit.group_clips(&[(0, 0), (1, 0)], "drums");
+//!
+//! edit.move_clip(0, 0, 50);
+//! assert_eq!(tracks[1].clips()[0].start, 50);
+//! ```
+
+use super::timeline::{Clip, TimelineTrack};
+
+/// Edits to clips across tracks that carry over to the clips grouped with them
+pub struct LinkedEdit<'a> {
+    tracks: &'a mut [TimelineTrack],
+    phase_locked: bool,
+}
+
+/// The edits a group's clips share
+#[derive(Clone, Copy)]
+enum Edit {
+    Move,
+    TrimStart,
+    TrimEnd,
+    Remove,
+}
+
+impl Edit {
+    /// The clip's value the edit changes
+    fn measure(self, clip: &Clip) -> usize {
+        match self {
+            Self::Move | Self::TrimStart | Self::Remove => clip.start,
+            Self::TrimEnd => clip.length(),
+        }
+    }
+
+    /// Changes the clip at `index` of `track`, which was `before` when the edit started,
+    /// by `delta`
+    fn apply(self, track: &mut TimelineTrack, index: usize, before: &Clip, delta: isize) {
+        let to = self.measure(before).saturating_add_signed(delta);
+        match self {
+            Self::Move => {
+                track.move_clip(index, to);
+            }
+            Self::TrimStart => {
+                track.trim_start(index, to);
+            }
+            Self::TrimEnd => {
+                track.trim_end(index, to);
+            }
+            Self::Remove => {
+                track.remove_clip(index);
+            }
+        }
+    }
+
+    /// Later clips go first when moving later, so they're out of the way of the earlier
+    /// ones. Nothing else reorders a group's clips.
+    const fn later_first(self, delta: isize) -> bool {
+        !matches!(self, Self::Move) || delta > 0
+    }
+}
+
+impl<'a> LinkedEdit<'a> {
+    pub const fn new(tracks: &'a mut [TimelineTrack]) -> Self {
+        Self {
+            tracks,
+            phase_locked: false,
+        }
+    }
+
+    /// Keeps grouped recordings of one source, e.g. the mics on a drum kit, in phase: every
+    /// clip in a group gets exactly the edit points and fades of the clip being edited.
+    /// Without it each track snaps edits to its own grid and fades cut edges its own way.
+    #[must_use]
+    pub const fn phase_locked(mut self, phase_locked: bool) -> Self {
+        self.phase_locked = phase_locked;
+        self
+    }
+
+    /// Puts the clips at `(track, index)` in `clips` in group `group`. `false`, with no
+    /// clip changed, if any of them doesn't exist.
+    pub fn group_clips(&mut self, clips: &[(usize, usize)], group: &str) -> bool {
+        let exists = |&(track, index): &(usize, usize)| {
+            self.tracks
+                .get(track)
+                .is_some_and(|track| index < track.clips().len())
+        };
+        if !clips.iter().all(exists) {
+            return false;
+        }
+        for &(track, index) in clips {
+            self.tracks[track].set_clip_group(index, Some(group.to_owned()));
+        }
+        true
+    }
+
+    /// Takes every clip out of group `group`
+    pub fn ungroup(&mut self, group: &str) {
+        for track in &mut *self.tracks {
+            for index in 0..track.clips().len() {
+                if track.clips()[index].group() == Some(group) {
+                    track.set_clip_group(index, None);
+                }
+            }
+        }
+    }
+
+    /// Moves the clip at `index` of track `track` to start at `start`, and every clip
+    /// grouped with it by as much. `false` if there's no such clip.
+    pub fn move_clip(&mut self, track: usize, index: usize, start: usize) -> bool {
+        self.edit(Edit::Move, track, index, start)
+    }
+
+    /// Moves where the clip at `index` of track `track` starts to `start`, as
+    /// [`TimelineTrack::trim_start`], and the starts of every clip grouped with it by as
+    /// much. `false` if there's no such clip.
+    pub fn trim_start(&mut self, track: usize, index: usize, start: usize) -> bool {
+        self.edit(Edit::TrimStart, track, index, start)
+    }
+
+    /// Changes the length of the clip at `index` of track `track` to `length`, as
+    /// [`TimelineTrack::trim_end`], and the lengths of every clip grouped with it by as
+    /// much. `false` if there's no such clip.
+    pub fn trim_end(&mut self, track: usize, index: usize, length: usize) -> bool {
+        self.edit(Edit::TrimEnd, track, index, length)
+    }
+
+    /// Removes the clip at `index` of track `track` and every clip grouped with it.
+    /// `false` if there's no such clip.
+    pub fn remove_clip(&mut self, track: usize, index: usize) -> bool {
+        let Some(clip) = self.clip(track, index) else {
+            return false;
+        };
+        let start = clip.start;
+        self.edit(Edit::Remove, track, index, start)
+    }
+
+    /// Gives the clip at `index` of track `track` and every clip grouped with it linear
+    /// fades of `fade_in` and `fade_out` frames. `false` if there's no such clip.
+    pub fn set_fades(
+        &mut self,
+        track: usize,
+        index: usize,
+        fade_in: usize,
+        fade_out: usize,
+    ) -> bool {
+        let Some(clip) = self.clip(track, index) else {
+            return false;
+        };
+        let Some(group) = clip.group().map(str::to_owned) else {
+            let mut clip = clip.clone();
+            clip.set_fades(fade_in, fade_out);
+            return self.tracks[track].set_clip_fades(index, clip.fades(), clip.fade_curves());
+        };
+        let mut faded = clip.clone();
+        faded.set_fades(fade_in, fade_out);
+        self.copy_fades(&group, &faded);
+        true
+    }
+
+    fn clip(&self, track: usize, index: usize) -> Option<&Clip> {
+        self.tracks.get(track)?.clips().get(index)
+    }
+
+    /// Applies `edit` to the clip at `index` of track `track`, bringing its measure to `to`,
+    /// and by as much to every clip grouped with it.
+    ///
+    /// A group's clips on one track keep their order through an edit that changes them all
+    /// alike, so each is found again by its position among them.
+    fn edit(&mut self, edit: Edit, track: usize, index: usize, to: usize) -> bool {
+        let Some(clip) = self.clip(track, index) else {
+            return false;
+        };
+        let delta = to.cast_signed() - edit.measure(clip).cast_signed();
+        let Some(group) = clip.group().map(str::to_owned) else {
+            let clip = clip.clone();
+            edit.apply(&mut self.tracks[track], index, &clip, delta);
+            return true;
+        };
+        let in_group = |clip: &Clip| clip.group() == Some(group.as_str());
+        let before: Vec<Vec<Clip>> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                track
+                    .clips()
+                    .iter()
+                    .filter(|clip| in_group(clip))
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        let leader = self.tracks[track].clips()[..index]
+            .iter()
+            .filter(|clip| in_group(clip))
+            .count();
+
+        if !self.phase_locked || matches!(edit, Edit::Remove) {
+            for (other, clips) in self.tracks.iter_mut().zip(&before) {
+                apply_all(other, &group, clips, edit, delta, None);
+            }
+            return true;
+        }
+
+        // the edited clip goes first, snapped as its track does, then the rest follow it
+        // to the sample without snapping
+        let leader_before = &before[track][leader];
+        edit.apply(&mut self.tracks[track], index, leader_before, delta);
+        let Some(edited) = nth_in_group(&self.tracks[track], &group, leader)
+            .map(|index| self.tracks[track].clips()[index].clone())
+        else {
+            return true;
+        };
+        let delta = edit.measure(&edited).cast_signed() - edit.measure(leader_before).cast_signed();
+        for (number, (other, clips)) in self.tracks.iter_mut().zip(&before).enumerate() {
+            let grid = other.grid().copied();
+            other.set_grid(None);
+            let skip = (number == track).then_some(leader);
+            apply_all(other, &group, clips, edit, delta, skip);
+            other.set_grid(grid);
+        }
+        self.copy_fades(&group, &edited);
+        true
+    }
+
+    /// Gives every clip in `group` the fades of `clip`
+    fn copy_fades(&mut self, group: &str, clip: &Clip) {
+        for track in &mut *self.tracks {
+            for index in 0..track.clips().len() {
+                if track.clips()[index].group() == Some(group) {
+                    track.set_clip_fades(index, clip.fades(), clip.fade_curves());
+                }
+            }
+        }
+    }
+}
+
+/// Applies `edit` by `delta` to `track`'s clips in `group`, which were `before`, except the
+/// `skip`th of them
+fn apply_all(
+    track: &mut TimelineTrack,
+    group: &str,
+    before: &[Clip],
+    edit: Edit,
+    delta: isize,
+    skip: Option<usize>,
+) {
+    let mut order: Vec<usize> = (0..before.len()).filter(|&nth| Some(nth) != skip).collect();
+    if edit.later_first(delta) {
+        order.reverse();
+    }
+    for nth in order {
+        if let Some(index) = nth_in_group(track, group, nth) {
+            edit.apply(track, index, &before[nth], delta);
+        }
+    }
+}
+
+/// Index of the `nth` clip in `group` on `track`
+fn nth_in_group(track: &TimelineTrack, group: &str, nth: usize) -> Option<usize> {
+    track
+        .clips()
+        .iter()
+        .enumerate()
+        .filter(|(_, clip)| clip.group() == Some(group))
+        .nth(nth)
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use transport::{display::FrameRate, timecode::TimecodeGrid};
+
+    use super::*;
+    use crate::{
+        buffer::AudioBuffer,
+        track::timeline::{EditMode, FadeCurve},
+    };
+
+    fn layout(track: &TimelineTrack) -> Vec<(usize, usize)> {
+        track
+            .clips()
+            .iter()
+            .map(|clip| (clip.start, clip.end()))
+            .collect()
+    }
+
+    /// Two drum tracks with a grouped clip each, the snare's starting 5 frames later, and
+    /// an ungrouped clip on the kick
+    fn drums() -> [TimelineTrack; 2] {
+        let audio = Arc::new(AudioBuffer::stereo(20));
+        let mut tracks = [TimelineTrack::new("kick"), TimelineTrack::new("snare")];
+        tracks[0].add_clip(Clip::new(0, Arc::clone(&audio)));
+        tracks[0].add_clip(Clip::new(100, Arc::clone(&audio)));
+        tracks[1].add_clip(Clip::new(5, audio));
+        assert!(LinkedEdit::new(&mut tracks).group_clips(&[(0, 0), (1, 0)], "drums"));
+        tracks
+    }
+
+    #[test]
+    fn test_grouped_clips_move_and_trim_together() {
+        let mut tracks = drums();
+        let mut edit = LinkedEdit::new(&mut tracks);
+        assert!(edit.move_clip(1, 0, 45));
+        assert!(edit.trim_end(0, 0, 10));
+        assert!(edit.trim_start(0, 0, 42));
+
+        assert_eq!(layout(&tracks[0]), vec![(42, 50), (100, 120)]);
+        assert_eq!(layout(&tracks[1]), vec![(47, 55)]);
+    }
+
+    #[test]
+    fn test_ungrouped_clips_edit_alone() {
+        let mut tracks = drums();
+        let mut edit = LinkedEdit::new(&mut tracks);
+        assert!(edit.move_clip(0, 1, 80));
+        edit.ungroup("drums");
+        assert!(edit.move_clip(0, 0, 30));
+        assert!(!edit.move_clip(2, 0, 30));
+        assert!(!edit.group_clips(&[(0, 0), (1, 3)], "drums"));
+
+        assert_eq!(layout(&tracks[1]), vec![(5, 25)]);
+        assert_eq!(tracks[0].clips()[0].group(), None);
+    }
+
+    #[test]
+    fn test_removing_a_grouped_clip_follows_each_tracks_mode() {
+        let mut tracks = drums();
+        tracks[0].set_edit_mode(EditMode::Ripple);
+        assert!(LinkedEdit::new(&mut tracks).remove_clip(1, 0));
+
+        assert_eq!(layout(&tracks[0]), vec![(80, 100)]);
+        assert!(tracks[1].clips().is_empty());
+    }
+
+    #[test]
+    fn test_phase_locked_edits_are_sample_identical() {
+        let mut tracks = drums();
+        // only the snare snaps, to 25 fps frames of 40 samples at 1000 Hz, and only the
+        // kick fades its cut edges
+        tracks[1].set_grid(Some(TimecodeGrid::new(FrameRate::Fps25, 1000.0)));
+        tracks[0].set_edit_fade(3);
+
+        let mut edit = LinkedEdit::new(&mut tracks);
+        assert!(edit.move_clip(0, 0, 33));
+        assert_eq!(tracks[1].clips()[0].start, 40);
+
+        let mut tracks = drums();
+        tracks[1].set_grid(Some(TimecodeGrid::new(FrameRate::Fps25, 1000.0)));
+        tracks[0].set_edit_fade(3);
+        let mut edit = LinkedEdit::new(&mut tracks).phase_locked(true);
+        // the snare snaps 38 to 40, the kick follows it 35 frames later instead of to 33
+        assert!(edit.move_clip(1, 0, 38));
+        assert!(edit.trim_end(0, 0, 12));
+        assert_eq!(layout(&tracks[0]), vec![(35, 47), (100, 120)]);
+        assert_eq!(layout(&tracks[1]), vec![(40, 52)]);
+        // the kick's trim faded its end, the snare's end follows
+        assert_eq!(tracks[1].clips()[0].fades(), (0, 3));
+        assert_eq!(
+            tracks[1].clips()[0].fade_curves(),
+            (FadeCurve::Linear, FadeCurve::EqualPower)
+        );
+
+        assert!(LinkedEdit::new(&mut tracks).set_fades(1, 0, 4, 0));
+        assert_eq!(tracks[0].clips()[0].fades(), (4, 0));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::track::Track;
+use crate::{buffer::AudioBuffer, track::Track};
 
 pub struct ConstantTrack {
     sample: (f32, f32),
@@ -17,9 +17,9 @@ impl Track for ConstantTrack {
         "constant-track".to_string()
     }
 
-    fn fill_next_samples(&mut self, next_sample: &mut [(f32, f32)]) {
-        for sample in next_sample.iter_mut() {
-            *sample = self.sample;
-        }
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        let (left, right) = buffer.stereo_mut();
+        left.fill(self.sample.0);
+        right.fill(self.sample.1);
     }
 }
@@ -0,0 +1,77 @@
+use crate::{
+    buffer::AudioBuffer, dsp::Processor, midi::EventKind, scheduler::command::ParameterChange,
+    track::Track,
+};
+
+/// Runs a track through a chain of processors, e.g. the effects added by
+/// [`TrackBuilder::effect`](crate::track::builder::TrackBuilder::effect)
+pub struct EffectTrack {
+    id: String,
+    inner: Box<dyn Track>,
+    effects: Vec<Box<dyn Processor>>,
+}
+
+impl EffectTrack {
+    #[must_use]
+    pub fn new(id: &str, inner: Box<dyn Track>, effects: Vec<Box<dyn Processor>>) -> Self {
+        Self {
+            id: id.to_owned(),
+            inner,
+            effects,
+        }
+    }
+
+    fn process(&mut self, buffer: &mut AudioBuffer) {
+        let frames = buffer.frames();
+        for effect in &mut self.effects {
+            effect.process(buffer, 0, frames);
+        }
+    }
+}
+
+impl Track for EffectTrack {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        self.inner.fill_next_samples(buffer);
+        self.process(buffer);
+    }
+
+    fn fill_from(&mut self, position: usize, buffer: &mut AudioBuffer) {
+        self.inner.fill_from(position, buffer);
+        self.process(buffer);
+    }
+
+    fn apply_param_change(&mut self, change: &ParameterChange) {
+        self.inner.apply_param_change(change);
+    }
+
+    fn queue_event(&mut self, event: EventKind) {
+        self.inner.queue_event(event);
+    }
+
+    fn aux_outputs(&self) -> usize {
+        self.inner.aux_outputs()
+    }
+
+    fn aux_output(&self, index: usize) -> Option<&AudioBuffer> {
+        self.inner.aux_output(index)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Track> {
+        Some(self.inner.as_mut())
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        for effect in &mut self.effects {
+            effect.reset();
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{scheduler::command::ParameterChange, track::Track};
+use crate::{
+    buffer::AudioBuffer,
+    dsp::math::{PanLaw, pan_gains},
+    midi::EventKind,
+    scheduler::command::ParameterChange,
+    track::Track,
+};
 
 pub struct GainPanTrack {
     /// track id
@@ -21,6 +27,19 @@ impl GainPanTrack {
             pan,
         }
     }
+
+    /// Pans with the mixer's default law, so a track sounds the same here as on a channel
+    fn apply_gain_pan(&self, buffer: &mut AudioBuffer) {
+        let (pan_l, pan_r) = pan_gains(PanLaw::default(), self.pan);
+
+        let (left, right) = buffer.stereo_mut();
+        for l in left.iter_mut() {
+            *l *= self.gain * pan_l;
+        }
+        for r in right.iter_mut() {
+            *r *= self.gain * pan_r;
+        }
+    }
 }
 
 impl Track for GainPanTrack {
@@ -28,25 +47,17 @@ impl Track for GainPanTrack {
         self.id.clone()
     }
 
-    fn fill_next_samples(&mut self, next_samples: &mut [(f32, f32)]) {
-        // @todo review panning logic here
-        let pan_l = (1.0 - self.pan.clamp(-1.0, 1.0)) * 0.5;
-        let pan_r = (1.0 + self.pan.clamp(-1.0, 1.0)) * 0.5;
-
-        self.inner.fill_next_samples(next_samples);
-
-        for (l, r) in next_samples.iter_mut() {
-            *l = *l * self.gain * pan_l;
-            *r = *r * self.gain * pan_r;
-        }
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        self.inner.fill_next_samples(buffer);
+        self.apply_gain_pan(buffer);
     }
 
-    fn apply_param_change(&mut self, id: &str, change: &ParameterChange) {
-        if self.id != id {
-            self.inner.apply_param_change(id, change);
-            return;
-        }
+    fn fill_from(&mut self, position: usize, buffer: &mut AudioBuffer) {
+        self.inner.fill_from(position, buffer);
+        self.apply_gain_pan(buffer);
+    }
 
+    fn apply_param_change(&mut self, change: &ParameterChange) {
         match change {
             ParameterChange::SetGain(val) => {
                 self.gain = *val;
@@ -54,10 +65,34 @@ impl Track for GainPanTrack {
             ParameterChange::SetPan(val) => {
                 self.pan = *val;
             }
+            // not ours, so it's meant for the track inside
+            ParameterChange::SetFrequency(_) | ParameterChange::Instrument { .. } => {
+                self.inner.apply_param_change(change);
+            }
         }
     }
 
+    fn queue_event(&mut self, event: EventKind) {
+        self.inner.queue_event(event);
+    }
+
+    fn aux_outputs(&self) -> usize {
+        self.inner.aux_outputs()
+    }
+
+    fn aux_output(&self, index: usize) -> Option<&AudioBuffer> {
+        self.inner.aux_output(index)
+    }
+
+    fn inner_mut(&mut self) -> Option<&mut dyn Track> {
+        Some(self.inner.as_mut())
+    }
+
     fn reset(&mut self) {
         self.inner.reset();
     }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
 }
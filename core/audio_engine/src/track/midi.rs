@@ -0,0 +1,412 @@
+use crate::{
+    buffer::AudioBuffer,
+    constants::MAX_BLOCK_FRAMES,
+    midi::{
+        EventKind, EventList, Instrument, SequenceEvent,
+        drum_map::{DrumMap, KeyRemap},
+        effect::MidiEffect,
+        humanize::Humanize,
+    },
+    scheduler::command::ParameterChange,
+    track::Track,
+};
+
+/// A track playing a sequence of events through an [`Instrument`].
+///
+/// Events are handed to the instrument at their exact frame within each block, so notes
+/// don't snap to block boundaries. Live events sent with
+/// [`SchedulerCommand::Midi`](crate::scheduler::command::SchedulerCommand::Midi) play at the
+/// start of the next block.
+///
+/// Sequenced notes can be left out by chance and humanized, see [`MidiTrack::set_humanize`].
+/// Events pass through the MIDI effects in order, then the key remap, on their way to the
+/// instrument.
+///
+/// Instruments with several outputs play their first on the track's main output, the others
+/// are the track's aux outputs, see [`Track::aux_outputs`].
+///
+/// Never finishes: the instrument may ring on after the last event.
+pub struct MidiTrack {
+    id: String,
+    instrument: Box<dyn Instrument>,
+    /// One buffer per output of a multi-output instrument, empty for single-output ones
+    outputs: Vec<AudioBuffer>,
+    effects: Vec<Box<dyn MidiEffect>>,
+    /// Sorted by frame
+    sequence: Vec<SequenceEvent>,
+    humanize: Humanize,
+    /// Jumps in playback since the last reset, so every loop pass rolls anew
+    pass: u64,
+    /// Live events waiting for the next block
+    live: EventList,
+    /// The block's events, handed to the instrument
+    events: EventList,
+    /// Output of the effect being run
+    scratch: EventList,
+    position: usize,
+    /// Pad names of the instrument, for display
+    drum_map: Option<DrumMap>,
+    /// Moves note keys on their way to the instrument
+    remap: Option<KeyRemap>,
+}
+
+impl MidiTrack {
+    #[must_use]
+    pub fn new(id: &str, instrument: Box<dyn Instrument>) -> Self {
+        let outputs = match instrument.outputs() {
+            0 | 1 => Vec::new(),
+            count => (0..count)
+                .map(|_| AudioBuffer::stereo(MAX_BLOCK_FRAMES))
+                .collect(),
+        };
+        Self {
+            id: id.to_owned(),
+            instrument,
+            outputs,
+            effects: Vec::new(),
+            sequence: Vec::new(),
+            humanize: Humanize::default(),
+            pass: 0,
+            live: EventList::new(),
+            events: EventList::new(),
+            scratch: EventList::new(),
+            position: 0,
+            drum_map: None,
+            remap: None,
+        }
+    }
+
+    /// Replaces the events played, e.g. [`MidiClip::to_sequence`] or `(frame, event)` pairs
+    ///
+    /// [`MidiClip::to_sequence`]: crate::midi::clip::MidiClip::to_sequence
+    pub fn set_sequence<E: Into<SequenceEvent>>(&mut self, sequence: impl IntoIterator<Item = E>) {
+        self.sequence = sequence.into_iter().map(Into::into).collect();
+        self.sequence.sort_by_key(|event| event.frame);
+    }
+
+    #[must_use]
+    pub fn sequence(&self) -> &[SequenceEvent] {
+        &self.sequence
+    }
+
+    #[must_use]
+    pub const fn humanize(&self) -> Humanize {
+        self.humanize
+    }
+
+    /// Varies the timing and velocity of sequenced notes as they play, and rolls their
+    /// chance of playing with the same seed. Live events are left alone.
+    pub const fn set_humanize(&mut self, humanize: Humanize) {
+        self.humanize = humanize;
+    }
+
+    /// Appends a MIDI effect after the existing ones
+    pub fn add_effect(&mut self, effect: Box<dyn MidiEffect>) {
+        self.effects.push(effect);
+    }
+
+    pub fn remove_effect(&mut self, index: usize) -> Option<Box<dyn MidiEffect>> {
+        (index < self.effects.len()).then(|| self.effects.remove(index))
+    }
+
+    #[must_use]
+    pub fn effects(&self) -> &[Box<dyn MidiEffect>] {
+        &self.effects
+    }
+
+    #[must_use]
+    pub fn drum_map(&self) -> Option<&DrumMap> {
+        self.drum_map.as_ref()
+    }
+
+    /// Names the instrument's pads. Only metadata: notes aren't moved, see
+    /// [`MidiTrack::set_key_remap`].
+    pub fn set_drum_map(&mut self, drum_map: Option<DrumMap>) {
+        self.drum_map = drum_map;
+    }
+
+    #[must_use]
+    pub fn key_remap(&self) -> Option<&KeyRemap> {
+        self.remap.as_ref()
+    }
+
+    /// Plays notes on remapped keys, e.g. [`DrumMap::remap_to`] for a pattern written for
+    /// another instrument's layout. The sequence itself is left as it is.
+    pub fn set_key_remap(&mut self, remap: Option<KeyRemap>) {
+        self.remap = remap;
+    }
+
+    fn reset_voices(&mut self) {
+        for effect in &mut self.effects {
+            effect.reset();
+        }
+        self.instrument.reset();
+    }
+}
+
+impl Track for MidiTrack {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        let frames = buffer.frames();
+        let window_end = self.position + frames;
+        self.events.clear();
+        for event in self.live.iter() {
+            self.events.push(0, event.kind);
+        }
+        self.live.clear();
+
+        // humanized notes can move into this block from either side of it
+        let reach = self.humanize.timing;
+        let first = self
+            .sequence
+            .partition_point(|event| event.frame + reach < self.position);
+        for event in self.sequence[first..]
+            .iter()
+            .take_while(|event| event.frame < window_end + reach)
+        {
+            let mut kind = event.kind;
+            let mut frame = event.frame;
+            if let Some(note) = event.note {
+                if !self.humanize.plays(self.pass, note, event.chance) {
+                    continue;
+                }
+                frame = frame.saturating_add_signed(self.humanize.offset(self.pass, note));
+                if let EventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                } = kind
+                {
+                    let velocity = self.humanize.velocity(self.pass, note, velocity);
+                    kind = EventKind::NoteOn {
+                        channel,
+                        key,
+                        velocity,
+                    };
+                }
+            }
+            if (self.position..window_end).contains(&frame) {
+                self.events.push(frame - self.position, kind);
+            }
+        }
+
+        for effect in &mut self.effects {
+            self.scratch.clear();
+            effect.process(&self.events, &mut self.scratch, self.position, frames);
+            std::mem::swap(&mut self.events, &mut self.scratch);
+        }
+        if let Some(remap) = self.remap.as_ref() {
+            self.events.map_kinds(|kind| {
+                kind.key()
+                    .map_or(kind, |key| kind.with_key(remap.apply(key)))
+            });
+        }
+
+        buffer.clear();
+        if self.outputs.is_empty() {
+            self.instrument.process(&self.events, buffer);
+        } else {
+            for output in &mut self.outputs {
+                if output.capacity() < frames {
+                    *output = AudioBuffer::stereo(frames);
+                }
+                output.set_frames(frames);
+                output.clear();
+            }
+            self.instrument
+                .process_outputs(&self.events, &mut self.outputs);
+            buffer.copy_from(0, &self.outputs[0], 0, frames);
+        }
+        self.position = window_end;
+    }
+
+    fn fill_from(&mut self, position: usize, buffer: &mut AudioBuffer) {
+        if position != self.position {
+            // notes held before a seek or loop wrap would never get their note off
+            self.reset_voices();
+            self.position = position;
+            self.pass += 1;
+        }
+        self.fill_next_samples(buffer);
+    }
+
+    fn queue_event(&mut self, event: EventKind) {
+        self.live.push(0, event);
+    }
+
+    fn aux_outputs(&self) -> usize {
+        self.outputs.len().saturating_sub(1)
+    }
+
+    fn aux_output(&self, index: usize) -> Option<&AudioBuffer> {
+        self.outputs.get(index + 1)
+    }
+
+    fn apply_param_change(&mut self, change: &ParameterChange) {
+        if let ParameterChange::Instrument { id, value } = *change {
+            self.live.push(0, EventKind::Parameter { id, value });
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position = 0;
+        self.pass = 0;
+        self.live.clear();
+        self.reset_voices();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::{
+        clip::{MidiClip, Note},
+        effect::{ChordTrigger, Scale, ScaleQuantize},
+    };
+
+    /// Writes each note's velocity as an impulse at the note's frame
+    struct Impulses;
+
+    impl Instrument for Impulses {
+        fn process(&mut self, events: &EventList, buffer: &mut AudioBuffer) {
+            for event in events.iter() {
+                if let EventKind::NoteOn { velocity, .. } = event.kind {
+                    buffer.channel_mut(0)[event.offset] = f32::from(velocity);
+                }
+            }
+        }
+    }
+
+    /// Plays each note's velocity as an impulse on the output numbered by its channel
+    struct PerChannelOuts;
+
+    impl Instrument for PerChannelOuts {
+        fn process(&mut self, events: &EventList, buffer: &mut AudioBuffer) {
+            Impulses.process(events, buffer);
+        }
+
+        fn outputs(&self) -> usize {
+            3
+        }
+
+        fn process_outputs(&mut self, events: &EventList, outputs: &mut [AudioBuffer]) {
+            for event in events.iter() {
+                if let EventKind::NoteOn {
+                    channel, velocity, ..
+                } = event.kind
+                {
+                    outputs[usize::from(channel)].channel_mut(0)[event.offset] =
+                        f32::from(velocity);
+                }
+            }
+        }
+    }
+
+    fn note_on(velocity: u8) -> EventKind {
+        EventKind::NoteOn {
+            channel: 0,
+            key: 60,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn test_notes_start_at_their_frame_within_the_block() {
+        let mut track = MidiTrack::new("keys", Box::new(Impulses));
+        track.set_sequence(vec![(70, note_on(2)), (5, note_on(1))]);
+
+        let first = track.next_samples(64);
+        assert_eq!(first.channel(0)[5], 1.0);
+        assert_eq!(first.channel(0).iter().filter(|&&s| s != 0.0).count(), 1);
+
+        track.queue_event(note_on(3));
+        let second = track.next_samples(64);
+        assert_eq!(second.channel(0)[0], 3.0);
+        assert_eq!(second.channel(0)[6], 2.0);
+    }
+
+    #[test]
+    fn test_humanized_render_repeats_with_the_same_seed() {
+        let render = |seed| {
+            let mut clip = MidiClip::new();
+            for step in 0..16 {
+                clip.add_note(Note {
+                    start: step * 120,
+                    length: 60,
+                    key: 36,
+                    velocity: 100,
+                    channel: 0,
+                    chance: 50,
+                });
+            }
+            let mut track = MidiTrack::new("drums", Box::new(Impulses));
+            track.set_sequence(clip.to_sequence(10.0));
+            track.set_humanize(Humanize {
+                timing: 30,
+                velocity: 20,
+                seed,
+            });
+            track.next_samples(19200).channel(0).to_vec()
+        };
+
+        let take = render(1);
+        let hits: Vec<_> = take.iter().filter(|&&s| s != 0.0).collect();
+        assert!(!hits.is_empty() && hits.len() < 16);
+        assert!(
+            hits.iter()
+                .all(|&&velocity| (80.0..=120.0).contains(&velocity))
+        );
+        assert_eq!(render(1), take);
+        assert_ne!(render(2), take);
+    }
+
+    #[test]
+    fn test_effects_run_before_the_remap() {
+        let mut track = MidiTrack::new("keys", Box::new(Impulses));
+        track.add_effect(Box::new(ChordTrigger::major()));
+        track.add_effect(Box::new(ScaleQuantize::new(0, Scale::MINOR_PENTATONIC)));
+        let mut remap = KeyRemap::identity();
+        remap.set(67, 48);
+        track.set_key_remap(Some(remap));
+        track.set_sequence(vec![(0, note_on(100))]);
+
+        track.next_samples(8);
+        // C major chord, the E pulled down to E flat, the G moved by the remap
+        assert_eq!(
+            track
+                .events
+                .iter()
+                .filter_map(|e| e.kind.key())
+                .collect::<Vec<_>>(),
+            vec![60, 63, 48]
+        );
+    }
+
+    #[test]
+    fn test_instrument_outputs_become_aux_outputs() {
+        let mut track = MidiTrack::new("drums", Box::new(PerChannelOuts));
+        let hit = |channel| EventKind::NoteOn {
+            channel,
+            key: 36,
+            velocity: 100,
+        };
+        track.set_sequence(vec![(1, hit(0)), (2, hit(2))]);
+
+        let main = track.next_samples(4);
+        assert_eq!(track.aux_outputs(), 2);
+        assert_eq!(main.channel(0), &[0.0, 100.0, 0.0, 0.0]);
+        assert!(
+            track
+                .aux_output(0)
+                .unwrap()
+                .channel(0)
+                .iter()
+                .all(|&s| s == 0.0)
+        );
+        assert_eq!(track.aux_output(1).unwrap().channel(0)[2], 100.0);
+        assert!(track.aux_output(2).is_none());
+    }
+}
@@ -1,23 +1,94 @@
-use crate::scheduler::command::ParameterChange;
+use crate::{buffer::AudioBuffer, midi::EventKind, scheduler::command::ParameterChange};
 
+pub mod builder;
+pub mod clip_group;
 pub mod constant;
+pub mod effect;
 pub mod gainpan;
+pub mod midi;
 pub mod sinewave;
+pub mod timeline;
+pub mod video;
 pub mod wav;
 
-/// A track produces stereo audio frames (L, R)
+/// Separates the track ids of a path, e.g. `"drums/kick"`
+pub const PATH_SEPARATOR: char = '/';
+
+/// A track renders stereo audio into planar [`AudioBuffer`]s, or mono audio when it reports
+/// a single channel
 pub trait Track
 where
     Self: Sync + Send,
 {
     fn id(&self) -> String;
-    fn fill_next_samples(&mut self, next_samples: &mut [(f32, f32)]);
-    fn apply_param_change(&mut self, _id: &str, _change: &ParameterChange) {}
+    /// Fills all `buffer.frames()` frames of a (at least) stereo buffer, or of a mono one if
+    /// [`Track::channels`] is 1
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer);
+    /// Channels of the track's material. Mono tracks render into mono buffers, half the
+    /// work and memory, and the mixer up-mixes them to both sides of the channel strip.
+    /// Tracks that process stereo, wrappers included, keep the default of 2.
+    fn channels(&self) -> usize {
+        2
+    }
+    /// Fills `buffer` starting at frame `position` of the track's material, so the
+    /// scheduler decides where playback is after seeks and loop wraps.
+    /// Tracks that can only stream ignore `position` and carry on.
+    fn fill_from(&mut self, _position: usize, buffer: &mut AudioBuffer) {
+        self.fill_next_samples(buffer);
+    }
+    /// Applies a change addressed to this track, see [`find_mut`] for how tracks are addressed
+    fn apply_param_change(&mut self, _change: &ParameterChange) {}
+    /// Plays a live MIDI event at the start of the next block. Tracks without an instrument
+    /// ignore it, wrappers pass it on.
+    fn queue_event(&mut self, _event: EventKind) {}
+    /// Stereo outputs besides the main one, e.g. an instrument's per-pad outs. Wrappers pass
+    /// them on untouched.
+    fn aux_outputs(&self) -> usize {
+        0
+    }
+    /// Aux output `index` of the last block rendered
+    fn aux_output(&self, _index: usize) -> Option<&AudioBuffer> {
+        None
+    }
+    /// The track this one wraps, if any, so changes can be addressed to it
+    fn inner_mut(&mut self) -> Option<&mut dyn Track> {
+        None
+    }
     fn reset(&mut self) {} // Optional; for retriggerable tracks
+    /// `true` once a finite track has played all of its material
+    fn is_finished(&self) -> bool {
+        false
+    }
+    /// Frame `frame` of the track's material, read out of order for scrubbing.
+    /// `None` past the end, and for tracks that can only stream.
+    fn frame_at(&self, _frame: usize) -> Option<(f32, f32)> {
+        None
+    }
     /// required for testing
-    fn next_samples(&mut self, frame_size: usize) -> Vec<(f32, f32)> {
-        let mut buf = vec![(0.0f32, 0.0f32); frame_size];
-        self.fill_next_samples(&mut buf[..]);
+    fn next_samples(&mut self, frame_size: usize) -> AudioBuffer {
+        let mut buf = AudioBuffer::stereo(frame_size);
+        self.fill_next_samples(&mut buf);
         buf
     }
 }
+
+/// The track `path` points to below `track`, or `track` itself for an empty path.
+///
+/// Each id in the path names the nearest track with that id wrapped (directly or not) by the
+/// previous one, so `"outer/inner"` reaches `inner` inside `outer` without naming any
+/// wrappers in between.
+pub fn find_mut<'a>(mut track: &'a mut dyn Track, path: &str) -> Option<&'a mut dyn Track> {
+    for id in path.split(PATH_SEPARATOR).filter(|id| !id.is_empty()) {
+        track = find_wrapped(track, id)?;
+    }
+    Some(track)
+}
+
+fn find_wrapped<'a>(track: &'a mut dyn Track, id: &str) -> Option<&'a mut dyn Track> {
+    let inner = track.inner_mut()?;
+    if inner.id() == id {
+        Some(inner)
+    } else {
+        find_wrapped(inner, id)
+    }
+}
@@ -1,10 +1,19 @@
 use std::f32::consts::PI;
 
-use crate::track::Track;
+use crate::{buffer::AudioBuffer, scheduler::command::ParameterChange, track::Track};
+
+/// Time constant of frequency changes in seconds, so sweeps and jumps don't click
+const GLIDE_TIME: f32 = 0.01;
+/// Glide steps smaller than this (in Hz) finish the glide
+const MIN_GLIDE_STEP: f32 = 1e-4;
 
 #[derive(Clone, Copy)]
 pub struct SineWaveTrack {
     freq: f32,
+    /// Frequency `freq` glides towards
+    target_freq: f32,
+    /// Share of the distance to `target_freq` covered per sample
+    glide: f32,
     sample_rate: f32,
     phase: f32,
 }
@@ -13,10 +22,18 @@ impl SineWaveTrack {
     pub fn new(freq: f32, sample_rate: f32) -> Self {
         Self {
             freq,
+            target_freq: freq,
+            glide: 1.0 - (-1.0 / (GLIDE_TIME * sample_rate)).exp(),
             sample_rate,
             phase: 0.0,
         }
     }
+
+    /// Current frequency in Hz, part way to the last one set while gliding
+    #[must_use]
+    pub fn frequency(&self) -> f32 {
+        self.freq
+    }
 }
 
 impl Track for SineWaveTrack {
@@ -24,17 +41,47 @@ impl Track for SineWaveTrack {
         "sine-wave-track".to_owned()
     }
 
-    fn fill_next_samples(&mut self, next_samples: &mut [(f32, f32)]) {
-        let phase_increment = 2.0 * PI * self.freq / self.sample_rate;
-
-        for (l, r) in next_samples {
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        let (left, right) = buffer.stereo_mut();
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
             let sample = (self.phase).sin();
             *l = sample;
             *r = sample;
-            self.phase += phase_increment;
+            let step = (self.target_freq - self.freq) * self.glide;
+            // snaps the last fraction of a Hz that f32 steps can't close
+            self.freq = if step.abs() < MIN_GLIDE_STEP {
+                self.target_freq
+            } else {
+                self.freq + step
+            };
+            self.phase += 2.0 * PI * self.freq / self.sample_rate;
             if self.phase >= 2.0 * PI {
                 self.phase -= 2.0 * PI;
             }
         }
     }
+
+    fn apply_param_change(&mut self, change: &ParameterChange) {
+        if let ParameterChange::SetFrequency(freq) = change {
+            self.target_freq = freq.max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_glides_to_new_value() {
+        let mut sine = SineWaveTrack::new(440.0, 48000.0);
+        sine.apply_param_change(&ParameterChange::SetFrequency(880.0));
+
+        sine.next_samples(48);
+        // one millisecond in: on its way, not there yet
+        assert!(sine.frequency() > 440.0 && sine.frequency() < 660.0);
+
+        sine.next_samples(9600);
+        assert!((sine.frequency() - 880.0).abs() < 0.01);
+    }
 }
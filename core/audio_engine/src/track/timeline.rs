@@ -0,0 +1,1173 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use transport::timecode::TimecodeGrid;
+
+use crate::{
+    buffer::AudioBuffer,
+    dsp::{math::fade_gain, stretch::time_stretch},
+    metadata::Metadata,
+    track::Track,
+};
+
+/// Tempos closer than this play the original audio rather than a stretch
+const TEMPO_EPSILON: f64 = 1e-6;
+
+/// How clip edits on a [`TimelineTrack`] treat the clips around them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EditMode {
+    /// Edits replace whatever they land on, gaps stay where they are
+    #[default]
+    Overwrite,
+    /// Removing or shortening a clip pulls the later clips earlier to close the gap,
+    /// placing or lengthening one pushes them later
+    Ripple,
+    /// Placed clips push the later clips out of the way, removed clips leave a gap
+    Insert,
+}
+
+/// Shape of a clip fade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// A quarter sine, keeping the summed power of a fade out and a fade in over the same
+    /// frames constant
+    EqualPower,
+}
+
+/// Channels of its source a clip plays, e.g. one microphone of a multichannel field
+/// recording. Indices count from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelSelection {
+    /// The source as it is, its first two channels if it has more
+    #[default]
+    All,
+    /// One channel, played as a mono clip
+    Mono(usize),
+    /// Two channels, played as left and right
+    Stereo(usize, usize),
+}
+
+/// A piece of audio placed on a [`TimelineTrack`].
+///
+/// Clips share their audio, so splitting or trimming one never copies samples.
+#[derive(Debug, Clone)]
+pub struct Clip {
+    /// Frame on the track where the clip begins
+    pub start: usize,
+    /// Frame of the source audio heard at `start`
+    offset: usize,
+    length: usize,
+    source: Arc<AudioBuffer>,
+    /// Channels of `source` played
+    selection: ChannelSelection,
+    /// The clip as it was before its audio was processed, see [`Clip::with_render`]
+    unprocessed: Option<Box<Self>>,
+    /// Linear gain over the whole clip
+    gain: f32,
+    /// Frames faded in from the clip's start
+    fade_in: usize,
+    /// Frames faded out to the clip's end
+    fade_out: usize,
+    /// Shapes of the fade in and the fade out
+    fade_curves: (FadeCurve, FadeCurve),
+    /// `(source frame, linear gain)` breakpoints, sorted by frame
+    envelope: Vec<(usize, f32)>,
+    /// The clip's audio with gain, fades and envelope applied, see [`Clip::set_cached`]
+    cache: Option<Arc<AudioBuffer>>,
+    /// Color, notes and tags, kept by splits and processing
+    metadata: Metadata,
+    /// Clip group the clip is edited with, see [`clip_group`](crate::track::clip_group)
+    group: Option<String>,
+    /// BPM of the original audio, `Some` if the clip follows the project tempo
+    follow_tempo: Option<f64>,
+    /// The audio is a stretch of the clip in `unprocessed`, see [`Clip::at_tempo`]
+    stretched: bool,
+}
+
+impl Clip {
+    /// A clip playing all of `source` from `start`
+    #[must_use]
+    pub fn new(start: usize, source: Arc<AudioBuffer>) -> Self {
+        Self {
+            start,
+            offset: 0,
+            length: source.frames(),
+            source,
+            selection: ChannelSelection::All,
+            unprocessed: None,
+            gain: 1.0,
+            fade_in: 0,
+            fade_out: 0,
+            fade_curves: (FadeCurve::Linear, FadeCurve::Linear),
+            envelope: Vec::new(),
+            cache: None,
+            metadata: Metadata::default(),
+            group: None,
+            follow_tempo: None,
+            stretched: false,
+        }
+    }
+
+    /// A clip playing `length` frames of `source` from its frame `offset`, both limited to
+    /// the audio there is
+    #[must_use]
+    pub fn excerpt(start: usize, source: Arc<AudioBuffer>, offset: usize, length: usize) -> Self {
+        let offset = offset.min(source.frames());
+        let length = length.min(source.frames() - offset);
+        Self {
+            offset,
+            length,
+            ..Self::new(start, source)
+        }
+    }
+
+    /// A copy of the clip playing `render` instead, a processed version of its audio.
+    ///
+    /// Non-destructive: the new clip remembers this one, [`Clip::unprocessed`] gets it back.
+    /// Both share their position, the render covers the clip from its first frame. Gain,
+    /// fades and envelope carry over and are applied on top of the render.
+    #[must_use]
+    pub fn with_render(&self, render: AudioBuffer) -> Self {
+        // the envelope moves with the audio, from source frames to render frames
+        let envelope = if self.envelope.is_empty() {
+            Vec::new()
+        } else {
+            std::iter::once((0, self.envelope_at(self.offset)))
+                .chain(
+                    self.envelope
+                        .iter()
+                        .filter(|&&(frame, _)| frame > self.offset)
+                        .map(|&(frame, gain)| (frame - self.offset, gain)),
+                )
+                .collect()
+        };
+        let mut clip = Self {
+            start: self.start,
+            offset: 0,
+            length: render.frames(),
+            source: Arc::new(render),
+            // the render already holds just the selected channels
+            selection: ChannelSelection::All,
+            unprocessed: Some(Box::new(self.clone())),
+            gain: self.gain,
+            fade_in: self.fade_in,
+            fade_out: self.fade_out,
+            fade_curves: self.fade_curves,
+            envelope,
+            cache: None,
+            metadata: self.metadata.clone(),
+            group: self.group.clone(),
+            follow_tempo: self.follow_tempo,
+            stretched: false,
+        };
+        clip.set_cached(self.is_cached());
+        clip
+    }
+
+    /// The clip before its last processing, at the current position
+    #[must_use]
+    pub fn unprocessed(&self) -> Option<Self> {
+        self.unprocessed.as_deref().map(|clip| Self {
+            start: self.start,
+            metadata: self.metadata.clone(),
+            group: self.group.clone(),
+            ..clip.clone()
+        })
+    }
+
+    /// Forgets how the clip was processed, making its current audio the original
+    pub fn flatten(&mut self) {
+        self.unprocessed = None;
+    }
+
+    /// Copies the audio the clip plays out of its source, before gain, fades and envelope,
+    /// with only the selected channels
+    #[must_use]
+    pub fn audio(&self) -> AudioBuffer {
+        let mut audio = AudioBuffer::new(self.channels(), self.length);
+        self.copy_source(&mut audio, 0, 0, self.length);
+        audio
+    }
+
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Channels the clip plays, 1 for mono
+    #[must_use]
+    pub fn channels(&self) -> usize {
+        match self.selection {
+            ChannelSelection::All => self.source.channels().min(2),
+            ChannelSelection::Mono(_) => 1,
+            ChannelSelection::Stereo(..) => 2,
+        }
+    }
+
+    #[must_use]
+    pub const fn channel_selection(&self) -> ChannelSelection {
+        self.selection
+    }
+
+    /// Plays `selection` of the source's channels, `false` and unchanged if the source
+    /// doesn't have them
+    pub fn set_channel_selection(&mut self, selection: ChannelSelection) -> bool {
+        let channels = self.source.channels();
+        let valid = match selection {
+            ChannelSelection::All => true,
+            ChannelSelection::Mono(channel) => channel < channels,
+            ChannelSelection::Stereo(left, right) => left.max(right) < channels,
+        };
+        if valid {
+            self.selection = selection;
+            self.refresh_cache();
+        }
+        valid
+    }
+
+    /// Channel of the source heard on played channel `channel`, the last played one for
+    /// channels past it
+    fn source_channel(&self, channel: usize) -> usize {
+        match self.selection {
+            ChannelSelection::All => channel.min(1).min(self.source.channels().saturating_sub(1)),
+            ChannelSelection::Mono(source) => source,
+            ChannelSelection::Stereo(left, _) if channel == 0 => left,
+            ChannelSelection::Stereo(_, right) => right,
+        }
+    }
+
+    /// Copies `frames` frames of the selected source channels, from frame `from` of the
+    /// clip, to frame `at` of every channel of `buffer`
+    fn copy_source(&self, buffer: &mut AudioBuffer, at: usize, from: usize, frames: usize) {
+        let start = self.offset + from;
+        for channel in 0..buffer.channels() {
+            let source = &self.source.channel(self.source_channel(channel))[start..start + frames];
+            buffer.channel_mut(channel)[at..at + frames].copy_from_slice(source);
+        }
+    }
+
+    /// First frame after the clip
+    #[must_use]
+    pub const fn end(&self) -> usize {
+        self.start + self.length
+    }
+
+    /// Frame of the source audio heard at the clip's start
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[must_use]
+    pub fn source(&self) -> &AudioBuffer {
+        &self.source
+    }
+
+    #[must_use]
+    pub const fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+        self.refresh_cache();
+    }
+
+    /// Frames faded in at the start and out at the end, as `(fade_in, fade_out)`
+    #[must_use]
+    pub const fn fades(&self) -> (usize, usize) {
+        (self.fade_in, self.fade_out)
+    }
+
+    /// Fades the first `fade_in` frames in and the last `fade_out` frames out, linearly.
+    /// Fades stay at the clip's edges when it's trimmed.
+    pub fn set_fades(&mut self, fade_in: usize, fade_out: usize) {
+        self.fade_in = fade_in;
+        self.fade_out = fade_out;
+        self.fade_curves = (FadeCurve::Linear, FadeCurve::Linear);
+        self.refresh_cache();
+    }
+
+    /// Shapes of the fade in and the fade out, as `(fade_in, fade_out)`
+    #[must_use]
+    pub const fn fade_curves(&self) -> (FadeCurve, FadeCurve) {
+        self.fade_curves
+    }
+
+    pub fn set_fade_curves(&mut self, fade_in: FadeCurve, fade_out: FadeCurve) {
+        self.fade_curves = (fade_in, fade_out);
+        self.refresh_cache();
+    }
+
+    #[must_use]
+    pub fn envelope(&self) -> &[(usize, f32)] {
+        &self.envelope
+    }
+
+    /// Sets gain breakpoints, `(frame of the source audio, linear gain)`, so they stay with
+    /// the audio when the clip is trimmed or split. Gain is interpolated linearly between
+    /// points and held before the first and after the last.
+    pub fn set_envelope(&mut self, mut points: Vec<(usize, f32)>) {
+        points.sort_by_key(|&(frame, _)| frame);
+        self.envelope = points;
+        self.refresh_cache();
+    }
+
+    #[must_use]
+    pub const fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub const fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    /// Clip group the clip is edited with, `None` if it's edited on its own
+    #[must_use]
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Adds the clip to clip group `group`, or takes it out of its group with `None`.
+    /// Both halves of a split stay in the group.
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group;
+    }
+
+    /// BPM of the clip's original audio, `Some` if it follows the project tempo
+    #[must_use]
+    pub const fn follow_tempo(&self) -> Option<f64> {
+        self.follow_tempo
+    }
+
+    /// Has the clip follow the project tempo, its audio being at `bpm`, e.g. from
+    /// [`estimate_tempo`](crate::analysis::tempo::estimate_tempo), or stop following with
+    /// `None`. It's stretched by the next [`TimelineTrack::set_tempo`].
+    pub const fn set_follow_tempo(&mut self, bpm: Option<f64>) {
+        self.follow_tempo = bpm;
+    }
+
+    /// The clip stretched to play at `bpm` at the same pitch, from the same start, see
+    /// [`time_stretch`]. Clips not following the tempo come back as they are.
+    ///
+    /// A stretched clip is stretched again from its original audio, so tempo changes don't
+    /// add up artifacts. Its gain, fades and envelope carry over.
+    #[must_use]
+    pub fn at_tempo(&self, bpm: f64, sample_rate: f64) -> Self {
+        let Some(original_bpm) = self.follow_tempo else {
+            return self.clone();
+        };
+        let mut original = self.clone();
+        if self.stretched
+            && let Some(unstretched) = self.unprocessed()
+        {
+            // back from the frames of the stretch to those of the original audio
+            let speed = unstretched.length as f64 / self.length.max(1) as f64;
+            original = Self {
+                gain: self.gain,
+                fade_in: self.fade_in,
+                fade_out: self.fade_out,
+                fade_curves: self.fade_curves,
+                envelope: self
+                    .envelope
+                    .iter()
+                    .map(|&(frame, gain)| {
+                        (
+                            unstretched.offset + (frame as f64 * speed).round() as usize,
+                            gain,
+                        )
+                    })
+                    .collect(),
+                ..unstretched
+            };
+            original.refresh_cache();
+        }
+        if (bpm - original_bpm).abs() < TEMPO_EPSILON {
+            return original;
+        }
+
+        let speed = bpm / original_bpm;
+        let mut clip = original.with_render(time_stretch(&original.audio(), speed, sample_rate));
+        let envelope = clip
+            .envelope
+            .iter()
+            .map(|&(frame, gain)| ((frame as f64 / speed).round() as usize, gain))
+            .collect();
+        clip.set_envelope(envelope);
+        clip.stretched = true;
+        clip
+    }
+
+    #[must_use]
+    pub const fn is_cached(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    /// Renders gain, fades and envelope into a buffer up front, so playing the clip is a
+    /// straight copy instead of per-sample math. Costs a copy of the clip's audio in memory
+    /// and a re-render on every edit, so it suits clips that are no longer being worked on.
+    pub fn set_cached(&mut self, cached: bool) {
+        self.cache = cached.then(|| Arc::new(self.processed()));
+    }
+
+    /// The clip's audio as played
+    #[must_use]
+    pub fn processed(&self) -> AudioBuffer {
+        let mut audio = self.audio();
+        if !self.is_unity() {
+            for channel in 0..audio.channels() {
+                for (frame, sample) in audio.channel_mut(channel).iter_mut().enumerate() {
+                    *sample *= self.gain_at(frame);
+                }
+            }
+        }
+        audio
+    }
+
+    /// Nothing to apply on top of the source audio
+    fn is_unity(&self) -> bool {
+        self.gain == 1.0 && self.fade_in == 0 && self.fade_out == 0 && self.envelope.is_empty()
+    }
+
+    /// Gain at frame `frame` of the clip, from gain, fades and envelope
+    fn gain_at(&self, frame: usize) -> f32 {
+        let mut gain = self.gain * self.envelope_at(self.offset + frame);
+        if frame < self.fade_in {
+            gain *= fade_gain(self.fade_curves.0, frame as f32 / self.fade_in as f32);
+        }
+        let remaining = self.length - 1 - frame;
+        if remaining < self.fade_out {
+            gain *= fade_gain(self.fade_curves.1, remaining as f32 / self.fade_out as f32);
+        }
+        gain
+    }
+
+    fn envelope_at(&self, source_frame: usize) -> f32 {
+        let index = self
+            .envelope
+            .partition_point(|&(frame, _)| frame <= source_frame);
+        let before = index.checked_sub(1).map(|index| self.envelope[index]);
+        match (before, self.envelope.get(index)) {
+            (None, None) => 1.0,
+            (Some((_, gain)), None) | (None, Some(&(_, gain))) => gain,
+            (Some((from, a)), Some(&(to, b))) => {
+                a + (b - a) * (source_frame - from) as f32 / (to - from) as f32
+            }
+        }
+    }
+
+    /// Keeps the cache in step with an edit
+    fn refresh_cache(&mut self) {
+        if self.is_cached() {
+            self.set_cached(true);
+        }
+    }
+
+    /// Writes `frames` frames of the clip as played, from frame `from` of the clip, to
+    /// frame `at` of `buffer`
+    fn render_into(&self, buffer: &mut AudioBuffer, at: usize, from: usize, frames: usize) {
+        if let Some(cache) = &self.cache {
+            buffer.copy_upmixed_from(at, cache, from, frames);
+            return;
+        }
+        self.copy_source(buffer, at, from, frames);
+        if self.is_unity() {
+            return;
+        }
+        for channel in 0..buffer.channels() {
+            let samples = &mut buffer.channel_mut(channel)[at..at + frames];
+            for (index, sample) in samples.iter_mut().enumerate() {
+                *sample *= self.gain_at(from + index);
+            }
+        }
+    }
+
+    /// Frame `frame` of the clip as played
+    fn frame(&self, frame: usize) -> (f32, f32) {
+        if let Some(cache) = &self.cache {
+            return cache.frame(frame);
+        }
+        let [left, right] = [0, 1]
+            .map(|channel| self.source.channel(self.source_channel(channel))[self.offset + frame]);
+        let gain = self.gain_at(frame);
+        (left * gain, right * gain)
+    }
+
+    /// Cuts the clip at track frame `at`, keeping the head and returning the tail.
+    /// `None` unless `at` lies strictly inside the clip.
+    fn split_off(&mut self, at: usize) -> Option<Self> {
+        if at <= self.start || at >= self.end() {
+            return None;
+        }
+        let head = at - self.start;
+        if self.stretched {
+            // the halves can't go back to the original, they follow the tempo from the stretch
+            let speed = self
+                .unprocessed
+                .as_ref()
+                .map_or(1.0, |original| original.length as f64 / self.length as f64);
+            self.follow_tempo = self.follow_tempo.map(|bpm| bpm * speed);
+            self.stretched = false;
+        }
+        let mut tail = Self {
+            start: at,
+            offset: self.offset + head,
+            length: self.length - head,
+            source: Arc::clone(&self.source),
+            selection: self.selection,
+            unprocessed: None,
+            gain: self.gain,
+            fade_in: 0,
+            fade_out: self.fade_out,
+            fade_curves: self.fade_curves,
+            envelope: self.envelope.clone(),
+            cache: None,
+            metadata: self.metadata.clone(),
+            group: self.group.clone(),
+            follow_tempo: self.follow_tempo,
+            stretched: false,
+        };
+        tail.set_cached(self.is_cached());
+        // a split clip can't be reverted as a whole any more
+        self.unprocessed = None;
+        self.length = head;
+        self.fade_out = 0;
+        self.refresh_cache();
+        Some(tail)
+    }
+
+    /// Gives the clip `curve` fades of at least `frames` at the chosen edges, at most
+    /// half its length so short clips keep some audio. Longer fades stay as they are.
+    fn fade_edges(&mut self, (frames, curve): (usize, FadeCurve), start: bool, end: bool) {
+        let frames = frames.min(self.length / 2);
+        let fade_in = start && self.fade_in < frames;
+        let fade_out = end && self.fade_out < frames;
+        if fade_in {
+            self.fade_in = frames;
+            self.fade_curves.0 = curve;
+        }
+        if fade_out {
+            self.fade_out = frames;
+            self.fade_curves.1 = curve;
+        }
+        if fade_in || fade_out {
+            self.refresh_cache();
+        }
+    }
+
+    /// Drops the first `frames` frames, the clip then starts that much later
+    fn trim_head(&mut self, frames: usize) {
+        let frames = frames.min(self.length);
+        self.start += frames;
+        self.offset += frames;
+        self.length -= frames;
+        self.refresh_cache();
+    }
+}
+
+/// A track playing clips at their positions on its own timeline.
+///
+/// Clips never overlap. Move, trim and remove edits follow the track's [`EditMode`]. With a
+/// [`TimecodeGrid`] set, placed, moved and trimmed clip edges snap to video frames.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+/// use audio_engine::{buffer::AudioBuffer, track::timeline::{Clip, EditMode, TimelineTrack}};
+///
+/// let audio = Arc::new(AudioBuffer::stereo(100));
+/// let mut track = TimelineTrack::new("vox");
+/// track.add_clip(Clip::new(0, Arc::clone(&audio)));
+/// track.add_clip(Clip::new(100, audio));
+///
+/// track.set_edit_mode(EditMode::Ripple);
+/// track.remove_clip(0);
+/// assert_eq!(track.clips()[0].start, 0);
+/// ```
+pub struct TimelineTrack {
+    id: String,
+    /// Sorted by start
+    clips: Vec<Clip>,
+    edit_mode: EditMode,
+    /// Video frames clip edits snap to, `None` edits to the sample
+    grid: Option<TimecodeGrid>,
+    /// Frames and shape of the fades given to every clip edge an edit creates, 0 frames
+    /// for none
+    edit_fade: (usize, FadeCurve),
+    position: usize,
+}
+
+impl TimelineTrack {
+    #[must_use]
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            clips: Vec::new(),
+            edit_mode: EditMode::default(),
+            grid: None,
+            edit_fade: (0, FadeCurve::EqualPower),
+            position: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn edit_mode(&self) -> EditMode {
+        self.edit_mode
+    }
+
+    pub const fn set_edit_mode(&mut self, mode: EditMode) {
+        self.edit_mode = mode;
+    }
+
+    #[must_use]
+    pub const fn grid(&self) -> Option<&TimecodeGrid> {
+        self.grid.as_ref()
+    }
+
+    /// Snaps later clip edits to `grid`'s video frames, for editing to picture. Clips
+    /// already placed stay where they are.
+    pub const fn set_grid(&mut self, grid: Option<TimecodeGrid>) {
+        self.grid = grid;
+    }
+
+    /// Frames of the automatic edit fades, 0 when they're off
+    #[must_use]
+    pub const fn edit_fade(&self) -> usize {
+        self.edit_fade.0
+    }
+
+    /// Fades every clip edge later edits create over `frames`, so cuts don't click: both
+    /// sides of a split, trimmed edges and the edges of placed clips, such as recorded
+    /// takes. The fades are equal-power unless [`Self::set_edit_fade_curve`] says
+    /// otherwise, and never shorten a longer fade already there.
+    /// 0 turns them off, see [`EDIT_FADE_SECONDS`](crate::constants::EDIT_FADE_SECONDS) for
+    /// the usual length.
+    pub const fn set_edit_fade(&mut self, frames: usize) {
+        self.edit_fade.0 = frames;
+    }
+
+    #[must_use]
+    pub const fn edit_fade_curve(&self) -> FadeCurve {
+        self.edit_fade.1
+    }
+
+    /// Shapes the automatic edit fades, see [`Self::set_edit_fade`]
+    pub const fn set_edit_fade_curve(&mut self, curve: FadeCurve) {
+        self.edit_fade.1 = curve;
+    }
+
+    /// `frame` on the grid, unchanged without one
+    fn snap(&self, frame: usize) -> usize {
+        self.grid
+            .map_or(frame, |grid| grid.snap(frame as u64) as usize)
+    }
+
+    /// The project tempo changed from `from_bpm` to `to_bpm`: clips following the tempo are
+    /// stretched to it and move with the grid, so loops stay on the beat, see
+    /// [`Clip::set_follow_tempo`]. Other clips stay as they are.
+    pub fn set_tempo(&mut self, from_bpm: f64, to_bpm: f64, sample_rate: f64) {
+        let scale = from_bpm / to_bpm;
+        for clip in &mut self.clips {
+            if clip.follow_tempo.is_some() {
+                let start = (clip.start as f64 * scale).round() as usize;
+                *clip = clip.at_tempo(to_bpm, sample_rate);
+                clip.start = start;
+            }
+        }
+        self.clips.sort_by_key(|clip| clip.start);
+    }
+
+    /// All clips, earliest first
+    #[must_use]
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    /// First frame after the last clip
+    pub fn end(&self) -> usize {
+        self.clips.last().map_or(0, Clip::end)
+    }
+
+    /// Adds the clip at `index` to clip group `group`, or takes it out of its group with
+    /// `None`. `false` if there's no clip at `index`.
+    pub fn set_clip_group(&mut self, index: usize, group: Option<String>) -> bool {
+        let Some(clip) = self.clips.get_mut(index) else {
+            return false;
+        };
+        clip.set_group(group);
+        true
+    }
+
+    /// Sets the fades of the clip at `index` and their shapes, both as
+    /// `(fade_in, fade_out)`. `false` if there's no clip at `index`.
+    pub fn set_clip_fades(
+        &mut self,
+        index: usize,
+        fades: (usize, usize),
+        curves: (FadeCurve, FadeCurve),
+    ) -> bool {
+        let Some(clip) = self.clips.get_mut(index) else {
+            return false;
+        };
+        clip.fade_in = fades.0;
+        clip.fade_out = fades.1;
+        clip.fade_curves = curves;
+        clip.refresh_cache();
+        true
+    }
+
+    /// Swaps the clip at `index` for `clip` at the same position, e.g. a processed version
+    /// of it, and returns the old one. A change in length is handled like [`Self::trim_end`].
+    pub fn replace_clip(&mut self, index: usize, mut clip: Clip) -> Option<Clip> {
+        if index >= self.clips.len() {
+            return None;
+        }
+        let old = self.clips.remove(index);
+        clip.start = old.start;
+        self.make_room(old.end(), clip.end());
+        self.clips.insert(index, clip);
+        Some(old)
+    }
+
+    /// Places a clip, overwriting or pushing aside the clips at its position depending on
+    /// the edit mode. Returns the clip's index.
+    pub fn add_clip(&mut self, mut clip: Clip) -> usize {
+        clip.start = self.snap(clip.start);
+        clip.fade_edges(self.edit_fade, true, true);
+        match self.edit_mode {
+            EditMode::Overwrite => self.clear_range(clip.start, clip.end()),
+            EditMode::Ripple | EditMode::Insert => {
+                self.split_at(clip.start);
+                self.shift_from(clip.start, clip.length.cast_signed());
+            }
+        }
+        let index = self.clips.partition_point(|other| other.start < clip.start);
+        self.clips.insert(index, clip);
+        index
+    }
+
+    /// Removes the clip at `index`. In ripple mode the clips after it move up to close the gap.
+    pub fn remove_clip(&mut self, index: usize) -> Option<Clip> {
+        if index >= self.clips.len() {
+            return None;
+        }
+        let clip = self.clips.remove(index);
+        if self.edit_mode == EditMode::Ripple {
+            self.shift_from(clip.end(), -clip.length.cast_signed());
+        }
+        Some(clip)
+    }
+
+    /// Moves the clip at `index` to start at `start`, as a remove followed by an add in the
+    /// current edit mode. Returns the clip's new index.
+    pub fn move_clip(&mut self, index: usize, start: usize) -> Option<usize> {
+        let mut clip = self.remove_clip(index)?;
+        clip.start = start;
+        Some(self.add_clip(clip))
+    }
+
+    /// Changes where the clip at `index` ends, at most to the end of its audio.
+    /// Ripple and insert modes move the later clips by the change in length, overwrite mode
+    /// cuts into them when the clip grows. `false` if there's no clip at `index`.
+    pub fn trim_end(&mut self, index: usize, length: usize) -> bool {
+        let Some(clip) = self.clips.get(index) else {
+            return false;
+        };
+        let old_end = clip.end();
+        let length =
+            (self.snap(clip.start + length) - clip.start).min(clip.source.frames() - clip.offset);
+        let new_end = clip.start + length;
+
+        let mut clip = self.clips.remove(index);
+        clip.length = length;
+        clip.refresh_cache();
+        clip.fade_edges(self.edit_fade, false, true);
+        self.make_room(old_end, new_end);
+        self.clips.insert(index, clip);
+        true
+    }
+
+    /// Adjusts the clips after a clip (already taken out) whose end moves from `old_end` to
+    /// `new_end`: ripple and insert modes move them, overwrite mode cuts into them
+    fn make_room(&mut self, old_end: usize, new_end: usize) {
+        match self.edit_mode {
+            EditMode::Overwrite if new_end > old_end => self.clear_range(old_end, new_end),
+            EditMode::Overwrite => {}
+            EditMode::Ripple | EditMode::Insert => {
+                self.shift_from(old_end, new_end.cast_signed() - old_end.cast_signed());
+            }
+        }
+    }
+
+    /// Moves where the clip at `index` starts without moving its audio, at most back to the
+    /// start of the audio and forward to the clip's end. In ripple mode the clip keeps its
+    /// position instead and everything after it moves by the change in length.
+    /// `false` if there's no clip at `index`.
+    pub fn trim_start(&mut self, index: usize, start: usize) -> bool {
+        let Some(clip) = self.clips.get(index) else {
+            return false;
+        };
+        let start = self.snap(start).clamp(clip.start - clip.offset, clip.end());
+        let old_start = clip.start;
+        let old_end = clip.end();
+
+        let mut clip = self.clips.remove(index);
+        if start < old_start {
+            clip.start = start;
+            clip.offset -= old_start - start;
+            clip.length += old_start - start;
+            clip.refresh_cache();
+        } else {
+            clip.trim_head(start - old_start);
+        }
+        clip.fade_edges(self.edit_fade, true, false);
+
+        if self.edit_mode == EditMode::Ripple {
+            let change = old_start.cast_signed() - start.cast_signed();
+            self.shift_from(old_end, change);
+            clip.start = old_start;
+        } else if start < old_start {
+            self.clear_range(start, old_start);
+        }
+        let index = self.clips.partition_point(|other| other.start < clip.start);
+        self.clips.insert(index, clip);
+        true
+    }
+
+    /// Cuts the clip spanning `at` in two, nothing if `at` falls between clips
+    fn split_at(&mut self, at: usize) {
+        let Some(index) = self
+            .clips
+            .iter()
+            .position(|clip| clip.start < at && at < clip.end())
+        else {
+            return;
+        };
+        if let Some(mut tail) = self.clips[index].split_off(at) {
+            self.clips[index].fade_edges(self.edit_fade, false, true);
+            tail.fade_edges(self.edit_fade, true, false);
+            self.clips.insert(index + 1, tail);
+        }
+    }
+
+    /// Moves every clip starting at or after `at` by `frames`, never before frame 0
+    fn shift_from(&mut self, at: usize, frames: isize) {
+        for clip in self.clips.iter_mut().filter(|clip| clip.start >= at) {
+            clip.start = clip.start.saturating_add_signed(frames);
+        }
+    }
+
+    /// Removes all audio in `start..end`, shortening or splitting the clips it cuts into
+    fn clear_range(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        self.split_at(start);
+        self.split_at(end);
+        self.clips
+            .retain(|clip| clip.end() <= start || clip.start >= end);
+    }
+}
+
+impl Track for TimelineTrack {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        buffer.clear();
+        let window_end = self.position + buffer.frames();
+        for clip in &self.clips {
+            if clip.start >= window_end {
+                break;
+            }
+            let from = clip.start.max(self.position);
+            let to = clip.end().min(window_end);
+            if from < to {
+                clip.render_into(buffer, from - self.position, from - clip.start, to - from);
+            }
+        }
+        self.position = window_end;
+    }
+
+    fn fill_from(&mut self, position: usize, buffer: &mut AudioBuffer) {
+        self.position = position;
+        self.fill_next_samples(buffer);
+    }
+
+    /// Mono while every clip is
+    fn channels(&self) -> usize {
+        if !self.clips.is_empty() && self.clips.iter().all(|clip| clip.channels() == 1) {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position >= self.end()
+    }
+
+    fn frame_at(&self, frame: usize) -> Option<(f32, f32)> {
+        if frame >= self.end() {
+            return None;
+        }
+        let index = self.clips.partition_point(|clip| clip.start <= frame);
+        let sample = index
+            .checked_sub(1)
+            .map(|index| &self.clips[index])
+            .filter(|clip| frame < clip.end())
+            .map_or((0.0, 0.0), |clip| clip.frame(frame - clip.start));
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use transport::display::FrameRate;
+
+    use super::*;
+
+    /// A clip of `length` frames whose samples count up from `first`
+    fn clip(start: usize, first: usize, length: usize) -> Clip {
+        let frames: Vec<_> = (first..first + length)
+            .map(|i| (i as f32, i as f32))
+            .collect();
+        Clip::new(start, Arc::new(AudioBuffer::from_frames(&frames)))
+    }
+
+    fn layout(track: &TimelineTrack) -> Vec<(usize, usize)> {
+        track
+            .clips()
+            .iter()
+            .map(|clip| (clip.start, clip.end()))
+            .collect()
+    }
+
+    fn track(mode: EditMode) -> TimelineTrack {
+        let mut track = TimelineTrack::new("timeline");
+        track.add_clip(clip(0, 0, 10));
+        track.add_clip(clip(10, 100, 10));
+        track.add_clip(clip(30, 200, 10));
+        track.set_edit_mode(mode);
+        track
+    }
+
+    #[test]
+    fn test_remove_clip_closes_gap_only_in_ripple_mode() {
+        let mut overwrite = track(EditMode::Overwrite);
+        overwrite.remove_clip(1);
+        assert_eq!(layout(&overwrite), vec![(0, 10), (30, 40)]);
+
+        let mut ripple = track(EditMode::Ripple);
+        ripple.remove_clip(1);
+        assert_eq!(layout(&ripple), vec![(0, 10), (20, 30)]);
+    }
+
+    #[test]
+    fn test_add_clip_overwrites_or_pushes() {
+        let mut overwrite = track(EditMode::Overwrite);
+        overwrite.add_clip(clip(5, 300, 10));
+        assert_eq!(
+            layout(&overwrite),
+            vec![(0, 5), (5, 15), (15, 20), (30, 40)]
+        );
+        assert_eq!(overwrite.clips()[2].offset(), 5);
+
+        let mut insert = track(EditMode::Insert);
+        insert.add_clip(clip(5, 300, 10));
+        assert_eq!(
+            layout(&insert),
+            vec![(0, 5), (5, 15), (15, 20), (20, 30), (40, 50)]
+        );
+    }
+
+    #[test]
+    fn test_move_clip_follows_edit_mode() {
+        let mut ripple = track(EditMode::Ripple);
+        let index = ripple.move_clip(0, 20).unwrap();
+        // the gap at the start closes, the clip then pushes the last one
+        assert_eq!(layout(&ripple), vec![(0, 10), (20, 30), (30, 40)]);
+        assert_eq!(index, 1);
+
+        let mut insert = track(EditMode::Insert);
+        insert.move_clip(0, 20);
+        assert_eq!(layout(&insert), vec![(10, 20), (20, 30), (40, 50)]);
+        assert!(insert.move_clip(7, 0).is_none());
+    }
+
+    #[test]
+    fn test_trim_end() {
+        let mut ripple = track(EditMode::Ripple);
+        assert!(ripple.trim_end(1, 4));
+        assert_eq!(layout(&ripple), vec![(0, 10), (10, 14), (24, 34)]);
+        // can't grow past the end of the audio
+        ripple.trim_end(1, 50);
+        assert_eq!(layout(&ripple), vec![(0, 10), (10, 20), (30, 40)]);
+
+        let mut overwrite = track(EditMode::Overwrite);
+        overwrite.trim_end(1, 4);
+        overwrite.trim_start(2, 25);
+        assert_eq!(layout(&overwrite), vec![(0, 10), (10, 14), (30, 40)]);
+    }
+
+    #[test]
+    fn test_trim_start_in_ripple_mode_keeps_position() {
+        let mut ripple = track(EditMode::Ripple);
+        assert!(ripple.trim_start(1, 14));
+        assert_eq!(layout(&ripple), vec![(0, 10), (10, 16), (26, 36)]);
+        assert_eq!(ripple.clips()[1].offset(), 4);
+    }
+
+    #[test]
+    fn test_edit_fades_cover_cut_edges() {
+        let mut track = TimelineTrack::new("timeline");
+        track.add_clip(clip(0, 0, 20));
+        track.set_edit_fade(4);
+        // cuts the first clip at 10 and fades both the cut and the new clip
+        track.add_clip(clip(10, 100, 20));
+
+        let (head, placed) = (&track.clips()[0], &track.clips()[1]);
+        assert_eq!(head.fades(), (0, 4));
+        assert_eq!(placed.fades(), (4, 4));
+        assert_eq!(
+            placed.fade_curves(),
+            (FadeCurve::EqualPower, FadeCurve::EqualPower)
+        );
+        assert_eq!(track.frame_at(7).unwrap().0, 7.0 * 0.5f32.sqrt());
+        assert_eq!(track.frame_at(9).unwrap().0, 0.0);
+
+        // fades already long enough stay, short clips keep half their audio
+        track.trim_end(0, 6);
+        assert_eq!(track.clips()[0].fades(), (0, 4));
+        track.add_clip(clip(40, 0, 4));
+        assert_eq!(track.clips()[2].fades(), (2, 2));
+    }
+
+    #[test]
+    fn test_edits_snap_to_video_frames_on_a_grid() {
+        let mut track = TimelineTrack::new("dialogue");
+        // 25 fps at 250 Hz, a frame every 10 samples
+        track.set_grid(Some(TimecodeGrid::new(FrameRate::Fps25, 250.0)));
+        track.add_clip(clip(13, 0, 40));
+        assert_eq!(layout(&track), vec![(10, 50)]);
+
+        track.move_clip(0, 27);
+        track.trim_start(0, 44);
+        track.trim_end(0, 14);
+        assert_eq!(layout(&track), vec![(40, 50)]);
+        assert_eq!(track.clips()[0].offset(), 10);
+    }
+
+    #[test]
+    fn test_replace_clip_keeps_position_and_makes_room() {
+        let mut ripple = track(EditMode::Ripple);
+        let longer = clip(50, 500, 15);
+        let old = ripple.replace_clip(1, longer).unwrap();
+
+        assert_eq!(old.start, 10);
+        assert_eq!(layout(&ripple), vec![(0, 10), (10, 25), (35, 45)]);
+        assert_eq!(ripple.clips()[1].source().frame(0), (500.0, 500.0));
+        assert!(ripple.replace_clip(3, clip(0, 0, 1)).is_none());
+    }
+
+    #[test]
+    fn test_plays_clips_at_their_positions() {
+        let mut track = track(EditMode::Overwrite);
+        track.trim_start(1, 12);
+
+        let first = track.next_samples(16);
+        assert_eq!(first.frame(9), (9.0, 9.0));
+        assert_eq!(first.frame(11), (0.0, 0.0));
+        assert_eq!(first.frame(12), (102.0, 102.0));
+
+        let second = track.next_samples(16);
+        assert_eq!(second.frame(3), (109.0, 109.0));
+        assert_eq!(second.frame(4), (0.0, 0.0));
+        assert_eq!(second.frame(14), (200.0, 200.0));
+        assert!(!track.is_finished());
+        track.next_samples(16);
+        assert!(track.is_finished());
+    }
+
+    #[test]
+    fn test_cached_clip_plays_like_uncached() {
+        let mut uncached = clip(0, 100, 20);
+        uncached.set_gain(0.5);
+        uncached.set_fades(4, 4);
+        uncached.set_envelope(vec![(5, 1.0), (15, 0.0)]);
+        let mut cached = uncached.clone();
+        cached.set_cached(true);
+
+        let render = |clip: &Clip| {
+            let mut track = TimelineTrack::new("timeline");
+            track.add_clip(clip.clone());
+            track.trim_start(0, 3);
+            track.trim_end(0, 10);
+            track.next_samples(16)
+        };
+        let expected = render(&uncached);
+        assert_eq!(render(&cached).channel(0), expected.channel(0));
+        // trimmed clip: the fade in starts from silence again, the envelope stays put
+        assert_eq!(expected.frame(3), (0.0, 0.0));
+        // source frame 7: half gain, envelope at 0.8
+        assert!((expected.frame(7).0 - 42.8).abs() < 1e-4);
+        assert_eq!(expected.frame(12), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_clips_select_channels_of_multichannel_sources() {
+        let mut source = AudioBuffer::new(4, 2);
+        for channel in 0..4 {
+            source.channel_mut(channel).fill(channel as f32);
+        }
+        let mut clip = Clip::new(0, Arc::new(source));
+        assert_eq!(clip.channels(), 2);
+        assert!(!clip.set_channel_selection(ChannelSelection::Mono(4)));
+
+        assert!(clip.set_channel_selection(ChannelSelection::Mono(2)));
+        assert_eq!(clip.channels(), 1);
+        assert_eq!(clip.audio().channel(0), [2.0, 2.0]);
+        let mut track = TimelineTrack::new("field");
+        track.add_clip(clip.clone());
+        assert_eq!(track.channels(), 1);
+        assert_eq!(track.next_samples(1).frame(0), (2.0, 2.0));
+
+        assert!(clip.set_channel_selection(ChannelSelection::Stereo(3, 0)));
+        let (head, tail) = (clip.clone(), clip.split_off(1).unwrap());
+        assert_eq!(tail.channel_selection(), head.channel_selection());
+        assert_eq!(tail.frame(0), (3.0, 0.0));
+    }
+
+    #[test]
+    fn test_clips_following_the_tempo_are_stretched_to_it() {
+        let mut track = TimelineTrack::new("loops");
+        // a beat at 120 BPM is 400 frames at 800 Hz
+        let mut drums = Clip::new(800, Arc::new(AudioBuffer::from_frames(&[(0.5, 0.5); 800])));
+        drums.set_follow_tempo(Some(120.0));
+        drums.set_gain(0.5);
+        track.add_clip(drums);
+        track.add_clip(clip(4000, 0, 10));
+
+        track.set_tempo(120.0, 100.0, 800.0);
+        assert_eq!(layout(&track), [(960, 1920), (4000, 4010)]);
+        let stretched = &track.clips()[0];
+        assert!((stretched.frame(480).0 - 0.25).abs() < 1e-4);
+
+        // back at the original tempo the original audio plays again
+        track.set_tempo(100.0, 120.0, 800.0);
+        assert_eq!(layout(&track), [(800, 1600), (4000, 4010)]);
+        assert_eq!(
+            track.clips()[0].unprocessed().map(|clip| clip.length()),
+            None
+        );
+        assert_eq!(track.clips()[0].gain(), 0.5);
+    }
+}
@@ -0,0 +1,113 @@
+use transport::{display::Timecode, timecode::TimecodeGrid};
+
+/// An audio-less reference track for picture: which video frame plays at each timeline
+/// frame, so an embedding app can show video locked to the transport.
+///
+/// Set on the scheduler with
+/// [`SchedulerCommand::SetVideoReference`](crate::scheduler::command::SchedulerCommand::SetVideoReference),
+/// which then emits a
+/// [`SchedulerEvent::VideoFrame`](crate::scheduler::event::SchedulerEvent::VideoFrame)
+/// whenever the picture should change: at every frame boundary while playing and after
+/// locates, loop wraps and scrubs.
+///
+/// # Example
+/// ```
+/// use audio_engine::track::video::VideoTrack;
+/// use transport::{display::FrameRate, timecode::TimecodeGrid};
+///
+/// // 25 fps picture starting one second into the timeline
+/// let video = VideoTrack::new(TimecodeGrid::new(FrameRate::Fps25, 48000.0)).with_start(48000);
+/// assert_eq!(video.frame_at(47999), None);
+/// assert_eq!(video.frame_at(48000 + 1920), Some(1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoTrack {
+    grid: TimecodeGrid,
+    /// Timeline frame the first video frame plays at
+    start: u64,
+}
+
+impl VideoTrack {
+    /// Video counted on `grid`, starting at the first timeline frame
+    #[must_use]
+    pub const fn new(grid: TimecodeGrid) -> Self {
+        Self { grid, start: 0 }
+    }
+
+    /// Starts the video at timeline frame `start`
+    #[must_use]
+    pub const fn with_start(mut self, start: u64) -> Self {
+        self.start = start;
+        self
+    }
+
+    #[must_use]
+    pub const fn grid(&self) -> &TimecodeGrid {
+        &self.grid
+    }
+
+    /// Timeline frame the first video frame plays at
+    #[must_use]
+    pub const fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Video frame showing at timeline frame `frame`, `None` before the video starts
+    #[must_use]
+    pub fn frame_at(&self, frame: u64) -> Option<u64> {
+        let frame = frame.checked_sub(self.start)?;
+        Some(self.grid.rate().video_frame(frame, self.grid.sample_rate()))
+    }
+
+    /// Timecode of video frame `video_frame`, counted from the grid's start timecode
+    #[must_use]
+    pub fn timecode(&self, video_frame: u64) -> Timecode {
+        Timecode::from_video_frame(
+            self.grid.start().video_frame() + video_frame,
+            self.grid.rate(),
+        )
+    }
+
+    /// `(timeline frame, video frame)` of every frame boundary in `from..to`
+    pub fn boundaries(&self, from: u64, to: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let rate = self.grid.rate();
+        let sample_rate = self.grid.sample_rate();
+        let first = match self.frame_at(from) {
+            Some(video_frame) if rate.sample_of(video_frame, sample_rate) + self.start < from => {
+                video_frame + 1
+            }
+            Some(video_frame) => video_frame,
+            None => 0,
+        };
+        (first..)
+            .map(move |video_frame| {
+                let at = self.start + rate.sample_of(video_frame, sample_rate);
+                (at, video_frame)
+            })
+            .take_while(move |&(at, _)| at < to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use transport::display::FrameRate;
+
+    use super::*;
+
+    #[test]
+    fn test_boundaries_in_a_block() {
+        // 25 fps at 1000 Hz, a frame every 40 samples from frame 100
+        let video = VideoTrack::new(TimecodeGrid::new(FrameRate::Fps25, 1000.0)).with_start(100);
+
+        assert_eq!(video.boundaries(0, 100).count(), 0);
+        assert_eq!(
+            video.boundaries(90, 190).collect::<Vec<_>>(),
+            vec![(100, 0), (140, 1), (180, 2)]
+        );
+        assert_eq!(
+            video.boundaries(141, 221).collect::<Vec<_>>(),
+            vec![(180, 2), (220, 3)]
+        );
+        assert_eq!(video.timecode(26).to_string(), "00:00:01:01");
+    }
+}
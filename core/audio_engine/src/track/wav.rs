@@ -1,14 +1,20 @@
 use std::{io::Read, path::Path};
 
-use hound::WavReader;
+use crate::{
+    analysis::{self, ClipAnalysis},
+    buffer::AudioBuffer,
+    decode::{DecodeLimits, decode},
+    error::DecodeError,
+    track::Track,
+};
 
-use crate::track::Track;
-
-/// `WavTrack` represents an in-memory, stereo-normalized PCM buffer loaded from a `.wav` file.
+/// `WavTrack` represents an in-memory PCM buffer loaded from a `.wav` file.
 ///
 /// Supports:
-/// - Mono and Stereo files (mono is duplicated into both channels)
-/// - 16-bit integer or 32-bit float samples (converted to `f32`)
+/// - Mono and Stereo files (mono stays mono, see [`Track::channels`], and is duplicated
+///   into both channels of stereo buffers)
+/// - 8, 16, 24 or 32-bit integer or 32 or 64-bit float samples (converted to `f32`),
+///   see [`decode_wav`](crate::decode::decode_wav)
 ///
 /// Does NOT support:
 /// - More than 2 channels
@@ -21,72 +27,98 @@ use crate::track::Track;
 /// let track = WavTrack::from_file("assets/wav/piano.wav").unwrap();
 /// ```
 pub struct WavTrack {
-    /// Interleaved stereo frames
-    pub(crate) samples: Vec<(f32, f32)>,
+    /// Decoded audio, mono or stereo
+    pub(crate) samples: AudioBuffer,
     /// Current read position (frame index)
     pub(crate) position: usize,
+    /// Tempo, key and pitch, once analysed
+    analysis: Option<ClipAnalysis>,
 }
 
 impl WavTrack {
-    fn from_reader<R: Read + Send + 'static>(reader: WavReader<R>) -> Result<Self, String> {
-        let spec = reader.spec();
-        let channels = spec.channels;
-        if channels == 0 || channels > 2 {
-            return Err("Only mono or stereo WAVs are supported".into());
-        }
+    /// A track playing the mono or stereo WAV file in `bytes`
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let limits = DecodeLimits {
+            max_channels: 2,
+            ..DecodeLimits::default()
+        };
+        Ok(Self::from_buffer(decode(bytes, &limits)?.samples))
+    }
 
-        let pcm_samples = Self::decode_pcm_samples(reader)?;
-        Ok(Self {
-            samples: pcm_samples,
-            position: 0,
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DecodeError> {
+        Self::from_bytes(&Self::read(path.as_ref())?)
+    }
+
+    /// Decodes every channel of the file at `path`, however many, e.g. for clips playing
+    /// some of the channels of a field recording, see
+    /// [`Clip::set_channel_selection`](crate::track::timeline::Clip::set_channel_selection)
+    pub fn decode_file<P: AsRef<Path>>(path: P) -> Result<AudioBuffer, DecodeError> {
+        let bytes = Self::read(path.as_ref())?;
+        Ok(decode(&bytes, &DecodeLimits::default())?.samples)
+    }
+
+    fn read(path: &Path) -> Result<Vec<u8>, DecodeError> {
+        #[cfg(feature = "rt-audit")]
+        crate::rt_audit::report(crate::rt_audit::Violation::FileIo);
+        std::fs::read(path).map_err(|source| DecodeError::File {
+            path: path.to_path_buf(),
+            source,
         })
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let reader =
-            WavReader::open(path).map_err(|e| format!("Failed to open WAV file: {}", e))?;
-        Self::from_reader(reader)
-    }
-
-    pub fn from_stream<R: Read + Send + 'static>(stream: R) -> Result<Self, String> {
-        let reader =
-            WavReader::new(stream).map_err(|e| format!("Failed to parse WAV stream: {}", e))?;
-        Self::from_reader(reader)
-    }
-
-    fn decode_pcm_samples<R: Read + Send + 'static>(
-        reader: WavReader<R>,
-    ) -> Result<Vec<(f32, f32)>, String> {
-        let spec = reader.spec();
-        let raw_samples = match spec.sample_format {
-            hound::SampleFormat::Int => reader
-                .into_samples::<i16>()
-                .filter_map(Result::ok)
-                .map(|s| s as f32 / i16::MAX as f32)
-                .collect::<Vec<f32>>(),
-            hound::SampleFormat::Float => reader
-                .into_samples::<f32>()
-                .filter_map(Result::ok)
-                .collect::<Vec<f32>>(),
-        };
+    pub fn from_stream<R: Read>(mut stream: R) -> Result<Self, DecodeError> {
+        let mut bytes = Vec::new();
+        stream
+            .read_to_end(&mut bytes)
+            .map_err(DecodeError::Stream)?;
+        Self::from_bytes(&bytes)
+    }
 
-        Ok(Self::interleave_channels(
-            raw_samples,
-            spec.channels as usize,
-        ))
-    }
-
-    /// Converts raw f32 samples into stereo `(L, R)` frames.
-    /// Mono is duplicated into both channels.
-    fn interleave_channels(samples: Vec<f32>, channels: usize) -> Vec<(f32, f32)> {
-        match channels {
-            1 => samples.into_iter().map(|s| (s, s)).collect(),
-            2 => samples
-                .chunks_exact(2)
-                .map(|chunk| (chunk[0], chunk[1]))
-                .collect(),
-            _ => unreachable!("Unsupported channel count"),
+    /// A track playing already decoded mono or stereo audio
+    #[must_use]
+    pub const fn from_buffer(samples: AudioBuffer) -> Self {
+        Self {
+            samples,
+            position: 0,
+            analysis: None,
+        }
+    }
+
+    /// Cuts the audio at `points` (frames from the start, ascending), returning one track
+    /// per piece. Points outside the audio or not after the previous one are ignored.
+    #[must_use]
+    pub fn split_at(&self, points: &[usize]) -> Vec<Self> {
+        let mut pieces = Vec::with_capacity(points.len() + 1);
+        let mut start = 0;
+        for &end in points.iter().chain(std::iter::once(&self.samples.frames())) {
+            if end <= start || end > self.samples.frames() {
+                continue;
+            }
+            let mut piece = AudioBuffer::new(self.samples.channels(), end - start);
+            piece.copy_from(0, &self.samples, start, end - start);
+            pieces.push(Self::from_buffer(piece));
+            start = end;
         }
+        pieces
+    }
+
+    /// The decoded audio, e.g. for analysis
+    #[must_use]
+    pub const fn samples(&self) -> &AudioBuffer {
+        &self.samples
+    }
+
+    /// Detects tempo, key and pitch and keeps them as the clip's metadata.
+    /// Slow for long files, run it off the audio thread before scheduling the track.
+    pub fn analyze(&mut self, sample_rate: f64) -> &ClipAnalysis {
+        self.analysis
+            .insert(analysis::analyze(&self.samples, sample_rate))
+    }
+
+    /// Metadata from the last [`WavTrack::analyze`], `None` until then
+    #[must_use]
+    pub const fn analysis(&self) -> Option<&ClipAnalysis> {
+        self.analysis.as_ref()
     }
 }
 
@@ -95,16 +127,37 @@ impl Track for WavTrack {
         "wav-track".to_owned()
     }
 
-    fn fill_next_samples(&mut self, next_samples: &mut [(f32, f32)]) {
-        let end = (self.position + next_samples.len()).min(self.samples.len());
-        let _ = &next_samples[..(end - self.position)]
-            .copy_from_slice(&self.samples[self.position..end]);
+    fn fill_next_samples(&mut self, buffer: &mut AudioBuffer) {
+        let end = (self.position + buffer.frames()).min(self.samples.frames());
+        let copied = end - self.position;
+        buffer.copy_upmixed_from(0, &self.samples, self.position, copied);
+        for channel in 0..buffer.channels() {
+            // past the end of the file: pad with silence so reused buffers don't leak old data
+            buffer.channel_mut(channel)[copied..].fill(0.0);
+        }
         self.position = end;
     }
 
+    fn channels(&self) -> usize {
+        self.samples.channels()
+    }
+
+    fn fill_from(&mut self, position: usize, buffer: &mut AudioBuffer) {
+        self.position = position.min(self.samples.frames());
+        self.fill_next_samples(buffer);
+    }
+
     fn reset(&mut self) {
         self.position = 0;
     }
+
+    fn is_finished(&self) -> bool {
+        self.position >= self.samples.frames()
+    }
+
+    fn frame_at(&self, frame: usize) -> Option<(f32, f32)> {
+        (frame < self.samples.frames()).then(|| self.samples.frame(frame))
+    }
 }
 
 #[cfg(test)]
@@ -139,10 +192,11 @@ mod tests {
         let buffer = create_wav_buffer(spec, &samples);
         let mut track = WavTrack::from_stream(buffer).unwrap();
 
+        assert_eq!(track.channels(), 1);
         let output = track.next_samples(2);
-        assert_eq!(output.len(), 2);
-        assert!((output[0].0 - output[0].1).abs() < AUDIO_SAMPLE_EPSILON); // L = R
-        assert!((output[1].0 - output[1].1).abs() < AUDIO_SAMPLE_EPSILON);
+        assert_eq!(output.frames(), 2);
+        assert!((output.frame(0).0 - output.frame(0).1).abs() < AUDIO_SAMPLE_EPSILON); // L = R
+        assert!((output.frame(1).0 - output.frame(1).1).abs() < AUDIO_SAMPLE_EPSILON);
     }
 
     #[test]
@@ -159,10 +213,24 @@ mod tests {
         let mut track = WavTrack::from_stream(buffer).unwrap();
 
         let output = track.next_samples(3); // request more than exists
-        assert_eq!(output.len(), 3);
-        assert_ne!(output[0], (0.0, 0.0)); // actual sample
-        assert_eq!(output[1], (0.0, 0.0)); // padded silence
-        assert_eq!(output[2], (0.0, 0.0));
+        assert_eq!(output.frames(), 3);
+        assert_ne!(output.frame(0), (0.0, 0.0)); // actual sample
+        assert_eq!(output.frame(1), (0.0, 0.0)); // padded silence
+        assert_eq!(output.frame(2), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_split_at_keeps_every_frame() {
+        let frames: Vec<_> = (0..10).map(|i| (i as f32, -(i as f32))).collect();
+        let track = WavTrack::from_buffer(AudioBuffer::from_frames(&frames));
+
+        let pieces = track.split_at(&[0, 3, 3, 7, 20]);
+        let lengths: Vec<_> = pieces
+            .iter()
+            .map(|piece| piece.samples().frames())
+            .collect();
+        assert_eq!(lengths, vec![3, 4, 3]);
+        assert_eq!(pieces[1].samples().frame(0), (3.0, -3.0));
     }
 
     #[test]
@@ -177,6 +245,23 @@ mod tests {
         let samples = [0; 6];
         let buffer = create_wav_buffer(spec, &samples);
         let result = WavTrack::from_stream(buffer);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(DecodeError::UnsupportedChannels(3))));
+    }
+
+    #[test]
+    fn test_decode_file_keeps_every_channel() {
+        let spec = WavSpec {
+            channels: 3,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let path = std::env::temp_dir().join("freqform-wav-three-channels.wav");
+        std::fs::write(&path, create_wav_buffer(spec, &[0, 0, 16384]).into_inner()).unwrap();
+
+        let audio = WavTrack::decode_file(&path).unwrap();
+        assert_eq!((audio.channels(), audio.frames()), (3, 1));
+        assert_eq!(audio.channel(2)[0], 16384.0 / f32::from(i16::MAX));
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -0,0 +1,254 @@
+//! Noticing when the device stops calling back, e.g. after the machine slept or the driver
+//! hung, see [`Watchdog`].
+
+use std::{
+    ptr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use dasp_sample::Sample as _;
+
+use crate::{
+    device_manager::{AudioSource, AudioSourceBufferKind},
+    scheduler::event::SchedulerEvent,
+};
+
+/// Most times per timeout the watchdog looks at the callback count
+const CHECKS_PER_TIMEOUT: u32 = 4;
+
+/// When the audio callback counts as stalled and what to do about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// How long the device may go without calling back
+    pub timeout: Duration,
+    /// Rebuilds the output stream after a stall, see
+    /// [`Engine::check_watchdog`](crate::engine::Engine::check_watchdog)
+    pub restart: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(500),
+            restart: false,
+        }
+    }
+}
+
+/// An [`AudioSource`] the watchdog can see being called and a restarted stream can take
+/// over.
+///
+/// Clones share the source. A callback takes it out of a shared slot and puts it back when
+/// done, without locking: if a hung stream is stuck holding it, the new one plays silence.
+#[derive(Clone)]
+pub struct WatchedSource {
+    source: Arc<SourceSlot>,
+    callbacks: Arc<AtomicU64>,
+}
+
+impl WatchedSource {
+    #[must_use]
+    pub fn new(source: Box<dyn AudioSource>) -> Self {
+        Self {
+            source: Arc::new(SourceSlot::new(source)),
+            callbacks: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Callbacks so far, across every stream playing the source
+    #[must_use]
+    pub fn callbacks(&self) -> u64 {
+        self.callbacks.load(Ordering::Relaxed)
+    }
+}
+
+impl AudioSource for WatchedSource {
+    fn fill_buffer(&mut self, buffer: AudioSourceBufferKind<'_>, frame_size: usize) {
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        let taken = self.source.0.swap(ptr::null_mut(), Ordering::Acquire);
+        // SAFETY: a non-null pointer came from `Box::into_raw` and swapping it out of the slot
+        // leaves this callback the only one holding it until it's stored back
+        if let Some(source) = unsafe { taken.as_mut() } {
+            source.fill_buffer(buffer, frame_size);
+            self.source.0.store(taken, Ordering::Release);
+            return;
+        }
+        match buffer {
+            AudioSourceBufferKind::F32(data) => data.fill(0.0),
+            AudioSourceBufferKind::I16(data) => data.fill(0),
+            AudioSourceBufferKind::U16(data) => data.fill(0.0f32.to_sample()),
+        }
+    }
+}
+
+/// Where a [`WatchedSource`] waits between callbacks, null while one is playing it
+struct SourceSlot(AtomicPtr<Box<dyn AudioSource>>);
+
+impl SourceSlot {
+    fn new(source: Box<dyn AudioSource>) -> Self {
+        Self(AtomicPtr::new(Box::into_raw(Box::new(source))))
+    }
+}
+
+impl Drop for SourceSlot {
+    fn drop(&mut self) {
+        let source = *self.0.get_mut();
+        if !source.is_null() {
+            // SAFETY: the pointer came from `Box::into_raw` and, with the last clone gone, no
+            // callback can be holding it
+            drop(unsafe { Box::from_raw(source) });
+        }
+    }
+}
+
+/// Watches a [`WatchedSource`] from its own thread, reporting
+/// [`SchedulerEvent::CallbackStalled`] once it hasn't been called for the timeout and
+/// [`SchedulerEvent::CallbackResumed`] when calls come back.
+///
+/// Stops when dropped.
+pub struct Watchdog {
+    stalled: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Starts watching `source`, sending what it notices to `events`
+    #[must_use]
+    pub fn spawn(
+        source: &WatchedSource,
+        timeout: Duration,
+        events: Sender<SchedulerEvent>,
+    ) -> Self {
+        let stalled = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let callbacks = Arc::clone(&source.callbacks);
+        let thread = {
+            let (stalled, stop) = (Arc::clone(&stalled), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let mut seen = callbacks.load(Ordering::Relaxed);
+                let mut last_call = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::park_timeout(timeout / CHECKS_PER_TIMEOUT);
+                    let count = callbacks.load(Ordering::Relaxed);
+                    let event = if count == seen {
+                        let silent_for = last_call.elapsed();
+                        (silent_for >= timeout && !stalled.swap(true, Ordering::Relaxed))
+                            .then_some(SchedulerEvent::CallbackStalled { silent_for })
+                    } else {
+                        seen = count;
+                        last_call = Instant::now();
+                        stalled
+                            .swap(false, Ordering::Relaxed)
+                            .then_some(SchedulerEvent::CallbackResumed)
+                    };
+                    if let Some(event) = event
+                        && events.send(event).is_err()
+                    {
+                        return;
+                    }
+                }
+            })
+        };
+        Self {
+            stalled,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Spawns a watchdog and the receiving end for its events, e.g. for
+    /// [`EventBus::attach`](crate::events::EventBus::attach)
+    #[must_use]
+    pub fn channel(source: &WatchedSource, timeout: Duration) -> (Self, Receiver<SchedulerEvent>) {
+        let (events, receiver) = mpsc::channel();
+        (Self::spawn(source, timeout, events), receiver)
+    }
+
+    /// The callback hasn't been called for the timeout and hasn't come back yet
+    #[must_use]
+    pub fn is_stalled(&self) -> bool {
+        self.stalled.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Silence;
+
+    impl AudioSource for Silence {
+        fn fill_buffer(&mut self, _buffer: AudioSourceBufferKind<'_>, _frame_size: usize) {}
+    }
+
+    #[test]
+    fn test_reports_stalls_and_recoveries() {
+        let mut source = WatchedSource::new(Box::new(Silence));
+        let (watchdog, events) = Watchdog::channel(&source, Duration::from_millis(20));
+
+        let stalled = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(
+            stalled,
+            SchedulerEvent::CallbackStalled { silent_for } if silent_for >= Duration::from_millis(20)
+        ));
+        assert!(watchdog.is_stalled());
+
+        source.fill_buffer(AudioSourceBufferKind::F32(&mut [0.0; 4]), 2);
+        let resumed = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(resumed, SchedulerEvent::CallbackResumed));
+        assert_eq!(source.callbacks(), 1);
+    }
+
+    /// Counts its callbacks into the buffer and reports being dropped
+    struct Counter(f32, Arc<AtomicBool>);
+
+    impl AudioSource for Counter {
+        fn fill_buffer(&mut self, buffer: AudioSourceBufferKind<'_>, _frame_size: usize) {
+            self.0 += 1.0;
+            if let AudioSourceBufferKind::F32(data) = buffer {
+                data.fill(self.0);
+            }
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            self.1.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_clones_take_turns_with_one_source() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut first = WatchedSource::new(Box::new(Counter(0.0, Arc::clone(&dropped))));
+        let mut second = first.clone();
+
+        let mut output = [0.0f32; 4];
+        first.fill_buffer(AudioSourceBufferKind::F32(&mut output), 2);
+        second.fill_buffer(AudioSourceBufferKind::F32(&mut output), 2);
+        assert_eq!(output, [2.0; 4]);
+        assert_eq!(first.callbacks(), 2);
+
+        drop(first);
+        assert!(!dropped.load(Ordering::Relaxed));
+        drop(second);
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+}
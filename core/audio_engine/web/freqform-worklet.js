@@ -0,0 +1,42 @@
+// AudioWorklet processor driving a wasm32 build of the engine.
+//
+// Usage (main thread):
+//   await ctx.audioWorklet.addModule("freqform-worklet.js");
+//   const node = new AudioWorkletNode(ctx, "freqform", {
+//     outputChannelCount: [2],
+//     processorOptions: { module: wasmModule, entry: "freqform_main" },
+//   });
+//
+// `entry` names an export of the host crate that builds the scheduler and calls
+// `WebAudioDeviceManager::start_output_stream`; it receives the context sample rate.
+
+class FreqFormProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    const { module, entry = "freqform_main" } = options.processorOptions;
+    this.instance = new WebAssembly.Instance(module, {});
+    this.exports = this.instance.exports;
+    this.exports[entry](sampleRate);
+  }
+
+  process(_inputs, outputs) {
+    const [left, right] = outputs[0];
+    const frames = left.length;
+
+    const ptr = this.exports.freqform_output_buffer(frames);
+    if (ptr === 0 || !this.exports.freqform_render(frames)) {
+      return true;
+    }
+
+    // memory may grow between calls, always take a fresh view
+    const interleaved = new Float32Array(this.exports.memory.buffer, ptr, frames * 2);
+    for (let i = 0; i < frames; i++) {
+      left[i] = interleaved[i * 2];
+      right[i] = interleaved[i * 2 + 1];
+    }
+
+    return true;
+  }
+}
+
+registerProcessor("freqform", FreqFormProcessor);
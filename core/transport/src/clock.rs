@@ -1,4 +1,4 @@
-use crate::resolution::TickResolution;
+use crate::{resolution::TickResolution, timebase};
 
 #[derive(Debug, Clone, Copy)]
 pub struct TimeSignature {
@@ -26,12 +26,6 @@ impl TempoClock {
         Self::with_signature(bpm, sample_rate, resolution, time_signature)
     }
 
-    fn compute_samples_per_tick(bpm: f64, sample_rate: f64, ticks_per_beat: u64) -> f64 {
-        let seconds_per_beat = 60.0 / bpm;
-        let seconds_per_tick = seconds_per_beat / ticks_per_beat as f64;
-        sample_rate * seconds_per_tick
-    }
-
     pub fn samples_per_tick(&self) -> f64 {
         self.samples_per_tick
     }
@@ -53,6 +47,11 @@ impl TempoClock {
         tick_emitted
     }
 
+    #[must_use]
+    pub fn ticks_per_bar(&self) -> u64 {
+        self.ticks_per_beat * self.time_signature.beats_per_bar
+    }
+
     pub fn current_tick(&self) -> u64 {
         self.tick_counter
     }
@@ -85,8 +84,7 @@ impl TempoClock {
         time_signature: TimeSignature,
     ) -> Self {
         let ticks_per_beat = resolution.ticks_per_beat();
-        let samples_per_tick =
-            TempoClock::compute_samples_per_tick(bpm, sample_rate, ticks_per_beat);
+        let samples_per_tick = timebase::samples_per_tick(bpm, sample_rate, ticks_per_beat);
         Self {
             samples_per_tick,
             sample_position: 0.0,
@@ -99,15 +97,11 @@ impl TempoClock {
     }
 
     pub fn bar_beat_tick(&self) -> (u64, u64, u64) {
-        let ticks_per_bar = self.ticks_per_beat * self.time_signature.beats_per_bar;
-
-        let bar = self.tick_counter / ticks_per_bar + 1;
-        let ticks_into_bar = self.tick_counter % ticks_per_bar;
-
-        let beat = ticks_into_bar / self.ticks_per_beat + 1;
-        let tick = ticks_into_bar % self.ticks_per_beat + 1;
-
-        (bar, beat, tick)
+        timebase::ticks_to_bbt(
+            self.tick_counter,
+            self.ticks_per_beat,
+            self.time_signature.beats_per_bar,
+        )
     }
 }
 
@@ -0,0 +1,265 @@
+use std::fmt;
+
+use crate::{clock::TempoClock, timebase};
+
+/// Video frame rates SMPTE timecode can count in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameRate {
+    /// 24 fps slowed down by 1000/1001, film transferred to NTSC video
+    Fps23_976,
+    #[default]
+    Fps24,
+    Fps25,
+    /// 29.97 fps counting every frame number, so the timecode drifts from the clock
+    Fps29_97,
+    /// 29.97 fps skipping frame numbers 0 and 1 every minute but every tenth, so the
+    /// timecode stays with the clock
+    Fps29_97Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    /// Video frames per second
+    #[must_use]
+    pub fn fps(self) -> f64 {
+        let (frames, seconds) = self.ratio();
+        frames / seconds
+    }
+
+    /// Frames per seconds as a whole ratio, so frame boundaries land on exact samples
+    const fn ratio(self) -> (f64, f64) {
+        match self {
+            Self::Fps23_976 => (24000.0, 1001.0),
+            Self::Fps24 => (24.0, 1.0),
+            Self::Fps25 => (25.0, 1.0),
+            Self::Fps29_97 | Self::Fps29_97Drop => (30000.0, 1001.0),
+            Self::Fps30 => (30.0, 1.0),
+        }
+    }
+
+    /// Frames counted per timecode second
+    #[must_use]
+    pub const fn nominal(self) -> u64 {
+        match self {
+            Self::Fps23_976 | Self::Fps24 => 24,
+            Self::Fps25 => 25,
+            Self::Fps29_97 | Self::Fps29_97Drop | Self::Fps30 => 30,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_drop_frame(self) -> bool {
+        matches!(self, Self::Fps29_97Drop)
+    }
+
+    /// Video frame playing at sample `frame`
+    #[must_use]
+    pub fn video_frame(self, frame: u64, sample_rate: f64) -> u64 {
+        let (frames, seconds) = self.ratio();
+        (frame as f64 * frames / (sample_rate * seconds)).floor() as u64
+    }
+
+    /// First sample of video frame `video_frame`
+    #[must_use]
+    pub fn sample_of(self, video_frame: u64, sample_rate: f64) -> u64 {
+        let (frames, seconds) = self.ratio();
+        (video_frame as f64 * sample_rate * seconds / frames).ceil() as u64
+    }
+}
+
+/// Frame numbers skipped per dropped minute, per ten minutes and frames in ten minutes of
+/// 29.97 drop-frame timecode
+const DROPPED: u64 = 2;
+const DROPPED_PER_TEN_MINUTES: u64 = 18;
+const FRAMES_PER_TEN_MINUTES: u64 = 17982;
+const FRAMES_PER_DROPPED_MINUTE: u64 = 1798;
+
+/// An SMPTE timecode, `hh:mm:ss:ff`, with `;` before the frames in drop-frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u64,
+    pub minutes: u64,
+    pub seconds: u64,
+    pub frames: u64,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    /// Timecode of the `video_frame`th frame since 00:00:00:00
+    #[must_use]
+    pub const fn from_video_frame(video_frame: u64, rate: FrameRate) -> Self {
+        let nominal = rate.nominal();
+        let mut number = video_frame;
+        if rate.is_drop_frame() {
+            let tens = video_frame / FRAMES_PER_TEN_MINUTES;
+            let rest = video_frame % FRAMES_PER_TEN_MINUTES;
+            number += DROPPED_PER_TEN_MINUTES * tens;
+            if rest >= DROPPED {
+                number += DROPPED * ((rest - DROPPED) / FRAMES_PER_DROPPED_MINUTE);
+            }
+        }
+        Self {
+            hours: number / (nominal * 3600),
+            minutes: number / (nominal * 60) % 60,
+            seconds: number / nominal % 60,
+            frames: number % nominal,
+            rate,
+        }
+    }
+
+    /// Timecode at sample `frame`
+    #[must_use]
+    pub fn from_samples(frame: u64, sample_rate: f64, rate: FrameRate) -> Self {
+        Self::from_video_frame(rate.video_frame(frame, sample_rate), rate)
+    }
+
+    /// Frames since 00:00:00:00, the inverse of [`Timecode::from_video_frame`]
+    #[must_use]
+    pub const fn video_frame(&self) -> u64 {
+        let nominal = self.rate.nominal();
+        let number = ((self.hours * 60 + self.minutes) * 60 + self.seconds) * nominal + self.frames;
+        if self.rate.is_drop_frame() {
+            let minutes = self.hours * 60 + self.minutes;
+            number - DROPPED * (minutes - minutes / 10)
+        } else {
+            number
+        }
+    }
+
+    /// First sample of the timecode's frame
+    #[must_use]
+    pub fn to_samples(&self, sample_rate: f64) -> u64 {
+        self.rate.sample_of(self.video_frame(), sample_rate)
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate.is_drop_frame() { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{separator}{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// How a host shows positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// Bars, beats and ticks
+    #[default]
+    Musical,
+    /// Minutes, seconds and milliseconds
+    MinSec,
+    Timecode(FrameRate),
+    Samples,
+}
+
+/// A position converted for display, see [`DisplayTime::at`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayTime {
+    /// 1-based, as [`TempoClock::bar_beat_tick`], shown `bar.beat.tick`
+    Musical {
+        bar: u64,
+        beat: u64,
+        tick: u64,
+    },
+    /// Shown `m:ss.mmm`
+    MinSec {
+        minutes: u64,
+        seconds: u64,
+        millis: u64,
+    },
+    Timecode(Timecode),
+    Samples(u64),
+}
+
+impl DisplayTime {
+    /// Sample `frame` in `format`, at the tempo, time signature and sample rate of `clock`
+    #[must_use]
+    pub fn at(frame: u64, format: TimeFormat, clock: &TempoClock) -> Self {
+        match format {
+            TimeFormat::Musical => {
+                let ticks = timebase::frame_to_tick(frame, clock.samples_per_tick());
+                let (bar, beat, tick) = timebase::ticks_to_bbt(
+                    ticks,
+                    clock.ticks_per_beat,
+                    clock.time_signature.beats_per_bar,
+                );
+                Self::Musical { bar, beat, tick }
+            }
+            TimeFormat::MinSec => {
+                let millis = (frame as f64 * 1000.0 / clock.sample_rate()).floor() as u64;
+                Self::MinSec {
+                    minutes: millis / 60_000,
+                    seconds: millis / 1000 % 60,
+                    millis: millis % 1000,
+                }
+            }
+            TimeFormat::Timecode(rate) => {
+                Self::Timecode(Timecode::from_samples(frame, clock.sample_rate(), rate))
+            }
+            TimeFormat::Samples => Self::Samples(frame),
+        }
+    }
+}
+
+impl fmt::Display for DisplayTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Musical { bar, beat, tick } => write!(f, "{bar}.{beat}.{tick:03}"),
+            Self::MinSec {
+                minutes,
+                seconds,
+                millis,
+            } => write!(f, "{minutes}:{seconds:02}.{millis:03}"),
+            Self::Timecode(timecode) => timecode.fmt(f),
+            Self::Samples(frame) => write!(f, "{frame}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod display_tests {
+    use super::*;
+    use crate::resolution::TickResolution;
+
+    #[test]
+    fn test_positions_in_every_format() {
+        let clock = TempoClock::new(120.0, 48000.0, TickResolution::Quarter);
+        // bar 2, beat 2 and a half at 120 bpm is 2.75 seconds in
+        let frame = 132_000;
+        let show = |format| DisplayTime::at(frame, format, &clock).to_string();
+
+        assert_eq!(show(TimeFormat::Musical), "2.2.241");
+        assert_eq!(show(TimeFormat::MinSec), "0:02.750");
+        assert_eq!(show(TimeFormat::Timecode(FrameRate::Fps25)), "00:00:02:18");
+        assert_eq!(show(TimeFormat::Samples), "132000");
+    }
+
+    #[test]
+    fn test_drop_frame_skips_numbers_except_every_tenth_minute() {
+        let rate = FrameRate::Fps29_97Drop;
+        let after = |video_frame| Timecode::from_video_frame(video_frame, rate).to_string();
+
+        assert_eq!(after(1799), "00:00:59;29");
+        assert_eq!(after(1800), "00:01:00;02");
+        assert_eq!(after(17982), "00:10:00;00");
+        // an hour of drop-frame timecode is an hour of clock time, to within a frame
+        let hour = Timecode::from_samples(48000 * 3600, 48000.0, rate);
+        assert_eq!(hour.to_string(), "01:00:00;00");
+
+        for video_frame in [0, 1799, 1800, 17981, 17982, 107_892] {
+            let timecode = Timecode::from_video_frame(video_frame, rate);
+            assert_eq!(timecode.video_frame(), video_frame);
+        }
+    }
+
+    #[test]
+    fn test_timecode_to_samples_is_the_frame_start() {
+        let timecode = Timecode::from_samples(48_100, 48000.0, FrameRate::Fps24);
+        assert_eq!(timecode.to_string(), "00:00:01:00");
+        assert_eq!(timecode.to_samples(48000.0), 48000);
+    }
+}
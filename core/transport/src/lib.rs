@@ -1,5 +1,13 @@
+//! Implementation detail of the `freqform` crate, which re-exports the supported parts of
+//! this one. Its modules may be reorganised between releases.
+
 pub mod clock;
+pub mod display;
+pub mod markers;
 pub mod quantizer;
 pub mod resolution;
+pub mod roll;
+pub mod timebase;
+pub mod timecode;
 pub mod timeline;
 pub mod transport;
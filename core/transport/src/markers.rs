@@ -0,0 +1,271 @@
+use crate::timeline::{shift_for_insert, shift_for_removal};
+
+/// A named point on the timeline, in ticks from the start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub name: String,
+    pub tick: u64,
+}
+
+/// A named section of the timeline, `start..end` in ticks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Region {
+    #[must_use]
+    pub fn contains(&self, tick: u64) -> bool {
+        (self.start..self.end).contains(&tick)
+    }
+
+    #[must_use]
+    pub fn length(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Markers and regions of an arrangement, each kept sorted by position.
+/// Names identify them: adding one under an existing name replaces it.
+#[derive(Debug, Clone, Default)]
+pub struct MarkerList {
+    markers: Vec<Marker>,
+    regions: Vec<Region>,
+}
+
+impl MarkerList {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a marker, or moves the one already called `name`
+    pub fn add_marker(&mut self, name: &str, tick: u64) {
+        self.remove_marker(name);
+        let index = self.markers.partition_point(|marker| marker.tick <= tick);
+        self.markers.insert(
+            index,
+            Marker {
+                name: name.to_owned(),
+                tick,
+            },
+        );
+    }
+
+    /// Moves marker `name` to `tick`, `false` if there's no such marker
+    pub fn move_marker(&mut self, name: &str, tick: u64) -> bool {
+        let exists = self.marker(name).is_some();
+        if exists {
+            self.add_marker(name, tick);
+        }
+        exists
+    }
+
+    pub fn remove_marker(&mut self, name: &str) -> Option<Marker> {
+        let index = self.markers.iter().position(|marker| marker.name == name)?;
+        Some(self.markers.remove(index))
+    }
+
+    #[must_use]
+    pub fn marker(&self, name: &str) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.name == name)
+    }
+
+    /// All markers, earliest first
+    #[must_use]
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// First marker strictly after `tick`
+    #[must_use]
+    pub fn next_marker(&self, tick: u64) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.tick > tick)
+    }
+
+    /// Last marker strictly before `tick`
+    #[must_use]
+    pub fn previous_marker(&self, tick: u64) -> Option<&Marker> {
+        self.markers.iter().rev().find(|marker| marker.tick < tick)
+    }
+
+    /// Adds a region between `start` and `end` (in either order), or replaces the one
+    /// already called `name`
+    pub fn add_region(&mut self, name: &str, start: u64, end: u64) {
+        self.remove_region(name);
+        let (start, end) = (start.min(end), start.max(end));
+        let index = self.regions.partition_point(|region| region.start <= start);
+        self.regions.insert(
+            index,
+            Region {
+                name: name.to_owned(),
+                start,
+                end,
+            },
+        );
+    }
+
+    /// Moves region `name` to start at `start`, keeping its length.
+    /// `false` if there's no such region.
+    pub fn move_region(&mut self, name: &str, start: u64) -> bool {
+        let Some(length) = self.region(name).map(Region::length) else {
+            return false;
+        };
+        self.add_region(name, start, start + length);
+        true
+    }
+
+    pub fn remove_region(&mut self, name: &str) -> Option<Region> {
+        let index = self.regions.iter().position(|region| region.name == name)?;
+        Some(self.regions.remove(index))
+    }
+
+    #[must_use]
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /// All regions, earliest start first
+    #[must_use]
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Makes room for `length` ticks at `at`, moving everything from there on later.
+    /// Regions spanning `at` grow.
+    pub fn insert_time(&mut self, at: u64, length: u64) {
+        for marker in &mut self.markers {
+            marker.tick = shift_for_insert(marker.tick, at, length);
+        }
+        for region in &mut self.regions {
+            region.start = shift_for_insert(region.start, at, length);
+            region.end = shift_for_insert(region.end, at, length);
+        }
+    }
+
+    /// Cuts `start..end` out of the timeline, pulling everything after it earlier.
+    /// Markers and regions entirely inside the range are removed, overlapping regions shrink.
+    pub fn remove_time(&mut self, start: u64, end: u64) {
+        self.markers
+            .retain(|marker| !(start..end).contains(&marker.tick) || marker.tick == start);
+        for marker in &mut self.markers {
+            marker.tick = shift_for_removal(marker.tick, start, end);
+        }
+        for region in &mut self.regions {
+            region.start = shift_for_removal(region.start, start, end);
+            region.end = shift_for_removal(region.end, start, end);
+        }
+        self.regions.retain(|region| region.length() > 0);
+    }
+
+    /// Ticks of the region `name`, or between the markers `"{name} in"` and `"{name} out"`,
+    /// e.g. to auto-punch. `None` if there's neither.
+    #[must_use]
+    pub fn punch_range(&self, name: &str) -> Option<(u64, u64)> {
+        self.region(name)
+            .map(|region| (region.start, region.end))
+            .or_else(|| {
+                let start = self.marker(&format!("{name} in"))?.tick;
+                let end = self.marker(&format!("{name} out"))?.tick;
+                Some((start, end))
+            })
+    }
+
+    /// Regions covering `tick`, regions may overlap
+    pub fn regions_at(&self, tick: u64) -> impl Iterator<Item = &Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.contains(tick))
+    }
+}
+
+#[cfg(test)]
+mod marker_tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_stay_sorted() {
+        let mut list = MarkerList::new();
+        list.add_marker("chorus", 1920);
+        list.add_marker("intro", 0);
+        list.add_marker("verse", 960);
+        list.move_marker("intro", 2880);
+
+        let names: Vec<_> = list.markers().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["verse", "chorus", "intro"]);
+        assert!(!list.move_marker("bridge", 0));
+    }
+
+    #[test]
+    fn test_adding_existing_name_replaces_marker() {
+        let mut list = MarkerList::new();
+        list.add_marker("drop", 100);
+        list.add_marker("drop", 200);
+
+        assert_eq!(list.markers().len(), 1);
+        assert_eq!(list.marker("drop").map(|m| m.tick), Some(200));
+    }
+
+    #[test]
+    fn test_next_and_previous_marker() {
+        let mut list = MarkerList::new();
+        list.add_marker("a", 0);
+        list.add_marker("b", 480);
+        list.add_marker("c", 960);
+
+        assert_eq!(list.next_marker(480).map(|m| m.tick), Some(960));
+        assert_eq!(list.previous_marker(480).map(|m| m.tick), Some(0));
+        assert!(list.next_marker(960).is_none());
+    }
+
+    #[test]
+    fn test_insert_and_remove_time_ripple() {
+        let mut list = MarkerList::new();
+        list.add_marker("intro", 0);
+        list.add_marker("break", 600);
+        list.add_marker("outro", 1000);
+        list.add_region("verse", 400, 800);
+
+        list.insert_time(480, 480);
+        assert_eq!(list.marker("outro").map(|m| m.tick), Some(1480));
+        let verse = list.region("verse").unwrap();
+        assert_eq!((verse.start, verse.end), (400, 1280));
+
+        list.remove_time(960, 1280);
+        assert!(list.marker("break").is_none());
+        assert_eq!(list.marker("outro").map(|m| m.tick), Some(1160));
+        assert_eq!(list.region("verse").map(|r| r.end), Some(960));
+
+        list.remove_time(300, 1000);
+        assert!(list.region("verse").is_none());
+        assert_eq!(list.marker("intro").map(|m| m.tick), Some(0));
+    }
+
+    #[test]
+    fn test_regions_are_normalised_and_moved() {
+        let mut list = MarkerList::new();
+        list.add_region("verse", 1920, 960);
+        list.add_region("intro", 0, 960);
+        assert!(list.move_region("verse", 3840));
+
+        let verse = list.region("verse").unwrap();
+        assert_eq!((verse.start, verse.end), (3840, 4800));
+        assert_eq!(list.regions()[0].name, "intro");
+        assert_eq!(list.regions_at(4000).count(), 1);
+        assert_eq!(list.remove_region("intro").map(|r| r.end), Some(960));
+    }
+
+    #[test]
+    fn test_punch_range_from_region_or_markers() {
+        let mut list = MarkerList::new();
+        list.add_marker("take in", 480);
+        list.add_marker("take out", 960);
+        assert_eq!(list.punch_range("take"), Some((480, 960)));
+
+        list.add_region("take", 0, 240);
+        assert_eq!(list.punch_range("take"), Some((0, 240)));
+        assert_eq!(list.punch_range("solo"), None);
+    }
+}
@@ -1,12 +1,11 @@
-use crate::resolution::QuantizeResolution;
+use crate::{resolution::QuantizeResolution, timebase};
 
 pub struct Quantizer;
 
 impl Quantizer {
     /// Snap to nearest tick on the quantization grid
     pub fn quantize_tick(tick: u64, resolution: QuantizeResolution, ticks_per_beat: u64) -> u64 {
-        let grid_size = resolution.ticks_per_grid_unit(ticks_per_beat);
-        ((tick as f64 / grid_size as f64).round() as u64) * grid_size
+        timebase::quantize(tick, resolution.ticks_per_grid_unit(ticks_per_beat))
     }
 
     /// Always quantize forward to next grid position
@@ -15,8 +14,7 @@ impl Quantizer {
         resolution: QuantizeResolution,
         ticks_per_beat: u64,
     ) -> u64 {
-        let grid_size = resolution.ticks_per_grid_unit(ticks_per_beat);
-        ((tick + grid_size - 1) / grid_size) * grid_size
+        timebase::quantize_forward(tick, resolution.ticks_per_grid_unit(ticks_per_beat))
     }
 }
 
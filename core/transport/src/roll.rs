@@ -0,0 +1,41 @@
+use crate::clock::TempoClock;
+
+/// Context played before or after a selection
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RollLength {
+    #[default]
+    Off,
+    /// Whole bars at the current tempo and time signature
+    Bars(u64),
+    Seconds(f64),
+}
+
+impl RollLength {
+    /// Length in frames at `clock`'s tempo and sample rate
+    #[must_use]
+    pub fn frames(self, clock: &TempoClock) -> u64 {
+        let frames = match self {
+            Self::Off => 0.0,
+            Self::Bars(bars) => (bars * clock.ticks_per_bar()) as f64 * clock.samples_per_tick(),
+            Self::Seconds(seconds) => seconds * clock.sample_rate(),
+        };
+        frames.max(0.0).round() as u64
+    }
+}
+
+#[cfg(test)]
+mod roll_tests {
+    use super::*;
+    use crate::resolution::TickResolution;
+
+    #[test]
+    fn test_roll_length_in_frames() {
+        let clock = TempoClock::new(120.0, 48000.0, TickResolution::Sixteenth);
+
+        assert_eq!(RollLength::Off.frames(&clock), 0);
+        // one 4/4 bar at 120 bpm lasts two seconds
+        assert_eq!(RollLength::Bars(2).frames(&clock), 192_000);
+        assert_eq!(RollLength::Seconds(0.5).frames(&clock), 24_000);
+        assert_eq!(RollLength::Seconds(-1.0).frames(&clock), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! The conversions between sample frames, ticks and bars/beats/ticks the engine uses, as
+//! pure functions, so hosts drawing grids and rulers land on exactly the same positions.
+//!
+//! Bars, beats and ticks count from 1, plain ticks and frames from 0.
+
+/// Length of a tick in sample frames, at `bpm` and `sample_rate` with `ticks_per_beat`
+#[must_use]
+pub fn samples_per_tick(bpm: f64, sample_rate: f64, ticks_per_beat: u64) -> f64 {
+    let seconds_per_beat = 60.0 / bpm;
+    let seconds_per_tick = seconds_per_beat / ticks_per_beat as f64;
+    sample_rate * seconds_per_tick
+}
+
+/// Frame `tick` starts on, rounded to the nearest frame
+#[must_use]
+pub fn tick_to_frame(tick: u64, samples_per_tick: f64) -> u64 {
+    (tick as f64 * samples_per_tick).round() as u64
+}
+
+/// Tick playing at `frame`, the last one started
+#[must_use]
+pub fn frame_to_tick(frame: u64, samples_per_tick: f64) -> u64 {
+    (frame as f64 / samples_per_tick).floor() as u64
+}
+
+/// Ticks from the start to `bar.beat.tick`. Zeroes count as ones.
+#[must_use]
+pub const fn bbt_to_ticks(
+    (bar, beat, tick): (u64, u64, u64),
+    ticks_per_beat: u64,
+    beats_per_bar: u64,
+) -> u64 {
+    (bar.saturating_sub(1) * beats_per_bar + beat.saturating_sub(1)) * ticks_per_beat
+        + tick.saturating_sub(1)
+}
+
+/// `(bar, beat, tick)` of `ticks` from the start
+#[must_use]
+pub const fn ticks_to_bbt(ticks: u64, ticks_per_beat: u64, beats_per_bar: u64) -> (u64, u64, u64) {
+    let ticks_per_bar = ticks_per_beat * beats_per_bar;
+    let ticks_into_bar = ticks % ticks_per_bar;
+    (
+        ticks / ticks_per_bar + 1,
+        ticks_into_bar / ticks_per_beat + 1,
+        ticks_into_bar % ticks_per_beat + 1,
+    )
+}
+
+/// `tick` moved to the nearest multiple of `grid` ticks, halfway rounds up
+#[must_use]
+pub fn quantize(tick: u64, grid: u64) -> u64 {
+    ((tick as f64 / grid as f64).round() as u64) * grid
+}
+
+/// `tick` moved to the next multiple of `grid` ticks, unless it's on one
+#[must_use]
+pub const fn quantize_forward(tick: u64, grid: u64) -> u64 {
+    tick.div_ceil(grid) * grid
+}
+
+#[cfg(test)]
+mod timebase_tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_samples_per_tick() {
+        // a beat is half a second at 120 bpm
+        assert_eq!(samples_per_tick(120.0, 48_000.0, 120), 200.0);
+        assert_eq!(tick_to_frame(3, 183.75), 551);
+        assert_eq!(frame_to_tick(551, 183.75), 2);
+    }
+
+    proptest! {
+        #[test]
+        fn test_frames_of_ticks_map_back_to_the_tick(
+            tick in 0u64..10_000_000,
+            bpm in 20.0f64..400.0,
+            sample_rate in prop::sample::select(vec![22_050.0, 44_100.0, 48_000.0, 96_000.0]),
+        ) {
+            let samples_per_tick = samples_per_tick(bpm, sample_rate, 120);
+            prop_assume!(samples_per_tick >= 1.0);
+            let frame = tick_to_frame(tick, samples_per_tick);
+            let back = frame_to_tick(frame, samples_per_tick);
+            // rounding to a frame moves the start by half a frame at most
+            prop_assert!(back == tick || back + 1 == tick);
+        }
+
+        #[test]
+        fn test_bbt_roundtrips(
+            ticks in 0u64..100_000_000,
+            ticks_per_beat in 1u64..1000,
+            beats_per_bar in 1u64..16,
+        ) {
+            let bbt = ticks_to_bbt(ticks, ticks_per_beat, beats_per_bar);
+            prop_assert!(bbt.0 >= 1 && (1..=beats_per_bar).contains(&bbt.1));
+            prop_assert!((1..=ticks_per_beat).contains(&bbt.2));
+            prop_assert_eq!(bbt_to_ticks(bbt, ticks_per_beat, beats_per_bar), ticks);
+        }
+
+        #[test]
+        fn test_quantized_ticks_are_on_the_nearest_grid_line(tick in 0u64..100_000_000, grid in 1u64..2000) {
+            let nearest = quantize(tick, grid);
+            let forward = quantize_forward(tick, grid);
+            prop_assert_eq!(nearest % grid, 0);
+            prop_assert!(nearest.abs_diff(tick) * 2 <= grid);
+            prop_assert_eq!(forward % grid, 0);
+            prop_assert!(forward >= tick && forward - tick < grid);
+            prop_assert_eq!(quantize(nearest, grid), nearest);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::display::{FrameRate, Timecode};
+
+/// A timeline counted in video frames instead of bars, for editing audio to picture.
+///
+/// Positions snap to the start of the video frame they're nearest, and sample 0 can sit at
+/// any timecode, e.g. `01:00:00:00` as post-production sessions usually start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimecodeGrid {
+    rate: FrameRate,
+    sample_rate: f64,
+    /// Video frame number at sample 0
+    start: u64,
+}
+
+impl TimecodeGrid {
+    /// A grid of `rate` frames starting at `00:00:00:00`
+    #[must_use]
+    pub const fn new(rate: FrameRate, sample_rate: f64) -> Self {
+        Self {
+            rate,
+            sample_rate,
+            start: 0,
+        }
+    }
+
+    /// Puts sample 0 at `start`, counted in the grid's frame rate
+    #[must_use]
+    pub const fn with_start(mut self, start: Timecode) -> Self {
+        self.start = Timecode {
+            rate: self.rate,
+            ..start
+        }
+        .video_frame();
+        self
+    }
+
+    #[must_use]
+    pub const fn rate(&self) -> FrameRate {
+        self.rate
+    }
+
+    #[must_use]
+    pub const fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Timecode at sample 0
+    #[must_use]
+    pub const fn start(&self) -> Timecode {
+        Timecode::from_video_frame(self.start, self.rate)
+    }
+
+    /// First sample of the video frame boundary nearest sample `frame`
+    #[must_use]
+    pub fn snap(&self, frame: u64) -> u64 {
+        let floor = self.rate.video_frame(frame, self.sample_rate);
+        let before = self.rate.sample_of(floor, self.sample_rate);
+        let after = self.rate.sample_of(floor + 1, self.sample_rate);
+        if frame.saturating_sub(before) < after.saturating_sub(frame) {
+            before
+        } else {
+            after
+        }
+    }
+
+    /// Timecode at sample `frame`
+    #[must_use]
+    pub fn timecode_at(&self, frame: u64) -> Timecode {
+        let video_frame = self.rate.video_frame(frame, self.sample_rate);
+        Timecode::from_video_frame(self.start + video_frame, self.rate)
+    }
+
+    /// First sample of `timecode`'s frame, `None` before sample 0
+    #[must_use]
+    pub fn frame_of(&self, timecode: Timecode) -> Option<u64> {
+        let video_frame = Timecode {
+            rate: self.rate,
+            ..timecode
+        }
+        .video_frame();
+        video_frame
+            .checked_sub(self.start)
+            .map(|video_frame| self.rate.sample_of(video_frame, self.sample_rate))
+    }
+}
+
+#[cfg(test)]
+mod timecode_tests {
+    use super::*;
+
+    fn timecode(hours: u64, minutes: u64, seconds: u64, frames: u64) -> Timecode {
+        Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate: FrameRate::Fps25,
+        }
+    }
+
+    #[test]
+    fn test_snaps_to_the_nearest_frame_boundary() {
+        let grid = TimecodeGrid::new(FrameRate::Fps25, 48000.0);
+        // frames are 1920 samples apart
+        assert_eq!(grid.snap(959), 0);
+        assert_eq!(grid.snap(960), 1920);
+        assert_eq!(grid.snap(3000), 3840);
+
+        let film = TimecodeGrid::new(FrameRate::Fps23_976, 48000.0);
+        assert_eq!(film.snap(2000), 2002);
+    }
+
+    #[test]
+    fn test_session_start_offsets_the_timecode() {
+        let grid = TimecodeGrid::new(FrameRate::Fps25, 48000.0).with_start(timecode(1, 0, 0, 0));
+
+        assert_eq!(grid.start(), timecode(1, 0, 0, 0));
+        assert_eq!(grid.timecode_at(48000 + 1920), timecode(1, 0, 1, 1));
+        assert_eq!(grid.frame_of(timecode(1, 0, 1, 1)), Some(49920));
+        assert_eq!(grid.frame_of(timecode(0, 59, 59, 24)), None);
+    }
+}
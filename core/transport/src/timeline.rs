@@ -6,3 +6,45 @@ pub struct TimelinePosition {
     pub tick: u64,
     pub tick_within_beat: u64,
 }
+
+/// Where `position` ends up after `length` is inserted at `at`.
+/// Works on any unit as long as all arguments share it (ticks, frames).
+#[must_use]
+pub const fn shift_for_insert(position: u64, at: u64, length: u64) -> u64 {
+    if position >= at {
+        position + length
+    } else {
+        position
+    }
+}
+
+/// Where `position` ends up after `start..end` is removed,
+/// positions inside the removed range collapse onto `start`
+#[must_use]
+pub const fn shift_for_removal(position: u64, start: u64, end: u64) -> u64 {
+    if position >= end {
+        position - (end - start)
+    } else if position > start {
+        start
+    } else {
+        position
+    }
+}
+
+#[cfg(test)]
+mod timeline_tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_moves_later_positions() {
+        assert_eq!(shift_for_insert(100, 100, 50), 150);
+        assert_eq!(shift_for_insert(99, 100, 50), 99);
+    }
+
+    #[test]
+    fn test_removal_collapses_range() {
+        assert_eq!(shift_for_removal(50, 100, 200), 50);
+        assert_eq!(shift_for_removal(150, 100, 200), 100);
+        assert_eq!(shift_for_removal(250, 100, 200), 150);
+    }
+}
@@ -0,0 +1,190 @@
+//! A real-time audio engine: tracks and clips played on a tempo-synced timeline, through a
+//! mixer, to an output device or an offline render.
+//!
+//! This crate is the supported API. The `audio_engine` and `transport` crates behind it are
+//! implementation details and may be reorganised between releases; depend on `freqform`
+//! instead and import from the modules below, or everything common at once from
+//! [`prelude`].
+//!
+//! # Example
+//! ```
+//! use freqform::prelude::*;
+//!
+//! let (_commands, consumer) = freqform::engine::command_queue(16);
+//! let clock = TempoClock::new(120.0, 44100.0, TickResolution::Sixteenth);
+//! let mut scheduler = Scheduler::new(consumer, clock);
+//!
+//! let lead = TrackBuilder::new(Box::new(SineWaveTrack::new(440.0, 44100.0)))
+//!     .id("lead")
+//!     .gain(0.5)
+//!     .build();
+//! scheduler.process_command(SchedulerCommand::ScheduleTrack {
+//!     track: lead,
+//!     start_frame: 0,
+//! });
+//! scheduler.process_command(SchedulerCommand::Play);
+//!
+//! let block = scheduler.next_samples(512);
+//! assert_eq!(block.frames(), 512);
+//! ```
+
+pub use audio_engine::{
+    buffer::AudioBuffer,
+    error::{
+        ArchiveError, AudioPoolError, CueListError, DecodeError, DeviceError, EngineError,
+        PresetError, ProjectError, RoutingError, SchedulingError, SettingsError, SfzError,
+    },
+};
+
+/// The scheduler, the commands that drive it and what it reports back
+pub mod engine {
+    pub use audio_engine::{
+        automation::{AutomatedParameter, AutomationLane, AutomationMode, AutomationStatus},
+        cv::{CvOutput, CvSource, LfoShape},
+        device_manager::{AudioDeviceManager, AudioSource},
+        diagnostics::{
+            AUDIO_TARGET, Diagnostic, DiagnosticsDrain, DiagnosticsLogger, diagnostics_channel,
+        },
+        events::{
+            BarStarted, CallbackResumed, CallbackStalled, ClipLaunched, ClipStopped, ClippingFound,
+            ErrorRaised, EventBus, LoopPassCompleted, MeterFrame, MixCaptured, PreviewFinished,
+            RecordEnabled, SceneLaunched, ShutdownReady, StreamRestarted, Subscription,
+            TrackFinished, TrackRemoved, TrackScheduled, TransportChanged, VideoFrame,
+        },
+        metering::MeterBallistics,
+        mix_snapshot::{
+            BusMix, ChannelMix, MixCapture, MixMorph, MixSnapshot, MixSnapshotChange, MixSnapshots,
+        },
+        mixer::{Bus, Channel, Headroom, HeadroomStatus, Mixer, OutputStrip},
+        performance::{CapturedClip, ClipAudio, LoopFades, PerformanceCapture, arrange},
+        punch::{MonitorMode, PUNCH_RAMP_SECONDS, PunchSwitch},
+        resample::{ResampleQuality, Resampler},
+        scene::{ClipLaunch, LaunchFade, Scene, SceneChange, SceneLauncher},
+        scheduler::{
+            LoopPoints, Scheduler,
+            command::{
+                ChannelChange, LoopOptions, LoopRecordOptions, MarkerChange, ParameterChange,
+                SchedulerCommand, ScrubChange,
+            },
+            event::SchedulerEvent,
+            garbage::{GarbageCollector, garbage_channel},
+        },
+        settings::EngineSettings,
+        snapshot::{EngineSnapshot, Playhead, SnapshotReader, TrackSnapshot},
+        stems::{MASTER_STEM, StemReader, StemTap, stem_channel},
+    };
+
+    #[cfg(all(feature = "jack", not(target_arch = "wasm32")))]
+    pub use audio_engine::device_manager::jack_dm::JackStemPorts;
+    #[cfg(target_arch = "wasm32")]
+    pub use audio_engine::device_manager::web_dm::WebAudioDeviceManager;
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use audio_engine::{
+        device_manager::cpal_dm::CpalAudioDeviceManager,
+        engine::{Engine, EngineBuilder, EngineConfig, EngineGroup, EngineHandle, TakeId},
+        rtp::{RtpConfig, RtpHeader, RtpSender, decode_packet, encode_packet},
+        watchdog::{Watchdog, WatchdogConfig, WatchedSource},
+    };
+
+    /// Sends [`SchedulerCommand`]s to a [`Scheduler`] from other threads
+    pub type CommandProducer = rtrb::Producer<SchedulerCommand>;
+    pub use audio_engine::scheduler::command::SchedulerCommandConsumer as CommandConsumer;
+
+    /// A command queue holding up to `capacity` commands, the consumer goes to
+    /// [`Scheduler::new`]
+    #[must_use]
+    pub fn command_queue(capacity: usize) -> (CommandProducer, CommandConsumer) {
+        rtrb::RingBuffer::new(capacity)
+    }
+}
+
+/// Sources of audio and how they are composed
+pub mod track {
+    pub use audio_engine::decode::{DecodeLimits, DecodedAudio, decode, decode_wav};
+    pub use audio_engine::track::{
+        Track,
+        builder::TrackBuilder,
+        clip_group::LinkedEdit,
+        constant::ConstantTrack,
+        effect::EffectTrack,
+        gainpan::GainPanTrack,
+        midi::MidiTrack,
+        sinewave::SineWaveTrack,
+        timeline::{ChannelSelection, Clip, EditMode, FadeCurve, TimelineTrack},
+        video::VideoTrack,
+        wav::WavTrack,
+    };
+}
+
+/// Note and controller events and the instruments that play them
+pub mod midi {
+    pub use audio_engine::midi::{
+        Event, EventKind, EventList, Instrument, MAX_BLOCK_EVENTS, SequenceEvent,
+        clip::{MidiClip, Note},
+        drum_map::{DrumMap, KeyRemap},
+        effect::{ChordTrigger, MidiEffect, NoteRepeat, Scale, ScaleQuantize},
+        humanize::Humanize,
+        sampler::{MAX_VOICES, Sampler, Zone},
+    };
+}
+
+/// Effects for tracks, channels and busses
+pub mod dsp {
+    pub use audio_engine::{
+        dsp::{
+            EffectSettings, Processor, bypass::Bypass, dither::Dither, ducker::Ducker, gain::Gain,
+            limiter::TruePeakLimiter, math, stretch::time_stretch,
+        },
+        preset::{
+            ChannelPreset, EffectChainPreset, InstrumentParameter, InstrumentPreset, Preset,
+            PresetKind,
+        },
+        preset_library::{PresetEntry, PresetLibrary},
+    };
+}
+
+/// Tempo, musical time, markers and transport state
+pub mod transport {
+    pub use ::transport::{
+        clock::{TempoClock, TimeSignature},
+        display::{DisplayTime, FrameRate, TimeFormat, Timecode},
+        markers::{Marker, MarkerList, Region},
+        resolution::{QuantizeResolution, TickResolution},
+        roll::RollLength,
+        timebase,
+        timecode::TimecodeGrid,
+        timeline::TimelinePosition,
+        transport::TransportState,
+    };
+}
+
+/// Projects on disk and rendering them without a device
+pub mod project {
+    pub use audio_engine::{
+        analysis::{
+            clipping::{ClippingAnalyzer, Over},
+            peaks::Peaks,
+        },
+        audio_pool::{AudioPool, PoolUsage, SharedAudioPool},
+        cue_list::{Cue, CueList, CueSource},
+        metadata::{Color, Metadata},
+        offline::{self, BounceRange, TailSettings},
+        project::{Project, ProjectDefaults, ProjectTrack, TrackGroup},
+        record::{RecordAutomation, RecordFormat, RecordSettings, TakeWriter},
+        rng::{SeededRng, derive_seed},
+    };
+}
+
+/// The types most programs need
+pub mod prelude {
+    pub use crate::{
+        AudioBuffer, EngineError,
+        engine::{ChannelChange, ParameterChange, Scheduler, SchedulerCommand},
+        project::Project,
+        track::{SineWaveTrack, Track, TrackBuilder, WavTrack},
+        transport::{TempoClock, TickResolution, TransportState},
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use crate::engine::{Engine, EngineBuilder, EngineHandle};
+}